@@ -0,0 +1,379 @@
+use anyhow::{Error, Result};
+use serde_json::{json, Value};
+
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::models::Regexes;
+use crate::sandbox;
+use crate::{VersionInfo, VersionOptions};
+
+/// Name of the checksums file of the release artifacts
+pub const CHECKSUMS_FILE: &str = "SHA256SUMS";
+
+/// A release artifact with its SHA-256 checksum
+#[derive(Debug, Clone, PartialEq)]
+pub struct Artifact {
+    pub path: PathBuf,
+    /// File name the artifact is published with
+    pub name: String,
+    pub sha256: String,
+    pub size: u64,
+}
+
+/// Reads the files matching the `globs`, relative to `dir`, and checksums them. `*` and `?` match
+/// within a path component and `**` any number of directories, e.g. `dist/**/*.tar.gz`. Each glob
+/// has to match a file, so a missing build output fails instead of publishing a partial release.
+pub fn collect(dir: &Path, globs: &[String]) -> Result<Vec<Artifact>> {
+    let mut paths = vec![];
+    for glob in globs {
+        let components: Vec<String> = Path::new(glob)
+            .components()
+            .filter(|component| *component != Component::CurDir)
+            .map(|component| component.as_os_str().to_string_lossy().to_string())
+            .collect();
+        let mut matches = vec![];
+        expand(dir, &PathBuf::new(), &components, &mut matches)?;
+        if matches.is_empty() {
+            return Err(Error::msg(format!("No files match '{}'", glob)));
+        }
+        paths.extend(matches);
+    }
+    paths.sort();
+    paths.dedup();
+
+    let mut artifacts: Vec<Artifact> = vec![];
+    for path in paths {
+        let data = fs::read(dir.join(&path))
+            .map_err(|err| Error::msg(format!("Cannot read '{}': {}", path.display(), err)))?;
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if let Some(other) = artifacts.iter().find(|artifact| artifact.name == name) {
+            return Err(Error::msg(format!(
+                "'{}' and '{}' have the same file name",
+                other.path.display(),
+                path.display()
+            )));
+        }
+        artifacts.push(Artifact {
+            path,
+            name,
+            sha256: sha256(&data),
+            size: data.len() as u64,
+        });
+    }
+    Ok(artifacts)
+}
+
+#[derive(clap::Args, Debug)]
+pub struct ChecksumsArgs {
+    /// Artifact files, or globs of them, e.g. `dist/*.tar.gz`
+    #[arg(required = true)]
+    pub paths: Vec<String>,
+
+    /// Path of the checksums file
+    #[arg(long, default_value = CHECKSUMS_FILE)]
+    pub output: PathBuf,
+
+    /// Sign the checksums file with `gpg`, into an ASCII armored detached signature next to it,
+    /// e.g. `SHA256SUMS.asc`. `gpg` has to be allowed by the `[execution]` policy.
+    #[arg(long, action)]
+    pub sign: bool,
+
+    /// Key to sign with, the default key of `gpg` when not provided
+    #[arg(long, requires = "sign")]
+    pub signing_key: Option<String>,
+}
+
+/// Writes the `SHA256SUMS` of the artifacts, with the computed version in its header, and
+/// optionally signs it
+pub fn write_checksums(
+    options: &VersionOptions,
+    version_info: &VersionInfo,
+    args: &ChecksumsArgs,
+) -> Result<Value> {
+    let artifacts = collect(&std::env::current_dir()?, &args.paths)?;
+    let version = version_info.tag_version().to_string();
+    let tag = Regexes::new(&options.app_name, &options.config)?.tag_name(&version);
+    let commit = version_info
+        .commit
+        .sha
+        .clone()
+        .or(version_info.git_rev.clone())
+        .unwrap_or_default();
+    fs::write(&args.output, checksums(&artifacts, &version, &tag, &commit))
+        .map_err(|err| Error::msg(format!("Cannot write '{}': {}", args.output.display(), err)))?;
+
+    let signature = match args.sign {
+        true => {
+            let mut signature = args.output.clone().into_os_string();
+            signature.push(".asc");
+            let signature = PathBuf::from(signature);
+            sign(options, args, &signature)?;
+            Some(signature)
+        }
+        false => None,
+    };
+    Ok(json!({
+        "version": version,
+        "tag": tag,
+        "commit": commit,
+        "path": args.output,
+        "signature": signature,
+        "files": artifacts
+            .iter()
+            .map(|artifact| json!({
+                "name": artifact.name,
+                "path": artifact.path,
+                "sha256": artifact.sha256,
+                "size": artifact.size,
+            }))
+            .collect::<Vec<_>>(),
+    }))
+}
+
+fn sign(options: &VersionOptions, args: &ChecksumsArgs, signature: &Path) -> Result<()> {
+    let output = args.output.to_string_lossy();
+    let signature = signature.to_string_lossy();
+    let mut gpg_args = vec!["--batch", "--yes", "--armor", "--detach-sign"];
+    if let Some(signing_key) = &args.signing_key {
+        gpg_args.extend(["--local-user", signing_key]);
+    }
+    gpg_args.extend(["--output", &signature, &output]);
+    let result = sandbox::execute(&options.config.execution, "gpg", &gpg_args)?;
+    if !result.status.success() {
+        return Err(Error::msg(format!(
+            "Cannot sign '{}': {}",
+            output,
+            String::from_utf8_lossy(&result.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+/// Checksums in `sha256sum` format, `<checksum>  <file name>` lines, after a header with the
+/// version the artifacts were built as. `sha256sum --check` skips the header as comments.
+pub fn checksums(artifacts: &[Artifact], version: &str, tag: &str, commit: &str) -> String {
+    let mut checksums = format!(
+        "# version {}\n# tag {}\n# commit {}\n",
+        version, tag, commit
+    );
+    for artifact in artifacts {
+        checksums.push_str(&format!("{}  {}\n", artifact.sha256, artifact.name));
+    }
+    checksums
+}
+
+/// Adds the files matching the glob `components` below `dir` and `prefix` to the `matches`
+pub(crate) fn expand(
+    dir: &Path,
+    prefix: &Path,
+    components: &[String],
+    matches: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let Some((component, rest)) = components.split_first() else {
+        if dir.join(prefix).is_file() {
+            matches.push(prefix.to_path_buf());
+        }
+        return Ok(());
+    };
+    if !component.contains(['*', '?']) {
+        return expand(dir, &prefix.join(component), rest, matches);
+    }
+    let Ok(entries) = fs::read_dir(dir.join(prefix)) else {
+        return Ok(());
+    };
+    let mut names = vec![];
+    for entry in entries {
+        names.push(entry?.file_name().to_string_lossy().to_string());
+    }
+    names.sort();
+    if component == "**" {
+        // No directories at all, or one more level of them
+        expand(dir, prefix, rest, matches)?;
+        for name in names {
+            if !name.starts_with('.') && dir.join(prefix).join(&name).is_dir() {
+                expand(dir, &prefix.join(name), components, matches)?;
+            }
+        }
+        return Ok(());
+    }
+    for name in names {
+        // Like shells, wildcards don't match hidden files
+        if (!name.starts_with('.') || component.starts_with('.'))
+            && wildcard_match(component, &name)
+        {
+            expand(dir, &prefix.join(name), rest, matches)?;
+        }
+    }
+    Ok(())
+}
+
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` and the name position it matched up to
+    let mut star = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some('?') => {
+                p += 1;
+                n += 1;
+            }
+            Some(c) if *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// Hex encoded SHA-256 digest (FIPS 180-4) of the data
+pub fn sha256(data: &[u8]) -> String {
+    let mut state: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+        0x5be0cd19,
+    ];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = state;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let temp1 = h
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(K[i])
+                .wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let temp2 = s0.wrapping_add(maj);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(temp1);
+            d = c;
+            c = b;
+            b = a;
+            a = temp1.wrapping_add(temp2);
+        }
+        for (value, add) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *value = value.wrapping_add(add);
+        }
+    }
+    state.iter().map(|value| format!("{:08x}", value)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sha256() {
+        assert_eq!(
+            sha256(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            sha256(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        // Padding spilling into a second block
+        assert_eq!(
+            sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("*.tar.gz", "app-1.2.0.tar.gz"));
+        assert!(wildcard_match("app-?.zip", "app-1.zip"));
+        assert!(wildcard_match("*-*-*", "a-b-c-d"));
+        assert!(!wildcard_match("*.tar.gz", "app-1.2.0.zip"));
+        assert!(!wildcard_match("app-?.zip", "app-10.zip"));
+    }
+
+    #[test]
+    fn test_collect() {
+        let dir =
+            std::env::temp_dir().join(format!("version-vine-artifacts-{}", std::process::id()));
+        fs::create_dir_all(dir.join("dist/linux")).unwrap();
+        fs::write(dir.join("dist/app.zip"), "zip").unwrap();
+        fs::write(dir.join("dist/linux/app.tar.gz"), "abc").unwrap();
+        fs::write(dir.join("dist/linux/.app.tar.gz"), "hidden").unwrap();
+        fs::write(dir.join("dist/notes.txt"), "notes").unwrap();
+
+        let artifacts = collect(
+            &dir,
+            &["./dist/**/*.tar.gz".to_string(), "dist/*.zip".to_string()],
+        );
+        let missing = collect(&dir, &["dist/*.deb".to_string()]);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let artifacts = artifacts.unwrap();
+        assert_eq!(
+            artifacts
+                .iter()
+                .map(|artifact| artifact.path.to_str().unwrap())
+                .collect::<Vec<_>>(),
+            vec!["dist/app.zip", "dist/linux/app.tar.gz"]
+        );
+        assert_eq!(artifacts[1].name, "app.tar.gz");
+        assert_eq!(artifacts[1].size, 3);
+        assert_eq!(
+            checksums(&artifacts[1..], "1.3.0", "v1.3.0", "1234567"),
+            "# version 1.3.0\n# tag v1.3.0\n# commit 1234567\n\
+            ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad  app.tar.gz\n"
+        );
+        assert_eq!(
+            missing.unwrap_err().to_string(),
+            "No files match 'dist/*.deb'"
+        );
+    }
+}
@@ -0,0 +1,207 @@
+use anyhow::Result;
+use semver::{BuildMetadata, Version};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::git_command::GitCommandTrait;
+use crate::models::Regexes;
+use crate::stats::parse_tag;
+use crate::VersionOptions;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Check {
+    /// A release skipped in the version sequence, e.g. `1.5.0` between `1.4.0` and `1.6.0`
+    Gap,
+    /// A version tagged more than once, on different commits
+    Duplicate,
+}
+
+/// Irregularity in the versions tagged for an application
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Finding {
+    /// `None` for tags without an app name prefix
+    pub app: Option<String>,
+    pub check: Check,
+    /// The missing or duplicated version
+    pub version: String,
+    /// The tags around the gap, or the duplicated tags
+    pub tags: Vec<String>,
+    pub message: String,
+}
+
+struct Tag {
+    name: String,
+    version: Version,
+    commit: String,
+}
+
+/// Checks the continuity of the version sequence per application. Only the `--app-name`
+/// application when it's provided.
+pub fn audit(git_command: &impl GitCommandTrait, options: &VersionOptions) -> Result<Vec<Finding>> {
+    let regexes = Regexes::new(&options.app_name, &options.config)?;
+    // Annotated tags point at the commit when dereferenced (`*`)
+    let tags = git_command.run(vec![
+        "for-each-ref",
+        "--format=%(refname:lstrip=2)%09%(objectname)%09%(*objectname)",
+        "refs/tags",
+    ])?;
+    let mut apps: BTreeMap<Option<String>, Vec<Tag>> = BTreeMap::new();
+    for line in tags.lines() {
+        let (name, commit) = match line.split('\t').collect::<Vec<_>>().as_slice() {
+            [name, _, commit] if !commit.is_empty() => (*name, *commit),
+            [name, commit, ..] => (*name, *commit),
+            _ => continue,
+        };
+        let Some((app, version)) = parse_tag(&regexes, &options.app_name, name) else {
+            continue;
+        };
+        apps.entry(app).or_default().push(Tag {
+            name: name.to_string(),
+            version,
+            commit: commit.to_string(),
+        });
+    }
+    let mut findings = vec![];
+    for (app, mut tags) in apps {
+        tags.sort_by(|a, b| a.version.cmp(&b.version).then(a.name.cmp(&b.name)));
+        findings.extend(duplicates(&app, &tags));
+        findings.extend(gaps(&app, &tags));
+    }
+    Ok(findings)
+}
+
+/// Versions, without build metadata, tagged on different commits
+fn duplicates(app: &Option<String>, tags: &[Tag]) -> Vec<Finding> {
+    let mut versions: BTreeMap<Version, Vec<&Tag>> = BTreeMap::new();
+    for tag in tags {
+        let mut version = tag.version.clone();
+        version.build = BuildMetadata::EMPTY;
+        versions.entry(version).or_default().push(tag);
+    }
+    versions
+        .into_iter()
+        .filter(|(_, tags)| tags.iter().any(|tag| tag.commit != tags[0].commit))
+        .map(|(version, tags)| Finding {
+            app: app.clone(),
+            check: Check::Duplicate,
+            version: version.to_string(),
+            tags: tags.iter().map(|tag| tag.name.clone()).collect(),
+            message: format!(
+                "Version {} is tagged on different commits: {}",
+                version,
+                tags.iter()
+                    .map(|tag| format!("{} ({})", tag.name, short(&tag.commit)))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        })
+        .collect()
+}
+
+/// Releases (without pre-release) missing between consecutive releases
+fn gaps(app: &Option<String>, tags: &[Tag]) -> Vec<Finding> {
+    let releases: Vec<&Tag> = tags
+        .iter()
+        .filter(|tag| tag.version.pre.is_empty())
+        .collect();
+    releases
+        .windows(2)
+        .filter_map(|pair| {
+            let (previous, next) = (&pair[0].version, &pair[1].version);
+            let expected = if next.major > previous.major {
+                Version::new(previous.major + 1, 0, 0)
+            } else if next.minor > previous.minor {
+                Version::new(previous.major, previous.minor + 1, 0)
+            } else {
+                Version::new(previous.major, previous.minor, previous.patch + 1)
+            };
+            if (next.major, next.minor, next.patch)
+                <= (expected.major, expected.minor, expected.patch)
+            {
+                return None;
+            }
+            Some(Finding {
+                app: app.clone(),
+                check: Check::Gap,
+                version: expected.to_string(),
+                tags: vec![pair[0].name.clone(), pair[1].name.clone()],
+                message: format!(
+                    "Version {} is missing between {} and {}",
+                    expected, pair[0].name, pair[1].name
+                ),
+            })
+        })
+        .collect()
+}
+
+fn short(commit: &str) -> &str {
+    &commit[..commit.len().min(7)]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::git_command::MockGitCommandTrait;
+
+    use super::*;
+
+    fn mock_git(git_command: &mut MockGitCommandTrait, tags: &'static str) {
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "for-each-ref")
+            .returning(move |_| Ok(tags.to_string()));
+    }
+
+    #[test]
+    fn test_audit() {
+        let mut git_command = MockGitCommandTrait::new();
+        mock_git(
+            &mut git_command,
+            "1.4.0\taaaaaaaaaa\t\n\
+             1.4.1\tbbbbbbbbbb\t\n\
+             1.6.0-rc.1\tcccccccccc\t\n\
+             1.6.0\tdddddddddd\tcccccccccc\n\
+             1.6.0+build.2\teeeeeeeeee\t\n\
+             3.0.1\tffffffffff\t\n\
+             myapp-0.1.0\t1111111111\t\n\
+             myapp-0.1.0+again\t1111111111\t\n\
+             latest\tffffffffff\t",
+        );
+
+        let findings = audit(&git_command, &VersionOptions::default()).unwrap();
+
+        assert_eq!(
+            findings
+                .iter()
+                .map(|finding| (finding.check, finding.version.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (Check::Duplicate, "1.6.0"),
+                (Check::Gap, "1.5.0"),
+                (Check::Gap, "2.0.0"),
+            ]
+        );
+        assert_eq!(
+            findings[0].message,
+            "Version 1.6.0 is tagged on different commits: 1.6.0 (ccccccc), 1.6.0+build.2 (eeeeeee)"
+        );
+        assert_eq!(findings[1].tags, vec!["1.4.1", "1.6.0"]);
+        assert_eq!(
+            findings[2].message,
+            "Version 2.0.0 is missing between 1.6.0+build.2 and 3.0.1"
+        );
+    }
+
+    #[test]
+    fn test_audit_continuous() {
+        let mut git_command = MockGitCommandTrait::new();
+        mock_git(
+            &mut git_command,
+            "0.9.3\t1\t\n1.0.0-rc.1\t2\t\n1.0.0\t3\t\n1.0.1\t4\t\n1.1.0\t5\t\n2.0.0\t6\t",
+        );
+
+        let findings = audit(&git_command, &VersionOptions::default()).unwrap();
+
+        assert_eq!(findings, vec![]);
+    }
+}
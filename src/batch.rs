@@ -0,0 +1,358 @@
+use anyhow::{Error, Result};
+use clap::Parser;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use toml::Table;
+
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use crate::cache::CachedGitCommand;
+use crate::config::{Config, ExecutionConfig};
+use crate::error::error_json;
+use crate::git_command::GitCommandTrait;
+use crate::models::SCHEMA_VERSION;
+use crate::output::{render_table, TableFormat};
+use crate::schema::output_shape;
+use crate::{VersionCalculator, VersionOptions};
+
+/// A request of `batch`, one JSON object per line
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct BatchRequest {
+    /// Returned as is with the result, to match the results with the requests
+    pub id: Option<Value>,
+    /// Repository to version, relative to the directory of `batch`, which is the default
+    pub repo_path: Option<PathBuf>,
+    pub app_name: Option<String>,
+    #[serde(rename = "ref")]
+    pub git_ref: Option<String>,
+    /// The options of the version calculation by their long names, e.g.
+    /// `{"branch": "develop", "build_number": 12}`, as the CLI options
+    #[serde(default)]
+    pub options: Map<String, Value>,
+}
+
+#[derive(Parser)]
+#[command(name = "version-vine")]
+struct BatchOptions {
+    #[command(flatten)]
+    options: VersionOptions,
+}
+
+/// Computes the versions of newline-delimited JSON requests in one process, e.g. for a version
+/// service or a monorepo orchestrator. The git command of each repository is kept across the
+/// requests, with its cache of the git queries when enabled. The repository state is determined
+/// again for each request, so new commits and tags are picked up.
+pub struct Batch<G: GitCommandTrait, F: FnMut(&Path, &ExecutionConfig) -> Result<G>> {
+    /// Directory of the requests without `repo_path`
+    repo_path: PathBuf,
+    /// `[defaults]` of the user configuration
+    defaults: Table,
+    /// Refuse network access, as with `--offline`
+    offline: bool,
+    /// Programs allowed on top of the execution policy, as with `--allow-command`
+    allowed_commands: Vec<String>,
+    cache: bool,
+    /// Git command of a repository, with the execution policy of its configuration
+    open: F,
+    git_commands: BTreeMap<PathBuf, CachedGitCommand<G>>,
+}
+
+impl<G: GitCommandTrait, F: FnMut(&Path, &ExecutionConfig) -> Result<G>> Batch<G, F> {
+    pub fn new(
+        repo_path: PathBuf,
+        defaults: Table,
+        offline: bool,
+        allowed_commands: Vec<String>,
+        cache: bool,
+        open: F,
+    ) -> Self {
+        Self {
+            repo_path,
+            defaults,
+            offline,
+            allowed_commands,
+            cache,
+            open,
+            git_commands: BTreeMap::new(),
+        }
+    }
+
+    /// Writes a result line per request line, flushed right away: `{"id": .., "version": ..}`
+    /// with the version output, or `{"id": .., "error": ..}` with the failure as `--errors json`.
+    /// A failing request doesn't stop the batch. As CSV, the rows are written when the input ends,
+    /// with the `id` and the fields of the version output or of the failure as columns.
+    pub fn run(
+        &mut self,
+        input: impl BufRead,
+        output: &mut impl Write,
+        format: TableFormat,
+    ) -> Result<()> {
+        let mut rows = vec![];
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let result = match serde_json::from_str::<BatchRequest>(&line) {
+                Ok(request) => match self.version(&request) {
+                    Ok(version) => json!({"id": request.id, "version": version}),
+                    Err(err) => json!({"id": request.id, "error": error_json(&err)}),
+                },
+                Err(err) => json!({
+                    "id": null,
+                    "error": error_json(&Error::msg(format!("Invalid request: {}", err))),
+                }),
+            };
+            match format {
+                TableFormat::Json => {
+                    writeln!(output, "{}", serde_json::to_string(&result)?)?;
+                    output.flush()?;
+                }
+                TableFormat::Csv => rows.push(csv_row(result)),
+            }
+        }
+        if !rows.is_empty() {
+            writeln!(output, "{}", render_table(&rows, format, false)?)?;
+        }
+        Ok(())
+    }
+
+    fn version(&mut self, request: &BatchRequest) -> Result<Value> {
+        let repo_path = match &request.repo_path {
+            Some(repo_path) => self.repo_path.join(repo_path),
+            None => self.repo_path.clone(),
+        };
+        let mut options = BatchOptions::try_parse_from(option_args(&request.options)?)
+            .map_err(|err| {
+                let message = err.to_string();
+                Error::msg(format!(
+                    "Invalid options: {}",
+                    message
+                        .lines()
+                        .next()
+                        .unwrap_or_default()
+                        .trim_start_matches("error: ")
+                ))
+            })?
+            .options;
+        options.app_name = request.app_name.clone().or(options.app_name);
+        options.git_ref = request.git_ref.clone().or(options.git_ref);
+        let config = match Config::discover(&repo_path) {
+            Some(path) => Config::load(&path, &self.defaults)?,
+            None => Config::parse_with_defaults("", &self.defaults)?,
+        };
+        let schema_version = config.output.schema_version.unwrap_or(SCHEMA_VERSION);
+        options.apply_config(config);
+        if self.offline {
+            options.config.execution.offline = true;
+        }
+        options
+            .config
+            .execution
+            .allowed_commands
+            .extend(self.allowed_commands.iter().cloned());
+        if !self.git_commands.contains_key(&repo_path) {
+            let git_command = (self.open)(&repo_path, &options.config.execution)?;
+            self.git_commands.insert(
+                repo_path.clone(),
+                CachedGitCommand::new(git_command, self.cache),
+            );
+        }
+        let git_command = &self.git_commands[&repo_path];
+        git_command.invalidate();
+        let version_info = VersionCalculator::new(git_command, options)?.calculate()?;
+        output_shape(version_info.to_json(), schema_version)
+    }
+}
+
+/// The result with the fields of the version output, or of the failure, next to the `id`
+fn csv_row(result: Value) -> Value {
+    let mut row = Map::new();
+    for (key, value) in result.as_object().into_iter().flatten() {
+        match value {
+            Value::Object(fields) => row.extend(fields.clone()),
+            value => {
+                row.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    Value::Object(row)
+}
+
+/// The CLI arguments of the options: `true` flags, and the values of the other options, each
+/// value of lists, e.g. `{"fetch": true, "version_source": ["tag", "file"]}`
+fn option_args(options: &Map<String, Value>) -> Result<Vec<String>> {
+    let mut args = vec!["version-vine".to_string()];
+    for (name, value) in options {
+        let flag = format!("--{}", name.replace('_', "-"));
+        let values = match value {
+            Value::Bool(true) => {
+                args.push(flag);
+                continue;
+            }
+            Value::Bool(false) | Value::Null => continue,
+            Value::Array(values) => values.iter().collect(),
+            value => vec![value],
+        };
+        for value in values {
+            let value = match value {
+                Value::String(value) => value.clone(),
+                Value::Number(value) => value.to_string(),
+                _ => {
+                    return Err(Error::msg(format!(
+                        "Invalid options: '{}' isn't a string, number, boolean or list of them",
+                        name
+                    )))
+                }
+            };
+            args.extend([flag.clone(), value]);
+        }
+    }
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::git_command::GitCommand;
+
+    use std::fs;
+
+    use super::*;
+
+    fn run<F: FnMut(&Path, &ExecutionConfig) -> Result<GitCommand>>(
+        batch: &mut Batch<GitCommand, F>,
+        input: &str,
+    ) -> Vec<Value> {
+        let mut output = vec![];
+        batch
+            .run(input.as_bytes(), &mut output, TableFormat::Json)
+            .unwrap();
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_batch() {
+        let dir = std::env::temp_dir().join(format!("version-vine-batch-{}", std::process::id()));
+        let repo = dir.join("repo");
+        fs::create_dir_all(&repo).unwrap();
+        let git = |args: &[&str]| {
+            let git_command = GitCommand {
+                repo_path: Some(repo.clone()),
+                ..Default::default()
+            };
+            let mut all = vec!["-c", "user.name=test", "-c", "user.email=test@example.com"];
+            all.extend(args);
+            git_command.run(all).unwrap();
+        };
+        git(&["init", "-q", "-b", "main"]);
+        git(&["commit", "-q", "--allow-empty", "-m", "init"]);
+        git(&["tag", "1.0.0"]);
+        git(&["checkout", "-q", "-b", "develop"]);
+        git(&["commit", "-q", "--allow-empty", "-m", "feat: login"]);
+        let mut batch = Batch::new(
+            dir.clone(),
+            Table::new(),
+            false,
+            vec!["sh".to_string()],
+            true,
+            |repo_path: &Path, policy: &ExecutionConfig| {
+                assert!(policy.allowed_commands.contains(&"sh".to_string()));
+                Ok(GitCommand {
+                    policy: policy.clone(),
+                    git_path: None,
+                    repo_path: Some(repo_path.to_path_buf()),
+                })
+            },
+        );
+
+        let results = run(
+            &mut batch,
+            r#"{"id": 1, "repo_path": "repo"}
+{"id": "main", "repo_path": "repo", "ref": "main", "options": {"branch": "main"}}
+
+{"id": 3, "repo_path": "repo", "options": {"build_number": 7}}
+{"id": 4, "repo_path": "repo", "options": {"no_such_option": true}}
+not json
+"#,
+        );
+
+        assert_eq!(results.len(), 5);
+        assert_eq!(results[0]["id"], 1);
+        let version = |result: &Value| {
+            result["version"]["app_version"]
+                .as_str()
+                .unwrap()
+                .to_string()
+        };
+        assert!(version(&results[0]).starts_with("1.0.1-beta.1+"));
+        assert_eq!(results[1]["id"], "main");
+        assert!(version(&results[1]).starts_with("1.0.0+"));
+        assert!(version(&results[2]).starts_with("1.0.1-beta.7+"));
+        assert_eq!(results[3]["id"], 4);
+        assert!(results[3]["error"]["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("Invalid options: unexpected argument '--no-such-option'"));
+        assert_eq!(results[4]["id"], Value::Null);
+        assert_eq!(results[4]["error"]["exit_code"], 1);
+
+        // The cached git command of the repository picks up the new commit
+        git(&["commit", "-q", "--allow-empty", "-m", "fix: crash"]);
+        let results = run(&mut batch, r#"{"id": 5, "repo_path": "repo"}"#);
+        assert!(version(&results[0]).starts_with("1.0.1-beta.2+"));
+        assert_eq!(batch.git_commands.len(), 1);
+
+        let mut output = vec![];
+        batch
+            .run(
+                r#"{"id": 6, "repo_path": "repo"}
+{"id": 7, "repo_path": "repo", "options": {"no_such_option": true}}"#
+                    .as_bytes(),
+                &mut output,
+                TableFormat::Csv,
+            )
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("ahead_of_main,app_version,"));
+        assert!(lines[0].ends_with(",tagger,error,exit_code,message"));
+        assert!(lines[1].starts_with("2,1.0.1-beta.2+"));
+        assert!(lines[1].ends_with(",,,"));
+        assert!(lines[2].contains(",7,"));
+        assert!(lines[2].contains(",error,1,Invalid options: "));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_option_args() {
+        let options = json!({
+            "fetch": true,
+            "cache": false,
+            "build_number": 12,
+            "version_source": ["tag", "file"],
+        });
+
+        assert_eq!(
+            option_args(options.as_object().unwrap()).unwrap(),
+            [
+                "version-vine",
+                "--build-number",
+                "12",
+                "--fetch",
+                "--version-source",
+                "tag",
+                "--version-source",
+                "file"
+            ]
+        );
+        assert!(option_args(json!({"branch": {"name": "main"}}).as_object().unwrap()).is_err());
+    }
+}
@@ -0,0 +1,483 @@
+use anyhow::{Error, Result};
+
+use crate::git_command::GitCommandTrait;
+
+/// CI environment variables holding the branch of the build, in order of precedence. Pull request
+/// builds check out a merge commit, so their source branch comes first.
+const CI_BRANCH_VARIABLES: [&str; 9] = [
+    // GitHub Actions
+    "GITHUB_HEAD_REF",
+    "GITHUB_REF",
+    // GitLab CI
+    "CI_MERGE_REQUEST_SOURCE_BRANCH_NAME",
+    "CI_COMMIT_BRANCH",
+    "CI_COMMIT_REF_NAME",
+    // Azure Pipelines
+    "SYSTEM_PULLREQUEST_SOURCEBRANCH",
+    "BUILD_SOURCEBRANCH",
+    // Jenkins multibranch pipelines
+    "CHANGE_BRANCH",
+    "BRANCH_NAME",
+];
+
+/// Former and new names of renamed default branches
+const DEFAULT_BRANCH_RENAMES: [(&str, &str); 1] = [("master", "main")];
+
+/// Branch to version: the `--branch` option, or else the current branch. CI systems mostly check
+/// out a detached `HEAD`, for which the branch is taken from the CI environment variables, or else
+/// the single branch pointing at `HEAD`.
+pub fn resolve_branch(
+    git_command: &impl GitCommandTrait,
+    branch: &Option<String>,
+) -> Result<String> {
+    resolve_branch_with_env(git_command, branch, |name| std::env::var(name).ok())
+}
+
+/// `resolve_branch` with the environment variables looked up by `env`
+pub fn resolve_branch_with_env(
+    git_command: &impl GitCommandTrait,
+    branch: &Option<String>,
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<String> {
+    if let Some(branch) = branch {
+        return Ok(branch.clone());
+    }
+    let current_branch = git_command.run(vec!["branch", "--show-current"])?;
+    if !current_branch.is_empty() {
+        return Ok(current_branch);
+    }
+    if let Some(branch) = ci_branch(env) {
+        return Ok(branch);
+    }
+    let branches = branches_at(git_command, "HEAD")?;
+    match branches.as_slice() {
+        [branch] => Ok(branch.clone()),
+        [] => Err(Error::msg(
+            "HEAD is detached and no branch points at it. Provide the branch with --branch.",
+        )),
+        branches => Err(Error::msg(format!(
+            "HEAD is detached and multiple branches point at it: {}. Provide the branch with --branch.",
+            branches.join(", ")
+        ))),
+    }
+}
+
+/// Branch of a `--ref`: the branch it names, e.g. `release/2.0.0` of `origin/release/2.0.0`, or
+/// else the `--branch` option or the single branch pointing at the commit
+pub fn resolve_ref_branch(
+    git_command: &impl GitCommandTrait,
+    git_ref: &str,
+    branch: &Option<String>,
+) -> Result<String> {
+    if let Some(branch) = branch {
+        return Ok(branch.clone());
+    }
+    let full_name = git_command
+        .run(vec!["rev-parse", "--symbolic-full-name", git_ref])
+        .unwrap_or_default();
+    if let Some(branch) = branch_name(&full_name) {
+        return Ok(branch.to_string());
+    }
+    let branches = branches_at(git_command, git_ref)?;
+    match branches.as_slice() {
+        [branch] => Ok(branch.clone()),
+        [] => Err(Error::msg(format!(
+            "No branch points at '{}'. Provide the branch with --branch.",
+            git_ref
+        ))),
+        branches => Err(Error::msg(format!(
+            "Multiple branches point at '{}': {}. Provide the branch with --branch.",
+            git_ref,
+            branches.join(", ")
+        ))),
+    }
+}
+
+/// Number of the pull request build: `refs/pull/<number>/merge` of GitHub Actions, the merge
+/// request IID of GitLab CI, the pull request number of Azure Pipelines or the change ID of Jenkins
+pub fn ci_pull_request(env: impl Fn(&str) -> Option<String>) -> Option<u64> {
+    env("GITHUB_REF")
+        .and_then(|github_ref| {
+            github_ref
+                .strip_prefix("refs/pull/")?
+                .split('/')
+                .next()?
+                .parse()
+                .ok()
+        })
+        .or_else(|| {
+            [
+                "CI_MERGE_REQUEST_IID",
+                "SYSTEM_PULLREQUEST_PULLREQUESTNUMBER",
+            ]
+            .iter()
+            .find_map(|name| env(name)?.parse().ok())
+        })
+        .or_else(|| env("CHANGE_ID").filter(|_| jenkins(&env))?.parse().ok())
+}
+
+fn ci_branch(env: impl Fn(&str) -> Option<String>) -> Option<String> {
+    let env = |name: &str| env(name).filter(|value| !value.is_empty());
+    CI_BRANCH_VARIABLES.iter().find_map(|name| {
+        // The ref name of GitLab tag pipelines is the tag
+        if *name == "CI_COMMIT_REF_NAME" && env("CI_COMMIT_TAG").is_some() {
+            return None;
+        }
+        // Other tools set variables of these common names too
+        if matches!(*name, "CHANGE_BRANCH" | "BRANCH_NAME") && !jenkins(env) {
+            return None;
+        }
+        let value = env(name)?;
+        match value.strip_prefix("refs/heads/") {
+            Some(branch) => Some(branch.to_string()),
+            // E.g. `refs/tags/1.0.0` or `refs/pull/1/merge`
+            None if value.starts_with("refs/") => None,
+            None => Some(value),
+        }
+    })
+}
+
+fn jenkins(env: impl Fn(&str) -> Option<String>) -> bool {
+    env("JENKINS_URL").is_some_and(|url| !url.is_empty())
+}
+
+/// Local and remote branches pointing at `rev`, without the remote name. CI clones often only
+/// have the remote branches.
+fn branches_at(git_command: &impl GitCommandTrait, rev: &str) -> Result<Vec<String>> {
+    let refs = git_command.run(vec![
+        "for-each-ref",
+        "--points-at",
+        rev,
+        "--format=%(refname)",
+        "refs/heads",
+        "refs/remotes",
+    ])?;
+    let mut branches: Vec<String> = refs
+        .lines()
+        .filter_map(branch_name)
+        .map(|branch| branch.to_string())
+        .collect();
+    branches.sort();
+    branches.dedup();
+    Ok(branches)
+}
+
+/// Branch the branch is classified as when it's an alias of another one: the target of a local
+/// symbolic ref, e.g. `master` pointing at `main` while the default branch is renamed, or else the
+/// default branch for its former or new name. The latter warns about the stale ref.
+pub fn resolve_branch_alias(
+    git_command: &impl GitCommandTrait,
+    git_branch: &str,
+    default_branch: &str,
+) -> Option<String> {
+    let target = git_command
+        .run(vec![
+            "symbolic-ref",
+            "--quiet",
+            "--short",
+            &format!("refs/heads/{}", git_branch),
+        ])
+        .ok()
+        .filter(|target| !target.is_empty() && target != git_branch);
+    if target.is_some() {
+        return target;
+    }
+    for (former, new) in DEFAULT_BRANCH_RENAMES {
+        if (git_branch, default_branch) == (former, new) {
+            eprintln!(
+                "Warning: Branch '{}' is the former name of the default branch '{}', and versioned as it. \
+                Rename the stale local branch with `git branch -m {} {}`.",
+                former, new, former, new
+            );
+            return Some(new.to_string());
+        }
+        if (git_branch, default_branch) == (new, former) {
+            eprintln!(
+                "Warning: The default branch is '{}', the former name of '{}', which is versioned as it. \
+                Update a stale `origin/HEAD` with `git remote set-head origin --auto`, or the `default_branch` configuration.",
+                former, new
+            );
+            return Some(former.to_string());
+        }
+    }
+    None
+}
+
+/// Local and remote branches, without the remote name
+pub fn all_branches(git_command: &impl GitCommandTrait) -> Result<Vec<String>> {
+    let refs = git_command.run(vec![
+        "for-each-ref",
+        "--format=%(refname)",
+        "refs/heads",
+        "refs/remotes",
+    ])?;
+    let mut branches: Vec<String> = refs
+        .lines()
+        .filter_map(branch_name)
+        .map(|branch| branch.to_string())
+        .collect();
+    branches.sort();
+    branches.dedup();
+    Ok(branches)
+}
+
+/// Branch of a local or remote branch ref, without the remote name
+fn branch_name(full_name: &str) -> Option<&str> {
+    let branch = match full_name.strip_prefix("refs/heads/") {
+        Some(branch) => branch,
+        None => full_name.strip_prefix("refs/remotes/")?.split_once('/')?.1,
+    };
+    Some(branch).filter(|branch| *branch != "HEAD")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::git_command::MockGitCommandTrait;
+
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn mock_git(git_command: &mut MockGitCommandTrait, refs: &'static str) {
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "branch --show-current")
+            .returning(|_| Ok(String::new()));
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "for-each-ref" && args[1] == "--points-at")
+            .returning(move |_| Ok(refs.to_string()));
+    }
+
+    fn resolve(variables: &[(&str, &str)], refs: &'static str) -> Result<String> {
+        let mut git_command = MockGitCommandTrait::new();
+        mock_git(&mut git_command, refs);
+        let variables: HashMap<String, String> = variables
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        resolve_branch_with_env(&git_command, &None, |name| variables.get(name).cloned())
+    }
+
+    #[test]
+    fn test_resolve_branch_alias() {
+        let resolve =
+            |git_branch: &str, default_branch: &str, symbolic_ref: Option<&'static str>| {
+                let mut git_command = MockGitCommandTrait::new();
+                git_command
+                    .expect_run()
+                    .withf(|args| args[0] == "symbolic-ref")
+                    .returning(move |_| match symbolic_ref {
+                        Some(target) => Ok(target.to_string()),
+                        None => Err(Error::msg("")),
+                    });
+                resolve_branch_alias(&git_command, git_branch, default_branch)
+            };
+
+        assert_eq!(
+            resolve("master", "main", Some("main")),
+            Some("main".to_string())
+        );
+        assert_eq!(
+            resolve("dev", "main", Some("develop")),
+            Some("develop".to_string())
+        );
+        // Stale names of the default branch
+        assert_eq!(resolve("master", "main", None), Some("main".to_string()));
+        assert_eq!(resolve("main", "master", None), Some("master".to_string()));
+        assert_eq!(resolve("main", "main", None), None);
+        assert_eq!(resolve("feature/master", "main", None), None);
+    }
+
+    #[test]
+    fn test_resolve_branch_current() {
+        let mut git_command = MockGitCommandTrait::new();
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "branch --show-current")
+            .returning(|_| Ok("develop".to_string()));
+
+        let branch = resolve_branch_with_env(&git_command, &None, |_| {
+            Some("refs/heads/other".to_string())
+        });
+
+        assert_eq!(branch.unwrap(), "develop");
+        assert_eq!(
+            resolve_branch(&git_command, &Some("release/1.0.0".to_string())).unwrap(),
+            "release/1.0.0"
+        );
+    }
+
+    #[test]
+    fn test_resolve_branch_github() {
+        assert_eq!(
+            resolve(&[("GITHUB_REF", "refs/heads/feature/x")], "").unwrap(),
+            "feature/x"
+        );
+        assert_eq!(
+            resolve(
+                &[
+                    ("GITHUB_HEAD_REF", "feature/pr"),
+                    ("GITHUB_REF", "refs/pull/1/merge")
+                ],
+                ""
+            )
+            .unwrap(),
+            "feature/pr"
+        );
+    }
+
+    #[test]
+    fn test_resolve_branch_gitlab() {
+        assert_eq!(
+            resolve(&[("CI_COMMIT_REF_NAME", "release/1.0.0")], "").unwrap(),
+            "release/1.0.0"
+        );
+        assert_eq!(
+            resolve(
+                &[
+                    ("CI_MERGE_REQUEST_SOURCE_BRANCH_NAME", "feature/mr"),
+                    ("CI_COMMIT_REF_NAME", "feature/mr")
+                ],
+                ""
+            )
+            .unwrap(),
+            "feature/mr"
+        );
+        // Tag pipelines fall back to the branches pointing at HEAD
+        assert_eq!(
+            resolve(
+                &[("CI_COMMIT_REF_NAME", "1.0.0"), ("CI_COMMIT_TAG", "1.0.0")],
+                "refs/remotes/origin/main"
+            )
+            .unwrap(),
+            "main"
+        );
+    }
+
+    #[test]
+    fn test_resolve_branch_azure() {
+        assert_eq!(
+            resolve(&[("BUILD_SOURCEBRANCH", "refs/heads/hotfix/1.0.1")], "").unwrap(),
+            "hotfix/1.0.1"
+        );
+        assert_eq!(
+            resolve(
+                &[
+                    ("SYSTEM_PULLREQUEST_SOURCEBRANCH", "refs/heads/feature/pr"),
+                    ("BUILD_SOURCEBRANCH", "refs/pull/1/merge")
+                ],
+                ""
+            )
+            .unwrap(),
+            "feature/pr"
+        );
+    }
+
+    #[test]
+    fn test_resolve_branch_points_at_head() {
+        assert_eq!(
+            resolve(
+                &[],
+                "refs/heads/develop\nrefs/remotes/origin/HEAD\nrefs/remotes/origin/develop"
+            )
+            .unwrap(),
+            "develop"
+        );
+        assert!(resolve(&[], "")
+            .unwrap_err()
+            .to_string()
+            .contains("no branch points at it"));
+        assert!(resolve(&[], "refs/heads/develop\nrefs/remotes/origin/main")
+            .unwrap_err()
+            .to_string()
+            .contains("multiple branches point at it: develop, main"));
+    }
+
+    #[test]
+    fn test_resolve_ref_branch() {
+        let resolve_ref = |git_ref: &'static str, full_name: &'static str| {
+            let mut git_command = MockGitCommandTrait::new();
+            git_command
+                .expect_run()
+                .withf(move |args| {
+                    args.join(" ") == format!("rev-parse --symbolic-full-name {}", git_ref)
+                })
+                .returning(move |_| Ok(full_name.to_string()));
+            git_command
+                .expect_run()
+                .withf(move |args| {
+                    args.join(" ")
+                        .starts_with(&format!("for-each-ref --points-at {} ", git_ref))
+                })
+                .returning(|_| Ok("refs/remotes/origin/main".to_string()));
+            resolve_ref_branch(&git_command, git_ref, &None).unwrap()
+        };
+
+        assert_eq!(
+            resolve_ref("origin/release/2.0.0", "refs/remotes/origin/release/2.0.0"),
+            "release/2.0.0"
+        );
+        assert_eq!(resolve_ref("develop", "refs/heads/develop"), "develop");
+        // Tags and commits fall back to the branches pointing at them
+        assert_eq!(resolve_ref("1.0.0", "refs/tags/1.0.0"), "main");
+        assert_eq!(resolve_ref("1234567", ""), "main");
+    }
+
+    #[test]
+    fn test_resolve_branch_jenkins() {
+        let jenkins_url = ("JENKINS_URL", "https://jenkins.example.com/");
+        assert_eq!(
+            resolve(&[jenkins_url, ("BRANCH_NAME", "develop")], "").unwrap(),
+            "develop"
+        );
+        assert_eq!(
+            resolve(
+                &[
+                    jenkins_url,
+                    ("CHANGE_BRANCH", "feature/pr"),
+                    ("BRANCH_NAME", "PR-12")
+                ],
+                ""
+            )
+            .unwrap(),
+            "feature/pr"
+        );
+        assert_eq!(
+            resolve(&[("BRANCH_NAME", "develop")], "refs/remotes/origin/main").unwrap(),
+            "main"
+        );
+    }
+
+    #[test]
+    fn test_ci_pull_request() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+
+        assert_eq!(
+            ci_pull_request(env(&[("GITHUB_REF", "refs/pull/123/merge")])),
+            Some(123)
+        );
+        assert_eq!(
+            ci_pull_request(env(&[("CI_MERGE_REQUEST_IID", "7")])),
+            Some(7)
+        );
+        assert_eq!(
+            ci_pull_request(env(&[
+                ("JENKINS_URL", "https://jenkins.example.com/"),
+                ("CHANGE_ID", "12")
+            ])),
+            Some(12)
+        );
+        assert_eq!(
+            ci_pull_request(env(&[("GITHUB_REF", "refs/heads/main")])),
+            None
+        );
+        assert_eq!(ci_pull_request(env(&[("CHANGE_ID", "12")])), None);
+    }
+}
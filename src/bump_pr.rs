@@ -0,0 +1,331 @@
+use anyhow::{Error, Result};
+use semver::{BuildMetadata, Prerelease};
+use serde_json::{json, Value};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::changelog::{changelog, prepend, read_changelog, ChangelogArgs};
+use crate::forge::{ForgeArgs, HttpClientTrait};
+use crate::git_command::GitCommandTrait;
+use crate::set_version::{set_version, SetArgs};
+use crate::{VersionInfo, VersionOptions};
+
+#[derive(clap::Args, Debug)]
+pub struct BumpPrArgs {
+    /// Branch the pull request targets. Defaults to the current branch.
+    #[arg(long)]
+    pub base: Option<String>,
+
+    /// File (relative to the repository root) the next version is written to
+    #[arg(long, default_value = "VERSION")]
+    pub version_file: String,
+
+    /// Manifests the next version is also written to, as with `set --target`, e.g. `Cargo.toml`. Defaults to the `[[stamp]]` files of the configuration.
+    #[arg(short, long = "target")]
+    pub targets: Vec<PathBuf>,
+
+    /// Changelog the section of the next version is prepended to, as with `changelog --output`, e.g. `CHANGELOG.md`
+    #[arg(long, value_name = "PATH")]
+    pub changelog: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub forge: ForgeArgs,
+
+    /// Only print what would be done, without committing, pushing or calling the forge API
+    #[arg(long, action)]
+    pub dry_run: bool,
+}
+
+/// Commits the next release version to a bump branch, pushes it and opens a pull request for it.
+/// The version file, the manifests of the `--target`s or `[[stamp]]` files, and the changelog are
+/// updated as `set` and `changelog` would for the release. Used instead of tagging directly when
+/// branch protection forbids pushes from CI.
+pub fn create_bump_pr(
+    git_command: &impl GitCommandTrait,
+    http_client: &impl HttpClientTrait,
+    options: &VersionOptions,
+    version_info: &VersionInfo,
+    args: &BumpPrArgs,
+) -> Result<Value> {
+    let mut version = version_info.version.clone();
+    version.pre = Prerelease::EMPTY;
+    version.build = BuildMetadata::EMPTY;
+    let tag = match &options.app_name {
+        Some(app_name) => format!("{}-{}", app_name, version),
+        None => version.to_string(),
+    };
+    let base = match &args.base {
+        Some(base) => base.clone(),
+        None => version_info.git_branch.clone(),
+    };
+    let branch = format!("version-vine/bump-{}", tag);
+    let title = format!("Bump version to {}", tag);
+
+    let (_, repository) = args.forge.repository(git_command)?;
+
+    let manifests: Vec<&PathBuf> = match args.targets.is_empty() {
+        true => options
+            .config
+            .stamp
+            .iter()
+            .map(|stamp| &stamp.file)
+            .collect(),
+        false => args.targets.iter().collect(),
+    };
+    let mut files = vec![PathBuf::from(&args.version_file)];
+    files.extend(manifests.iter().map(|path| path.to_path_buf()));
+    files.extend(args.changelog.clone());
+    let mut plan = json!({
+        "version": version.to_string(),
+        "tag": tag,
+        "branch": branch,
+        "base": base,
+        "repository": repository,
+        "files": files,
+        "pull_request": null,
+        "created": false,
+    });
+    if args.dry_run {
+        return Ok(plan);
+    }
+
+    let forge = args.forge.forge(git_command, http_client)?;
+
+    let toplevel = git_command.run(vec!["rev-parse", "--show-toplevel"])?;
+    // The version file is relative to the repository root, the others to the working directory
+    // as with `set` and `changelog`, while git may run in another repository path
+    let current_dir = env::current_dir()?;
+    let mut paths = vec![Path::new(&toplevel).join(&args.version_file)];
+    paths.extend(files[1..].iter().map(|path| current_dir.join(path)));
+    let paths: Vec<&str> = paths.iter().filter_map(|path| path.to_str()).collect();
+    let release = VersionInfo {
+        container_tag: version.to_string(),
+        version: version.clone(),
+        ..version_info.clone()
+    };
+
+    git_command.run(vec!["checkout", "-B", &branch])?;
+    let commit_result = fs::write(paths[0], format!("{}\n", version))
+        .map_err(Error::from)
+        .and_then(|_| update_files(git_command, options, &release, &manifests, args))
+        .and_then(|_| git_command.run([vec!["add", "--"], paths.clone()].concat()))
+        .and_then(|_| git_command.run(vec!["commit", "-m", &title]))
+        .and_then(|_| {
+            git_command.run(vec![
+                "push",
+                "--force",
+                &args.forge.remote,
+                &format!("HEAD:refs/heads/{}", branch),
+            ])
+        });
+    // Always return to the branch we started from, also when committing failed
+    git_command.run(vec!["checkout", "-"])?;
+    commit_result?;
+
+    let (url, created) = forge.ensure_pull_request(
+        &branch,
+        &base,
+        &title,
+        &format!(
+            "Bumps the version to `{}`.\n\nCreated by [version-vine](https://github.com/Freakazoid182/version-vine).",
+            tag
+        ),
+    )?;
+    plan["pull_request"] = json!(url);
+    plan["created"] = json!(created);
+    Ok(plan)
+}
+
+/// Writes the release version to the manifests as `set`, and prepends its section to the changelog
+fn update_files(
+    git_command: &impl GitCommandTrait,
+    options: &VersionOptions,
+    release: &VersionInfo,
+    manifests: &[&PathBuf],
+    args: &BumpPrArgs,
+) -> Result<()> {
+    if !manifests.is_empty() {
+        let set_args = SetArgs {
+            targets: args.targets.clone(),
+            regex: None,
+            anchor: None,
+            services: vec![],
+            value: None,
+            json_path: None,
+            checksums: None,
+            sha256: None,
+            dry_run: false,
+        };
+        set_version(release, options, &set_args)?;
+    }
+    if let Some(path) = &args.changelog {
+        let changelog_args = ChangelogArgs {
+            output: None,
+            contributors: None,
+        };
+        let section = changelog(git_command, options, release, &changelog_args)?;
+        let existing = read_changelog(path)?;
+        fs::write(path, prepend(&existing, &section))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::forge::MockHttpClientTrait;
+    use crate::git_command::MockGitCommandTrait;
+
+    use super::*;
+    use crate::version_calculator::version_info;
+
+    fn bump_pr_args(dry_run: bool) -> BumpPrArgs {
+        BumpPrArgs {
+            base: None,
+            version_file: "VERSION".to_string(),
+            targets: vec![],
+            changelog: None,
+            forge: ForgeArgs {
+                remote: "origin".to_string(),
+                kind: None,
+                api_url: Some("https://api.github.com".to_string()),
+                token: Some("token".to_string()),
+                repository: None,
+            },
+            dry_run,
+        }
+    }
+
+    #[test]
+    fn test_create_bump_pr_dry_run() {
+        let mut git_command = MockGitCommandTrait::new();
+        let http_client = MockHttpClientTrait::new();
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "remote get-url origin")
+            .returning(|_| Ok("git@github.com:owner/repo.git".to_string()));
+
+        let mut options = VersionOptions {
+            app_name: Some("myapp".to_string()),
+            ..Default::default()
+        };
+        options.apply_config(
+            Config::parse("[[stamp]]\nfile = 'Dockerfile'\nanchor = '# version-vine'").unwrap(),
+        );
+        let args = BumpPrArgs {
+            changelog: Some(PathBuf::from("CHANGELOG.md")),
+            ..bump_pr_args(true)
+        };
+
+        let result = create_bump_pr(
+            &git_command,
+            &http_client,
+            &options,
+            &version_info("develop", "1.0.1-beta.3+1234567"),
+            &args,
+        );
+
+        let plan = result.unwrap();
+        assert_eq!(plan["version"], "1.0.1");
+        assert_eq!(plan["tag"], "myapp-1.0.1");
+        assert_eq!(plan["branch"], "version-vine/bump-myapp-1.0.1");
+        assert_eq!(plan["base"], "develop");
+        assert_eq!(plan["repository"], "owner/repo");
+        assert_eq!(
+            plan["files"],
+            json!(["VERSION", "Dockerfile", "CHANGELOG.md"])
+        );
+    }
+
+    #[test]
+    fn test_create_bump_pr() {
+        let toplevel = std::env::temp_dir().join("version-vine-test-create-bump-pr");
+        fs::create_dir_all(&toplevel).unwrap();
+        let toplevel_str = toplevel.to_str().unwrap().to_string();
+        let manifest = toplevel.join("Cargo.toml");
+        fs::write(
+            &manifest,
+            "[package]\nname = \"app\"\nversion = \"1.1.0\"\n",
+        )
+        .unwrap();
+        let changelog = toplevel.join("CHANGELOG.md");
+        fs::write(&changelog, "# Changelog\n\n## 1.1.0\n\n- Initial\n").unwrap();
+
+        let mut git_command = MockGitCommandTrait::new();
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "remote get-url origin")
+            .returning(|_| Ok("https://github.com/owner/repo.git".to_string()));
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "rev-parse --show-toplevel")
+            .returning(move |_| Ok(toplevel_str.clone()));
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "log" && args[2] == "--format=%h%x1f%aN%x1f%aE%x1f%B%x00")
+            .returning(|_| {
+                Ok("abc1234\x1fJane\x1fjane@example.com\x1ffeat: Add export\n\0".to_string())
+            });
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "add")
+            .times(1)
+            .returning(|args| {
+                assert_eq!(args.len(), 5);
+                Ok(String::new())
+            });
+        git_command
+            .expect_run()
+            .withf(|args| ["checkout", "commit", "push"].contains(&args[0]))
+            .times(4)
+            .returning(|_| Ok(String::new()));
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "log -1 --format=%cs HEAD")
+            .returning(|_| Ok("2024-02-14".to_string()));
+        // No previous tag
+        git_command
+            .expect_run()
+            .returning(|_| Err(Error::msg("No names found")));
+        let mut http_client = MockHttpClientTrait::new();
+        http_client
+            .expect_send()
+            .withf(|request| request.method == "GET")
+            .returning(|_| Ok(json!([])));
+        http_client
+            .expect_send()
+            .withf(|request| request.method == "POST")
+            .returning(|_| Ok(json!({"html_url": "https://github.com/owner/repo/pull/2"})));
+
+        let args = BumpPrArgs {
+            targets: vec![manifest.clone()],
+            changelog: Some(changelog.clone()),
+            ..bump_pr_args(false)
+        };
+
+        let result = create_bump_pr(
+            &git_command,
+            &http_client,
+            &VersionOptions::default(),
+            &version_info("release/1.2.0", "1.2.0-rc.4+1234567"),
+            &args,
+        );
+
+        let plan = result.unwrap();
+        assert_eq!(plan["pull_request"], "https://github.com/owner/repo/pull/2");
+        assert_eq!(plan["created"], true);
+        assert_eq!(
+            fs::read_to_string(toplevel.join("VERSION")).unwrap(),
+            "1.2.0\n"
+        );
+        assert_eq!(
+            fs::read_to_string(&manifest).unwrap(),
+            "[package]\nname = \"app\"\nversion = \"1.2.0\"\n"
+        );
+        let changelog = fs::read_to_string(&changelog).unwrap();
+        assert!(changelog.starts_with("# Changelog\n\n## 1.2.0"));
+        assert!(changelog.contains("- Add export (abc1234)"));
+        assert!(changelog.contains("## 1.1.0\n\n- Initial"));
+    }
+}
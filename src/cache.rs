@@ -0,0 +1,293 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::error::VersionError;
+use crate::git_command::GitCommandTrait;
+
+/// Directory of the cache in the git directory
+pub const CACHE_DIR: &str = "version-vine-cache";
+/// Cache files kept, one per repository state, e.g. for switching between branches
+const MAX_FILES: usize = 16;
+
+/// Memoizes the results of read-only git queries on disk, under `.git/version-vine-cache`.
+///
+/// The results are cached per repository state: `HEAD`, the branch it points at and the commits
+/// of all refs. Creating a tag, fetching or committing changes the state, so the cached results
+/// of the previous state are never returned. Other commands run uncached, and the state is
+/// determined again on the next query.
+pub struct CachedGitCommand<G: GitCommandTrait> {
+    git_command: G,
+    enabled: bool,
+    state: Mutex<Option<CacheState>>,
+}
+
+struct CacheState {
+    path: PathBuf,
+    entries: BTreeMap<String, Entry>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+enum Entry {
+    Ok(String),
+    Err(String),
+}
+
+impl<G: GitCommandTrait> CachedGitCommand<G> {
+    pub fn new(git_command: G, enabled: bool) -> Self {
+        Self {
+            git_command,
+            enabled,
+            state: Mutex::new(None),
+        }
+    }
+
+    /// Forgets the repository state, so the next query determines it again, e.g. between the
+    /// requests of `batch`
+    pub fn invalidate(&self) {
+        *self.state.lock().unwrap() = None;
+    }
+
+    /// Cache file of the current repository state, with the results cached so far
+    fn load(&self) -> Result<CacheState> {
+        // Relative to the repository of `git -C` otherwise
+        let git_dir = self
+            .git_command
+            .run(vec!["rev-parse", "--absolute-git-dir"])?;
+        let head = self
+            .git_command
+            .run(vec!["rev-parse", "HEAD"])
+            .unwrap_or_default();
+        let head_ref = self
+            .git_command
+            .run(vec!["symbolic-ref", "-q", "HEAD"])
+            .unwrap_or_default();
+        let refs = self
+            .git_command
+            .run(vec!["for-each-ref", "--format=%(objectname) %(refname)"])?;
+        let key = fnv1a(&[env!("CARGO_PKG_VERSION"), &head, &head_ref, &refs].join("\n"));
+        let path = Path::new(&git_dir)
+            .join(CACHE_DIR)
+            .join(format!("{:016x}.json", key));
+        let entries = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Ok(CacheState { path, entries })
+    }
+}
+
+impl CacheState {
+    fn save(&self) -> Result<()> {
+        let dir = self.path.parent().unwrap();
+        let created = !self.path.exists();
+        fs::create_dir_all(dir)?;
+        fs::write(&self.path, serde_json::to_string(&self.entries)?)?;
+        if created {
+            prune(dir)?;
+        }
+        Ok(())
+    }
+}
+
+impl<G: GitCommandTrait> GitCommandTrait for CachedGitCommand<G> {
+    fn run(&self, args: Vec<&str>) -> Result<String> {
+        if !self.enabled {
+            return self.git_command.run(args);
+        }
+        if !cacheable(&args) {
+            // The command may change the refs
+            *self.state.lock().unwrap() = None;
+            return self.git_command.run(args);
+        }
+        let query = serde_json::to_string(&args)?;
+        {
+            let mut state = self.state.lock().unwrap();
+            if state.is_none() {
+                // Outside of repositories the queries fail uncached
+                *state = self.load().ok();
+            }
+            let Some(state) = state.as_ref() else {
+                drop(state);
+                return self.git_command.run(args);
+            };
+            if let Some(entry) = state.entries.get(&query) {
+                return match entry {
+                    Entry::Ok(output) => Ok(output.clone()),
+                    Entry::Err(message) => Err(VersionError::GitFailure(message.clone()).into()),
+                };
+            }
+        }
+        // Unlocked, so the queries of other threads run meanwhile
+        let result = self.git_command.run(args);
+        let entry = match &result {
+            Ok(output) => Entry::Ok(output.clone()),
+            Err(err) => Entry::Err(err.to_string()),
+        };
+        if let Some(state) = self.state.lock().unwrap().as_mut() {
+            state.entries.insert(query, entry);
+            // A cache which can't be written only costs performance
+            let _ = state.save();
+        }
+        result
+    }
+}
+
+/// Options taking dates, which can be relative to the current time, e.g. `--before=2 weeks ago`
+const DATE_OPTIONS: [&str; 4] = ["--before", "--after", "--since", "--until"];
+
+/// Queries whose results only depend on the commits and refs
+fn cacheable(args: &[&str]) -> bool {
+    let dated = args.iter().any(|arg| {
+        let option = arg.split('=').next().unwrap_or_default();
+        DATE_OPTIONS.contains(&option)
+    });
+    if dated {
+        return false;
+    }
+    match args {
+        ["branch", "--show-current"] => true,
+        ["symbolic-ref", "-q" | "--short", _] => true,
+        // Fetching the full history doesn't change the refs
+        ["rev-parse", "--is-shallow-repository"] => false,
+        [command, ..] => matches!(
+            *command,
+            "describe" | "for-each-ref" | "log" | "merge-base" | "rev-list" | "rev-parse" | "show"
+        ),
+        [] => false,
+    }
+}
+
+/// Removes the least recently written cache files beyond `MAX_FILES`
+fn prune(dir: &Path) -> Result<()> {
+    let mut files = fs::read_dir(dir)?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            Some((entry.metadata().ok()?.modified().ok()?, entry.path()))
+        })
+        .collect::<Vec<_>>();
+    files.sort();
+    for (_, path) in files.iter().rev().skip(MAX_FILES) {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// 64-bit FNV-1a hash, stable across Rust versions unlike `DefaultHasher`
+fn fnv1a(value: &str) -> u64 {
+    value.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::git_command::MockGitCommandTrait;
+
+    use anyhow::Error;
+
+    use super::*;
+
+    fn mock_git(git_command: &mut MockGitCommandTrait, git_dir: &Path, tag: &'static str) {
+        let git_dir = git_dir.to_str().unwrap().to_string();
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "rev-parse --absolute-git-dir")
+            .returning(move |_| Ok(git_dir.clone()));
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "rev-parse HEAD")
+            .returning(|_| Ok("1234567890".to_string()));
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "symbolic-ref -q HEAD")
+            .returning(|_| Ok("refs/heads/main".to_string()));
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "for-each-ref")
+            .returning(move |_| Ok(format!("1234567890 refs/tags/{}", tag)));
+    }
+
+    #[test]
+    fn test_cached_git_command() {
+        let git_dir = std::env::temp_dir().join("version-vine-test-cache");
+        let _ = fs::remove_dir_all(&git_dir);
+
+        let mut git_command = MockGitCommandTrait::new();
+        mock_git(&mut git_command, &git_dir, "1.0.0");
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "describe --abbrev=0 --tags")
+            .times(1)
+            .returning(|_| Ok("1.0.0".to_string()));
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "rev-list --count 1.0.0..HEAD")
+            .times(1)
+            .returning(|_| Err(Error::msg("unknown revision")));
+        let cached = CachedGitCommand::new(git_command, true);
+        for _ in 0..2 {
+            assert_eq!(
+                cached
+                    .run(vec!["describe", "--abbrev=0", "--tags"])
+                    .unwrap(),
+                "1.0.0"
+            );
+            assert!(cached
+                .run(vec!["rev-list", "--count", "1.0.0..HEAD"])
+                .is_err());
+        }
+
+        // Read from disk by a later run
+        let mut git_command = MockGitCommandTrait::new();
+        mock_git(&mut git_command, &git_dir, "1.0.0");
+        let cached = CachedGitCommand::new(git_command, true);
+        assert_eq!(
+            cached
+                .run(vec!["describe", "--abbrev=0", "--tags"])
+                .unwrap(),
+            "1.0.0"
+        );
+
+        // A new tag changes the repository state
+        let mut git_command = MockGitCommandTrait::new();
+        mock_git(&mut git_command, &git_dir, "1.1.0");
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "describe --abbrev=0 --tags")
+            .times(1)
+            .returning(|_| Ok("1.1.0".to_string()));
+        let cached = CachedGitCommand::new(git_command, true);
+        assert_eq!(
+            cached
+                .run(vec!["describe", "--abbrev=0", "--tags"])
+                .unwrap(),
+            "1.1.0"
+        );
+        assert_eq!(fs::read_dir(git_dir.join(CACHE_DIR)).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_cacheable() {
+        assert!(cacheable(&["rev-parse", "--short", "HEAD"]));
+        assert!(cacheable(&["branch", "--show-current"]));
+        assert!(!cacheable(&["branch", "release/1.0.0"]));
+        assert!(!cacheable(&["symbolic-ref", "HEAD", "refs/heads/main"]));
+        assert!(!cacheable(&["rev-parse", "--is-shallow-repository"]));
+        assert!(!cacheable(&["fetch", "--tags"]));
+        assert!(!cacheable(&["tag", "1.0.0"]));
+        assert!(!cacheable(&["rev-parse", "--before=2 weeks ago"]));
+        assert!(!cacheable(&[
+            "rev-list",
+            "-1",
+            "--since",
+            "yesterday",
+            "HEAD"
+        ]));
+    }
+}
@@ -0,0 +1,549 @@
+use anyhow::{Error, Result};
+use clap::ValueEnum;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::conventional_commits::{squashed_messages, ConventionalCommit};
+use crate::git_command::GitCommandTrait;
+use crate::models::Regexes;
+use crate::version_calculator::{describe_tag, get_latest_tag, get_merge_strategy, with_path};
+use crate::{MergeStrategy, VersionInfo, VersionOptions};
+
+/// Headings of the Conventional Commit types, in the order of the changelog. Commits of other
+/// types, and commits which aren't Conventional Commits, are listed under `Other Changes`.
+const GROUPS: [(&str, &str); 6] = [
+    ("feat", "Features"),
+    ("fix", "Bug Fixes"),
+    ("perf", "Performance Improvements"),
+    ("revert", "Reverts"),
+    ("docs", "Documentation"),
+    ("refactor", "Code Refactoring"),
+];
+
+#[derive(clap::Args, Debug)]
+pub struct ChangelogArgs {
+    /// File the changelog section is prepended to, e.g. `CHANGELOG.md`. Printed to stdout when not provided.
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+
+    /// List the authors of the commits under `Contributors`, resolved through `.mailmap`. `initials` and `hash` keep the emails private.
+    #[arg(long, value_enum)]
+    pub contributors: Option<Identity>,
+}
+
+/// How the contributors are listed
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Identity {
+    /// `Name <email>`
+    Full,
+    /// Only the name
+    Name,
+    /// Initials of the name, e.g. `J.D.`
+    Initials,
+    /// Hash of the email, stable across releases
+    Hash,
+}
+
+impl Identity {
+    fn render(&self, name: &str, email: &str) -> String {
+        match self {
+            Identity::Full => format!("{} <{}>", name, email),
+            Identity::Name => name.to_string(),
+            Identity::Initials => name
+                .split_whitespace()
+                .filter_map(|part| part.chars().next())
+                .map(|initial| format!("{}.", initial.to_uppercase()))
+                .collect(),
+            Identity::Hash => {
+                format!("{:016x}", fnv1a(email.trim().to_lowercase().as_bytes()))[..12].to_string()
+            }
+        }
+    }
+}
+
+/// 64-bit FNV-1a, a hash which stays the same across platforms and Rust versions
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+struct Entry {
+    sha: String,
+    author_name: String,
+    author_email: String,
+    commit: Option<ConventionalCommit>,
+    subject: String,
+}
+
+impl Entry {
+    fn render(&self) -> String {
+        match &self.commit {
+            Some(ConventionalCommit {
+                scope: Some(scope),
+                description,
+                ..
+            }) => format!("- **{}:** {} ({})", scope, description, self.sha),
+            Some(commit) => format!("- {} ({})", commit.description, self.sha),
+            None => format!("- {} ({})", self.subject, self.sha),
+        }
+    }
+}
+
+/// Markdown changelog section of the computed version, listing the commits since the previous tag.
+/// On a tagged commit, that's the tag before it. With the squash merge strategy, the commits listed
+/// in the body of a squash merge are entries of their own. A `Changelog:` trailer curates the entry
+/// of its commit: `hidden` leaves it out, and other values replace its description.
+pub fn changelog(
+    git_command: &impl GitCommandTrait,
+    options: &VersionOptions,
+    version_info: &VersionInfo,
+    args: &ChangelogArgs,
+) -> Result<String> {
+    version_info.require_commits("list")?;
+    let regexes = Regexes::new(&options.app_name, &options.config)?;
+    let version = version_info.tag_version();
+    let head = options.git_ref.as_deref().unwrap_or("HEAD");
+    let range = match previous_tag(
+        git_command,
+        &regexes,
+        &regexes.tag_name(&version.to_string()),
+        options.git_ref.as_deref(),
+    ) {
+        Some(tag) => format!("{}..{}", tag, head),
+        None => head.to_string(),
+    };
+    let log = git_command.run(with_path(
+        // `%aN` and `%aE` are mapped through `.mailmap`
+        vec![
+            "log",
+            "--no-merges",
+            "--format=%h%x1f%aN%x1f%aE%x1f%B%x00",
+            &range,
+        ],
+        options,
+    ))?;
+    let squash = get_merge_strategy(git_command, options) == Some(MergeStrategy::Squash);
+    let entries: Vec<Entry> = log
+        .split('\0')
+        .filter_map(|commit| {
+            let mut fields = commit.trim_start().splitn(4, '\x1f');
+            Some((
+                fields.next()?,
+                fields.next()?,
+                fields.next()?,
+                fields.next()?,
+            ))
+        })
+        .flat_map(|(sha, author_name, author_email, message)| {
+            let messages = match squash {
+                true => squashed_messages(message),
+                false => vec![message.to_string()],
+            };
+            messages.into_iter().filter_map(move |message| {
+                let mut commit = ConventionalCommit::parse(&message);
+                let mut subject = message.lines().next().unwrap_or_default().to_string();
+                match changelog_trailer(&message) {
+                    Some(trailer) if trailer.eq_ignore_ascii_case("hidden") => return None,
+                    Some(trailer) => {
+                        if let Some(commit) = &mut commit {
+                            commit.description = trailer.clone();
+                        }
+                        subject = trailer;
+                    }
+                    None => {}
+                }
+                Some(Entry {
+                    sha: sha.to_string(),
+                    author_name: author_name.to_string(),
+                    author_email: author_email.to_string(),
+                    commit,
+                    subject,
+                })
+            })
+        })
+        .collect();
+    let date_format = format!("--format=%{}s", options.date_source().log_placeholder());
+    let date = git_command.run(vec!["log", "-1", &date_format, head])?;
+
+    let mut section = format!("## {} ({})\n", version, date);
+    let mut add_group = |heading: &str, entries: Vec<&Entry>| {
+        if !entries.is_empty() {
+            section.push_str(&format!("\n### {}\n\n", heading));
+            for entry in entries {
+                section.push_str(&entry.render());
+                section.push('\n');
+            }
+        }
+    };
+    let breaking = |entry: &Entry| entry.commit.as_ref().is_some_and(|commit| commit.breaking);
+    let kind = |entry: &Entry| entry.commit.as_ref().map(|commit| commit.kind.clone());
+    add_group(
+        "Breaking Changes",
+        entries.iter().filter(|entry| breaking(entry)).collect(),
+    );
+    for (group_kind, heading) in GROUPS {
+        add_group(
+            heading,
+            entries
+                .iter()
+                .filter(|entry| !breaking(entry) && kind(entry).as_deref() == Some(group_kind))
+                .collect(),
+        );
+    }
+    add_group(
+        "Other Changes",
+        entries
+            .iter()
+            .filter(|entry| {
+                !breaking(entry)
+                    && !GROUPS
+                        .iter()
+                        .any(|(group_kind, _)| kind(entry).as_deref() == Some(*group_kind))
+            })
+            .collect(),
+    );
+    if let Some(identity) = args.contributors {
+        let mut contributors: Vec<String> = entries
+            .iter()
+            .map(|entry| identity.render(&entry.author_name, &entry.author_email))
+            .collect();
+        contributors.sort_by_key(|contributor| contributor.to_lowercase());
+        contributors.dedup();
+        if !contributors.is_empty() {
+            section.push_str("\n### Contributors\n\n");
+            for contributor in contributors {
+                section.push_str(&format!("- {}\n", contributor));
+            }
+        }
+    }
+    Ok(section)
+}
+
+/// Value of the `Changelog:` trailer, from the last paragraph of the message below its subject
+fn changelog_trailer(message: &str) -> Option<String> {
+    let (_, body) = message.trim().split_once("\n\n")?;
+    let trailers = body.rsplit("\n\n").next()?;
+    trailers.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        match key.trim().eq_ignore_ascii_case("changelog") && !value.trim().is_empty() {
+            true => Some(value.trim().to_string()),
+            false => None,
+        }
+    })
+}
+
+/// The existing changelog to prepend to, empty when the file doesn't exist yet. Other errors fail,
+/// so an unreadable changelog isn't overwritten with only the new section.
+pub fn read_changelog(path: &Path) -> Result<String> {
+    match fs::read_to_string(path) {
+        Ok(changelog) => Ok(changelog),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+        Err(err) => Err(Error::msg(format!(
+            "Cannot read '{}': {}",
+            path.display(),
+            err
+        ))),
+    }
+}
+
+/// Prepends the section to an existing changelog, below its `# ` title. A section of the same
+/// version is replaced, so re-runs don't duplicate it.
+pub fn prepend(changelog: &str, section: &str) -> String {
+    let heading = section.lines().next().unwrap_or_default();
+    let version = heading.split(' ').nth(1).unwrap_or_default();
+    let (title, rest) = match changelog.strip_prefix("# ") {
+        Some(_) => changelog.split_once('\n').unwrap_or((changelog, "")),
+        None => ("", changelog),
+    };
+
+    let mut sections = vec![];
+    let mut current = String::new();
+    for line in rest.lines() {
+        if line.starts_with("## ") && !current.trim().is_empty() {
+            sections.push(current);
+            current = String::new();
+        }
+        current.push_str(line);
+        current.push('\n');
+    }
+    sections.push(current);
+    let sections: Vec<String> = sections
+        .into_iter()
+        .filter(|existing| existing.split(' ').nth(1) != Some(version))
+        .map(|existing| existing.trim().to_string())
+        .filter(|existing| !existing.is_empty())
+        .collect();
+
+    let mut result = String::new();
+    if !title.is_empty() {
+        result.push_str(title);
+        result.push_str("\n\n");
+    }
+    result.push_str(section.trim());
+    result.push('\n');
+    for existing in sections {
+        result.push('\n');
+        result.push_str(&existing);
+        result.push('\n');
+    }
+    result
+}
+
+/// Latest tag, or the tag before it when it's the tag of the computed version
+fn previous_tag(
+    git_command: &impl GitCommandTrait,
+    regexes: &Regexes,
+    version_tag: &str,
+    rev: Option<&str>,
+) -> Option<String> {
+    let latest_tag = get_latest_tag(git_command, regexes, rev)?;
+    if latest_tag != version_tag {
+        return Some(latest_tag);
+    }
+    let rev = format!("{}^", latest_tag);
+    describe_tag(git_command, regexes, false, Some(&rev)).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::git_command::MockGitCommandTrait;
+
+    use anyhow::Error;
+
+    use super::*;
+    use crate::version_calculator::version_info;
+
+    fn changelog_args(contributors: Option<Identity>) -> ChangelogArgs {
+        ChangelogArgs {
+            output: None,
+            contributors,
+        }
+    }
+
+    fn mock_git(git_command: &mut MockGitCommandTrait, expected_range: &'static str) {
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "describe --abbrev=0 --tags")
+            .returning(|_| Ok("1.1.0".to_string()));
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "describe --abbrev=0 --tags 1.1.0^")
+            .returning(|_| Ok("1.0.0".to_string()));
+        git_command
+            .expect_run()
+            .withf(move |args| args[0] == "log" && args[1] == "--no-merges")
+            .returning(move |args| {
+                assert_eq!(args[3], expected_range);
+                Ok([
+                    "aaaaaaa\x1fJane Doe\x1fjane@example.com\x1ffeat(cli): add --output-format\n",
+                    "bbbbbbb\x1fjohn smith\x1fjohn@example.com\x1ffix: crash on empty tag\n",
+                    "ccccccc\x1fJane Doe\x1fjane@example.com\x1frefactor!: rename options\n",
+                    "ddddddd\x1fJane Doe\x1fjane@example.com\x1fUpdate README\n",
+                    "eeeeeee\x1fbot\x1fbot@example.com\x1fchore: bump deps\n",
+                ]
+                .join("\0\n"))
+            });
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "log -1 --format=%cs HEAD")
+            .returning(|_| Ok("2024-02-14".to_string()));
+    }
+
+    #[test]
+    fn test_changelog() {
+        let mut git_command = MockGitCommandTrait::new();
+        mock_git(&mut git_command, "1.1.0..HEAD");
+
+        let section = changelog(
+            &git_command,
+            &VersionOptions::default(),
+            &version_info("release/1.2.0", "1.2.0-rc.1+3"),
+            &changelog_args(None),
+        )
+        .unwrap();
+
+        assert_eq!(
+            section,
+            "## 1.2.0-rc.1 (2024-02-14)\n\
+             \n### Breaking Changes\n\n- rename options (ccccccc)\n\
+             \n### Features\n\n- **cli:** add --output-format (aaaaaaa)\n\
+             \n### Bug Fixes\n\n- crash on empty tag (bbbbbbb)\n\
+             \n### Other Changes\n\n- Update README (ddddddd)\n- bump deps (eeeeeee)\n"
+        );
+    }
+
+    #[test]
+    fn test_changelog_squash() {
+        let mut git_command = MockGitCommandTrait::new();
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "log" && args[1] == "--no-merges")
+            .returning(|_| {
+                Ok([
+                    "aaaaaaa\x1fJane Doe\x1fjane@example.com\x1fLogin page (#12)\n\n* feat(ui): add the login page\n\n* fix: typo\n",
+                    "bbbbbbb\x1fjohn smith\x1fjohn@example.com\x1ffix: crash on empty tag (#11)\n\n* wip\n",
+                ]
+                .join("\0\n"))
+            });
+        mock_git(&mut git_command, "1.1.0..HEAD");
+        let options = VersionOptions {
+            merge_strategy: Some(MergeStrategy::Squash),
+            ..Default::default()
+        };
+
+        let section = changelog(
+            &git_command,
+            &options,
+            &version_info("release/1.2.0", "1.2.0-rc.1+3"),
+            &changelog_args(None),
+        )
+        .unwrap();
+
+        assert_eq!(
+            section,
+            "## 1.2.0-rc.1 (2024-02-14)\n\
+             \n### Features\n\n- **ui:** add the login page (aaaaaaa)\n\
+             \n### Bug Fixes\n\n- typo (aaaaaaa)\n- crash on empty tag (#11) (bbbbbbb)\n"
+        );
+    }
+
+    #[test]
+    fn test_changelog_trailers() {
+        let mut git_command = MockGitCommandTrait::new();
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "log" && args[1] == "--no-merges")
+            .returning(|_| {
+                Ok([
+                    "aaaaaaa\x1fJane Doe\x1fjane@example.com\x1ffeat(ui): add LoginPage component\n\nChangelog: add a login page\nSigned-off-by: Jane Doe <jane@example.com>\n",
+                    "bbbbbbb\x1fjohn smith\x1fjohn@example.com\x1ffix: flaky test\n\nChangelog: hidden\n",
+                    "ccccccc\x1fJane Doe\x1fjane@example.com\x1fchangelog: entry of the subject\n",
+                ]
+                .join("\0\n"))
+            });
+        mock_git(&mut git_command, "1.1.0..HEAD");
+
+        let section = changelog(
+            &git_command,
+            &VersionOptions::default(),
+            &version_info("release/1.2.0", "1.2.0-rc.1+3"),
+            &changelog_args(None),
+        )
+        .unwrap();
+
+        assert_eq!(
+            section,
+            "## 1.2.0-rc.1 (2024-02-14)\n\
+             \n### Features\n\n- **ui:** add a login page (aaaaaaa)\n\
+             \n### Other Changes\n\n- entry of the subject (ccccccc)\n"
+        );
+    }
+
+    #[test]
+    fn test_changelog_contributors() {
+        let contributors = |identity| {
+            let mut git_command = MockGitCommandTrait::new();
+            mock_git(&mut git_command, "1.1.0..HEAD");
+            let section = changelog(
+                &git_command,
+                &VersionOptions::default(),
+                &version_info("release/1.2.0", "1.2.0-rc.1+3"),
+                &changelog_args(Some(identity)),
+            )
+            .unwrap();
+            section
+                .split_once("\n### Contributors\n\n")
+                .unwrap()
+                .1
+                .to_string()
+        };
+
+        assert_eq!(
+            contributors(Identity::Full),
+            "- bot <bot@example.com>\n- Jane Doe <jane@example.com>\n- john smith <john@example.com>\n"
+        );
+        assert_eq!(contributors(Identity::Initials), "- B.\n- J.D.\n- J.S.\n");
+        let hashed = contributors(Identity::Hash);
+        assert_eq!(hashed.lines().count(), 3);
+        assert!(!hashed.contains("example.com"));
+        assert_eq!(
+            Identity::Hash.render("Jane", "Jane@Example.com "),
+            Identity::Hash.render("Jane Doe", "jane@example.com")
+        );
+    }
+
+    #[test]
+    fn test_changelog_tagged() {
+        let mut git_command = MockGitCommandTrait::new();
+        mock_git(&mut git_command, "1.0.0..HEAD");
+
+        let section = changelog(
+            &git_command,
+            &VersionOptions::default(),
+            &version_info("main", "1.1.0"),
+            &changelog_args(None),
+        )
+        .unwrap();
+
+        assert!(section.starts_with("## 1.1.0 (2024-02-14)\n"));
+    }
+
+    #[test]
+    fn test_changelog_no_tag() {
+        let mut git_command = MockGitCommandTrait::new();
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "describe")
+            .returning(|_| Err(Error::msg("No names found")));
+        git_command
+            .expect_run()
+            .withf(|args| {
+                args.join(" ") == "log --no-merges --format=%h%x1f%aN%x1f%aE%x1f%B%x00 HEAD"
+            })
+            .returning(|_| Ok("aaaaaaa\x1fJane Doe\x1fjane@example.com\x1finit\n\0".to_string()));
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "log" && args[1] == "-1")
+            .returning(|_| Ok("2024-01-01".to_string()));
+
+        let section = changelog(
+            &git_command,
+            &VersionOptions::default(),
+            &version_info("develop", "0.0.1-beta.1+1"),
+            &changelog_args(None),
+        )
+        .unwrap();
+
+        assert_eq!(
+            section,
+            "## 0.0.1-beta.1 (2024-01-01)\n\n### Other Changes\n\n- init (aaaaaaa)\n"
+        );
+    }
+
+    #[test]
+    fn test_read_changelog() {
+        let dir = std::env::temp_dir();
+        let missing = dir.join(format!("version-vine-changelog-{}.md", std::process::id()));
+
+        assert_eq!(read_changelog(&missing).unwrap(), "");
+        assert!(read_changelog(&dir)
+            .unwrap_err()
+            .to_string()
+            .starts_with(&format!("Cannot read '{}': ", dir.display())));
+    }
+
+    #[test]
+    fn test_prepend() {
+        let existing =
+            "# Changelog\n\n## 1.1.0 (2024-02-01)\n\n- old\n\n## 1.0.0 (2024-01-01)\n\n- first\n";
+        let section = "## 1.1.0 (2024-02-14)\n\n- new\n";
+
+        assert_eq!(
+            prepend(existing, section),
+            "# Changelog\n\n## 1.1.0 (2024-02-14)\n\n- new\n\n## 1.0.0 (2024-01-01)\n\n- first\n"
+        );
+        assert_eq!(prepend("", section), section);
+    }
+}
@@ -0,0 +1,509 @@
+use anyhow::{Error, Result};
+use clap::ValueEnum;
+use serde::Serialize;
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::output::{azure_escape, export_env, teamcity_escape, ExportEnv};
+use crate::{VersionInfo, VersionOptions};
+
+/// CI systems with their own way of passing variables to the following steps
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CiSystem {
+    GithubActions,
+    AzurePipelines,
+    TeamCity,
+    GitlabCi,
+    Jenkins,
+}
+
+/// CI system the build runs on, from the variables each of them sets
+pub fn detect_ci(env: impl Fn(&str) -> Option<String>) -> Option<CiSystem> {
+    if env("GITHUB_ACTIONS").is_some_and(|value| value == "true") {
+        Some(CiSystem::GithubActions)
+    } else if env("TF_BUILD").is_some_and(|value| value.eq_ignore_ascii_case("true")) {
+        Some(CiSystem::AzurePipelines)
+    } else if env("TEAMCITY_VERSION").is_some_and(|value| !value.is_empty()) {
+        Some(CiSystem::TeamCity)
+    } else if env("GITLAB_CI").is_some_and(|value| value == "true") {
+        Some(CiSystem::GitlabCi)
+    } else if env("JENKINS_URL").is_some_and(|value| !value.is_empty()) {
+        Some(CiSystem::Jenkins)
+    } else {
+        None
+    }
+}
+
+/// CI system of the `--ci` integration
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Ci {
+    /// The detected CI system
+    Auto,
+    /// GitHub Actions
+    Github,
+    /// GitLab CI
+    Gitlab,
+    /// Azure Pipelines
+    Azure,
+    /// Jenkins
+    Jenkins,
+    /// TeamCity
+    Teamcity,
+}
+
+/// Machine the version is computed on, the `builder` block of `--builder`
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Builder {
+    /// CI system, e.g. `github-actions`, `None` outside of CI
+    pub ci: Option<String>,
+    /// Operating system, e.g. `linux`
+    pub os: String,
+    /// CPU architecture, e.g. `x86_64`
+    pub arch: String,
+    /// Name of the runner or agent
+    pub runner: Option<String>,
+    /// URL of the pipeline run
+    pub pipeline_url: Option<String>,
+}
+
+/// The machine identity of the build, from the variables of the detected CI system
+pub fn builder(env: impl Fn(&str) -> Option<String>) -> Builder {
+    let env = |name: &str| env(name).filter(|value| !value.is_empty());
+    let adapter = detect_ci(env).map(adapter);
+    Builder {
+        ci: adapter.map(|adapter| adapter.name().to_string()),
+        os: std::env::consts::OS.to_string(),
+        arch: std::env::consts::ARCH.to_string(),
+        runner: adapter.and_then(|adapter| env(adapter.runner_variable())),
+        pipeline_url: adapter.and_then(|adapter| adapter.pipeline_url(&env)),
+    }
+}
+
+/// Attempt of the CI run, 1 for the first run and outside of CI systems which rerun pipelines
+pub fn run_attempt(env: impl Fn(&str) -> Option<String>) -> u64 {
+    detect_ci(&env)
+        .map(adapter)
+        .and_then(|adapter| adapter.run_attempt_variable())
+        .and_then(|variable| env(variable)?.parse().ok())
+        .unwrap_or(1)
+}
+
+/// What a CI system supports beyond its environment variables
+trait CiAdapter {
+    /// Name of the `builder` block
+    fn name(&self) -> &'static str;
+
+    /// Variable of the runner or agent name
+    fn runner_variable(&self) -> &'static str;
+
+    /// URL of the pipeline run
+    fn pipeline_url(&self, env: &dyn Fn(&str) -> Option<String>) -> Option<String>;
+
+    /// Variable of the attempt of reruns
+    fn run_attempt_variable(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Mode of `--export-env`
+    fn export_env(&self) -> ExportEnv;
+
+    /// Logging command renaming the build after the version
+    fn build_number(&self, _version_info: &VersionInfo) -> Option<String> {
+        None
+    }
+
+    /// Publishes the Markdown job summary, returning the logging command to print
+    fn summary(
+        &self,
+        _summary: &str,
+        _env: &dyn Fn(&str) -> Option<String>,
+    ) -> Result<Option<String>> {
+        Ok(None)
+    }
+}
+
+struct GithubActions;
+struct AzurePipelines;
+struct TeamCity;
+struct GitlabCi;
+struct Jenkins;
+
+impl CiAdapter for GithubActions {
+    fn name(&self) -> &'static str {
+        "github-actions"
+    }
+
+    fn runner_variable(&self) -> &'static str {
+        "RUNNER_NAME"
+    }
+
+    fn run_attempt_variable(&self) -> Option<&'static str> {
+        Some("GITHUB_RUN_ATTEMPT")
+    }
+
+    fn pipeline_url(&self, env: &dyn Fn(&str) -> Option<String>) -> Option<String> {
+        Some(format!(
+            "{}/{}/actions/runs/{}",
+            env("GITHUB_SERVER_URL")?,
+            env("GITHUB_REPOSITORY")?,
+            env("GITHUB_RUN_ID")?
+        ))
+    }
+
+    fn export_env(&self) -> ExportEnv {
+        ExportEnv::Github
+    }
+
+    /// Appended to the `$GITHUB_STEP_SUMMARY` file
+    fn summary(
+        &self,
+        summary: &str,
+        env: &dyn Fn(&str) -> Option<String>,
+    ) -> Result<Option<String>> {
+        let path = env("GITHUB_STEP_SUMMARY")
+            .filter(|path| !path.is_empty())
+            .ok_or(Error::msg(
+                "GITHUB_STEP_SUMMARY is not set. Provide the CI system with --ci outside of GitHub Actions.",
+            ))?;
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| file.write_all(summary.as_bytes()))
+            .map_err(|err| Error::msg(format!("Cannot write '{}': {}", path, err)))?;
+        Ok(None)
+    }
+}
+
+impl CiAdapter for AzurePipelines {
+    fn name(&self) -> &'static str {
+        "azure-pipelines"
+    }
+
+    fn runner_variable(&self) -> &'static str {
+        "AGENT_NAME"
+    }
+
+    fn run_attempt_variable(&self) -> Option<&'static str> {
+        Some("SYSTEM_JOBATTEMPT")
+    }
+
+    fn pipeline_url(&self, env: &dyn Fn(&str) -> Option<String>) -> Option<String> {
+        Some(format!(
+            "{}{}/_build/results?buildId={}",
+            env("SYSTEM_COLLECTIONURI")?,
+            env("SYSTEM_TEAMPROJECT")?,
+            env("BUILD_BUILDID")?
+        ))
+    }
+
+    fn export_env(&self) -> ExportEnv {
+        ExportEnv::Azure
+    }
+
+    fn build_number(&self, version_info: &VersionInfo) -> Option<String> {
+        Some(format!(
+            "##vso[build.updatebuildnumber]{}",
+            azure_escape(&version_info.version.to_string())
+        ))
+    }
+
+    /// Written to the agent's temporary directory and attached to the build with
+    /// `task.uploadsummary`
+    fn summary(
+        &self,
+        summary: &str,
+        env: &dyn Fn(&str) -> Option<String>,
+    ) -> Result<Option<String>> {
+        let dir = env("AGENT_TEMPDIRECTORY")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(std::env::temp_dir);
+        let path = dir.join("version-vine-summary.md");
+        fs::write(&path, summary)
+            .map_err(|err| Error::msg(format!("Cannot write '{}': {}", path.display(), err)))?;
+        Ok(Some(format!(
+            "##vso[task.uploadsummary]{}",
+            azure_escape(&path.display().to_string())
+        )))
+    }
+}
+
+impl CiAdapter for TeamCity {
+    fn name(&self) -> &'static str {
+        "teamcity"
+    }
+
+    /// Not set by default, only when the agent name is passed as `env.AGENT_NAME` parameter
+    fn runner_variable(&self) -> &'static str {
+        "AGENT_NAME"
+    }
+
+    /// Not exposed to the build environment
+    fn pipeline_url(&self, _env: &dyn Fn(&str) -> Option<String>) -> Option<String> {
+        None
+    }
+
+    fn export_env(&self) -> ExportEnv {
+        ExportEnv::Teamcity
+    }
+
+    fn build_number(&self, version_info: &VersionInfo) -> Option<String> {
+        Some(format!(
+            "##teamcity[buildNumber '{}']",
+            teamcity_escape(&version_info.version.to_string())
+        ))
+    }
+}
+
+/// GitLab CI and Jenkins only take the variables, e.g. by `eval` or a dotenv report
+impl CiAdapter for GitlabCi {
+    fn name(&self) -> &'static str {
+        "gitlab-ci"
+    }
+
+    fn runner_variable(&self) -> &'static str {
+        "CI_RUNNER_DESCRIPTION"
+    }
+
+    fn pipeline_url(&self, env: &dyn Fn(&str) -> Option<String>) -> Option<String> {
+        env("CI_PIPELINE_URL")
+    }
+
+    fn export_env(&self) -> ExportEnv {
+        ExportEnv::Shell
+    }
+}
+
+impl CiAdapter for Jenkins {
+    fn name(&self) -> &'static str {
+        "jenkins"
+    }
+
+    fn runner_variable(&self) -> &'static str {
+        "NODE_NAME"
+    }
+
+    fn pipeline_url(&self, env: &dyn Fn(&str) -> Option<String>) -> Option<String> {
+        env("BUILD_URL")
+    }
+
+    fn export_env(&self) -> ExportEnv {
+        ExportEnv::Shell
+    }
+}
+
+fn adapter(system: CiSystem) -> &'static dyn CiAdapter {
+    match system {
+        CiSystem::GithubActions => &GithubActions,
+        CiSystem::AzurePipelines => &AzurePipelines,
+        CiSystem::TeamCity => &TeamCity,
+        CiSystem::GitlabCi => &GitlabCi,
+        CiSystem::Jenkins => &Jenkins,
+    }
+}
+
+/// Integrates the version into the build of the CI system: exports the version fields as
+/// environment variables, names the build after the version and adds a job summary, where the CI
+/// system supports them. Returns the output to print.
+pub fn ci_integration(
+    version_info: &VersionInfo,
+    options: &VersionOptions,
+    ci: Ci,
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<String> {
+    let system = match ci {
+        Ci::Auto => detect_ci(&env).ok_or(Error::msg(
+            "No CI system detected. Provide it with --ci <CI>.",
+        ))?,
+        Ci::Github => CiSystem::GithubActions,
+        Ci::Gitlab => CiSystem::GitlabCi,
+        Ci::Azure => CiSystem::AzurePipelines,
+        Ci::Jenkins => CiSystem::Jenkins,
+        Ci::Teamcity => CiSystem::TeamCity,
+    };
+    let adapter = adapter(system);
+    let mut lines = vec![export_env(
+        version_info,
+        options,
+        adapter.export_env(),
+        &env,
+    )?];
+    lines.extend(adapter.build_number(version_info));
+    lines.extend(adapter.summary(&job_summary(version_info, options), &env)?);
+    lines.retain(|line| !line.is_empty());
+    Ok(lines.join("\n"))
+}
+
+/// Markdown table of the version, for the job summary
+fn job_summary(version_info: &VersionInfo, options: &VersionOptions) -> String {
+    let title = match &options.app_name {
+        Some(app_name) => format!("{} {}", app_name, version_info.version),
+        None => version_info.version.to_string(),
+    };
+    let mut rows = vec![
+        ("Version", version_info.version.to_string()),
+        ("Container tag", version_info.container_tag.clone()),
+        ("Branch", version_info.git_branch.clone()),
+        ("Commit", version_info.git_rev.clone().unwrap_or_default()),
+        ("Channel", version_info.channel.clone()),
+    ];
+    if version_info.container_tags.len() > 1 {
+        rows.push(("Container tags", version_info.container_tags.join(", ")));
+    }
+    let mut summary = format!("### {}\n\n| Field | Value |\n| --- | --- |\n", title);
+    for (field, value) in rows {
+        summary.push_str(&format!(
+            "| {} | `{}` |\n",
+            field,
+            value.replace('|', "\\|")
+        ));
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    fn version_info() -> VersionInfo {
+        VersionInfo {
+            container_tags: vec!["1.0.1-beta.3.1234567".to_string(), "beta".to_string()],
+            ..crate::version_calculator::version_info("develop", "1.0.1-beta.3+1234567")
+        }
+    }
+
+    #[test]
+    fn test_detect_ci() {
+        let detect = |name: &'static str, value: &'static str| {
+            detect_ci(|variable| (variable == name).then(|| value.to_string()))
+        };
+
+        assert_eq!(
+            detect("GITHUB_ACTIONS", "true"),
+            Some(CiSystem::GithubActions)
+        );
+        assert_eq!(detect("TF_BUILD", "True"), Some(CiSystem::AzurePipelines));
+        assert_eq!(
+            detect("TEAMCITY_VERSION", "2024.03"),
+            Some(CiSystem::TeamCity)
+        );
+        assert_eq!(detect("GITHUB_ACTIONS", "false"), None);
+        assert_eq!(detect("GITLAB_CI", "true"), Some(CiSystem::GitlabCi));
+        assert_eq!(
+            detect("JENKINS_URL", "https://jenkins.example.com/"),
+            Some(CiSystem::Jenkins)
+        );
+    }
+
+    #[test]
+    fn test_builder() {
+        let vars = BTreeMap::from([
+            ("GITHUB_ACTIONS", "true"),
+            ("GITHUB_SERVER_URL", "https://github.com"),
+            ("GITHUB_REPOSITORY", "org/app"),
+            ("GITHUB_RUN_ID", "42"),
+            ("RUNNER_NAME", "GitHub Actions 7"),
+            ("GITHUB_RUN_ATTEMPT", "2"),
+        ]);
+
+        let github = builder(|name| vars.get(name).map(|value| value.to_string()));
+
+        assert_eq!(github.ci.as_deref(), Some("github-actions"));
+        assert_eq!(github.os, std::env::consts::OS);
+        assert_eq!(github.runner.as_deref(), Some("GitHub Actions 7"));
+        assert_eq!(
+            github.pipeline_url.as_deref(),
+            Some("https://github.com/org/app/actions/runs/42")
+        );
+        assert_eq!(
+            run_attempt(|name| vars.get(name).map(|value| value.to_string())),
+            2
+        );
+        // Outside of CI
+        assert_eq!(builder(|_| None).ci, None);
+        assert_eq!(run_attempt(|_| None), 1);
+    }
+
+    #[test]
+    fn test_ci_integration_azure() {
+        let dir = std::env::temp_dir().join("version-vine-test-ci-azure");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let temp_dir = dir.to_str().unwrap().to_string();
+
+        let output = ci_integration(
+            &version_info(),
+            &VersionOptions::default(),
+            Ci::Auto,
+            |name| match name {
+                "TF_BUILD" => Some("True".to_string()),
+                "AGENT_TEMPDIRECTORY" => Some(temp_dir.clone()),
+                _ => None,
+            },
+        )
+        .unwrap();
+
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines.contains(
+            &"##vso[task.setvariable variable=VERSION_VINE_APP_VERSION]1.0.1-beta.3+1234567"
+        ));
+        assert!(lines.contains(&"##vso[build.updatebuildnumber]1.0.1-beta.3+1234567"));
+        let summary_path = dir.join("version-vine-summary.md");
+        assert_eq!(
+            lines.last().unwrap(),
+            &format!("##vso[task.uploadsummary]{}", summary_path.display())
+        );
+        let summary = fs::read_to_string(summary_path).unwrap();
+        assert!(summary.starts_with("### 1.0.1-beta.3+1234567\n"));
+        assert!(summary.contains("| Container tags | `1.0.1-beta.3.1234567, beta` |"));
+    }
+
+    #[test]
+    fn test_ci_integration_github() {
+        let dir = std::env::temp_dir().join("version-vine-test-ci-github");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let github_env = dir.join("env").to_str().unwrap().to_string();
+        let step_summary = dir.join("summary").to_str().unwrap().to_string();
+
+        let output = ci_integration(
+            &version_info(),
+            &VersionOptions::default(),
+            Ci::Github,
+            |name| match name {
+                "GITHUB_ENV" => Some(github_env.clone()),
+                "GITHUB_STEP_SUMMARY" => Some(step_summary.clone()),
+                _ => None,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(output, "");
+        assert!(fs::read_to_string(&github_env)
+            .unwrap()
+            .contains("VERSION_VINE_APP_VERSION=1.0.1-beta.3+1234567\n"));
+        assert!(fs::read_to_string(&step_summary)
+            .unwrap()
+            .contains("| Branch | `develop` |"));
+    }
+
+    #[test]
+    fn test_ci_integration_undetected() {
+        let result = ci_integration(
+            &version_info(),
+            &VersionOptions::default(),
+            Ci::Auto,
+            |_| None,
+        );
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "No CI system detected. Provide it with --ci <CI>."
+        );
+    }
+}
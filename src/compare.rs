@@ -0,0 +1,134 @@
+use anyhow::{Error, Result};
+use semver::Version;
+use serde::Serialize;
+
+use std::cmp::Ordering;
+
+use crate::git_command::GitCommandTrait;
+use crate::{VersionCalculator, VersionOptions};
+
+/// Part of the version that differs between two versions, the most significant one
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum VersionChange {
+    Major,
+    Minor,
+    Patch,
+    /// Only the pre-release, e.g. `1.2.0-rc.1` and `1.2.0`
+    Prerelease,
+}
+
+/// SemVer precedence of a version relative to another one
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Precedence {
+    Lower,
+    Equal,
+    Greater,
+}
+
+/// Computed version of a ref
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct RefVersion {
+    pub git_ref: String,
+    pub git_branch: String,
+    pub version: Version,
+}
+
+/// Versions of two refs and how they relate, e.g. to check that a release branch is ahead of main
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Comparison {
+    pub from: RefVersion,
+    pub to: RefVersion,
+    /// SemVer precedence of `to` relative to `from`, build metadata ignored
+    pub ordering: Precedence,
+    /// Most significant part which differs, `None` for versions of the same precedence
+    pub change: Option<VersionChange>,
+    /// Commits of `to` which aren't on `from`
+    pub ahead: u32,
+    /// Commits of `from` which aren't on `to`
+    pub behind: u32,
+}
+
+/// Computes the versions of both refs, as with `--ref`, and compares them
+pub fn compare(
+    git_command: &impl GitCommandTrait,
+    options: &VersionOptions,
+    from: &str,
+    to: &str,
+) -> Result<Comparison> {
+    let version = |git_ref: &str| -> Result<RefVersion> {
+        let options = VersionOptions {
+            git_ref: Some(git_ref.to_string()),
+            ..options.clone()
+        };
+        let version_info = VersionCalculator::new(git_command, options)?
+            .calculate()
+            .map_err(|err| err.context(format!("Cannot compute the version of '{}'", git_ref)))?;
+        Ok(RefVersion {
+            git_ref: git_ref.to_string(),
+            git_branch: version_info.git_branch,
+            version: version_info.version,
+        })
+    };
+    let (from, to) = (version(from)?, version(to)?);
+    let range = format!("{}...{}", from.git_ref, to.git_ref);
+    let counts = git_command.run(vec!["rev-list", "--left-right", "--count", &range])?;
+    let (behind, ahead) = match counts.split_whitespace().collect::<Vec<_>>().as_slice() {
+        [behind, ahead] => (behind.parse()?, ahead.parse()?),
+        _ => {
+            return Err(Error::msg(format!(
+                "Unexpected commit counts '{}' of {}",
+                counts, range
+            )))
+        }
+    };
+    Ok(Comparison {
+        ordering: match to.version.cmp_precedence(&from.version) {
+            Ordering::Less => Precedence::Lower,
+            Ordering::Equal => Precedence::Equal,
+            Ordering::Greater => Precedence::Greater,
+        },
+        change: version_change(&from.version, &to.version),
+        from,
+        to,
+        ahead,
+        behind,
+    })
+}
+
+fn version_change(from: &Version, to: &Version) -> Option<VersionChange> {
+    if from.major != to.major {
+        Some(VersionChange::Major)
+    } else if from.minor != to.minor {
+        Some(VersionChange::Minor)
+    } else if from.patch != to.patch {
+        Some(VersionChange::Patch)
+    } else if from.pre != to.pre {
+        Some(VersionChange::Prerelease)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(from: &str, to: &str) -> Option<VersionChange> {
+        version_change(&Version::parse(from).unwrap(), &Version::parse(to).unwrap())
+    }
+
+    #[test]
+    fn test_version_change() {
+        assert_eq!(change("1.2.0", "2.0.0-rc.1"), Some(VersionChange::Major));
+        assert_eq!(change("1.2.0", "1.3.0-rc.1"), Some(VersionChange::Minor));
+        assert_eq!(change("1.3.0", "1.2.1"), Some(VersionChange::Minor));
+        assert_eq!(change("1.2.0", "1.2.1-beta.4"), Some(VersionChange::Patch));
+        assert_eq!(
+            change("1.3.0-rc.1", "1.3.0"),
+            Some(VersionChange::Prerelease)
+        );
+        assert_eq!(change("1.3.0+1234567", "1.3.0+7654321"), None);
+    }
+}
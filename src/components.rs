@@ -0,0 +1,180 @@
+use anyhow::{Error, Result};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+use std::path::Path;
+
+use crate::git_command::GitCommandTrait;
+
+/// Submodule of the versioned commit, an entry of the `components` of `--components`
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Component {
+    /// Path of the submodule in the repository
+    pub path: String,
+    /// Full SHA of the commit the submodule is pinned to
+    pub sha: String,
+    /// Latest tag of the submodule reachable from the commit, `None` when the submodule isn't
+    /// checked out or tagged
+    pub tag: Option<String>,
+    /// Version of the tag, without a `v` prefix. `None` for tags which aren't SemVer.
+    pub version: Option<String>,
+}
+
+/// The submodules of the `.gitmodules` of the commit, with the commits they're pinned to, so the
+/// contents of a composite build are fully described. The tags are looked up in the checked out
+/// submodules. Submodules of `.gitmodules` which aren't in the tree of the commit are skipped.
+pub fn components(git_command: &impl GitCommandTrait, rev: &str) -> Result<Vec<Component>> {
+    // Fails without `.gitmodules` or submodules in it
+    let Ok(paths) = git_command.run(vec![
+        "config",
+        "--blob",
+        &format!("{}:.gitmodules", rev),
+        "--get-regexp",
+        r"^submodule\..*\.path$",
+    ]) else {
+        return Ok(vec![]);
+    };
+    // The submodule paths are relative to the root, not to the working directory
+    let toplevel = git_command.run(vec!["rev-parse", "--show-toplevel"])?;
+    let mut components = vec![];
+    for path in paths.lines().filter_map(|line| line.split_once(' ')) {
+        let path = path.1;
+        let Ok(sha) = git_command.run(vec!["rev-parse", &format!("{}:{}", rev, path)]) else {
+            eprintln!(
+                "Warning: Submodule '{}' of .gitmodules isn't in the tree of '{}'",
+                path, rev
+            );
+            continue;
+        };
+        let submodule = Path::new(&toplevel).join(path);
+        let tag = git_command
+            .run(vec![
+                "-C",
+                &submodule.to_string_lossy(),
+                "describe",
+                "--tags",
+                "--abbrev=0",
+                &sha,
+            ])
+            .ok()
+            .filter(|tag| !tag.is_empty());
+        let version = tag
+            .as_deref()
+            .and_then(|tag| Version::parse(tag.strip_prefix('v').unwrap_or(tag)).ok())
+            .map(|version| version.to_string());
+        components.push(Component {
+            path: path.to_string(),
+            sha,
+            tag,
+            version,
+        });
+    }
+    Ok(components)
+}
+
+/// Checks that `--submodule` is a checked out submodule, `git_command` running inside it. The
+/// queries would otherwise run in the superproject, or fail with a git error.
+pub fn check_submodule(git_command: &impl GitCommandTrait, path: &Path) -> Result<()> {
+    if !path.join(".git").exists() {
+        return Err(Error::msg(format!(
+            "Submodule '{}' isn't checked out. Run `git submodule update --init {}` first.",
+            path.display(),
+            path.display()
+        )));
+    }
+    let superproject = git_command.run(vec!["rev-parse", "--show-superproject-working-tree"])?;
+    match superproject.is_empty() {
+        true => Err(Error::msg(format!(
+            "'{}' isn't a submodule, but a repository of its own",
+            path.display()
+        ))),
+        false => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::git_command::MockGitCommandTrait;
+
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_components() {
+        let mut git_command = MockGitCommandTrait::new();
+        let commands: Vec<(&str, Result<&str, &str>)> = vec![
+            (
+                r"config --blob HEAD:.gitmodules --get-regexp ^submodule\..*\.path$",
+                Ok("submodule.lib.path vendor/lib\nsubmodule.ui.path vendor/ui\nsubmodule.old.path old"),
+            ),
+            ("rev-parse --show-toplevel", Ok("/repo")),
+            ("rev-parse HEAD:vendor/lib", Ok("1111111111")),
+            ("rev-parse HEAD:vendor/ui", Ok("2222222222")),
+            ("rev-parse HEAD:old", Err("fatal: path 'old' does not exist")),
+            (
+                "-C /repo/vendor/lib describe --tags --abbrev=0 1111111111",
+                Ok("v2.1.0"),
+            ),
+            (
+                "-C /repo/vendor/ui describe --tags --abbrev=0 2222222222",
+                Ok("nightly"),
+            ),
+        ];
+        for (command, output) in commands {
+            git_command
+                .expect_run()
+                .withf(move |args| args.join(" ") == command)
+                .returning(move |_| output.map(String::from).map_err(Error::msg));
+        }
+
+        assert_eq!(
+            components(&git_command, "HEAD").unwrap(),
+            vec![
+                Component {
+                    path: "vendor/lib".to_string(),
+                    sha: "1111111111".to_string(),
+                    tag: Some("v2.1.0".to_string()),
+                    version: Some("2.1.0".to_string()),
+                },
+                Component {
+                    path: "vendor/ui".to_string(),
+                    sha: "2222222222".to_string(),
+                    tag: Some("nightly".to_string()),
+                    version: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_submodule() {
+        let dir = std::env::temp_dir().join("version-vine-test-submodule");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let mut git_command = MockGitCommandTrait::new();
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "rev-parse --show-superproject-working-tree")
+            .times(1)
+            .returning(|_| Ok("/repo".to_string()));
+        git_command.expect_run().returning(|_| Ok(String::new()));
+
+        let err = check_submodule(&git_command, &dir).unwrap_err();
+        assert!(err.to_string().ends_with(&format!(
+            "isn't checked out. Run `git submodule update --init {}` first.",
+            dir.display()
+        )));
+
+        fs::write(dir.join(".git"), "gitdir: ../.git/modules/lib").unwrap();
+        assert!(check_submodule(&git_command, &dir).is_ok());
+        assert_eq!(
+            check_submodule(&git_command, &dir).unwrap_err().to_string(),
+            format!(
+                "'{}' isn't a submodule, but a repository of its own",
+                dir.display()
+            )
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,1434 @@
+use anyhow::{Error, Result};
+use clap::{ColorChoice, ValueEnum};
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use toml::Table;
+
+use crate::artifacts::expand;
+use crate::changelog::fnv1a;
+use crate::container_tag::ContainerTagStrategy;
+use crate::conventional_commits::Bump;
+use crate::error::VersionError;
+use crate::messages::Messages;
+use crate::models::BranchClass;
+use crate::output::{ExtraFormat, OutputFormat};
+use crate::scheme::Scheme;
+use crate::{
+    BumpStrategy, CountStrategy, Counter, DateSource, Dirty, EmptyBranch, MergeStrategy, OnTag,
+    RequireSignature, UniquePrerelease, VersionSource,
+};
+
+pub const CONFIG_FILE_NAME: &str = ".version-vine.toml";
+/// Path of the user configuration in the user's config directory
+pub const USER_CONFIG_PATH: &str = "versionvine/config.toml";
+
+/// Repository configuration, read from a `.version-vine.toml` file.
+/// Options provided on the command line take precedence over the configuration.
+#[derive(Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Range of version-vine versions the configuration works with, as with `--require-version`
+    pub require_version: Option<VersionReq>,
+    pub app_name: Option<String>,
+    pub fetch: Option<bool>,
+    pub auto_deepen: Option<bool>,
+    /// The same output for the same repository state, as with `--reproducible`
+    pub reproducible: Option<bool>,
+    /// Threads computing the versions of multiple applications, as with `--jobs`
+    pub jobs: Option<usize>,
+    /// Cache the git queries, as with `--cache`
+    pub cache: Option<bool>,
+    pub default_branch: Option<String>,
+    pub require_major_approval: Option<bool>,
+    pub require_signature: Option<RequireSignature>,
+    pub require_signed_tags: Option<bool>,
+    pub verify_semver_order: Option<bool>,
+    pub release_branch_base: Option<bool>,
+    pub on_tag: Option<OnTag>,
+    pub unique_prerelease: Option<UniquePrerelease>,
+    pub container_tag_strategy: Option<ContainerTagStrategy>,
+    pub dirty: Option<Dirty>,
+    pub empty_branch: Option<EmptyBranch>,
+    pub empty_branch_version: Option<Version>,
+    pub scheme: Option<Scheme>,
+    pub bump_strategy: Option<BumpStrategy>,
+    pub counter: Option<Counter>,
+    /// Branch of the `merge-base` counter, as with `--counter-base`
+    pub counter_base: Option<String>,
+    /// Sources of the base version in fallback order, as with `--version-source`
+    pub version_source: Option<Vec<VersionSource>>,
+    /// How pull requests are merged, as with `--merge-strategy`
+    pub merge_strategy: Option<MergeStrategy>,
+    /// Commits the counters count, as with `--count-strategy`
+    pub count_strategy: Option<CountStrategy>,
+    pub date_source: Option<DateSource>,
+    pub path: Option<String>,
+    /// Container image name of the `channel_image`, e.g. `ghcr.io/org/api`. The app name when not
+    /// set.
+    pub image: Option<String>,
+    pub branches: BranchPatterns,
+    pub prerelease: PrereleaseLabels,
+    pub tag: TagConfig,
+    pub calver: CalverConfig,
+    pub build_metadata: BuildMetadataConfig,
+    pub increment: IncrementConfig,
+    pub output: OutputConfig,
+    pub dotnet: DotnetConfig,
+    pub channels: Channels,
+    pub counter_format: CounterFormats,
+    pub container_tags: ContainerTags,
+    pub execution: ExecutionConfig,
+    pub metrics: MetricsConfig,
+    pub release: ReleaseConfig,
+    /// Files `set` stamps the version into besides the `--target`s, e.g. the `ARG` of a Dockerfile
+    pub stamp: Vec<StampConfig>,
+    /// Commands run before and after the version is computed, e.g. to notify or stamp files
+    pub hooks: Vec<HookConfig>,
+    /// Applications of a monorepo, versioned together with `--all-apps`, with their own
+    /// configuration for `--app-name`
+    pub apps: BTreeMap<String, AppConfig>,
+    /// Version ranges accepted by the services consuming this one, by name, checked by `constraints`
+    pub consumers: BTreeMap<String, String>,
+    /// Templates of the human-facing messages of `--explain` and `ui` by key, overriding the
+    /// English ones, e.g. to localize them
+    pub messages: BTreeMap<String, String>,
+    /// The configuration as parsed, merged over the defaults, for the `config_hash` of `--inputs`
+    #[serde(skip)]
+    pub source: Table,
+}
+
+/// Regexes classifying the branches. The release pattern must contain a `version` capture group,
+/// and can use the `{app_prefix}` placeholder for the `<app name>-` prefix.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct BranchPatterns {
+    pub main: String,
+    pub develop: String,
+    pub release: String,
+    /// Hotfix branches, versioned as patch release of the latest tag at their branch point.
+    /// Can use the `{app_prefix}` placeholder.
+    pub hotfix: String,
+    /// Support branches, maintaining an older release line. The `version` capture group is the
+    /// major, or major and minor, version of the line, e.g. `1` of `support/1.x`. Can use the
+    /// `{app_prefix}` placeholder.
+    pub support: String,
+    /// Custom classes, matched in order before the patterns of the branch classes
+    pub custom: Vec<CustomBranch>,
+    /// Pattern the feature branches, matching none of the other patterns, must match for
+    /// `validate`, e.g. `^(feature|fix)/`
+    pub feature: Option<String>,
+    /// Order in which the patterns are matched, the first matching class wins
+    pub priority: Vec<BranchClass>,
+    /// What to do when a branch matches multiple patterns
+    pub ambiguous: AmbiguousBranch,
+}
+
+impl Default for BranchPatterns {
+    fn default() -> Self {
+        Self {
+            main: r"^main|master$".to_string(),
+            develop: r"^develop|dev$".to_string(),
+            release: r"^release\/{app_prefix}(?<version>.+)".to_string(),
+            hotfix: r"^hotfix\/{app_prefix}".to_string(),
+            support: r"^support\/{app_prefix}(?<version>\d+(?:\.\d+)?)\.x$".to_string(),
+            custom: vec![],
+            feature: None,
+            priority: vec![
+                BranchClass::Main,
+                BranchClass::Hotfix,
+                BranchClass::Release,
+                BranchClass::Develop,
+                BranchClass::Support,
+            ],
+            ambiguous: AmbiguousBranch::Warn,
+        }
+    }
+}
+
+/// Custom branch class, e.g. `maintenance` for `^maint/`, versioned as the built-in `class`. The
+/// pattern can use the `{app_prefix}` placeholder, and must contain a `version` capture group for
+/// the release and support classes.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct CustomBranch {
+    pub name: String,
+    pub pattern: String,
+    pub class: BranchClass,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum AmbiguousBranch {
+    /// Warn on stderr and use the highest priority class
+    Warn,
+    /// Fail with an error
+    Error,
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PrereleaseLabels {
+    pub release: String,
+    pub hotfix: String,
+    pub develop: String,
+    pub support: String,
+    pub feature: String,
+    /// Label of pull request builds, followed by the pull request number, e.g. `pr.123`
+    pub pull_request: String,
+    /// Label of `--nightly` builds, followed by the date, e.g. `nightly.20240512`
+    pub nightly: String,
+    /// Labels of the branches matching a pattern, taking precedence over the labels of the branch
+    /// classes. The first matching pattern wins.
+    pub branches: Vec<BranchLabel>,
+}
+
+impl Default for PrereleaseLabels {
+    fn default() -> Self {
+        Self {
+            release: "rc".to_string(),
+            hotfix: "hotfix".to_string(),
+            develop: "beta".to_string(),
+            support: "support".to_string(),
+            feature: "alpha".to_string(),
+            pull_request: "pr".to_string(),
+            nightly: "nightly".to_string(),
+            branches: vec![],
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct BranchLabel {
+    pub pattern: String,
+    pub label: String,
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct TagConfig {
+    /// Format of the version tags, with the `{app_prefix}` and `{version}` placeholders
+    pub format: String,
+    /// Prefix of the version in the tags, e.g. `v` for `v1.2.3` or `myapp-v1.2.3` tags
+    pub prefix: String,
+    /// Regex the tags are parsed with instead of the format, with a `version` capture group and
+    /// the `{app_prefix}` placeholder, e.g. `^{app_prefix}v?(?<version>\d+\.\d+\.\d+.*)$`.
+    /// Tags not matching it are ignored. New tags still follow the format.
+    pub pattern: Option<String>,
+    /// Which reachable tag is the latest tag the version is based on
+    pub selection: TagSelection,
+}
+
+/// Which of the tags reachable from the commit is its latest tag
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum TagSelection {
+    /// The nearest tag, as `git describe` finds it
+    Nearest,
+    /// The tag of the highest version by SemVer precedence, e.g. when a lower tag was created after it on a merged branch
+    Highest,
+}
+
+/// Calendar versions of the `calver` scheme
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CalverConfig {
+    /// Three dot separated segments: `YYYY`, `YY` (years since 2000), `MM`, `WW` (ISO week), `DD`,
+    /// and `MICRO` (or `N`) for the counter. E.g. `YYYY.MM.MICRO` or `YY.WW.N`.
+    pub format: String,
+    pub date: CalverDate,
+    /// How a system clock behind the commit, remote ref or `SOURCE_DATE_EPOCH` dates is handled
+    pub clock_skew: ClockSkew,
+    /// Seconds the system clock may be behind those dates
+    pub max_clock_skew: u64,
+}
+
+impl Default for CalverConfig {
+    fn default() -> Self {
+        Self {
+            format: "YYYY.MM.MICRO".to_string(),
+            date: CalverDate::Commit,
+            clock_skew: ClockSkew::Warn,
+            max_clock_skew: 24 * 60 * 60,
+        }
+    }
+}
+
+/// Handling of a system clock behind the dates of the repository, which would date the versions
+/// in the past
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClockSkew {
+    Ignore,
+    /// Print a warning
+    Warn,
+    /// Fail the calculation
+    Fail,
+}
+
+/// Date the calendar segments are derived from, in UTC
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum CalverDate {
+    /// Date of the `HEAD` commit, following the date source, so rebuilds get the same version
+    Commit,
+    /// Time of the build, `SOURCE_DATE_EPOCH` when set, or the `--as-of` date
+    Build,
+}
+
+impl Default for TagConfig {
+    fn default() -> Self {
+        Self {
+            format: "{app_prefix}{version}".to_string(),
+            prefix: String::new(),
+            pattern: None,
+            selection: TagSelection::Nearest,
+        }
+    }
+}
+
+impl TagConfig {
+    /// The format with the prefix before the version
+    pub fn format_with_prefix(&self) -> String {
+        self.format
+            .replacen("{version}", &format!("{}{{version}}", self.prefix), 1)
+    }
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct BuildMetadataConfig {
+    /// Maximum length of the escaped branch name in the build metadata of feature branches
+    pub max_branch_length: usize,
+    /// Minimum length of the abbreviated commit SHA. Defaults to git's `core.abbrev`.
+    /// Either way git extends the SHA when it would be ambiguous.
+    pub sha_length: Option<usize>,
+    /// Environment variables whose values are appended to the build metadata, e.g. `RUNNER_ARCH`.
+    /// Unset and empty variables are skipped.
+    pub env: Vec<String>,
+    /// How the non-ASCII characters of branch names and environment variables are made valid
+    /// build metadata
+    pub non_ascii: NonAscii,
+    /// Components of the build metadata, in this order. The `env` variables follow them.
+    pub components: Vec<BuildMetadataComponent>,
+    /// Maximum length of the whole build metadata, cut off at the end
+    pub max_length: Option<usize>,
+}
+
+impl Default for BuildMetadataConfig {
+    fn default() -> Self {
+        Self {
+            max_branch_length: 50,
+            sha_length: None,
+            env: vec![],
+            non_ascii: NonAscii::Transliterate,
+            components: vec![BuildMetadataComponent::Branch, BuildMetadataComponent::Sha],
+            max_length: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum BuildMetadataComponent {
+    /// Escaped branch name, on feature branches only
+    Branch,
+    /// Abbreviated commit SHA
+    Sha,
+    /// Date of the commit following the date source, e.g. `20240214`
+    Date,
+    /// The `--build-number`, when provided
+    BuildNumber,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum NonAscii {
+    /// Spell Latin, Greek and Cyrillic letters in ASCII, e.g. `función` as `funcion`. Other
+    /// characters are replaced by `-`.
+    Transliterate,
+    /// Replace them by `-`, e.g. `funci-n`
+    Replace,
+    /// Drop them, e.g. `funcin`
+    Strip,
+}
+
+/// GitVersion style bump hints of the branch names and commit messages, raising the bump of
+/// develop, feature and pull request branches
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct IncrementConfig {
+    pub enabled: bool,
+    /// Commit messages since the latest tag matching it bump the major version
+    pub major: String,
+    /// Commit messages since the latest tag matching it bump the minor version
+    pub minor: String,
+    /// Branches matching it bump the major version, e.g. `feature/major/new-api`
+    pub major_branch: String,
+    /// Branches matching it bump the minor version
+    pub minor_branch: String,
+    /// Custom directives of legacy commit conventions, e.g. `[FEATURE]` bumping the minor version
+    pub messages: Vec<MessageRule>,
+}
+
+/// Commit messages since the latest tag matching the regex get the bump
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct MessageRule {
+    pub bump: Bump,
+    pub regex: String,
+}
+
+impl FromStr for MessageRule {
+    type Err = String;
+
+    /// `<bump>=<regex>`, e.g. `minor=^\[FEATURE\]`
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (bump, regex) = value
+            .split_once('=')
+            .ok_or(format!("'{}' isn't formatted as <BUMP>=<REGEX>", value))?;
+        let bump = match bump {
+            "major" => Bump::Major,
+            "minor" => Bump::Minor,
+            "patch" => Bump::Patch,
+            _ => {
+                return Err(format!(
+                    "Unknown bump '{}', expected major, minor or patch",
+                    bump
+                ))
+            }
+        };
+        Ok(Self {
+            bump,
+            regex: regex.to_string(),
+        })
+    }
+}
+
+impl Default for IncrementConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            major: r"\+semver:\s?(breaking|major)".to_string(),
+            minor: r"\+semver:\s?(feature|minor)".to_string(),
+            major_branch: r"(^|/)major/".to_string(),
+            minor_branch: r"(^|/)minor/".to_string(),
+            messages: vec![],
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    /// Environment variables added as output fields, named after the lowercased variable, e.g.
+    /// `build_flavor` for `BUILD_FLAVOR`. Unset and empty variables are skipped.
+    pub env: Vec<String>,
+    /// Fields the version output is limited to, as with `--fields`
+    pub fields: Vec<String>,
+    /// Version of the output contract, as with `--schema-version`
+    pub schema_version: Option<u32>,
+    /// Add the `builder` block, as with `--builder`
+    pub builder: bool,
+    /// Add the `inputs` block, as with `--inputs`
+    pub inputs: bool,
+    /// Add the `components` array, as with `--components`
+    pub components: bool,
+    /// Template of the `cache_key` field, as with `--cache-key`
+    pub cache_key: Option<String>,
+    /// Additional fields, as with `--extra-formats`
+    pub extra_formats: Vec<ExtraFormat>,
+}
+
+/// Templates of the .NET version fields of `--extra-formats dotnet`, with the placeholders of
+/// `--template`. The assembly and file versions are up to four numbers of at most 65534.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DotnetConfig {
+    pub assembly_version: String,
+    pub file_version: String,
+    pub informational_version: String,
+}
+
+impl Default for DotnetConfig {
+    fn default() -> Self {
+        Self {
+            assembly_version: "{{major}}.{{minor}}.{{patch}}.0".to_string(),
+            file_version: "{{major}}.{{minor}}.{{patch}}.{{counter}}".to_string(),
+            informational_version: "{{app_version}}".to_string(),
+        }
+    }
+}
+
+/// Release channels of the branch classes, e.g. to decide which registry channel to publish to
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Channels {
+    pub main: String,
+    pub release: String,
+    pub hotfix: String,
+    pub develop: String,
+    pub support: String,
+    pub feature: String,
+    pub pull_request: String,
+}
+
+impl Default for Channels {
+    fn default() -> Self {
+        Self {
+            main: "stable".to_string(),
+            release: "rc".to_string(),
+            hotfix: "rc".to_string(),
+            develop: "beta".to_string(),
+            support: "rc".to_string(),
+            feature: "dev".to_string(),
+            pull_request: "dev".to_string(),
+        }
+    }
+}
+
+impl Channels {
+    pub fn channel(&self, class: BranchClass) -> &str {
+        match class {
+            BranchClass::Main => &self.main,
+            BranchClass::Release => &self.release,
+            BranchClass::Hotfix => &self.hotfix,
+            BranchClass::Develop => &self.develop,
+            BranchClass::Support => &self.support,
+            BranchClass::Feature => &self.feature,
+            BranchClass::PullRequest => &self.pull_request,
+        }
+    }
+}
+
+/// Format of the pre-release counter, e.g. `beta.42`
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum CounterFormat {
+    /// The counter as identifier of its own, e.g. `beta.42`
+    #[default]
+    Number,
+    /// Zero-padded and joined to the label, e.g. `beta00042`, like the padded versions of
+    /// GitVersion. SemVer doesn't allow leading zeros in numeric identifiers, but padded counters
+    /// also sort as text, e.g. in NuGet v2 feeds.
+    Padded,
+    /// Prefixed by the date of the commit following the date source, e.g. `beta.20240131.7`
+    Date,
+    /// Suffixed by the attempt of the CI run, e.g. `beta.7.2` for the first rerun, so reruns don't
+    /// collide with the artifacts of the failed run. The attempt is 1 outside of GitHub Actions and
+    /// Azure Pipelines.
+    RunAttempt,
+}
+
+/// Formats of the pre-release counters of the branch classes. Main branches have no counter.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CounterFormats {
+    pub release: CounterFormat,
+    pub hotfix: CounterFormat,
+    pub develop: CounterFormat,
+    pub support: CounterFormat,
+    pub feature: CounterFormat,
+    pub pull_request: CounterFormat,
+    /// Digits of the `padded` format
+    pub padding: usize,
+}
+
+impl Default for CounterFormats {
+    fn default() -> Self {
+        Self {
+            release: CounterFormat::Number,
+            hotfix: CounterFormat::Number,
+            develop: CounterFormat::Number,
+            support: CounterFormat::Number,
+            feature: CounterFormat::Number,
+            pull_request: CounterFormat::Number,
+            padding: 5,
+        }
+    }
+}
+
+impl CounterFormats {
+    pub fn format(&self, class: BranchClass) -> CounterFormat {
+        match class {
+            BranchClass::Main => CounterFormat::Number,
+            BranchClass::Release => self.release,
+            BranchClass::Hotfix => self.hotfix,
+            BranchClass::Develop => self.develop,
+            BranchClass::Support => self.support,
+            BranchClass::Feature => self.feature,
+            BranchClass::PullRequest => self.pull_request,
+        }
+    }
+}
+
+/// Rolling container tags of the branch classes, which `container_tags` lists after the full
+/// `container_tag`. `{major}`, `{minor}` and `{patch}` are replaced by the parts of the version,
+/// and `{channel}` by the release channel.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ContainerTags {
+    pub main: Vec<String>,
+    pub release: Vec<String>,
+    pub hotfix: Vec<String>,
+    pub develop: Vec<String>,
+    pub support: Vec<String>,
+    pub feature: Vec<String>,
+    pub pull_request: Vec<String>,
+}
+
+impl Default for ContainerTags {
+    fn default() -> Self {
+        Self {
+            main: [
+                "{major}.{minor}.{patch}",
+                "{major}.{minor}",
+                "{major}",
+                "latest",
+            ]
+            .map(str::to_string)
+            .to_vec(),
+            release: vec![],
+            hotfix: vec![],
+            develop: vec!["{channel}".to_string()],
+            support: vec![],
+            feature: vec![],
+            pull_request: vec![],
+        }
+    }
+}
+
+impl ContainerTags {
+    pub fn rules(&self, class: BranchClass) -> &[String] {
+        match class {
+            BranchClass::Main => &self.main,
+            BranchClass::Release => &self.release,
+            BranchClass::Hotfix => &self.hotfix,
+            BranchClass::Develop => &self.develop,
+            BranchClass::Support => &self.support,
+            BranchClass::Feature => &self.feature,
+            BranchClass::PullRequest => &self.pull_request,
+        }
+    }
+}
+
+/// Configuration of an application. Unknown keys are rejected by `Config::for_app`, which the
+/// configuration is checked with when it's parsed.
+#[derive(Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct AppConfig {
+    /// `--path` of the application, e.g. `services/api`
+    pub path: Option<String>,
+    /// Keys of the configuration overridden for the application, e.g. `[apps.api.tag]`
+    #[serde(flatten)]
+    pub overrides: Table,
+}
+
+/// The crates of the Cargo workspace of the `manifest` as applications, by package name, with the
+/// directory of the crate as path. `members` globs are expanded, `exclude`d directories skipped,
+/// and a `[package]` of the workspace manifest itself is included.
+pub fn cargo_workspace_apps(manifest: &Path) -> Result<BTreeMap<String, AppConfig>> {
+    let read = |path: &Path| -> Result<Table> {
+        let content = fs::read_to_string(path)
+            .map_err(|err| Error::msg(format!("Cannot read '{}': {}", path.display(), err)))?;
+        content
+            .parse::<Table>()
+            .map_err(|err| Error::msg(format!("Invalid manifest '{}': {}", path.display(), err)))
+    };
+    let strings = |table: &Table, key: &str| -> Vec<String> {
+        table
+            .get(key)
+            .and_then(|value| value.as_array())
+            .map(|values| {
+                values
+                    .iter()
+                    .filter_map(|value| value.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let root = read(manifest)?;
+    let workspace = root
+        .get("workspace")
+        .and_then(|workspace| workspace.as_table())
+        .ok_or(Error::msg(format!(
+            "'{}' doesn't define a [workspace]",
+            manifest.display()
+        )))?;
+    let dir = manifest.parent().unwrap_or(Path::new(""));
+    let excluded: Vec<PathBuf> = strings(workspace, "exclude")
+        .iter()
+        .map(|path| Path::new(path).components().collect())
+        .collect();
+
+    let mut members: Vec<PathBuf> = vec![];
+    if root.contains_key("package") {
+        members.push(PathBuf::new());
+    }
+    for member in strings(workspace, "members") {
+        let mut components: Vec<String> = Path::new(&member)
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().to_string())
+            .collect();
+        components.push("Cargo.toml".to_string());
+        let mut matches = vec![];
+        expand(dir, &PathBuf::new(), &components, &mut matches)?;
+        if matches.is_empty() {
+            return Err(Error::msg(format!(
+                "No crates match the workspace member '{}'",
+                member
+            )));
+        }
+        members.extend(
+            matches
+                .into_iter()
+                .filter_map(|path| path.parent().map(Path::to_path_buf))
+                .filter(|member| !excluded.contains(member)),
+        );
+    }
+
+    let mut apps = BTreeMap::new();
+    for member in members {
+        let member_manifest = dir.join(&member).join("Cargo.toml");
+        let name = read(&member_manifest)?
+            .get("package")
+            .and_then(|package| package.get("name"))
+            .and_then(|name| name.as_str())
+            .map(str::to_string)
+            .ok_or(Error::msg(format!(
+                "'{}' doesn't define a package name",
+                member_manifest.display()
+            )))?;
+        let path = match member.as_os_str().is_empty() {
+            true => dir.to_path_buf(),
+            false => dir.join(&member),
+        };
+        let path = match path.to_string_lossy().replace('\\', "/").as_str() {
+            "" => ".".to_string(),
+            path => path.to_string(),
+        };
+        apps.insert(
+            name,
+            AppConfig {
+                path: Some(path),
+                ..Default::default()
+            },
+        );
+    }
+    if apps.is_empty() {
+        return Err(Error::msg(format!(
+            "The workspace of '{}' doesn't have any crates",
+            manifest.display()
+        )));
+    }
+    Ok(apps)
+}
+
+/// A file `set` writes the version to, located by either a regex, an anchor or the Docker Compose
+/// services
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct StampConfig {
+    pub file: PathBuf,
+    /// Regex matching the version, as `set --regex`
+    pub regex: Option<String>,
+    /// Text marking the lines of which the version is replaced, as `set --anchor`
+    pub anchor: Option<String>,
+    /// Services of a Docker Compose file whose image tag is replaced, as `set --service`
+    #[serde(default)]
+    pub services: Vec<String>,
+    /// Template of the value written, as `set --value`, e.g. `{{container_tag}}`
+    pub value: Option<String>,
+}
+
+/// A command run before or after the version is computed, see `hooks::run_hooks`
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct HookConfig {
+    pub event: HookEvent,
+    /// Program and arguments. The arguments of `post-compute` hooks are templates of the output
+    /// fields, as `--template`.
+    pub command: Vec<String>,
+    /// Seconds after which the hook is killed and fails
+    #[serde(default = "default_hook_timeout")]
+    pub timeout_seconds: u64,
+    #[serde(default)]
+    pub on_failure: HookFailure,
+}
+
+fn default_hook_timeout() -> u64 {
+    60
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum HookEvent {
+    /// Before the version is computed
+    PreCompute,
+    /// After the version is computed, with the output fields
+    PostCompute,
+}
+
+/// How a failing hook, exiting with a non-zero code or timing out, is handled
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum HookFailure {
+    /// Fail the run
+    #[default]
+    Fail,
+    /// Print a warning and continue
+    Warn,
+    /// Continue silently
+    Ignore,
+}
+
+/// Endpoints the release events of `tag` and `promote` are pushed to, as deployment markers of
+/// the dashboards
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfig {
+    /// Prometheus Pushgateway, e.g. `http://pushgateway:9091`
+    pub pushgateway_url: Option<String>,
+    /// OTLP/HTTP endpoint, e.g. `http://otel-collector:4318`, posted to `/v1/metrics`
+    pub otlp_url: Option<String>,
+    /// `job` grouping label of the Pushgateway
+    pub job: String,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            pushgateway_url: None,
+            otlp_url: None,
+            job: "version-vine".to_string(),
+        }
+    }
+}
+
+/// Forge release of the `release` command
+#[derive(Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReleaseConfig {
+    /// Globs of the artifact files uploaded to the release, relative to the working directory,
+    /// e.g. `dist/*.tar.gz`
+    pub assets: Vec<String>,
+}
+
+/// Policy for the commands version-vine runs, see `sandbox::execute`. The policy is trusted from
+/// the user configuration and the command line only: the repository configuration can narrow it,
+/// but not widen it, so untrusted changes can't allow themselves to run commands.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExecutionConfig {
+    pub allowed_commands: Vec<String>,
+    /// Run commands with only the `env_allowlist` variables, e.g. to keep CI credentials from them.
+    /// The `[[hooks]]` are scrubbed unless it's `false`, other commands only when it's `true`.
+    pub scrub_env: Option<bool>,
+    /// Names of the variables kept when scrubbing, or prefixes with a trailing `*`
+    pub env_allowlist: Vec<String>,
+    pub timeout_seconds: Option<u64>,
+    /// Refuse network access, as with `--offline`
+    pub offline: bool,
+}
+
+impl Default for ExecutionConfig {
+    fn default() -> Self {
+        Self {
+            allowed_commands: vec!["git".to_string()],
+            scrub_env: None,
+            env_allowlist: ["PATH", "HOME", "LANG", "LC_*", "TMPDIR", "GIT_*"]
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            timeout_seconds: None,
+            offline: false,
+        }
+    }
+}
+
+impl ExecutionConfig {
+    /// The policy narrowed by the one of the repository configuration: only the commands and
+    /// environment variables both allow, the shorter timeout, and scrubbing the environment or
+    /// refusing network access when either does
+    pub fn narrow(&self, repository: &ExecutionConfig) -> Self {
+        let timeout_seconds = match (self.timeout_seconds, repository.timeout_seconds) {
+            (Some(trusted), Some(repository)) => Some(trusted.min(repository)),
+            (trusted, repository) => trusted.or(repository),
+        };
+        Self {
+            allowed_commands: self
+                .allowed_commands
+                .iter()
+                .filter(|command| repository.allowed_commands.contains(command))
+                .cloned()
+                .collect(),
+            // Only the trusted policy can opt out of scrubbing the hooks
+            scrub_env: match (self.scrub_env, repository.scrub_env) {
+                (Some(true), _) | (_, Some(true)) => Some(true),
+                (trusted, _) => trusted,
+            },
+            env_allowlist: self
+                .env_allowlist
+                .iter()
+                .filter(|name| repository.env_allowlist.contains(name))
+                .cloned()
+                .collect(),
+            timeout_seconds,
+            offline: self.offline || repository.offline,
+        }
+    }
+}
+
+impl Config {
+    /// Loads the config file on top of the `defaults` of the user configuration
+    pub fn load(path: &Path, defaults: &Table) -> Result<Self, Error> {
+        let content = fs::read_to_string(path).map_err(|err| {
+            Error::msg(format!(
+                "Cannot read config file '{}': {}",
+                path.display(),
+                err
+            ))
+        })?;
+        Self::parse_with_defaults(&content, defaults).map_err(|err| {
+            VersionError::ParseError(format!("Invalid config file '{}': {}", path.display(), err))
+                .into()
+        })
+    }
+
+    pub fn parse(content: &str) -> Result<Self, Error> {
+        Self::parse_with_defaults(content, &Table::new())
+    }
+
+    /// Parses the configuration merged over the `defaults`: tables are merged key by key, other
+    /// values (including arrays) replace the default.
+    pub fn parse_with_defaults(content: &str, defaults: &Table) -> Result<Self, Error> {
+        let mut table = defaults.clone();
+        merge(&mut table, toml::from_str(content)?);
+        let mut config: Self = table.clone().try_into()?;
+        // The execution policy of the user configuration, which the repository can only narrow
+        let trusted: ExecutionConfig = match defaults.get("execution") {
+            Some(execution) => execution.clone().try_into()?,
+            None => ExecutionConfig::default(),
+        };
+        config.execution = trusted.narrow(&config.execution);
+        config.source = table;
+        Messages::new(&config.messages)?;
+        for app in config.apps.keys() {
+            config.for_app(app)?;
+        }
+        Ok(config)
+    }
+
+    /// The configuration of an application: the keys of its `[apps.<name>]` table merged over the
+    /// global ones, as the global configuration is merged over the defaults. The configuration
+    /// itself for other applications.
+    pub fn for_app(&self, app: &str) -> Result<Self, Error> {
+        let Some(app_config) = self.apps.get(app) else {
+            return Ok(self.clone());
+        };
+        let mut config = match app_config.overrides.is_empty() {
+            // Also keeps configurations built in code, which have no source to merge into
+            true => self.clone(),
+            false => {
+                let invalid = |message: String| {
+                    Error::from(VersionError::ParseError(format!(
+                        "Invalid [apps.{}] configuration: {}",
+                        app, message
+                    )))
+                };
+                if app_config.overrides.contains_key("apps") {
+                    return Err(invalid("applications can't be nested".to_string()));
+                }
+                let mut table = self.source.clone();
+                merge(&mut table, app_config.overrides.clone());
+                let mut config: Self = table
+                    .clone()
+                    .try_into()
+                    .map_err(|err: toml::de::Error| invalid(err.message().to_string()))?;
+                Messages::new(&config.messages).map_err(|err| invalid(err.to_string()))?;
+                // Applications can narrow the policy further, but not widen it again
+                config.execution = match app_config.overrides.contains_key("execution") {
+                    true => self.execution.narrow(&config.execution),
+                    false => self.execution.clone(),
+                };
+                config.source = table;
+                config.apps = self.apps.clone();
+                config
+            }
+        };
+        config.path = app_config.path.clone().or(config.path);
+        Ok(config)
+    }
+
+    /// Hash of the configuration as parsed, the same across platforms. The `[messages]` don't
+    /// change the version, so they're left out.
+    pub fn hash(&self) -> String {
+        let mut source = self.source.clone();
+        source.remove("messages");
+        format!("{:016x}", fnv1a(source.to_string().as_bytes()))
+    }
+
+    /// Finds the config file by walking up from `start_dir`, up to and including the repository
+    /// root (the directory containing `.git`, a file in submodules and linked worktrees).
+    pub fn discover(start_dir: &Path) -> Option<PathBuf> {
+        for dir in start_dir.ancestors() {
+            let path = dir.join(CONFIG_FILE_NAME);
+            if path.is_file() {
+                return Some(path);
+            }
+            if dir.join(".git").exists() {
+                break;
+            }
+        }
+        None
+    }
+}
+
+fn merge(base: &mut Table, overrides: Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overrides)) => {
+                merge(base, overrides)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Colors of the help and error messages
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Color {
+    /// When writing to a terminal
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl From<Color> for ColorChoice {
+    fn from(color: Color) -> Self {
+        match color {
+            Color::Auto => ColorChoice::Auto,
+            Color::Always => ColorChoice::Always,
+            Color::Never => ColorChoice::Never,
+        }
+    }
+}
+
+/// Settings of the user configuration which only apply to the user, not to repositories
+#[derive(Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct UserSettings {
+    /// Output format when `--output-format` isn't provided
+    pub output_format: Option<OutputFormat>,
+    /// Git executable of the `cli` backend, e.g. `/opt/homebrew/bin/git`. Only configurable per
+    /// user, so repositories can't make version-vine run other programs.
+    pub git_path: Option<String>,
+    pub color: Color,
+}
+
+const USER_SETTINGS: [&str; 3] = ["output_format", "git_path", "color"];
+
+/// User level configuration, read from `~/.config/versionvine/config.toml`: the user settings,
+/// and defaults of the repository configuration for all repositories. The repository
+/// configuration and the command line options take precedence over the defaults.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UserConfig {
+    pub settings: UserSettings,
+    pub defaults: Table,
+}
+
+impl UserConfig {
+    /// `$XDG_CONFIG_HOME/versionvine/config.toml`, falling back to `~/.config`
+    pub fn path(env: impl Fn(&str) -> Option<String>) -> Option<PathBuf> {
+        let config_dir = match env("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(env("HOME").filter(|dir| !dir.is_empty())?).join(".config"),
+        };
+        Some(config_dir.join(USER_CONFIG_PATH))
+    }
+
+    /// Loads the user configuration, which is optional
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path).map_err(|err| {
+            Error::msg(format!(
+                "Cannot read user config file '{}': {}",
+                path.display(),
+                err
+            ))
+        })?;
+        Self::parse(&content).map_err(|err| {
+            VersionError::ParseError(format!(
+                "Invalid user config file '{}': {}",
+                path.display(),
+                err
+            ))
+            .into()
+        })
+    }
+
+    pub fn parse(content: &str) -> Result<Self, Error> {
+        let mut defaults: Table = toml::from_str(content)?;
+        let settings: Table = USER_SETTINGS
+            .iter()
+            .filter_map(|key| Some((key.to_string(), defaults.remove(*key)?)))
+            .collect();
+        let user_config = Self {
+            settings: settings.try_into()?,
+            defaults,
+        };
+        // Fail on invalid defaults, instead of in each repository
+        Config::parse_with_defaults("", &user_config.defaults)?;
+        Ok(user_config)
+    }
+}
+
+/// Fails when the running `version` of version-vine doesn't satisfy the `--require-version`, so
+/// pipelines fail early on incompatible tooling rather than computing a different version
+pub fn check_required_version(required: &VersionReq, version: &str) -> Result<()> {
+    match required.matches(&Version::parse(version)?) {
+        true => Ok(()),
+        false => Err(Error::msg(format!(
+            "version-vine {} doesn't satisfy the required version '{}'. Install a matching version, e.g. with `version-vine self-update --version <tag>`.",
+            version, required
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_defaults() {
+        let config = Config::parse("").unwrap();
+
+        assert_eq!(config, Config::default());
+        assert_eq!(config.prerelease.develop, "beta");
+        assert_eq!(config.build_metadata.max_branch_length, 50);
+    }
+
+    #[test]
+    fn test_parse() {
+        let config = Config::parse(
+            r#"
+            app_name = "myapp"
+            default_branch = "trunk"
+            empty_branch = "error"
+            empty_branch_version = "0.1.0"
+
+            [branches]
+            main = "^trunk$"
+
+            [prerelease]
+            develop = "nightly"
+
+            [tag]
+            format = "{app_prefix}v{version}"
+            pattern = '^{app_prefix}v?(?<version>.+)$'
+
+            [build_metadata]
+            env = ["RUNNER_ARCH"]
+            components = ["sha", "build-number"]
+            max_length = 40
+
+            [output]
+            env = ["BUILD_FLAVOR"]
+
+            [channels]
+            develop = "nightly"
+
+            [counter_format]
+            feature = "date"
+            padding = 3
+
+            [release]
+            assets = ["dist/*.tar.gz"]
+
+            [apps.api]
+            path = "services/api"
+
+            [apps.web]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.app_name, Some("myapp".to_string()));
+        assert_eq!(config.empty_branch, Some(EmptyBranch::Error));
+        assert_eq!(config.empty_branch_version, Some(Version::new(0, 1, 0)));
+        assert_eq!(config.branches.main, "^trunk$");
+        assert_eq!(config.branches.develop, BranchPatterns::default().develop);
+        assert_eq!(config.prerelease.develop, "nightly");
+        assert_eq!(config.prerelease.release, "rc");
+        assert_eq!(config.tag.format, "{app_prefix}v{version}");
+        assert_eq!(config.tag.format_with_prefix(), "{app_prefix}v{version}");
+        assert_eq!(
+            config.tag.pattern,
+            Some("^{app_prefix}v?(?<version>.+)$".to_string())
+        );
+        assert_eq!(config.build_metadata.env, vec!["RUNNER_ARCH"]);
+        assert_eq!(
+            config.build_metadata.components,
+            vec![
+                BuildMetadataComponent::Sha,
+                BuildMetadataComponent::BuildNumber
+            ]
+        );
+        assert_eq!(config.build_metadata.max_length, Some(40));
+        assert_eq!(config.output.env, vec!["BUILD_FLAVOR"]);
+        assert_eq!(config.channels.channel(BranchClass::Develop), "nightly");
+        assert_eq!(config.channels.channel(BranchClass::Main), "stable");
+        assert_eq!(
+            config.counter_format.format(BranchClass::Feature),
+            CounterFormat::Date
+        );
+        assert_eq!(config.counter_format.padding, 3);
+        assert_eq!(config.release.assets, vec!["dist/*.tar.gz"]);
+        assert_eq!(config.apps["api"].path, Some("services/api".to_string()));
+        assert_eq!(config.apps["web"], AppConfig::default());
+    }
+
+    #[test]
+    fn test_config_for_app() {
+        let config = Config::parse(
+            r#"
+            bump_strategy = "conventional"
+            [tag]
+            prefix = "v"
+            [apps.api]
+            path = "services/api"
+            image = "ghcr.io/org/api"
+            bump_strategy = "patch"
+            [apps.api.tag]
+            prefix = "api-v"
+            [apps.api.prerelease]
+            develop = "next"
+            [apps.web]
+            "#,
+        )
+        .unwrap();
+
+        let api = config.for_app("api").unwrap();
+        assert_eq!(api.path, Some("services/api".to_string()));
+        assert_eq!(api.image, Some("ghcr.io/org/api".to_string()));
+        assert_eq!(api.bump_strategy, Some(BumpStrategy::Patch));
+        assert_eq!(api.tag.prefix, "api-v");
+        assert_eq!(api.prerelease.develop, "next");
+        // Tables are merged key by key
+        assert_eq!(api.prerelease.feature, "alpha");
+        assert_eq!(api.apps, config.apps);
+
+        let web = config.for_app("web").unwrap();
+        assert_eq!(web.bump_strategy, Some(BumpStrategy::Conventional));
+        assert_eq!(web.tag.prefix, "v");
+        assert_eq!(web.image, None);
+        assert_eq!(config.for_app("other").unwrap(), config);
+
+        let err = Config::parse(
+            "[apps.api]
+bump = \"patch\"",
+        )
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("Invalid [apps.api] configuration: unknown field `bump`"));
+        let err = Config::parse("[apps.api.apps.web]").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid [apps.api] configuration: applications can't be nested"
+        );
+    }
+
+    #[test]
+    fn test_config_hash() {
+        let config = Config::parse("app_name = \"myapp\"").unwrap();
+
+        assert_eq!(
+            config.hash(),
+            Config::parse("app_name = 'myapp'").unwrap().hash()
+        );
+        assert_ne!(config.hash(), Config::parse("").unwrap().hash());
+        assert_eq!(Config::parse("").unwrap().hash(), Config::default().hash());
+        // The messages don't change the version
+        assert_eq!(
+            Config::parse("[messages]\n\"ui.branch\" = \"Zweig\"")
+                .unwrap()
+                .hash(),
+            Config::default().hash()
+        );
+        assert!(Config::parse("[messages]\n\"ui.branches\" = \"Zweige\"").is_err());
+    }
+
+    #[test]
+    fn test_parse_unknown_field() {
+        let result = Config::parse("unknown = true");
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_user_config() {
+        let user_config = UserConfig::parse(
+            r#"
+            output_format = "plain"
+            git_path = "/opt/homebrew/bin/git"
+            bump_strategy = "conventional"
+
+            [prerelease]
+            develop = "nightly"
+            feature = "preview"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            user_config.settings,
+            UserSettings {
+                output_format: Some(OutputFormat::Plain),
+                git_path: Some("/opt/homebrew/bin/git".to_string()),
+                color: Color::Auto,
+            }
+        );
+        let config = Config::parse_with_defaults(
+            "[prerelease]\ndevelop = 'beta'\n[tag]\nformat = 'v{version}'",
+            &user_config.defaults,
+        )
+        .unwrap();
+        assert_eq!(config.bump_strategy, Some(BumpStrategy::Conventional));
+        assert_eq!(config.prerelease.develop, "beta");
+        assert_eq!(config.prerelease.feature, "preview");
+        assert_eq!(config.tag.format, "v{version}");
+    }
+
+    #[test]
+    fn test_execution_policy_narrowed_by_repository() {
+        let user_config = UserConfig::parse(
+            "[execution]\nallowed_commands = ['git', 'sh', 'gpg']\ntimeout_seconds = 60",
+        )
+        .unwrap();
+
+        let config = Config::parse_with_defaults(
+            "[execution]\nallowed_commands = ['git', 'sh', 'curl']\nscrub_env = true\ntimeout_seconds = 600\n[apps.api.execution]\nallowed_commands = ['git']",
+            &user_config.defaults,
+        )
+        .unwrap();
+        assert_eq!(config.execution.allowed_commands, vec!["git", "sh"]);
+        assert_eq!(config.execution.scrub_env, Some(true));
+        assert_eq!(config.execution.timeout_seconds, Some(60));
+        assert_eq!(
+            config.for_app("api").unwrap().execution.allowed_commands,
+            vec!["git"]
+        );
+
+        let config = Config::parse("[execution]\nallowed_commands = ['git', 'sh']").unwrap();
+        assert_eq!(config.execution.allowed_commands, vec!["git"]);
+        let config = Config::parse_with_defaults("", &user_config.defaults).unwrap();
+        assert_eq!(config.execution.allowed_commands, vec!["git", "sh", "gpg"]);
+    }
+
+    #[test]
+    fn test_parse_user_config_invalid_defaults() {
+        let result = UserConfig::parse("[prerelease]\nunknown = 'x'");
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_user_config_path() {
+        let env = |xdg: &'static str| {
+            move |name: &str| match name {
+                "XDG_CONFIG_HOME" => Some(xdg.to_string()),
+                "HOME" => Some("/home/dev".to_string()),
+                _ => None,
+            }
+        };
+
+        assert_eq!(
+            UserConfig::path(env("/xdg")),
+            Some(PathBuf::from("/xdg/versionvine/config.toml"))
+        );
+        assert_eq!(
+            UserConfig::path(env("")),
+            Some(PathBuf::from("/home/dev/.config/versionvine/config.toml"))
+        );
+        assert_eq!(UserConfig::path(|_| None), None);
+    }
+
+    #[test]
+    fn test_discover() {
+        let root = std::env::temp_dir().join("version-vine-test-discover");
+        let nested = root.join("services").join("myapp");
+        fs::create_dir_all(&nested).unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::write(root.join(CONFIG_FILE_NAME), "").unwrap();
+
+        assert_eq!(Config::discover(&nested), Some(root.join(CONFIG_FILE_NAME)));
+
+        // Submodules and linked worktrees have a `.git` file, and their own configuration
+        let submodule = root.join("libs").join("core");
+        fs::create_dir_all(&submodule).unwrap();
+        fs::write(submodule.join(".git"), "gitdir: ../../.git/modules/core").unwrap();
+        assert_eq!(Config::discover(&submodule), None);
+    }
+
+    #[test]
+    fn test_cargo_workspace_apps() {
+        let root = std::env::temp_dir().join("version-vine-test-cargo-workspace");
+        let _ = fs::remove_dir_all(&root);
+        for (dir, name) in [
+            ("", "cli"),
+            ("crates/core", "vine-core"),
+            ("crates/macros", "vine-macros"),
+            ("crates/scratch", "scratch"),
+        ] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+            fs::write(
+                root.join(dir).join("Cargo.toml"),
+                format!("[package]\nname = \"{}\"\n", name),
+            )
+            .unwrap();
+        }
+        let manifest = root.join("Cargo.toml");
+        let content = fs::read_to_string(&manifest).unwrap();
+        fs::write(
+            &manifest,
+            content + "\n[workspace]\nmembers = [\"crates/*\"]\nexclude = [\"crates/scratch\"]\n",
+        )
+        .unwrap();
+
+        let apps = cargo_workspace_apps(&manifest).unwrap();
+
+        let path = |dir: &str| Some(root.join(dir).to_string_lossy().to_string());
+        assert_eq!(
+            apps.keys().collect::<Vec<_>>(),
+            ["cli", "vine-core", "vine-macros"]
+        );
+        assert_eq!(apps["cli"].path, Some(root.to_string_lossy().to_string()));
+        assert_eq!(apps["vine-core"].path, path("crates/core"));
+        assert!(cargo_workspace_apps(&root.join("crates/core/Cargo.toml")).is_err());
+    }
+
+    #[test]
+    fn test_check_required_version() {
+        let config = Config::parse("require_version = '>=0.5'").unwrap();
+        let required = config.require_version.unwrap();
+
+        assert!(check_required_version(&required, "0.5.0").is_ok());
+        assert!(check_required_version(&required, "1.2.0").is_ok());
+        assert_eq!(
+            check_required_version(&required, "0.4.9").unwrap_err().to_string(),
+            "version-vine 0.4.9 doesn't satisfy the required version '>=0.5'. Install a matching version, e.g. with `version-vine self-update --version <tag>`."
+        );
+        assert!(Config::parse("require_version = 'latest'").is_err());
+    }
+}
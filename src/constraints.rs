@@ -0,0 +1,148 @@
+use anyhow::{Error, Result};
+use semver::Version;
+use serde::Serialize;
+use serde_json::Value;
+
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::image_policy::{parse_range, range_matches};
+use crate::{VersionInfo, VersionOptions};
+
+/// Version range a consumer service accepts, checked against the computed version
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Constraint {
+    pub consumer: String,
+    pub range: String,
+    pub satisfied: bool,
+}
+
+/// Consumer services and their accepted version ranges, from a JSON file or URL mapping the
+/// consumers to their range, or else the `[consumers]` table of the configuration
+pub fn load_consumers(
+    options: &VersionOptions,
+    source: &Option<String>,
+) -> Result<BTreeMap<String, String>> {
+    let consumers = match source {
+        None => return Ok(options.config.consumers.clone()),
+        Some(url) if url.starts_with("http://") || url.starts_with("https://") => {
+            fetch(url, options.config.execution.offline)?
+        }
+        Some(path) => {
+            let content = fs::read_to_string(path)
+                .map_err(|err| Error::msg(format!("Cannot read '{}': {}", path, err)))?;
+            serde_json::from_str(&content)
+                .map_err(|err| Error::msg(format!("Cannot parse '{}': {}", path, err)))?
+        }
+    };
+    parse_consumers(&consumers)
+}
+
+#[cfg(feature = "forge")]
+fn fetch(url: &str, offline: bool) -> Result<Value> {
+    use crate::forge::{HttpClient, HttpClientTrait, HttpRequest};
+
+    HttpClient { offline }.send(&HttpRequest {
+        method: "GET".to_string(),
+        url: url.to_string(),
+        headers: vec![
+            ("Accept".to_string(), "application/json".to_string()),
+            ("User-Agent".to_string(), "version-vine".to_string()),
+        ],
+        body: None,
+    })
+}
+
+#[cfg(not(feature = "forge"))]
+fn fetch(url: &str, _offline: bool) -> Result<Value> {
+    Err(Error::msg(format!(
+        "Cannot fetch '{}': version-vine is built without the 'forge' feature",
+        url
+    )))
+}
+
+/// Reads a `{"consumer": "range"}` JSON object
+fn parse_consumers(consumers: &Value) -> Result<BTreeMap<String, String>> {
+    let invalid = || Error::msg("The consumers must be a JSON object of version ranges by name");
+    consumers
+        .as_object()
+        .ok_or_else(invalid)?
+        .iter()
+        .map(|(consumer, range)| {
+            let range = range.as_str().ok_or_else(invalid)?;
+            Ok((consumer.clone(), range.to_string()))
+        })
+        .collect()
+}
+
+/// Checks the release the computed version leads to, i.e. without pre-release and build metadata,
+/// against the ranges of the consumers. A consumer accepting `^1.2` is broken by `2.0.0-beta.3`
+/// of develop, before it's deployed.
+pub fn check_constraints(
+    version_info: &VersionInfo,
+    consumers: &BTreeMap<String, String>,
+) -> Result<Vec<Constraint>> {
+    if consumers.is_empty() {
+        return Err(Error::msg(
+            "No consumers found. Add a [consumers] table to the configuration or provide '--consumers'.",
+        ));
+    }
+    let version = &version_info.version;
+    let release = Version::new(version.major, version.minor, version.patch);
+
+    consumers
+        .iter()
+        .map(|(consumer, range)| {
+            let reqs = parse_range(range).map_err(|err| {
+                Error::msg(format!(
+                    "Invalid version range '{}' of consumer '{}': {}",
+                    range, consumer, err
+                ))
+            })?;
+            Ok(Constraint {
+                consumer: consumer.clone(),
+                range: range.clone(),
+                satisfied: reqs.iter().any(|req| range_matches(req, &release)),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use serde_json::json;
+
+    use super::*;
+
+    fn version_info(version: &str) -> VersionInfo {
+        crate::version_calculator::version_info("develop", version)
+    }
+
+    #[test]
+    fn test_check_constraints() {
+        let consumers = parse_consumers(&json!({
+            "billing": "^1.2",
+            "checkout": ">=1.0.0 <3.0.0",
+            "legacy": "1.x || 2.0.x",
+        }))
+        .unwrap();
+
+        let constraints =
+            check_constraints(&version_info("2.1.0-beta.3+1234567"), &consumers).unwrap();
+
+        assert_eq!(
+            constraints
+                .iter()
+                .map(|constraint| (constraint.consumer.as_str(), constraint.satisfied))
+                .collect::<Vec<_>>(),
+            vec![("billing", false), ("checkout", true), ("legacy", false)]
+        );
+    }
+
+    #[test]
+    fn test_check_constraints_invalid() {
+        assert!(parse_consumers(&json!({"billing": 1})).is_err());
+        assert!(check_constraints(&version_info("1.0.0"), &BTreeMap::new()).is_err());
+    }
+}
@@ -0,0 +1,183 @@
+use clap::ValueEnum;
+use semver::{BuildMetadata, Version};
+use serde::Deserialize;
+
+use crate::changelog::fnv1a;
+
+/// Maximum length of an OCI image tag
+pub const MAX_LENGTH: usize = 128;
+
+/// Hex digits of the hash appended to truncated tags
+const HASH_LENGTH: usize = 7;
+
+/// How the version is made a valid OCI image tag, `[A-Za-z0-9_][A-Za-z0-9_.-]{0,127}`
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ContainerTagStrategy {
+    /// Replace the `+` of the build metadata with `.`, and cut tags over 128 characters, ending them with a hash of the full tag, e.g. `1.3.0-alpha.4.feature-very-long-...-4f9a2c1`
+    TruncateHash,
+    /// Leave out the build metadata, e.g. `1.3.0-alpha.4`, as the pre-release already tells the builds apart. Tags still over 128 characters are cut like with `truncate-hash`.
+    DropBuildMetadata,
+    /// Lowercase the tag and replace the `+` with `-`, e.g. `1.3.0-alpha.4-feature-login`, cut at 128 characters
+    Slugify,
+}
+
+/// The container tag of the version following the strategy
+pub fn container_tag(version: &Version, strategy: ContainerTagStrategy) -> String {
+    match strategy {
+        ContainerTagStrategy::TruncateHash => truncate_hash(&sanitize(&version.to_string())),
+        ContainerTagStrategy::DropBuildMetadata => {
+            let mut version = version.clone();
+            version.build = BuildMetadata::EMPTY;
+            truncate_hash(&sanitize(&version.to_string()))
+        }
+        ContainerTagStrategy::Slugify => slugify(&version.to_string()),
+    }
+}
+
+/// Whether the tag is a valid OCI image tag
+pub fn is_valid(tag: &str) -> bool {
+    let mut bytes = tag.bytes();
+    match bytes.next() {
+        Some(first) if first.is_ascii_alphanumeric() || first == b'_' => {}
+        _ => return false,
+    }
+    tag.len() <= MAX_LENGTH && bytes.all(is_tag_byte)
+}
+
+fn is_tag_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || matches!(byte, b'_' | b'.' | b'-')
+}
+
+/// Replaces the `+` of the build metadata with `.` and other characters tags can't have with `-`.
+/// Leading separators are dropped, as tags start with a letter, digit or `_`.
+fn sanitize(tag: &str) -> String {
+    tag.chars()
+        .map(|c| match c {
+            '+' => '.',
+            c if c.is_ascii() && is_tag_byte(c as u8) => c,
+            _ => '-',
+        })
+        .collect::<String>()
+        .trim_start_matches(['.', '-'])
+        .to_string()
+}
+
+/// Cuts tags over the maximum length, ending them with a hash of the full tag so cut tags of
+/// different versions don't collide
+fn truncate_hash(tag: &str) -> String {
+    if tag.len() <= MAX_LENGTH {
+        return tag.to_string();
+    }
+    let hash = format!("{:016x}", fnv1a(tag.as_bytes()));
+    let kept = tag[..MAX_LENGTH - HASH_LENGTH - 1].trim_end_matches(['.', '-']);
+    format!("{}-{}", kept, &hash[..HASH_LENGTH])
+}
+
+/// Lowercases the tag, replaces the characters other than letters, digits, `.` and `_` between the
+/// parts with a single `-`, and cuts it at the maximum length
+fn slugify(tag: &str) -> String {
+    let mut slug = String::with_capacity(tag.len());
+    for c in tag.chars() {
+        let c = match c.to_ascii_lowercase() {
+            c if c.is_ascii_alphanumeric() || c == '.' || c == '_' => c,
+            _ => '-',
+        };
+        if c == '-' && (slug.is_empty() || slug.ends_with('-')) {
+            continue;
+        }
+        slug.push(c);
+    }
+    slug.truncate(MAX_LENGTH);
+    slug.trim_start_matches('.')
+        .trim_end_matches(['.', '-'])
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag(version: &str, strategy: ContainerTagStrategy) -> String {
+        container_tag(&Version::parse(version).unwrap(), strategy)
+    }
+
+    #[test]
+    fn test_container_tag() {
+        use ContainerTagStrategy::*;
+
+        assert_eq!(tag("1.2.3", TruncateHash), "1.2.3");
+        assert_eq!(
+            tag("1.0.1-beta.2+56c1976", TruncateHash),
+            "1.0.1-beta.2.56c1976"
+        );
+        assert_eq!(
+            tag("1.0.1-beta.2+56c1976", DropBuildMetadata),
+            "1.0.1-beta.2"
+        );
+        assert_eq!(tag("1.2.3+56c1976", DropBuildMetadata), "1.2.3");
+        assert_eq!(
+            tag("1.0.1-alpha.3+Feature--Login.56c1976", Slugify),
+            "1.0.1-alpha.3-feature-login.56c1976"
+        );
+    }
+
+    #[test]
+    fn test_container_tag_long() {
+        use ContainerTagStrategy::*;
+
+        let branch = "feature-".to_string() + &"x".repeat(150);
+        let long = format!("1.3.0-alpha.4+{}", branch);
+        let other = format!("1.3.0-alpha.4+{}y", branch);
+
+        let truncated = tag(&long, TruncateHash);
+        assert_eq!(truncated.len(), MAX_LENGTH);
+        assert!(truncated.starts_with("1.3.0-alpha.4.feature-xxx"));
+        assert!(is_valid(&truncated));
+        assert_eq!(truncated, tag(&long, TruncateHash));
+        assert_ne!(truncated, tag(&other, TruncateHash));
+
+        assert_eq!(tag(&long, DropBuildMetadata), "1.3.0-alpha.4");
+        let long_prerelease = format!("1.3.0-alpha.{}", "y".repeat(150));
+        let dropped = tag(&long_prerelease, DropBuildMetadata);
+        assert_eq!(dropped.len(), MAX_LENGTH);
+        assert!(is_valid(&dropped));
+
+        let slug = tag(&long, Slugify);
+        assert_eq!(slug.len(), MAX_LENGTH);
+        assert!(slug.starts_with("1.3.0-alpha.4-feature-xxx"));
+        assert!(is_valid(&slug));
+    }
+
+    #[test]
+    fn test_truncate_hash_separator() {
+        // The cut lands right after a separator, which isn't repeated before the hash
+        let tag = format!("{}.{}", "a".repeat(119), "b".repeat(20));
+
+        let truncated = truncate_hash(&tag);
+
+        assert!(truncated.starts_with(&format!("{}-", "a".repeat(119))));
+        assert_eq!(truncated.len(), 119 + 1 + HASH_LENGTH);
+        assert!(is_valid(&truncated));
+    }
+
+    #[test]
+    fn test_sanitize() {
+        assert_eq!(sanitize("1.2.3+build"), "1.2.3.build");
+        assert_eq!(sanitize("-1.2.3/ä"), "1.2.3--");
+        assert_eq!(slugify("--A/B..C"), "a-b..c");
+    }
+
+    #[test]
+    fn test_is_valid() {
+        assert!(is_valid("1.2.3"));
+        assert!(is_valid("_latest"));
+        assert!(is_valid(&"a".repeat(MAX_LENGTH)));
+        assert!(!is_valid(""));
+        assert!(!is_valid(".1.2.3"));
+        assert!(!is_valid("-1.2.3"));
+        assert!(!is_valid("1.2.3+56c1976"));
+        assert!(!is_valid("1.2.3/feature"));
+        assert!(!is_valid(&"a".repeat(MAX_LENGTH + 1)));
+    }
+}
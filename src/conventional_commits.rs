@@ -0,0 +1,190 @@
+use regex_lite::Regex;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+/// Version bump, ordered by significance
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Bump {
+    Patch,
+    Minor,
+    Major,
+}
+
+impl Bump {
+    pub fn apply(&self, version: &mut Version) {
+        match self {
+            Bump::Patch => version.patch += 1,
+            Bump::Minor => {
+                version.minor += 1;
+                version.patch = 0;
+            }
+            Bump::Major => {
+                version.major += 1;
+                version.minor = 0;
+                version.patch = 0;
+            }
+        }
+    }
+}
+
+/// Header of a Conventional Commit message, following https://www.conventionalcommits.org
+#[derive(Debug, PartialEq)]
+pub struct ConventionalCommit {
+    /// Lowercase type, e.g. `feat` or `fix`
+    pub kind: String,
+    pub scope: Option<String>,
+    pub description: String,
+    /// `!` after the type/scope, or a `BREAKING CHANGE` footer
+    pub breaking: bool,
+}
+
+impl ConventionalCommit {
+    /// `None` for messages which aren't Conventional Commits
+    pub fn parse(message: &str) -> Option<Self> {
+        let header = Regex::new(
+            r"^(?<type>[a-zA-Z]+)(\((?<scope>[^)]*)\))?(?<breaking>!)?: (?<description>.*)",
+        )
+        .unwrap();
+        let breaking_footer = Regex::new(r"(?m)^BREAKING[ -]CHANGE: ").unwrap();
+        let caps = header.captures(message.trim_start())?;
+        Some(Self {
+            kind: caps.name("type").unwrap().as_str().to_lowercase(),
+            scope: caps.name("scope").map(|scope| scope.as_str().to_string()),
+            description: caps
+                .name("description")
+                .unwrap()
+                .as_str()
+                .trim()
+                .to_string(),
+            breaking: caps.name("breaking").is_some() || breaking_footer.is_match(message),
+        })
+    }
+
+    pub fn bump(&self) -> Bump {
+        if self.breaking {
+            return Bump::Major;
+        }
+        match self.kind.as_str() {
+            "feat" => Bump::Minor,
+            _ => Bump::Patch,
+        }
+    }
+}
+
+/// Bump implied by a single commit message. `None` for messages which aren't Conventional Commits.
+pub fn commit_bump(message: &str) -> Option<Bump> {
+    ConventionalCommit::parse(message).map(|commit| commit.bump())
+}
+
+/// Messages of the commits squashed into a squash merge, from the bullet list GitHub writes into
+/// its body, e.g. `* feat: add --output-format`, each with the lines below its bullet. The message
+/// itself when none of the bullets is a Conventional Commit.
+pub fn squashed_messages(message: &str) -> Vec<String> {
+    let mut messages: Vec<String> = vec![];
+    for line in message.lines().skip(1) {
+        match line.strip_prefix("* ").or(line.strip_prefix("- ")) {
+            Some(bullet) => messages.push(bullet.to_string()),
+            None => {
+                if let Some(current) = messages.last_mut() {
+                    current.push('\n');
+                    current.push_str(line.strip_prefix("  ").unwrap_or(line));
+                }
+            }
+        }
+    }
+    if !messages
+        .iter()
+        .any(|message| ConventionalCommit::parse(message).is_some())
+    {
+        return vec![message.to_string()];
+    }
+    messages
+        .into_iter()
+        .map(|message| message.trim_end().to_string())
+        .collect()
+}
+
+/// Most significant bump of the commit messages, a patch when none of them implies more
+pub fn analyze<'a>(messages: impl IntoIterator<Item = &'a str>) -> Bump {
+    messages
+        .into_iter()
+        .filter_map(commit_bump)
+        .max()
+        .unwrap_or(Bump::Patch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commit_bump() {
+        assert_eq!(commit_bump("fix: crash on empty tag"), Some(Bump::Patch));
+        assert_eq!(commit_bump("docs(readme): typo"), Some(Bump::Patch));
+        assert_eq!(
+            commit_bump("feat(cli): add --output-format"),
+            Some(Bump::Minor)
+        );
+        assert_eq!(
+            commit_bump("feat!: drop the json output"),
+            Some(Bump::Major)
+        );
+        assert_eq!(
+            commit_bump("refactor: rename options\n\nBREAKING CHANGE: `--app` is now `--app-name`"),
+            Some(Bump::Major)
+        );
+        assert_eq!(commit_bump("Merge branch 'develop'"), None);
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            ConventionalCommit::parse("Feat(cli)!: add --output-format\n\nBody"),
+            Some(ConventionalCommit {
+                kind: "feat".to_string(),
+                scope: Some("cli".to_string()),
+                description: "add --output-format".to_string(),
+                breaking: true,
+            })
+        );
+    }
+
+    #[test]
+    fn test_analyze() {
+        assert_eq!(analyze(["fix: a", "feat: b", "chore: c"]), Bump::Minor);
+        assert_eq!(analyze(["Update README"]), Bump::Patch);
+    }
+
+    #[test]
+    fn test_squashed_messages() {
+        assert_eq!(
+            squashed_messages(
+                "Login page (#12)\n\n* feat(ui): add the login page\n\n* fix: typo\n\nBREAKING CHANGE: `/login` moved\n"
+            ),
+            vec![
+                "feat(ui): add the login page",
+                "fix: typo\n\nBREAKING CHANGE: `/login` moved"
+            ]
+        );
+        assert_eq!(
+            analyze(
+                squashed_messages("Login page (#12)\n\n* feat: add the login page")
+                    .iter()
+                    .map(String::as_str)
+            ),
+            Bump::Minor
+        );
+        let message = "fix: crash (#13)\n\n* wip\n* more wip";
+        assert_eq!(squashed_messages(message), vec![message]);
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut version = Version::parse("1.2.3").unwrap();
+        Bump::Minor.apply(&mut version);
+        assert_eq!(version, Version::parse("1.3.0").unwrap());
+        Bump::Major.apply(&mut version);
+        assert_eq!(version, Version::parse("2.0.0").unwrap());
+    }
+}
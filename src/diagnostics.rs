@@ -0,0 +1,237 @@
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::git_command::GitCommandTrait;
+use crate::models::Regexes;
+use crate::version_calculator::{get_default_branch, get_latest_tag, parse_tag_version};
+use crate::VersionOptions;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Check {
+    /// A release branch which isn't merged into main forks from develop at a commit of main, so it
+    /// wasn't cut from develop
+    ReleaseBase,
+    /// The main branch has commits after its latest tag
+    MainAhead,
+    /// The latest tag of main isn't merged back into develop, e.g. of an unfinished hotfix
+    UnmergedMain,
+    /// The latest tag of develop is newer than the latest tag of main
+    DevelopTag,
+}
+
+/// Branch state which doesn't follow the Gitflow model
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Finding {
+    pub check: Check,
+    pub message: String,
+}
+
+/// Checks how the main, develop and release branches were cut and merged, with merge-base and
+/// ancestry queries. The develop checks are skipped without a local develop branch.
+pub fn diagnose(
+    git_command: &impl GitCommandTrait,
+    options: &VersionOptions,
+) -> Result<Vec<Finding>> {
+    let regexes = Regexes::new(&options.app_name, &options.config)?;
+    let main = get_default_branch(git_command, options);
+    let branches = git_command.run(vec![
+        "for-each-ref",
+        "--format=%(refname:lstrip=2)",
+        "refs/heads",
+    ])?;
+    let develop = branches
+        .lines()
+        .find(|name| regexes.develop_branches.is_match(name));
+    let is_ancestor = |ancestor: &str, rev: &str| {
+        git_command
+            .run(vec!["merge-base", "--is-ancestor", ancestor, rev])
+            .is_ok()
+    };
+    let mut findings = vec![];
+    let mut finding = |check: Check, message: String| findings.push(Finding { check, message });
+
+    let main_tag = get_latest_tag(git_command, &regexes, Some(&main));
+    if let Some(tag) = &main_tag {
+        let range = format!("{}..{}", tag, main);
+        let ahead = git_command
+            .run(vec!["rev-list", "--count", &range])?
+            .parse::<u32>()?;
+        if ahead > 0 {
+            finding(
+                Check::MainAhead,
+                format!(
+                    "Main branch '{}' is {} commit(s) ahead of its latest tag '{}'",
+                    main, ahead, tag
+                ),
+            );
+        }
+    }
+
+    let develop = match develop {
+        Some(develop) => develop,
+        None => return Ok(findings),
+    };
+    let releases = branches
+        .lines()
+        .filter(|name| regexes.rc_branches.is_match(name));
+    for release in releases {
+        if is_ancestor(release, &main) {
+            // Finished, but not deleted
+            continue;
+        }
+        let fork = git_command.run(vec!["merge-base", release, develop])?;
+        if is_ancestor(&fork, &main) {
+            finding(
+                Check::ReleaseBase,
+                format!(
+                    "Release branch '{}' wasn't cut from develop branch '{}': it forks from it at {}, a commit of main branch '{}'",
+                    release,
+                    develop,
+                    &fork[..fork.len().min(7)],
+                    main
+                ),
+            );
+        }
+    }
+
+    if let Some(tag) = &main_tag {
+        if !is_ancestor(tag, develop) {
+            finding(
+                Check::UnmergedMain,
+                format!(
+                    "Latest tag '{}' of main branch '{}' isn't merged into develop branch '{}'",
+                    tag, main, develop
+                ),
+            );
+        }
+    }
+    let version = |tag: &Option<String>| {
+        tag.as_deref()
+            .and_then(|tag| parse_tag_version(&regexes, tag).ok())
+    };
+    let develop_tag = get_latest_tag(git_command, &regexes, Some(develop));
+    if let Some(develop_version) = version(&develop_tag) {
+        if version(&main_tag).is_none_or(|main_version| develop_version > main_version) {
+            finding(
+                Check::DevelopTag,
+                format!(
+                    "Tag '{}' of develop branch '{}' is newer than {} of main branch '{}'",
+                    develop_tag.as_deref().unwrap_or_default(),
+                    develop,
+                    match &main_tag {
+                        Some(tag) => format!("the latest tag '{}'", tag),
+                        None => "any tag".to_string(),
+                    },
+                    main
+                ),
+            );
+        }
+    }
+    Ok(findings)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::git_command::MockGitCommandTrait;
+
+    use anyhow::Error;
+
+    use super::*;
+
+    fn mock_git(responses: &'static [(&'static str, Option<&'static str>)]) -> MockGitCommandTrait {
+        let mut git_command = MockGitCommandTrait::new();
+        for (args, response) in responses {
+            git_command
+                .expect_run()
+                .withf(move |a| a.join(" ") == *args)
+                .returning(move |_| match response {
+                    Some(output) => Ok(output.to_string()),
+                    None => Err(Error::msg("exit status: 1")),
+                });
+        }
+        git_command
+    }
+
+    fn options() -> VersionOptions {
+        VersionOptions {
+            default_branch: Some("main".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_diagnose() {
+        let git_command = mock_git(&[
+            (
+                "for-each-ref --format=%(refname:lstrip=2) refs/heads",
+                Some("develop\nmain\nrelease/1.3.0\nrelease/1.4.0\nrelease/1.5.0"),
+            ),
+            ("describe --abbrev=0 --tags main", Some("1.4.1")),
+            ("describe --abbrev=0 --tags develop", Some("1.5.0-beta.1")),
+            ("rev-list --count 1.4.1..main", Some("2")),
+            ("merge-base --is-ancestor release/1.3.0 main", Some("")),
+            ("merge-base --is-ancestor release/1.4.0 main", None),
+            ("merge-base --is-ancestor release/1.5.0 main", None),
+            ("merge-base release/1.4.0 develop", Some("aaaaaaaaaa")),
+            ("merge-base release/1.5.0 develop", Some("bbbbbbbbbb")),
+            ("merge-base --is-ancestor aaaaaaaaaa main", Some("")),
+            ("merge-base --is-ancestor bbbbbbbbbb main", None),
+            ("merge-base --is-ancestor 1.4.1 develop", None),
+        ]);
+
+        let findings = diagnose(&git_command, &options()).unwrap();
+
+        assert_eq!(
+            findings,
+            vec![
+                Finding {
+                    check: Check::MainAhead,
+                    message: "Main branch 'main' is 2 commit(s) ahead of its latest tag '1.4.1'"
+                        .to_string()
+                },
+                Finding {
+                    check: Check::ReleaseBase,
+                    message: "Release branch 'release/1.4.0' wasn't cut from develop branch 'develop': it forks from it at aaaaaaa, a commit of main branch 'main'".to_string()
+                },
+                Finding {
+                    check: Check::UnmergedMain,
+                    message: "Latest tag '1.4.1' of main branch 'main' isn't merged into develop branch 'develop'".to_string()
+                },
+                Finding {
+                    check: Check::DevelopTag,
+                    message: "Tag '1.5.0-beta.1' of develop branch 'develop' is newer than the latest tag '1.4.1' of main branch 'main'".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diagnose_clean() {
+        let git_command = mock_git(&[
+            (
+                "for-each-ref --format=%(refname:lstrip=2) refs/heads",
+                Some("develop\nmain"),
+            ),
+            ("describe --abbrev=0 --tags main", Some("1.4.1")),
+            ("describe --abbrev=0 --tags develop", Some("1.4.1")),
+            ("rev-list --count 1.4.1..main", Some("0")),
+            ("merge-base --is-ancestor 1.4.1 develop", Some("")),
+        ]);
+
+        assert_eq!(diagnose(&git_command, &options()).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_diagnose_without_develop() {
+        let git_command = mock_git(&[
+            (
+                "for-each-ref --format=%(refname:lstrip=2) refs/heads",
+                Some("main\nrelease/1.5.0"),
+            ),
+            ("describe --abbrev=0 --tags main", None),
+        ]);
+
+        assert_eq!(diagnose(&git_command, &options()).unwrap(), vec![]);
+    }
+}
@@ -0,0 +1,527 @@
+use anyhow::Error;
+use serde::Serialize;
+
+use std::fs;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use crate::branch_resolver::resolve_branch;
+use crate::git_command::GitCommandTrait;
+use crate::messages::{Message, Messages};
+use crate::models::Regexes;
+use crate::version_calculator::{get_latest_tag, parse_tag_version};
+use crate::VersionOptions;
+
+/// Oldest git supporting all the queries, `branch --show-current` being the newest of them
+const MIN_GIT_VERSION: (u64, u64) = (2, 22);
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Check {
+    /// The git executable runs, and is recent enough
+    Git,
+    /// The working directory (or `--repo-path`) is inside a git checkout
+    Repository,
+    /// The refs were fetched recently, and the history isn't shallow
+    Fetch,
+    /// The latest tag is a SemVer version
+    Tags,
+    /// The branch is determined, and classified by one branch pattern
+    Branch,
+    /// The configuration parses, and its patterns compile
+    Config,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Status {
+    Pass,
+    Fail,
+    /// Not checked, as a check it depends on failed
+    Skip,
+}
+
+/// Result of a check of `doctor`, with how to fix it when it failed
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Diagnosis {
+    pub check: Check,
+    pub status: Status,
+    pub message: String,
+    pub hint: Option<String>,
+}
+
+impl Diagnosis {
+    fn pass(messages: &Messages, check: Check, message: Message) -> Self {
+        Self {
+            check,
+            status: Status::Pass,
+            message: messages.get(&message),
+            hint: None,
+        }
+    }
+
+    fn fail(messages: &Messages, check: Check, message: Message, hint: Message) -> Self {
+        Self {
+            check,
+            status: Status::Fail,
+            // Without the newline of the git errors
+            message: messages.get(&message).trim_end().to_string(),
+            hint: Some(messages.get(&hint)),
+        }
+    }
+
+    fn skip(messages: &Messages, check: Check) -> Self {
+        Self {
+            check,
+            status: Status::Skip,
+            message: messages.get(&Message::new("doctor.skip")),
+            hint: None,
+        }
+    }
+}
+
+/// Checks the environment the version is computed in, one check after the other: the checks of
+/// the repository are skipped without git, and those of the refs outside a repository. The refs
+/// are fetched recently when `FETCH_HEAD`, or else the clone, is at most `max_fetch_age` hours
+/// older than `now`. `config` is the configuration file, and the error loading it. The messages and
+/// hints follow the `[messages]` of the configuration.
+pub fn doctor(
+    git_command: &impl GitCommandTrait,
+    options: &VersionOptions,
+    config: (Option<&Path>, Option<&Error>),
+    now: u64,
+    max_fetch_age: u64,
+) -> Vec<Diagnosis> {
+    // An invalid `[messages]` fails the configuration check, which is then reported in English
+    let messages = &Messages::new(&options.config.messages).unwrap_or_default();
+    let mut diagnoses = vec![check_git(git_command, messages)];
+    let git = diagnoses[0].status == Status::Pass;
+    diagnoses.push(match git {
+        true => check_repository(git_command, messages),
+        false => Diagnosis::skip(messages, Check::Repository),
+    });
+    let repository = diagnoses[1].status == Status::Pass;
+    diagnoses.push(match repository {
+        true => check_fetch(git_command, messages, now, max_fetch_age),
+        false => Diagnosis::skip(messages, Check::Fetch),
+    });
+    let regexes = match config.1 {
+        Some(_) => Err(Error::msg("The configuration is invalid")),
+        None => Regexes::new(&options.app_name, &options.config),
+    };
+    match (&regexes, repository) {
+        (Ok(regexes), true) => {
+            diagnoses.push(check_tags(git_command, messages, regexes));
+            diagnoses.push(check_branch(git_command, messages, options, regexes));
+        }
+        _ => {
+            diagnoses.push(Diagnosis::skip(messages, Check::Tags));
+            diagnoses.push(Diagnosis::skip(messages, Check::Branch));
+        }
+    }
+    diagnoses.push(match (config, regexes) {
+        ((_, Some(err)), _) => Diagnosis::fail(
+            messages,
+            Check::Config,
+            Message::new("doctor.config_invalid").arg("error", err),
+            Message::new("doctor.config_hint"),
+        ),
+        (_, Err(err)) => Diagnosis::fail(
+            messages,
+            Check::Config,
+            Message::new("doctor.config_invalid").arg("error", err),
+            Message::new("doctor.pattern_hint"),
+        ),
+        ((Some(path), None), Ok(_)) => Diagnosis::pass(
+            messages,
+            Check::Config,
+            Message::new("doctor.config_valid").arg("path", path.display()),
+        ),
+        ((None, None), Ok(_)) => Diagnosis::pass(
+            messages,
+            Check::Config,
+            Message::new("doctor.config_default"),
+        ),
+    });
+    diagnoses
+}
+
+fn check_git(git_command: &impl GitCommandTrait, messages: &Messages) -> Diagnosis {
+    let output = match git_command.run(vec!["--version"]) {
+        Ok(output) => output,
+        Err(err) => {
+            return Diagnosis::fail(
+                messages,
+                Check::Git,
+                Message::new("doctor.git_error").arg("error", err),
+                Message::new("doctor.git_hint"),
+            )
+        }
+    };
+    let (min_major, min_minor) = MIN_GIT_VERSION;
+    let min_version = format!("{}.{}", min_major, min_minor);
+    match parse_git_version(&output) {
+        Some((major, minor)) if (major, minor) >= MIN_GIT_VERSION => Diagnosis::pass(
+            messages,
+            Check::Git,
+            Message::new("doctor.git").arg("output", output),
+        ),
+        Some(_) => Diagnosis::fail(
+            messages,
+            Check::Git,
+            Message::new("doctor.git_old")
+                .arg("output", output)
+                .arg("version", &min_version),
+            Message::new("doctor.git_upgrade_hint").arg("version", &min_version),
+        ),
+        None => Diagnosis::fail(
+            messages,
+            Check::Git,
+            Message::new("doctor.git_unknown").arg("output", output),
+            Message::new("doctor.git_path_hint"),
+        ),
+    }
+}
+
+/// Major and minor version of `git --version`, e.g. `git version 2.41.0.windows.1`
+fn parse_git_version(output: &str) -> Option<(u64, u64)> {
+    let version = output
+        .strip_prefix("git version ")?
+        .split_whitespace()
+        .next()?;
+    let mut parts = version.split('.').map(str::parse::<u64>);
+    Some((parts.next()?.ok()?, parts.next()?.ok()?))
+}
+
+fn check_repository(git_command: &impl GitCommandTrait, messages: &Messages) -> Diagnosis {
+    match git_command.run(vec!["rev-parse", "--show-toplevel"]) {
+        Ok(toplevel) => Diagnosis::pass(
+            messages,
+            Check::Repository,
+            Message::new("doctor.repository").arg("path", toplevel),
+        ),
+        Err(err) => Diagnosis::fail(
+            messages,
+            Check::Repository,
+            Message::new("doctor.no_repository").arg("error", err),
+            Message::new("doctor.repository_hint"),
+        ),
+    }
+}
+
+fn check_fetch(
+    git_command: &impl GitCommandTrait,
+    messages: &Messages,
+    now: u64,
+    max_fetch_age: u64,
+) -> Diagnosis {
+    if git_command
+        .run(vec!["rev-parse", "--is-shallow-repository"])
+        .is_ok_and(|shallow| shallow == "true")
+    {
+        return Diagnosis::fail(
+            messages,
+            Check::Fetch,
+            Message::new("doctor.shallow"),
+            Message::new("doctor.shallow_hint"),
+        );
+    }
+    let remotes = git_command.run(vec!["remote"]).unwrap_or_default();
+    let remote = match remotes.lines().find(|remote| *remote == "origin") {
+        Some(remote) => remote,
+        None => match remotes.lines().next() {
+            Some(remote) => remote,
+            None => {
+                return Diagnosis::pass(messages, Check::Fetch, Message::new("doctor.no_remote"))
+            }
+        },
+    };
+    let fetched = fetch_head_time(git_command).or_else(|| clone_time(git_command, remote));
+    let hint = Message::new("doctor.fetch_hint");
+    match fetched {
+        Some(fetched) if now.saturating_sub(fetched) <= max_fetch_age * 3600 => Diagnosis::pass(
+            messages,
+            Check::Fetch,
+            Message::new("doctor.fetched")
+                .arg("remote", remote)
+                .arg("hours", now.saturating_sub(fetched) / 3600),
+        ),
+        Some(fetched) => Diagnosis::fail(
+            messages,
+            Check::Fetch,
+            Message::new("doctor.fetch_stale")
+                .arg("remote", remote)
+                .arg("hours", now.saturating_sub(fetched) / 3600)
+                .arg("max_fetch_age", max_fetch_age),
+            hint,
+        ),
+        None => Diagnosis::fail(
+            messages,
+            Check::Fetch,
+            Message::new("doctor.never_fetched").arg("remote", remote),
+            hint,
+        ),
+    }
+}
+
+/// Modification time of `FETCH_HEAD`, written by every fetch
+fn fetch_head_time(git_command: &impl GitCommandTrait) -> Option<u64> {
+    let git_dir = git_command
+        .run(vec!["rev-parse", "--absolute-git-dir"])
+        .ok()?;
+    let modified = fs::metadata(Path::new(&git_dir).join("FETCH_HEAD"))
+        .and_then(|metadata| metadata.modified())
+        .ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs())
+}
+
+/// Time of the clone, of the reflog entry of the remote `HEAD`, e.g.
+/// `refs/remotes/origin/HEAD@{1705608251}`
+fn clone_time(git_command: &impl GitCommandTrait, remote: &str) -> Option<u64> {
+    let reflog = git_command
+        .run(vec![
+            "reflog",
+            "show",
+            "-1",
+            "--date=unix",
+            "--format=%gd",
+            &format!("refs/remotes/{}/HEAD", remote),
+        ])
+        .ok()?;
+    reflog.split_once("@{")?.1.strip_suffix('}')?.parse().ok()
+}
+
+fn check_tags(
+    git_command: &impl GitCommandTrait,
+    messages: &Messages,
+    regexes: &Regexes,
+) -> Diagnosis {
+    let tag = match get_latest_tag(git_command, regexes, None) {
+        Some(tag) => tag,
+        None => return Diagnosis::pass(messages, Check::Tags, Message::new("doctor.no_tags")),
+    };
+    match parse_tag_version(regexes, &tag) {
+        Ok(version) => Diagnosis::pass(
+            messages,
+            Check::Tags,
+            Message::new("doctor.tag")
+                .arg("tag", &tag)
+                .arg("version", version),
+        ),
+        Err(_) => Diagnosis::fail(
+            messages,
+            Check::Tags,
+            Message::new("doctor.tag_not_semver").arg("tag", &tag),
+            Message::new("doctor.tag_hint"),
+        ),
+    }
+}
+
+fn check_branch(
+    git_command: &impl GitCommandTrait,
+    messages: &Messages,
+    options: &VersionOptions,
+    regexes: &Regexes,
+) -> Diagnosis {
+    let branch = match resolve_branch(git_command, &options.branch) {
+        Ok(branch) => branch,
+        Err(err) => {
+            return Diagnosis::fail(
+                messages,
+                Check::Branch,
+                Message::new("doctor.no_branch").arg("error", err),
+                Message::new("doctor.branch_hint"),
+            )
+        }
+    };
+    let classes = regexes.matching_classes(&branch);
+    match classes.len() > 1 && regexes.custom_branch(&branch).is_none() {
+        true => Diagnosis::fail(
+            messages,
+            Check::Branch,
+            Message::new("doctor.branch_ambiguous")
+                .arg("branch", &branch)
+                .arg("classes", format!("{:?}", classes)),
+            Message::new("doctor.branch_ambiguous_hint"),
+        ),
+        false => Diagnosis::pass(
+            messages,
+            Check::Branch,
+            Message::new("doctor.branch")
+                .arg("branch", &branch)
+                .arg("class", format!("{:?}", regexes.branch_class(&branch))),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::git_command::MockGitCommandTrait;
+
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    fn mock_git(responses: &'static [(&'static str, Option<&'static str>)]) -> MockGitCommandTrait {
+        let mut git_command = MockGitCommandTrait::new();
+        for (args, response) in responses {
+            git_command
+                .expect_run()
+                .withf(move |a| a.join(" ") == *args)
+                .returning(move |_| match response {
+                    Some(output) => Ok(output.to_string()),
+                    None => Err(Error::msg("exit status: 128")),
+                });
+        }
+        git_command
+    }
+
+    fn statuses(diagnoses: &[Diagnosis]) -> Vec<(Check, Status)> {
+        diagnoses
+            .iter()
+            .map(|diagnosis| (diagnosis.check, diagnosis.status))
+            .collect()
+    }
+
+    #[test]
+    fn test_doctor() {
+        let git_command = mock_git(&[
+            ("--version", Some("git version 2.43.0")),
+            ("rev-parse --show-toplevel", Some("/repo")),
+            ("rev-parse --is-shallow-repository", Some("false")),
+            ("remote", Some("origin")),
+            ("rev-parse --absolute-git-dir", Some("/nonexistent/.git")),
+            (
+                "reflog show -1 --date=unix --format=%gd refs/remotes/origin/HEAD",
+                Some("refs/remotes/origin/HEAD@{1705608251}"),
+            ),
+            ("describe --abbrev=0 --tags", Some("1.2.0")),
+            ("branch --show-current", Some("develop")),
+        ]);
+
+        let diagnoses = doctor(
+            &git_command,
+            &VersionOptions::default(),
+            (None, None),
+            1705608251 + 3 * 3600,
+            24,
+        );
+
+        assert_eq!(
+            statuses(&diagnoses),
+            vec![
+                (Check::Git, Status::Pass),
+                (Check::Repository, Status::Pass),
+                (Check::Fetch, Status::Pass),
+                (Check::Tags, Status::Pass),
+                (Check::Branch, Status::Pass),
+                (Check::Config, Status::Pass),
+            ]
+        );
+        assert_eq!(diagnoses[2].message, "Fetched from 'origin' 3 hour(s) ago");
+        assert_eq!(diagnoses[3].message, "Latest tag '1.2.0' is version 1.2.0");
+        assert_eq!(
+            diagnoses[4].message,
+            "Branch 'develop' is classified as Develop"
+        );
+
+        let diagnoses = doctor(
+            &git_command,
+            &VersionOptions::default(),
+            (None, None),
+            1705608251 + 48 * 3600,
+            24,
+        );
+        assert_eq!(diagnoses[2].status, Status::Fail);
+        assert_eq!(
+            diagnoses[2].message,
+            "Last fetched from 'origin' 48 hour(s) ago, more than 24 hour(s)"
+        );
+    }
+
+    #[test]
+    fn test_doctor_messages() {
+        let git_command = mock_git(&[("--version", None)]);
+        let mut options = VersionOptions::default();
+        options.config.messages = BTreeMap::from([
+            ("doctor.git_hint".to_string(), "Installiere git".to_string()),
+            ("doctor.skip".to_string(), "Nicht geprüft".to_string()),
+        ]);
+
+        let diagnoses = doctor(&git_command, &options, (None, None), 0, 24);
+
+        assert_eq!(diagnoses[0].hint.as_deref(), Some("Installiere git"));
+        assert_eq!(diagnoses[1].message, "Nicht geprüft");
+    }
+
+    #[test]
+    fn test_doctor_without_git() {
+        let git_command = mock_git(&[("--version", None)]);
+        let err = Config::parse("no_such_setting = 1").unwrap_err();
+
+        let diagnoses = doctor(
+            &git_command,
+            &VersionOptions::default(),
+            (Some(Path::new("version-vine.toml")), Some(&err)),
+            0,
+            24,
+        );
+
+        assert_eq!(
+            statuses(&diagnoses),
+            vec![
+                (Check::Git, Status::Fail),
+                (Check::Repository, Status::Skip),
+                (Check::Fetch, Status::Skip),
+                (Check::Tags, Status::Skip),
+                (Check::Branch, Status::Skip),
+                (Check::Config, Status::Fail),
+            ]
+        );
+        assert_eq!(
+            diagnoses[0].hint.as_deref(),
+            Some("Install git, or point --git-path or GIT_EXECUTABLE at it")
+        );
+    }
+
+    #[test]
+    fn test_check_git_fetch() {
+        let git_command = mock_git(&[("--version", Some("git version 2.20.1"))]);
+        assert_eq!(
+            check_git(&git_command, &Messages::default()).message,
+            "git version 2.20.1 is older than 2.22"
+        );
+
+        let git_command = mock_git(&[
+            ("rev-parse --is-shallow-repository", Some("true")),
+            ("remote", Some("")),
+        ]);
+        assert_eq!(
+            check_fetch(&git_command, &Messages::default(), 0, 24).status,
+            Status::Fail
+        );
+        let git_command = mock_git(&[
+            ("rev-parse --is-shallow-repository", Some("false")),
+            ("remote", Some("")),
+        ]);
+        assert_eq!(
+            check_fetch(&git_command, &Messages::default(), 0, 24).message,
+            "No remote to fetch from"
+        );
+    }
+
+    #[test]
+    fn test_parse_git_version() {
+        assert_eq!(parse_git_version("git version 2.43.0"), Some((2, 43)));
+        assert_eq!(
+            parse_git_version("git version 2.41.0.windows.1"),
+            Some((2, 41))
+        );
+        assert_eq!(
+            parse_git_version("git version 2.39.3 (Apple Git-145)"),
+            Some((2, 39))
+        );
+        assert_eq!(parse_git_version("hub version 2.14.2"), None);
+    }
+}
@@ -0,0 +1,121 @@
+use anyhow::{Error, Result};
+use serde::Serialize;
+
+use crate::output::render_template;
+use crate::{VersionInfo, VersionOptions};
+
+/// Highest number of a part of a .NET assembly or file version
+const MAX_PART: u32 = 65534;
+
+/// The .NET versions of `--extra-formats dotnet`, for the MSBuild properties of the same names
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct DotnetVersions {
+    /// `AssemblyVersion`, e.g. `1.2.3.0`
+    pub assembly_version: String,
+    /// `FileVersion`, e.g. `1.2.3.4` for `1.2.3-beta.4`
+    pub file_version: String,
+    /// `InformationalVersion`, e.g. `1.2.3-beta.4+56c1976`
+    pub informational_version: String,
+}
+
+/// Renders the `[dotnet]` templates. Fails when the assembly or file version isn't a .NET
+/// version, e.g. when a date counter exceeds 65534, rather than producing a version MSBuild
+/// rejects.
+pub fn dotnet_versions(
+    version_info: &VersionInfo,
+    options: &VersionOptions,
+) -> Result<DotnetVersions> {
+    let config = &options.config.dotnet;
+    let numeric = |name: &str, template: &str| {
+        let version = render_template(version_info, options, template)?;
+        check_numeric(name, &version)?;
+        Ok::<_, Error>(version)
+    };
+    Ok(DotnetVersions {
+        assembly_version: numeric("assembly_version", &config.assembly_version)?,
+        file_version: numeric("file_version", &config.file_version)?,
+        informational_version: render_template(
+            version_info,
+            options,
+            &config.informational_version,
+        )?,
+    })
+}
+
+fn check_numeric(name: &str, version: &str) -> Result<()> {
+    let parts: Vec<&str> = version.split('.').collect();
+    let valid = (1..=4).contains(&parts.len())
+        && parts
+            .iter()
+            .all(|part| part.parse::<u32>().is_ok_and(|part| part <= MAX_PART));
+    match valid {
+        true => Ok(()),
+        false => Err(Error::msg(format!(
+            "The {} '{}' isn't a .NET version: up to four numbers of at most {}. Adjust the [dotnet] {} template.",
+            name, version, MAX_PART, name
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+
+    use super::*;
+
+    fn version_info(version: &str) -> VersionInfo {
+        VersionInfo {
+            rev_count: "70000".to_string(),
+            ..crate::version_calculator::version_info("develop", version)
+        }
+    }
+
+    #[test]
+    fn test_dotnet_versions() {
+        let options = VersionOptions::default();
+
+        assert_eq!(
+            dotnet_versions(&version_info("1.2.3-beta.4+56c1976"), &options).unwrap(),
+            DotnetVersions {
+                assembly_version: "1.2.3.0".to_string(),
+                file_version: "1.2.3.4".to_string(),
+                informational_version: "1.2.3-beta.4+56c1976".to_string(),
+            }
+        );
+        assert_eq!(
+            dotnet_versions(&version_info("1.2.3"), &options)
+                .unwrap()
+                .file_version,
+            "1.2.3.0"
+        );
+
+        let mut options = VersionOptions::default();
+        options
+            .apply_config(Config::parse("[dotnet]\nassembly_version = '{{major}}.0.0.0'").unwrap());
+        assert_eq!(
+            dotnet_versions(&version_info("1.2.3"), &options)
+                .unwrap()
+                .assembly_version,
+            "1.0.0.0"
+        );
+    }
+
+    #[test]
+    fn test_dotnet_versions_invalid() {
+        let mut options = VersionOptions::default();
+        options.apply_config(
+            Config::parse("[dotnet]\nfile_version = '{{major}}.{{minor}}.{{patch}}.{{rev_count}}'")
+                .unwrap(),
+        );
+
+        let err = dotnet_versions(&version_info("1.2.3"), &options).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "The file_version '1.2.3.70000' isn't a .NET version: up to four numbers of at most 65534. Adjust the [dotnet] file_version template."
+        );
+        assert!(check_numeric("assembly_version", "1.2.3.4.5").is_err());
+        assert!(check_numeric("assembly_version", "1.2.3-beta").is_err());
+        assert!(check_numeric("assembly_version", "1").is_ok());
+    }
+}
@@ -0,0 +1,107 @@
+use anyhow::Error;
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use std::fmt;
+
+/// Failures CI pipelines can branch on, by their exit code or the `error` field of `--errors json`.
+/// Other failures exit with 1, and invalid arguments with 2.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "error", content = "message", rename_all = "kebab-case")]
+pub enum VersionError {
+    /// A main branch commit without a release tag
+    NoTagOnMain(String),
+    /// Uncommitted changes where a clean checkout is required
+    DirtyWorktree(String),
+    /// A branch name which doesn't fit its branch class, e.g. a release branch without version
+    InvalidBranch(String),
+    /// A git command which couldn't be run or failed
+    GitFailure(String),
+    /// A tag or configuration which cannot be parsed
+    ParseError(String),
+}
+
+impl VersionError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            VersionError::NoTagOnMain(_) => 3,
+            VersionError::DirtyWorktree(_) => 4,
+            VersionError::InvalidBranch(_) => 5,
+            VersionError::GitFailure(_) => 6,
+            VersionError::ParseError(_) => 7,
+        }
+    }
+
+    fn message(&self) -> &str {
+        match self {
+            VersionError::NoTagOnMain(message)
+            | VersionError::DirtyWorktree(message)
+            | VersionError::InvalidBranch(message)
+            | VersionError::GitFailure(message)
+            | VersionError::ParseError(message) => message,
+        }
+    }
+}
+
+impl fmt::Display for VersionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for VersionError {}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum ErrorFormat {
+    /// `Error: <message>`, with the causes
+    Text,
+    /// `{"error": "<kind>", "message": "<message>", "exit_code": <code>}`
+    Json,
+}
+
+/// The typed error of the failure, the first in its chain of causes
+pub fn version_error(err: &Error) -> Option<&VersionError> {
+    err.chain()
+        .find_map(|cause| cause.downcast_ref::<VersionError>())
+}
+
+pub fn exit_code(err: &Error) -> i32 {
+    version_error(err).map_or(1, VersionError::exit_code)
+}
+
+/// The failure for `--errors json`, `error` being `error` for untyped failures
+pub fn error_json(err: &Error) -> Value {
+    let mut json = match version_error(err) {
+        Some(version_error) => json!(version_error),
+        None => json!({"error": "error"}),
+    };
+    json["message"] = json!(format!("{:#}", err));
+    json["exit_code"] = json!(exit_code(err));
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_json() {
+        let err = Error::new(VersionError::NoTagOnMain(
+            "Cannot version a production release from a commit without a tag".to_string(),
+        ))
+        .context("Cannot compute the version of 'myapp'");
+        assert_eq!(
+            error_json(&err),
+            json!({
+                "error": "no-tag-on-main",
+                "message": "Cannot compute the version of 'myapp': Cannot version a production release from a commit without a tag",
+                "exit_code": 3,
+            })
+        );
+
+        let err = Error::msg("Unknown bump 'huge'");
+        assert_eq!(exit_code(&err), 1);
+        assert_eq!(error_json(&err)["error"], "error");
+    }
+}
@@ -0,0 +1,125 @@
+use anyhow::Result;
+use serde_json::{json, Value};
+
+use crate::branch_resolver::{resolve_branch, resolve_branch_alias};
+use crate::git_command::GitCommandTrait;
+use crate::models::Regexes;
+use crate::version_calculator::get_default_branch;
+use crate::VersionOptions;
+
+/// How the branch is classified: the patterns evaluated in priority order, which of them match
+/// and the resulting class. Unlike the version calculation, it doesn't fail on ambiguous branches.
+pub fn explain(git_command: &impl GitCommandTrait, options: &VersionOptions) -> Result<Value> {
+    let branch = resolve_branch(git_command, &options.branch)?;
+    let default_branch = get_default_branch(git_command, options);
+    // The branch an alias stands for is classified instead
+    let alias = resolve_branch_alias(git_command, &branch, &default_branch);
+    let classified = alias.as_deref().unwrap_or(&branch);
+    let regexes =
+        Regexes::new(&options.app_name, &options.config)?.with_default_branch(&default_branch)?;
+    // Custom classes are matched first
+    let custom_rules = regexes.custom_branches.iter().map(|(name, regex, class)| {
+        json!({
+            "class": class,
+            "custom": name,
+            "pattern": regex.as_str(),
+            "matched": regex.is_match(classified),
+        })
+    });
+    let rules: Vec<Value> = custom_rules
+        .chain(regexes.priority.iter().filter_map(|class| {
+            let regex = regexes.class_regex(*class)?;
+            Some(json!({
+                "class": class,
+                "pattern": regex.as_str(),
+                "matched": regex.is_match(classified),
+            }))
+        }))
+        .collect();
+    let matching_classes = regexes.matching_classes(classified);
+    let custom = regexes.custom_branch(classified);
+    Ok(json!({
+        "branch": branch,
+        "default_branch": default_branch,
+        "alias": alias,
+        "rules": rules,
+        "class": regexes.branch_class(classified),
+        "custom": custom.map(|(name, _, _)| name),
+        "ambiguous": custom.is_none() && matching_classes.len() > 1,
+        "on_ambiguous": options.config.branches.ambiguous,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::git_command::MockGitCommandTrait;
+    use anyhow::Error;
+
+    use super::*;
+
+    #[test]
+    fn test_explain() {
+        let mut git_command = MockGitCommandTrait::new();
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "branch --show-current")
+            .returning(|_| Ok("release/main".to_string()));
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "symbolic-ref")
+            .returning(|_| {
+                Err(Error::msg(
+                    "fatal: ref refs/heads/release/main is not a symbolic ref",
+                ))
+            });
+        let mut options = VersionOptions {
+            default_branch: Some("main".to_string()),
+            ..Default::default()
+        };
+        options.apply_config(
+            Config::parse("[branches]\nmain = 'main$'\npriority = ['release', 'main', 'develop']")
+                .unwrap(),
+        );
+
+        let explanation = explain(&git_command, &options).unwrap();
+
+        assert_eq!(explanation["class"], "release");
+        assert_eq!(explanation["alias"], Value::Null);
+        assert_eq!(explanation["ambiguous"], true);
+        assert_eq!(
+            explanation["rules"],
+            json!([
+                {"class": "hotfix", "pattern": r"^hotfix\/", "matched": false},
+                {"class": "release", "pattern": r"^release\/(?<version>.+)", "matched": true},
+                {"class": "main", "pattern": "main$", "matched": true},
+                {"class": "develop", "pattern": "^develop|dev$", "matched": false},
+                {"class": "support", "pattern": r"^support\/(?<version>\d+(?:\.\d+)?)\.x$", "matched": false},
+            ])
+        );
+    }
+
+    #[test]
+    fn test_explain_alias() {
+        let mut git_command = MockGitCommandTrait::new();
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "branch --show-current")
+            .returning(|_| Ok("master".to_string()));
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "symbolic-ref --quiet --short refs/heads/master")
+            .returning(|_| Ok("main".to_string()));
+        let mut options = VersionOptions {
+            default_branch: Some("trunk".to_string()),
+            ..Default::default()
+        };
+        options.apply_config(Config::parse("[branches]\nmain = '^main$'").unwrap());
+
+        let explanation = explain(&git_command, &options).unwrap();
+
+        assert_eq!(explanation["branch"], "master");
+        assert_eq!(explanation["alias"], "main");
+        assert_eq!(explanation["class"], "main");
+    }
+}
@@ -1,26 +1,179 @@
-use anyhow::{Error, Result};
+use anyhow::Result;
+use clap::ValueEnum;
 use mockall::{automock, concretize};
-use std::process::Command;
+use std::path::{Path, PathBuf};
 
-pub struct GitCommand {}
+use crate::config::ExecutionConfig;
+use crate::error::VersionError;
+use crate::sandbox;
 
+#[cfg(feature = "native-git")]
+mod native;
+#[cfg(feature = "native-git")]
+pub use native::NativeGitCommand;
+
+#[derive(Default)]
+pub struct GitCommand {
+    pub policy: ExecutionConfig,
+    /// Git executable, `git` from the `PATH` when not provided
+    pub git_path: Option<String>,
+    /// Repository the commands run in with `git -C`, the working directory when not provided
+    pub repo_path: Option<PathBuf>,
+}
+
+/// Runs git commands. Shared between the threads computing the versions of multiple apps.
 #[automock]
-pub trait GitCommandTrait {
+pub trait GitCommandTrait: Send + Sync {
     #[concretize]
     fn run(&self, args: Vec<&str>) -> Result<String>;
 }
 
 impl GitCommandTrait for GitCommand {
     fn run(&self, args: Vec<&str>) -> Result<String> {
-        let output = Command::new("git").args(args).output()?;
+        let git_path = self.git_path.as_deref().unwrap_or("git");
+        let repo_path = self.repo_path.as_ref().map(|path| path.to_string_lossy());
+        let mut git_args = vec![];
+        if let Some(repo_path) = &repo_path {
+            git_args.extend(["-C", repo_path.as_ref()]);
+        }
+        git_args.extend(args);
+        let output = sandbox::execute_path(&self.policy, "git", git_path, &git_args)?;
+
+        // Git may print file names and commit messages which aren't valid UTF-8, e.g. on Windows
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        match output.status.code() {
+            Some(0) => Ok(String::from_utf8_lossy(&output.stdout).trim().to_string()),
+            Some(_) => {
+                Err(VersionError::GitFailure(format!("Git command failed: {}", stderr)).into())
+            }
+            // Killed by a signal
+            None => Err(VersionError::GitFailure(format!(
+                "Git command was terminated ({}): {}",
+                output.status, stderr
+            ))
+            .into()),
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Backend {
+    /// Spawn `git` processes
+    Cli,
+    /// Query the repository in-process with libgit2, falling back to `git` for other commands.
+    /// Requires the `native-git` feature.
+    Native,
+}
+
+/// The git backend selected at runtime
+pub enum GitBackend {
+    Cli(GitCommand),
+    #[cfg(feature = "native-git")]
+    Native(NativeGitCommand),
+}
+
+impl GitBackend {
+    pub fn new(
+        backend: Backend,
+        policy: &ExecutionConfig,
+        git_path: Option<String>,
+        repo_path: Option<&Path>,
+    ) -> Result<Self> {
+        let git_command = GitCommand {
+            policy: policy.clone(),
+            git_path,
+            repo_path: repo_path.map(Path::to_path_buf),
+        };
+        match backend {
+            Backend::Cli => Ok(Self::Cli(git_command)),
+            #[cfg(feature = "native-git")]
+            Backend::Native => Ok(Self::Native(NativeGitCommand::discover(
+                &match repo_path {
+                    Some(repo_path) => repo_path.to_path_buf(),
+                    None => std::env::current_dir()?,
+                },
+                git_command,
+            )?)),
+            #[cfg(not(feature = "native-git"))]
+            Backend::Native => Err(anyhow::Error::msg(
+                "The native backend requires version-vine to be built with the 'native-git' feature",
+            )),
+        }
+    }
+}
+
+impl GitCommandTrait for GitBackend {
+    fn run(&self, args: Vec<&str>) -> Result<String> {
+        match self {
+            Self::Cli(git_command) => git_command.run(args),
+            #[cfg(feature = "native-git")]
+            Self::Native(git_command) => git_command.run(args),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
 
-        if output.status.code().unwrap() != 0 {
-            return Err(Error::msg(format!(
-                "Git command failed: {}",
-                String::from_utf8(output.stderr)?
-            )));
+    use super::*;
+
+    /// Fake git executable running the shell script
+    #[cfg(unix)]
+    fn fake_git(name: &str, script: &str) -> GitCommand {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(name);
+        fs::write(&path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        GitCommand {
+            git_path: Some(path.to_string_lossy().to_string()),
+            ..Default::default()
         }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_git_command_non_utf8() {
+        let git_command = fake_git(
+            "version-vine-test-git-non-utf8",
+            r"printf 'feature/funci\363n\n'",
+        );
+
+        assert_eq!(
+            git_command.run(vec!["branch", "--show-current"]).unwrap(),
+            "feature/funci\u{fffd}n"
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_git_command_terminated() {
+        let git_command = fake_git("version-vine-test-git-terminated", "kill -9 $$");
+
+        let err = git_command.run(vec!["status"]).unwrap_err().to_string();
+
+        assert!(err.starts_with("Git command was terminated"), "{}", err);
+    }
+
+    #[test]
+    fn test_git_command_repo_path() {
+        let path = std::env::temp_dir().join("version-vine-test-repo-path");
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        let git_command = GitCommand {
+            repo_path: Some(path.clone()),
+            ..Default::default()
+        };
+        git_command.run(vec!["init", "-q"]).unwrap();
+
+        let toplevel = git_command
+            .run(vec!["rev-parse", "--show-toplevel"])
+            .unwrap();
 
-        Ok(String::from_utf8(output.stdout)?.trim().to_string())
+        assert_eq!(
+            fs::canonicalize(toplevel).unwrap(),
+            fs::canonicalize(&path).unwrap()
+        );
     }
 }
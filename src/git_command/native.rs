@@ -0,0 +1,254 @@
+use anyhow::{Error, Result};
+use git2::{DescribeFormatOptions, DescribeOptions, Repository};
+use std::path::Path;
+use std::sync::Mutex;
+
+use super::{GitCommand, GitCommandTrait};
+use crate::error::VersionError;
+
+/// In-process git backend using libgit2. Handles the queries the version calculation runs on
+/// every invocation (branch, SHAs, commit count and tag lookups) without spawning processes, and
+/// delegates all other commands to the `git` CLI.
+pub struct NativeGitCommand {
+    /// libgit2 repositories can't be shared between threads, so the native queries are serialized
+    repository: Mutex<Repository>,
+    fallback: GitCommand,
+}
+
+impl NativeGitCommand {
+    /// Opens the repository containing `path`, like `git` does for the working directory.
+    /// Commands which aren't supported natively run with `fallback`.
+    pub fn discover(path: &Path, fallback: GitCommand) -> Result<Self> {
+        let repository = Repository::discover(path).map_err(git_error)?;
+        Ok(Self {
+            repository: Mutex::new(repository),
+            fallback,
+        })
+    }
+}
+
+fn show_current_branch(repository: &Repository) -> Result<String> {
+    let head = repository.find_reference("HEAD").map_err(git_error)?;
+    // Detached HEADs have no current branch, like `git branch --show-current`
+    let branch = head
+        .symbolic_target()
+        .and_then(|target| target.strip_prefix("refs/heads/"))
+        .unwrap_or_default();
+    Ok(branch.to_string())
+}
+
+fn head_commit(repository: &Repository) -> Result<git2::Commit<'_>> {
+    repository
+        .head()
+        .and_then(|head| head.peel_to_commit())
+        .map_err(git_error)
+}
+
+/// Abbreviated SHA honoring `core.abbrev`, at least `min_length` long
+fn short_sha(repository: &Repository, min_length: Option<usize>) -> Result<String> {
+    let commit = head_commit(repository)?;
+    let short_id = commit.as_object().short_id().map_err(git_error)?;
+    let short_id = short_id.as_str().unwrap_or_default().to_string();
+    let id = commit.id().to_string();
+    Ok(match min_length {
+        // A longer prefix of an unambiguous prefix is unambiguous as well
+        Some(min_length) if min_length > short_id.len() => {
+            id[..min_length.min(id.len())].to_string()
+        }
+        _ => short_id,
+    })
+}
+
+fn count_commits(repository: &Repository) -> Result<String> {
+    let mut revwalk = repository.revwalk().map_err(git_error)?;
+    revwalk.push_head().map_err(git_error)?;
+    Ok(revwalk.count().to_string())
+}
+
+/// `git describe --abbrev=0 --tags [--match <pattern>] [--exact-match] [<rev>]`
+fn describe(
+    repository: &Repository,
+    pattern: Option<&str>,
+    exact_match: bool,
+    rev: &str,
+) -> Result<String> {
+    let mut options = DescribeOptions::new();
+    options.describe_tags();
+    if let Some(pattern) = pattern {
+        options.pattern(pattern);
+    }
+    if exact_match {
+        options.max_candidates_tags(0);
+    }
+    let mut format = DescribeFormatOptions::new();
+    format.abbreviated_size(0);
+    let object = repository.revparse_single(rev).map_err(git_error)?;
+    object
+        .describe(&options)
+        .and_then(|describe| describe.format(Some(&format)))
+        .map_err(git_error)
+}
+
+/// Parses the describe arguments, `None` when some aren't supported natively
+fn run_describe(repository: &Repository, args: &[&str]) -> Option<Result<String>> {
+    let mut pattern = None;
+    let mut tags = false;
+    let mut exact_match = false;
+    let mut rev = "HEAD";
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match *arg {
+            "--abbrev=0" => {}
+            "--tags" => tags = true,
+            "--exact-match" => exact_match = true,
+            "--match" => pattern = Some(*args.next()?),
+            arg if !arg.starts_with('-') => rev = arg,
+            _ => return None,
+        }
+    }
+    if !tags {
+        return None;
+    }
+    Some(describe(repository, pattern, exact_match, rev))
+}
+
+impl GitCommandTrait for NativeGitCommand {
+    fn run(&self, args: Vec<&str>) -> Result<String> {
+        let repository = || self.repository.lock().unwrap();
+        match args.as_slice() {
+            ["branch", "--show-current"] => show_current_branch(&repository()),
+            ["rev-parse", "HEAD"] => Ok(head_commit(&repository())?.id().to_string()),
+            ["rev-parse", "--short", "HEAD"] => short_sha(&repository(), None),
+            ["rev-parse", short, "HEAD"] if short.starts_with("--short=") => {
+                let min_length = short["--short=".len()..].parse::<usize>()?;
+                short_sha(&repository(), Some(min_length))
+            }
+            ["rev-list", "--count", "HEAD"] => count_commits(&repository()),
+            ["rev-parse", "--is-shallow-repository"] => Ok(repository().is_shallow().to_string()),
+            ["describe", "--abbrev=0", describe_args @ ..] => {
+                // Released before falling back, which may take a while
+                let described = run_describe(&repository(), describe_args);
+                match described {
+                    Some(result) => result,
+                    None => self.fallback.run(args),
+                }
+            }
+            _ => self.fallback.run(args),
+        }
+    }
+}
+
+fn git_error(err: git2::Error) -> Error {
+    VersionError::GitFailure(format!("Git command failed: {}", err.message())).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use git2::Signature;
+    use std::fs;
+
+    use super::*;
+
+    /// Repository with two commits, the first tagged `1.0.0` and the second `other-2.0.0`
+    fn repository(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&path);
+        let repository = Repository::init(&path).unwrap();
+        let signature = Signature::now("test", "test@example.com").unwrap();
+        let tree_id = repository.index().unwrap().write_tree().unwrap();
+        let tree = repository.find_tree(tree_id).unwrap();
+        let first = repository
+            .commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])
+            .unwrap();
+        let first = repository.find_commit(first).unwrap();
+        repository
+            .tag_lightweight("1.0.0", first.as_object(), false)
+            .unwrap();
+        let second = repository
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                "feat: x",
+                &tree,
+                &[&first],
+            )
+            .unwrap();
+        let second = repository.find_object(second, None).unwrap();
+        repository
+            .tag_lightweight("other-2.0.0", &second, false)
+            .unwrap();
+        path
+    }
+
+    #[test]
+    fn test_native_queries() {
+        let path = repository("version-vine-test-native-queries");
+        let git_command = NativeGitCommand::discover(&path, GitCommand::default()).unwrap();
+        let head = git_command.run(vec!["rev-parse", "HEAD"]).unwrap();
+
+        let repository = Repository::open(&path).unwrap();
+        assert_eq!(
+            git_command.run(vec!["branch", "--show-current"]).unwrap(),
+            repository.head().unwrap().shorthand().unwrap()
+        );
+        assert_eq!(
+            git_command
+                .run(vec!["rev-list", "--count", "HEAD"])
+                .unwrap(),
+            "2"
+        );
+        assert_eq!(
+            git_command
+                .run(vec!["rev-parse", "--is-shallow-repository"])
+                .unwrap(),
+            "false"
+        );
+        assert!(head.starts_with(
+            &git_command
+                .run(vec!["rev-parse", "--short", "HEAD"])
+                .unwrap()
+        ));
+        assert_eq!(
+            git_command
+                .run(vec!["rev-parse", "--short=12", "HEAD"])
+                .unwrap(),
+            head[..12]
+        );
+    }
+
+    #[test]
+    fn test_native_describe() {
+        let path = repository("version-vine-test-native-describe");
+        let git_command = NativeGitCommand::discover(&path, GitCommand::default()).unwrap();
+
+        assert_eq!(
+            git_command
+                .run(vec!["describe", "--abbrev=0", "--tags"])
+                .unwrap(),
+            "other-2.0.0"
+        );
+        assert_eq!(
+            git_command
+                .run(vec!["describe", "--abbrev=0", "--match", "1.*", "--tags"])
+                .unwrap(),
+            "1.0.0"
+        );
+        assert!(git_command
+            .run(vec![
+                "describe",
+                "--abbrev=0",
+                "--match",
+                "1.*",
+                "--exact-match",
+                "--tags"
+            ])
+            .is_err());
+        assert_eq!(
+            git_command
+                .run(vec!["describe", "--abbrev=0", "--tags", "HEAD^"])
+                .unwrap(),
+            "1.0.0"
+        );
+    }
+}
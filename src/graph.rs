@@ -0,0 +1,245 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use semver::Version;
+
+use crate::git_command::GitCommandTrait;
+use crate::models::{BranchClass, Regexes};
+use crate::version_calculator::describe_tag;
+use crate::{VersionInfo, VersionOptions};
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum GraphFormat {
+    /// Graphviz DOT
+    Dot,
+    /// Mermaid flowchart, rendered by GitHub and GitLab markdown
+    Mermaid,
+}
+
+#[derive(Debug, PartialEq)]
+struct Node {
+    /// Tag or branch name, `HEAD` for the current checkout
+    name: String,
+    label: String,
+    kind: NodeKind,
+}
+
+#[derive(Debug, PartialEq)]
+enum NodeKind {
+    Tag,
+    Branch,
+    Head,
+}
+
+/// Graph of the release tags, release/hotfix branches and `HEAD`, each pointing from the latest
+/// tag they're based on. Shows where the computed versions come from.
+pub fn version_graph(
+    git_command: &impl GitCommandTrait,
+    options: &VersionOptions,
+    version_info: &VersionInfo,
+    format: GraphFormat,
+) -> Result<String> {
+    let regexes = Regexes::new(&options.app_name, &options.config)?;
+
+    let mut tags: Vec<(Version, String)> = git_command
+        .run(vec!["tag", "--list"])?
+        .lines()
+        .filter_map(|tag| {
+            let version = regexes.tag.captures(tag)?.name("version")?.as_str();
+            Some((Version::parse(version).ok()?, tag.to_string()))
+        })
+        .collect();
+    tags.sort();
+    let branches: Vec<String> = git_command
+        .run(vec![
+            "for-each-ref",
+            "--format=%(refname:short)",
+            "refs/heads",
+        ])?
+        .lines()
+        .filter(|branch| {
+            matches!(
+                regexes.branch_class(branch),
+                BranchClass::Release | BranchClass::Hotfix | BranchClass::Support
+            )
+        })
+        .map(|branch| branch.to_string())
+        .collect();
+
+    let mut nodes: Vec<Node> = vec![];
+    let mut edges: Vec<(usize, usize)> = vec![];
+    for (_, tag) in &tags {
+        nodes.push(Node {
+            name: tag.clone(),
+            label: tag.clone(),
+            kind: NodeKind::Tag,
+        });
+    }
+    for branch in &branches {
+        nodes.push(Node {
+            name: branch.clone(),
+            label: branch.clone(),
+            kind: NodeKind::Branch,
+        });
+    }
+    nodes.push(Node {
+        name: "HEAD".to_string(),
+        label: format!(
+            "HEAD ({})\n{}",
+            version_info.git_branch, version_info.version
+        ),
+        kind: NodeKind::Head,
+    });
+
+    let index_of = |nodes: &[Node], name: &str| nodes.iter().position(|node| node.name == name);
+    for (index, node) in nodes.iter().enumerate() {
+        let parent = match node.kind {
+            NodeKind::Tag => nearest_tag(git_command, &regexes, &format!("{}^", node.name)),
+            NodeKind::Branch => nearest_tag(git_command, &regexes, &node.name),
+            // HEAD continues its release branch, when it's on one
+            NodeKind::Head => match index_of(&nodes, &version_info.git_branch) {
+                Some(_) => Some(version_info.git_branch.clone()),
+                None => nearest_tag(git_command, &regexes, "HEAD"),
+            },
+        };
+        if let Some(parent) = parent.and_then(|parent| index_of(&nodes, &parent)) {
+            edges.push((parent, index));
+        }
+    }
+
+    Ok(match format {
+        GraphFormat::Dot => render_dot(&nodes, &edges),
+        GraphFormat::Mermaid => render_mermaid(&nodes, &edges),
+    })
+}
+
+fn nearest_tag(git_command: &impl GitCommandTrait, regexes: &Regexes, rev: &str) -> Option<String> {
+    describe_tag(git_command, regexes, false, Some(rev)).ok()
+}
+
+fn render_dot(nodes: &[Node], edges: &[(usize, usize)]) -> String {
+    let mut lines = vec![
+        "digraph versions {".to_string(),
+        "  rankdir=LR;".to_string(),
+    ];
+    for (index, node) in nodes.iter().enumerate() {
+        let shape = match node.kind {
+            NodeKind::Tag => "ellipse",
+            NodeKind::Branch => "box",
+            NodeKind::Head => "doubleoctagon",
+        };
+        let label = node
+            .label
+            .replace('\\', r"\\")
+            .replace('"', "\\\"")
+            .replace('\n', r"\n");
+        lines.push(format!(
+            "  n{} [label=\"{}\", shape={}];",
+            index, label, shape
+        ));
+    }
+    for (from, to) in edges {
+        lines.push(format!("  n{} -> n{};", from, to));
+    }
+    lines.push("}".to_string());
+    lines.join("\n")
+}
+
+fn render_mermaid(nodes: &[Node], edges: &[(usize, usize)]) -> String {
+    let mut lines = vec!["flowchart LR".to_string()];
+    for (index, node) in nodes.iter().enumerate() {
+        let label = node.label.replace('"', "#quot;").replace('\n', "<br/>");
+        let node = match node.kind {
+            NodeKind::Tag => format!("(\"{}\")", label),
+            NodeKind::Branch => format!("[\"{}\"]", label),
+            NodeKind::Head => format!("{{{{\"{}\"}}}}", label),
+        };
+        lines.push(format!("  n{}{}", index, node));
+    }
+    for (from, to) in edges {
+        lines.push(format!("  n{} --> n{}", from, to));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+
+    use crate::git_command::MockGitCommandTrait;
+
+    use super::*;
+    use crate::version_calculator::version_info;
+
+    fn mock_git(git_command: &mut MockGitCommandTrait) {
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "tag")
+            .returning(|_| Ok("1.1.0\n1.0.0\nother-2.0.0\nlatest".to_string()));
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "for-each-ref")
+            .returning(|_| Ok("develop\nrelease/2.0.0\nmain".to_string()));
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "describe" && args[3] == "1.0.0^")
+            .returning(|_| Err(Error::msg("No names found")));
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "describe" && args[3] == "1.1.0^")
+            .returning(|_| Ok("1.0.0".to_string()));
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "describe" && args[3] != "1.0.0^" && args[3] != "1.1.0^")
+            .returning(|_| Ok("1.1.0".to_string()));
+    }
+
+    #[test]
+    fn test_version_graph_mermaid() {
+        let mut git_command = MockGitCommandTrait::new();
+        mock_git(&mut git_command);
+
+        let graph = version_graph(
+            &git_command,
+            &VersionOptions::default(),
+            &version_info("release/2.0.0", "2.0.0-rc.3+1234567"),
+            GraphFormat::Mermaid,
+        )
+        .unwrap();
+
+        assert_eq!(
+            graph,
+            [
+                "flowchart LR",
+                "  n0(\"1.0.0\")",
+                "  n1(\"1.1.0\")",
+                "  n2[\"release/2.0.0\"]",
+                "  n3{{\"HEAD (release/2.0.0)<br/>2.0.0-rc.3+1234567\"}}",
+                "  n0 --> n1",
+                "  n1 --> n2",
+                "  n2 --> n3",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_version_graph_dot() {
+        let mut git_command = MockGitCommandTrait::new();
+        mock_git(&mut git_command);
+
+        let graph = version_graph(
+            &git_command,
+            &VersionOptions::default(),
+            &version_info("develop", "1.1.1-beta.3+1234567"),
+            GraphFormat::Dot,
+        )
+        .unwrap();
+
+        assert!(graph.starts_with("digraph versions {\n"));
+        assert!(graph.contains(
+            "  n3 [label=\"HEAD (develop)\\n1.1.1-beta.3+1234567\", shape=doubleoctagon];"
+        ));
+        assert!(graph.contains("  n1 -> n3;"));
+        assert!(graph.ends_with("}"));
+    }
+}
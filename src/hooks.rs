@@ -0,0 +1,230 @@
+use anyhow::{Error, Result};
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use crate::config::{HookConfig, HookEvent, HookFailure};
+use crate::output::{export_variables, render_template};
+use crate::sandbox;
+use crate::version_calculator::app_options;
+use crate::{VersionInfo, VersionOptions};
+
+/// Runs the `[[hooks]]` of the event in order. `post-compute` hooks get the output fields as
+/// `VERSION_VINE_*` environment variables, as `--export-env`, and their arguments are rendered as
+/// templates. The programs have to be in the `allowed_commands` of the execution policy, and run
+/// with a scrubbed environment unless the policy's `scrub_env` is `false`.
+///
+/// The output of the hooks goes to stderr, so it can't corrupt the version output on stdout. A
+/// failing hook fails the run, unless its `on_failure` is `warn` or `ignore`.
+pub fn run_hooks(
+    options: &VersionOptions,
+    event: HookEvent,
+    version_info: Option<&VersionInfo>,
+) -> Result<()> {
+    let hooks = options
+        .config
+        .hooks
+        .iter()
+        .filter(|hook| hook.event == event);
+    for hook in hooks {
+        let result = run_hook(options, hook, version_info);
+        match (result, hook.on_failure) {
+            (Ok(()), _) | (Err(_), HookFailure::Ignore) => {}
+            (Err(err), HookFailure::Fail) => return Err(err),
+            (Err(err), HookFailure::Warn) => eprintln!("Warning: {}", err),
+        }
+    }
+    Ok(())
+}
+
+/// Runs the `post-compute` hooks of each application of `calculate_apps`, with the configuration
+/// of the application
+pub fn run_app_hooks(
+    options: &VersionOptions,
+    versions: &BTreeMap<String, VersionInfo>,
+) -> Result<()> {
+    for (app, version_info) in versions {
+        run_hooks(
+            &app_options(options, app),
+            HookEvent::PostCompute,
+            Some(version_info),
+        )?;
+    }
+    Ok(())
+}
+
+fn run_hook(
+    options: &VersionOptions,
+    hook: &HookConfig,
+    version_info: Option<&VersionInfo>,
+) -> Result<()> {
+    let name = match hook.event {
+        HookEvent::PreCompute => "pre-compute",
+        HookEvent::PostCompute => "post-compute",
+    };
+    let failed = |reason: String| {
+        Error::msg(format!(
+            "The {} hook '{}' failed: {}",
+            name,
+            hook.command.join(" "),
+            reason
+        ))
+    };
+    let (program, args) = hook
+        .command
+        .split_first()
+        .ok_or(failed("the command is empty".to_string()))?;
+    let mut env = vec![("VERSION_VINE_HOOK".to_string(), name.to_string())];
+    let args = match version_info {
+        Some(version_info) => {
+            env.extend(export_variables(version_info, options));
+            args.iter()
+                .map(|arg| render_template(version_info, options, arg))
+                .collect::<Result<Vec<_>>>()
+                .map_err(|err| failed(err.to_string()))?
+        }
+        None => {
+            env.push((
+                "VERSION_VINE_APP_NAME".to_string(),
+                options.app_name.clone().unwrap_or_default(),
+            ));
+            args.to_vec()
+        }
+    };
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let mut policy = options.config.execution.clone();
+    policy.scrub_env = Some(policy.scrub_env.unwrap_or(true));
+    // The hook timeout comes from the repository, so it can only shorten the trusted one
+    let timeout_seconds = policy
+        .timeout_seconds
+        .map_or(hook.timeout_seconds, |max| max.min(hook.timeout_seconds));
+    let output = sandbox::execute_env(&policy, program, &args, &env, timeout_seconds)
+        .map_err(|err| failed(err.to_string()))?;
+    let mut stderr = io::stderr().lock();
+    stderr.write_all(&output.stdout)?;
+    stderr.write_all(&output.stderr)?;
+    match output.status.success() {
+        true => Ok(()),
+        false => Err(failed(format!("{}", output.status))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{Config, UserConfig};
+
+    use std::fs;
+
+    use super::*;
+
+    fn version_info() -> VersionInfo {
+        crate::version_calculator::version_info("develop", "1.2.4-beta.1+1234567")
+    }
+
+    fn options(hooks: &str) -> VersionOptions {
+        let mut options = VersionOptions::default();
+        // The repository configuration can't allow the programs itself
+        let user_config = UserConfig::parse("[execution]\nallowed_commands = ['sh']").unwrap();
+        options.apply_config(Config::parse_with_defaults(hooks, &user_config.defaults).unwrap());
+        options
+    }
+
+    #[test]
+    fn test_run_hooks() {
+        let dir = std::env::temp_dir().join(format!("version-vine-hooks-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let stamp = dir.join("stamp.txt");
+        let options = options(&format!(
+            r#"
+[[hooks]]
+event = "post-compute"
+command = ["sh", "-c", "echo \"$VERSION_VINE_APP_VERSION $1 $VERSION_VINE_HOOK\" > '{}'", "sh", "{{{{container_tag}}}}"]
+
+[[hooks]]
+event = "pre-compute"
+command = ["sh", "-c", "exit 1"]
+on_failure = "ignore"
+"#,
+            stamp.display()
+        ));
+
+        run_hooks(&options, HookEvent::PreCompute, None).unwrap();
+        assert!(!stamp.exists());
+        run_hooks(&options, HookEvent::PostCompute, Some(&version_info())).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&stamp).unwrap(),
+            "1.2.4-beta.1+1234567 1.2.4-beta.1.1234567 post-compute\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_run_hooks_scrub_env() {
+        std::env::set_var("VERSION_VINE_HOOK_SECRET", "token");
+        let hook = "[[hooks]]\nevent = \"pre-compute\"\ncommand = [\"sh\", \"-c\", \"test -z \\\"$VERSION_VINE_HOOK_SECRET\\\"\"]";
+        let run = |user_config: &str, config: &str| {
+            let user_config = UserConfig::parse(user_config).unwrap();
+            let mut options = VersionOptions::default();
+            options.apply_config(
+                Config::parse_with_defaults(
+                    &format!("{}\n{}", config, hook),
+                    &user_config.defaults,
+                )
+                .unwrap(),
+            );
+            run_hooks(&options, HookEvent::PreCompute, None)
+        };
+        let allowed = "[execution]\nallowed_commands = ['sh']";
+
+        assert!(run(allowed, "").is_ok());
+        // The repository can't opt out of scrubbing
+        assert!(run(allowed, "[execution]\nscrub_env = false").is_ok());
+        assert!(run(&format!("{}\nscrub_env = false", allowed), "").is_err());
+    }
+
+    #[test]
+    fn test_run_hooks_timeout_capped() {
+        let user_config =
+            UserConfig::parse("[execution]\nallowed_commands = ['sh']\ntimeout_seconds = 1")
+                .unwrap();
+        let mut options = VersionOptions::default();
+        options.apply_config(
+            Config::parse_with_defaults(
+                "[[hooks]]\nevent = \"pre-compute\"\ncommand = [\"sh\", \"-c\", \"sleep 5\"]\ntimeout_seconds = 600",
+                &user_config.defaults,
+            )
+            .unwrap(),
+        );
+
+        assert!(run_hooks(&options, HookEvent::PreCompute, None)
+            .unwrap_err()
+            .to_string()
+            .contains("timed out after 1 seconds"));
+    }
+
+    #[test]
+    fn test_run_hooks_failure() {
+        let run = |hook: &str| {
+            let options = options(&format!("[[hooks]]\nevent = \"pre-compute\"\n{}", hook));
+            run_hooks(&options, HookEvent::PreCompute, None)
+        };
+
+        assert_eq!(
+            run(r#"command = ["sh", "-c", "exit 3"]"#)
+                .unwrap_err()
+                .to_string(),
+            "The pre-compute hook 'sh -c exit 3' failed: exit status: 3"
+        );
+        assert!(
+            run("command = [\"sh\", \"-c\", \"sleep 5\"]\ntimeout_seconds = 1")
+                .unwrap_err()
+                .to_string()
+                .contains("timed out after 1 seconds")
+        );
+        assert!(run(r#"command = ["curl", "https://example.com"]"#)
+            .unwrap_err()
+            .to_string()
+            .contains("Command 'curl' is not allowed by the execution policy"));
+        assert!(run("command = [\"sh\", \"-c\", \"exit 3\"]\non_failure = \"warn\"").is_ok());
+    }
+}
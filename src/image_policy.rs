@@ -0,0 +1,250 @@
+use anyhow::{Error, Result};
+use regex_lite::Regex;
+use semver::{BuildMetadata, Comparator, Op, Prerelease, Version, VersionReq};
+use serde_json::{json, Value};
+
+/// An image automation policy as configured for Flux (`ImagePolicy`) or Argo CD Image Updater.
+/// Both filter candidate tags with a regex, optionally extract part of the tag, and order the
+/// remaining tags by a SemVer range.
+pub struct ImagePolicy {
+    pub pattern: Option<Regex>,
+    pub extract: Option<String>,
+    pub semver_range: Option<Vec<VersionReq>>,
+}
+
+impl ImagePolicy {
+    pub fn new(
+        pattern: &Option<String>,
+        extract: &Option<String>,
+        semver_range: &Option<String>,
+    ) -> Result<Self, Error> {
+        let pattern = match pattern {
+            Some(p) => Some(Regex::new(&normalize_pattern(p)).map_err(|err| {
+                Error::msg(format!("Invalid image policy pattern '{}': {}", p, err))
+            })?),
+            None => None,
+        };
+        let semver_range = match semver_range {
+            Some(range) => Some(parse_range(range)?),
+            None => None,
+        };
+        Ok(Self {
+            pattern,
+            extract: extract.clone(),
+            semver_range,
+        })
+    }
+
+    /// Evaluates the tag the same way the image automation controllers would and reports
+    /// whether it would be selected.
+    pub fn check(&self, container_tag: &str) -> Value {
+        let mut reasons: Vec<String> = Vec::new();
+        let mut candidate = container_tag.to_string();
+
+        let matches_pattern =
+            self.pattern
+                .as_ref()
+                .map(|pattern| match pattern.captures(container_tag) {
+                    Some(caps) => {
+                        if let Some(extract) = &self.extract {
+                            let mut extracted = String::new();
+                            caps.expand(extract, &mut extracted);
+                            candidate = extracted;
+                        }
+                        true
+                    }
+                    None => {
+                        reasons.push(format!(
+                            "Tag '{}' does not match pattern '{}'",
+                            container_tag,
+                            pattern.as_str()
+                        ));
+                        false
+                    }
+                });
+
+        let satisfies_range = self.semver_range.as_ref().map(|range| {
+            if matches_pattern == Some(false) {
+                return false;
+            }
+            match Version::parse(candidate.trim_start_matches('v')) {
+                Ok(version) => {
+                    let satisfied = range.iter().any(|req| range_matches(req, &version));
+                    if !satisfied {
+                        reasons.push(format!(
+                            "Version '{}' does not satisfy range '{}'",
+                            version,
+                            range
+                                .iter()
+                                .map(|req| req.to_string())
+                                .collect::<Vec<_>>()
+                                .join(" || ")
+                        ));
+                    }
+                    satisfied
+                }
+                Err(err) => {
+                    reasons.push(format!(
+                        "Tag '{}' cannot be parsed to SemVer Version: {}",
+                        candidate, err
+                    ));
+                    false
+                }
+            }
+        });
+
+        let picked_up = matches_pattern.unwrap_or(true) && satisfies_range.unwrap_or(true);
+        json!({
+            "container_tag": container_tag,
+            "evaluated_tag": candidate,
+            "matches_pattern": matches_pattern,
+            "satisfies_range": satisfies_range,
+            "picked_up": picked_up,
+            "reasons": reasons,
+        })
+    }
+}
+
+/// Masterminds semver only considers prereleases when the range itself contains a prerelease,
+/// but then orders them like any other version, unlike `VersionReq::matches`.
+pub(crate) fn range_matches(req: &VersionReq, version: &Version) -> bool {
+    if req.comparators.iter().all(|c| c.pre.is_empty()) {
+        return req.matches(version);
+    }
+    req.comparators
+        .iter()
+        .all(|c| comparator_matches(c, version))
+}
+
+fn comparator_matches(comparator: &Comparator, version: &Version) -> bool {
+    let mut version = version.clone();
+    version.build = BuildMetadata::EMPTY;
+    match (comparator.minor, comparator.patch) {
+        (Some(minor), Some(patch)) => {
+            let mut bound = Version::new(comparator.major, minor, patch);
+            bound.pre = comparator.pre.clone();
+            match comparator.op {
+                Op::Exact => version == bound,
+                Op::Greater => version > bound,
+                Op::GreaterEq => version >= bound,
+                Op::Less => version < bound,
+                Op::LessEq => version <= bound,
+                _ => {
+                    version.pre = Prerelease::EMPTY;
+                    comparator.matches(&version)
+                }
+            }
+        }
+        _ => {
+            version.pre = Prerelease::EMPTY;
+            comparator.matches(&version)
+        }
+    }
+}
+
+/// Argo CD Image Updater prefixes allow-tags patterns with `regexp:`
+fn normalize_pattern(pattern: &str) -> String {
+    pattern
+        .strip_prefix("regexp:")
+        .unwrap_or(pattern)
+        .to_string()
+}
+
+/// Converts a Masterminds style range (as used by Flux and Argo CD) to one or more
+/// `VersionReq`s. E.g. `>= 1.0.0 < 2.0.0 || 3.x` or `1.2 - 1.4`.
+pub(crate) fn parse_range(range: &str) -> Result<Vec<VersionReq>, Error> {
+    let operator_spacing = Regex::new(r"([<>=~^!]+)\s+").unwrap();
+    let hyphen_range = Regex::new(r"^\s*(?<from>\S+)\s+-\s+(?<to>\S+)\s*$").unwrap();
+
+    range
+        .split("||")
+        .map(|alternative| {
+            let comparators = match hyphen_range.captures(alternative) {
+                Some(caps) => vec![format!(">={}", &caps["from"]), format!("<={}", &caps["to"])],
+                None => operator_spacing
+                    .replace_all(alternative, "$1")
+                    .split(|c: char| c.is_whitespace() || c == ',')
+                    .filter(|comparator| !comparator.is_empty())
+                    .map(|comparator| comparator.replace(['x', 'X'], "*"))
+                    .collect(),
+            };
+            VersionReq::parse(&comparators.join(", ")).map_err(|err| {
+                Error::msg(format!(
+                    "Invalid SemVer range '{}': {}",
+                    alternative.trim(),
+                    err
+                ))
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_semver_range_picked_up() {
+        let policy = ImagePolicy::new(&None, &None, &Some(String::from(">=1.0.0 <2.0.0"))).unwrap();
+
+        let report = policy.check("1.2.3");
+
+        assert_eq!(report["picked_up"], true);
+        assert_eq!(report["satisfies_range"], true);
+        assert_eq!(report["matches_pattern"], Value::Null);
+    }
+
+    #[test]
+    fn test_check_semver_range_out_of_range() {
+        let policy = ImagePolicy::new(&None, &None, &Some(String::from("~1.2 || 3.x"))).unwrap();
+
+        assert_eq!(policy.check("3.1.0")["picked_up"], true);
+        assert_eq!(policy.check("2.0.0")["picked_up"], false);
+    }
+
+    #[test]
+    fn test_check_semver_range_with_prerelease() {
+        let policy = ImagePolicy::new(&None, &None, &Some(String::from(">=1.0.0-0"))).unwrap();
+
+        assert_eq!(policy.check("1.0.1-beta.2.1234567")["picked_up"], true);
+        assert_eq!(policy.check("0.9.0-beta.2")["picked_up"], false);
+    }
+
+    #[test]
+    fn test_check_build_metadata_tag_not_semver() {
+        let policy = ImagePolicy::new(&None, &None, &Some(String::from(">=1.0.0"))).unwrap();
+
+        let report = policy.check("1.2.3.1234567");
+
+        assert_eq!(report["picked_up"], false);
+        assert_eq!(report["satisfies_range"], false);
+    }
+
+    #[test]
+    fn test_check_pattern_with_extract() {
+        let policy = ImagePolicy::new(
+            &Some(String::from(
+                r"regexp:^(?P<version>\d+\.\d+\.\d+)\.[a-f0-9]+$",
+            )),
+            &Some(String::from("$version")),
+            &Some(String::from("1.2.1 - 1.4.0")),
+        )
+        .unwrap();
+
+        let report = policy.check("1.2.3.1234567");
+
+        assert_eq!(report["picked_up"], true);
+        assert_eq!(report["evaluated_tag"], "1.2.3");
+    }
+
+    #[test]
+    fn test_check_pattern_mismatch() {
+        let policy =
+            ImagePolicy::new(&Some(String::from(r"^\d+\.\d+\.\d+$")), &None, &None).unwrap();
+
+        let report = policy.check("1.2.4-alpha.3.feature-feat-1.1234567");
+
+        assert_eq!(report["picked_up"], false);
+        assert_eq!(report["matches_pattern"], false);
+    }
+}
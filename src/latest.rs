@@ -0,0 +1,114 @@
+use anyhow::{Error, Result};
+use serde_json::{json, Value};
+
+use crate::git_command::GitCommandTrait;
+use crate::models::Regexes;
+use crate::version_calculator::version_tags;
+use crate::VersionOptions;
+
+/// Highest version tag of the app by SemVer precedence, of all tags rather than the nearest one
+/// `git describe` finds. Only the releases, unless `include_prerelease`, and only those of the
+/// `line` when provided, e.g. `1.2` for the `1.2.x` releases or `1` for the `1.x` ones.
+pub fn latest_release(
+    git_command: &impl GitCommandTrait,
+    options: &VersionOptions,
+    line: Option<&str>,
+    include_prerelease: bool,
+) -> Result<Value> {
+    let regexes = Regexes::new(&options.app_name, &options.config)?;
+    let parts = line.map(parse_line).transpose()?;
+    let latest = version_tags(git_command, &regexes, None)?
+        .into_iter()
+        .find(|(_, version)| {
+            (include_prerelease || version.pre.is_empty())
+                && parts.as_ref().is_none_or(|parts| {
+                    version.major == parts[0]
+                        && parts.get(1).is_none_or(|minor| version.minor == *minor)
+                })
+        });
+    let kind = match include_prerelease {
+        true => "version",
+        false => "release",
+    };
+    match latest {
+        Some((tag, version)) => Ok(json!({
+            "tag": tag,
+            "version": version.to_string(),
+            "line": line,
+        })),
+        None => Err(Error::msg(match line {
+            Some(line) => format!("No {} tag of the {}.x line found", kind, line),
+            None => format!("No {} tag found", kind),
+        })),
+    }
+}
+
+/// The major, and optionally minor, version of a `--line`
+fn parse_line(line: &str) -> Result<Vec<u64>> {
+    line.split('.')
+        .map(|part| part.parse::<u64>())
+        .collect::<Result<Vec<_>, _>>()
+        .ok()
+        .filter(|parts| (1..=2).contains(&parts.len()))
+        .ok_or(Error::msg(format!(
+            "Line '{}' isn't a major or major.minor version, e.g. 1 or 1.2",
+            line
+        )))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::git_command::MockGitCommandTrait;
+
+    use super::*;
+
+    #[test]
+    fn test_latest_release() {
+        let mut git_command = MockGitCommandTrait::new();
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "for-each-ref --format=%(refname:lstrip=2) refs/tags")
+            .returning(|_| {
+                Ok(["1.10.0", "1.2.3", "1.9.0", "2.0.0-rc.1", "1.2.10", "latest"].join("\n"))
+            });
+        let latest = |line: Option<&str>, include_prerelease: bool| {
+            latest_release(
+                &git_command,
+                &VersionOptions::default(),
+                line,
+                include_prerelease,
+            )
+        };
+
+        assert_eq!(
+            latest(None, false).unwrap(),
+            json!({"tag": "1.10.0", "version": "1.10.0", "line": null})
+        );
+        assert_eq!(latest(None, true).unwrap()["version"], "2.0.0-rc.1");
+        assert_eq!(latest(Some("1.2"), false).unwrap()["tag"], "1.2.10");
+        assert_eq!(latest(Some("1"), true).unwrap()["tag"], "1.10.0");
+        assert_eq!(
+            latest(Some("2"), false).unwrap_err().to_string(),
+            "No release tag of the 2.x line found"
+        );
+        assert!(latest(Some("1.x"), false).is_err());
+    }
+
+    #[test]
+    fn test_latest_release_app() {
+        let mut git_command = MockGitCommandTrait::new();
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "for-each-ref")
+            .returning(|_| Ok(["api-1.4.0", "web-2.0.0", "api-1.3.9"].join("\n")));
+        let options = VersionOptions {
+            app_name: Some("api".to_string()),
+            ..Default::default()
+        };
+
+        let latest = latest_release(&git_command, &options, None, false).unwrap();
+
+        assert_eq!(latest["tag"], "api-1.4.0");
+        assert_eq!(latest["version"], "1.4.0");
+    }
+}
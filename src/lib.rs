@@ -0,0 +1,86 @@
+//! Git flow opinionated SemVer version calculation.
+//!
+//! The [`VersionCalculator`] computes the version for the current git checkout, the same way the
+//! `version-vine` CLI does:
+//!
+//! ```no_run
+//! use version_vine::git_command::GitCommand;
+//! use version_vine::{VersionCalculator, VersionOptions};
+//!
+//! let git_command = GitCommand::default();
+//! let options = VersionOptions {
+//!     app_name: Some("myapp".to_string()),
+//!     ..Default::default()
+//! };
+//! let version_info = VersionCalculator::new(&git_command, options)?.calculate()?;
+//! println!("{}", version_info.version);
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+pub mod artifacts;
+pub mod audit;
+pub mod batch;
+pub mod branch_resolver;
+#[cfg(feature = "forge")]
+pub mod bump_pr;
+pub mod cache;
+pub mod changelog;
+pub mod ci;
+pub mod compare;
+pub mod components;
+pub mod config;
+pub mod constraints;
+pub mod container_tag;
+pub mod conventional_commits;
+pub mod diagnostics;
+pub mod doctor;
+pub mod dotnet;
+pub mod error;
+pub mod explain;
+#[cfg(feature = "forge")]
+pub mod forge;
+pub mod git_command;
+pub mod graph;
+pub mod hooks;
+pub mod image_policy;
+pub mod latest;
+pub mod log;
+pub mod manifest_plan;
+pub mod messages;
+#[cfg(feature = "forge")]
+pub mod metrics;
+pub mod models;
+pub mod next;
+pub mod output;
+#[cfg(feature = "forge")]
+pub mod pr_comment;
+pub mod promote;
+pub mod provenance;
+#[cfg(feature = "forge")]
+pub mod release;
+pub mod release_notes;
+pub mod release_plan;
+pub mod sandbox;
+pub mod schema;
+pub mod scheme;
+#[cfg(feature = "self-update")]
+pub mod self_update;
+pub mod set_version;
+pub mod start_release;
+pub mod stats;
+pub mod support_bundle;
+pub mod tag;
+#[cfg(feature = "otel")]
+pub mod telemetry;
+pub mod trace;
+pub mod transliterate;
+pub mod ui;
+pub mod validate;
+pub mod verify;
+mod version_calculator;
+
+pub use version_calculator::{
+    calculate_apps, BumpStrategy, Clock, CommitMetadata, CountStrategy, Counter, DateSource, Dirty,
+    EmptyBranch, FixedClock, MergeStrategy, OnTag, RequireSignature, SystemClock, UniquePrerelease,
+    VersionCalculator, VersionInfo, VersionOptions, VersionSource,
+};
@@ -0,0 +1,165 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use serde_json::{json, Map, Value};
+use std::time::Instant;
+
+use crate::git_command::GitCommandTrait;
+
+/// Characters of the git output logged with `-vv`
+const MAX_OUTPUT_LENGTH: usize = 500;
+
+/// Format of the `--verbose` log lines on stderr
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum LogFormat {
+    /// E.g. `debug git: describe --abbrev=0 --tags duration_ms=3 status="ok"`
+    Text,
+    /// A JSON object per line, e.g. for log collectors
+    Json,
+}
+
+/// Verbosity of a log event, the number of `-v` it's logged from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Level {
+    Debug = 1,
+    Trace = 2,
+}
+
+impl Level {
+    fn name(self) -> &'static str {
+        match self {
+            Level::Debug => "debug",
+            Level::Trace => "trace",
+        }
+    }
+}
+
+/// Structured log of the run on stderr, so stdout keeps only the version output. Nothing is
+/// logged without `-v`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Logger {
+    pub verbosity: u8,
+    pub format: LogFormat,
+}
+
+impl Logger {
+    pub fn new(verbosity: u8, format: LogFormat) -> Self {
+        Self { verbosity, format }
+    }
+
+    pub fn enabled(&self, level: Level) -> bool {
+        self.verbosity >= level as u8
+    }
+
+    /// Logs an event of the `target`, e.g. `git`, with the fields of the `fields` object
+    pub fn log(&self, level: Level, target: &str, message: &str, fields: Value) {
+        if self.enabled(level) {
+            eprintln!("{}", self.line(level, target, message, fields));
+        }
+    }
+
+    fn line(&self, level: Level, target: &str, message: &str, fields: Value) -> String {
+        let fields = match fields {
+            Value::Object(fields) => fields,
+            _ => Map::new(),
+        };
+        match self.format {
+            LogFormat::Text => {
+                let mut line = format!("{} {}: {}", level.name(), target, message);
+                for (name, value) in &fields {
+                    line.push_str(&format!(" {}={}", name, value));
+                }
+                line
+            }
+            LogFormat::Json => {
+                let mut line = json!({
+                    "level": level.name(),
+                    "target": target,
+                    "message": message,
+                });
+                line.as_object_mut().unwrap().extend(fields);
+                line.to_string()
+            }
+        }
+    }
+}
+
+/// Logs every git invocation of the wrapped git command at debug level: the command, its duration
+/// and whether it succeeded. `-vv` also logs the trimmed output.
+pub struct LoggedGitCommand<G: GitCommandTrait> {
+    git_command: G,
+    logger: Logger,
+}
+
+impl<G: GitCommandTrait> LoggedGitCommand<G> {
+    pub fn new(git_command: G, logger: Logger) -> Self {
+        Self {
+            git_command,
+            logger,
+        }
+    }
+}
+
+impl<G: GitCommandTrait> GitCommandTrait for LoggedGitCommand<G> {
+    fn run(&self, args: Vec<&str>) -> Result<String> {
+        if !self.logger.enabled(Level::Debug) {
+            return self.git_command.run(args);
+        }
+        let command = args.join(" ");
+        let start = Instant::now();
+        let result = self.git_command.run(args);
+        let mut fields = json!({"duration_ms": start.elapsed().as_millis() as u64});
+        match &result {
+            Ok(output) => {
+                fields["status"] = json!("ok");
+                if self.logger.enabled(Level::Trace) {
+                    fields["output"] =
+                        json!(output.chars().take(MAX_OUTPUT_LENGTH).collect::<String>());
+                }
+            }
+            Err(err) => {
+                fields["status"] = json!("failed");
+                fields["error"] = json!(err.to_string().trim());
+            }
+        }
+        self.logger.log(Level::Debug, "git", &command, fields);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_line() {
+        let fields = json!({"duration_ms": 3, "status": "ok"});
+
+        assert_eq!(
+            Logger::new(1, LogFormat::Text).line(
+                Level::Debug,
+                "git",
+                "describe --abbrev=0 --tags",
+                fields.clone()
+            ),
+            r#"debug git: describe --abbrev=0 --tags duration_ms=3 status="ok""#
+        );
+        let line: Value = serde_json::from_str(&Logger::new(1, LogFormat::Json).line(
+            Level::Debug,
+            "git",
+            "describe --abbrev=0 --tags",
+            fields,
+        ))
+        .unwrap();
+        assert_eq!(
+            line,
+            json!({
+                "level": "debug",
+                "target": "git",
+                "message": "describe --abbrev=0 --tags",
+                "duration_ms": 3,
+                "status": "ok",
+            })
+        );
+        assert!(!Logger::new(1, LogFormat::Text).enabled(Level::Trace));
+    }
+}
@@ -1,760 +1,1124 @@
-mod git_command;
-mod models;
-
 use anyhow::{Error, Result};
-use clap::Parser;
-use git_command::{GitCommand, GitCommandTrait};
-use models::Regexes;
-use semver::{BuildMetadata, Prerelease, Version};
-use serde_json::{json, to_string_pretty, Value};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+use semver::VersionReq;
+use serde_json::{Map, Value};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+use version_vine::artifacts::{write_checksums, ChecksumsArgs};
+use version_vine::audit::audit;
+use version_vine::batch::Batch;
+use version_vine::cache::CachedGitCommand;
+use version_vine::changelog::{changelog, prepend, read_changelog, ChangelogArgs};
+use version_vine::ci::{ci_integration, Ci};
+use version_vine::compare::{compare, Precedence};
+use version_vine::components::check_submodule;
+use version_vine::config::{
+    cargo_workspace_apps, check_required_version, Config, ExecutionConfig, HookEvent, UserConfig,
+};
+use version_vine::constraints::{check_constraints, load_consumers};
+use version_vine::diagnostics::diagnose;
+use version_vine::doctor::{doctor, Status};
+use version_vine::error::{error_json, exit_code, ErrorFormat};
+use version_vine::explain::explain;
+use version_vine::git_command::{Backend, GitBackend};
+use version_vine::graph::{version_graph, GraphFormat};
+use version_vine::hooks::{run_app_hooks, run_hooks};
+use version_vine::image_policy::ImagePolicy;
+use version_vine::latest::latest_release;
+use version_vine::log::LoggedGitCommand;
+use version_vine::manifest_plan::{image_rows, plan_manifests, Platform};
+use version_vine::models::SCHEMA_VERSION;
+use version_vine::next::{next_release, NextBump};
+use version_vine::output::{
+    export_env, render, render_table, render_template, select_fields, to_json_string, write_atomic,
+    ExportEnv, OutputFormat, TableFormat,
+};
+use version_vine::promote::{promote, PromoteArgs};
+use version_vine::provenance::{provenance, write_provenance, Signer};
+use version_vine::release_notes::{release_notes, ReleaseNotesArgs};
+use version_vine::release_plan::{apply_plan, create_plan, Plan, PlanArgs};
+use version_vine::schema::{output_schema, output_shape};
+#[cfg(feature = "self-update")]
+use version_vine::self_update;
+use version_vine::set_version::{set_version, SetArgs};
+use version_vine::start_release::{start_release, StartReleaseArgs};
+use version_vine::stats::{release_stabilization, release_stats};
+use version_vine::support_bundle::{support_bundle, write_bundle, SupportBundleArgs};
+use version_vine::tag::{create_tag, TagArgs};
+#[cfg(feature = "otel")]
+use version_vine::telemetry::{TracedGitCommand, Tracer};
+#[cfg(feature = "interactive")]
+use version_vine::ui::run_interactive;
+use version_vine::ui::run_ui;
+use version_vine::validate::validate;
+use version_vine::verify::{manifest_inputs, verify};
+#[cfg(feature = "forge")]
+use version_vine::{bump_pr, forge, pr_comment, release};
+use version_vine::{calculate_apps, VersionCalculator, VersionInfo, VersionOptions};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
-struct Args {
-    /// Useful for monorepos with multiple versionable applications. Tags and release branches will have to be prefixed with an application name. E.g. tag: `app-1.0.0`, branch: `release/app-1.0.0`.
-    #[arg(short, long)]
-    app_name: Option<String>,
-
-    /// Build number to be included in the SemVer build metadata. Often used when using a build system. When not provided, the git commit count for the branch is used.
-    #[arg(short, long)]
-    build_number: Option<u32>,
-
-    /// Include fetching (decreases performance for local runs, but ensures latest information is used)
+struct Cli {
+    /// Path to the configuration file. Discovered by walking up from the current directory to the repository root when not provided.
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Repository to version, e.g. a checkout next to the working directory. Defaults to the repository of the working directory. The configuration is discovered from it.
+    #[arg(long, value_name = "DIR")]
+    repo_path: Option<PathBuf>,
+
+    /// Version the submodule at the path instead, e.g. `libs/core`, relative to the working directory or the `--repo-path`. The git queries run inside the submodule, so its own tags apply, and its configuration is discovered from it.
+    #[arg(long, value_name = "PATH")]
+    submodule: Option<PathBuf>,
+
+    /// Git executable. Defaults to `GIT_EXECUTABLE`, the `git_path` of the user configuration, or else `git` from the `PATH`.
+    #[arg(long, value_name = "PATH")]
+    git_path: Option<String>,
+
+    /// Git backend
+    #[arg(long, value_enum, default_value = "cli")]
+    backend: Backend,
+
+    /// Cache the results of git queries under `.git/version-vine-cache`, per `HEAD` and state of the refs. Speeds up repeated runs in large repositories.
+    #[arg(long, action)]
+    cache: bool,
+
+    /// Don't use the cache, also when enabled by the `cache` configuration
+    #[arg(long, action, conflicts_with = "cache")]
+    no_cache: bool,
+
+    /// Format of the version output. Defaults to the `output_format` of the user configuration, or `json`.
+    #[arg(short, long, alias = "format", value_enum)]
+    output_format: Option<OutputFormat>,
+
+    /// Export the version fields as `VERSION_VINE_*` environment variables of the following CI steps, instead of printing them. Without a value, the mode follows the detected CI system.
+    #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "auto")]
+    export_env: Option<ExportEnv>,
+
+    /// Integrate the version into the CI build: besides exporting the `VERSION_VINE_*` environment variables, name the build after the version (Azure Pipelines, TeamCity) and add a job summary (GitHub Actions, Azure Pipelines). Without a value, the CI system is detected.
+    #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "auto", conflicts_with_all = ["export_env", "template", "template_file"])]
+    ci: Option<Ci>,
+
+    /// Print the version with a template instead, e.g. `{{major}}.{{minor}}.{{patch}}-{{pre}}`. `{{ name }}` placeholders are replaced by the output fields (`major`, `minor`, `patch`, `prerelease`, `build`, `branch_class`, ...), `pre` for the pre-release, `escaped_branch`, `app_name` and `tag`.
+    #[arg(long, conflicts_with_all = ["template_file", "export_env"])]
+    template: Option<String>,
+
+    /// Print the version with a template file, e.g. to generate a `version.h` or `AssemblyInfo.cs`. Same placeholders as `--template`.
+    #[arg(long, conflicts_with = "export_env")]
+    template_file: Option<PathBuf>,
+
+    /// Also write the version output to a file, e.g. `version.json` to `COPY` into container images. The file is replaced atomically, and its parent directories are created.
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["export_env", "ci"])]
+    output_file: Option<PathBuf>,
+
+    /// Also write the version output to files in the format of their extension, comma separated, e.g. `version.env,version.txt`: `.json`, `.yaml` or `.yml`, `.env`, or `.txt` for the plain version. Written like `--output-file`.
+    #[arg(long, value_delimiter = ',', value_name = "PATHS", conflicts_with_all = ["export_env", "ci"])]
+    write: Vec<PathBuf>,
+
+    /// Also write a provenance document of the version to a file, e.g. `provenance.json`: an in-toto statement with a SLSA provenance predicate, with the version tag and commit as subject, the version, branch and tags as parameters, the CI system as builder, and the time (`SOURCE_DATE_EPOCH` when set).
+    #[arg(long, value_name = "PATH")]
+    provenance: Option<PathBuf>,
+
+    /// Sign the provenance document with the key, e.g. a minisign secret key without password or an SSH private key, next to it as `<file>.minisig` or `<file>.sig`
+    #[arg(long, value_name = "KEY", requires = "provenance")]
+    provenance_key: Option<PathBuf>,
+
+    /// Tool signing the provenance document, which has to be allowed by the `[execution]` policy
+    #[arg(
+        long,
+        value_enum,
+        default_value = "minisign",
+        requires = "provenance_key"
+    )]
+    provenance_signer: Signer,
+
+    /// Don't print the version output when it's written to files with `--output-file` or `--write`
     #[arg(short, long, action)]
-    fetch: bool,
-}
-
-fn main() -> Result<()> {
-    let args = Args::parse();
-    let git_command = GitCommand {};
-    let version_output = get_version_output(&args, &git_command)?;
-    println!("{}", to_string_pretty(&version_output)?);
-    Ok(())
-}
-
-fn get_version_output(args: &Args, git_command: &impl GitCommandTrait) -> Result<Value, Error> {
-    let regexes = Regexes::new(&args.app_name)?;
-    if args.fetch {
-        git_command.run(vec!["fetch", "--tags"])?;
-    }
-    let git_branch = git_command.run(vec!["branch", "--show-current"])?;
-    let git_rev = git_command.run(vec!["rev-parse", "--short", "HEAD"])?;
-    let rev_count = git_command.run(vec!["rev-list", "--count", "HEAD"])?;
-    let semver = get_version(git_command, &regexes, &git_branch, args)?;
-    let new_semver = update_version(
-        &git_branch,
-        &regexes,
-        &git_rev,
-        get_count(args, &rev_count)?,
-        &semver,
-    )?;
-    let version_output = json!({
-        "git_branch": git_branch,
-        "git_rev": git_rev,
-        "rev_count": rev_count,
-        "app_version": new_semver.to_string(),
-        "container_tag": new_semver.to_string().replace('+', ".")
-    });
-    Ok(version_output)
-}
-
-fn update_version(
-    git_branch: &str,
-    regexes: &Regexes,
-    git_rev: &String,
-    counter: u32,
-    semver: &Version,
-) -> Result<Version> {
-    let mut new_semver = semver.clone();
-    if regexes.main_branches.is_match(git_branch) {
-        new_semver.build = BuildMetadata::new(git_rev)?;
-    } else if regexes.rc_branches.is_match(git_branch) {
-        new_semver.pre = Prerelease::new(&format!("rc.{}", counter)).unwrap();
-        new_semver.build = BuildMetadata::new(git_rev)?;
-    } else if regexes.develop_branches.is_match(git_branch) {
-        new_semver.patch += 1;
-        new_semver.pre = Prerelease::new(&format!("beta.{}", counter)).unwrap();
-        new_semver.build = BuildMetadata::new(git_rev)?;
-    } else {
-        new_semver.patch += 1;
-        new_semver.pre = Prerelease::new(&format!("alpha.{}", counter)).unwrap();
-        let escaped_branch = regexes.escape_branch.replace_all(git_branch, "-");
-        if escaped_branch.len() > 50 {
-            escaped_branch.to_string().truncate(50);
-        }
-        new_semver.build = BuildMetadata::new(&format!("{}.{}", escaped_branch, &git_rev))?;
-    };
-    Ok(new_semver)
-}
+    quiet: bool,
 
-fn get_count(args: &Args, rev_count: &str) -> Result<u32, Error> {
-    let counter = if args.build_number.is_some() {
-        args.build_number.unwrap()
-    } else {
-        rev_count.parse::<u32>()?
-    };
-    Ok(counter)
-}
+    /// Only output these fields of the version, comma separated, e.g. `app_version,container_tag,git_rev`. Defaults to the `[output] fields` of the configuration, or all fields.
+    #[arg(long, value_delimiter = ',')]
+    fields: Vec<String>,
 
-fn get_version(
-    git_command: &impl GitCommandTrait,
-    regexes: &Regexes,
-    git_branch: &str,
-    args: &Args,
-) -> Result<Version> {
-    let tag: String;
-    let version: String;
-    let semver: Version;
-    // For release branches, get the version from the branch name
-    if regexes.rc_branches.is_match(git_branch) {
-        let caps = regexes
-            .rc_branches
-            .captures(git_branch)
-            .ok_or(Error::msg("Invalid branch name format"))?;
-        version = caps.name("version").unwrap().as_str().to_string();
-        semver = Version::parse(&version)?;
-    } else {
-        // For all other branches, get the version from the latest tag
-        let get_tags_result = if args.app_name.is_none() {
-            git_command.run(vec!["describe", "--abbrev=0", "--tags"])
-        } else {
-            git_command.run(vec![
-                "describe",
-                "--abbrev=0",
-                "--match",
-                format!("{}-*", args.app_name.as_ref().unwrap()).as_str(),
-                "--tags",
-            ])
-        };
+    /// Print JSON outputs as canonical JSON: compact, with the keys sorted, so they can be hashed, cached, diffed and signed
+    #[arg(long, action)]
+    canonical: bool,
 
-        // Fall back to 0.0.0 if no tags are found
-        match get_tags_result {
-            Ok(t) => tag = t,
-            Err(_) => {
-                tag = if args.app_name.is_none() {
-                    "0.0.0".to_string()
-                } else {
-                    format!("{}-0.0.0", args.app_name.as_ref().unwrap())
-                }
-            }
-        }
+    /// Version of the output contract, the `schema_version` field. `1` prints the output without `schema_version`, for tools expecting the previous shape. Defaults to the `[output] schema_version` of the configuration, or the latest version.
+    #[arg(long, value_name = "VERSION")]
+    schema_version: Option<u32>,
 
-        // For the main branch, a tag must exist on the current commit
-        if regexes.main_branches.is_match(git_branch) {
-            let exact_tag =
-                git_command.run(vec!["describe", "--abbrev=0", "--exact-match", "--tags"])?;
-            if exact_tag != tag {
-                return Err(Error::msg(
-                    "Cannot version a production release from a commit without a tag",
-                ));
-            }
-        }
+    /// Guarantee that version-vine doesn't access the network, e.g. for air-gapped builds and reproducibility audits. Git fetches and pushes (`--fetch`, `--auto-deepen`, `tag --push`, ...) and HTTP requests (forge commands, consumer URLs, metrics and traces) fail instead. Also the `offline` of the `[execution]` configuration.
+    #[arg(long, action)]
+    offline: bool,
 
-        // Extract the semver version from the tag
-        let caps = regexes
-            .tag
-            .captures(&tag)
-            .ok_or(Error::msg("No tag found"))?;
-        version = caps.name("version").unwrap().as_str().to_string();
-        semver = Version::parse(&version).map_err(|err| {
-            Error::msg(format!(
-                "Tag '{}' cannot be parsed to SemVer Version.\nDo you have app names in your tags? Provide the '--app-name' option.\nError: '{}'",
-                tag, err
-            ))
-        })?;
-    }
-    Ok(semver)
-}
+    /// Also allow the program to run, e.g. `sh` for the `[[hooks]]`, repeatable. Adds to the `allowed_commands` of the `[execution]` policy of the user configuration, which the repository configuration can only narrow.
+    #[arg(long, value_name = "PROGRAM")]
+    allow_command: Vec<String>,
 
-#[cfg(test)]
-mod tests {
-    use crate::git_command::MockGitCommandTrait;
-
-    use super::*;
-
-    #[test]
-    fn test_get_version_main_branch_with_tag() {
-        let mut git_command = MockGitCommandTrait::new();
-        let app_name = Some("myapp");
-        let branch = "main";
-        let rev = "1234567";
-        let count = "1";
-        let version = Some("1.0.0");
-
-        let args = Args {
-            app_name: Some(app_name.unwrap().to_owned()),
-            build_number: None,
-            fetch: false,
-        };
+    /// Fail unless the running version-vine satisfies the SemVer range, e.g. `>=0.5`, before doing anything else, so pipelines don't compute versions with incompatible tooling. Also the `require_version` of the configuration.
+    #[arg(long, value_name = "RANGE")]
+    require_version: Option<VersionReq>,
 
-        mock_git(&mut git_command, app_name, branch, rev, count, version);
+    /// Format of the errors printed to stderr. `json` prints an object with the `error` kind (`no-tag-on-main`, `dirty-worktree`, `invalid-branch`, `git-failure`, `parse-error` or `error`), the `message` and the `exit_code`.
+    #[arg(long, value_enum, default_value = "text", value_name = "FORMAT")]
+    errors: ErrorFormat,
 
-        let result = get_version_output(&args, &git_command);
+    #[command(flatten)]
+    options: VersionOptions,
 
-        assert!(result.is_ok());
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
 
-        let output = result.unwrap();
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Check whether the computed `container_tag` would be picked up by a Flux or Argo CD image automation policy
+    ImagePolicy {
+        /// SemVer range of the policy. E.g. `>=1.0.0 <2.0.0` or `~1.2`.
+        #[arg(short, long)]
+        semver_range: Option<String>,
+
+        /// Regex filtering the tags. Argo CD style `regexp:` prefixes are accepted.
+        #[arg(short, long)]
+        pattern: Option<String>,
+
+        /// Replacement applied to the pattern captures before evaluating the range. E.g. `$version`.
+        #[arg(short, long, requires = "pattern")]
+        extract: Option<String>,
+    },
+
+    /// Check the computed version against the version ranges accepted by the consumer services, flagging breaking rollouts before deploying. Fails when a range isn't satisfied.
+    Constraints {
+        /// JSON file or URL of an object mapping the consumers to their version range, e.g. `{"billing": "^1.2"}`. Defaults to the `[consumers]` table of the configuration.
+        #[arg(long, value_name = "FILE|URL")]
+        consumers: Option<String>,
+
+        /// Format of the checks
+        #[arg(long, value_enum, default_value = "json")]
+        format: TableFormat,
+    },
+
+    /// Plan the per platform image tags and the manifest list tag for a multi-arch image build
+    ManifestPlan {
+        /// Platforms in `os/architecture[/variant]` notation. E.g. `linux/amd64` or `linux/arm/v7`.
+        #[arg(short, long = "platform", required = true)]
+        platforms: Vec<String>,
+
+        /// Image variants, each getting their own manifest list. E.g. `alpine` or `slim`.
+        #[arg(short, long = "variant")]
+        variants: Vec<String>,
+
+        /// Image name to produce fully qualified references for. E.g. `ghcr.io/org/app`.
+        #[arg(short, long)]
+        image_name: Option<String>,
+
+        /// Format of the plan. CSV has a row per platform image.
+        #[arg(long, value_enum, default_value = "json")]
+        format: TableFormat,
+    },
+
+    /// Emit a graph of the release tags, release/hotfix branches and `HEAD` with its computed version
+    Graph {
+        /// Graph syntax
+        #[arg(long, value_enum, default_value = "mermaid")]
+        format: GraphFormat,
+    },
+
+    /// Render a Markdown changelog section of the computed version from the commits since the previous tag, grouped by Conventional Commit type
+    Changelog(ChangelogArgs),
+
+    /// Combine the changelog sections of several apps (`--app-name` or `--all-apps`) since their previous tag into one Markdown document with a summary, e.g. for platform release announcements
+    ReleaseNotes(ReleaseNotesArgs),
+
+    /// Write the computed version to project files: `Cargo.toml`, `package.json`, `pyproject.toml`, `*.csproj`, package manager manifests, or others with `--regex` or `--json-path`
+    Set(SetArgs),
+
+    /// Create the git tag for the computed version, optionally annotated, signed and pushed
+    Tag(TagArgs),
+
+    /// Write the `SHA256SUMS` of artifact files, with the computed version in its header, and optionally sign it with `gpg`
+    Checksums(ChecksumsArgs),
+
+    /// Promote the pre-release of a release or hotfix branch (or the version of main) to the stable release version, and optionally tag it. `-o plain` prints only the version.
+    Promote(PromoteArgs),
+
+    /// Start a release from the develop branch: create the release branch of the next release version (`release/<app->X.Y.Z`), optionally push it, and print the release candidate version of the branch. `-o plain` prints only that version.
+    StartRelease(StartReleaseArgs),
+
+    /// Plan a release of the computed version: the files to change, the tag and the pushes. Review the plan and execute it with `apply`.
+    Plan(PlanArgs),
+
+    /// Execute a release plan created by `plan`
+    Apply {
+        /// Path to the plan JSON document
+        #[arg(long)]
+        plan: PathBuf,
+    },
+
+    /// Explain how the branch is classified: the branch patterns evaluated in priority order and which of them match
+    Explain,
+
+    /// Summarize the release cadence per app from the tags: releases per month, time from release candidate to release, hotfix frequency, lead time for changes and deployment frequency
+    Stats {
+        /// Format of the statistics
+        #[arg(long, value_enum, default_value = "json")]
+        format: TableFormat,
+
+        /// Report the stabilization of the current release branch instead: the commits since it was cut, the release candidates and the days since the cut
+        #[arg(long, action)]
+        stabilization: bool,
+    },
+
+    /// Print the next release version and tag after the latest tag, regardless of the branch. E.g. to name the release branch of the upcoming version. `-o plain` prints only the version.
+    Next {
+        /// Part of the version to bump
+        #[arg(long, value_enum, default_value = "auto")]
+        bump: NextBump,
+    },
+
+    /// Print the highest version tag by SemVer precedence, of all tags of the app rather than the nearest tag `git describe` finds. `-o plain` prints only the version.
+    Latest {
+        /// Only the versions of the release line, e.g. `1.2` for `1.2.x` or `1` for `1.x`
+        #[arg(long)]
+        line: Option<String>,
+
+        /// Also consider pre-release tags, e.g. `2.0.0-rc.1`
+        #[arg(long, action)]
+        include_prerelease: bool,
+    },
+
+    /// Compute the versions of two refs, as with `--ref`, and report their SemVer ordering, the part of the version which changes and the commit distance. E.g. `compare main release/1.3.0 --require-greater` as merge gate.
+    Compare {
+        /// Ref of the base version, e.g. `main`
+        from: String,
+        /// Ref compared with it, e.g. `release/1.3.0`
+        to: String,
+        /// Fail unless the version of `to` is greater than the version of `from`
+        #[arg(long, action)]
+        require_greater: bool,
+    },
+
+    /// Recompute the version of a version output recorded with `--inputs`, from its commit, branch, arguments and environment variables, and confirm it still derives to the same version with the same configuration. Fails otherwise, e.g. for audits and incident forensics.
+    Verify {
+        /// Path to the version output JSON
+        manifest: PathBuf,
+    },
+
+    /// Check the version sequence per app for gaps (e.g. `1.4.0` → `1.6.0` without `1.5.0`) and versions tagged on different commits. Fails when there are findings.
+    Audit {
+        /// Format of the findings
+        #[arg(long, value_enum, default_value = "json")]
+        format: TableFormat,
+    },
+
+    /// Lint the repository state against the versioning model: the branch name, the latest tag, the release branch version, the tag of main branches and duplicate tags. Fails when there are violations, e.g. as pull request gate.
+    Validate {
+        /// Format of the violations
+        #[arg(long, value_enum, default_value = "json")]
+        format: TableFormat,
+    },
+
+    /// Check the Gitflow branch state: release branches not cut from develop, main ahead of its latest tag, the tag of main not merged into develop and tags of develop newer than those of main. Warns about the findings, or fails with --strict.
+    Diagnose {
+        /// Fail when there are findings
+        #[arg(long)]
+        strict: bool,
+
+        /// Format of the findings
+        #[arg(long, value_enum, default_value = "json")]
+        format: TableFormat,
+    },
+
+    /// Check that the version can be computed here, e.g. on the first run or when a CI build fails: git is installed and recent enough, the working directory is in a repository, the refs were fetched recently and not shallowly, the latest tag is SemVer, the branch is classified and the configuration is valid. Fails with the failed checks and hints how to fix them.
+    Doctor {
+        /// Hours since the last fetch (or the clone) after which the refs are stale
+        #[arg(long, value_name = "HOURS", default_value_t = 24)]
+        max_fetch_age: u64,
+
+        /// Format of the checks
+        #[arg(long, value_enum, default_value = "json")]
+        format: TableFormat,
+    },
+
+    /// Interactive release management: the branch class, computed version, recent tags and affected apps, with keys to cut a release branch, tag or render the changelog
+    Ui,
+
+    /// Prepare a release locally: shows the branch, the base tag and the next release version of each bump, and cuts the release branch or creates the tag of the picked version
+    #[cfg(feature = "interactive")]
+    Interactive,
+
+    /// Collect the diagnostics of the version calculation into a tar archive to attach to bug reports: the decisions of the calculation or its error, the configuration file, a summary of the git state and the version output. Remote URLs and email addresses are redacted.
+    SupportBundle(SupportBundleArgs),
+
+    /// Compute the versions of newline-delimited JSON requests on stdin, with the repository, app name, ref and options of each, printing a result line per request, e.g. for version services and monorepo orchestrators. The git queries are cached across the requests, unless --no-cache.
+    Batch {
+        /// Format of the results: a JSON line per request as it's computed, or CSV rows when the input ends
+        #[arg(long, value_enum, default_value = "json")]
+        format: TableFormat,
+    },
+
+    /// Print the JSON Schema of the version output, of the `--schema-version`, so downstream tools can validate it
+    Schema,
+
+    /// Open a pull request (GitHub) or merge request (GitLab) bumping the version file, manifests and changelog to the next release version, instead of tagging directly
+    #[cfg(feature = "forge")]
+    BumpPr(bump_pr::BumpPrArgs),
+
+    /// Comment the version the pull request produces after merging into its target branch on the pull request (GitHub) or merge request (GitLab), updating the comment on re-runs
+    #[cfg(feature = "forge")]
+    PrComment(pr_comment::PrCommentArgs),
+
+    /// Replace the binary by the prebuilt binary of the latest GitHub release, or of the --version, for this platform, after verifying the checksum released with it
+    #[cfg(feature = "self-update")]
+    SelfUpdate(self_update::SelfUpdateArgs),
+
+    /// Create the GitHub release (or GitLab release) of the computed version, with its changelog section as notes. Pre-release versions, e.g. `1.3.0-rc.1`, are marked as pre-release.
+    #[cfg(feature = "forge")]
+    Release(release::ReleaseArgs),
+}
 
-        let mut expected_version = Version::parse(&version.clone().unwrap()).unwrap();
-        expected_version.build = BuildMetadata::new(&rev).unwrap();
-        assert_expected_version(branch, rev, count, expected_version, output);
+fn main() {
+    let mut errors = ErrorFormat::Text;
+    if let Err(err) = run(&mut errors) {
+        match errors {
+            ErrorFormat::Text => eprintln!("Error: {:?}", err),
+            ErrorFormat::Json => eprintln!("{}", error_json(&err)),
+        }
+        process::exit(exit_code(&err));
     }
+}
 
-    #[test]
-    fn test_get_version_main_branch_without_tag() {
-        let mut git_command = MockGitCommandTrait::new();
-        let app_name = Some("myapp");
-        let branch = "main";
-        let rev = "1234567";
-        let count = "1";
-        let version = None;
-
-        let args = Args {
-            app_name: Some(String::from("myapp")),
-            build_number: None,
-            fetch: false,
-        };
-
-        mock_git(&mut git_command, app_name, branch, rev, count, version);
-
-        let result = get_version_output(&args, &git_command);
-
-        assert!(result.is_ok());
-
-        let output = result.unwrap();
-
-        let mut expected_version = Version::parse("0.0.0").unwrap();
-        expected_version.build = BuildMetadata::new(&rev).unwrap();
-        assert_expected_version(branch, rev, count, expected_version, output);
+/// Runs the command, setting the `--errors` format once the arguments are parsed
+fn run(errors: &mut ErrorFormat) -> Result<()> {
+    let user_config = match UserConfig::path(|name| env::var(name).ok()) {
+        Some(path) => UserConfig::load(&path)?,
+        None => UserConfig::default(),
+    };
+    let matches = Cli::command()
+        .color(user_config.settings.color.into())
+        .get_matches();
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    *errors = cli.errors;
+    // Before loading the configuration, which may have settings of other versions. Updating fixes
+    // a version which doesn't satisfy the requirement.
+    let updating = matches.subcommand_name() == Some("self-update");
+    if let (Some(required), false) = (&cli.require_version, updating) {
+        check_required_version(required, env!("CARGO_PKG_VERSION"))?;
     }
-
-    #[test]
-    fn test_get_version_no_app_name_main_branch_with_tag() {
-        let mut git_command = MockGitCommandTrait::new();
-        let app_name: Option<&str> = None;
-        let branch = "main";
-        let rev = "1234567";
-        let count = "1";
-        let version = Some("1.0.0");
-
-        let args = Args {
-            app_name: None,
-            build_number: None,
-            fetch: false,
-        };
-
-        mock_git(&mut git_command, app_name, branch, rev, count, version);
-
-        let result = get_version_output(&args, &git_command);
-
-        assert!(result.is_ok());
-
-        let output = result.unwrap();
-
-        let mut expected_version = Version::parse(&version.clone().unwrap()).unwrap();
-        expected_version.build = BuildMetadata::new(&rev).unwrap();
-        assert_expected_version(branch, rev, count, expected_version, output);
+    if let Some(submodule) = &cli.submodule {
+        cli.repo_path = Some(match &cli.repo_path {
+            Some(repo_path) => repo_path.join(submodule),
+            None => submodule.clone(),
+        });
     }
-
-    #[test]
-    fn test_get_version_no_app_name_main_branch_without_tag() {
-        let mut git_command = MockGitCommandTrait::new();
-        let app_name: Option<&str> = None;
-        let branch = "main";
-        let rev = "1234567";
-        let count = "1";
-        let version = None;
-
-        let args = Args {
-            app_name: None,
-            build_number: None,
-            fetch: false,
-        };
-
-        mock_git(&mut git_command, app_name, branch, rev, count, version);
-
-        let result = get_version_output(&args, &git_command);
-
-        assert!(result.is_ok());
-
-        let output = result.unwrap();
-
-        let mut expected_version = Version::parse("0.0.0").unwrap();
-        expected_version.build = BuildMetadata::new(&rev).unwrap();
-        assert_expected_version(branch, rev, count, expected_version, output);
+    let config_path = match &cli.config {
+        Some(config_path) => Some(config_path.clone()),
+        None => Config::discover(&match &cli.repo_path {
+            Some(repo_path) => repo_path.clone(),
+            None => env::current_dir()?,
+        }),
+    };
+    let config = match &config_path {
+        Some(config_path) => Config::load(config_path, &user_config.defaults),
+        None => Config::parse_with_defaults("", &user_config.defaults),
+    };
+    // The doctor reports an invalid configuration, checking the rest with the defaults
+    let (config, config_error) = match config {
+        Err(err) if matches.subcommand_name() == Some("doctor") => (
+            Config::parse_with_defaults("", &user_config.defaults)?,
+            Some(err),
+        ),
+        config => (config?, None),
+    };
+    if let (Some(required), false) = (&config.require_version, updating) {
+        check_required_version(required, env!("CARGO_PKG_VERSION"))?;
     }
-
-    #[test]
-    fn test_get_version_develop_branch_with_tag() {
-        let mut git_command = MockGitCommandTrait::new();
-        let app_name = Some("myapp");
-        let branch = "develop";
-        let rev = "1234567";
-        let count = "1";
-        let version = Some("1.0.0");
-
-        let args = Args {
-            app_name: Some(String::from("myapp")),
-            build_number: None,
-            fetch: false,
-        };
-
-        mock_git(&mut git_command, app_name, branch, rev, count, version);
-
-        let result = get_version_output(&args, &git_command);
-
-        assert!(result.is_ok());
-
-        let output = result.unwrap();
-
-        let mut expected_version = Version::parse(&version.clone().unwrap()).unwrap();
-        expected_version.patch += 1;
-        expected_version.pre = Prerelease::new(&format!("beta.{}", count)).unwrap();
-        expected_version.build = BuildMetadata::new(&rev).unwrap();
-        assert_expected_version(branch, rev, count, expected_version, output);
+    // Verifying applies it to the recorded arguments instead
+    let loaded_config = config.clone();
+    cli.options.apply_config(config);
+    if cli.offline {
+        cli.options.config.execution.offline = true;
     }
-
-    #[test]
-    fn test_get_version_develop_branch_without_tag() {
-        let mut git_command = MockGitCommandTrait::new();
-        let app_name = Some("myapp");
-        let branch = "develop";
-        let rev = "1234567";
-        let count = "1";
-        let version = None;
-
-        let args = Args {
-            app_name: Some(String::from("myapp")),
-            build_number: None,
-            fetch: false,
-        };
-
-        mock_git(&mut git_command, app_name, branch, rev, count, version);
-
-        let result = get_version_output(&args, &git_command);
-
-        assert!(result.is_ok());
-
-        let output = result.unwrap();
-
-        let mut expected_version = Version::parse("0.0.0").unwrap();
-        expected_version.patch += 1;
-        expected_version.pre = Prerelease::new(&format!("beta.{}", count)).unwrap();
-        expected_version.build = BuildMetadata::new(&rev).unwrap();
-
-        assert_expected_version(branch, rev, count, expected_version, output);
+    cli.options
+        .config
+        .execution
+        .allowed_commands
+        .extend(cli.allow_command.iter().cloned());
+    if cli.options.reproducible {
+        cli.canonical = true;
     }
-
-    #[test]
-    fn test_get_version_no_app_name_develop_branch_with_tag() {
-        let mut git_command = MockGitCommandTrait::new();
-        let app_name: Option<&str> = None;
-        let branch = "develop";
-        let rev = "1234567";
-        let count = "1";
-        let version = Some("1.0.0");
-
-        let args = Args {
-            app_name: None,
-            build_number: None,
-            fetch: false,
-        };
-
-        mock_git(&mut git_command, app_name, branch, rev, count, version);
-
-        let result = get_version_output(&args, &git_command);
-
-        assert!(result.is_ok());
-
-        let output = result.unwrap();
-
-        let mut expected_version = Version::parse(&version.clone().unwrap()).unwrap();
-        expected_version.patch += 1;
-        expected_version.pre = Prerelease::new(&format!("beta.{}", count)).unwrap();
-        expected_version.build = BuildMetadata::new(&rev).unwrap();
-        assert_expected_version(branch, rev, count, expected_version, output);
+    if let Some(manifest) = &cli.options.from_cargo_workspace {
+        cli.options.config.apps = cargo_workspace_apps(manifest)?;
+        cli.options.all_apps = true;
     }
-
-    #[test]
-    fn test_get_version_no_app_name_develop_branch_without_tag() {
-        let mut git_command = MockGitCommandTrait::new();
-        let app_name: Option<&str> = None;
-        let branch = "develop";
-        let rev = "1234567";
-        let count = "1";
-        let version = None;
-
-        let args = Args {
-            app_name: None,
-            build_number: None,
-            fetch: false,
+    let fields = match cli.fields.is_empty() {
+        true => cli.options.config.output.fields.clone(),
+        false => cli.fields.clone(),
+    };
+    let schema_version = cli
+        .schema_version
+        .or(cli.options.config.output.schema_version)
+        .unwrap_or(SCHEMA_VERSION);
+    let output_format = cli
+        .output_format
+        .or(user_config.settings.output_format)
+        .unwrap_or(OutputFormat::Json);
+    let git_path = cli
+        .git_path
+        .clone()
+        .or(env::var("GIT_EXECUTABLE")
+            .ok()
+            .filter(|path| !path.is_empty()))
+        .or(user_config.settings.git_path.clone());
+    if let Some(Commands::Batch { format }) = &cli.command {
+        let repo_path = match &cli.repo_path {
+            Some(repo_path) => repo_path.clone(),
+            None => env::current_dir()?,
         };
-
-        mock_git(&mut git_command, app_name, branch, rev, count, version);
-
-        let result = get_version_output(&args, &git_command);
-
-        assert!(result.is_ok());
-
-        let output = result.unwrap();
-
-        let mut expected_version = Version::parse("0.0.0").unwrap();
-        expected_version.patch += 1;
-        expected_version.pre = Prerelease::new(&format!("beta.{}", count)).unwrap();
-        expected_version.build = BuildMetadata::new(&rev).unwrap();
-
-        assert_expected_version(branch, rev, count, expected_version, output);
+        let logger = cli.options.logger();
+        let mut batch = Batch::new(
+            repo_path,
+            user_config.defaults.clone(),
+            cli.offline,
+            cli.allow_command.clone(),
+            !cli.no_cache,
+            |repo_path: &Path, policy: &ExecutionConfig| {
+                Ok(LoggedGitCommand::new(
+                    GitBackend::new(cli.backend, policy, git_path.clone(), Some(repo_path))?,
+                    logger,
+                ))
+            },
+        );
+        batch.run(io::stdin().lock(), &mut io::stdout().lock(), *format)?;
+        return Ok(());
     }
-
-    #[test]
-    fn test_get_version_release_branch_with_tag() {
-        let mut git_command = MockGitCommandTrait::new();
-        let app_name = Some("myapp");
-        let branch = "release/myapp-1.1.0";
-        let rev = "1234567";
-        let count = "1";
-        let version = Some("1.0.0");
-
-        let args = Args {
-            app_name: Some(String::from("myapp")),
-            build_number: None,
-            fetch: false,
-        };
-
-        mock_git(&mut git_command, app_name, branch, rev, count, version);
-
-        let result = get_version_output(&args, &git_command);
-
-        assert!(result.is_ok());
-
-        let output = result.unwrap();
-
-        let mut expected_version = Version::parse("1.1.0").unwrap();
-        expected_version.pre = Prerelease::new(&format!("rc.{}", count)).unwrap();
-        expected_version.build = BuildMetadata::new(&rev).unwrap();
-        assert_expected_version(branch, rev, count, expected_version, output);
+    let git_backend = GitBackend::new(
+        cli.backend,
+        &cli.options.config.execution,
+        git_path,
+        cli.repo_path.as_deref(),
+    )?;
+    #[cfg(feature = "otel")]
+    let tracer = Tracer::from_env(
+        &["version-vine"]
+            .into_iter()
+            .chain(matches.subcommand_name())
+            .collect::<Vec<_>>()
+            .join(" "),
+        |name| env::var(name).ok(),
+    )
+    .with_offline(cli.options.config.execution.offline);
+    #[cfg(feature = "otel")]
+    let git_backend = TracedGitCommand::new(git_backend, &tracer);
+    // Below the cache, so only the actual invocations are logged
+    let git_backend = LoggedGitCommand::new(git_backend, cli.options.logger());
+    let git_command = CachedGitCommand::new(
+        git_backend,
+        (cli.cache || cli.options.config.cache.unwrap_or(false)) && !cli.no_cache,
+    );
+    if let (Some(_), Some(repo_path)) = (&cli.submodule, &cli.repo_path) {
+        check_submodule(&git_command, repo_path)?;
     }
-
-    #[test]
-    fn test_get_version_release_branch_without_tag() {
-        let mut git_command = MockGitCommandTrait::new();
-        let app_name = Some("myapp");
-        let branch = "release/myapp-1.1.0";
-        let rev = "1234567";
-        let count = "1";
-        let version = None;
-
-        let args = Args {
-            app_name: Some(String::from("myapp")),
-            build_number: None,
-            fetch: false,
-        };
-
-        mock_git(&mut git_command, app_name, branch, rev, count, version);
-
-        let result = get_version_output(&args, &git_command);
-
-        assert!(result.is_ok());
-
-        let output = result.unwrap();
-
-        let mut expected_version = Version::parse("1.1.0").unwrap();
-        expected_version.pre = Prerelease::new(&format!("rc.{}", count)).unwrap();
-        expected_version.build = BuildMetadata::new(&rev).unwrap();
-        assert_expected_version(branch, rev, count, expected_version, output);
+    // Commands which don't need the version of the current checkout
+    match &cli.command {
+        #[cfg(feature = "self-update")]
+        Some(Commands::SelfUpdate(args)) => {
+            let http_client = forge::HttpClient {
+                offline: cli.options.config.execution.offline,
+            };
+            let result =
+                self_update::self_update(&http_client, &cli.options, args, &env::current_exe()?)?;
+            println!("{}", to_json_string(&result, cli.canonical)?);
+            return Ok(());
+        }
+        // Applying executes the reviewed plan as is, without computing the version again
+        Some(Commands::Apply { plan }) => {
+            let content = fs::read_to_string(plan)?;
+            let plan: Plan = serde_json::from_str(&content)
+                .map_err(|err| Error::msg(format!("Invalid plan '{}': {}", plan.display(), err)))?;
+            println!(
+                "{}",
+                to_json_string(&apply_plan(&git_command, &plan)?, cli.canonical)?
+            );
+            return Ok(());
+        }
+        Some(Commands::Schema) => {
+            println!(
+                "{}",
+                to_json_string(&output_schema(schema_version)?, cli.canonical)?
+            );
+            return Ok(());
+        }
+        // Also when the calculation fails, to report the failure
+        Some(Commands::SupportBundle(args)) => {
+            let files = support_bundle(
+                &git_command,
+                &cli.options,
+                config_path.as_deref(),
+                &env::args().skip(1).collect::<Vec<_>>(),
+            )?;
+            let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+            write_bundle(&args.output, &files, now)?;
+            println!(
+                "{}",
+                to_json_string(
+                    &serde_json::json!({"archive": args.output, "files": files}),
+                    cli.canonical
+                )?
+            );
+            return Ok(());
+        }
+        Some(Commands::Explain) => {
+            println!(
+                "{}",
+                to_json_string(&explain(&git_command, &cli.options)?, cli.canonical)?
+            );
+            return Ok(());
+        }
+        Some(Commands::Stats {
+            format,
+            stabilization: true,
+        }) => {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+            let stabilization = release_stabilization(&git_command, &cli.options, now)?;
+            println!(
+                "{}",
+                render_table(&[stabilization], *format, cli.canonical)?
+            );
+            return Ok(());
+        }
+        Some(Commands::Stats { format, .. }) => {
+            let stats = release_stats(&git_command, &cli.options)?;
+            println!("{}", render_table(&stats, *format, cli.canonical)?);
+            return Ok(());
+        }
+        Some(Commands::Ui) => {
+            return run_ui(
+                &git_command,
+                &cli.options,
+                &mut io::stdin().lock(),
+                &mut io::stdout(),
+            );
+        }
+        #[cfg(feature = "interactive")]
+        Some(Commands::Interactive) => {
+            return run_interactive(
+                &git_command,
+                &cli.options,
+                &mut io::stdin().lock(),
+                &mut io::stdout(),
+            );
+        }
+        Some(Commands::Next { bump }) => {
+            let release = next_release(&git_command, &cli.options, *bump)?;
+            match output_format {
+                OutputFormat::Plain => println!("{}", release["version"].as_str().unwrap()),
+                _ => println!("{}", to_json_string(&release, cli.canonical)?),
+            }
+            return Ok(());
+        }
+        Some(Commands::Latest {
+            line,
+            include_prerelease,
+        }) => {
+            let latest = latest_release(
+                &git_command,
+                &cli.options,
+                line.as_deref(),
+                *include_prerelease,
+            )?;
+            match output_format {
+                OutputFormat::Plain => println!("{}", latest["version"].as_str().unwrap()),
+                _ => println!("{}", to_json_string(&latest, cli.canonical)?),
+            }
+            return Ok(());
+        }
+        Some(Commands::Compare {
+            from,
+            to,
+            require_greater,
+        }) => {
+            let comparison = compare(&git_command, &cli.options, from, to)?;
+            match output_format {
+                OutputFormat::Plain => println!(
+                    "{} {} {}",
+                    comparison.from.version,
+                    match comparison.ordering {
+                        Precedence::Lower => ">",
+                        Precedence::Equal => "=",
+                        Precedence::Greater => "<",
+                    },
+                    comparison.to.version
+                ),
+                _ => println!("{}", to_json_string(&comparison, cli.canonical)?),
+            }
+            if *require_greater && comparison.ordering != Precedence::Greater {
+                return Err(Error::msg(format!(
+                    "The version {} of '{}' isn't greater than the version {} of '{}'",
+                    comparison.to.version, to, comparison.from.version, from
+                )));
+            }
+            return Ok(());
+        }
+        Some(Commands::Verify { manifest }) => {
+            let content = fs::read_to_string(manifest).map_err(|err| {
+                Error::msg(format!("Cannot read '{}': {}", manifest.display(), err))
+            })?;
+            let manifest: Value = serde_json::from_str(&content).map_err(|err| {
+                Error::msg(format!(
+                    "Invalid manifest '{}': {}",
+                    manifest.display(),
+                    err
+                ))
+            })?;
+            let args = manifest_inputs(&manifest)?.args;
+            let mut recorded = Cli::try_parse_from(
+                ["version-vine".to_string()].into_iter().chain(args),
+            )
+            .map_err(|err| Error::msg(format!("Invalid arguments of the manifest: {}", err)))?;
+            recorded.options.apply_config(loaded_config);
+            let verification = verify(&git_command, &recorded.options, &manifest)?;
+            println!("{}", to_json_string(&verification, cli.canonical)?);
+            if !verification.verified {
+                return Err(Error::msg(
+                    "The version doesn't derive from the recorded inputs",
+                ));
+            }
+            return Ok(());
+        }
+        Some(Commands::Validate { format }) => {
+            let violations = validate(&git_command, &cli.options)?;
+            println!("{}", render_table(&violations, *format, cli.canonical)?);
+            if !violations.is_empty() {
+                return Err(Error::msg(format!(
+                    "The validation found {} violation(s)",
+                    violations.len()
+                )));
+            }
+            return Ok(());
+        }
+        Some(Commands::Doctor {
+            max_fetch_age,
+            format,
+        }) => {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+            let diagnoses = doctor(
+                &git_command,
+                &cli.options,
+                (config_path.as_deref(), config_error.as_ref()),
+                now,
+                *max_fetch_age,
+            );
+            println!("{}", render_table(&diagnoses, *format, cli.canonical)?);
+            let failed = diagnoses
+                .iter()
+                .filter(|diagnosis| diagnosis.status == Status::Fail)
+                .count();
+            if failed > 0 {
+                return Err(Error::msg(format!("{} check(s) failed", failed)));
+            }
+            return Ok(());
+        }
+        Some(Commands::Diagnose { strict, format }) => {
+            let findings = diagnose(&git_command, &cli.options)?;
+            println!("{}", render_table(&findings, *format, cli.canonical)?);
+            match (findings.is_empty(), strict) {
+                (true, _) => {}
+                (false, true) => {
+                    return Err(Error::msg(format!(
+                        "The diagnosis found {} finding(s)",
+                        findings.len()
+                    )))
+                }
+                (false, false) => {
+                    eprintln!("Warning: the diagnosis found {} finding(s)", findings.len())
+                }
+            }
+            return Ok(());
+        }
+        Some(Commands::ReleaseNotes(release_notes_args)) => {
+            let apps = cli.options.apps()?;
+            let notes = release_notes(&git_command, &cli.options, &apps, release_notes_args)?;
+            match &release_notes_args.output {
+                Some(output) => fs::write(output, notes)?,
+                None => print!("{}", notes),
+            }
+            return Ok(());
+        }
+        Some(Commands::Audit { format }) => {
+            let findings = audit(&git_command, &cli.options)?;
+            println!("{}", render_table(&findings, *format, cli.canonical)?);
+            if !findings.is_empty() {
+                return Err(Error::msg(format!(
+                    "The audit found {} issue(s)",
+                    findings.len()
+                )));
+            }
+            return Ok(());
+        }
+        _ => {}
     }
-
-    #[test]
-    fn test_get_version_no_app_name_release_branch_with_tag() {
-        let mut git_command = MockGitCommandTrait::new();
-        let app_name: Option<&str> = None;
-        let branch = "release/1.1.0";
-        let rev = "1234567";
-        let count = "1";
-        let version = Some("1.0.0");
-
-        let args = Args {
-            app_name: None,
-            build_number: None,
-            fetch: false,
-        };
-
-        mock_git(&mut git_command, app_name, branch, rev, count, version);
-
-        let result = get_version_output(&args, &git_command);
-
-        assert!(result.is_ok());
-
-        let output = result.unwrap();
-
-        let mut expected_version = Version::parse("1.1.0").unwrap();
-        expected_version.pre = Prerelease::new(&format!("rc.{}", count)).unwrap();
-        expected_version.build = BuildMetadata::new(&rev).unwrap();
-        assert_expected_version(branch, rev, count, expected_version, output);
+    run_hooks(&cli.options, HookEvent::PreCompute, None)?;
+    let apps = cli.options.apps()?;
+    if !apps.is_empty() {
+        if cli.command.is_some()
+            || cli.export_env.is_some()
+            || cli.ci.is_some()
+            || cli.template.is_some()
+            || cli.template_file.is_some()
+            || cli.output_file.is_some()
+            || !cli.write.is_empty()
+            || cli.provenance.is_some()
+            || output_format != OutputFormat::Json
+        {
+            return Err(Error::msg(
+                "Multiple applications are only supported for the JSON version output",
+            ));
+        }
+        let versions = calculate_apps(&git_command, &cli.options, &apps)?;
+        run_app_hooks(&cli.options, &versions)?;
+        let versions: Map<_, _> = versions
+            .into_iter()
+            .map(|(app, version_info)| {
+                #[cfg(feature = "otel")]
+                tracer.set_attribute(&format!("version_vine.apps.{}", app), &version_info.version);
+                let json = output_shape(version_info.to_json(), schema_version)?;
+                Ok((app, select_fields(json, &fields)?))
+            })
+            .collect::<Result<_>>()?;
+        println!("{}", to_json_string(&versions, cli.canonical)?);
+        return Ok(());
     }
-
-    #[test]
-    fn test_get_version_no_app_name_release_branch_without_tag() {
-        let mut git_command = MockGitCommandTrait::new();
-        let app_name: Option<&str> = None;
-        let branch = "release/1.1.0";
-        let rev = "1234567";
-        let count = "1";
-        let version = None;
-
-        let args = Args {
-            app_name: None,
-            build_number: None,
-            fetch: false,
+    let version_info = VersionCalculator::new(&git_command, cli.options.clone())?.calculate()?;
+    run_hooks(&cli.options, HookEvent::PostCompute, Some(&version_info))?;
+    #[cfg(feature = "otel")]
+    trace_version(&tracer, &version_info);
+    if let Some(path) = &cli.provenance {
+        let now = match env::var("SOURCE_DATE_EPOCH")
+            .ok()
+            .and_then(|epoch| epoch.parse().ok())
+        {
+            Some(epoch) => epoch,
+            None if cli.options.reproducible => version_info.commit.epoch.unwrap_or_default(),
+            None => SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
         };
-
-        mock_git(&mut git_command, app_name, branch, rev, count, version);
-
-        let result = get_version_output(&args, &git_command);
-
-        assert!(result.is_ok());
-
-        let output = result.unwrap();
-
-        let mut expected_version = Version::parse("1.1.0").unwrap();
-        expected_version.pre = Prerelease::new(&format!("rc.{}", count)).unwrap();
-        expected_version.build = BuildMetadata::new(&rev).unwrap();
-        assert_expected_version(branch, rev, count, expected_version, output);
+        let document = provenance(
+            &git_command,
+            &cli.options,
+            &version_info,
+            |name| env::var(name).ok(),
+            now,
+        )?;
+        let signing = cli
+            .provenance_key
+            .as_deref()
+            .map(|key| (cli.provenance_signer, key));
+        write_provenance(&cli.options.config.execution, path, &document, signing)?;
     }
-
-    #[test]
-    fn test_get_version_feature_branch_with_tag() {
-        let mut git_command = MockGitCommandTrait::new();
-        let app_name = Some("myapp");
-        let branch = "feature/feat-1";
-        let rev = "1234567";
-        let count = "1";
-        let version = Some("1.0.0");
-
-        let args = Args {
-            app_name: Some(String::from("myapp")),
-            build_number: None,
-            fetch: false,
-        };
-
-        mock_git(&mut git_command, app_name, branch, rev, count, version);
-
-        let result = get_version_output(&args, &git_command);
-
-        assert!(result.is_ok());
-
-        let output = result.unwrap();
-
-        let mut expected_version = Version::parse("1.0.0").unwrap();
-        expected_version.patch += 1;
-        expected_version.pre = Prerelease::new(&format!("alpha.{}", count)).unwrap();
-        expected_version.build = BuildMetadata::new(&format!("feature-feat-1.{}", rev)).unwrap();
-        assert_expected_version(branch, rev, count, expected_version, output);
+    if let (None, Some(ci)) = (&cli.command, cli.ci) {
+        let output = ci_integration(&version_info, &cli.options, ci, |name| env::var(name).ok())?;
+        if !output.is_empty() {
+            println!("{}", output);
+        }
+        return Ok(());
     }
-
-    #[test]
-    fn test_get_version_feature_branch_without_tag() {
-        let mut git_command = MockGitCommandTrait::new();
-        let app_name = Some("myapp");
-        let branch = "feature/feat-1";
-        let rev = "1234567";
-        let count = "1";
-        let version = None;
-
-        let args = Args {
-            app_name: Some(String::from("myapp")),
-            build_number: None,
-            fetch: false,
-        };
-
-        mock_git(&mut git_command, app_name, branch, rev, count, version);
-
-        let result = get_version_output(&args, &git_command);
-
-        assert!(result.is_ok());
-
-        let output = result.unwrap();
-
-        let mut expected_version = Version::parse("0.0.0").unwrap();
-        expected_version.patch += 1;
-        expected_version.pre = Prerelease::new(&format!("alpha.{}", count)).unwrap();
-        expected_version.build = BuildMetadata::new(&format!("feature-feat-1.{}", rev)).unwrap();
-        assert_expected_version(branch, rev, count, expected_version, output);
+    match &cli.command {
+        None => match cli.export_env {
+            Some(export) => {
+                let output = export_env(&version_info, &cli.options, export, |name| {
+                    env::var(name).ok()
+                })?;
+                if !output.is_empty() {
+                    println!("{}", output);
+                }
+            }
+            None => {
+                let output = match (&cli.template, &cli.template_file) {
+                    (Some(template), _) => render_template(&version_info, &cli.options, template)?,
+                    (None, Some(path)) => {
+                        let template = fs::read_to_string(path).map_err(|err| {
+                            Error::msg(format!("Cannot read '{}': {}", path.display(), err))
+                        })?;
+                        render_template(&version_info, &cli.options, &template)?
+                    }
+                    (None, None) => render(
+                        &version_info,
+                        output_format,
+                        &fields,
+                        schema_version,
+                        cli.canonical,
+                    )?,
+                };
+                // Rendered up front, so an unknown extension doesn't leave some files written
+                let mut files = vec![];
+                for path in &cli.write {
+                    let format = OutputFormat::from_path(path)?;
+                    let rendered = render(
+                        &version_info,
+                        format,
+                        &fields,
+                        schema_version,
+                        cli.canonical,
+                    )?;
+                    files.push((path, rendered));
+                }
+                if let Some(path) = &cli.output_file {
+                    files.insert(0, (path, output.clone()));
+                }
+                for (path, rendered) in &files {
+                    write_atomic(path, rendered)?;
+                }
+                if files.is_empty() || !cli.quiet {
+                    match output.ends_with('\n') {
+                        true => print!("{}", output),
+                        false => println!("{}", output),
+                    }
+                }
+            }
+        },
+        Some(Commands::ImagePolicy {
+            semver_range,
+            pattern,
+            extract,
+        }) => {
+            let policy = ImagePolicy::new(pattern, extract, semver_range)?;
+            let report = policy.check(&version_info.container_tag);
+            println!("{}", to_json_string(&report, cli.canonical)?);
+            if report["picked_up"] != true {
+                return Err(Error::msg(
+                    "The container tag would not be picked up by the image policy",
+                ));
+            }
+        }
+        Some(Commands::Constraints { consumers, format }) => {
+            let consumers = load_consumers(&cli.options, consumers)?;
+            let constraints = check_constraints(&version_info, &consumers)?;
+            println!("{}", render_table(&constraints, *format, cli.canonical)?);
+            let broken = constraints.iter().filter(|c| !c.satisfied).count();
+            if broken > 0 {
+                return Err(Error::msg(format!(
+                    "The version {} breaks {} consumer(s)",
+                    version_info.version, broken
+                )));
+            }
+        }
+        Some(Commands::ManifestPlan {
+            platforms,
+            variants,
+            image_name,
+            format,
+        }) => {
+            let platforms = platforms
+                .iter()
+                .map(|platform| Platform::parse(platform))
+                .collect::<Result<Vec<_>>>()?;
+            let plan = plan_manifests(
+                &version_info.container_tag,
+                &platforms,
+                variants,
+                image_name,
+            );
+            match format {
+                TableFormat::Json => println!("{}", to_json_string(&plan, cli.canonical)?),
+                TableFormat::Csv => println!(
+                    "{}",
+                    render_table(&image_rows(&plan), *format, cli.canonical)?
+                ),
+            }
+        }
+        Some(Commands::Graph { format }) => {
+            let graph = version_graph(&git_command, &cli.options, &version_info, *format)?;
+            println!("{}", graph);
+        }
+        Some(Commands::Changelog(changelog_args)) => {
+            let section = changelog(&git_command, &cli.options, &version_info, changelog_args)?;
+            match &changelog_args.output {
+                Some(output) => {
+                    let existing = read_changelog(output)?;
+                    fs::write(output, prepend(&existing, &section))?;
+                }
+                None => print!("{}", section),
+            }
+        }
+        Some(Commands::Set(set_args)) => {
+            let result = set_version(&version_info, &cli.options, set_args)?;
+            println!("{}", to_json_string(&result, cli.canonical)?);
+        }
+        Some(Commands::Tag(tag_args)) => {
+            let mut result = create_tag(&git_command, &cli.options, &version_info, tag_args)?;
+            push_metrics(&cli.options, &version_info, &mut result)?;
+            println!("{}", to_json_string(&result, cli.canonical)?);
+        }
+        Some(Commands::Checksums(checksums_args)) => {
+            let result = write_checksums(&cli.options, &version_info, checksums_args)?;
+            println!("{}", to_json_string(&result, cli.canonical)?);
+        }
+        Some(Commands::Promote(promote_args)) => {
+            let mut result = promote(&git_command, &cli.options, &version_info, promote_args)?;
+            push_metrics(&cli.options, &version_info, &mut result["tagged"])?;
+            match output_format {
+                OutputFormat::Plain => println!("{}", result["release"].as_str().unwrap()),
+                _ => println!("{}", to_json_string(&result, cli.canonical)?),
+            }
+        }
+        Some(Commands::StartRelease(start_release_args)) => {
+            let result = start_release(
+                &git_command,
+                &cli.options,
+                &version_info,
+                start_release_args,
+            )?;
+            match output_format {
+                OutputFormat::Plain => {
+                    println!("{}", result["rc_version"].as_str().unwrap_or_default())
+                }
+                _ => println!("{}", to_json_string(&result, cli.canonical)?),
+            }
+        }
+        Some(Commands::Plan(plan_args)) => {
+            let plan = create_plan(&git_command, &cli.options, &version_info, plan_args)?;
+            println!("{}", to_json_string(&plan, cli.canonical)?);
+        }
+        Some(Commands::Apply { .. })
+        | Some(Commands::Schema)
+        | Some(Commands::Explain)
+        | Some(Commands::SupportBundle(_))
+        | Some(Commands::Stats { .. })
+        | Some(Commands::Next { .. })
+        | Some(Commands::Latest { .. })
+        | Some(Commands::Compare { .. })
+        | Some(Commands::Verify { .. })
+        | Some(Commands::Audit { .. })
+        | Some(Commands::ReleaseNotes(_))
+        | Some(Commands::Validate { .. })
+        | Some(Commands::Diagnose { .. })
+        | Some(Commands::Doctor { .. })
+        | Some(Commands::Batch { .. })
+        | Some(Commands::Ui) => unreachable!(),
+        #[cfg(feature = "interactive")]
+        Some(Commands::Interactive) => unreachable!(),
+        #[cfg(feature = "self-update")]
+        Some(Commands::SelfUpdate(_)) => unreachable!(),
+        #[cfg(feature = "forge")]
+        Some(Commands::BumpPr(bump_pr_args)) => {
+            let http_client = forge::HttpClient {
+                offline: cli.options.config.execution.offline,
+            };
+            let result = bump_pr::create_bump_pr(
+                &git_command,
+                &http_client,
+                &cli.options,
+                &version_info,
+                bump_pr_args,
+            )?;
+            println!("{}", to_json_string(&result, cli.canonical)?);
+        }
+        #[cfg(feature = "forge")]
+        Some(Commands::PrComment(pr_comment_args)) => {
+            let http_client = forge::HttpClient {
+                offline: cli.options.config.execution.offline,
+            };
+            let result = pr_comment::pr_comment(
+                &git_command,
+                &http_client,
+                &cli.options,
+                &version_info,
+                pr_comment_args,
+            )?;
+            println!("{}", to_json_string(&result, cli.canonical)?);
+        }
+        #[cfg(feature = "forge")]
+        Some(Commands::Release(release_args)) => {
+            let http_client = forge::HttpClient {
+                offline: cli.options.config.execution.offline,
+            };
+            let result = release::create_release(
+                &git_command,
+                &http_client,
+                &cli.options,
+                &version_info,
+                release_args,
+            )?;
+            println!("{}", to_json_string(&result, cli.canonical)?);
+        }
     }
+    Ok(())
+}
 
-    #[test]
-    fn test_get_version_no_app_name_feature_branch_with_tag() {
-        let mut git_command = MockGitCommandTrait::new();
-        let app_name: Option<&str> = None;
-        let branch = "feature/feat-1";
-        let rev = "1234567";
-        let count = "1";
-        let version = Some("1.0.0");
-
-        let args = Args {
-            app_name: None,
-            build_number: None,
-            fetch: false,
-        };
-
-        mock_git(&mut git_command, app_name, branch, rev, count, version);
-
-        let result = get_version_output(&args, &git_command);
-
-        assert!(result.is_ok());
-
-        let output = result.unwrap();
-
-        let mut expected_version = Version::parse("1.0.0").unwrap();
-        expected_version.patch += 1;
-        expected_version.pre = Prerelease::new(&format!("alpha.{}", count)).unwrap();
-        expected_version.build = BuildMetadata::new(&format!("feature-feat-1.{}", rev)).unwrap();
-        assert_expected_version(branch, rev, count, expected_version, output);
+/// Pushes the release event of a created tag to the `[metrics]` endpoints, adding the URLs pushed
+/// to as `metrics`. A failed push only warns, as the release is tagged already.
+#[cfg(feature = "forge")]
+fn push_metrics(
+    options: &VersionOptions,
+    version_info: &VersionInfo,
+    tagged: &mut serde_json::Value,
+) -> Result<()> {
+    use version_vine::metrics::{push_release_event, ReleaseEvent};
+
+    if tagged["created"] != true {
+        return Ok(());
     }
+    let event = ReleaseEvent {
+        app_name: options.app_name.clone(),
+        version: tagged["version"].as_str().unwrap_or_default().to_string(),
+        tag: tagged["tag"].as_str().unwrap_or_default().to_string(),
+        branch_class: version_info.branch_class,
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+    };
+    let urls = push_release_event(
+        &forge::HttpClient {
+            offline: options.config.execution.offline,
+        },
+        &options.config.metrics,
+        &event,
+    )
+    .unwrap_or_else(|err| {
+        eprintln!("Warning: Cannot push the release event: {}", err);
+        vec![]
+    });
+    tagged["metrics"] = serde_json::json!(urls);
+    Ok(())
+}
 
-    #[test]
-    fn test_get_version_no_app_name_feature_branch_without_tag() {
-        let mut git_command = MockGitCommandTrait::new();
-        let app_name: Option<&str> = None;
-        let branch = "feature/feat-1";
-        let rev = "1234567";
-        let count = "1";
-        let version = None;
-
-        let args = Args {
-            app_name: None,
-            build_number: None,
-            fetch: false,
-        };
-
-        mock_git(&mut git_command, app_name, branch, rev, count, version);
-
-        let result = get_version_output(&args, &git_command);
-
-        assert!(result.is_ok());
-
-        let output = result.unwrap();
-
-        let mut expected_version = Version::parse("0.0.0").unwrap();
-        expected_version.patch += 1;
-        expected_version.pre = Prerelease::new(&format!("alpha.{}", count)).unwrap();
-        expected_version.build = BuildMetadata::new(&format!("feature-feat-1.{}", rev)).unwrap();
-        assert_expected_version(branch, rev, count, expected_version, output);
+#[cfg(not(feature = "forge"))]
+fn push_metrics(
+    options: &VersionOptions,
+    _version_info: &VersionInfo,
+    _tagged: &mut serde_json::Value,
+) -> Result<()> {
+    let metrics = &options.config.metrics;
+    if metrics.pushgateway_url.is_some() || metrics.otlp_url.is_some() {
+        eprintln!("Warning: Cannot push the release event: version-vine is built without the 'forge' feature");
     }
+    Ok(())
+}
 
-    fn mock_git<'a>(
-        git_command: &mut MockGitCommandTrait,
-        app_name: Option<&'a str>,
-        branch: &'a str,
-        rev: &'a str,
-        count: &'a str,
-        version: Option<&'a str>,
-    ) where
-        'a: 'static,
-    {
-        git_command
-            .expect_run()
-            .withf(|args| args[0] == "fetch" && args[1] == "--tags")
-            .returning(|_| Ok(String::from("")));
-
-        git_command
-            .expect_run()
-            .withf(|args| args[0] == "branch" && args[1] == "--show-current")
-            .returning(|_| Ok(branch.to_string()));
-
-        git_command
-            .expect_run()
-            .withf(|args| args[0] == "rev-parse" && args[1] == "--short" && args[2] == "HEAD")
-            .returning(|_| Ok(rev.to_string()));
-
-        git_command
-            .expect_run()
-            .withf(|args| args[0] == "rev-list" && args[1] == "--count" && args[2] == "HEAD")
-            .returning(move |_| Ok(count.to_string()));
-
-        let exact_version: &str;
-        if version.is_some() {
-            if app_name.is_none() {
-                git_command
-                    .expect_run()
-                    .withf(|args| {
-                        args[0] == "describe" && args[1] == "--abbrev=0" && args[2] == "--tags"
-                    })
-                    .returning(move |_| Ok(version.unwrap().to_string()));
-            } else {
-                git_command
-                    .expect_run()
-                    .withf(move |args| {
-                        args[0] == "describe"
-                            && args[1] == "--abbrev=0"
-                            && args[2] == "--match"
-                            && args[3] == format!("{}-*", app_name.unwrap())
-                            && args[4] == "--tags"
-                    })
-                    .returning(move |_| Ok(format!("{}-{}", app_name.unwrap(), version.unwrap())));
-            }
-            exact_version = version.unwrap();
-        } else {
-            if app_name.is_none() {
-                git_command
-                    .expect_run()
-                    .withf(|args| {
-                        args[0] == "describe" && args[1] == "--abbrev=0" && args[2] == "--tags"
-                    })
-                    .returning(|_| Err(Error::msg("No tag found")));
-            } else {
-                git_command
-                    .expect_run()
-                    .withf(move |args| {
-                        args[0] == "describe"
-                            && args[1] == "--abbrev=0"
-                            && args[2] == "--match"
-                            && args[3] == format!("{}-*", app_name.unwrap())
-                            && args[4] == "--tags"
-                    })
-                    .returning(|_| Err(Error::msg("No tag found")));
+/// The computed version and the decisions leading to it, as attributes of the traced run
+#[cfg(feature = "otel")]
+fn trace_version(tracer: &Tracer, version_info: &VersionInfo) {
+    let output = version_info.to_json();
+    tracer.set_attribute("service.version", &version_info.version);
+    tracer.set_attribute("vcs.ref.head.name", &version_info.git_branch);
+    for field in [
+        "app_version",
+        "branch_class",
+        "bump",
+        "channel",
+        "changed_since_tag",
+        "pr_number",
+        "signature_verified",
+    ] {
+        match &output[field] {
+            serde_json::Value::Null => {}
+            serde_json::Value::String(value) => {
+                tracer.set_attribute(&format!("version_vine.{}", field), value)
             }
-            exact_version = "0.0.0";
-        }
-        if app_name.is_none() {
-            git_command
-                .expect_run()
-                .withf(|args| {
-                    args[0] == "describe"
-                        && args[1] == "--abbrev=0"
-                        && args[2] == "--exact-match"
-                        && args[3] == "--tags"
-                })
-                .returning(move |_| Ok(exact_version.to_string()));
-        } else {
-            git_command
-                .expect_run()
-                .withf(move |args| {
-                    args[0] == "describe"
-                        && args[1] == "--abbrev=0"
-                        && args[2] == "--exact-match"
-                        && args[3] == "--tags"
-                })
-                .returning(move |_| Ok(format!("{}-{}", app_name.unwrap(), exact_version)));
+            value => tracer.set_attribute(&format!("version_vine.{}", field), value),
         }
     }
-
-    fn assert_expected_version(
-        branch: &str,
-        rev: &str,
-        count: &str,
-        expected_version: Version,
-        output: Value,
-    ) {
-        let expected_output = json!(
-            {
-                "git_branch": branch,
-                "git_rev": rev,
-                "rev_count": count,
-                "app_version":  format!("{}", expected_version),
-                "container_tag": format!("{}", expected_version).replace('+', ".")
-            }
-        );
-        assert_eq!(output, expected_output);
-    }
 }
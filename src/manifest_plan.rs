@@ -0,0 +1,218 @@
+use anyhow::{Error, Result};
+use serde_json::{json, Value};
+
+/// An OCI platform in `os/architecture[/variant]` notation, e.g. `linux/arm/v7`
+#[derive(Debug, PartialEq)]
+pub struct Platform {
+    pub os: String,
+    pub architecture: String,
+    pub variant: Option<String>,
+}
+
+impl Platform {
+    pub fn parse(platform: &str) -> Result<Self, Error> {
+        let parts: Vec<&str> = platform.split('/').collect();
+        match parts.as_slice() {
+            [os, architecture] => Ok(Self {
+                os: os.to_string(),
+                architecture: architecture.to_string(),
+                variant: None,
+            }),
+            [os, architecture, variant] => Ok(Self {
+                os: os.to_string(),
+                architecture: architecture.to_string(),
+                variant: Some(variant.to_string()),
+            }),
+            _ => Err(Error::msg(format!(
+                "Invalid platform '{}'. Expected 'os/architecture[/variant]', e.g. 'linux/amd64'.",
+                platform
+            ))),
+        }
+    }
+
+    /// Suffix used for the per platform tag. The OS is left out for linux, as it is by convention
+    /// for images like `1.0.0-arm64`.
+    fn tag_suffix(&self) -> String {
+        let mut suffix = if self.os == "linux" {
+            self.architecture.clone()
+        } else {
+            format!("{}-{}", self.os, self.architecture)
+        };
+        if let Some(variant) = &self.variant {
+            suffix.push_str(variant);
+        }
+        suffix
+    }
+
+    fn to_platform_string(&self) -> String {
+        match &self.variant {
+            Some(variant) => format!("{}/{}/{}", self.os, self.architecture, variant),
+            None => format!("{}/{}", self.os, self.architecture),
+        }
+    }
+}
+
+/// Plans the per platform image tags and the manifest list tag combining them, for each variant
+/// (e.g. `alpine`, `slim`). Without variants, a single manifest list is planned.
+pub fn plan_manifests(
+    container_tag: &str,
+    platforms: &[Platform],
+    variants: &[String],
+    image_name: &Option<String>,
+) -> Value {
+    let reference = |tag: &str| image_name.as_ref().map(|name| format!("{}:{}", name, tag));
+    let variants: Vec<Option<&String>> = if variants.is_empty() {
+        vec![None]
+    } else {
+        variants.iter().map(Some).collect()
+    };
+
+    let manifests: Vec<Value> = variants
+        .into_iter()
+        .map(|variant| {
+            let manifest_tag = match variant {
+                Some(variant) => format!("{}-{}", container_tag, variant),
+                None => container_tag.to_string(),
+            };
+            let images: Vec<Value> = platforms
+                .iter()
+                .map(|platform| {
+                    let tag = format!("{}-{}", manifest_tag, platform.tag_suffix());
+                    json!({
+                        "platform": platform.to_platform_string(),
+                        "os": platform.os,
+                        "architecture": platform.architecture,
+                        "variant": platform.variant,
+                        "tag": tag,
+                        "reference": reference(&tag),
+                    })
+                })
+                .collect();
+            json!({
+                "variant": variant,
+                "tag": manifest_tag,
+                "reference": reference(&manifest_tag),
+                "images": images,
+            })
+        })
+        .collect();
+
+    json!({
+        "container_tag": container_tag,
+        "manifests": manifests,
+    })
+}
+
+/// Rows of a manifest plan, one per platform image with the manifest list it belongs to, for
+/// tabular output
+pub fn image_rows(plan: &Value) -> Vec<Value> {
+    let empty = vec![];
+    let manifests = plan["manifests"].as_array().unwrap_or(&empty);
+    manifests
+        .iter()
+        .flat_map(|manifest| {
+            let images = manifest["images"].as_array().unwrap_or(&empty);
+            images.iter().map(move |image| {
+                json!({
+                    "manifest_tag": manifest["tag"],
+                    "manifest_reference": manifest["reference"],
+                    "variant": manifest["variant"],
+                    "platform": image["platform"],
+                    "os": image["os"],
+                    "architecture": image["architecture"],
+                    "platform_variant": image["variant"],
+                    "tag": image["tag"],
+                    "reference": image["reference"],
+                })
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_platform() {
+        assert_eq!(
+            Platform::parse("linux/arm/v7").unwrap(),
+            Platform {
+                os: "linux".to_string(),
+                architecture: "arm".to_string(),
+                variant: Some("v7".to_string()),
+            }
+        );
+        assert!(Platform::parse("amd64").is_err());
+    }
+
+    #[test]
+    fn test_plan_manifests() {
+        let platforms = vec![
+            Platform::parse("linux/amd64").unwrap(),
+            Platform::parse("linux/arm64/v8").unwrap(),
+            Platform::parse("windows/amd64").unwrap(),
+        ];
+
+        let plan = plan_manifests(
+            "1.0.1-beta.2.1234567",
+            &platforms,
+            &[],
+            &Some("ghcr.io/org/app".to_string()),
+        );
+
+        let manifest = &plan["manifests"][0];
+        assert_eq!(manifest["tag"], "1.0.1-beta.2.1234567");
+        assert_eq!(
+            manifest["reference"],
+            "ghcr.io/org/app:1.0.1-beta.2.1234567"
+        );
+        assert_eq!(manifest["images"][0]["tag"], "1.0.1-beta.2.1234567-amd64");
+        assert_eq!(manifest["images"][1]["tag"], "1.0.1-beta.2.1234567-arm64v8");
+        assert_eq!(manifest["images"][1]["platform"], "linux/arm64/v8");
+        assert_eq!(
+            manifest["images"][2]["tag"],
+            "1.0.1-beta.2.1234567-windows-amd64"
+        );
+    }
+
+    #[test]
+    fn test_plan_manifests_with_variants() {
+        let platforms = vec![Platform::parse("linux/amd64").unwrap()];
+
+        let plan = plan_manifests(
+            "1.0.0.1234567",
+            &platforms,
+            &["alpine".to_string(), "slim".to_string()],
+            &None,
+        );
+
+        assert_eq!(plan["manifests"][0]["tag"], "1.0.0.1234567-alpine");
+        assert_eq!(
+            plan["manifests"][1]["images"][0]["tag"],
+            "1.0.0.1234567-slim-amd64"
+        );
+        assert_eq!(plan["manifests"][1]["reference"], Value::Null);
+    }
+
+    #[test]
+    fn test_image_rows() {
+        let platforms = vec![
+            Platform::parse("linux/amd64").unwrap(),
+            Platform::parse("linux/arm64").unwrap(),
+        ];
+        let plan = plan_manifests(
+            "1.0.0.1234567",
+            &platforms,
+            &["alpine".to_string(), "slim".to_string()],
+            &None,
+        );
+
+        let rows = image_rows(&plan);
+
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[3]["manifest_tag"], "1.0.0.1234567-slim");
+        assert_eq!(rows[3]["variant"], "slim");
+        assert_eq!(rows[3]["tag"], "1.0.0.1234567-slim-arm64");
+    }
+}
@@ -0,0 +1,400 @@
+use anyhow::{Error, Result};
+use regex_lite::Regex;
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+
+/// The human-facing messages of `--explain`, `ui` and `doctor` by key, as English templates with `{name}`
+/// placeholders. The `[messages]` configuration overrides them, e.g. to localize them. Machine
+/// readable outputs, like the `trace` output field, always use the English templates.
+pub const CATALOG: &[(&str, &str)] = &[
+    ("explain.branch", "'{branch}'"),
+    ("explain.branch_ref", "'{branch}' of --ref {ref}"),
+    (
+        "explain.branch_simulated",
+        "'{branch}', simulating the merge of {head}",
+    ),
+    (
+        "explain.branch_alias",
+        "'{target}', as '{branch}' is an alias of it",
+    ),
+    ("explain.no_commits", "The branch doesn't have any commits"),
+    ("explain.commit", "{rev} with {count} commits in total"),
+    (
+        "explain.class",
+        "{class}, matching '{pattern}'",
+    ),
+    (
+        "explain.class_first",
+        "{class}, matching '{pattern}' first of the matching {others} patterns",
+    ),
+    (
+        "explain.class_none",
+        "feature, matching none of the branch patterns",
+    ),
+    (
+        "explain.class_custom",
+        "{class}, matching the '{name}' custom class '{pattern}'",
+    ),
+    (
+        "explain.class_pull_request",
+        "pull-request, the build of pull request {number}",
+    ),
+    ("explain.tag_none", "None found, starting from 0.0.0"),
+    ("explain.tag_none_from", "None found, starting from '{tag}'"),
+    ("explain.tag_on_commit", "'{tag}' on the commit"),
+    (
+        "explain.tag_branch_point",
+        "'{tag}', the latest at the branch point",
+    ),
+    (
+        "explain.tag_support",
+        "'{tag}', the highest of the {line}.x line",
+    ),
+    (
+        "explain.tag_reachable",
+        "'{tag}', the latest reachable from the commit",
+    ),
+    (
+        "explain.tag_nightly",
+        "'{tag}', the latest for the nightly build",
+    ),
+    (
+        "explain.tag_ahead",
+        "'{branch}' is ahead of the tag, starting from {version}",
+    ),
+    (
+        "explain.tag_release_branch",
+        "None, {version} is taken from the release branch name",
+    ),
+    ("explain.tag_file", "None, {version} is taken from '{path}'"),
+    (
+        "explain.tag_trailer",
+        "None, {version} is taken from the 'Version' trailer",
+    ),
+    (
+        "explain.bump_hotfix",
+        "patch, as hotfix branches patch their release",
+    ),
+    (
+        "explain.bump_support",
+        "patch, as support branches patch their release line",
+    ),
+    (
+        "explain.bump_patch",
+        "patch, following the patch bump strategy",
+    ),
+    (
+        "explain.bump_conventional",
+        "{bump}, following the Conventional Commits since the latest tag",
+    ),
+    ("explain.bump_hint", "{bump}, raised by the [increment] hints"),
+    ("explain.counter", "{counter}, {source}"),
+    ("explain.counter_build_number", "the --build-number"),
+    ("explain.counter_total", "the commits in total"),
+    ("explain.counter_ahead", "the commits ahead of '{base}'"),
+    ("explain.counter_since_tag", "the commits since the tag '{tag}'"),
+    (
+        "explain.counter_first_parent",
+        "the first-parent commits since the tag '{tag}'",
+    ),
+    (
+        "explain.counter_no_merges",
+        "the commits since the tag '{tag}' without the merge commits",
+    ),
+    (
+        "explain.on_tag",
+        "HEAD is on the tag '{tag}', versioned following --on-tag {on_tag}",
+    ),
+    (
+        "explain.clock",
+        "The system clock is {hours} hours behind {source}. Correct the clock, or increase the [calver] max_clock_skew.",
+    ),
+    ("explain.clock_commit", "the commit"),
+    ("explain.clock_remote_ref", "the remote ref '{ref}'"),
+    ("explain.clock_source_date_epoch", "SOURCE_DATE_EPOCH"),
+    ("explain.dirty", "Uncommitted changes in {files}"),
+    (
+        "explain.unique_branch_hash",
+        "{version}, with the hash '{hash}' of the branch name",
+    ),
+    (
+        "explain.unique_taken",
+        "'{tag}' is published on another commit, incrementing the counter",
+    ),
+    (
+        "explain.version",
+        "{version} from {semver} with the {bump} bump, pre-release label '{label}', counter {counter} and escaped branch '{branch}'",
+    ),
+    (
+        "explain.build",
+        "Build metadata '{build}' with the [build_metadata] env variables",
+    ),
+    ("explain.result", "Version {version}"),
+    ("ui.branch", "Branch"),
+    ("ui.version", "Version"),
+    ("ui.container_tag", "Container tag"),
+    ("ui.recent_tags", "Recent tags"),
+    ("ui.affected_apps", "Affected apps"),
+    ("ui.base_tag", "Base tag"),
+    (
+        "ui.actions",
+        "[r] cut release branch  [t] tag  [c] changelog  [q] quit",
+    ),
+    (
+        "ui.release_actions",
+        "[r] cut release branch '{branch}'  [t] tag '{tag}'  [q] quit",
+    ),
+    ("ui.pick_version", "Version [1-{count}, q]:"),
+    ("ui.confirm", "{question} [y/N]"),
+    ("ui.yes", "y"),
+    ("ui.create_branch", "Create branch '{branch}'?"),
+    ("ui.create_tag", "Create tag '{tag}'?"),
+    ("ui.create_tag_head", "Create tag '{tag}' on HEAD?"),
+    ("ui.switched", "Switched to '{branch}'"),
+    ("ui.created_tag", "Created tag '{tag}'"),
+    ("ui.tag_exists", "Tag '{tag}' already points at HEAD"),
+    ("ui.unknown_key", "Unknown key '{key}'"),
+    ("ui.unknown_choice", "Unknown choice '{choice}'"),
+    ("ui.error", "Error: {error}"),
+    ("doctor.skip", "Not checked"),
+    ("doctor.git", "{output}"),
+    ("doctor.git_error", "Git doesn't run: {error}"),
+    (
+        "doctor.git_hint",
+        "Install git, or point --git-path or GIT_EXECUTABLE at it",
+    ),
+    ("doctor.git_old", "{output} is older than {version}"),
+    ("doctor.git_upgrade_hint", "Upgrade git to {version} or later"),
+    ("doctor.git_unknown", "'{output}' isn't the output of git"),
+    (
+        "doctor.git_path_hint",
+        "Point --git-path or GIT_EXECUTABLE at git",
+    ),
+    ("doctor.repository", "Repository '{path}'"),
+    (
+        "doctor.no_repository",
+        "Not inside a git repository: {error}",
+    ),
+    (
+        "doctor.repository_hint",
+        "Run version-vine inside a checkout, or pass it with --repo-path",
+    ),
+    (
+        "doctor.shallow",
+        "The repository is a shallow clone, without the history and tags the version is computed from",
+    ),
+    (
+        "doctor.shallow_hint",
+        "Run `git fetch --unshallow --tags`, check out with `fetch-depth: 0` on GitHub Actions, or use --auto-deepen",
+    ),
+    ("doctor.no_remote", "No remote to fetch from"),
+    (
+        "doctor.fetched",
+        "Fetched from '{remote}' {hours} hour(s) ago",
+    ),
+    (
+        "doctor.fetch_stale",
+        "Last fetched from '{remote}' {hours} hour(s) ago, more than {max_fetch_age} hour(s)",
+    ),
+    ("doctor.never_fetched", "Never fetched from '{remote}'"),
+    (
+        "doctor.fetch_hint",
+        "Run `git fetch --tags`, or compute the version with --fetch",
+    ),
+    ("doctor.no_tags", "No tags yet, versions start from 0.0.0"),
+    ("doctor.tag", "Latest tag '{tag}' is version {version}"),
+    (
+        "doctor.tag_not_semver",
+        "Latest tag '{tag}' isn't a SemVer version",
+    ),
+    (
+        "doctor.tag_hint",
+        "Pass the --app-name of the tags, set the `tag_format` of the configuration, or tag a SemVer version",
+    ),
+    ("doctor.no_branch", "{error}"),
+    (
+        "doctor.branch_hint",
+        "Check out a branch, or pass it with --branch",
+    ),
+    (
+        "doctor.branch_ambiguous",
+        "Branch '{branch}' matches the {classes} branch patterns",
+    ),
+    (
+        "doctor.branch_ambiguous_hint",
+        "Narrow the branch patterns of the configuration, or order them with `priority`",
+    ),
+    ("doctor.branch", "Branch '{branch}' is classified as {class}"),
+    ("doctor.config_invalid", "{error}"),
+    (
+        "doctor.config_hint",
+        "Fix the configuration file, or pass another one with --config",
+    ),
+    ("doctor.pattern_hint", "Fix the pattern of the configuration"),
+    (
+        "doctor.config_valid",
+        "Configuration '{path}' is valid",
+    ),
+    (
+        "doctor.config_default",
+        "No configuration file, the defaults apply",
+    ),
+];
+
+const PLACEHOLDER: &str = r"\{([a-z_]+)\}";
+
+fn placeholders(template: &str) -> Vec<&str> {
+    Regex::new(PLACEHOLDER)
+        .unwrap()
+        .captures_iter(template)
+        .map(|captures| captures.get(1).unwrap().as_str())
+        .collect()
+}
+
+/// The message templates: the catalog with the overrides of the `[messages]` configuration
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Messages {
+    overrides: BTreeMap<String, String>,
+}
+
+impl Messages {
+    /// Fails on overrides of unknown messages, and on placeholders their template doesn't have
+    pub fn new(overrides: &BTreeMap<String, String>) -> Result<Self> {
+        for (key, template) in overrides {
+            let Some((_, english)) = CATALOG.iter().find(|(name, _)| name == key) else {
+                return Err(Error::msg(format!(
+                    "Unknown message '{}' in [messages]",
+                    key
+                )));
+            };
+            let available = placeholders(english);
+            let unknown = placeholders(template)
+                .into_iter()
+                .find(|name| !available.contains(name));
+            if let Some(unknown) = unknown {
+                return Err(Error::msg(format!(
+                    "Unknown placeholder '{{{}}}' in the '{}' message of [messages]. Available: {}",
+                    unknown,
+                    key,
+                    match available.is_empty() {
+                        true => "none".to_string(),
+                        false => available.join(", "),
+                    }
+                )));
+            }
+        }
+        Ok(Self {
+            overrides: overrides.clone(),
+        })
+    }
+
+    /// Template of the message, overridden or English
+    pub fn template<'a>(&'a self, key: &'a str) -> &'a str {
+        match self.overrides.get(key) {
+            Some(template) => template,
+            None => CATALOG
+                .iter()
+                .find(|(name, _)| *name == key)
+                .map_or(key, |(_, template)| template),
+        }
+    }
+
+    /// Renders a message of the catalog
+    pub fn get(&self, message: &Message) -> String {
+        let template = self.template(message.key);
+        Regex::new(PLACEHOLDER)
+            .unwrap()
+            .replace_all(template, |captures: &regex_lite::Captures| {
+                let name = &captures[1];
+                match message.args.iter().find(|(arg, _)| *arg == name) {
+                    Some((_, Arg::Text(text))) => text.clone(),
+                    Some((_, Arg::Message(message))) => self.get(message),
+                    None => captures[0].to_string(),
+                }
+            })
+            .into_owned()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Arg {
+    Text(String),
+    Message(Message),
+}
+
+/// A message of the catalog with the values of its placeholders. Displayed in English, rendered
+/// with the overrides by `Messages::get`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Message {
+    key: &'static str,
+    args: Vec<(&'static str, Arg)>,
+}
+
+impl Message {
+    pub fn new(key: &'static str) -> Self {
+        Self { key, args: vec![] }
+    }
+
+    pub fn arg(mut self, name: &'static str, value: impl Display) -> Self {
+        self.args.push((name, Arg::Text(value.to_string())));
+        self
+    }
+
+    /// Placeholder filled with another message, rendered with the same templates
+    pub fn message(mut self, name: &'static str, message: Message) -> Self {
+        self.args.push((name, Arg::Message(message)));
+        self
+    }
+}
+
+impl Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&Messages::default().get(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages() {
+        let message = Message::new("explain.counter").arg("counter", 3).message(
+            "source",
+            Message::new("explain.counter_since_tag").arg("tag", "1.2.0"),
+        );
+        assert_eq!(message.to_string(), "3, the commits since the tag '1.2.0'");
+
+        let messages = Messages::new(&BTreeMap::from([
+            (
+                "explain.counter".to_string(),
+                "{counter}, {source}".to_string(),
+            ),
+            (
+                "explain.counter_since_tag".to_string(),
+                "die Commits seit dem Tag '{tag}'".to_string(),
+            ),
+        ]))
+        .unwrap();
+        assert_eq!(
+            messages.get(&message),
+            "3, die Commits seit dem Tag '1.2.0'"
+        );
+        assert_eq!(messages.template("ui.branch"), "Branch");
+
+        let err = Messages::new(&BTreeMap::from([(
+            "ui.branch".to_string(),
+            "Zweig {branch}".to_string(),
+        )]))
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unknown placeholder '{branch}' in the 'ui.branch' message of [messages]. Available: none"
+        );
+        assert!(Messages::new(&BTreeMap::from([(
+            "ui.unknown".to_string(),
+            "?".to_string()
+        )]))
+        .is_err());
+    }
+}
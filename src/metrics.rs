@@ -0,0 +1,209 @@
+use anyhow::Result;
+use serde_json::{json, Value};
+
+use crate::config::MetricsConfig;
+use crate::forge::{HttpClientTrait, HttpRequest};
+use crate::models::BranchClass;
+
+/// A created release tag, pushed as deployment marker to the dashboards
+#[derive(Debug, PartialEq)]
+pub struct ReleaseEvent {
+    pub app_name: Option<String>,
+    pub version: String,
+    pub tag: String,
+    pub branch_class: BranchClass,
+    /// Seconds since the epoch
+    pub timestamp: u64,
+}
+
+/// Pushes the release event to the Prometheus Pushgateway and the OTLP endpoint of the `[metrics]`
+/// configuration, returning the URLs pushed to. Nothing is pushed without endpoints.
+pub fn push_release_event(
+    http_client: &impl HttpClientTrait,
+    config: &MetricsConfig,
+    event: &ReleaseEvent,
+) -> Result<Vec<String>> {
+    let mut requests = vec![];
+    if let Some(url) = &config.pushgateway_url {
+        requests.push(pushgateway_request(
+            url.trim_end_matches('/'),
+            config,
+            event,
+        ));
+    }
+    if let Some(url) = &config.otlp_url {
+        requests.push(otlp_request(url.trim_end_matches('/'), event));
+    }
+    for request in &requests {
+        http_client.send(request)?;
+    }
+    Ok(requests.into_iter().map(|request| request.url).collect())
+}
+
+/// `version_vine_release_timestamp_seconds` gauge in the text exposition format, grouped by job
+/// and app, so each release replaces the previous one of the app
+fn pushgateway_request(url: &str, config: &MetricsConfig, event: &ReleaseEvent) -> HttpRequest {
+    let mut url = format!("{}/metrics/job/{}", url, path_segment(&config.job));
+    if let Some(app_name) = &event.app_name {
+        url.push_str(&format!("/app/{}", path_segment(app_name)));
+    }
+    let labels = [
+        ("version", event.version.as_str()),
+        ("tag", event.tag.as_str()),
+        ("branch_class", &branch_class_name(event.branch_class)),
+    ]
+    .iter()
+    .map(|(name, value)| format!("{}=\"{}\"", name, label_escape(value)))
+    .collect::<Vec<_>>()
+    .join(",");
+    let body = format!(
+        "# HELP version_vine_release_timestamp_seconds Time of the latest release tag.\n\
+         # TYPE version_vine_release_timestamp_seconds gauge\n\
+         version_vine_release_timestamp_seconds{{{}}} {}\n",
+        labels, event.timestamp
+    );
+    HttpRequest {
+        method: "POST".to_string(),
+        url,
+        headers: vec![(
+            "Content-Type".to_string(),
+            "text/plain; version=0.0.4".to_string(),
+        )],
+        body: Some(Value::String(body)),
+    }
+}
+
+/// `version_vine.release` gauge of the OTLP/HTTP JSON encoding, with the release as attributes
+fn otlp_request(url: &str, event: &ReleaseEvent) -> HttpRequest {
+    let url = match url.ends_with("/v1/metrics") {
+        true => url.to_string(),
+        false => format!("{}/v1/metrics", url),
+    };
+    let attribute = |key: &str, value: &str| json!({"key": key, "value": {"stringValue": value}});
+    let service_name = event.app_name.as_deref().unwrap_or("version-vine");
+    let time_unix_nano = (event.timestamp * 1_000_000_000).to_string();
+    let body = json!({
+        "resourceMetrics": [{
+            "resource": {"attributes": [attribute("service.name", service_name)]},
+            "scopeMetrics": [{
+                "scope": {"name": "version-vine", "version": env!("CARGO_PKG_VERSION")},
+                "metrics": [{
+                    "name": "version_vine.release",
+                    "description": "Release tag created by version-vine",
+                    "unit": "1",
+                    "gauge": {
+                        "dataPoints": [{
+                            "asInt": "1",
+                            "timeUnixNano": time_unix_nano,
+                            "attributes": [
+                                attribute("service.version", &event.version),
+                                attribute("vcs.tag", &event.tag),
+                                attribute("branch_class", &branch_class_name(event.branch_class)),
+                            ],
+                        }],
+                    },
+                }],
+            }],
+        }],
+    });
+    HttpRequest {
+        method: "POST".to_string(),
+        url,
+        headers: vec![("Content-Type".to_string(), "application/json".to_string())],
+        body: Some(body),
+    }
+}
+
+fn branch_class_name(branch_class: BranchClass) -> String {
+    json!(branch_class).as_str().unwrap_or_default().to_string()
+}
+
+/// https://github.com/prometheus/docs/blob/main/content/docs/instrumenting/exposition_formats.md
+fn label_escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Percent-encodes the characters which aren't allowed in a grouping label of the URL path
+fn path_segment(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            byte => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::forge::MockHttpClientTrait;
+
+    use super::*;
+
+    fn release_event() -> ReleaseEvent {
+        ReleaseEvent {
+            app_name: Some("my app".to_string()),
+            version: "1.2.0".to_string(),
+            tag: "my app-1.2.0".to_string(),
+            branch_class: BranchClass::Main,
+            timestamp: 1707908400,
+        }
+    }
+
+    #[test]
+    fn test_push_release_event() {
+        let config = MetricsConfig {
+            pushgateway_url: Some("http://pushgateway:9091/".to_string()),
+            otlp_url: Some("http://collector:4318".to_string()),
+            ..Default::default()
+        };
+        let mut http_client = MockHttpClientTrait::new();
+        http_client
+            .expect_send()
+            .withf(|request| {
+                request.url == "http://pushgateway:9091/metrics/job/version-vine/app/my%20app"
+                    && request.body.as_ref().unwrap().as_str().unwrap().contains(
+                        "version_vine_release_timestamp_seconds{version=\"1.2.0\",tag=\"my app-1.2.0\",branch_class=\"main\"} 1707908400\n",
+                    )
+            })
+            .times(1)
+            .returning(|_| Ok(Value::Null));
+        http_client
+            .expect_send()
+            .withf(|request| {
+                let data_point = &request.body.as_ref().unwrap()["resourceMetrics"][0]
+                    ["scopeMetrics"][0]["metrics"][0]["gauge"]["dataPoints"][0];
+                request.url == "http://collector:4318/v1/metrics"
+                    && data_point["timeUnixNano"] == "1707908400000000000"
+                    && data_point["attributes"][0]["value"]["stringValue"] == "1.2.0"
+            })
+            .times(1)
+            .returning(|_| Ok(Value::Null));
+
+        let urls = push_release_event(&http_client, &config, &release_event()).unwrap();
+
+        assert_eq!(urls.len(), 2);
+    }
+
+    #[test]
+    fn test_push_release_event_unconfigured() {
+        let urls = push_release_event(
+            &MockHttpClientTrait::new(),
+            &MetricsConfig::default(),
+            &release_event(),
+        )
+        .unwrap();
+
+        assert!(urls.is_empty());
+    }
+
+    #[test]
+    fn test_label_escape() {
+        assert_eq!(label_escape("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+}
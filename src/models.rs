@@ -1,39 +1,710 @@
 use anyhow::Error;
-use regex_lite::Regex;
+use regex_lite::{escape, Regex};
+use semver::BuildMetadata;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+#[cfg(test)]
+use std::sync::Mutex;
 
+use crate::ci::Builder;
+use crate::components::Component;
+use crate::config::{Config, NonAscii, TagSelection};
+use crate::conventional_commits::Bump;
+use crate::dotnet::DotnetVersions;
+use crate::trace::TraceStep;
+use crate::transliterate::to_ascii;
+
+/// Class of a branch, determining how it's versioned
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum BranchClass {
+    Main,
+    Release,
+    /// Patch releases of the release a hotfix branch branched off from
+    Hotfix,
+    Develop,
+    /// Maintenance branches of an older release line, e.g. `support/1.x`, versioned as patch
+    /// releases of the highest tag of their line
+    Support,
+    /// Branches not matching any of the other classes
+    Feature,
+    /// Pull request builds, e.g. of `refs/pull/123/merge` on GitHub Actions
+    PullRequest,
+}
+
+/// What the pre-release of a version is derived from
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum PrereleaseSource {
+    /// The version of the release branch name, e.g. `release/1.2.0`
+    BranchName,
+    /// The base tag, with the commits since it as counter
+    Tag,
+    /// The `--build-number` counter
+    BuildNumber,
+}
+
+/// Version of the output contract, incremented when fields are renamed, removed or change their
+/// type. `version-vine schema` prints the JSON Schema of the output.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// Fields of the version output, shared by all output formats
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct VersionOutput {
+    /// `SCHEMA_VERSION` of the output
+    pub schema_version: u32,
+    pub git_branch: String,
+    pub git_rev: Option<String>,
+    pub rev_count: String,
+    pub app_version: String,
+    pub container_tag: String,
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    /// Pre-release of the version, e.g. `beta.3`, empty on main
+    pub prerelease: String,
+    /// Build metadata of the version, e.g. `56c1976`
+    pub build: String,
+    pub branch_class: BranchClass,
+    /// Release channel of the branch class, e.g. `beta` on develop
+    pub channel: String,
+    /// Container image reference of the channel, e.g. `myapp:beta`, `None` without app name
+    pub channel_image: Option<String>,
+    pub container_tags: Vec<String>,
+    pub ahead_of_main: Option<u32>,
+    pub behind_main: Option<u32>,
+    pub branch_created_at: Option<String>,
+    pub branch_age_days: Option<u64>,
+    pub days_since_last_commit: Option<u64>,
+    pub changed_since_tag: bool,
+    pub bump: Option<Bump>,
+    /// Tag the version is based on, `None` without tag
+    pub base_tag: Option<String>,
+    /// Version the bump is applied to, from the base tag or the release branch name
+    pub base_version: Option<String>,
+    /// What the pre-release is derived from, `None` without pre-release
+    pub prerelease_source: Option<PrereleaseSource>,
+    /// Number of the pull request of pull request builds
+    pub pr_number: Option<u64>,
+    /// Whether the signature of the release was verified, `None` when it isn't checked
+    pub signature_verified: Option<bool>,
+    pub git_sha_full: Option<String>,
+    /// Committer date of the commit, ISO-8601
+    pub commit_timestamp: Option<String>,
+    /// Committer date of the commit, seconds since the epoch
+    pub commit_epoch: Option<u64>,
+    /// `Name <email>` of the committer
+    pub committer: Option<String>,
+    /// Creation date of the base tag, ISO-8601
+    pub tag_date: Option<String>,
+    /// Annotation message of the base tag, `None` for lightweight tags
+    pub tag_message: Option<String>,
+    /// `Name <email>` of the tagger of the base tag
+    pub tagger: Option<String>,
+    /// Whether the base tag carries a signature, `None` for lightweight tags
+    pub tag_signed: Option<bool>,
+    /// Values of the `[output] env` variables, as fields of their own
+    #[serde(flatten)]
+    pub env_fields: BTreeMap<String, String>,
+    /// Decisions of the calculation, only with `--explain=field`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub trace: Vec<TraceStep>,
+    /// Machine identity of the build, only with `--builder`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub builder: Option<Builder>,
+    /// What the version was computed from, only with `--inputs`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inputs: Option<Inputs>,
+    /// Submodules with their pinned commits, only with `--components`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub components: Option<Vec<Component>>,
+    /// Key for CI caches and artifacts, only with `--cache-key`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_key: Option<String>,
+    /// .NET versions, only with `--extra-formats dotnet`
+    #[serde(flatten)]
+    pub dotnet: Option<DotnetVersions>,
+}
+
+/// Everything which influenced the version, the `inputs` block of `--inputs`, so the version can
+/// be recomputed and verified later
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Inputs {
+    /// Version of version-vine
+    pub tool_version: String,
+    /// Full SHA of the versioned commit
+    pub head_sha: Option<String>,
+    /// Tag the version is based on
+    pub base_tag: Option<String>,
+    pub branch: String,
+    /// Hash of the configuration, the config file merged over the user defaults
+    pub config_hash: String,
+    /// Arguments of the invocation
+    pub args: Vec<String>,
+    /// Environment variables read by the calculation, e.g. the CI branch and the `[output] env`
+    pub env: BTreeMap<String, String>,
+}
+
+/// Number of compilations of each pattern of the regexes, to check that multi-app runs share the
+/// regexes which are the same for every app
+#[cfg(test)]
+pub(crate) static COMPILATIONS: Mutex<BTreeMap<String, usize>> = Mutex::new(BTreeMap::new());
+
+fn compile(pattern: &str) -> Result<Regex, regex_lite::Error> {
+    #[cfg(test)]
+    {
+        *COMPILATIONS
+            .lock()
+            .unwrap()
+            .entry(pattern.to_string())
+            .or_default() += 1;
+    }
+    Regex::new(pattern)
+}
+
+/// The regexes of the configuration which are the same for every app: compiled once for a
+/// multi-app run and cloned into the `Regexes` of its apps, which share the compiled programs
+#[derive(Clone)]
+pub struct SharedRegexes {
+    main_branches: Regex,
+    develop_branches: Regex,
+    escape_branch: Regex,
+    priority: Vec<BranchClass>,
+    prerelease_branches: Vec<(Regex, String)>,
+}
+
+impl SharedRegexes {
+    pub fn new(config: &Config) -> Result<Self, Error> {
+        let main_branches = Regexes::branch_regex("main", &config.branches.main)?;
+        let develop_branches = Regexes::branch_regex("develop", &config.branches.develop)?;
+        let escape_branch = compile(r"[^a-zA-Z0-9-]").unwrap();
+        let mut priority = config.branches.priority.clone();
+        // Priorities predating the hotfix class match hotfix branches first, as they used to
+        // match the release pattern
+        if !priority.contains(&BranchClass::Hotfix) {
+            let release = priority
+                .iter()
+                .position(|class| *class == BranchClass::Release);
+            priority.insert(release.unwrap_or(0), BranchClass::Hotfix);
+        }
+        // Priorities predating the support class match support branches last
+        if !priority.contains(&BranchClass::Support) {
+            priority.push(BranchClass::Support);
+        }
+        let mut classes = priority.clone();
+        classes.sort_by_key(|class| *class as u8);
+        if classes
+            != [
+                BranchClass::Main,
+                BranchClass::Release,
+                BranchClass::Hotfix,
+                BranchClass::Develop,
+                BranchClass::Support,
+            ]
+        {
+            return Err(Error::msg(format!(
+                "Branch class priority {:?} must list main, release, hotfix, develop and support once",
+                config.branches.priority
+            )));
+        }
+        let prerelease_branches = config
+            .prerelease
+            .branches
+            .iter()
+            .map(|branch| {
+                let regex = compile(&branch.pattern).map_err(|err| {
+                    Error::msg(format!(
+                        "Invalid pre-release branch pattern '{}': {}",
+                        branch.pattern, err
+                    ))
+                })?;
+                Ok((regex, branch.label.clone()))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(Self {
+            main_branches,
+            develop_branches,
+            escape_branch,
+            priority,
+            prerelease_branches,
+        })
+    }
+}
+
+#[derive(Clone)]
 pub struct Regexes {
     pub tag: Regex,
+    /// Literal parts of the tag format around the version, e.g. `app-` and ``
+    pub tag_prefix: String,
+    pub tag_suffix: String,
+    /// Glob passed to `git describe --match`, when tags have to be filtered. Anchored to the
+    /// version, so `app-[0-9]*` doesn't match the `app-ui-1.0.0` tags of an `app-ui` app.
+    pub tag_match: Option<String>,
+    /// Globs passed to `git describe --exclude`: the tags of the other `[apps]` with the tag
+    /// prefix of this app, e.g. `app-2fa-*` for the `app` app
+    pub tag_exclude: Vec<String>,
+    /// Whether `tag` is the `[tag] pattern`, which `git describe` can't filter by
+    pub tag_pattern: bool,
+    /// Which reachable tag is the latest tag, see `get_latest_tag`
+    pub tag_selection: TagSelection,
+    /// Tags of any app, with the `app` group, for tag formats namespacing the apps with the
+    /// `{app_name}` placeholder. Only without an app name.
+    pub app_tag: Option<Regex>,
     pub main_branches: Regex,
     pub rc_branches: Regex,
+    pub hotfix_branches: Regex,
     pub develop_branches: Regex,
+    pub support_branches: Regex,
+    /// Names, patterns and classes of the `[[branches.custom]]` rules, matched before the classes
+    pub custom_branches: Vec<(String, Regex, BranchClass)>,
     pub escape_branch: Regex,
+    pub non_ascii: NonAscii,
+    /// Maximum length of the escaped branch name, see `escaped_branch`
+    pub max_branch_length: usize,
+    /// Order in which the branch classes are matched
+    pub priority: Vec<BranchClass>,
+    /// Pre-release labels of the branches matching the patterns, see `PrereleaseLabels::branches`
+    pub prerelease_branches: Vec<(Regex, String)>,
+    /// Branch and number of the pull request build, see `with_pull_request`
+    pub pull_request: Option<(String, u64)>,
 }
 
 impl Regexes {
-    pub fn new(app_name: &Option<String>) -> Result<Self, Error> {
-        let tag = if app_name.is_none() {
-            Regex::new(r"(?<version>.+)$")?
-        } else {
-            Regex::new(&format!(r"^{}-(?<version>.+)$", app_name.as_ref().unwrap()))?
+    pub fn new(app_name: &Option<String>, config: &Config) -> Result<Self, Error> {
+        Self::with_shared(&SharedRegexes::new(config)?, app_name, config)
+    }
+
+    /// The regexes of the app, compiling only its tag and release branch patterns
+    pub fn with_shared(
+        shared: &SharedRegexes,
+        app_name: &Option<String>,
+        config: &Config,
+    ) -> Result<Self, Error> {
+        let app_prefix = match app_name {
+            Some(app_name) => format!("{}-", app_name),
+            None => String::new(),
         };
-        let main_branches = Regex::new(r"^main|master$").unwrap();
-        let rc_branches = if app_name.is_none() {
-            Regex::new(r"^(hotfix\/|release\/)(?<version>.+)")?
-        } else {
-            Regex::new(&format!(
-                r"^(hotfix\/|release\/){}-(?<version>.+)",
-                app_name.as_ref().unwrap()
-            ))?
+        let format = config.tag.format_with_prefix();
+        let tag_format = format.replace("{app_prefix}", &app_prefix);
+        // Without an app name, e.g. `apps/{app_name}/{version}` tags are `apps/{version}`
+        let tag_format = match app_name {
+            Some(app_name) => tag_format.replace("{app_name}", app_name),
+            None => tag_format
+                .replace("{app_name}/", "")
+                .replace("{app_name}", ""),
         };
-        let develop_branches = Regex::new(r"^develop|dev$").unwrap();
-        let escape_branch = Regex::new(r"[^a-zA-Z0-9-]").unwrap();
+        let (tag_prefix, tag_suffix) =
+            tag_format
+                .split_once("{version}")
+                .ok_or(Error::msg(format!(
+                    "Tag format '{}' must contain the '{{version}}' placeholder",
+                    config.tag.format
+                )))?;
+        let tag = match &config.tag.pattern {
+            Some(pattern) => {
+                let pattern = pattern.replace("{app_prefix}", &escape(&app_prefix));
+                let regex = compile(&pattern).map_err(|err| {
+                    Error::msg(format!("Invalid tag pattern '{}': {}", pattern, err))
+                })?;
+                if !regex.capture_names().any(|name| name == Some("version")) {
+                    return Err(Error::msg(format!(
+                        "Tag pattern '{}' must contain a 'version' capture group",
+                        pattern
+                    )));
+                }
+                regex
+            }
+            None => compile(&format!(
+                r"^{}(?<version>.+){}$",
+                escape(tag_prefix),
+                escape(tag_suffix)
+            ))?,
+        };
+        // The pattern may match tags the format doesn't, e.g. legacy tags without a `v` prefix
+        let tag_match =
+            match config.tag.pattern.is_some() || tag_prefix.is_empty() && tag_suffix.is_empty() {
+                true => None,
+                false => Some(format!("{}[0-9]*{}", tag_prefix, tag_suffix)),
+            };
+        let tag_exclude = config
+            .apps
+            .keys()
+            .filter(|app| Some(*app) != app_name.as_ref())
+            .filter_map(|app| {
+                let app_format = format
+                    .replace("{app_prefix}", &format!("{}-", app))
+                    .replace("{app_name}", app);
+                let (app_tag_prefix, _) = app_format.split_once("{version}")?;
+                match app_tag_prefix.starts_with(tag_prefix) && app_tag_prefix != tag_prefix {
+                    true => Some(format!("{}*", app_tag_prefix)),
+                    false => None,
+                }
+            })
+            .collect();
+        let app_tag = match (app_name, format.split_once("{version}")) {
+            (None, Some((prefix, suffix))) if prefix.contains("{app_name}") => {
+                let prefix = prefix
+                    .replace("{app_prefix}", "")
+                    .split("{app_name}")
+                    .map(escape)
+                    .collect::<Vec<_>>()
+                    .join("(?<app>[^/]+)");
+                Some(compile(&format!(
+                    r"^{}(?<version>.+){}$",
+                    prefix,
+                    escape(suffix)
+                ))?)
+            }
+            _ => None,
+        };
+        let rc_branches = Self::branch_regex(
+            "release",
+            &config
+                .branches
+                .release
+                .replace("{app_prefix}", &escape(&app_prefix)),
+        )?;
+        let hotfix_branches = Self::branch_regex(
+            "hotfix",
+            &config
+                .branches
+                .hotfix
+                .replace("{app_prefix}", &escape(&app_prefix)),
+        )?;
+        let support_branches = Self::branch_regex(
+            "support",
+            &config
+                .branches
+                .support
+                .replace("{app_prefix}", &escape(&app_prefix)),
+        )?;
+        let custom_branches = config
+            .branches
+            .custom
+            .iter()
+            .map(|custom| {
+                let pattern = custom.pattern.replace("{app_prefix}", &escape(&app_prefix));
+                let regex = Self::branch_regex(&custom.name, &pattern)?;
+                let versioned = matches!(custom.class, BranchClass::Release | BranchClass::Support);
+                if custom.class == BranchClass::PullRequest {
+                    return Err(Error::msg(format!(
+                        "Custom branch class '{}' can't be versioned as pull-request",
+                        custom.name
+                    )));
+                }
+                if versioned && !regex.capture_names().any(|name| name == Some("version")) {
+                    return Err(Error::msg(format!(
+                        "Pattern '{}' of custom branch class '{}' must contain a 'version' capture group",
+                        custom.pattern, custom.name
+                    )));
+                }
+                Ok((custom.name.clone(), regex, custom.class))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
 
         Ok(Self {
             tag,
-            main_branches,
+            tag_prefix: tag_prefix.to_string(),
+            tag_suffix: tag_suffix.to_string(),
+            tag_match,
+            tag_exclude,
+            tag_pattern: config.tag.pattern.is_some(),
+            tag_selection: config.tag.selection,
+            app_tag,
+            main_branches: shared.main_branches.clone(),
             rc_branches,
-            develop_branches,
-            escape_branch,
+            hotfix_branches,
+            develop_branches: shared.develop_branches.clone(),
+            support_branches,
+            custom_branches,
+            escape_branch: shared.escape_branch.clone(),
+            non_ascii: config.build_metadata.non_ascii,
+            max_branch_length: config.build_metadata.max_branch_length,
+            priority: shared.priority.clone(),
+            prerelease_branches: shared.prerelease_branches.clone(),
+            pull_request: None,
+        })
+    }
+
+    /// The value with the characters which aren't allowed in build metadata replaced by `-`, after
+    /// making it ASCII following the `non_ascii` strategy
+    pub fn escape(&self, value: &str) -> String {
+        self.escape_branch
+            .replace_all(&to_ascii(value, self.non_ascii), "-")
+            .to_string()
+    }
+
+    /// The escaped branch name of the build metadata, truncated to the maximum branch length
+    pub fn escaped_branch(&self, branch: &str) -> String {
+        let mut escaped_branch = self.escape(branch);
+        // Escaped names are ASCII, so any length is a char boundary
+        escaped_branch.truncate(self.max_branch_length);
+        escaped_branch
+    }
+
+    /// Name of the tag for a version, following the tag format
+    pub fn tag_name(&self, version: &str) -> String {
+        format!("{}{}{}", self.tag_prefix, version, self.tag_suffix)
+    }
+
+    /// `git describe` options selecting the tags of the app
+    pub fn describe_filters(&self) -> Vec<&str> {
+        let mut filters = vec![];
+        if let Some(tag_match) = &self.tag_match {
+            filters.extend(["--match", tag_match.as_str()]);
+        }
+        for tag_exclude in &self.tag_exclude {
+            filters.extend(["--exclude", tag_exclude.as_str()]);
+        }
+        filters
+    }
+
+    /// Refspec fetching only the tags of the app, for tag formats namespacing the apps, e.g.
+    /// `refs/tags/apps/myapp/*`
+    pub fn tag_refspec(&self, config: &Config) -> Option<String> {
+        if !config.tag.format.contains("{app_name}") || self.tag_prefix.is_empty() {
+            return None;
+        }
+        let tags = format!("refs/tags/{}*", self.tag_prefix);
+        Some(format!("{}:{}", tags, tags))
+    }
+
+    /// Pattern of a branch class, `None` for feature branches
+    pub fn class_regex(&self, class: BranchClass) -> Option<&Regex> {
+        match class {
+            BranchClass::Main => Some(&self.main_branches),
+            BranchClass::Release => Some(&self.rc_branches),
+            BranchClass::Hotfix => Some(&self.hotfix_branches),
+            BranchClass::Develop => Some(&self.develop_branches),
+            BranchClass::Support => Some(&self.support_branches),
+            BranchClass::Feature | BranchClass::PullRequest => None,
+        }
+    }
+
+    /// First `[[branches.custom]]` rule matching the branch, with its name and class
+    pub fn custom_branch(&self, branch: &str) -> Option<&(String, Regex, BranchClass)> {
+        self.custom_branches
+            .iter()
+            .find(|(_, regex, _)| regex.is_match(branch))
+    }
+
+    /// Pattern the branch is classified by: of its custom rule, or else of the highest priority
+    /// class matching it. `None` for feature and pull request branches.
+    pub fn classifying_regex(&self, branch: &str) -> Option<&Regex> {
+        match self.custom_branch(branch) {
+            Some((_, regex, _)) => Some(regex),
+            None => self.class_regex(*self.matching_classes(branch).first()?),
+        }
+    }
+
+    /// `version` capture of the pattern the branch is classified by, e.g. `1.3.0` of
+    /// `release/1.3.0` or `1` of `support/1.x`
+    pub fn branch_version<'a>(&self, branch: &'a str) -> Option<&'a str> {
+        let captures = self.classifying_regex(branch)?.captures(branch)?;
+        Some(captures.name("version")?.as_str())
+    }
+
+    /// Major, or major and minor, version of the release line of a support branch, e.g. `1` of
+    /// `support/1.x` or `1.4` of `release/1.4.x`
+    pub fn support_line<'a>(&self, branch: &'a str) -> Option<&'a str> {
+        let version = self.branch_version(branch)?;
+        Some(version.strip_suffix(".x").unwrap_or(version))
+    }
+
+    /// Classes of which the pattern matches the branch, in priority order
+    pub fn matching_classes(&self, branch: &str) -> Vec<BranchClass> {
+        self.priority
+            .iter()
+            .filter(|class| {
+                self.class_regex(**class)
+                    .is_some_and(|regex| regex.is_match(branch))
+            })
+            .copied()
+            .collect()
+    }
+
+    /// Class of the first matching custom rule, or else the highest priority class matching the
+    /// branch, or else feature. Pull request builds are classified before the patterns. Release
+    /// branches of a line instead of a version, e.g. `release/1.x`, are support branches.
+    pub fn branch_class(&self, branch: &str) -> BranchClass {
+        if self.pull_request_number(branch).is_some() {
+            return BranchClass::PullRequest;
+        }
+        let class = match self.custom_branch(branch) {
+            Some((_, _, class)) => *class,
+            None => self
+                .matching_classes(branch)
+                .first()
+                .copied()
+                .unwrap_or(BranchClass::Feature),
+        };
+        let is_line = |version: &str| {
+            version.strip_suffix(".x").is_some_and(|line| {
+                let parts: Vec<&str> = line.split('.').collect();
+                parts.len() <= 2 && parts.iter().all(|part| part.parse::<u64>().is_ok())
+            })
+        };
+        match class {
+            BranchClass::Release if self.branch_version(branch).is_some_and(is_line) => {
+                BranchClass::Support
+            }
+            class => class,
+        }
+    }
+
+    /// Label of the first pre-release branch pattern matching the branch
+    pub fn prerelease_label(&self, branch: &str) -> Option<&str> {
+        self.prerelease_branches
+            .iter()
+            .find(|(regex, _)| regex.is_match(branch))
+            .map(|(_, label)| label.as_str())
+    }
+
+    /// Number of the pull request build of the branch
+    pub fn pull_request_number(&self, branch: &str) -> Option<u64> {
+        match &self.pull_request {
+            Some((pr_branch, number)) if pr_branch == branch => Some(*number),
+            _ => None,
+        }
+    }
+
+    /// Also classifies the build of `branch` as build of pull request `number`
+    pub fn with_pull_request(&self, branch: &str, number: u64) -> Self {
+        let mut regexes = self.clone();
+        regexes.pull_request = Some((branch.to_string(), number));
+        regexes
+    }
+
+    /// Also excludes the `tags` globs from `git describe`, e.g. the tags created after `--as-of`
+    pub fn with_excluded_tags(&self, tags: Vec<String>) -> Self {
+        let mut regexes = self.clone();
+        regexes.tag_exclude.extend(tags);
+        regexes
+    }
+
+    /// Also classifies the default branch as main branch, e.g. `trunk`, unless it already matches
+    /// one of the branch classes
+    pub fn with_default_branch(&self, default_branch: &str) -> Result<Self, Error> {
+        let mut regexes = self.clone();
+        if ![
+            &self.main_branches,
+            &self.rc_branches,
+            &self.develop_branches,
+        ]
+        .iter()
+        .any(|class| class.is_match(default_branch))
+        {
+            regexes.main_branches = Regex::new(&format!(
+                "(?:{})|^{}$",
+                self.main_branches.as_str(),
+                escape(default_branch)
+            ))?;
+        }
+        Ok(regexes)
+    }
+
+    pub(crate) fn branch_regex(class: &str, pattern: &str) -> Result<Regex, Error> {
+        compile(pattern).map_err(|err| {
+            Error::msg(format!(
+                "Invalid {} branch pattern '{}': {}",
+                class, pattern, err
+            ))
         })
     }
 }
+
+/// Composes build metadata from identifiers, e.g. `feature-login.1234567`. Identifiers are
+/// validated against the SemVer charset, `[0-9A-Za-z-]`, so invalid values fail with the
+/// identifier instead of the whole build metadata.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BuildMetadataBuilder {
+    identifiers: Vec<String>,
+    max_length: Option<usize>,
+}
+
+impl BuildMetadataBuilder {
+    pub fn new(max_length: Option<usize>) -> Self {
+        Self {
+            identifiers: vec![],
+            max_length,
+        }
+    }
+
+    /// Continues the existing build metadata
+    pub fn extend(build: &BuildMetadata, max_length: Option<usize>) -> Self {
+        Self {
+            identifiers: build
+                .as_str()
+                .split('.')
+                .filter(|identifier| !identifier.is_empty())
+                .map(str::to_string)
+                .collect(),
+            max_length,
+        }
+    }
+
+    /// Appends the identifier. Empty identifiers, e.g. of branch names without any allowed
+    /// characters, are skipped.
+    pub fn push(&mut self, identifier: &str) -> Result<&mut Self, Error> {
+        if let Some(c) = identifier
+            .chars()
+            .find(|c| !c.is_ascii_alphanumeric() && *c != '-')
+        {
+            return Err(Error::msg(format!(
+                "Invalid build metadata identifier '{}': '{}' isn't allowed, only [0-9A-Za-z-]",
+                identifier, c
+            )));
+        }
+        if !identifier.is_empty() {
+            self.identifiers.push(identifier.to_string());
+        }
+        Ok(self)
+    }
+
+    /// The identifiers joined by `.`, cut off at the maximum length
+    pub fn build(&self) -> Result<BuildMetadata, Error> {
+        let mut build = self.identifiers.join(".");
+        if let Some(max_length) = self.max_length {
+            build.truncate(max_length);
+            build.truncate(build.trim_end_matches('.').len());
+        }
+        Ok(BuildMetadata::new(&build)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_metadata_builder() {
+        let mut builder = BuildMetadataBuilder::new(None);
+        builder.push("feature-login").unwrap().push("").unwrap();
+        builder.push("1234567").unwrap();
+        assert_eq!(builder.build().unwrap().as_str(), "feature-login.1234567");
+
+        let err = builder.push("x86_64").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid build metadata identifier 'x86_64': '_' isn't allowed, only [0-9A-Za-z-]"
+        );
+
+        // Cut off without a trailing separator
+        let builder = BuildMetadataBuilder::extend(&builder.build().unwrap(), Some(14));
+        assert_eq!(builder.build().unwrap().as_str(), "feature-login");
+        let builder = BuildMetadataBuilder::extend(&BuildMetadata::EMPTY, Some(14));
+        assert_eq!(builder.build().unwrap(), BuildMetadata::EMPTY);
+    }
+
+    #[test]
+    fn test_support_branches() {
+        let regexes = Regexes::new(&None, &Config::default()).unwrap();
+
+        assert_eq!(regexes.branch_class("support/1.x"), BranchClass::Support);
+        assert_eq!(regexes.support_line("support/1.x"), Some("1"));
+        assert_eq!(regexes.branch_class("release/1.4.x"), BranchClass::Support);
+        assert_eq!(regexes.support_line("release/1.4.x"), Some("1.4"));
+        assert_eq!(regexes.branch_class("release/1.4.0"), BranchClass::Release);
+        assert_eq!(regexes.branch_class("support/legacy"), BranchClass::Feature);
+    }
+}
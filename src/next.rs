@@ -0,0 +1,183 @@
+use anyhow::{Error, Result};
+use clap::ValueEnum;
+use semver::{BuildMetadata, Prerelease, Version};
+use serde_json::{json, Value};
+
+use crate::conventional_commits::Bump;
+use crate::git_command::GitCommandTrait;
+use crate::models::Regexes;
+use crate::version_calculator::{
+    get_conventional_bump, get_latest_path_tag, get_merge_strategy, is_unborn, parse_tag_version,
+};
+use crate::{EmptyBranch, VersionOptions};
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum NextBump {
+    Major,
+    Minor,
+    Patch,
+    /// Following the Conventional Commits since the latest tag
+    Auto,
+}
+
+/// Next release version and tag after the latest tag, regardless of the branch. A pre-release
+/// tag is released by the bump it's already part of, e.g. `2.0.0-rc.3` becomes `2.0.0` with a
+/// patch, minor or major bump.
+///
+/// Repositories without any commits yet release the `--empty-branch-version`, e.g. `0.0.1` after
+/// `git init`.
+pub fn next_release(
+    git_command: &impl GitCommandTrait,
+    options: &VersionOptions,
+    bump: NextBump,
+) -> Result<Value> {
+    let regexes = Regexes::new(&options.app_name, &options.config)?;
+    let latest_tag = get_latest_path_tag(git_command, &regexes, options, None)?;
+    let unborn = match latest_tag {
+        Some(_) => false,
+        None => is_unborn(git_command)?,
+    };
+    if unborn && options.empty_branch() == EmptyBranch::Error {
+        return Err(Error::msg(
+            "The repository doesn't have any commits to release yet",
+        ));
+    }
+    let latest = match &latest_tag {
+        Some(tag) => parse_tag_version(&regexes, tag)?,
+        None if unborn => options.empty_branch_version(),
+        None => Version::new(0, 0, 0),
+    };
+    let bump = match bump {
+        // There are no commits to follow yet
+        _ if unborn => Bump::Patch,
+        NextBump::Major => Bump::Major,
+        NextBump::Minor => Bump::Minor,
+        NextBump::Patch => Bump::Patch,
+        NextBump::Auto => get_conventional_bump(
+            git_command,
+            &regexes,
+            options,
+            get_merge_strategy(git_command, options),
+            None,
+        )?,
+    };
+    let version = next_version(&latest, bump);
+    Ok(json!({
+        "latest_tag": latest_tag,
+        "bump": bump,
+        "version": version.to_string(),
+        "tag": regexes.tag_name(&version.to_string()),
+    }))
+}
+
+pub(crate) fn next_version(latest: &Version, bump: Bump) -> Version {
+    let mut version = latest.clone();
+    version.build = BuildMetadata::EMPTY;
+    let released_by_bump = match bump {
+        Bump::Patch => true,
+        Bump::Minor => version.patch == 0,
+        Bump::Major => version.minor == 0 && version.patch == 0,
+    };
+    if version.pre.is_empty() || !released_by_bump {
+        bump.apply(&mut version);
+    }
+    version.pre = Prerelease::EMPTY;
+    version
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::git_command::MockGitCommandTrait;
+
+    use anyhow::Error;
+
+    use super::*;
+
+    fn next(latest: &str, bump: Bump) -> String {
+        next_version(&Version::parse(latest).unwrap(), bump).to_string()
+    }
+
+    #[test]
+    fn test_next_version() {
+        assert_eq!(next("1.4.2", Bump::Patch), "1.4.3");
+        assert_eq!(next("1.4.2+build", Bump::Minor), "1.5.0");
+        assert_eq!(next("1.4.2", Bump::Major), "2.0.0");
+        assert_eq!(next("2.0.0-rc.3", Bump::Patch), "2.0.0");
+        assert_eq!(next("2.0.0-rc.3", Bump::Major), "2.0.0");
+        assert_eq!(next("1.2.1-rc.1", Bump::Minor), "1.3.0");
+    }
+
+    #[test]
+    fn test_next_release() {
+        let mut git_command = MockGitCommandTrait::new();
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "describe --abbrev=0 --match myapp-[0-9]* --tags")
+            .returning(|_| Ok("myapp-1.4.0".to_string()));
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "log --format=%B%x00 myapp-1.4.0..HEAD")
+            .returning(|_| Ok("fix: a\n\0feat: b\n\0".to_string()));
+        let options = VersionOptions {
+            app_name: Some("myapp".to_string()),
+            ..Default::default()
+        };
+
+        let release = next_release(&git_command, &options, NextBump::Auto).unwrap();
+
+        assert_eq!(
+            release,
+            json!({
+                "latest_tag": "myapp-1.4.0",
+                "bump": "minor",
+                "version": "1.5.0",
+                "tag": "myapp-1.5.0",
+            })
+        );
+    }
+
+    #[test]
+    fn test_next_release_no_tag() {
+        let mut git_command = MockGitCommandTrait::new();
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "describe")
+            .returning(|_| Err(Error::msg("No names found")));
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "rev-parse -q --verify HEAD")
+            .returning(|_| Ok("1234567890".to_string()));
+
+        let release =
+            next_release(&git_command, &VersionOptions::default(), NextBump::Patch).unwrap();
+
+        assert_eq!(release["latest_tag"], Value::Null);
+        assert_eq!(release["version"], "0.0.1");
+    }
+
+    #[test]
+    fn test_next_release_unborn() {
+        let mut git_command = MockGitCommandTrait::new();
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "describe")
+            .returning(|_| Err(Error::msg("No names found")));
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "rev-parse -q --verify HEAD")
+            .returning(|_| Err(Error::msg("")));
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "symbolic-ref -q HEAD")
+            .returning(|_| Ok(String::from("refs/heads/main")));
+        let options = VersionOptions {
+            empty_branch_version: Some(Version::parse("0.1.0-init").unwrap()),
+            ..Default::default()
+        };
+
+        let release = next_release(&git_command, &options, NextBump::Auto).unwrap();
+
+        assert_eq!(release["bump"], "patch");
+        assert_eq!(release["version"], "0.1.0");
+    }
+}
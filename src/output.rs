@@ -0,0 +1,836 @@
+use anyhow::{Error, Result};
+use clap::ValueEnum;
+use regex_lite::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{to_string, to_string_pretty, to_value, Map, Value};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::process;
+
+use crate::ci::{detect_ci, CiSystem};
+use crate::models::Regexes;
+use crate::schema::output_shape;
+use crate::{VersionInfo, VersionOptions};
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputFormat {
+    /// Pretty printed JSON object
+    Json,
+    /// YAML mapping
+    Yaml,
+    /// Dotenv style `APP_VERSION=1.0.0` lines
+    Env,
+    /// Only the version
+    Plain,
+    /// `key=value` lines for appending to `$GITHUB_OUTPUT`
+    Github,
+    /// TeamCity `setParameter` and `buildNumber` service messages
+    Teamcity,
+}
+
+/// Additional fields of the version output, for the version formats of other ecosystems
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ExtraFormat {
+    /// `assembly_version`, `file_version` and `informational_version` for MSBuild properties, following the `[dotnet]` configuration
+    Dotnet,
+}
+
+impl OutputFormat {
+    /// Format of an output file by its extension, e.g. `env` for `version.env`
+    pub fn from_path(path: &Path) -> Result<OutputFormat> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Ok(OutputFormat::Json),
+            Some("yaml" | "yml") => Ok(OutputFormat::Yaml),
+            Some("env") => Ok(OutputFormat::Env),
+            Some("txt") => Ok(OutputFormat::Plain),
+            _ => Err(Error::msg(format!(
+                "Unknown output format of '{}'. Supported extensions: .json, .yaml, .yml, .env and .txt",
+                path.display()
+            ))),
+        }
+    }
+}
+
+/// Serializes to pretty printed JSON, or canonical JSON: compact, like RFC 8785 (JCS) for the
+/// strings and numbers version-vine outputs, so outputs can be hashed, diffed and signed. Either
+/// way the object keys are sorted, so outputs are the same across runs and platforms.
+pub fn to_json_string(value: &impl Serialize, canonical: bool) -> Result<String> {
+    // Objects of `Value` are sorted maps, while structs serialize in field order
+    let value = to_value(value)?;
+    match canonical {
+        true => Ok(to_string(&value)?),
+        false => Ok(to_string_pretty(&value)?),
+    }
+}
+
+/// Renders the version info in the given format and in the shape of the `schema_version`, so
+/// pipelines can consume it without `jq`. All formats list the fields sorted by name.
+pub fn render(
+    version_info: &VersionInfo,
+    format: OutputFormat,
+    selected_fields: &[String],
+    schema_version: u32,
+    canonical: bool,
+) -> Result<String> {
+    let json = output_shape(version_info.to_json(), schema_version)?;
+    let json = select_fields(json, selected_fields)?;
+    let fields = fields(&json);
+    let output = match format {
+        OutputFormat::Json => to_json_string(&json, canonical)?,
+        OutputFormat::Yaml => fields
+            .iter()
+            // JSON scalars are valid YAML scalars, strings being double quoted
+            .map(|(key, value)| format!("{}: {}", key, value))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        OutputFormat::Env => fields
+            .iter()
+            .map(|(key, value)| format!("{}={}", key.to_uppercase(), shell_quote(&scalar(value))))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        OutputFormat::Plain => version_info.version.to_string(),
+        OutputFormat::Github => fields
+            .iter()
+            .map(|(key, value)| github_variable(key, &scalar(value)))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        OutputFormat::Teamcity => {
+            let mut messages: Vec<String> = fields
+                .iter()
+                .map(|(key, value)| {
+                    format!(
+                        "##teamcity[setParameter name='{}' value='{}']",
+                        teamcity_escape(key),
+                        teamcity_escape(&scalar(value))
+                    )
+                })
+                .collect();
+            messages.push(format!(
+                "##teamcity[buildNumber '{}']",
+                teamcity_escape(&version_info.version.to_string())
+            ));
+            messages.join("\n")
+        }
+    };
+    Ok(output)
+}
+
+/// Only the `fields` of the version output, or all of them without fields. Unknown fields fail,
+/// so typos don't drop fields from the output.
+pub fn select_fields(json: Value, fields: &[String]) -> Result<Value> {
+    let Value::Object(mut object) = json else {
+        return Ok(json);
+    };
+    if fields.is_empty() {
+        return Ok(Value::Object(object));
+    }
+    let mut selected = Map::new();
+    for field in fields {
+        let value = object.remove(field).ok_or(Error::msg(format!(
+            "Unknown output field '{}'. Available fields: {}",
+            field,
+            object.keys().cloned().collect::<Vec<_>>().join(", ")
+        )))?;
+        selected.insert(field.clone(), value);
+    }
+    Ok(Value::Object(selected))
+}
+
+/// Variables of `--template`: the output fields, `pre` for the pre-release, the `counter` of the
+/// pre-release, the escaped branch, the app name and the tag of the version
+pub fn template_variables(
+    version_info: &VersionInfo,
+    options: &VersionOptions,
+) -> Result<Vec<(String, String)>> {
+    let regexes = Regexes::new(&options.app_name, &options.config)?;
+    let json = version_info.to_json();
+    let mut variables: Vec<(String, String)> = fields(&json)
+        .into_iter()
+        .map(|(key, value)| (key.clone(), scalar(value)))
+        .collect();
+    variables.extend([
+        ("pre".to_string(), version_info.version.pre.to_string()),
+        (
+            "counter".to_string(),
+            prerelease_counter(&version_info.version),
+        ),
+        (
+            "escaped_branch".to_string(),
+            regexes.escaped_branch(&version_info.git_branch),
+        ),
+        (
+            "app_name".to_string(),
+            options.app_name.clone().unwrap_or_default(),
+        ),
+        (
+            "tag".to_string(),
+            regexes.tag_name(&version_info.tag_version().to_string()),
+        ),
+    ]);
+    variables.sort();
+    Ok(variables)
+}
+
+/// The counter of the pre-release, its first numeric identifier, e.g. `4` of `1.3.0-beta.4`. `0`
+/// for releases.
+fn prerelease_counter(version: &semver::Version) -> String {
+    version
+        .pre
+        .split('.')
+        .find(|identifier| {
+            !identifier.is_empty() && identifier.bytes().all(|byte| byte.is_ascii_digit())
+        })
+        .unwrap_or("0")
+        .to_string()
+}
+
+/// Renders a template with `{{ variable }}` placeholders, e.g. `{{major}}.{{minor}}.{{patch}}`
+/// or a `version.h` file. Unknown variables fail, so typos don't end up in the output.
+pub fn render_template(
+    version_info: &VersionInfo,
+    options: &VersionOptions,
+    template: &str,
+) -> Result<String> {
+    let variables = template_variables(version_info, options)?;
+    let placeholder = Regex::new(r"\{\{\s*(?<name>[A-Za-z0-9_]+)\s*\}\}").unwrap();
+    let mut output = String::new();
+    let mut last = 0;
+    for caps in placeholder.captures_iter(template) {
+        let matched = caps.get(0).unwrap();
+        let name = &caps["name"];
+        let (_, value) = variables
+            .iter()
+            .find(|(variable, _)| variable == name)
+            .ok_or(Error::msg(format!(
+                "Unknown template variable '{}'. Available variables: {}",
+                name,
+                variables
+                    .iter()
+                    .map(|(variable, _)| variable.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )))?;
+        output.push_str(&template[last..matched.start()]);
+        output.push_str(value);
+        last = matched.end();
+    }
+    output.push_str(&template[last..]);
+    Ok(output)
+}
+
+/// Renders the cache key template, e.g. `myapp-1.4.0-beta.12-linux-x64`. Characters which CI
+/// caches and artifact names don't take everywhere, like the `+` of build metadata, are replaced by
+/// `-`.
+pub fn cache_key(
+    version_info: &VersionInfo,
+    options: &VersionOptions,
+    template: &str,
+) -> Result<String> {
+    let key = render_template(version_info, options, template)?;
+    Ok(key
+        .chars()
+        .map(|c| match c {
+            'A'..='Z' | 'a'..='z' | '0'..='9' | '.' | '_' | '-' => c,
+            _ => '-',
+        })
+        .collect())
+}
+
+/// How the version fields are exported as environment variables of the following CI steps
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum ExportEnv {
+    /// Following the detected CI system, `shell` outside of GitHub Actions, Azure Pipelines and TeamCity
+    Auto,
+    /// `export VERSION_VINE_APP_VERSION=...` lines, for `eval`
+    Shell,
+    /// Appended to the `$GITHUB_ENV` file
+    Github,
+    /// Azure Pipelines `task.setvariable` logging commands
+    Azure,
+    /// TeamCity `setParameter` service messages of `env.` parameters
+    Teamcity,
+}
+
+/// Environment variables of the version fields, like `VERSION_VINE_APP_VERSION`, along with the
+/// app name and the source of the bump: the bump strategy on develop and feature branches
+pub fn export_variables(
+    version_info: &VersionInfo,
+    options: &VersionOptions,
+) -> Vec<(String, String)> {
+    let json = version_info.to_json();
+    let bump_source = match version_info.bump {
+        Some(_) => options
+            .bump_strategy()
+            .to_possible_value()
+            .map(|value| value.get_name().to_string()),
+        None => None,
+    };
+    let mut variables: Vec<(String, String)> = fields(&json)
+        .into_iter()
+        .map(|(key, value)| (key.clone(), scalar(value)))
+        .collect();
+    variables.push((
+        "app_name".to_string(),
+        options.app_name.clone().unwrap_or_default(),
+    ));
+    variables.push(("bump_source".to_string(), bump_source.unwrap_or_default()));
+    variables.sort();
+    variables
+        .into_iter()
+        .map(|(key, value)| (format!("VERSION_VINE_{}", key.to_uppercase()), value))
+        .collect()
+}
+
+/// Exports the version fields as environment variables, returning the output to print. `auto`
+/// follows the CI system detected from the `env` variables. On GitHub Actions, the variables are
+/// appended to the `$GITHUB_ENV` file instead.
+pub fn export_env(
+    version_info: &VersionInfo,
+    options: &VersionOptions,
+    export: ExportEnv,
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<String> {
+    let variables = export_variables(version_info, options);
+    let export = match export {
+        ExportEnv::Auto => match detect_ci(&env) {
+            Some(CiSystem::GithubActions) => ExportEnv::Github,
+            Some(CiSystem::AzurePipelines) => ExportEnv::Azure,
+            Some(CiSystem::TeamCity) => ExportEnv::Teamcity,
+            Some(CiSystem::GitlabCi | CiSystem::Jenkins) | None => ExportEnv::Shell,
+        },
+        export => export,
+    };
+    let lines: Vec<String> = match export {
+        ExportEnv::Auto | ExportEnv::Shell => variables
+            .iter()
+            .map(|(name, value)| format!("export {}={}", name, shell_quote(value)))
+            .collect(),
+        ExportEnv::Github => {
+            let path = env("GITHUB_ENV")
+                .filter(|path| !path.is_empty())
+                .ok_or(Error::msg(
+                    "GITHUB_ENV is not set. Use --export-env shell outside of GitHub Actions.",
+                ))?;
+            let mut content = String::new();
+            for (name, value) in &variables {
+                content.push_str(&github_variable(name, value));
+                content.push('\n');
+            }
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .and_then(|mut file| file.write_all(content.as_bytes()))
+                .map_err(|err| Error::msg(format!("Cannot write '{}': {}", path, err)))?;
+            vec![]
+        }
+        ExportEnv::Azure => variables
+            .iter()
+            .map(|(name, value)| {
+                format!(
+                    "##vso[task.setvariable variable={}]{}",
+                    name,
+                    azure_escape(value)
+                )
+            })
+            .collect(),
+        ExportEnv::Teamcity => variables
+            .iter()
+            .map(|(name, value)| {
+                format!(
+                    "##teamcity[setParameter name='env.{}' value='{}']",
+                    name,
+                    teamcity_escape(value)
+                )
+            })
+            .collect(),
+    };
+    Ok(lines.join("\n"))
+}
+
+/// Formats of outputs with a row per item, like the release statistics per app
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum TableFormat {
+    /// Pretty printed JSON array
+    Json,
+    /// Comma separated values with a header row, for spreadsheets and dashboards
+    Csv,
+}
+
+/// Renders the rows in the given format. CSV columns are the fields of the rows, nested values
+/// being JSON encoded.
+pub fn render_table(
+    rows: &[impl Serialize],
+    format: TableFormat,
+    canonical: bool,
+) -> Result<String> {
+    let output = match format {
+        TableFormat::Json => to_json_string(&rows, canonical)?,
+        TableFormat::Csv => {
+            let rows = rows.iter().map(to_value).collect::<Result<Vec<_>, _>>()?;
+            let mut columns: Vec<&String> = vec![];
+            for (key, _) in rows.iter().flat_map(fields) {
+                if !columns.contains(&key) {
+                    columns.push(key);
+                }
+            }
+            let mut lines = vec![columns
+                .iter()
+                .map(|column| csv_escape(column))
+                .collect::<Vec<_>>()
+                .join(",")];
+            for row in &rows {
+                lines.push(
+                    columns
+                        .iter()
+                        .map(|column| csv_escape(&scalar(&row[column.as_str()])))
+                        .collect::<Vec<_>>()
+                        .join(","),
+                );
+            }
+            lines.join("\n")
+        }
+    };
+    Ok(output)
+}
+
+/// Writes the output to the file through a temporary file in the same directory, renamed over it,
+/// so readers like `docker build` never see a partial file. Missing parent directories are created.
+pub fn write_atomic(path: &Path, output: &str) -> Result<()> {
+    let error =
+        |err: std::io::Error| Error::msg(format!("Cannot write '{}': {}", path.display(), err));
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty());
+    if let Some(dir) = dir {
+        fs::create_dir_all(dir).map_err(error)?;
+    }
+    let file_name = path.file_name().ok_or(Error::msg(format!(
+        "'{}' isn't a file path",
+        path.display()
+    )))?;
+    let temp_path = path.with_file_name(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        process::id()
+    ));
+    let mut content = output.to_string();
+    if !content.ends_with('\n') {
+        content.push('\n');
+    }
+    fs::write(&temp_path, content).map_err(error)?;
+    fs::rename(&temp_path, path).map_err(|err| {
+        let _ = fs::remove_file(&temp_path);
+        error(err)
+    })
+}
+
+/// https://www.rfc-editor.org/rfc/rfc4180#section-2
+fn csv_escape(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn fields(json: &Value) -> Vec<(&String, &Value)> {
+    match json.as_object() {
+        Some(object) => object.iter().collect(),
+        None => vec![],
+    }
+}
+
+/// Value without JSON quoting, empty for `null`
+fn scalar(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        Value::Null => String::new(),
+        // E.g. the `container_tags`, like the `tags` input of docker/build-push-action
+        Value::Array(values) => values.iter().map(scalar).collect::<Vec<_>>().join(","),
+        value => value.to_string(),
+    }
+}
+
+/// Quotes values which the shell or dotenv parsers would otherwise interpret
+fn shell_quote(value: &str) -> String {
+    if value
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "-_.+/:".contains(c))
+    {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', r"'\''"))
+    }
+}
+
+/// Escaping of the `azure-pipelines-task-lib` logging commands
+pub(crate) fn azure_escape(value: &str) -> String {
+    value
+        .replace('%', "%AZP25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// A variable of `$GITHUB_OUTPUT` or `$GITHUB_ENV`: `name=value`, or a heredoc for multi-line
+/// values, whose lines would otherwise be read as variables of their own
+/// https://docs.github.com/en/actions/using-workflows/workflow-commands-for-github-actions#multiline-strings
+fn github_variable(name: &str, value: &str) -> String {
+    match value.contains('\n') {
+        true => format!("{}<<VERSION_VINE_EOF\n{}\nVERSION_VINE_EOF", name, value),
+        false => format!("{}={}", name, value),
+    }
+}
+
+/// https://www.jetbrains.com/help/teamcity/service-messages.html#Escaped+Values
+pub(crate) fn teamcity_escape(value: &str) -> String {
+    let mut escaped = String::new();
+    for c in value.chars() {
+        match c {
+            '|' => escaped.push_str("||"),
+            '\'' => escaped.push_str("|'"),
+            '\n' => escaped.push_str("|n"),
+            '\r' => escaped.push_str("|r"),
+            '[' => escaped.push_str("|["),
+            ']' => escaped.push_str("|]"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::SCHEMA_VERSION;
+
+    use super::*;
+
+    fn version_info() -> VersionInfo {
+        VersionInfo {
+            ahead_of_main: Some(3),
+            ..crate::version_calculator::version_info(
+                "feature/it's",
+                "1.0.1-alpha.3+feature-it-s.1234567",
+            )
+        }
+    }
+
+    #[test]
+    fn test_render_plain() {
+        let output = render(
+            &version_info(),
+            OutputFormat::Plain,
+            &[],
+            SCHEMA_VERSION,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(output, "1.0.1-alpha.3+feature-it-s.1234567");
+    }
+
+    #[test]
+    fn test_render_template() {
+        let output = render_template(
+            &version_info(),
+            &VersionOptions::default(),
+            "#define VERSION \"{{major}}.{{ minor }}.{{patch}}-{{pre}}\" // {{escaped_branch}} {{container_tag}} {{git_rev}} {{rev_count}}\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            output,
+            "#define VERSION \"1.0.1-alpha.3\" // feature-it-s 1.0.1-alpha.3.feature-it-s.1234567 1234567 3\n"
+        );
+    }
+
+    #[test]
+    fn test_cache_key() {
+        let mut version_info = version_info();
+        version_info
+            .env_fields
+            .insert("runner_os".to_string(), "Linux X64".to_string());
+        let options = VersionOptions {
+            app_name: Some("myapp".to_string()),
+            ..Default::default()
+        };
+
+        let key = cache_key(
+            &version_info,
+            &options,
+            "{{app_name}}-{{app_version}}-{{runner_os}}",
+        )
+        .unwrap();
+
+        assert_eq!(key, "myapp-1.0.1-alpha.3-feature-it-s.1234567-Linux-X64");
+    }
+
+    #[test]
+    fn test_render_template_unknown_variable() {
+        let result = render_template(&version_info(), &VersionOptions::default(), "{{mayor}}");
+
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .starts_with("Unknown template variable 'mayor'. Available variables: ahead_of_main,"));
+    }
+
+    #[test]
+    fn test_render_json_canonical() {
+        let mut version_info = version_info();
+        version_info.git_branch = "feature/é\t\"x\"".to_string();
+
+        let output = render(&version_info, OutputFormat::Json, &[], SCHEMA_VERSION, true).unwrap();
+
+        assert!(output.starts_with(
+            r#"{"ahead_of_main":3,"app_version":"1.0.1-alpha.3+feature-it-s.1234567","base_tag":null,"base_version":null,"behind_main":null,"#
+        ));
+        assert!(output.contains(r#","git_branch":"feature/é\t\"x\"","#));
+        assert_eq!(
+            render(&version_info, OutputFormat::Json, &[], SCHEMA_VERSION, true).unwrap(),
+            output
+        );
+    }
+
+    #[test]
+    fn test_to_json_string_sorted() {
+        #[derive(Serialize)]
+        struct Row {
+            zebra: u32,
+            apple: Vec<u32>,
+        }
+
+        let output = to_json_string(
+            &[Row {
+                zebra: 1,
+                apple: vec![2],
+            }],
+            true,
+        )
+        .unwrap();
+
+        assert_eq!(output, r#"[{"apple":[2],"zebra":1}]"#);
+    }
+
+    #[test]
+    fn test_render_env() {
+        let output = render(
+            &version_info(),
+            OutputFormat::Env,
+            &[],
+            SCHEMA_VERSION,
+            false,
+        )
+        .unwrap();
+
+        assert!(output.contains("\nAPP_VERSION=1.0.1-alpha.3+feature-it-s.1234567\n"));
+        assert!(output.contains("\nGIT_BRANCH='feature/it'\\''s'\n"));
+        assert!(output.contains("\nBEHIND_MAIN=\n"));
+    }
+
+    #[test]
+    fn test_render_fields() {
+        let fields = ["app_version".to_string(), "git_rev".to_string()];
+
+        let output = render(
+            &version_info(),
+            OutputFormat::Json,
+            &fields,
+            SCHEMA_VERSION,
+            true,
+        )
+        .unwrap();
+
+        assert_eq!(
+            output,
+            r#"{"app_version":"1.0.1-alpha.3+feature-it-s.1234567","git_rev":"1234567"}"#
+        );
+        assert!(render(
+            &version_info(),
+            OutputFormat::Env,
+            &["version".to_string()],
+            SCHEMA_VERSION,
+            false
+        )
+        .unwrap_err()
+        .to_string()
+        .starts_with(
+            "Unknown output field 'version'. Available fields: ahead_of_main, app_version,"
+        ));
+    }
+
+    #[test]
+    fn test_render_github() {
+        let output = render(
+            &version_info(),
+            OutputFormat::Github,
+            &[],
+            SCHEMA_VERSION,
+            false,
+        )
+        .unwrap();
+
+        assert!(
+            output.starts_with("ahead_of_main=3\napp_version=1.0.1-alpha.3+feature-it-s.1234567\n")
+        );
+    }
+
+    #[test]
+    fn test_render_github_multiline() {
+        let mut version_info = version_info();
+        version_info.commit.tag_message = Some("Release 1.0.0\n\napp_version=9.9.9".to_string());
+
+        let output = render(
+            &version_info,
+            OutputFormat::Github,
+            &["tag_message".to_string(), "app_version".to_string()],
+            SCHEMA_VERSION,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(
+            output,
+            "app_version=1.0.1-alpha.3+feature-it-s.1234567\n\
+             tag_message<<VERSION_VINE_EOF\nRelease 1.0.0\n\napp_version=9.9.9\nVERSION_VINE_EOF"
+        );
+    }
+
+    #[test]
+    fn test_render_teamcity() {
+        let output = render(
+            &version_info(),
+            OutputFormat::Teamcity,
+            &[],
+            SCHEMA_VERSION,
+            false,
+        )
+        .unwrap();
+
+        assert!(output.contains("##teamcity[setParameter name='git_branch' value='feature/it|'s']"));
+        assert!(output.ends_with("##teamcity[buildNumber '1.0.1-alpha.3+feature-it-s.1234567']"));
+    }
+
+    fn export(export: ExportEnv, variables: &[(&str, &str)]) -> Result<String> {
+        let version_info = VersionInfo {
+            bump: Some(crate::conventional_commits::Bump::Patch),
+            ..version_info()
+        };
+        let options = VersionOptions {
+            app_name: Some("myapp".to_string()),
+            ..Default::default()
+        };
+        export_env(&version_info, &options, export, |name| {
+            variables
+                .iter()
+                .find(|(variable, _)| *variable == name)
+                .map(|(_, value)| value.to_string())
+        })
+    }
+
+    #[test]
+    fn test_export_env_shell() {
+        let output = export(ExportEnv::Auto, &[]).unwrap();
+
+        assert!(output.starts_with("export VERSION_VINE_AHEAD_OF_MAIN=3\n"));
+        assert!(output.contains("\nexport VERSION_VINE_APP_NAME=myapp\n"));
+        assert!(output.contains("\nexport VERSION_VINE_BUMP_SOURCE=patch\n"));
+        assert!(output.contains("\nexport VERSION_VINE_GIT_BRANCH='feature/it'\\''s'\n"));
+    }
+
+    #[test]
+    fn test_export_env_github() {
+        let path =
+            std::env::temp_dir().join(format!("version-vine-github-env-{}", std::process::id()));
+        std::fs::write(&path, "EXISTING=1\n").unwrap();
+
+        let output = export(
+            ExportEnv::Auto,
+            &[
+                ("GITHUB_ACTIONS", "true"),
+                ("GITHUB_ENV", path.to_str().unwrap()),
+            ],
+        )
+        .unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(output, "");
+        assert!(content.starts_with("EXISTING=1\nVERSION_VINE_AHEAD_OF_MAIN=3\n"));
+        assert!(content.contains("\nVERSION_VINE_APP_VERSION=1.0.1-alpha.3+feature-it-s.1234567\n"));
+        assert!(export(ExportEnv::Github, &[])
+            .unwrap_err()
+            .to_string()
+            .contains("GITHUB_ENV is not set"));
+    }
+
+    #[test]
+    fn test_export_env_azure() {
+        let output = export(ExportEnv::Auto, &[("TF_BUILD", "True")]).unwrap();
+
+        assert!(output.contains(
+            "\n##vso[task.setvariable variable=VERSION_VINE_BUMP]patch\n##vso[task.setvariable variable=VERSION_VINE_BUMP_SOURCE]patch\n"
+        ));
+        assert_eq!(azure_escape("50%\r\n"), "50%AZP25%0D%0A");
+    }
+
+    #[test]
+    fn test_export_env_teamcity() {
+        let output = export(ExportEnv::Teamcity, &[("GITHUB_ACTIONS", "true")]).unwrap();
+
+        assert!(output.contains(
+            "##teamcity[setParameter name='env.VERSION_VINE_GIT_BRANCH' value='feature/it|'s']"
+        ));
+    }
+
+    #[test]
+    fn test_render_table_csv() {
+        let rows = [
+            serde_json::json!({"app": "my,app", "releases": 2, "per_month": {"2024-01": 2}}),
+            serde_json::json!({"app": null, "releases": 1, "per_month": {}}),
+        ];
+
+        let output = render_table(&rows, TableFormat::Csv, false).unwrap();
+
+        assert_eq!(
+            output,
+            "app,per_month,releases\n\"my,app\",\"{\"\"2024-01\"\":2}\",2\n,{},1"
+        );
+    }
+
+    #[test]
+    fn test_render_yaml() {
+        let output = render(
+            &version_info(),
+            OutputFormat::Yaml,
+            &[],
+            SCHEMA_VERSION,
+            false,
+        )
+        .unwrap();
+
+        assert!(output.contains("\nbehind_main: null\n"));
+        assert!(output.contains("\ngit_branch: \"feature/it's\"\n"));
+        assert!(output.starts_with("ahead_of_main: 3\n"));
+    }
+
+    #[test]
+    fn test_write_atomic() {
+        let dir = std::env::temp_dir().join("version-vine-test-write-atomic");
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("build/version.env");
+
+        write_atomic(&path, "APP_VERSION=1.0.0").unwrap();
+        write_atomic(&path, "APP_VERSION=1.0.1").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "APP_VERSION=1.0.1\n");
+        assert_eq!(fs::read_dir(dir.join("build")).unwrap().count(), 1);
+        assert_eq!(OutputFormat::from_path(&path).unwrap(), OutputFormat::Env);
+        assert_eq!(
+            OutputFormat::from_path(Path::new("version.txt")).unwrap(),
+            OutputFormat::Plain
+        );
+        assert!(OutputFormat::from_path(Path::new("version")).is_err());
+    }
+}
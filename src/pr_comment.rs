@@ -0,0 +1,152 @@
+use anyhow::{Error, Result};
+use semver::{BuildMetadata, Prerelease, Version};
+use serde_json::{json, Value};
+
+use crate::forge::{ForgeArgs, HttpClientTrait};
+use crate::git_command::GitCommandTrait;
+use crate::models::{BranchClass, Regexes};
+use crate::version_calculator::get_default_branch;
+use crate::{VersionCalculator, VersionInfo, VersionOptions};
+
+/// Hidden marker identifying the comment, so re-runs update it instead of adding another one
+const MARKER: &str = "<!-- version-vine -->";
+
+#[derive(clap::Args, Debug)]
+pub struct PrCommentArgs {
+    /// Branch the pull request targets. Taken from the CI environment variables, or else the
+    /// default branch, when not provided.
+    #[arg(long)]
+    pub target: Option<String>,
+
+    /// Number of the pull request (GitHub) or merge request IID (GitLab). Taken from the CI
+    /// environment variables when not provided.
+    #[arg(long)]
+    pub number: Option<u64>,
+
+    #[command(flatten)]
+    pub forge: ForgeArgs,
+
+    /// Only print the comment, without calling the forge API
+    #[arg(long, action)]
+    pub dry_run: bool,
+}
+
+/// Comments the version the pull request produces after merging on the pull request, or updates
+/// the comment of a previous run, so reviewers see the version impact before merging
+pub fn pr_comment(
+    git_command: &impl GitCommandTrait,
+    http_client: &impl HttpClientTrait,
+    options: &VersionOptions,
+    version_info: &VersionInfo,
+    args: &PrCommentArgs,
+) -> Result<Value> {
+    let env = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+    let target = match &args.target {
+        Some(target) => target.clone(),
+        None => ci_target_branch(env).unwrap_or(get_default_branch(git_command, options)),
+    };
+    let merged = merged_version(git_command, options, version_info, &target)?;
+    let body = comment_body(version_info, &target, &merged);
+
+    let (_, repository) = args.forge.repository(git_command)?;
+    let mut result = json!({
+        "repository": repository,
+        "pull_request": args.number.or(version_info.pr_number),
+        "target": target,
+        "version": version_info.version.to_string(),
+        "merged_version": merged.to_string(),
+        "comment": body,
+        "comment_id": null,
+        "created": false,
+    });
+    if args.dry_run {
+        return Ok(result);
+    }
+
+    let number = result["pull_request"].as_u64().ok_or(Error::msg(
+        "Cannot determine the pull request. Provide the '--number' option.",
+    ))?;
+    let forge = args.forge.forge(git_command, http_client)?;
+    let (id, created) = forge.upsert_comment(number, MARKER, &body)?;
+    result["comment_id"] = json!(id);
+    result["created"] = json!(created);
+    Ok(result)
+}
+
+/// Version of the target branch after merging `HEAD`, see `--simulate-merge`. Main branches are only versioned from a
+/// tag, so merging into them releases the version without pre-release.
+fn merged_version(
+    git_command: &impl GitCommandTrait,
+    options: &VersionOptions,
+    version_info: &VersionInfo,
+    target: &str,
+) -> Result<Version> {
+    let default_branch = get_default_branch(git_command, options);
+    let regexes =
+        Regexes::new(&options.app_name, &options.config)?.with_default_branch(&default_branch)?;
+    if regexes.branch_class(target) == BranchClass::Main {
+        let mut version = version_info.version.clone();
+        version.pre = Prerelease::EMPTY;
+        version.build = BuildMetadata::EMPTY;
+        return Ok(version);
+    }
+    let merged_options = VersionOptions {
+        simulate_merge: Some(target.to_string()),
+        ..options.clone()
+    };
+    Ok(VersionCalculator::new(git_command, merged_options)?
+        .calculate()?
+        .version)
+}
+
+/// Target branch of the pull request build, from the CI environment variables
+fn ci_target_branch(env: impl Fn(&str) -> Option<String>) -> Option<String> {
+    ["GITHUB_BASE_REF", "CI_MERGE_REQUEST_TARGET_BRANCH_NAME"]
+        .iter()
+        .find_map(|name| env(name))
+}
+
+fn comment_body(version_info: &VersionInfo, target: &str, merged: &Version) -> String {
+    let mut body = format!(
+        "{}\n### Version impact\n\n| Branch | Version |\n| --- | --- |\n| `{}` | `{}` |\n| `{}` after merging | `{}` |\n",
+        MARKER, version_info.git_branch, version_info.version, target, merged
+    );
+    if let Some(bump) = version_info.bump {
+        body.push_str(&format!(
+            "\nThe changes are a **{}** bump.\n",
+            json!(bump).as_str().unwrap_or_default()
+        ));
+    }
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::conventional_commits::Bump;
+
+    use super::*;
+
+    #[test]
+    fn test_comment_body() {
+        let version_info = VersionInfo {
+            bump: Some(Bump::Minor),
+            ..crate::version_calculator::version_info(
+                "feature/login",
+                "1.3.0-alpha.2.feature-login+1234567",
+            )
+        };
+
+        assert_eq!(
+            comment_body(
+                &version_info,
+                "develop",
+                &Version::parse("1.3.0-beta.5+abcdef0").unwrap()
+            ),
+            "<!-- version-vine -->\n### Version impact\n\n\
+             | Branch | Version |\n| --- | --- |\n\
+             | `feature/login` | `1.3.0-alpha.2.feature-login+1234567` |\n\
+             | `develop` after merging | `1.3.0-beta.5+abcdef0` |\n\
+             \nThe changes are a **minor** bump.\n"
+        );
+    }
+}
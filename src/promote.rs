@@ -0,0 +1,140 @@
+use anyhow::{Error, Result};
+use semver::Prerelease;
+use serde_json::{json, Value};
+
+use crate::git_command::GitCommandTrait;
+use crate::models::{BranchClass, Regexes};
+use crate::tag::{create_tag, TagArgs};
+use crate::{VersionInfo, VersionOptions};
+
+#[derive(clap::Args, Debug)]
+pub struct PromoteArgs {
+    /// Tag the release version
+    #[arg(long, action)]
+    pub tag: bool,
+
+    /// Create a GPG signed tag. Implies `--tag`.
+    #[arg(short, long, action)]
+    pub sign: bool,
+
+    /// Push the tag. Implies `--tag`.
+    #[arg(long, action)]
+    pub push: bool,
+
+    /// Remote the tag is pushed to
+    #[arg(long, default_value = "origin")]
+    pub remote: String,
+
+    /// Only print the git commands, without running them
+    #[arg(long, action)]
+    pub dry_run: bool,
+}
+
+/// Promotes the pre-release of a release or hotfix branch to the stable release version, without
+/// pre-release and build metadata: the "finish release" step of git flow. Main branches promote
+/// the version of their tag.
+pub fn promote(
+    git_command: &impl GitCommandTrait,
+    options: &VersionOptions,
+    version_info: &VersionInfo,
+    args: &PromoteArgs,
+) -> Result<Value> {
+    match version_info.branch_class {
+        BranchClass::Main | BranchClass::Release | BranchClass::Hotfix => {}
+        class => {
+            return Err(Error::msg(format!(
+                "Only release, hotfix and main branches can be promoted, '{}' is a {} branch",
+                version_info.git_branch,
+                json!(class).as_str().unwrap_or_default()
+            )))
+        }
+    }
+    let regexes = Regexes::new(&options.app_name, &options.config)?;
+    let mut release = version_info.tag_version();
+    release.pre = Prerelease::EMPTY;
+    let mut result = json!({
+        "version": version_info.version.to_string(),
+        "release": release.to_string(),
+        "tag": regexes.tag_name(&release.to_string()),
+        "tagged": null,
+    });
+    if args.tag || args.sign || args.push {
+        let tag_args = TagArgs {
+            annotate: false,
+            message: None,
+            sign: args.sign,
+            release: true,
+            push: args.push,
+            remote: args.remote.clone(),
+            dry_run: args.dry_run,
+        };
+        result["tagged"] = create_tag(git_command, options, version_info, &tag_args)?;
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::git_command::MockGitCommandTrait;
+
+    use super::*;
+    use crate::version_calculator::version_info;
+
+    fn promote_args() -> PromoteArgs {
+        PromoteArgs {
+            tag: false,
+            sign: false,
+            push: false,
+            remote: "origin".to_string(),
+            dry_run: false,
+        }
+    }
+
+    #[test]
+    fn test_promote_release_branch() {
+        let mut git_command = MockGitCommandTrait::new();
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "rev-parse HEAD")
+            .returning(|_| Ok("1234567890".to_string()));
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "rev-parse -q --verify refs/tags/2.0.0^{commit}")
+            .returning(|_| Err(Error::msg("")));
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "tag 2.0.0")
+            .times(1)
+            .returning(|_| Ok(String::new()));
+        let args = PromoteArgs {
+            tag: true,
+            ..promote_args()
+        };
+
+        let result = promote(
+            &git_command,
+            &VersionOptions::default(),
+            &version_info("release/2.0.0", "2.0.0-rc.3+1234567"),
+            &args,
+        )
+        .unwrap();
+
+        assert_eq!(result["release"], "2.0.0");
+        assert_eq!(result["tagged"]["created"], true);
+    }
+
+    #[test]
+    fn test_promote_develop_branch() {
+        let result = promote(
+            &MockGitCommandTrait::new(),
+            &VersionOptions::default(),
+            &version_info("develop", "2.0.1-beta.3+1234567"),
+            &promote_args(),
+        );
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Only release, hotfix and main branches can be promoted, 'develop' is a develop branch"
+        );
+    }
+}
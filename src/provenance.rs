@@ -0,0 +1,211 @@
+use anyhow::{Error, Result};
+use clap::ValueEnum;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+use crate::ci::builder;
+use crate::config::ExecutionConfig;
+use crate::git_command::GitCommandTrait;
+use crate::models::Regexes;
+use crate::output::{to_json_string, write_atomic};
+use crate::sandbox;
+use crate::scheme::utc_timestamp;
+use crate::{VersionInfo, VersionOptions};
+
+const STATEMENT_TYPE: &str = "https://in-toto.io/Statement/v1";
+const PREDICATE_TYPE: &str = "https://slsa.dev/provenance/v1";
+const BUILD_TYPE: &str = "https://github.com/Freakazoid182/version-vine/provenance/v1";
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Signer {
+    /// `minisign` with a secret key without password, into `<file>.minisig`
+    Minisign,
+    /// `ssh-keygen -Y sign` with a private key, in the `file` namespace, into `<file>.sig`
+    SshKeygen,
+}
+
+/// Provenance of the computed version: an in-toto statement with a SLSA provenance predicate,
+/// naming the version tag and commit as subject, the version, branch and tags as parameters and
+/// the CI system as builder. `now` is the time the version was computed, in seconds since the
+/// epoch.
+pub fn provenance(
+    git_command: &impl GitCommandTrait,
+    options: &VersionOptions,
+    version_info: &VersionInfo,
+    env: impl Fn(&str) -> Option<String>,
+    now: u64,
+) -> Result<Value> {
+    let version = version_info.tag_version().to_string();
+    let tag = Regexes::new(&options.app_name, &options.config)?.tag_name(&version);
+    let commit = &version_info.commit;
+    let digest = commit
+        .sha
+        .as_ref()
+        .map(|sha| json!({"gitCommit": sha}))
+        .unwrap_or(json!({}));
+    let builder = builder(env);
+    // Credentials of HTTPS remotes aren't recorded
+    let repository = git_command
+        .run(vec!["remote", "get-url", "origin"])
+        .ok()
+        .filter(|url| !url.is_empty())
+        .map(|url| match url.split_once("://") {
+            Some((scheme, rest)) => {
+                let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+                let host = authority
+                    .rsplit_once('@')
+                    .map_or(authority, |(_, host)| host);
+                format!("{}://{}/{}", scheme, host, path)
+            }
+            None => url,
+        });
+    let dependencies: Vec<Value> = repository
+        .map(|repository| {
+            json!({
+                "uri": format!("git+{}@refs/heads/{}", repository, version_info.git_branch),
+                "digest": digest,
+            })
+        })
+        .into_iter()
+        .collect();
+    Ok(json!({
+        "_type": STATEMENT_TYPE,
+        "subject": [{"name": tag, "digest": digest}],
+        "predicateType": PREDICATE_TYPE,
+        "predicate": {
+            "buildDefinition": {
+                "buildType": BUILD_TYPE,
+                "externalParameters": {
+                    "app_name": options.app_name,
+                    "version": version_info.version.to_string(),
+                    "tag": tag,
+                    "base_tag": commit.tag,
+                    "branch": version_info.git_branch,
+                    "branch_class": version_info.branch_class,
+                },
+                "internalParameters": {
+                    "tool_version": env!("CARGO_PKG_VERSION"),
+                    "config_hash": options.config.hash(),
+                    "commit_timestamp": commit.timestamp,
+                    "tag_date": commit.tag_date,
+                },
+                "resolvedDependencies": dependencies,
+            },
+            "runDetails": {
+                "builder": {
+                    "id": builder
+                        .pipeline_url
+                        .clone()
+                        .or(builder.ci.clone())
+                        .unwrap_or("local".to_string()),
+                    "version": {"version-vine": env!("CARGO_PKG_VERSION")},
+                },
+                "metadata": {
+                    "invocationId": builder.pipeline_url,
+                    "finishedOn": utc_timestamp(now),
+                },
+                "byproducts": [{"name": "builder", "content": builder}],
+            },
+        },
+    }))
+}
+
+/// Writes the provenance document, and signs it with the `signer` when a key is provided. The
+/// signer has to be allowed by the `[execution]` policy. Returns the path of the signature.
+pub fn write_provenance(
+    policy: &ExecutionConfig,
+    path: &Path,
+    document: &Value,
+    signing: Option<(Signer, &Path)>,
+) -> Result<Option<PathBuf>> {
+    write_atomic(path, &to_json_string(document, false)?)?;
+    let Some((signer, key)) = signing else {
+        return Ok(None);
+    };
+    let file = path.to_string_lossy();
+    let key = key.to_string_lossy();
+    let (program, extension, args) = match signer {
+        Signer::Minisign => ("minisign", "minisig", vec!["-S", "-s", &key, "-m", &file]),
+        Signer::SshKeygen => (
+            "ssh-keygen",
+            "sig",
+            vec!["-Y", "sign", "-f", &key, "-n", "file", &file],
+        ),
+    };
+    let result = sandbox::execute(policy, program, &args)?;
+    if !result.status.success() {
+        return Err(Error::msg(format!(
+            "Cannot sign '{}' with {}: {}",
+            file,
+            program,
+            String::from_utf8_lossy(&result.stderr).trim()
+        )));
+    }
+    let mut signature = path.to_path_buf().into_os_string();
+    signature.push(format!(".{}", extension));
+    Ok(Some(PathBuf::from(signature)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::git_command::MockGitCommandTrait;
+
+    use crate::CommitMetadata;
+
+    use super::*;
+
+    #[test]
+    fn test_provenance() {
+        let mut git_command = MockGitCommandTrait::new();
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "remote get-url origin")
+            .returning(|_| Ok("https://token@github.com/org/app.git".to_string()));
+        let version_info = VersionInfo {
+            commit: CommitMetadata {
+                tag: Some("1.2.0".to_string()),
+                sha: Some("1234567890abcdef".to_string()),
+                timestamp: Some("2024-02-14T12:00:00+01:00".to_string()),
+                ..Default::default()
+            },
+            ..crate::version_calculator::version_info("main", "1.2.0+1234567")
+        };
+        let env = |name: &str| match name {
+            "GITHUB_ACTIONS" => Some("true".to_string()),
+            "GITHUB_SERVER_URL" => Some("https://github.com".to_string()),
+            "GITHUB_REPOSITORY" => Some("org/app".to_string()),
+            "GITHUB_RUN_ID" => Some("42".to_string()),
+            _ => None,
+        };
+
+        let document = provenance(
+            &git_command,
+            &VersionOptions::default(),
+            &version_info,
+            env,
+            1715472000,
+        )
+        .unwrap();
+
+        assert_eq!(
+            document["subject"],
+            json!([{"name": "1.2.0", "digest": {"gitCommit": "1234567890abcdef"}}])
+        );
+        let predicate = &document["predicate"];
+        let parameters = &predicate["buildDefinition"]["externalParameters"];
+        assert_eq!(parameters["version"], "1.2.0+1234567");
+        assert_eq!(parameters["branch_class"], "main");
+        assert_eq!(
+            predicate["buildDefinition"]["resolvedDependencies"][0]["uri"],
+            "git+https://github.com/org/app.git@refs/heads/main"
+        );
+        assert_eq!(
+            predicate["runDetails"]["builder"]["id"],
+            "https://github.com/org/app/actions/runs/42"
+        );
+        assert_eq!(
+            predicate["runDetails"]["metadata"]["finishedOn"],
+            "2024-05-12T00:00:00Z"
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use anyhow::Result;
+use serde_json::{json, Value};
+
+use std::fs;
+
+use crate::artifacts::{self, CHECKSUMS_FILE};
+use crate::changelog::{changelog, ChangelogArgs, Identity};
+use crate::forge::{ForgeArgs, HttpClientTrait};
+use crate::git_command::GitCommandTrait;
+use crate::models::Regexes;
+use crate::{VersionInfo, VersionOptions};
+
+#[derive(clap::Args, Debug)]
+pub struct ReleaseArgs {
+    #[command(flatten)]
+    pub forge: ForgeArgs,
+
+    /// List the authors of the commits under `Contributors` in the notes, as with `changelog`
+    #[arg(long, value_enum)]
+    pub contributors: Option<Identity>,
+
+    /// Glob of artifact files to upload to the release, in addition to the `[release]` assets of
+    /// the configuration, e.g. `dist/*.tar.gz`. Can be repeated.
+    #[arg(long = "asset", value_name = "GLOB")]
+    pub assets: Vec<String>,
+
+    /// Only print the release, without calling the forge API
+    #[arg(long, action)]
+    pub dry_run: bool,
+}
+
+/// Creates the GitHub (or GitLab) release of the computed version, with the changelog section of
+/// the version as notes. Versions with a pre-release, e.g. `1.3.0-rc.1`, are marked as
+/// pre-release. Re-runs update the release instead of failing.
+///
+/// The asset files are uploaded to the release with a `SHA256SUMS` file of their checksums,
+/// replacing the assets of a previous run.
+pub fn create_release(
+    git_command: &impl GitCommandTrait,
+    http_client: &impl HttpClientTrait,
+    options: &VersionOptions,
+    version_info: &VersionInfo,
+    args: &ReleaseArgs,
+) -> Result<Value> {
+    let section = changelog(
+        git_command,
+        options,
+        version_info,
+        &ChangelogArgs {
+            output: None,
+            contributors: args.contributors,
+        },
+    )?;
+    // The version heading of the section is the title of the release
+    let notes = section
+        .split_once('\n')
+        .map_or("", |(_, notes)| notes)
+        .trim_start();
+    let version = version_info.tag_version();
+    let tag = Regexes::new(&options.app_name, &options.config)?.tag_name(&version.to_string());
+    let commit = version_info
+        .commit
+        .sha
+        .clone()
+        .or(version_info.git_rev.clone())
+        .unwrap_or_default();
+
+    let (_, repository) = args.forge.repository(git_command)?;
+    let globs: Vec<String> = options
+        .config
+        .release
+        .assets
+        .iter()
+        .chain(&args.assets)
+        .cloned()
+        .collect();
+    let artifacts = artifacts::collect(&std::env::current_dir()?, &globs)?;
+    let mut assets = vec![];
+    let mut uploads = vec![];
+    for artifact in &artifacts {
+        assets.push(json!({
+            "name": artifact.name,
+            "path": artifact.path,
+            "sha256": artifact.sha256,
+            "size": artifact.size,
+            "url": null,
+        }));
+        uploads.push((artifact.name.clone(), fs::read(&artifact.path)?));
+    }
+    if !artifacts.is_empty() {
+        let checksums = artifacts::checksums(&artifacts, &version.to_string(), &tag, &commit);
+        assets.push(json!({
+            "name": CHECKSUMS_FILE,
+            "path": null,
+            "sha256": artifacts::sha256(checksums.as_bytes()),
+            "size": checksums.len(),
+            "url": null,
+        }));
+        uploads.push((CHECKSUMS_FILE.to_string(), checksums.into_bytes()));
+    }
+    let mut result = json!({
+        "repository": repository,
+        "tag": tag,
+        "commit": commit,
+        "prerelease": !version.pre.is_empty(),
+        "notes": notes,
+        "url": null,
+        "created": false,
+        "assets": assets,
+    });
+    if args.dry_run {
+        return Ok(result);
+    }
+
+    let forge = args.forge.forge(git_command, http_client)?;
+    let (url, created) = forge.upsert_release(&tag, &commit, notes, !version.pre.is_empty())?;
+    result["url"] = json!(url);
+    result["created"] = json!(created);
+    let urls = forge.upload_release_assets(&tag, &uploads)?;
+    for (asset, url) in result["assets"]
+        .as_array_mut()
+        .into_iter()
+        .flatten()
+        .zip(urls)
+    {
+        asset["url"] = json!(url);
+    }
+    Ok(result)
+}
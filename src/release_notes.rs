@@ -0,0 +1,159 @@
+use anyhow::{Error, Result};
+use std::path::PathBuf;
+
+use crate::changelog::{changelog, ChangelogArgs, Identity};
+use crate::git_command::GitCommandTrait;
+use crate::version_calculator::app_options;
+use crate::{calculate_apps, VersionOptions};
+
+#[derive(clap::Args, Debug)]
+pub struct ReleaseNotesArgs {
+    /// Title of the document
+    #[arg(long, default_value = "Release Notes")]
+    pub title: String,
+
+    /// File the release notes are written to. Printed to stdout when not provided.
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+
+    /// List the authors of the commits per app, like `changelog --contributors`
+    #[arg(long, value_enum)]
+    pub contributors: Option<Identity>,
+}
+
+/// Changelog section of an application
+pub struct AppNotes {
+    pub app: String,
+    pub version: String,
+    pub section: String,
+}
+
+impl AppNotes {
+    /// Changelog entries, excluding the contributors
+    fn changes(&self) -> Vec<(&str, usize)> {
+        let mut changes: Vec<(&str, usize)> = vec![];
+        for line in self.section.lines() {
+            if let Some(heading) = line.strip_prefix("### ") {
+                changes.push((heading, 0));
+            } else if line.starts_with("- ") {
+                match changes.last_mut() {
+                    Some((heading, count)) if *heading != "Contributors" => *count += 1,
+                    _ => {}
+                }
+            }
+        }
+        changes
+    }
+}
+
+/// Release notes of several applications in one document, e.g. for a platform release: a summary
+/// of the versions, and the changelog section of each application since its previous tag.
+pub fn release_notes(
+    git_command: &impl GitCommandTrait,
+    options: &VersionOptions,
+    apps: &[String],
+    args: &ReleaseNotesArgs,
+) -> Result<String> {
+    if apps.is_empty() {
+        return Err(Error::msg(
+            "Release notes require the applications, provided with --app-name or --all-apps",
+        ));
+    }
+    let changelog_args = ChangelogArgs {
+        output: None,
+        contributors: args.contributors,
+    };
+    let notes = calculate_apps(git_command, options, apps)?
+        .into_iter()
+        .map(|(app, version_info)| {
+            let section = changelog(
+                git_command,
+                &app_options(options, &app),
+                &version_info,
+                &changelog_args,
+            )?;
+            Ok(AppNotes {
+                app,
+                version: version_info.tag_version().to_string(),
+                section,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(combine(&args.title, &notes))
+}
+
+/// Markdown document with the summary table, followed by the sections of the applications with
+/// changes. Their headings are prefixed with the application name, e.g. `## api 1.2.0 (2024-02-14)`.
+pub fn combine(title: &str, notes: &[AppNotes]) -> String {
+    let mut document = format!(
+        "# {}\n\n| App | Version | Changes | Breaking Changes |\n| --- | --- | --- | --- |\n",
+        title
+    );
+    for app_notes in notes {
+        let changes = app_notes.changes();
+        let count = |filter: fn(&str) -> bool| -> usize {
+            changes
+                .iter()
+                .filter(|(heading, _)| filter(heading))
+                .map(|(_, count)| count)
+                .sum()
+        };
+        document.push_str(&format!(
+            "| {} | {} | {} | {} |\n",
+            app_notes.app,
+            app_notes.version,
+            count(|_| true),
+            count(|heading| heading == "Breaking Changes"),
+        ));
+    }
+    for app_notes in notes {
+        if app_notes.changes().iter().all(|(_, count)| *count == 0) {
+            continue;
+        }
+        let section = match app_notes.section.strip_prefix("## ") {
+            Some(section) => format!("## {} {}", app_notes.app, section),
+            None => app_notes.section.clone(),
+        };
+        document.push('\n');
+        document.push_str(&section);
+    }
+    document
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_combine() {
+        let notes = [
+            AppNotes {
+                app: "api".to_string(),
+                version: "2.0.0".to_string(),
+                section: "## 2.0.0 (2024-02-14)\n\
+                          \n### Breaking Changes\n\n- drop v1 endpoints (aaaaaaa)\n\
+                          \n### Features\n\n- add search (bbbbbbb)\n- add paging (ccccccc)\n\
+                          \n### Contributors\n\n- Jane Doe\n"
+                    .to_string(),
+            },
+            AppNotes {
+                app: "web".to_string(),
+                version: "1.4.0".to_string(),
+                section: "## 1.4.0 (2024-02-14)\n".to_string(),
+            },
+        ];
+
+        assert_eq!(
+            combine("Platform 2024.02", &notes),
+            "# Platform 2024.02\n\n\
+             | App | Version | Changes | Breaking Changes |\n\
+             | --- | --- | --- | --- |\n\
+             | api | 2.0.0 | 3 | 1 |\n\
+             | web | 1.4.0 | 0 | 0 |\n\
+             \n## api 2.0.0 (2024-02-14)\n\
+             \n### Breaking Changes\n\n- drop v1 endpoints (aaaaaaa)\n\
+             \n### Features\n\n- add search (bbbbbbb)\n- add paging (ccccccc)\n\
+             \n### Contributors\n\n- Jane Doe\n"
+        );
+    }
+}
@@ -0,0 +1,391 @@
+use anyhow::{Error, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+
+use crate::git_command::GitCommandTrait;
+use crate::models::Regexes;
+use crate::tag::verify_pushed_tag;
+use crate::{VersionInfo, VersionOptions};
+
+/// Trailer of the release tag message recording the key of the plan which created it
+const PLAN_TRAILER: &str = "Version-Vine-Plan";
+
+#[derive(clap::Args, Debug)]
+pub struct PlanArgs {
+    /// Remote the release commit and tag are pushed to
+    #[arg(long, default_value = "origin")]
+    pub remote: String,
+
+    /// File (relative to the repository root) the version is written to and committed before tagging
+    #[arg(long)]
+    pub version_file: Option<String>,
+}
+
+/// Everything a release run would do, to be reviewed before it's applied with `apply --plan`
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Plan {
+    pub version: String,
+    pub tag: String,
+    /// Full SHA of the commit the plan was made for. Applying fails when `HEAD` moved since.
+    pub commit: String,
+    pub steps: Vec<Step>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Step {
+    WriteFile {
+        path: String,
+        content: String,
+    },
+    Commit {
+        paths: Vec<String>,
+        message: String,
+    },
+    Tag {
+        name: String,
+        message: String,
+    },
+    Push {
+        remote: String,
+        refspecs: Vec<String>,
+    },
+}
+
+impl Plan {
+    /// Idempotency key, a FNV-1a hash of the plan. Stable across runs and platforms.
+    pub fn key(&self) -> Result<String> {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for byte in serde_json::to_string(self)?.bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        Ok(format!("{:016x}", hash))
+    }
+}
+
+/// Plans tagging the computed version (without build metadata), preceded by committing the
+/// version file when one is given, and pushing the result
+pub fn create_plan(
+    git_command: &impl GitCommandTrait,
+    options: &VersionOptions,
+    version_info: &VersionInfo,
+    args: &PlanArgs,
+) -> Result<Plan> {
+    if options.git_ref.is_some() {
+        return Err(Error::msg(
+            "A plan releases the checkout, so --ref isn't supported. Check out the ref instead.",
+        ));
+    }
+    version_info.require_commits("release")?;
+    let regexes = Regexes::new(&options.app_name, &options.config)?;
+    let version = version_info.tag_version();
+    let tag = regexes.tag_name(&version.to_string());
+    let commit = git_command.run(vec!["rev-parse", "HEAD"])?;
+    let message = format!("Release {}", tag);
+
+    let mut steps = vec![];
+    let mut refspecs = vec![];
+    if let Some(version_file) = &args.version_file {
+        steps.push(Step::WriteFile {
+            path: version_file.clone(),
+            content: format!("{}\n", version),
+        });
+        steps.push(Step::Commit {
+            paths: vec![version_file.clone()],
+            message: message.clone(),
+        });
+        refspecs.push(format!("HEAD:refs/heads/{}", version_info.git_branch));
+    }
+    steps.push(Step::Tag {
+        name: tag.clone(),
+        message,
+    });
+    refspecs.push(format!("refs/tags/{}", tag));
+    steps.push(Step::Push {
+        remote: args.remote.clone(),
+        refspecs,
+    });
+
+    Ok(Plan {
+        version: version.to_string(),
+        tag,
+        commit,
+        steps,
+    })
+}
+
+/// Executes the steps of a reviewed plan. Re-applying a plan which already created its tag only
+/// repeats the pushes, so retried CI jobs succeed.
+pub fn apply_plan(git_command: &impl GitCommandTrait, plan: &Plan) -> Result<Value> {
+    let key = plan.key()?;
+    let tag_ref = format!("refs/tags/{}", plan.tag);
+    if git_command
+        .run(vec!["rev-parse", "-q", "--verify", &tag_ref])
+        .is_ok()
+    {
+        let tag_key = git_command.run(vec![
+            "for-each-ref",
+            &format!(
+                "--format=%(contents:trailers:key={},valueonly)",
+                PLAN_TRAILER
+            ),
+            &tag_ref,
+        ])?;
+        if tag_key.trim() != key {
+            return Err(Error::msg(format!(
+                "Tag '{}' already exists, but wasn't created by this plan",
+                plan.tag
+            )));
+        }
+        for step in &plan.steps {
+            if let Step::Push { remote, refspecs } = step {
+                push(git_command, remote, refspecs)?;
+            }
+        }
+        return Ok(json!({
+            "tag": plan.tag,
+            "key": key,
+            "already_applied": true,
+            "verified": pushes(plan),
+        }));
+    }
+
+    let head = git_command.run(vec!["rev-parse", "HEAD"])?;
+    if head != plan.commit {
+        return Err(Error::msg(format!(
+            "The plan was made for commit '{}', but HEAD is at '{}'. Create a new plan.",
+            plan.commit, head
+        )));
+    }
+    let toplevel = git_command.run(vec!["rev-parse", "--show-toplevel"])?;
+    for step in &plan.steps {
+        match step {
+            Step::WriteFile { path, content } => {
+                fs::write(Path::new(&toplevel).join(path), content)?
+            }
+            Step::Commit { paths, message } => {
+                let mut add = vec!["add", "--"];
+                add.extend(paths.iter().map(|path| path.as_str()));
+                git_command.run(add)?;
+                git_command.run(vec!["commit", "-m", message])?;
+            }
+            Step::Tag { name, message } => {
+                let message = format!("{}\n\n{}: {}", message, PLAN_TRAILER, key);
+                git_command.run(vec!["tag", "-a", name, "-m", &message])?;
+            }
+            Step::Push { remote, refspecs } => push(git_command, remote, refspecs)?,
+        }
+    }
+    Ok(json!({
+        "tag": plan.tag,
+        "key": key,
+        "already_applied": false,
+        "verified": pushes(plan),
+    }))
+}
+
+/// Pushes the refspecs, verifying the pushed tags on the remote
+fn push(git_command: &impl GitCommandTrait, remote: &str, refspecs: &[String]) -> Result<()> {
+    let mut push = vec!["push", remote];
+    push.extend(refspecs.iter().map(|refspec| refspec.as_str()));
+    git_command.run(push)?;
+    for tag in refspecs
+        .iter()
+        .filter_map(|refspec| refspec.strip_prefix("refs/tags/"))
+    {
+        verify_pushed_tag(git_command, remote, tag)?;
+    }
+    Ok(())
+}
+
+/// Whether the plan pushes, so applying it verified the pushed tag
+fn pushes(plan: &Plan) -> bool {
+    plan.steps
+        .iter()
+        .any(|step| matches!(step, Step::Push { .. }))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::git_command::MockGitCommandTrait;
+
+    use super::*;
+    use crate::version_calculator::version_info;
+
+    #[test]
+    fn test_create_plan() {
+        let mut git_command = MockGitCommandTrait::new();
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "rev-parse HEAD")
+            .returning(|_| Ok("1234567890".to_string()));
+        let options = VersionOptions {
+            app_name: Some("myapp".to_string()),
+            ..Default::default()
+        };
+        let args = PlanArgs {
+            remote: "origin".to_string(),
+            version_file: Some("VERSION".to_string()),
+        };
+
+        let plan = create_plan(
+            &git_command,
+            &options,
+            &version_info("release/myapp-1.2.0", "1.2.0-rc.3+1234567"),
+            &args,
+        )
+        .unwrap();
+
+        assert_eq!(plan.tag, "myapp-1.2.0-rc.3");
+        assert_eq!(plan.commit, "1234567890");
+        assert_eq!(
+            plan.steps.last(),
+            Some(&Step::Push {
+                remote: "origin".to_string(),
+                refspecs: vec![
+                    "HEAD:refs/heads/release/myapp-1.2.0".to_string(),
+                    "refs/tags/myapp-1.2.0-rc.3".to_string()
+                ],
+            })
+        );
+        assert_eq!(plan.steps.len(), 4);
+    }
+
+    fn tag_plan() -> Plan {
+        serde_json::from_str(
+            r#"{
+                "version": "1.0.0",
+                "tag": "1.0.0",
+                "commit": "1234567890",
+                "steps": [
+                    {"action": "tag", "name": "1.0.0", "message": "Release 1.0.0"},
+                    {"action": "push", "remote": "origin", "refspecs": ["refs/tags/1.0.0"]}
+                ]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    fn mock_remote_tag(git_command: &mut MockGitCommandTrait) {
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "rev-parse refs/tags/1.0.0")
+            .returning(|_| Ok("abcdef0123".to_string()));
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "ls-remote origin refs/tags/1.0.0")
+            .returning(|_| Ok("abcdef0123\trefs/tags/1.0.0".to_string()));
+    }
+
+    #[test]
+    fn test_apply_plan() {
+        let mut git_command = MockGitCommandTrait::new();
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "rev-parse -q --verify refs/tags/1.0.0")
+            .returning(|_| Err(Error::msg("")));
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "rev-parse HEAD")
+            .returning(|_| Ok("1234567890".to_string()));
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "rev-parse --show-toplevel")
+            .returning(|_| Ok(std::env::temp_dir().to_str().unwrap().to_string()));
+        git_command
+            .expect_run()
+            .withf(|args| {
+                args.join(" ")
+                    == format!(
+                        "tag -a 1.0.0 -m Release 1.0.0\n\nVersion-Vine-Plan: {}",
+                        tag_plan().key().unwrap()
+                    )
+            })
+            .times(1)
+            .returning(|_| Ok(String::new()));
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "push origin refs/tags/1.0.0")
+            .times(1)
+            .returning(|_| Ok(String::new()));
+        mock_remote_tag(&mut git_command);
+
+        let result = apply_plan(&git_command, &tag_plan()).unwrap();
+
+        assert_eq!(result["already_applied"], false);
+        assert_eq!(result["verified"], true);
+    }
+
+    #[test]
+    fn test_apply_plan_already_applied() {
+        let key = tag_plan().key().unwrap();
+        let mut git_command = MockGitCommandTrait::new();
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "rev-parse" && args[1] == "-q")
+            .returning(|_| Ok("abcdef0123".to_string()));
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "for-each-ref")
+            .returning(move |_| Ok(key.clone()));
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "push origin refs/tags/1.0.0")
+            .times(1)
+            .returning(|_| Ok(String::new()));
+        mock_remote_tag(&mut git_command);
+
+        let result = apply_plan(&git_command, &tag_plan()).unwrap();
+
+        assert_eq!(result["already_applied"], true);
+    }
+
+    #[test]
+    fn test_apply_plan_tag_exists() {
+        let mut git_command = MockGitCommandTrait::new();
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "rev-parse" && args[1] == "-q")
+            .returning(|_| Ok("abcdef0123".to_string()));
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "for-each-ref")
+            .returning(|_| Ok(String::new()));
+
+        let result = apply_plan(&git_command, &tag_plan());
+
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("wasn't created by this plan"));
+    }
+
+    #[test]
+    fn test_apply_plan_head_moved() {
+        let mut git_command = MockGitCommandTrait::new();
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "rev-parse" && args[1] == "-q")
+            .returning(|_| Err(Error::msg("")));
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "rev-parse HEAD")
+            .returning(|_| Ok("abcdef0123".to_string()));
+        let plan = Plan {
+            version: "1.0.0".to_string(),
+            tag: "1.0.0".to_string(),
+            commit: "1234567890".to_string(),
+            steps: vec![],
+        };
+
+        let result = apply_plan(&git_command, &plan);
+
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Create a new plan"));
+    }
+}
@@ -0,0 +1,238 @@
+use anyhow::{Error, Result};
+use std::io::Read;
+use std::process::{Child, Command, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::ExecutionConfig;
+
+/// Git commands which access remotes, refused offline
+const NETWORK_GIT_COMMANDS: [&str; 5] = ["fetch", "pull", "push", "ls-remote", "clone"];
+
+/// Runs a command under the execution policy: only allowlisted programs, optionally with a
+/// scrubbed environment and a timeout. All processes version-vine spawns have to go through here,
+/// so running it on untrusted changes can't be abused to run arbitrary commands with CI credentials.
+pub fn execute(policy: &ExecutionConfig, program: &str, args: &[&str]) -> Result<Output> {
+    execute_path(policy, program, program, args)
+}
+
+/// Runs the allowlisted `program` from another path, e.g. the git executable of the user
+/// configuration
+pub fn execute_path(
+    policy: &ExecutionConfig,
+    program: &str,
+    path: &str,
+    args: &[&str],
+) -> Result<Output> {
+    spawn(policy, program, path, args, &[], policy.timeout_seconds)
+}
+
+/// Runs the allowlisted `program` with additional environment variables, which are passed also
+/// when the environment is scrubbed, and its own timeout, e.g. for the `[[hooks]]`
+pub fn execute_env(
+    policy: &ExecutionConfig,
+    program: &str,
+    args: &[&str],
+    env: &[(String, String)],
+    timeout_seconds: u64,
+) -> Result<Output> {
+    spawn(policy, program, program, args, env, Some(timeout_seconds))
+}
+
+fn spawn(
+    policy: &ExecutionConfig,
+    program: &str,
+    path: &str,
+    args: &[&str],
+    env: &[(String, String)],
+    timeout_seconds: Option<u64>,
+) -> Result<Output> {
+    if !policy
+        .allowed_commands
+        .iter()
+        .any(|allowed| allowed == program)
+    {
+        return Err(Error::msg(format!(
+            "Command '{}' is not allowed by the execution policy. Allowed commands: {}",
+            program,
+            policy.allowed_commands.join(", ")
+        )));
+    }
+    let network = program == "git"
+        && git_subcommand(args).is_some_and(|command| NETWORK_GIT_COMMANDS.contains(&command));
+    if policy.offline && network {
+        return Err(offline_error(&format!(
+            "run '{} {}'",
+            program,
+            args.join(" ")
+        )));
+    }
+    let mut command = Command::new(path);
+    command.args(args);
+    if policy.scrub_env == Some(true) {
+        command.env_clear();
+        command.envs(std::env::vars().filter(|(name, _)| env_allowed(policy, name)));
+    }
+    command.envs(env.iter().map(|(name, value)| (name, value)));
+    if policy.offline && program == "git" {
+        // Partial clones would fetch missing objects on demand
+        command.env("GIT_NO_LAZY_FETCH", "1");
+    }
+    let timeout = match timeout_seconds {
+        Some(timeout_seconds) => Duration::from_secs(timeout_seconds),
+        None => return command.output().map_err(|err| spawn_error(path, err)),
+    };
+
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| spawn_error(path, err))?;
+    // Read the pipes while waiting, as a full pipe would block the child
+    let stdout = read_pipe(child.stdout.take());
+    let stderr = read_pipe(child.stderr.take());
+    let status = wait_with_timeout(&mut child, timeout)
+        .map_err(|err| Error::msg(format!("Command '{} {}' {}", program, args.join(" "), err)))?;
+    Ok(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+/// Error of an action which needs network access while it's disabled, e.g. by `--offline`
+pub fn offline_error(action: &str) -> Error {
+    Error::msg(format!(
+        "Cannot {}: network access is disabled by --offline or the `offline` execution policy",
+        action
+    ))
+}
+
+/// The git command of the arguments, after the global options, e.g. `fetch` of `-C repo fetch`
+fn git_subcommand<'a>(args: &[&'a str]) -> Option<&'a str> {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match *arg {
+            // Options with a separate value
+            "-C" | "-c" | "--git-dir" | "--work-tree" | "--namespace" => {
+                args.next();
+            }
+            arg if arg.starts_with('-') => {}
+            arg => return Some(arg),
+        }
+    }
+    None
+}
+
+fn spawn_error(path: &str, err: std::io::Error) -> Error {
+    Error::msg(format!("Cannot run '{}': {}", path, err))
+}
+
+/// Environment variable names are allowed exactly, or by prefix with a trailing `*`, e.g. `GIT_*`
+fn env_allowed(policy: &ExecutionConfig, name: &str) -> bool {
+    policy
+        .env_allowlist
+        .iter()
+        .any(|allowed| match allowed.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => name == allowed,
+        })
+}
+
+fn read_pipe(pipe: Option<impl Read + Send + 'static>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buffer = vec![];
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buffer);
+        }
+        buffer
+    })
+}
+
+fn wait_with_timeout(child: &mut Child, timeout: Duration) -> Result<std::process::ExitStatus> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        if Instant::now() >= deadline {
+            child.kill()?;
+            child.wait()?;
+            return Err(Error::msg(format!(
+                "timed out after {} seconds",
+                timeout.as_secs()
+            )));
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(allowed_commands: &[&str]) -> ExecutionConfig {
+        ExecutionConfig {
+            allowed_commands: allowed_commands.iter().map(|c| c.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_execute_not_allowed() {
+        let result = execute(&policy(&["git"]), "sh", &["-c", "true"]);
+
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Command 'sh' is not allowed by the execution policy"));
+    }
+
+    #[test]
+    fn test_execute_offline() {
+        let mut policy = policy(&["git"]);
+        policy.offline = true;
+
+        for args in [
+            vec!["fetch", "--tags"],
+            vec!["-C", "repo", "push", "origin", "refs/tags/1.0.0"],
+            vec!["-c", "protocol.version=2", "ls-remote", "origin"],
+        ] {
+            let result = execute(&policy, "git", &args);
+            assert!(result
+                .unwrap_err()
+                .to_string()
+                .contains("network access is disabled by --offline"));
+        }
+        assert!(execute(&policy, "git", &["--version"]).is_ok());
+    }
+
+    #[test]
+    fn test_execute_scrub_env() {
+        let mut policy = policy(&["env"]);
+        policy.scrub_env = Some(true);
+        policy.env_allowlist = vec!["PATH".to_string(), "VERSION_VINE_TEST_*".to_string()];
+        std::env::set_var("VERSION_VINE_TEST_SCRUB", "kept");
+        std::env::set_var("VERSION_VINE_SECRET", "scrubbed");
+
+        let output = execute(&policy, "env", &[]).unwrap();
+
+        let output = String::from_utf8(output.stdout).unwrap();
+        assert!(output.contains("VERSION_VINE_TEST_SCRUB=kept"));
+        assert!(!output.contains("VERSION_VINE_SECRET"));
+    }
+
+    #[test]
+    fn test_execute_timeout() {
+        let mut policy = policy(&["sleep"]);
+        policy.timeout_seconds = Some(1);
+
+        let result = execute(&policy, "sleep", &["5"]);
+
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Command 'sleep 5' timed out after 1 seconds"));
+    }
+}
@@ -0,0 +1,230 @@
+use anyhow::{Error, Result};
+use serde_json::{json, Value};
+
+use crate::models::SCHEMA_VERSION;
+
+/// JSON Schema (draft 2020-12) of the version output of the `schema_version`, so downstream tools
+/// can validate the output. Version 1 is the output before `schema_version` was added, and
+/// otherwise the same. The `[output] env` fields are the additional string properties.
+pub fn output_schema(schema_version: u32) -> Result<Value> {
+    check_schema_version(schema_version)?;
+    let nullable =
+        |kind: &str, description: &str| json!({"type": [kind, "null"], "description": description});
+    let string = |description: &str| json!({"type": "string", "description": description});
+    let unsigned =
+        |description: &str| json!({"type": "integer", "minimum": 0, "description": description});
+    let mut properties = json!({
+        "git_branch": string("Branch the version is computed for"),
+        "git_rev": nullable("string", "Abbreviated SHA of the commit, null for branches without commits"),
+        "rev_count": string("Number of commits counted into the pre-release"),
+        "app_version": string("The computed SemVer version"),
+        "container_tag": string("The version as valid container image tag"),
+        "major": unsigned("Major version"),
+        "minor": unsigned("Minor version"),
+        "patch": unsigned("Patch version"),
+        "prerelease": string("Pre-release of the version, e.g. `beta.3`, empty on main"),
+        "build": string("Build metadata of the version, e.g. `56c1976`"),
+        "branch_class": {
+            "enum": ["main", "release", "hotfix", "develop", "support", "feature", "pull-request"],
+            "description": "Class of the branch, determining how it's versioned",
+        },
+        "channel": string("Release channel of the branch class, e.g. `beta` on develop"),
+        "channel_image": nullable("string", "Container image reference of the channel, e.g. `myapp:beta`, null without app name or `image`"),
+        "container_tags": {
+            "type": "array",
+            "items": {"type": "string"},
+            "description": "`container_tag` and the rolling tags of the branch class, e.g. `1.2` and `latest`",
+        },
+        "ahead_of_main": nullable("integer", "Commits of the branch not on the main branch"),
+        "behind_main": nullable("integer", "Commits of the main branch not on the branch"),
+        "branch_created_at": nullable("string", "ISO 8601 date of the first commit of the branch"),
+        "branch_age_days": nullable("integer", "Days since the first commit of the branch"),
+        "days_since_last_commit": nullable("integer", "Days since the latest commit of the branch"),
+        "changed_since_tag": {
+            "type": "boolean",
+            "description": "Whether there are commits (touching the `--path`) since the latest tag",
+        },
+        "bump": {
+            "enum": ["major", "minor", "patch", null],
+            "description": "Bump applied to the latest tag for develop and feature branches",
+        },
+        "base_tag": nullable("string", "Tag the version is based on, null without tag"),
+        "base_version": nullable("string", "Version the bump is applied to, of the base tag or the release branch name"),
+        "prerelease_source": {
+            "enum": ["branch-name", "tag", "build-number", null],
+            "description": "What the pre-release is derived from, null without pre-release",
+        },
+        "pr_number": nullable("integer", "Number of the pull request of pull request builds"),
+        "signature_verified": nullable("boolean", "Whether the signature of the release was verified, null when it isn't checked"),
+        "git_sha_full": nullable("string", "Full SHA of the commit"),
+        "commit_timestamp": nullable("string", "ISO 8601 committer date of the commit"),
+        "commit_epoch": nullable("integer", "Committer date of the commit in seconds since the epoch, e.g. for `SOURCE_DATE_EPOCH`"),
+        "committer": nullable("string", "`Name <email>` of the committer"),
+        "tag_date": nullable("string", "ISO 8601 creation date of the latest tag, the committer date for lightweight tags"),
+        "tag_message": nullable("string", "Annotation message of the latest tag, null for lightweight tags"),
+        "tagger": nullable("string", "`Name <email>` of the tagger of the latest tag, null for lightweight tags"),
+        "tag_signed": nullable("boolean", "Whether the latest tag carries a signature, unverified, null for lightweight tags"),
+    });
+    if schema_version >= 2 {
+        properties["schema_version"] = json!({
+            "const": schema_version,
+            "description": "Version of the output contract",
+        });
+    }
+    let required: Vec<String> = properties.as_object().unwrap().keys().cloned().collect();
+    properties["trace"] = json!({
+        "type": "array",
+        "items": {
+            "type": "object",
+            "properties": {"step": {"type": "string"}, "detail": {"type": "string"}},
+        },
+        "description": "Decisions of the calculation, only with `--explain=field`",
+    });
+    properties["builder"] = json!({
+        "type": "object",
+        "properties": {
+            "ci": {"type": ["string", "null"]},
+            "os": {"type": "string"},
+            "arch": {"type": "string"},
+            "runner": {"type": ["string", "null"]},
+            "pipeline_url": {"type": ["string", "null"]},
+        },
+        "description": "Machine the version was computed on, only with `--builder`",
+    });
+    properties["inputs"] = json!({
+        "type": "object",
+        "properties": {
+            "tool_version": {"type": "string"},
+            "head_sha": {"type": ["string", "null"]},
+            "base_tag": {"type": ["string", "null"]},
+            "branch": {"type": "string"},
+            "config_hash": {"type": "string"},
+            "args": {"type": "array", "items": {"type": "string"}},
+            "env": {"type": "object", "additionalProperties": {"type": "string"}},
+        },
+        "description": "What the version was computed from, only with `--inputs`",
+    });
+    properties["components"] = json!({
+        "type": "array",
+        "items": {
+            "type": "object",
+            "properties": {
+                "path": {"type": "string"},
+                "sha": {"type": "string"},
+                "tag": {"type": ["string", "null"]},
+                "version": {"type": ["string", "null"]},
+            },
+        },
+        "description": "Submodules with their pinned commits and versions, only with `--components`",
+    });
+    properties["cache_key"] = json!({
+        "type": "string",
+        "description": "Key for CI caches and artifacts, only with `--cache-key`",
+    });
+    for (field, description) in [
+        (
+            "assembly_version",
+            ".NET `AssemblyVersion`, only with `--extra-formats dotnet`",
+        ),
+        (
+            "file_version",
+            ".NET `FileVersion`, only with `--extra-formats dotnet`",
+        ),
+        (
+            "informational_version",
+            ".NET `InformationalVersion`, only with `--extra-formats dotnet`",
+        ),
+    ] {
+        properties[field] = string(description);
+    }
+    Ok(json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": format!("version-vine version output, schema version {}", schema_version),
+        "type": "object",
+        "required": required,
+        "properties": properties,
+        "additionalProperties": {
+            "type": "string",
+            "description": "Values of the `[output] env` variables, by lowercased name",
+        },
+    }))
+}
+
+/// The version output in the shape of the `schema_version`, e.g. `1` for tools which reject the
+/// fields added since
+pub fn output_shape(mut json: Value, schema_version: u32) -> Result<Value> {
+    check_schema_version(schema_version)?;
+    if let Value::Object(object) = &mut json {
+        if schema_version < 2 {
+            object.remove("schema_version");
+        }
+    }
+    Ok(json)
+}
+
+fn check_schema_version(schema_version: u32) -> Result<()> {
+    match schema_version {
+        1..=SCHEMA_VERSION => Ok(()),
+        _ => Err(Error::msg(format!(
+            "Unsupported schema version {}. Supported versions: 1 to {}",
+            schema_version, SCHEMA_VERSION
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::VersionInfo;
+
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    fn version_info() -> VersionInfo {
+        VersionInfo {
+            env_fields: BTreeMap::from([("build_flavor".to_string(), "debug".to_string())]),
+            ..crate::version_calculator::version_info("develop", "1.0.1-beta.3+1234567")
+        }
+    }
+
+    #[test]
+    fn test_output_schema_covers_output() {
+        for schema_version in 1..=SCHEMA_VERSION {
+            let schema = output_schema(schema_version).unwrap();
+            let output = output_shape(version_info().to_json(), schema_version).unwrap();
+
+            let properties = schema["properties"].as_object().unwrap();
+            let mut fields: Vec<&String> = output.as_object().unwrap().keys().collect();
+            fields.retain(|field| *field != "build_flavor");
+            let mut optional: Vec<&String> = properties.keys().collect();
+            optional.retain(|property| !fields.contains(property));
+            assert_eq!(
+                optional,
+                vec![
+                    "assembly_version",
+                    "builder",
+                    "cache_key",
+                    "components",
+                    "file_version",
+                    "informational_version",
+                    "inputs",
+                    "trace"
+                ]
+            );
+            assert_eq!(schema["required"].as_array().unwrap().len(), fields.len());
+        }
+    }
+
+    #[test]
+    fn test_output_shape() {
+        let output = output_shape(version_info().to_json(), 2).unwrap();
+        assert_eq!(output["schema_version"], SCHEMA_VERSION);
+
+        let output = output_shape(version_info().to_json(), 1).unwrap();
+        assert!(output.get("schema_version").is_none());
+        assert_eq!(output["app_version"], "1.0.1-beta.3+1234567");
+
+        assert!(output_shape(version_info().to_json(), 3).is_err());
+    }
+}
@@ -0,0 +1,593 @@
+//! Version schemes: how the version of a checkout is formed from the base version and the
+//! information git and the branch classification provide.
+//!
+//! The built-in [`Scheme`]s are selected with `scheme` in the configuration. Downstream crates can
+//! implement [`VersionScheme`] for organization specific schemes, and calculate with
+//! [`VersionCalculator::with_scheme`](crate::VersionCalculator::with_scheme):
+//!
+//! ```no_run
+//! use semver::{BuildMetadata, Version};
+//! use version_vine::git_command::GitCommand;
+//! use version_vine::scheme::{SchemeContext, VersionScheme};
+//! use version_vine::{VersionCalculator, VersionOptions};
+//!
+//! /// Always the version of the latest tag, with the commit as build metadata
+//! struct Pinned;
+//!
+//! impl VersionScheme for Pinned {
+//!     fn version(&self, context: &SchemeContext) -> anyhow::Result<Version> {
+//!         let mut version = context.base.clone();
+//!         version.build = BuildMetadata::new(context.git_rev)?;
+//!         Ok(version)
+//!     }
+//! }
+//!
+//! let git_command = GitCommand::default();
+//! let version_info = VersionCalculator::new(&git_command, VersionOptions::default())?
+//!     .with_scheme(Pinned)
+//!     .calculate()?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use anyhow::{Error, Result};
+use clap::ValueEnum;
+use semver::{BuildMetadata, Prerelease, Version};
+use serde::Deserialize;
+
+use crate::config::{
+    BuildMetadataComponent, BuildMetadataConfig, CalverConfig, CalverDate, CounterFormat,
+};
+use crate::conventional_commits::Bump;
+use crate::models::{BranchClass, BuildMetadataBuilder};
+use crate::next::next_version;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Inputs of a version scheme
+pub struct SchemeContext<'a> {
+    pub branch: &'a str,
+    pub branch_class: BranchClass,
+    /// Version of the release branch name, the tag at the branch point of hotfix branches, or else
+    /// of the latest tag (`0.0.0` without tags)
+    pub base: &'a Version,
+    /// Bump of develop and feature branches, following the bump strategy. Hotfix branches bump the
+    /// patch.
+    pub bump: Bump,
+    /// Pre-release label of the branch, e.g. `beta`
+    pub label: &'a str,
+    /// Pre-release counter, following `--counter` and the `[counter_format]`
+    pub counter: PrereleaseCounter,
+    /// Abbreviated commit SHA
+    pub git_rev: &'a str,
+    /// Branch name with the characters not allowed in build metadata replaced, truncated to the
+    /// maximum branch length
+    pub escaped_branch: &'a str,
+    /// Date of the `HEAD` commit following the date source, in seconds since the epoch
+    pub commit_date: u64,
+    /// Time of the build, or the `--as-of` date, in seconds since the epoch
+    pub now: u64,
+    /// The `--build-number`
+    pub build_number: Option<u64>,
+    /// Components and maximum length of the build metadata
+    pub build_metadata: &'a BuildMetadataConfig,
+}
+
+/// Pre-release counter of a checkout with its format, e.g. the `42` of `beta.42`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PrereleaseCounter {
+    pub value: u64,
+    pub format: CounterFormat,
+    /// Digits of the `padded` format
+    pub padding: usize,
+    /// Date of the commit following the date source in seconds since the epoch, for the `date`
+    /// format
+    pub date: u64,
+    /// Attempt of the CI run, for the `run-attempt` format
+    pub run_attempt: u64,
+}
+
+impl PrereleaseCounter {
+    /// The counter as `number` format
+    pub fn new(value: u64) -> Self {
+        Self {
+            value,
+            format: CounterFormat::Number,
+            padding: 0,
+            date: 0,
+            run_attempt: 1,
+        }
+    }
+
+    /// The pre-release of the label with the formatted counter, e.g. `beta.20240131.7`
+    pub fn prerelease(&self, label: &str) -> String {
+        match self.format {
+            CounterFormat::Number => format!("{}.{}", label, self.value),
+            CounterFormat::Padded => format!("{}{:0>2$}", label, self.value, self.padding),
+            CounterFormat::Date => {
+                let (year, month, day) = civil_date((self.date / SECONDS_PER_DAY) as i64);
+                format!("{}.{}{:02}{:02}.{}", label, year, month, day, self.value)
+            }
+            CounterFormat::RunAttempt => format!("{}.{}.{}", label, self.value, self.run_attempt),
+        }
+    }
+}
+
+/// Forms the version of a checkout
+pub trait VersionScheme {
+    fn version(&self, context: &SchemeContext) -> Result<Version>;
+}
+
+/// Built-in version schemes
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Scheme {
+    /// Bumped version with a pre-release counter, e.g. `1.2.1-beta.3`
+    Semver,
+    /// The counter added to the patch instead of a pre-release counter, e.g. `1.2.3-beta` three
+    /// commits after `1.2.0`
+    Height,
+    /// Calendar versions following the `[calver]` format, e.g. `2024.3.7-beta`
+    Calver,
+}
+
+impl Scheme {
+    pub fn version_scheme(&self, calver: &CalverConfig) -> Result<Box<dyn VersionScheme>> {
+        Ok(match self {
+            Scheme::Semver => Box::new(SemVerScheme),
+            Scheme::Height => Box::new(HeightScheme),
+            Scheme::Calver => Box::new(CalVerScheme::new(calver)?),
+        })
+    }
+}
+
+/// The default scheme, see the behavior table of the README
+pub struct SemVerScheme;
+
+impl VersionScheme for SemVerScheme {
+    fn version(&self, context: &SchemeContext) -> Result<Version> {
+        let mut version = context.base.clone();
+        if matches!(
+            context.branch_class,
+            BranchClass::Hotfix
+                | BranchClass::Support
+                | BranchClass::Develop
+                | BranchClass::Feature
+                | BranchClass::PullRequest
+        ) {
+            context.bump.apply(&mut version);
+        }
+        if context.branch_class != BranchClass::Main {
+            version.pre = prerelease(context.label, Some(&context.counter))?;
+        }
+        version.build = build_metadata(context)?;
+        Ok(version)
+    }
+}
+
+/// Height based versions, like Nerdbank.GitVersioning: unique without a pre-release counter
+pub struct HeightScheme;
+
+impl VersionScheme for HeightScheme {
+    fn version(&self, context: &SchemeContext) -> Result<Version> {
+        let mut version = context.base.clone();
+        match context.branch_class {
+            BranchClass::Main => {}
+            BranchClass::Release => version.patch = add_counter(version.patch, context)?,
+            BranchClass::Hotfix
+            | BranchClass::Support
+            | BranchClass::Develop
+            | BranchClass::Feature
+            | BranchClass::PullRequest => {
+                // The counter takes the place of the patch increment
+                match context.bump {
+                    Bump::Patch => {}
+                    bump => bump.apply(&mut version),
+                }
+                version.patch = add_counter(version.patch, context)?;
+            }
+        }
+        if context.branch_class != BranchClass::Main {
+            version.pre = prerelease(context.label, None)?;
+        }
+        version.build = build_metadata(context)?;
+        Ok(version)
+    }
+}
+
+fn add_counter(patch: u64, context: &SchemeContext) -> Result<u64> {
+    patch
+        .checked_add(context.counter.value)
+        .ok_or(Error::msg(format!(
+            "The patch {} plus the counter {} overflows",
+            patch, context.counter.value
+        )))
+}
+
+/// Segment of a calendar version
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum CalverSegment {
+    FullYear,
+    ShortYear,
+    Month,
+    Week,
+    Day,
+    Micro,
+}
+
+/// Calendar versions like `2024.3.7`: the segments of the format from the date, and the counter
+/// as micro segment. Main branches keep the version of their tag, and release branches the version
+/// of the branch name with a pre-release counter, as in the SemVer scheme.
+pub struct CalVerScheme {
+    segments: [CalverSegment; 3],
+    date: CalverDate,
+}
+
+impl CalVerScheme {
+    pub fn new(config: &CalverConfig) -> Result<Self> {
+        let invalid = || {
+            Error::msg(format!(
+                "Invalid CalVer format '{}': expected three of YYYY, YY, MM, WW, DD and MICRO, separated by dots",
+                config.format
+            ))
+        };
+        let segments = config
+            .format
+            .split('.')
+            .map(|segment| match segment {
+                "YYYY" => Ok(CalverSegment::FullYear),
+                "YY" => Ok(CalverSegment::ShortYear),
+                "MM" => Ok(CalverSegment::Month),
+                "WW" => Ok(CalverSegment::Week),
+                "DD" => Ok(CalverSegment::Day),
+                "MICRO" | "N" => Ok(CalverSegment::Micro),
+                _ => Err(invalid()),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            segments: segments.try_into().map_err(|_| invalid())?,
+            date: config.date,
+        })
+    }
+}
+
+impl VersionScheme for CalVerScheme {
+    fn version(&self, context: &SchemeContext) -> Result<Version> {
+        let mut version = context.base.clone();
+        match context.branch_class {
+            BranchClass::Main => {}
+            BranchClass::Release => {
+                version.pre = prerelease(context.label, Some(&context.counter))?
+            }
+            BranchClass::Hotfix
+            | BranchClass::Support
+            | BranchClass::Develop
+            | BranchClass::Feature
+            | BranchClass::PullRequest => {
+                let timestamp = match self.date {
+                    CalverDate::Commit => context.commit_date,
+                    CalverDate::Build => context.now,
+                };
+                let days = (timestamp / SECONDS_PER_DAY) as i64;
+                let (year, month, day) = civil_date(days);
+                let (week_year, week) = iso_week(days);
+                // Weeks are numbered within their ISO year, e.g. 2024-12-30 is in week 1 of 2025
+                let year = match self.segments.contains(&CalverSegment::Week) {
+                    true => week_year,
+                    false => year,
+                };
+                let [major, minor, patch] = self.segments.map(|segment| match segment {
+                    CalverSegment::FullYear => year as u64,
+                    CalverSegment::ShortYear => year.saturating_sub(2000) as u64,
+                    CalverSegment::Month => month as u64,
+                    CalverSegment::Week => week as u64,
+                    CalverSegment::Day => day as u64,
+                    CalverSegment::Micro => context.counter.value,
+                });
+                version = Version::new(major, minor, patch);
+                version.pre = prerelease(context.label, None)?;
+            }
+        }
+        version.build = build_metadata(context)?;
+        Ok(version)
+    }
+}
+
+/// UTC date and time of the seconds since the epoch in ISO 8601, e.g. `2024-05-12T00:00:00Z`
+pub(crate) fn utc_timestamp(epoch: u64) -> String {
+    let (year, month, day) = civil_date((epoch / SECONDS_PER_DAY) as i64);
+    let seconds = epoch % SECONDS_PER_DAY;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Year, month and day of the days since the epoch, see
+/// <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>
+fn civil_date(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// ISO year and week of the days since the epoch: the week (starting on Monday) belongs to the
+/// year of its Thursday
+fn iso_week(days: i64) -> (i64, u32) {
+    // 1970-01-01 was a Thursday
+    let weekday = (days + 3).rem_euclid(7);
+    let thursday = days - weekday + 3;
+    let (year, _, _) = civil_date(thursday);
+    let mut first_thursday = thursday;
+    while civil_date(first_thursday - 7).0 == year {
+        first_thursday -= 7;
+    }
+    (year, ((thursday - first_thursday) / 7 + 1) as u32)
+}
+
+/// The `[build_metadata] components`, by default the commit prefixed with the escaped branch on
+/// feature branches
+pub fn build_metadata(context: &SchemeContext) -> Result<BuildMetadata> {
+    let mut builder = BuildMetadataBuilder::new(context.build_metadata.max_length);
+    for component in &context.build_metadata.components {
+        match component {
+            BuildMetadataComponent::Branch if context.branch_class == BranchClass::Feature => {
+                builder.push(context.escaped_branch)?;
+            }
+            BuildMetadataComponent::Branch => {}
+            BuildMetadataComponent::Sha => {
+                builder.push(context.git_rev)?;
+            }
+            BuildMetadataComponent::Date => {
+                let (year, month, day) = civil_date((context.commit_date / SECONDS_PER_DAY) as i64);
+                builder.push(&format!("{:04}{:02}{:02}", year, month, day))?;
+            }
+            BuildMetadataComponent::BuildNumber => {
+                if let Some(build_number) = context.build_number {
+                    builder.push(&build_number.to_string())?;
+                }
+            }
+        }
+    }
+    builder.build()
+}
+
+/// Nightly version of the next release after the `base` version, e.g. `1.3.0-nightly.20240512+56c1976`,
+/// dated by the build date `now`, so builds of the same commit on the same day get the same version
+pub fn nightly_version(
+    base: &Version,
+    bump: Bump,
+    label: &str,
+    now: u64,
+    git_rev: &str,
+    build_metadata: &BuildMetadataConfig,
+) -> Result<Version> {
+    let mut version = next_version(base, bump);
+    let (year, month, day) = civil_date((now / SECONDS_PER_DAY) as i64);
+    version.pre = prerelease(
+        &format!("{}.{:04}{:02}{:02}", label, year, month, day),
+        None,
+    )?;
+    version.build = BuildMetadataBuilder::new(build_metadata.max_length)
+        .push(git_rev)?
+        .build()?;
+    Ok(version)
+}
+
+/// Pre-release of the label, e.g. `beta.3` with the counter
+pub fn prerelease(label: &str, counter: Option<&PrereleaseCounter>) -> Result<Prerelease> {
+    let prerelease = match counter {
+        Some(counter) => counter.prerelease(label),
+        None => label.to_string(),
+    };
+    Prerelease::new(&prerelease)
+        .map_err(|err| Error::msg(format!("Invalid pre-release label '{}': {}", label, err)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(
+        scheme: &dyn VersionScheme,
+        branch_class: BranchClass,
+        bump: Bump,
+        counter: u64,
+    ) -> String {
+        let context = SchemeContext {
+            branch: "branch",
+            branch_class,
+            base: &Version::parse("1.2.0").unwrap(),
+            bump,
+            label: "beta",
+            counter: PrereleaseCounter::new(counter),
+            git_rev: "1234567",
+            escaped_branch: "feature-login",
+            // 2024-12-30, in week 1 of 2025
+            commit_date: 1735560000,
+            now: 1735560000,
+            build_number: None,
+            build_metadata: &BuildMetadataConfig::default(),
+        };
+        scheme.version(&context).unwrap().to_string()
+    }
+
+    #[test]
+    fn test_nightly_version() {
+        let nightly = |base: &str, bump: Bump| {
+            nightly_version(
+                &Version::parse(base).unwrap(),
+                bump,
+                "nightly",
+                1715472000,
+                "1234567",
+                &BuildMetadataConfig::default(),
+            )
+            .unwrap()
+            .to_string()
+        };
+
+        assert_eq!(
+            nightly("1.2.0", Bump::Minor),
+            "1.3.0-nightly.20240512+1234567"
+        );
+        // The release a pre-release tag leads to
+        assert_eq!(
+            nightly("2.0.0-rc.1", Bump::Patch),
+            "2.0.0-nightly.20240512+1234567"
+        );
+    }
+
+    #[test]
+    fn test_build_metadata_components() {
+        let build_metadata = |config: &BuildMetadataConfig, branch_class| {
+            let context = SchemeContext {
+                branch: "feature/login",
+                branch_class,
+                base: &Version::parse("1.2.0").unwrap(),
+                bump: Bump::Patch,
+                label: "alpha",
+                counter: PrereleaseCounter::new(3),
+                git_rev: "1234567",
+                escaped_branch: "feature-login",
+                // 2024-12-30
+                commit_date: 1735560000,
+                now: 1735560000,
+                build_number: Some(42),
+                build_metadata: config,
+            };
+            build_metadata(&context).unwrap().to_string()
+        };
+        let config = BuildMetadataConfig::default();
+        assert_eq!(
+            build_metadata(&config, BranchClass::Feature),
+            "feature-login.1234567"
+        );
+        assert_eq!(build_metadata(&config, BranchClass::Develop), "1234567");
+
+        let config = BuildMetadataConfig {
+            components: vec![
+                BuildMetadataComponent::BuildNumber,
+                BuildMetadataComponent::Date,
+                BuildMetadataComponent::Sha,
+                BuildMetadataComponent::Branch,
+            ],
+            max_length: Some(20),
+            ..Default::default()
+        };
+        assert_eq!(
+            build_metadata(&config, BranchClass::Feature),
+            "42.20241230.1234567"
+        );
+    }
+
+    #[test]
+    fn test_prerelease_counter() {
+        let counter = |format| PrereleaseCounter {
+            value: 42,
+            format,
+            padding: 5,
+            // 2024-01-31
+            date: 1706700000,
+            run_attempt: 2,
+        };
+        let prerelease = |format| prerelease("beta", Some(&counter(format))).unwrap();
+
+        assert_eq!(prerelease(CounterFormat::Number).as_str(), "beta.42");
+        assert_eq!(prerelease(CounterFormat::Padded).as_str(), "beta00042");
+        assert_eq!(prerelease(CounterFormat::Date).as_str(), "beta.20240131.42");
+        assert_eq!(prerelease(CounterFormat::RunAttempt).as_str(), "beta.42.2");
+        // Beyond u32
+        assert_eq!(
+            PrereleaseCounter::new(5_000_000_000).prerelease("beta"),
+            "beta.5000000000"
+        );
+    }
+
+    #[test]
+    fn test_height_scheme() {
+        let scheme = HeightScheme;
+
+        assert_eq!(
+            version(&scheme, BranchClass::Main, Bump::Patch, 0),
+            "1.2.0+1234567"
+        );
+        assert_eq!(
+            version(&scheme, BranchClass::Develop, Bump::Patch, 3),
+            "1.2.3-beta+1234567"
+        );
+        assert_eq!(
+            version(&scheme, BranchClass::Feature, Bump::Minor, 3),
+            "1.3.3-beta+feature-login.1234567"
+        );
+        assert_eq!(
+            version(&scheme, BranchClass::Release, Bump::Patch, 2),
+            "1.2.2-beta+1234567"
+        );
+    }
+
+    #[test]
+    fn test_calver_scheme() {
+        let calver = |format: &str| {
+            let config = CalverConfig {
+                format: format.to_string(),
+                ..Default::default()
+            };
+            CalVerScheme::new(&config).unwrap()
+        };
+
+        assert_eq!(
+            version(
+                &calver("YYYY.MM.MICRO"),
+                BranchClass::Develop,
+                Bump::Minor,
+                7
+            ),
+            "2024.12.7-beta+1234567"
+        );
+        assert_eq!(
+            version(&calver("YY.WW.N"), BranchClass::Feature, Bump::Patch, 3),
+            "25.1.3-beta+feature-login.1234567"
+        );
+        assert_eq!(
+            version(&calver("YYYY.MM.DD"), BranchClass::Main, Bump::Patch, 0),
+            "1.2.0+1234567"
+        );
+        assert!(CalVerScheme::new(&CalverConfig {
+            format: "YYYY.0M".to_string(),
+            ..Default::default()
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn test_iso_week() {
+        // 2021-01-03 is in week 53 of 2020, 2024-03-07 in week 10
+        assert_eq!(civil_date(18630), (2021, 1, 3));
+        assert_eq!(iso_week(18630), (2020, 53));
+        assert_eq!(civil_date(19789), (2024, 3, 7));
+        assert_eq!(iso_week(19789), (2024, 10));
+    }
+
+    #[test]
+    fn test_utc_timestamp() {
+        assert_eq!(utc_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(utc_timestamp(1715515506), "2024-05-12T12:05:06Z");
+    }
+}
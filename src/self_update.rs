@@ -0,0 +1,372 @@
+use anyhow::{Error, Result};
+use semver::Version;
+use serde_json::{json, Value};
+
+use std::env::consts::{ARCH, EXE_SUFFIX, OS};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::artifacts::sha256;
+use crate::forge::{HttpClientTrait, HttpRequest};
+use crate::sandbox;
+use crate::VersionOptions;
+
+/// Repository the prebuilt binaries are released from
+const REPOSITORY: &str = "Freakazoid182/version-vine";
+
+#[derive(clap::Args, Debug)]
+pub struct SelfUpdateArgs {
+    /// Release tag to install, e.g. `0.5.0`, also to pin an older version. Defaults to the latest release.
+    #[arg(long, value_name = "TAG")]
+    pub version: Option<String>,
+
+    /// Only check for a newer release, without installing it
+    #[arg(long, action)]
+    pub check: bool,
+}
+
+/// Replaces the `exe` binary by the prebuilt binary of the release for this platform, from the
+/// GitHub releases. The archive is verified against the SHA-256 checksum released with it before
+/// it's extracted. Without a `--version`, only a newer release than the running one is installed.
+pub fn self_update(
+    http_client: &impl HttpClientTrait,
+    options: &VersionOptions,
+    args: &SelfUpdateArgs,
+    exe: &Path,
+) -> Result<Value> {
+    let current = Version::parse(env!("CARGO_PKG_VERSION"))?;
+    let path = match &args.version {
+        Some(tag) => format!("releases/tags/{}", tag),
+        None => "releases/latest".to_string(),
+    };
+    let release = http_client.send(&request(&format!(
+        "https://api.github.com/repos/{}/{}",
+        REPOSITORY, path
+    )))?;
+    let tag = release["tag_name"]
+        .as_str()
+        .ok_or(Error::msg("The release doesn't have a tag"))?;
+    let version = Version::parse(tag.trim_start_matches('v')).map_err(|err| {
+        Error::msg(format!(
+            "Release tag '{}' isn't a SemVer version: {}",
+            tag, err
+        ))
+    })?;
+    let up_to_date = version == current || (args.version.is_none() && version < current);
+    if args.check || up_to_date {
+        return Ok(json!({
+            "current": current.to_string(),
+            "version": version.to_string(),
+            "tag": tag,
+            "update_available": !up_to_date,
+            "updated": false,
+        }));
+    }
+
+    let target = target(OS, ARCH)?;
+    let extension = match OS {
+        "windows" => "zip",
+        _ => "tar.gz",
+    };
+    let asset = format!("{}-{}.{}", tag, target, extension);
+    let checksum_asset = format!("{}.sha256", asset);
+    let download = |name: &str| {
+        let url = release["assets"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|asset| asset["name"] == name)
+            .and_then(|asset| asset["browser_download_url"].as_str())
+            .ok_or(Error::msg(format!(
+                "Release '{}' doesn't have the '{}' asset",
+                tag, name
+            )))?;
+        http_client.download(&request(url))
+    };
+    let checksums = String::from_utf8(download(&checksum_asset)?)?;
+    let expected = parse_checksum(&checksums).ok_or(Error::msg(format!(
+        "'{}' doesn't contain a SHA-256 checksum",
+        checksum_asset
+    )))?;
+    let archive = download(&asset)?;
+    let checksum = sha256(&archive);
+    if checksum != expected {
+        return Err(Error::msg(format!(
+            "The checksum of '{}' is {}, not the {} of '{}'. The download is discarded.",
+            asset, checksum, expected, checksum_asset
+        )));
+    }
+
+    let dir = std::env::temp_dir().join(format!("version-vine-update-{}", process::id()));
+    fs::create_dir_all(&dir)?;
+    let result = extract(options, &dir, &asset, &archive).and_then(|binary| replace(&binary, exe));
+    fs::remove_dir_all(&dir)?;
+    result?;
+    Ok(json!({
+        "current": current.to_string(),
+        "version": version.to_string(),
+        "tag": tag,
+        "asset": asset,
+        "sha256": checksum,
+        "path": exe,
+        "updated": true,
+    }))
+}
+
+fn request(url: &str) -> HttpRequest {
+    HttpRequest {
+        method: "GET".to_string(),
+        url: url.to_string(),
+        headers: vec![
+            (
+                "Accept".to_string(),
+                "application/vnd.github+json".to_string(),
+            ),
+            (
+                "User-Agent".to_string(),
+                format!("version-vine/{}", env!("CARGO_PKG_VERSION")),
+            ),
+        ],
+        body: None,
+    }
+}
+
+/// Target triple of the prebuilt binary of the platform
+fn target(os: &str, arch: &str) -> Result<&'static str> {
+    match (os, arch) {
+        ("linux", "x86_64") => Ok("x86_64-unknown-linux-musl"),
+        ("macos", "x86_64") => Ok("x86_64-apple-darwin"),
+        ("macos", "aarch64") => Ok("aarch64-apple-darwin"),
+        ("windows", "x86_64") => Ok("x86_64-pc-windows-msvc"),
+        _ => Err(Error::msg(format!(
+            "There's no prebuilt binary for {} {}. Install version-vine with `cargo install` instead.",
+            os, arch
+        ))),
+    }
+}
+
+/// The checksum of a `shasum` (`<checksum>  <file name>`) or `certutil -hashfile` output
+fn parse_checksum(checksums: &str) -> Option<String> {
+    checksums
+        .split_whitespace()
+        .find(|word| word.len() == 64 && word.bytes().all(|byte| byte.is_ascii_hexdigit()))
+        .map(str::to_ascii_lowercase)
+}
+
+/// Extracts the archive with `tar`, which also reads zip archives on Windows, returning the path
+/// of the binary. `tar` is run under the execution policy, with `tar` allowed.
+fn extract(options: &VersionOptions, dir: &Path, asset: &str, archive: &[u8]) -> Result<PathBuf> {
+    let archive_path = dir.join(asset);
+    fs::write(&archive_path, archive)?;
+    let mut policy = options.config.execution.clone();
+    policy.allowed_commands.push("tar".to_string());
+    let output = sandbox::execute(
+        &policy,
+        "tar",
+        &[
+            "-xf",
+            &archive_path.to_string_lossy(),
+            "-C",
+            &dir.to_string_lossy(),
+        ],
+    )?;
+    if !output.status.success() {
+        return Err(Error::msg(format!(
+            "Cannot extract '{}': {}",
+            asset,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    let binary = dir.join(format!("version-vine{}", EXE_SUFFIX));
+    match binary.is_file() {
+        true => Ok(binary),
+        false => Err(Error::msg(format!(
+            "'{}' doesn't contain the version-vine binary",
+            asset
+        ))),
+    }
+}
+
+/// Moves the binary in place of `exe` next to it, so the replacement is a rename. Windows can't
+/// replace a running executable, but can rename it, so it's kept as `.old` there.
+fn replace(binary: &Path, exe: &Path) -> Result<()> {
+    let staged = exe.with_extension("new");
+    fs::copy(binary, &staged)
+        .map_err(|err| Error::msg(format!("Cannot write '{}': {}", staged.display(), err)))?;
+    if OS == "windows" {
+        let old = exe.with_extension("old");
+        let _ = fs::remove_file(&old);
+        fs::rename(exe, &old)?;
+    }
+    fs::rename(&staged, exe)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::forge::MockHttpClientTrait;
+
+    use super::*;
+
+    fn release(tag: &'static str) -> MockHttpClientTrait {
+        let mut http_client = MockHttpClientTrait::new();
+        http_client.expect_send().returning(move |_| {
+            Ok(json!({
+                "tag_name": tag,
+                "assets": [
+                    {
+                        "name": format!("{}-x86_64-unknown-linux-musl.tar.gz", tag),
+                        "browser_download_url": "https://example.com/archive",
+                    },
+                    {
+                        "name": format!("{}-x86_64-unknown-linux-musl.tar.gz.sha256", tag),
+                        "browser_download_url": "https://example.com/checksum",
+                    },
+                ],
+            }))
+        });
+        http_client
+    }
+
+    #[test]
+    fn test_self_update_check() {
+        let mut http_client = MockHttpClientTrait::new();
+        http_client
+            .expect_send()
+            .withf(|request| {
+                request.url
+                    == "https://api.github.com/repos/Freakazoid182/version-vine/releases/latest"
+            })
+            .returning(|_| Ok(json!({"tag_name": "99.0.0"})));
+        let args = SelfUpdateArgs {
+            version: None,
+            check: true,
+        };
+
+        let result = self_update(
+            &http_client,
+            &VersionOptions::default(),
+            &args,
+            Path::new("version-vine"),
+        )
+        .unwrap();
+
+        assert_eq!(result["version"], "99.0.0");
+        assert_eq!(result["current"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(result["update_available"], true);
+        assert_eq!(result["updated"], false);
+    }
+
+    #[test]
+    fn test_self_update_up_to_date() {
+        let args = SelfUpdateArgs {
+            version: None,
+            check: false,
+        };
+
+        let result = self_update(
+            &release("0.0.1"),
+            &VersionOptions::default(),
+            &args,
+            Path::new("version-vine"),
+        )
+        .unwrap();
+
+        // Older releases are only installed when pinned with --version
+        assert_eq!(result["update_available"], false);
+        assert_eq!(result["updated"], false);
+    }
+
+    #[test]
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    fn test_self_update_checksum_mismatch() {
+        let mut http_client = release("99.0.0");
+        http_client.expect_download().returning(|request| {
+            Ok(match request.url.as_str() {
+                "https://example.com/checksum" => format!(
+                    "{}  99.0.0-x86_64-unknown-linux-musl.tar.gz\n",
+                    sha256(b"expected")
+                )
+                .into_bytes(),
+                _ => b"tampered".to_vec(),
+            })
+        });
+        let args = SelfUpdateArgs {
+            version: Some("99.0.0".to_string()),
+            check: false,
+        };
+
+        let err = self_update(
+            &http_client,
+            &VersionOptions::default(),
+            &args,
+            Path::new("version-vine"),
+        )
+        .unwrap_err();
+
+        assert!(err
+            .to_string()
+            .starts_with("The checksum of '99.0.0-x86_64-unknown-linux-musl.tar.gz' is "));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_extract_replace() {
+        let dir = std::env::temp_dir().join(format!("version-vine-self-update-{}", process::id()));
+        let build = dir.join("build");
+        fs::create_dir_all(&build).unwrap();
+        fs::write(build.join("version-vine"), "new").unwrap();
+        let archive = dir.join("1.0.0-x86_64-unknown-linux-musl.tar.gz");
+        let status = process::Command::new("tar")
+            .arg("-czf")
+            .arg(&archive)
+            .arg("-C")
+            .arg(&build)
+            .arg("version-vine")
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let exe = dir.join("installed");
+        fs::write(&exe, "old").unwrap();
+        let extracted = dir.join("extracted");
+        fs::create_dir_all(&extracted).unwrap();
+
+        let binary = extract(
+            &VersionOptions::default(),
+            &extracted,
+            "1.0.0-x86_64-unknown-linux-musl.tar.gz",
+            &fs::read(&archive).unwrap(),
+        )
+        .unwrap();
+        replace(&binary, &exe).unwrap();
+
+        assert_eq!(fs::read_to_string(&exe).unwrap(), "new");
+        assert!(!exe.with_extension("new").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_target() {
+        assert_eq!(target("macos", "aarch64").unwrap(), "aarch64-apple-darwin");
+        assert!(target("linux", "riscv64").is_err());
+    }
+
+    #[test]
+    fn test_parse_checksum() {
+        let checksum = "a".repeat(64);
+
+        assert_eq!(
+            parse_checksum(&format!("{}  1.0.0-x86_64-apple-darwin.tar.gz\n", checksum)),
+            Some(checksum.clone())
+        );
+        assert_eq!(
+            parse_checksum(&format!(
+                "SHA256 hash of 1.0.0-x86_64-pc-windows-msvc.zip:\r\n{}\r\nCertUtil: -hashfile command completed successfully.\r\n",
+                checksum.to_uppercase()
+            )),
+            Some(checksum)
+        );
+        assert_eq!(parse_checksum("not a checksum"), None);
+    }
+}
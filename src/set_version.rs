@@ -0,0 +1,1033 @@
+use anyhow::{Error, Result};
+use regex_lite::Regex;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::config::StampConfig;
+use crate::output::render_template;
+use crate::{VersionInfo, VersionOptions};
+
+#[derive(clap::Args, Debug)]
+pub struct SetArgs {
+    /// Files to write the version to. The format follows from the file name: `Cargo.toml`, `package.json`, `pyproject.toml`, `*.csproj`, `*.nuspec`, Docker Compose files (`compose.yaml`, `docker-compose*.yml`), Homebrew formulas (`*.rb`), Scoop manifests (other `*.json`), winget manifests (other `*.yaml`) or `chocolateyinstall.ps1`. Defaults to the `[[stamp]]` files of the configuration.
+    #[arg(short, long = "target")]
+    pub targets: Vec<PathBuf>,
+
+    /// Regex matching the version in the targets, for other formats. The `version` group is replaced, or the whole match without it. E.g. `VERSION = "(?<version>[^"]*)"`.
+    #[arg(long, conflicts_with_all = ["json_path", "anchor"])]
+    pub regex: Option<String>,
+
+    /// Replace the version on each line of the targets containing the text, e.g. `# version-vine` after `ARG APP_VERSION=1.2.0` in a Dockerfile
+    #[arg(long, conflicts_with = "json_path")]
+    pub anchor: Option<String>,
+
+    /// Service of the Docker Compose targets whose `image:` tag is replaced by the container tag. Can be passed multiple times, e.g. `--service api --service worker`.
+    #[arg(long = "service", value_name = "SERVICE")]
+    pub services: Vec<String>,
+
+    /// Value written instead of the version, a template of the output fields, e.g. `{{container_tag}}`
+    #[arg(long)]
+    pub value: Option<String>,
+
+    /// Dot separated path of the version in JSON targets, e.g. `metadata.version`
+    #[arg(long)]
+    pub json_path: Option<String>,
+
+    /// SHA-256 checksums of the release artifacts, in `sha256sum` format, for the checksums of Homebrew formulas, Scoop and winget manifests and Chocolatey install scripts. The checksum of the file name of each `url` is written.
+    #[arg(long)]
+    pub checksums: Option<PathBuf>,
+
+    /// SHA-256 checksum of the release artifact, for package manifests with a single artifact
+    #[arg(long, conflicts_with = "checksums")]
+    pub sha256: Option<String>,
+
+    /// Only report the changes, without writing the files
+    #[arg(long, action)]
+    pub dry_run: bool,
+}
+
+/// How the version is located in a file
+#[derive(Debug)]
+pub enum Target {
+    /// `version = "..."` key of the first of the TOML tables which has one
+    Toml(&'static [&'static str]),
+    /// String at the path of object keys
+    Json(Vec<String>),
+    /// Text of the first element, e.g. `<Version>` of MSBuild projects
+    Xml(&'static str),
+    Regex(Regex),
+    /// The version on each line containing the text
+    Anchor(String),
+    /// Tag of the `image` of each of the services of a Docker Compose file
+    Compose(Vec<String>),
+    /// `version`, the version in the `url`s and the `sha256` following each of them of a Homebrew
+    /// formula
+    Homebrew(Checksums),
+    /// `PackageVersion`, and the `InstallerUrl`s and `InstallerSha256`s of winget manifests
+    Winget(Checksums),
+    /// `url`, `url64bit` and their `checksum` and `checksum64` of a `chocolateyinstall.ps1`
+    Chocolatey(Checksums),
+    /// `version`, and the `url` and `hash` at the top level or per `architecture` of a Scoop
+    /// manifest
+    Scoop(Checksums),
+}
+
+/// SHA-256 checksums of the release artifacts, by file name
+#[derive(Debug, Default)]
+pub struct Checksums {
+    files: BTreeMap<String, String>,
+    /// `--sha256`, the checksum of the only artifact
+    sha256: Option<String>,
+}
+
+impl Checksums {
+    /// Checksums of the `--checksums` file, with `<checksum>  <file name>` lines, or the `--sha256`
+    pub fn new(args: &SetArgs) -> Result<Self> {
+        let Some(path) = &args.checksums else {
+            return Ok(Self {
+                files: BTreeMap::new(),
+                sha256: args.sha256.clone(),
+            });
+        };
+        let content = fs::read_to_string(path)
+            .map_err(|err| Error::msg(format!("Cannot read '{}': {}", path.display(), err)))?;
+        let files = content
+            .lines()
+            .filter_map(|line| {
+                let (checksum, file) = line.split_once(char::is_whitespace)?;
+                // Binary mode entries are marked with `*`, paths are matched by file name
+                let file = file.trim_start().trim_start_matches('*');
+                let file_name = file.rsplit(['/', '\\']).next()?;
+                Some((file_name.to_string(), checksum.to_lowercase()))
+            })
+            .collect();
+        Ok(Self {
+            files,
+            sha256: None,
+        })
+    }
+
+    /// Checksum of the artifact the URL downloads, one of `artifacts` in the manifest
+    fn get(&self, url: &str, artifacts: usize) -> Result<String> {
+        let file_name = url
+            .split('#')
+            .next()
+            .and_then(|url| url.rsplit('/').next())
+            .unwrap_or_default();
+        match (&self.sha256, self.files.get(file_name)) {
+            (_, Some(checksum)) => Ok(checksum.clone()),
+            (Some(sha256), None) if artifacts == 1 => Ok(sha256.clone()),
+            (Some(_), None) => Err(Error::msg(
+                "Multiple artifacts to checksum, use --checksums instead of --sha256",
+            )),
+            (None, None) => Err(Error::msg(format!(
+                "No checksum of '{}'. Provide it with --checksums or --sha256",
+                file_name
+            ))),
+        }
+    }
+}
+
+impl Target {
+    /// Target of a file, from the `--regex`, `--anchor` or `--json-path` option or else the file
+    /// name
+    pub fn new(path: &Path, args: &SetArgs) -> Result<Self> {
+        if let Some(regex) = &args.regex {
+            return Self::regex(regex);
+        }
+        if let Some(anchor) = &args.anchor {
+            return Ok(Target::Anchor(anchor.clone()));
+        }
+        if let Some(json_path) = &args.json_path {
+            return Ok(Target::Json(
+                json_path.split('.').map(|key| key.to_string()).collect(),
+            ));
+        }
+        let file_name = path
+            .file_name()
+            .and_then(|file_name| file_name.to_str())
+            .unwrap_or_default();
+        if !args.services.is_empty() {
+            return Ok(Target::Compose(args.services.clone()));
+        }
+        match file_name {
+            file_name if is_compose_file(file_name) => Err(Error::msg(format!(
+                "Select the services of '{}' with --service",
+                path.display()
+            ))),
+            "Cargo.toml" => Ok(Target::Toml(&["package", "workspace.package"])),
+            "pyproject.toml" => Ok(Target::Toml(&["project", "tool.poetry"])),
+            "package.json" => Ok(Target::Json(vec!["version".to_string()])),
+            file_name if file_name.ends_with(".csproj") => Ok(Target::Xml("Version")),
+            file_name if file_name.ends_with(".nuspec") => Ok(Target::Xml("version")),
+            file_name if file_name.eq_ignore_ascii_case("chocolateyinstall.ps1") => {
+                Ok(Target::Chocolatey(Checksums::new(args)?))
+            }
+            file_name if file_name.ends_with(".rb") => Ok(Target::Homebrew(Checksums::new(args)?)),
+            file_name if file_name.ends_with(".yaml") || file_name.ends_with(".yml") => {
+                Ok(Target::Winget(Checksums::new(args)?))
+            }
+            file_name if file_name.ends_with(".json") => Ok(Target::Scoop(Checksums::new(args)?)),
+            _ => Err(Error::msg(format!(
+                "Unknown version file format of '{}'. Use --regex or --json-path.",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Target of a `[[stamp]]` file
+    pub fn stamp(stamp: &StampConfig) -> Result<Self> {
+        match (&stamp.regex, &stamp.anchor, stamp.services.is_empty()) {
+            (Some(regex), None, true) => Self::regex(regex),
+            (None, Some(anchor), true) => Ok(Target::Anchor(anchor.clone())),
+            (None, None, false) => Ok(Target::Compose(stamp.services.clone())),
+            _ => Err(Error::msg(format!(
+                "The [[stamp]] of '{}' needs either a regex, an anchor or services",
+                stamp.file.display()
+            ))),
+        }
+    }
+
+    fn regex(regex: &str) -> Result<Self> {
+        let regex = Regex::new(regex)
+            .map_err(|err| Error::msg(format!("Invalid regex '{}': {}", regex, err)))?;
+        Ok(Target::Regex(regex))
+    }
+
+    /// Byte range of the version in the content
+    fn find(&self, content: &str) -> Option<Range<usize>> {
+        match self {
+            Target::Toml(tables) => tables
+                .iter()
+                .find_map(|table| toml_version_range(content, table)),
+            Target::Json(path) => {
+                serde_json::from_str::<Value>(content).ok()?;
+                json_string_range(content, path)
+            }
+            Target::Xml(element) => {
+                let regex =
+                    Regex::new(&format!(r"<{}>(?<version>[^<]*)</{}>", element, element)).unwrap();
+                regex_range(&regex, content)
+            }
+            Target::Regex(regex) => regex_range(regex, content),
+            // Several fields, see `patch`
+            Target::Anchor(_)
+            | Target::Compose(_)
+            | Target::Homebrew(_)
+            | Target::Winget(_)
+            | Target::Chocolatey(_)
+            | Target::Scoop(_) => None,
+        }
+    }
+}
+
+/// Replaces the version of the target in the content, returning the previous version and the
+/// patched content
+pub fn patch(content: &str, target: &Target, version: &str) -> Result<(String, String)> {
+    let (previous, mut edits) = match target {
+        Target::Homebrew(checksums) => line_edits(content, version, checksums, &HOMEBREW)?,
+        Target::Winget(checksums) => line_edits(content, version, checksums, &WINGET)?,
+        Target::Chocolatey(checksums) => line_edits(content, version, checksums, &CHOCOLATEY)?,
+        Target::Scoop(checksums) => scoop_edits(content, version, checksums)?,
+        Target::Anchor(anchor) => anchor_edits(content, anchor, version)?,
+        Target::Compose(services) => compose_edits(content, services, version)?,
+        _ => {
+            let range = target.find(content).ok_or(Error::msg("No version found"))?;
+            let previous = content[range.clone()].to_string();
+            (previous, vec![(range, version.to_string())])
+        }
+    };
+    edits.sort_by_key(|(range, _)| range.start);
+    let mut patched = content.to_string();
+    for (range, replacement) in edits.into_iter().rev() {
+        patched.replace_range(range, &replacement);
+    }
+    Ok((previous, patched))
+}
+
+type Edits = (String, Vec<(Range<usize>, String)>);
+
+/// Fields of a line based package manifest, regexes with a `value` group and optionally the
+/// `key`. A checksum is the one of the last `url` of the same architecture, by `64` in the key.
+struct LineFields {
+    version: Option<&'static str>,
+    url: &'static str,
+    checksum: &'static str,
+    uppercase: bool,
+}
+
+const HOMEBREW: LineFields = LineFields {
+    version: Some(r#"^\s*version\s+"(?<value>[^"]*)""#),
+    url: r#"^\s*url\s+"(?<value>[^"]*)""#,
+    checksum: r#"^\s*sha256\s+"(?<value>[^"]*)""#,
+    uppercase: false,
+};
+
+const WINGET: LineFields = LineFields {
+    version: Some(r#"^\s*PackageVersion:\s*['"]?(?<value>[^'"\s]+)"#),
+    url: r#"^\s*-?\s*InstallerUrl:\s*['"]?(?<value>[^'"\s]+)"#,
+    checksum: r#"^\s*-?\s*InstallerSha256:\s*['"]?(?<value>[^'"\s]+)"#,
+    uppercase: true,
+};
+
+const CHOCOLATEY: LineFields = LineFields {
+    version: None,
+    url: r#"(?i)^\s*\$?(?<key>url(?:64(?:bit)?)?)\s*=\s*['"](?<value>[^'"]*)['"]"#,
+    checksum: r#"(?i)^\s*\$?(?<key>checksum(?:64)?)\s*=\s*['"](?<value>[^'"]*)['"]"#,
+    uppercase: false,
+};
+
+/// Edits of a line based package manifest. The previous version is taken from the version field,
+/// or else from the first `url`, and replaced in all `url`s.
+fn line_edits(
+    content: &str,
+    version: &str,
+    checksums: &Checksums,
+    fields: &LineFields,
+) -> Result<Edits> {
+    let version_field = fields.version.map(|field| Regex::new(field).unwrap());
+    let (url_field, checksum_field) = (
+        Regex::new(fields.url).unwrap(),
+        Regex::new(fields.checksum).unwrap(),
+    );
+    let lines = content.split_inclusive('\n').collect::<Vec<_>>();
+    let value = |regex: &Regex, line: &str| {
+        let caps = regex.captures(line)?;
+        let value = caps.name("value")?;
+        // Architecture of the field, `false` without key
+        let is_64 = caps
+            .name("key")
+            .is_some_and(|key| key.as_str().contains("64"));
+        Some((value.start()..value.end(), is_64))
+    };
+    let version_value = |line: &str| value(version_field.as_ref()?, line);
+
+    let previous = lines
+        .iter()
+        .find_map(|line| Some(line[version_value(line)?.0].to_string()))
+        .or_else(|| {
+            let url = lines
+                .iter()
+                .find_map(|line| Some(&line[value(&url_field, line)?.0]))?;
+            let version = Regex::new(r"\d+\.\d+\.\d+").unwrap();
+            Some(version.find(url)?.as_str().to_string())
+        })
+        .ok_or(Error::msg("No version found"))?;
+    let artifacts = lines
+        .iter()
+        .filter(|line| checksum_field.is_match(line))
+        .count();
+
+    let mut edits = vec![];
+    let mut offset = 0;
+    let mut urls: Vec<(bool, String)> = vec![];
+    for line in lines {
+        let range = |range: Range<usize>| offset + range.start..offset + range.end;
+        if let Some((value, _)) = version_value(line) {
+            edits.push((range(value), version.to_string()));
+        } else if let Some((value, is_64)) = value(&url_field, line) {
+            let url = line[value.clone()].replace(&previous, version);
+            edits.push((range(value), url.clone()));
+            urls.push((is_64, url));
+        } else if let Some((value, is_64)) = value(&checksum_field, line) {
+            let url = urls
+                .iter()
+                .rev()
+                .find(|(url_64, _)| *url_64 == is_64)
+                .map(|(_, url)| url.as_str())
+                .unwrap_or_default();
+            let checksum = checksums.get(url, artifacts)?;
+            edits.push((
+                range(value),
+                match fields.uppercase {
+                    true => checksum.to_uppercase(),
+                    false => checksum,
+                },
+            ));
+        }
+        offset += line.len();
+    }
+    Ok((previous, edits))
+}
+
+/// Edits of the first version on each line containing the anchor, e.g. renovate style comments
+fn anchor_edits(content: &str, anchor: &str, version: &str) -> Result<Edits> {
+    let version_regex =
+        Regex::new(r"\d+\.\d+\.\d+(?:-[0-9A-Za-z.-]+)?(?:\+[0-9A-Za-z.-]+)?").unwrap();
+    let mut edits = vec![];
+    let mut offset = 0;
+    for line in content.split_inclusive('\n') {
+        // The anchor itself may contain a version
+        let searched = match line.find(anchor) {
+            Some(index) => &line[..index],
+            None => "",
+        };
+        if let Some(found) = version_regex.find(searched) {
+            edits.push((
+                offset + found.start()..offset + found.end(),
+                version.to_string(),
+            ));
+        }
+        offset += line.len();
+    }
+    let previous = edits
+        .first()
+        .map(|(range, _)| content[range.clone()].to_string())
+        .ok_or(Error::msg(format!("No version found before '{}'", anchor)))?;
+    Ok((previous, edits))
+}
+
+/// Docker Compose files by the names Compose looks up, and their overrides, e.g.
+/// `docker-compose.prod.yml`
+fn is_compose_file(file_name: &str) -> bool {
+    (file_name.starts_with("compose.") || file_name.starts_with("docker-compose."))
+        && (file_name.ends_with(".yaml") || file_name.ends_with(".yml"))
+}
+
+/// Edits of the tag of the `image` of each service of a Docker Compose file, line based to keep
+/// the formatting and comments. The previous tag is the one of the first service, and a digest of
+/// the image is removed, as it pins the previous tag.
+fn compose_edits(content: &str, services: &[String], tag: &str) -> Result<Edits> {
+    let key = Regex::new(r#"^(?<indent> *)(?<key>[^\s#][^:#]*):(?:\s|$)"#).unwrap();
+    let image = Regex::new(r#"^ *image:\s*['"]?(?<image>[^'"\s#]+)"#).unwrap();
+    let mut in_services = false;
+    // Indentation of the service names, and of the keys of the current service
+    let mut service_indent = None;
+    let mut key_indent = None;
+    let mut service = None;
+    let mut tags = BTreeMap::new();
+    let mut offset = 0;
+    for line in content.split_inclusive('\n') {
+        let line_offset = offset;
+        offset += line.len();
+        let Some(caps) = key.captures(line) else {
+            continue;
+        };
+        let indent = caps["indent"].len();
+        let name = caps["key"].trim().trim_matches(['"', '\'']);
+        if indent == 0 {
+            in_services = name == "services";
+            service_indent = None;
+            service = None;
+            continue;
+        }
+        if !in_services {
+            continue;
+        }
+        if indent <= *service_indent.get_or_insert(indent) {
+            service = services.iter().find(|service| *service == name);
+            key_indent = None;
+            continue;
+        }
+        if *key_indent.get_or_insert(indent) != indent {
+            continue;
+        }
+        if let (Some(service), Some(value)) = (
+            service,
+            image.captures(line).and_then(|caps| caps.name("image")),
+        ) {
+            let (range, previous) = image_tag_range(value.as_str());
+            let start = line_offset + value.start();
+            tags.insert(
+                service.as_str(),
+                (start + range.start..start + range.end, previous.to_string()),
+            );
+        }
+    }
+    let mut edits = vec![];
+    let mut previous = None;
+    for service in services {
+        let (range, service_previous) = tags.remove(service.as_str()).ok_or(Error::msg(
+            format!("No image of the service '{}' found", service),
+        ))?;
+        previous.get_or_insert(service_previous);
+        edits.push((range, format!(":{}", tag)));
+    }
+    Ok((previous.unwrap_or_default(), edits))
+}
+
+/// Range of the `:tag` and `@digest` of an image reference, empty at its end without them, and
+/// the tag
+fn image_tag_range(reference: &str) -> (Range<usize>, &str) {
+    let name_start = reference.rfind('/').map_or(0, |index| index + 1);
+    let start = match reference[name_start..].find([':', '@']) {
+        Some(index) => name_start + index,
+        None => reference.len(),
+    };
+    let tag = reference[start..]
+        .strip_prefix(':')
+        .map(|tag| tag.split('@').next().unwrap_or_default())
+        .unwrap_or_default();
+    (start..reference.len(), tag)
+}
+
+/// Edits of a Scoop manifest. The previous `version` is replaced in the `url`s.
+fn scoop_edits(content: &str, version: &str, checksums: &Checksums) -> Result<Edits> {
+    let manifest = serde_json::from_str::<Value>(content)?;
+    let version_range = json_string_range(content, &["version".to_string()])
+        .ok_or(Error::msg("No version found"))?;
+    let previous = content[version_range.clone()].to_string();
+
+    let mut parents = vec![vec![]];
+    if let Some(architectures) = manifest["architecture"].as_object() {
+        parents.extend(
+            architectures
+                .keys()
+                .map(|architecture| vec!["architecture".to_string(), architecture.clone()]),
+        );
+    }
+    let field = |parent: &[String], name: &str| {
+        let path = [parent, &[name.to_string()]].concat();
+        json_string_range(content, &path).map(|range| (path, range))
+    };
+    let artifacts = parents
+        .iter()
+        .filter(|parent| field(parent, "hash").is_some())
+        .count();
+
+    let mut edits = vec![(version_range, version.to_string())];
+    for parent in &parents {
+        let Some((path, range)) = field(parent, "url") else {
+            continue;
+        };
+        let pointer = format!("/{}", path.join("/"));
+        let url = manifest
+            .pointer(&pointer)
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .replace(&previous, version);
+        let escaped = serde_json::to_string(&url)?;
+        edits.push((range, escaped[1..escaped.len() - 1].to_string()));
+        if let Some((_, range)) = field(parent, "hash") {
+            edits.push((range, checksums.get(&url, artifacts)?));
+        }
+    }
+    Ok((previous, edits))
+}
+
+/// Writes the computed version (without build metadata) to the target files, the container tag to
+/// Docker Compose files, or the `--value` template, and to the `[[stamp]]` files without targets. Nothing is written when the version
+/// isn't found in any of them.
+pub fn set_version(
+    version_info: &VersionInfo,
+    options: &VersionOptions,
+    args: &SetArgs,
+) -> Result<Value> {
+    let version = version_info.tag_version().to_string();
+    // Image tags are the container tag
+    let value = |template: &Option<String>, target: &Target| match (template, target) {
+        (Some(template), _) => render_template(version_info, options, template),
+        (None, Target::Compose(_)) => Ok(version_info.container_tag.clone()),
+        (None, _) => Ok(version.clone()),
+    };
+    let mut targets = vec![];
+    for path in &args.targets {
+        let target = Target::new(path, args)?;
+        let value = value(&args.value, &target)?;
+        targets.push((path, target, value));
+    }
+    if args.targets.is_empty() {
+        for stamp in &options.config.stamp {
+            let target = Target::stamp(stamp)?;
+            let value = value(&stamp.value, &target)?;
+            targets.push((&stamp.file, target, value));
+        }
+    }
+    if targets.is_empty() {
+        return Err(Error::msg(
+            "No files to set the version in. Provide them with --target or [[stamp]] entries of the configuration",
+        ));
+    }
+    let mut results = vec![];
+    let mut writes = vec![];
+    for (path, target, value) in targets {
+        let content = fs::read_to_string(path)
+            .map_err(|err| Error::msg(format!("Cannot read '{}': {}", path.display(), err)))?;
+        let (previous, patched) = patch(&content, &target, &value)
+            .map_err(|err| Error::msg(format!("{} in '{}'", err, path.display())))?;
+        if patched != content {
+            writes.push((path, patched));
+        }
+        results.push(json!({
+            "path": path,
+            "previous": previous,
+            "version": value,
+        }));
+    }
+    if !args.dry_run {
+        for (path, patched) in writes {
+            fs::write(path, patched)?;
+        }
+    }
+    Ok(json!({"version": version, "dry_run": args.dry_run, "targets": results}))
+}
+
+/// Range of the string at the path of object keys in JSON text, without its quotes
+fn json_string_range(content: &str, path: &[String]) -> Option<Range<usize>> {
+    let mut scanner = JsonScanner {
+        bytes: content.as_bytes(),
+        position: 0,
+    };
+    let range = scanner.find(path)?;
+    match content[range.clone()].starts_with('"') {
+        true => Some(range.start + 1..range.end - 1),
+        false => None,
+    }
+}
+
+fn regex_range(regex: &Regex, content: &str) -> Option<Range<usize>> {
+    let caps = regex.captures(content)?;
+    let version = caps.name("version").or(caps.get(0))?;
+    Some(version.range())
+}
+
+/// Range of the quoted `version` value in a TOML table, line based to keep the formatting
+fn toml_version_range(content: &str, table: &str) -> Option<Range<usize>> {
+    let header = Regex::new(r"^\s*\[(?<table>[^\[\]]+)\]").unwrap();
+    let key = Regex::new(r#"^\s*version\s*=\s*"(?<version>[^"]*)""#).unwrap();
+    let mut in_table = false;
+    let mut offset = 0;
+    for line in content.split_inclusive('\n') {
+        if line.trim_start().starts_with('[') {
+            in_table = header
+                .captures(line)
+                .is_some_and(|caps| caps["table"].trim() == table);
+        } else if in_table {
+            if let Some(version) = key.captures(line).and_then(|caps| caps.name("version")) {
+                return Some(offset + version.start()..offset + version.end());
+            }
+        }
+        offset += line.len();
+    }
+    None
+}
+
+/// Locates values in JSON text, so they can be replaced without reformatting the document.
+/// Expects valid JSON.
+struct JsonScanner<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl JsonScanner<'_> {
+    /// Range of the value at the path of object keys, starting at the current position
+    fn find(&mut self, path: &[String]) -> Option<Range<usize>> {
+        self.skip_whitespace();
+        let Some((key, path)) = path.split_first() else {
+            let start = self.position;
+            self.skip_value()?;
+            return Some(start..self.position);
+        };
+        if self.next()? != b'{' {
+            return None;
+        }
+        loop {
+            self.skip_whitespace();
+            match self.peek()? {
+                b'}' => return None,
+                b',' => self.position += 1,
+                _ => {
+                    let start = self.position;
+                    self.skip_value()?;
+                    let member = &self.bytes[start + 1..self.position - 1];
+                    self.skip_whitespace();
+                    self.next()?; // `:`
+                    if member == key.as_bytes() {
+                        return self.find(path);
+                    }
+                    self.skip_whitespace();
+                    self.skip_value()?;
+                }
+            }
+        }
+    }
+
+    fn skip_value(&mut self) -> Option<()> {
+        match self.next()? {
+            b'"' => loop {
+                match self.next()? {
+                    b'\\' => self.position += 1,
+                    b'"' => return Some(()),
+                    _ => {}
+                }
+            },
+            b'{' | b'[' => {
+                let mut depth = 1;
+                while depth > 0 {
+                    match self.peek()? {
+                        b'"' => {
+                            self.skip_value()?;
+                            continue;
+                        }
+                        b'{' | b'[' => depth += 1,
+                        b'}' | b']' => depth -= 1,
+                        _ => {}
+                    }
+                    self.position += 1;
+                }
+                Some(())
+            }
+            _ => {
+                while self
+                    .peek()
+                    .is_some_and(|byte| !b",}] \t\r\n".contains(&byte))
+                {
+                    self.position += 1;
+                }
+                Some(())
+            }
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|byte| byte.is_ascii_whitespace()) {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let byte = self.peek()?;
+        self.position += 1;
+        Some(byte)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set_args() -> SetArgs {
+        SetArgs {
+            targets: vec![],
+            regex: None,
+            anchor: None,
+            services: vec![],
+            value: None,
+            json_path: None,
+            checksums: None,
+            sha256: None,
+            dry_run: false,
+        }
+    }
+
+    fn target(file_name: &str, args: &SetArgs) -> Target {
+        Target::new(Path::new(file_name), args).unwrap()
+    }
+
+    #[test]
+    fn test_patch_cargo_toml() {
+        let content = "[dependencies]\nversion = \"1\"\n\n[package]\nname = \"app\"\nversion = \"0.1.0\" # comment\n";
+
+        let (previous, patched) =
+            patch(content, &target("Cargo.toml", &set_args()), "1.2.0").unwrap();
+
+        assert_eq!(previous, "0.1.0");
+        assert_eq!(
+            patched,
+            "[dependencies]\nversion = \"1\"\n\n[package]\nname = \"app\"\nversion = \"1.2.0\" # comment\n"
+        );
+    }
+
+    #[test]
+    fn test_patch_pyproject_toml() {
+        let content = "[build-system]\nrequires = []\n\n[tool.poetry]\nversion = \"0.1.0\"\n";
+
+        let (_, patched) = patch(
+            content,
+            &target("pyproject.toml", &set_args()),
+            "1.2.0-rc.1",
+        )
+        .unwrap();
+
+        assert!(patched.ends_with("[tool.poetry]\nversion = \"1.2.0-rc.1\"\n"));
+    }
+
+    #[test]
+    fn test_patch_package_json() {
+        let content = r#"{
+  "name": "app",
+  "scripts": {"version": "echo \"}\""},
+  "version": "0.1.0",
+  "private": true
+}"#;
+
+        let (previous, patched) =
+            patch(content, &target("package.json", &set_args()), "1.2.0").unwrap();
+
+        assert_eq!(previous, "0.1.0");
+        assert!(patched.contains("\n  \"version\": \"1.2.0\",\n"));
+        assert!(patched.contains(r#""scripts": {"version": "echo \"}\""},"#));
+    }
+
+    #[test]
+    fn test_patch_json_path() {
+        let args = SetArgs {
+            json_path: Some("metadata.version".to_string()),
+            ..set_args()
+        };
+        let content = r#"{"version": 1, "metadata": {"name": "app", "version": "0.1.0"}}"#;
+
+        let (_, patched) = patch(content, &target("app.json", &args), "1.2.0").unwrap();
+
+        assert_eq!(
+            patched,
+            r#"{"version": 1, "metadata": {"name": "app", "version": "1.2.0"}}"#
+        );
+        assert!(patch(
+            r#"{"version": 1}"#,
+            &target("package.json", &set_args()),
+            "1.2.0"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_patch_csproj() {
+        let content = "<Project>\n  <PropertyGroup>\n    <Version>0.1.0</Version>\n  </PropertyGroup>\n</Project>\n";
+
+        let (_, patched) = patch(content, &target("App.csproj", &set_args()), "1.2.0").unwrap();
+
+        assert!(patched.contains("<Version>1.2.0</Version>"));
+    }
+
+    #[test]
+    fn test_patch_regex() {
+        let args = SetArgs {
+            regex: Some(r#"VERSION = "(?<version>[^"]*)""#.to_string()),
+            ..set_args()
+        };
+
+        let (_, patched) = patch(
+            "VERSION = \"0.1.0\"\n",
+            &target("version.py", &args),
+            "1.2.0",
+        )
+        .unwrap();
+
+        assert_eq!(patched, "VERSION = \"1.2.0\"\n");
+        assert!(Target::new(Path::new("version.py"), &set_args()).is_err());
+    }
+
+    #[test]
+    fn test_patch_anchor() {
+        let content = "FROM app:1.0.0\nARG APP_VERSION=1.1.0 # version-vine\nARG BASE=2.0.0\n";
+
+        let (previous, patched) = patch(
+            content,
+            &Target::Anchor("# version-vine".to_string()),
+            "1.2.0-rc.1",
+        )
+        .unwrap();
+
+        assert_eq!(previous, "1.1.0");
+        assert_eq!(
+            patched,
+            "FROM app:1.0.0\nARG APP_VERSION=1.2.0-rc.1 # version-vine\nARG BASE=2.0.0\n"
+        );
+        assert!(patch("1.0.0\n", &Target::Anchor("# v".to_string()), "1.2.0").is_err());
+    }
+
+    #[test]
+    fn test_set_version_stamp() {
+        let dir = std::env::temp_dir().join("version-vine-test-set-stamp");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let dockerfile = dir.join("Dockerfile");
+        fs::write(&dockerfile, "ARG APP_VERSION=1.1.0 # version-vine\n").unwrap();
+        let mut options = VersionOptions::default();
+        options.config.stamp = vec![StampConfig {
+            file: dockerfile.clone(),
+            regex: None,
+            anchor: Some("# version-vine".to_string()),
+            services: vec![],
+            value: Some("{{container_tag}}".to_string()),
+        }];
+        let version_info =
+            crate::version_calculator::version_info("release/1.2.0", "1.2.0-rc.1+1234567");
+
+        let result = set_version(&version_info, &options, &set_args()).unwrap();
+
+        assert_eq!(result["targets"][0]["previous"], "1.1.0");
+        assert_eq!(
+            fs::read_to_string(&dockerfile).unwrap(),
+            "ARG APP_VERSION=1.2.0-rc.1.1234567 # version-vine\n"
+        );
+        assert!(set_version(&version_info, &VersionOptions::default(), &set_args()).is_err());
+    }
+
+    #[test]
+    fn test_patch_compose() {
+        let content = "\
+# Deployed by the pipeline
+services:
+  api:
+    # Pinned by version-vine
+    image: ghcr.io/org/api:1.1.0 # latest release
+    environment:
+      image: keep
+  worker:
+    image: \"registry:5000/org/worker@sha256:abcd\"
+  db:
+    image: postgres:16
+volumes:
+  data:
+";
+
+        let (previous, patched) = patch(
+            content,
+            &Target::Compose(vec!["api".to_string(), "worker".to_string()]),
+            "1.2.0-rc.1.1234567",
+        )
+        .unwrap();
+
+        assert_eq!(previous, "1.1.0");
+        assert_eq!(
+            patched,
+            content
+                .replace("api:1.1.0", "api:1.2.0-rc.1.1234567")
+                .replace("worker@sha256:abcd", "worker:1.2.0-rc.1.1234567")
+        );
+        assert_eq!(
+            patch(content, &Target::Compose(vec!["data".to_string()]), "1.2.0")
+                .unwrap_err()
+                .to_string(),
+            "No image of the service 'data' found"
+        );
+        assert!(Target::new(Path::new("compose.yaml"), &set_args()).is_err());
+    }
+
+    fn checksums() -> Checksums {
+        Checksums {
+            files: BTreeMap::from([
+                ("app-1.2.0-arm64.tar.gz".to_string(), "aaaa".to_string()),
+                ("app-1.2.0-x86_64.tar.gz".to_string(), "bbbb".to_string()),
+            ]),
+            sha256: None,
+        }
+    }
+
+    #[test]
+    fn test_patch_homebrew_formula() {
+        let content = r#"class App < Formula
+  desc "App"
+  on_arm do
+    url "https://example.com/v1.1.0/app-1.1.0-arm64.tar.gz"
+    sha256 "0000"
+  end
+  on_intel do
+    url "https://example.com/v1.1.0/app-1.1.0-x86_64.tar.gz"
+    sha256 "1111"
+  end
+end
+"#;
+
+        let (previous, patched) = patch(content, &Target::Homebrew(checksums()), "1.2.0").unwrap();
+
+        assert_eq!(previous, "1.1.0");
+        assert!(patched.contains(
+            "url \"https://example.com/v1.2.0/app-1.2.0-arm64.tar.gz\"\n    sha256 \"aaaa\""
+        ));
+        assert!(patched.contains(
+            "url \"https://example.com/v1.2.0/app-1.2.0-x86_64.tar.gz\"\n    sha256 \"bbbb\""
+        ));
+        let single = Checksums {
+            files: BTreeMap::new(),
+            sha256: Some("cccc".to_string()),
+        };
+        assert!(patch(content, &Target::Homebrew(single), "1.2.0").is_err());
+    }
+
+    #[test]
+    fn test_patch_scoop_manifest() {
+        let content = r#"{
+    "version": "1.1.0",
+    "architecture": {
+        "64bit": {
+            "url": "https://example.com/v1.1.0/app-1.1.0-x86_64.tar.gz",
+            "hash": "1111"
+        },
+        "arm64": {
+            "url": "https://example.com/v1.1.0/app-1.1.0-arm64.tar.gz",
+            "hash": "0000"
+        }
+    },
+    "bin": "app.exe"
+}"#;
+
+        let (previous, patched) = patch(content, &Target::Scoop(checksums()), "1.2.0").unwrap();
+
+        assert_eq!(previous, "1.1.0");
+        assert_eq!(
+            serde_json::from_str::<Value>(&patched).unwrap(),
+            json!({
+                "version": "1.2.0",
+                "architecture": {
+                    "64bit": {
+                        "url": "https://example.com/v1.2.0/app-1.2.0-x86_64.tar.gz",
+                        "hash": "bbbb"
+                    },
+                    "arm64": {
+                        "url": "https://example.com/v1.2.0/app-1.2.0-arm64.tar.gz",
+                        "hash": "aaaa"
+                    }
+                },
+                "bin": "app.exe"
+            })
+        );
+    }
+
+    #[test]
+    fn test_patch_winget_manifest() {
+        let content = "PackageIdentifier: Org.App
+PackageVersion: 1.1.0
+Installers:
+  - Architecture: arm64
+    InstallerUrl: https://example.com/v1.1.0/app-1.1.0-arm64.tar.gz
+    InstallerSha256: 0000
+  - Architecture: x64
+    InstallerUrl: https://example.com/v1.1.0/app-1.1.0-x86_64.tar.gz
+    InstallerSha256: 1111
+ManifestVersion: 1.6.0
+";
+
+        let (_, patched) = patch(content, &Target::Winget(checksums()), "1.2.0").unwrap();
+
+        assert!(patched.contains("PackageVersion: 1.2.0\n"));
+        assert!(patched.contains(
+            "InstallerUrl: https://example.com/v1.2.0/app-1.2.0-x86_64.tar.gz\n    InstallerSha256: BBBB\n"
+        ));
+        assert!(patched.ends_with("ManifestVersion: 1.6.0\n"));
+        assert!(matches!(
+            target("Org.App.installer.yaml", &set_args()),
+            Target::Winget(_)
+        ));
+    }
+
+    #[test]
+    fn test_patch_chocolatey_install_script() {
+        let content = "$url = 'https://example.com/v1.1.0/app-1.1.0-arm64.tar.gz'
+$url64 = 'https://example.com/v1.1.0/app-1.1.0-x86_64.tar.gz'
+$packageArgs = @{
+  url           = $url
+  url64bit      = $url64
+  checksum      = '0000'
+  checksum64    = '1111'
+  checksumType  = 'sha256'
+}
+";
+
+        let (previous, patched) =
+            patch(content, &Target::Chocolatey(checksums()), "1.2.0").unwrap();
+
+        assert_eq!(previous, "1.1.0");
+        assert!(patched.contains("$url64 = 'https://example.com/v1.2.0/app-1.2.0-x86_64.tar.gz'"));
+        assert!(patched.contains("  checksum      = 'aaaa'\n  checksum64    = 'bbbb'\n"));
+    }
+}
@@ -0,0 +1,244 @@
+use anyhow::{Error, Result};
+use regex_lite::{escape, Regex};
+use serde_json::{json, Value};
+
+use crate::git_command::GitCommandTrait;
+use crate::models::{BranchClass, Regexes};
+use crate::next::{next_release, NextBump};
+use crate::{VersionCalculator, VersionInfo, VersionOptions};
+
+#[derive(clap::Args, Debug)]
+pub struct StartReleaseArgs {
+    /// Part of the version to bump
+    #[arg(long, value_enum, default_value = "auto")]
+    pub bump: NextBump,
+
+    /// Push the release branch, tracking it
+    #[arg(long, action)]
+    pub push: bool,
+
+    /// Remote the release branch is pushed to
+    #[arg(long, default_value = "origin")]
+    pub remote: String,
+
+    /// Only print the git commands, without running them
+    #[arg(long, action)]
+    pub dry_run: bool,
+}
+
+/// Name of the release branch of a version: the release branch pattern with its `version`
+/// capture group filled in, e.g. `release/myapp-1.3.0`. Fails for patterns the name can't be
+/// derived from, e.g. with alternations.
+pub fn release_branch(options: &VersionOptions, version: &str) -> Result<String> {
+    let regexes = Regexes::new(&options.app_name, &options.config)?;
+    let app_prefix = match &options.app_name {
+        Some(app_name) => format!("{}-", app_name),
+        None => String::new(),
+    };
+    let pattern = options
+        .config
+        .branches
+        .release
+        .replace("{app_prefix}", &escape(&app_prefix));
+    let unescape = |part: &str| {
+        Regex::new(r"\\(.)")
+            .unwrap()
+            .replace_all(part, "$1")
+            .into_owned()
+    };
+    let name = Regex::new(r"^\^?(.*)\(\?<version>[^()]*\)(.*?)\$?$")
+        .unwrap()
+        .captures(&pattern)
+        .map(|captures| {
+            format!(
+                "{}{}{}",
+                unescape(&captures[1]),
+                version,
+                unescape(&captures[2])
+            )
+        });
+    match name {
+        Some(name)
+            if regexes
+                .rc_branches
+                .captures(&name)
+                .and_then(|captures| captures.name("version"))
+                .is_some_and(|captured| captured.as_str() == version) =>
+        {
+            Ok(name)
+        }
+        _ => Err(Error::msg(format!(
+            "Cannot derive the release branch name of {} from the release branch pattern '{}'",
+            version, options.config.branches.release
+        ))),
+    }
+}
+
+/// Starts a release from the develop branch: the "start release" step of git flow. Creates the
+/// release branch of the next release version at `HEAD` (or the `--ref`), switches to it and
+/// optionally pushes it. Returns the release candidate version of the new branch.
+pub fn start_release(
+    git_command: &impl GitCommandTrait,
+    options: &VersionOptions,
+    version_info: &VersionInfo,
+    args: &StartReleaseArgs,
+) -> Result<Value> {
+    if version_info.branch_class != BranchClass::Develop {
+        return Err(Error::msg(format!(
+            "Releases are started from the develop branch, '{}' is a {} branch",
+            version_info.git_branch,
+            json!(version_info.branch_class)
+                .as_str()
+                .unwrap_or_default()
+        )));
+    }
+    let release = next_release(git_command, options, args.bump)?;
+    let version = release["version"].as_str().unwrap_or_default();
+    let branch = release_branch(options, version)?;
+    let branch_ref = format!("refs/heads/{}", branch);
+    if git_command
+        .run(vec!["rev-parse", "-q", "--verify", &branch_ref])
+        .is_ok()
+    {
+        return Err(Error::msg(format!(
+            "Release branch '{}' already exists",
+            branch
+        )));
+    }
+
+    let mut switch_command = vec!["switch", "-c", &branch];
+    if let Some(git_ref) = &options.git_ref {
+        switch_command.push(git_ref);
+    }
+    let mut commands = vec![switch_command];
+    if args.push {
+        commands.push(vec!["push", "-u", &args.remote, &branch]);
+    }
+    let mut rc_version = None;
+    if !args.dry_run {
+        for command in &commands {
+            git_command.run(command.clone())?;
+        }
+        let branch_options = VersionOptions {
+            git_ref: Some(branch.clone()),
+            ..options.clone()
+        };
+        let branch_info = VersionCalculator::new(git_command, branch_options)?.calculate()?;
+        rc_version = Some(branch_info.version.to_string());
+    }
+    Ok(json!({
+        "branch": branch,
+        "version": version,
+        "bump": release["bump"],
+        "latest_tag": release["latest_tag"],
+        "rc_version": rc_version,
+        "created": !args.dry_run,
+        "pushed": args.push && !args.dry_run,
+        "commands": commands
+            .iter()
+            .map(|command| format!("git {}", command.join(" ")))
+            .collect::<Vec<_>>(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::git_command::MockGitCommandTrait;
+
+    use super::*;
+
+    fn develop_info() -> VersionInfo {
+        crate::version_calculator::version_info("develop", "1.5.0-alpha.3")
+    }
+
+    #[test]
+    fn test_release_branch() {
+        let options = VersionOptions {
+            app_name: Some("myapp".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            release_branch(&options, "1.3.0").unwrap(),
+            "release/myapp-1.3.0"
+        );
+        assert_eq!(
+            release_branch(&VersionOptions::default(), "1.3.0").unwrap(),
+            "release/1.3.0"
+        );
+
+        let mut config = Config::default();
+        config.branches.release = r"^releases\/v(?<version>\d+\.\d+\.\d+)$".to_string();
+        let options = VersionOptions {
+            config,
+            ..Default::default()
+        };
+        assert_eq!(
+            release_branch(&options, "1.3.0").unwrap(),
+            "releases/v1.3.0"
+        );
+
+        let mut config = Config::default();
+        config.branches.release = r"^(release|rc)\/(?<version>.+)".to_string();
+        let options = VersionOptions {
+            config,
+            ..Default::default()
+        };
+        assert!(release_branch(&options, "1.3.0").is_err());
+    }
+
+    #[test]
+    fn test_start_release() {
+        let mut git_command = MockGitCommandTrait::new();
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "describe --abbrev=0 --tags")
+            .returning(|_| Ok("1.4.0".to_string()));
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "rev-parse -q --verify refs/heads/release/1.5.0")
+            .returning(|_| Err(anyhow::Error::msg("")));
+        let args = StartReleaseArgs {
+            bump: NextBump::Minor,
+            push: true,
+            remote: "origin".to_string(),
+            dry_run: true,
+        };
+
+        let result = start_release(
+            &git_command,
+            &VersionOptions::default(),
+            &develop_info(),
+            &args,
+        )
+        .unwrap();
+
+        assert_eq!(
+            result,
+            json!({
+                "branch": "release/1.5.0",
+                "version": "1.5.0",
+                "bump": "minor",
+                "latest_tag": "1.4.0",
+                "rc_version": null,
+                "created": false,
+                "pushed": false,
+                "commands": [
+                    "git switch -c release/1.5.0",
+                    "git push -u origin release/1.5.0",
+                ],
+            })
+        );
+
+        let mut feature = develop_info();
+        feature.git_branch = "feature/login".to_string();
+        feature.branch_class = BranchClass::Feature;
+        let err =
+            start_release(&git_command, &VersionOptions::default(), &feature, &args).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Releases are started from the develop branch, 'feature/login' is a feature branch"
+        );
+    }
+}
@@ -0,0 +1,537 @@
+use anyhow::{Error, Result};
+use semver::Version;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+
+use crate::branch_resolver::resolve_branch;
+use crate::git_command::GitCommandTrait;
+use crate::models::{BranchClass, Regexes};
+use crate::version_calculator::{get_default_branch, parse_tag_version};
+use crate::{DateSource, VersionOptions};
+
+const SECONDS_PER_DAY: f64 = 24.0 * 60.0 * 60.0;
+
+/// Release cadence of an application, derived from its tags
+#[derive(Serialize, Debug, PartialEq)]
+pub struct AppStats {
+    /// `None` for tags without an app name prefix
+    pub app: Option<String>,
+    /// Final (non pre-release) versions
+    pub releases: usize,
+    pub first_release: Option<String>,
+    pub last_release: Option<String>,
+    /// Releases per `YYYY-MM`
+    pub releases_per_month: BTreeMap<String, usize>,
+    /// Average days from the first release candidate of a version to its final release
+    pub average_rc_to_release_days: Option<f64>,
+    /// Releases of a patch version, as produced by hotfix branches
+    pub hotfixes: usize,
+    pub hotfix_ratio: Option<f64>,
+    /// Lead time for changes: days from the first commit of a release to its tag
+    pub average_lead_time_days: Option<f64>,
+    pub median_lead_time_days: Option<f64>,
+    /// Deployment frequency, from the intervals between the releases
+    pub releases_per_week: Option<f64>,
+}
+
+/// Stabilization of a release branch since it was cut, to judge the readiness of the release
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Stabilization {
+    pub branch: String,
+    pub version: String,
+    /// Branch the release branch was cut from, the develop branch or else the default branch
+    pub cut_from: String,
+    /// The merge-base with `cut_from`
+    pub cut_commit: String,
+    pub cut_at: Option<String>,
+    pub days_since_cut: Option<f64>,
+    pub commits_since_cut: u32,
+    /// Release candidate tags of the version, e.g. `1.2.0-rc.3`
+    pub release_candidates: usize,
+    pub latest_release_candidate: Option<String>,
+    pub commits_since_release_candidate: Option<u32>,
+}
+
+struct Tag {
+    name: String,
+    app: Option<String>,
+    version: Version,
+    timestamp: u64,
+    /// `YYYY-MM-DD`
+    date: String,
+}
+
+/// Release statistics per application. Only the `--app-name` application when it's provided.
+pub fn release_stats(
+    git_command: &impl GitCommandTrait,
+    options: &VersionOptions,
+) -> Result<Vec<AppStats>> {
+    let regexes = Regexes::new(&options.app_name, &options.config)?;
+    // Lightweight tags have the dates of the commit, annotated tags of the tagged commit when
+    // dereferenced (`*`)
+    let (date_field, dereference) = options.date_source().ref_field();
+    let mut format = format!(
+        "--format=%(refname:lstrip=2)%09%({0}:unix)%09%({0}:short)",
+        date_field
+    );
+    if dereference {
+        format.push_str(&format!("%09%(*{0}:unix)%09%(*{0}:short)", date_field));
+    }
+    let tags = git_command.run(vec!["for-each-ref", &format, "refs/tags"])?;
+    let mut apps: BTreeMap<Option<String>, Vec<Tag>> = BTreeMap::new();
+    for line in tags.lines() {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let (name, timestamp, date) = match fields.as_slice() {
+            [name, _, _, timestamp, date] if !timestamp.is_empty() => (*name, *timestamp, *date),
+            [name, timestamp, date, ..] => (*name, *timestamp, *date),
+            _ => continue,
+        };
+        let (app, version) = match parse_tag(&regexes, &options.app_name, name) {
+            Some(parsed) => parsed,
+            None => continue,
+        };
+        apps.entry(app.clone()).or_default().push(Tag {
+            name: name.to_string(),
+            app,
+            version,
+            timestamp: timestamp.parse().unwrap_or_default(),
+            date: date.to_string(),
+        });
+    }
+    let mut stats = vec![];
+    for mut tags in apps.into_values() {
+        tags.sort_by_key(|tag| tag.timestamp);
+        let lead_times = lead_times(git_command, &tags, options.date_source())?;
+        stats.push(app_stats(
+            tags,
+            &options.prerelease_labels().release,
+            &lead_times,
+        ));
+    }
+    Ok(stats)
+}
+
+/// Stabilization metrics of the current release branch: the commits since the branch was cut from
+/// develop, the release candidates tagged since and the time since the cut. The cut is the
+/// merge-base with develop, so it moves when the release branch is merged back.
+pub fn release_stabilization(
+    git_command: &impl GitCommandTrait,
+    options: &VersionOptions,
+    now: u64,
+) -> Result<Stabilization> {
+    let branch = resolve_branch(git_command, &options.branch)?;
+    let regexes = Regexes::new(&options.app_name, &options.config)?;
+    let class = regexes.branch_class(&branch);
+    let version = match regexes.branch_version(&branch) {
+        Some(version) if class == BranchClass::Release => Version::parse(version)?,
+        _ => {
+            return Err(Error::msg(format!(
+                "Stabilization metrics are only available on release branches, '{}' is a {} branch",
+                branch,
+                json!(class).as_str().unwrap_or_default()
+            )))
+        }
+    };
+    let branches = git_command.run(vec![
+        "for-each-ref",
+        "--format=%(refname:lstrip=2)",
+        "refs/heads",
+    ])?;
+    let cut_from = branches
+        .lines()
+        .find(|name| regexes.develop_branches.is_match(name))
+        .map(str::to_string)
+        .unwrap_or_else(|| get_default_branch(git_command, options));
+    let cut_commit = git_command
+        .run(vec!["merge-base", "HEAD", &cut_from])
+        .map_err(|err| {
+            Error::msg(format!(
+                "Cannot find where '{}' was cut from '{}': {}",
+                branch, cut_from, err
+            ))
+        })?;
+    let date = options.date_source().log_placeholder();
+    let cut_date = git_command
+        .run(vec![
+            "log",
+            "-1",
+            &format!("--format=%{0}t%n%{0}I", date),
+            &cut_commit,
+        ])
+        .ok();
+    let (cut_timestamp, cut_at) = match cut_date.as_ref().and_then(|d| d.split_once('\n')) {
+        Some((timestamp, date)) => (timestamp.parse::<u64>().ok(), Some(date.to_string())),
+        None => (None, None),
+    };
+    let commits_since = |commit: &str| -> Result<u32> {
+        let range = format!("{}..HEAD", commit);
+        Ok(git_command
+            .run(vec!["rev-list", "--count", &range])?
+            .parse::<u32>()?)
+    };
+    let label = options.prerelease_labels().release;
+    let pattern = format!(
+        "refs/tags/{}",
+        regexes.tag_name(&format!("{}-{}*", version, label))
+    );
+    let tags = git_command.run(vec![
+        "for-each-ref",
+        "--format=%(refname:lstrip=2)",
+        &pattern,
+    ])?;
+    let mut release_candidates: Vec<(Version, &str)> = tags
+        .lines()
+        .filter_map(|tag| Some((parse_tag_version(&regexes, tag).ok()?, tag)))
+        .filter(|(rc, _)| rc.pre.starts_with(&label))
+        .collect();
+    release_candidates.sort();
+    let latest_release_candidate = release_candidates.last().map(|(_, tag)| tag.to_string());
+    Ok(Stabilization {
+        commits_since_cut: commits_since(&cut_commit)?,
+        commits_since_release_candidate: match &latest_release_candidate {
+            Some(tag) => Some(commits_since(tag)?),
+            None => None,
+        },
+        branch,
+        version: version.to_string(),
+        cut_from,
+        cut_commit,
+        cut_at,
+        days_since_cut: cut_timestamp
+            .map(|timestamp| now.saturating_sub(timestamp) as f64 / SECONDS_PER_DAY),
+        release_candidates: release_candidates.len(),
+        latest_release_candidate,
+    })
+}
+
+/// Days from the first commit of each release, since the previous release, to its tag
+fn lead_times(
+    git_command: &impl GitCommandTrait,
+    tags: &[Tag],
+    date_source: DateSource,
+) -> Result<Vec<f64>> {
+    let format = format!("--format=%{}t", date_source.log_placeholder());
+    let mut lead_times = vec![];
+    let mut previous: Option<&Tag> = None;
+    for release in tags.iter().filter(|tag| tag.version.pre.is_empty()) {
+        let range = match previous {
+            Some(previous) => format!("refs/tags/{}..refs/tags/{}", previous.name, release.name),
+            None => format!("refs/tags/{}", release.name),
+        };
+        let timestamps = git_command.run(vec!["log", &format, &range])?;
+        if let Some(first_commit) = timestamps
+            .lines()
+            .filter_map(|timestamp| timestamp.trim().parse::<u64>().ok())
+            .min()
+        {
+            lead_times
+                .push(release.timestamp.saturating_sub(first_commit) as f64 / SECONDS_PER_DAY);
+        }
+        previous = Some(release);
+    }
+    Ok(lead_times)
+}
+
+/// App name and version of a tag. Without `--app-name`, the app name is the prefix before the
+/// version, e.g. `myapp` for `myapp-1.0.0`, or the namespace of `{app_name}` tag formats, e.g.
+/// `myapp` for `apps/myapp/1.0.0`.
+pub(crate) fn parse_tag(
+    regexes: &Regexes,
+    app_name: &Option<String>,
+    tag: &str,
+) -> Option<(Option<String>, Version)> {
+    if let Some(caps) = regexes
+        .app_tag
+        .as_ref()
+        .and_then(|app_tag| app_tag.captures(tag))
+    {
+        if let Ok(version) = Version::parse(&caps["version"]) {
+            return Some((Some(caps["app"].to_string()), version));
+        }
+    }
+    let version = regexes.tag.captures(tag)?.name("version")?.as_str();
+    if let Ok(version) = Version::parse(version) {
+        return Some((app_name.clone(), version));
+    }
+    if app_name.is_some() {
+        return None;
+    }
+    tag.match_indices('-').find_map(|(index, _)| {
+        let version = Version::parse(&tag[index + 1..]).ok()?;
+        Some((Some(tag[..index].to_string()), version))
+    })
+}
+
+/// Statistics of the tags of an app, sorted by date
+fn app_stats(tags: Vec<Tag>, rc_label: &str, lead_times: &[f64]) -> AppStats {
+    let releases: Vec<&Tag> = tags
+        .iter()
+        .filter(|tag| tag.version.pre.is_empty())
+        .collect();
+    let mut releases_per_month = BTreeMap::new();
+    for release in &releases {
+        *releases_per_month
+            .entry(release.date.chars().take(7).collect())
+            .or_insert(0) += 1;
+    }
+    let rc_to_release_days: Vec<f64> = releases
+        .iter()
+        .filter_map(|release| {
+            let first_rc = tags.iter().find(|tag| {
+                tag.version.major == release.version.major
+                    && tag.version.minor == release.version.minor
+                    && tag.version.patch == release.version.patch
+                    && tag.version.pre.starts_with(rc_label)
+            })?;
+            Some(release.timestamp.saturating_sub(first_rc.timestamp) as f64 / SECONDS_PER_DAY)
+        })
+        .collect();
+    let hotfixes = releases
+        .iter()
+        .filter(|release| release.version.patch > 0)
+        .count();
+    let releases_per_week = match (releases.first(), releases.last()) {
+        (Some(first), Some(last)) if last.timestamp > first.timestamp => Some(
+            (releases.len() - 1) as f64 * 7.0 * SECONDS_PER_DAY
+                / (last.timestamp - first.timestamp) as f64,
+        ),
+        _ => None,
+    };
+    AppStats {
+        app: tags.first().and_then(|tag| tag.app.clone()),
+        releases: releases.len(),
+        first_release: releases.first().map(|release| release.date.clone()),
+        last_release: releases.last().map(|release| release.date.clone()),
+        releases_per_month,
+        average_rc_to_release_days: average(&rc_to_release_days),
+        hotfixes,
+        hotfix_ratio: match releases.len() {
+            0 => None,
+            count => Some(hotfixes as f64 / count as f64),
+        },
+        average_lead_time_days: average(lead_times),
+        median_lead_time_days: median(lead_times),
+        releases_per_week,
+    }
+}
+
+fn average(values: &[f64]) -> Option<f64> {
+    match values.len() {
+        0 => None,
+        count => Some(values.iter().sum::<f64>() / count as f64),
+    }
+}
+
+fn median(values: &[f64]) -> Option<f64> {
+    let mut values = values.to_vec();
+    values.sort_by(f64::total_cmp);
+    match values.len() {
+        0 => None,
+        count if count % 2 == 0 => Some((values[count / 2 - 1] + values[count / 2]) / 2.0),
+        count => Some(values[count / 2]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::git_command::MockGitCommandTrait;
+
+    use super::*;
+
+    const DAY: u64 = 24 * 60 * 60;
+
+    fn mock_git(git_command: &mut MockGitCommandTrait) {
+        let tags = [
+            ("1.0.0-rc.1", 0, "2024-01-01"),
+            ("1.0.0", 2 * DAY, "2024-01-03"),
+            ("1.0.1", 10 * DAY, "2024-01-11"),
+            ("1.1.0-rc.1", 40 * DAY, "2024-02-10"),
+            ("1.1.0-rc.2", 41 * DAY, "2024-02-11"),
+            ("1.1.0", 44 * DAY, "2024-02-14"),
+            ("myapp-0.1.0", 5 * DAY, "2024-01-06"),
+            ("latest", 5 * DAY, "2024-01-06"),
+        ]
+        .iter()
+        .map(|(tag, timestamp, date)| format!("{}\t{}\t{}", tag, timestamp, date))
+        .collect::<Vec<_>>()
+        .join("\n");
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "for-each-ref")
+            .returning(move |_| Ok(tags.clone()));
+        // The first commit of each release is a day before its release candidate or tag
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "log")
+            .returning(|args| {
+                let first_commit = match args[2] {
+                    "refs/tags/1.0.0" => 0,
+                    "refs/tags/1.0.0..refs/tags/1.0.1" => 9 * DAY,
+                    "refs/tags/1.0.1..refs/tags/1.1.0" => 38 * DAY,
+                    _ => 4 * DAY,
+                };
+                Ok(format!("{}\n{}", first_commit + DAY, first_commit))
+            });
+    }
+
+    #[test]
+    fn test_parse_tag_namespaced() {
+        let config =
+            crate::config::Config::parse("[tag]\nformat = 'apps/{app_name}/{version}'").unwrap();
+        let regexes = Regexes::new(&None, &config).unwrap();
+
+        assert_eq!(
+            parse_tag(&regexes, &None, "apps/app-ui/1.0.0"),
+            Some((Some("app-ui".to_string()), Version::new(1, 0, 0)))
+        );
+        assert_eq!(
+            parse_tag(&regexes, &None, "apps/1.1.0"),
+            Some((None, Version::new(1, 1, 0)))
+        );
+        let regexes = Regexes::new(&Some("app".to_string()), &config).unwrap();
+        assert_eq!(
+            parse_tag(&regexes, &Some("app".to_string()), "apps/app-ui/1.0.0"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_release_stats() {
+        let mut git_command = MockGitCommandTrait::new();
+        mock_git(&mut git_command);
+
+        let stats = release_stats(&git_command, &VersionOptions::default()).unwrap();
+
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].app, None);
+        assert_eq!(stats[0].releases, 3);
+        assert_eq!(stats[0].first_release, Some("2024-01-03".to_string()));
+        assert_eq!(
+            stats[0].releases_per_month,
+            BTreeMap::from([("2024-01".to_string(), 2), ("2024-02".to_string(), 1)])
+        );
+        assert_eq!(stats[0].average_rc_to_release_days, Some(3.0));
+        assert_eq!(stats[0].hotfixes, 1);
+        assert_eq!(stats[0].average_lead_time_days, Some(3.0));
+        assert_eq!(stats[0].median_lead_time_days, Some(2.0));
+        assert_eq!(stats[0].releases_per_week, Some(2.0 * 7.0 / 42.0));
+        assert_eq!(stats[1].app, Some("myapp".to_string()));
+        assert_eq!(stats[1].releases, 1);
+        assert_eq!(stats[1].average_rc_to_release_days, None);
+        assert_eq!(stats[1].average_lead_time_days, Some(1.0));
+        assert_eq!(stats[1].releases_per_week, None);
+    }
+
+    #[test]
+    fn test_release_stabilization() {
+        let mut git_command = MockGitCommandTrait::new();
+        let responses = [
+            ("branch --show-current", "release/1.1.0"),
+            (
+                "for-each-ref --format=%(refname:lstrip=2) refs/heads",
+                "develop\nmain\nrelease/1.1.0",
+            ),
+            ("merge-base HEAD develop", "abc1234"),
+            (
+                "log -1 --format=%ct%n%cI abc1234",
+                "0\n2024-02-01T12:00:00+00:00",
+            ),
+            ("rev-list --count abc1234..HEAD", "7"),
+            (
+                "for-each-ref --format=%(refname:lstrip=2) refs/tags/1.1.0-rc*",
+                "1.1.0-rc.10\n1.1.0-rc.2\n1.1.0-rc.1",
+            ),
+            ("rev-list --count 1.1.0-rc.10..HEAD", "2"),
+        ];
+        for (command, output) in responses {
+            git_command
+                .expect_run()
+                .withf(move |args| args.join(" ") == command)
+                .returning(move |_| Ok(output.to_string()));
+        }
+
+        let stabilization =
+            release_stabilization(&git_command, &VersionOptions::default(), 3 * DAY).unwrap();
+
+        assert_eq!(
+            stabilization,
+            Stabilization {
+                branch: "release/1.1.0".to_string(),
+                version: "1.1.0".to_string(),
+                cut_from: "develop".to_string(),
+                cut_commit: "abc1234".to_string(),
+                cut_at: Some("2024-02-01T12:00:00+00:00".to_string()),
+                days_since_cut: Some(3.0),
+                commits_since_cut: 7,
+                release_candidates: 3,
+                latest_release_candidate: Some("1.1.0-rc.10".to_string()),
+                commits_since_release_candidate: Some(2),
+            }
+        );
+    }
+
+    #[test]
+    fn test_release_stabilization_feature_branch() {
+        let mut git_command = MockGitCommandTrait::new();
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "branch --show-current")
+            .returning(|_| Ok("feature/login".to_string()));
+
+        let result = release_stabilization(&git_command, &VersionOptions::default(), 0);
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Stabilization metrics are only available on release branches, 'feature/login' is a feature branch"
+        );
+    }
+
+    #[test]
+    fn test_release_stats_date_source() {
+        let mut git_command = MockGitCommandTrait::new();
+        git_command
+            .expect_run()
+            .withf(|args| {
+                args[1]
+                    == "--format=%(refname:lstrip=2)%09%(authordate:unix)%09%(authordate:short)\
+                        %09%(*authordate:unix)%09%(*authordate:short)"
+            })
+            .returning(|_| {
+                Ok(format!(
+                    "1.0.0\t{}\t2024-01-02\t\t\n1.1.0\t\t\t{}\t2024-01-09",
+                    DAY,
+                    8 * DAY
+                ))
+            });
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "log" && args[1] == "--format=%at")
+            .returning(|_| Ok("0".to_string()));
+        let options = VersionOptions {
+            date_source: Some(DateSource::Author),
+            ..Default::default()
+        };
+
+        let stats = release_stats(&git_command, &options).unwrap();
+
+        assert_eq!(stats[0].last_release, Some("2024-01-09".to_string()));
+        assert_eq!(stats[0].releases_per_week, Some(1.0));
+    }
+
+    #[test]
+    fn test_release_stats_app_name() {
+        let mut git_command = MockGitCommandTrait::new();
+        mock_git(&mut git_command);
+        let options = VersionOptions {
+            app_name: Some("myapp".to_string()),
+            ..Default::default()
+        };
+
+        let stats = release_stats(&git_command, &options).unwrap();
+
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].app, Some("myapp".to_string()));
+        assert_eq!(stats[0].hotfix_ratio, Some(0.0));
+    }
+}
@@ -0,0 +1,257 @@
+use anyhow::{Error, Result};
+use regex_lite::Regex;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::explain::explain;
+use crate::git_command::GitCommandTrait;
+use crate::output::to_json_string;
+use crate::trace::Explain;
+use crate::{VersionCalculator, VersionOptions};
+
+const SUPPORT_BUNDLE_FILE: &str = "version-vine-support.tar";
+
+/// Git queries of the state summary. Only names and counts, no file contents or paths.
+const GIT_STATE: [&[&str]; 8] = [
+    &["--version"],
+    &["branch", "--show-current"],
+    &["rev-parse", "--short", "HEAD"],
+    &["rev-parse", "--is-shallow-repository"],
+    &["status", "--porcelain", "--untracked-files=no"],
+    &["remote", "-v"],
+    &[
+        "for-each-ref",
+        "--count=20",
+        "--sort=-creatordate",
+        "--format=%(refname:short)",
+        "refs/tags",
+    ],
+    &[
+        "for-each-ref",
+        "--count=50",
+        "--format=%(refname:short)",
+        "refs/heads",
+        "refs/remotes",
+    ],
+];
+
+#[derive(clap::Args, Debug)]
+pub struct SupportBundleArgs {
+    /// Path of the archive
+    #[arg(long, default_value = SUPPORT_BUNDLE_FILE)]
+    pub output: PathBuf,
+}
+
+/// A file of the support bundle, redacted
+#[derive(Serialize, Debug, PartialEq)]
+pub struct BundleFile {
+    pub name: String,
+    #[serde(skip)]
+    pub content: String,
+}
+
+/// Collects the diagnostics of a version calculation for bug reports: the decisions of the
+/// calculation and its error, the configuration file, a summary of the git state and the version
+/// output. Remote URLs and email addresses are redacted.
+pub fn support_bundle(
+    git_command: &impl GitCommandTrait,
+    options: &VersionOptions,
+    config_path: Option<&Path>,
+    args: &[String],
+) -> Result<Vec<BundleFile>> {
+    let mut traced = options.clone();
+    traced.explain = Some(Explain::Field);
+    let version =
+        VersionCalculator::new(git_command, traced).and_then(|calculator| calculator.calculate());
+
+    let mut log = vec![
+        format!("version-vine {}", env!("CARGO_PKG_VERSION")),
+        format!("os: {} {}", std::env::consts::OS, std::env::consts::ARCH),
+        format!("args: {}", args.join(" ")),
+    ];
+    match &version {
+        Ok(version_info) => log.extend(
+            version_info
+                .trace
+                .iter()
+                .map(|step| format!("{}: {}", step.step, step.detail)),
+        ),
+        Err(err) => log.push(format!("error: {:#}", err)),
+    }
+    match explain(git_command, options) {
+        Ok(classification) => log.push(format!(
+            "classification: {}",
+            to_json_string(&classification, true)?
+        )),
+        Err(err) => log.push(format!("classification error: {:#}", err)),
+    }
+
+    let config = match config_path {
+        Some(path) => fs::read_to_string(path)
+            .unwrap_or_else(|err| format!("# Cannot read '{}': {}\n", path.display(), err)),
+        None => "# No configuration file, the defaults are used\n".to_string(),
+    };
+
+    let git_state: Vec<String> = GIT_STATE
+        .iter()
+        .map(|args| {
+            let output = match git_command.run(args.to_vec()) {
+                // The changed files are counted, not listed
+                Ok(output) if args[0] == "status" => {
+                    format!("{} changed file(s)", output.lines().count())
+                }
+                Ok(output) => output,
+                Err(err) => format!("error: {:#}", err),
+            };
+            format!("$ git {}\n{}\n", args.join(" "), output.trim_end())
+        })
+        .collect();
+
+    let output = match &version {
+        Ok(version_info) => to_json_string(&version_info.to_json(), false)?,
+        Err(err) => to_json_string(&serde_json::json!({ "error": format!("{:#}", err) }), false)?,
+    };
+
+    Ok([
+        ("diagnostics.log", log.join("\n")),
+        ("config.toml", config),
+        ("git-state.txt", git_state.join("\n")),
+        ("output.json", output),
+    ]
+    .into_iter()
+    .map(|(name, content)| BundleFile {
+        name: name.to_string(),
+        content: redact(&format!("{}\n", content.trim_end())),
+    })
+    .collect())
+}
+
+/// Replaces remote URLs (also `git@host:org/repo.git`) and email addresses
+pub fn redact(text: &str) -> String {
+    let url =
+        Regex::new(r"[a-zA-Z][a-zA-Z0-9+.-]*://[^\s'`]+|\b[\w.-]+@[\w.-]+:[\w./~-]+").unwrap();
+    let email = Regex::new(r"[\w.+-]+@[\w-]+(\.[\w-]+)*").unwrap();
+    let text = url.replace_all(text, "<redacted-url>");
+    email.replace_all(&text, "<redacted-email>").into_owned()
+}
+
+/// Writes the files into an uncompressed tar archive, e.g. to attach to an issue
+pub fn write_bundle(path: &Path, files: &[BundleFile], mtime: u64) -> Result<()> {
+    fs::write(path, tar(files, mtime)?)
+        .map_err(|err| Error::msg(format!("Cannot write '{}': {}", path.display(), err)))
+}
+
+/// Archive in the POSIX ustar format, with regular files only
+fn tar(files: &[BundleFile], mtime: u64) -> Result<Vec<u8>> {
+    let mut archive = vec![];
+    for file in files {
+        if file.name.len() > 100 {
+            return Err(Error::msg(format!("File name '{}' is too long", file.name)));
+        }
+        let mut header = [0u8; 512];
+        let mut field = |offset: usize, value: &[u8]| {
+            header[offset..offset + value.len()].copy_from_slice(value)
+        };
+        field(0, file.name.as_bytes());
+        field(100, b"0000644\0");
+        field(108, b"0000000\0");
+        field(116, b"0000000\0");
+        field(124, format!("{:011o}\0", file.content.len()).as_bytes());
+        field(136, format!("{:011o}\0", mtime).as_bytes());
+        field(156, b"0");
+        field(257, b"ustar\x0000");
+        // The checksum is computed with its own field as spaces
+        field(148, b"        ");
+        let checksum: u32 = header.iter().map(|byte| *byte as u32).sum();
+        header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+        archive.extend_from_slice(&header);
+        archive.extend_from_slice(file.content.as_bytes());
+        archive.resize(archive.len().div_ceil(512) * 512, 0);
+    }
+    // End of the archive
+    archive.resize(archive.len() + 1024, 0);
+    Ok(archive)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::git_command::MockGitCommandTrait;
+
+    use super::*;
+
+    #[test]
+    fn test_redact() {
+        assert_eq!(
+            redact("origin\tgit@github.com:org/repo.git (fetch)\nupstream\thttps://token@gitlab.com/org/repo.git (push)"),
+            "origin\t<redacted-url> (fetch)\nupstream\t<redacted-url> (push)"
+        );
+        assert_eq!(
+            redact("\"committer\": \"Jane Doe <jane.doe@example.com>\""),
+            "\"committer\": \"Jane Doe <<redacted-email>>\""
+        );
+        assert_eq!(redact("feature/login 1.2.0"), "feature/login 1.2.0");
+    }
+
+    #[test]
+    fn test_support_bundle() {
+        let mut git_command = MockGitCommandTrait::new();
+        let commands: Vec<(&str, Result<&str, &str>)> = vec![
+            ("branch --show-current", Ok("main")),
+            ("rev-parse --short HEAD", Ok("1234567")),
+            (
+                "status --porcelain --untracked-files=no",
+                Ok(" M src/secret.rs\n M README.md"),
+            ),
+            (
+                "remote -v",
+                Ok("origin\tgit@github.com:org/repo.git (fetch)"),
+            ),
+        ];
+        for (command, output) in commands {
+            git_command
+                .expect_run()
+                .withf(move |args| args.join(" ") == command)
+                .returning(move |_| output.map(String::from).map_err(Error::msg));
+        }
+        git_command
+            .expect_run()
+            .returning(|_| Err(Error::msg("fatal: not a git repository")));
+
+        let files = support_bundle(&git_command, &VersionOptions::default(), None, &[]).unwrap();
+
+        let names: Vec<&str> = files.iter().map(|file| file.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "diagnostics.log",
+                "config.toml",
+                "git-state.txt",
+                "output.json"
+            ]
+        );
+        // Main without a tag
+        assert!(files[0].content.contains("error: "));
+        assert!(files[2]
+            .content
+            .contains("$ git status --porcelain --untracked-files=no\n2 changed file(s)"));
+        assert!(!files[2].content.contains("secret.rs"));
+        assert!(files[2].content.contains("origin\t<redacted-url> (fetch)"));
+        assert!(files[3].content.starts_with("{\n  \"error\""));
+
+        let archive = tar(&files, 0).unwrap();
+        assert_eq!(archive.len() % 512, 0);
+        assert_eq!(&archive[0..15], b"diagnostics.log");
+        assert_eq!(&archive[257..265], b"ustar\x0000");
+        let checksum = archive[..512]
+            .iter()
+            .enumerate()
+            .map(|(i, byte)| match i {
+                148..=155 => b' ' as u32,
+                _ => *byte as u32,
+            })
+            .sum::<u32>();
+        let recorded = std::str::from_utf8(&archive[148..154]).unwrap();
+        assert_eq!(u32::from_str_radix(recorded, 8).unwrap(), checksum);
+    }
+}
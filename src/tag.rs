@@ -0,0 +1,354 @@
+use anyhow::{Error, Result};
+use semver::Prerelease;
+use serde_json::{json, Value};
+
+use crate::git_command::GitCommandTrait;
+use crate::models::Regexes;
+use crate::{VersionInfo, VersionOptions};
+
+#[derive(clap::Args, Debug)]
+pub struct TagArgs {
+    /// Create an annotated tag
+    #[arg(short, long, action)]
+    pub annotate: bool,
+
+    /// Message of the annotated tag. Implies `--annotate`. Defaults to `Release <tag>`.
+    #[arg(short, long)]
+    pub message: Option<String>,
+
+    /// Create a GPG signed tag. Implies `--annotate`.
+    #[arg(short, long, action)]
+    pub sign: bool,
+
+    /// Tag the release version, without pre-release. E.g. `2.0.0` on `release/2.0.0`, instead of `2.0.0-rc.3`.
+    #[arg(long, action)]
+    pub release: bool,
+
+    /// Push the tag
+    #[arg(long, action)]
+    pub push: bool,
+
+    /// Remote the tag is pushed to
+    #[arg(long, default_value = "origin")]
+    pub remote: String,
+
+    /// Only print the git commands, without running them
+    #[arg(long, action)]
+    pub dry_run: bool,
+}
+
+/// Tags `HEAD` (or the `--ref`) with the computed version (without build metadata), following the
+/// tag format. When the tag already points at the commit, it's kept, so re-runs only push.
+pub fn create_tag(
+    git_command: &impl GitCommandTrait,
+    options: &VersionOptions,
+    version_info: &VersionInfo,
+    args: &TagArgs,
+) -> Result<Value> {
+    version_info.require_commits("tag")?;
+    let regexes = Regexes::new(&options.app_name, &options.config)?;
+    let mut version = version_info.tag_version();
+    if args.release {
+        version.pre = Prerelease::EMPTY;
+    }
+    let tag = regexes.tag_name(&version.to_string());
+    let message = args.message.clone().unwrap_or(format!("Release {}", tag));
+
+    let mut tag_command = vec!["tag"];
+    if args.sign {
+        tag_command.push("-s");
+    } else if args.annotate || args.message.is_some() {
+        tag_command.push("-a");
+    }
+    if tag_command.len() > 1 {
+        tag_command.extend(["-m", &message]);
+    }
+    tag_command.push(&tag);
+    if let Some(git_ref) = &options.git_ref {
+        tag_command.push(git_ref);
+    }
+    let tag_ref = format!("refs/tags/{}", tag);
+    let push_command = vec!["push", &args.remote, &tag_ref];
+
+    let head = git_command.run(vec![
+        "rev-parse",
+        options.git_ref.as_deref().unwrap_or("HEAD"),
+    ])?;
+    let existing = git_command
+        .run(vec![
+            "rev-parse",
+            "-q",
+            "--verify",
+            &format!("{}^{{commit}}", tag_ref),
+        ])
+        .ok();
+    let exists = match existing {
+        Some(commit) if commit == head => true,
+        Some(commit) => {
+            return Err(Error::msg(format!(
+                "Tag '{}' already exists on commit '{}'",
+                tag, commit
+            )))
+        }
+        None => false,
+    };
+
+    let mut commands = vec![];
+    if !exists {
+        commands.push(tag_command);
+    }
+    if args.push {
+        commands.push(push_command);
+    }
+    if !args.dry_run {
+        for command in &commands {
+            git_command.run(command.clone())?;
+        }
+        if args.push {
+            verify_pushed_tag(git_command, &args.remote, &tag)?;
+        }
+    }
+    Ok(json!({
+        "tag": tag,
+        "version": version.to_string(),
+        "commit": head,
+        "created": !exists && !args.dry_run,
+        "pushed": args.push && !args.dry_run,
+        "verified": args.push && !args.dry_run,
+        "commands": commands
+            .iter()
+            .map(|command| format!("git {}", command.join(" ")))
+            .collect::<Vec<_>>(),
+    }))
+}
+
+/// Checks with `ls-remote` that the tag of the remote points at the local tag after pushing it,
+/// as pushes can be rejected silently, e.g. by hooks or tag protection rules
+pub(crate) fn verify_pushed_tag(
+    git_command: &impl GitCommandTrait,
+    remote: &str,
+    tag: &str,
+) -> Result<()> {
+    let tag_ref = format!("refs/tags/{}", tag);
+    let local = git_command.run(vec!["rev-parse", &tag_ref])?;
+    let remote_refs = git_command.run(vec!["ls-remote", remote, &tag_ref])?;
+    let remote_sha = remote_refs.lines().find_map(|line| {
+        let (sha, name) = line.split_once('\t')?;
+        (name == tag_ref).then_some(sha)
+    });
+    match remote_sha {
+        Some(sha) if sha == local => Ok(()),
+        Some(sha) => Err(Error::msg(format!(
+            "Pushing tag '{}' to '{}' didn't apply: the remote tag points at '{}' instead of '{}'",
+            tag, remote, sha, local
+        ))),
+        None => Err(Error::msg(format!(
+            "Pushing tag '{}' to '{}' didn't apply: the remote doesn't have the tag. Check the hooks and tag protection rules of the remote.",
+            tag, remote
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::git_command::MockGitCommandTrait;
+
+    use super::*;
+    use crate::version_calculator::version_info;
+
+    fn tag_args() -> TagArgs {
+        TagArgs {
+            annotate: false,
+            message: None,
+            sign: false,
+            release: false,
+            push: false,
+            remote: "origin".to_string(),
+            dry_run: false,
+        }
+    }
+
+    fn mock_git(git_command: &mut MockGitCommandTrait, existing: Option<&'static str>) {
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "rev-parse HEAD")
+            .returning(|_| Ok("1234567890".to_string()));
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "rev-parse" && args[1] == "-q")
+            .returning(move |_| match existing {
+                Some(commit) => Ok(commit.to_string()),
+                None => Err(Error::msg("")),
+            });
+    }
+
+    fn mock_remote_tag(
+        git_command: &mut MockGitCommandTrait,
+        tag: &'static str,
+        sha: &'static str,
+    ) {
+        git_command
+            .expect_run()
+            .withf(move |args| args.join(" ") == format!("rev-parse refs/tags/{}", tag))
+            .returning(move |_| Ok(sha.to_string()));
+        git_command
+            .expect_run()
+            .withf(move |args| args.join(" ") == format!("ls-remote origin refs/tags/{}", tag))
+            .returning(move |_| Ok(format!("{}\trefs/tags/{}", sha, tag)));
+    }
+
+    #[test]
+    fn test_create_tag_annotated_pushed() {
+        let mut git_command = MockGitCommandTrait::new();
+        mock_git(&mut git_command, None);
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "tag -a -m Release myapp-1.2.0-rc.3 myapp-1.2.0-rc.3")
+            .times(1)
+            .returning(|_| Ok(String::new()));
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "push origin refs/tags/myapp-1.2.0-rc.3")
+            .times(1)
+            .returning(|_| Ok(String::new()));
+        mock_remote_tag(&mut git_command, "myapp-1.2.0-rc.3", "abcdef0123");
+        let options = VersionOptions {
+            app_name: Some("myapp".to_string()),
+            ..Default::default()
+        };
+        let args = TagArgs {
+            annotate: true,
+            push: true,
+            ..tag_args()
+        };
+
+        let result = create_tag(
+            &git_command,
+            &options,
+            &version_info("release/myapp-1.2.0", "1.2.0-rc.3+1234567"),
+            &args,
+        )
+        .unwrap();
+
+        assert_eq!(result["tag"], "myapp-1.2.0-rc.3");
+        assert_eq!(result["created"], true);
+        assert_eq!(result["pushed"], true);
+        assert_eq!(result["verified"], true);
+    }
+
+    #[test]
+    fn test_create_tag_push_rejected() {
+        let mut git_command = MockGitCommandTrait::new();
+        mock_git(&mut git_command, Some("1234567890"));
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "push origin refs/tags/1.0.1-beta.3")
+            .returning(|_| Ok(String::new()));
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "rev-parse refs/tags/1.0.1-beta.3")
+            .returning(|_| Ok("abcdef0123".to_string()));
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "ls-remote origin refs/tags/1.0.1-beta.3")
+            .returning(|_| Ok(String::new()));
+        let args = TagArgs {
+            push: true,
+            ..tag_args()
+        };
+
+        let result = create_tag(
+            &git_command,
+            &VersionOptions::default(),
+            &version_info("develop", "1.0.1-beta.3+1234567"),
+            &args,
+        );
+
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("the remote doesn't have the tag"));
+    }
+
+    #[test]
+    fn test_create_tag_dry_run() {
+        let mut git_command = MockGitCommandTrait::new();
+        mock_git(&mut git_command, None);
+        let args = TagArgs {
+            sign: true,
+            message: Some("Beta".to_string()),
+            dry_run: true,
+            ..tag_args()
+        };
+
+        let result = create_tag(
+            &git_command,
+            &VersionOptions::default(),
+            &version_info("develop", "1.0.1-beta.3+1234567"),
+            &args,
+        )
+        .unwrap();
+
+        assert_eq!(
+            result["commands"],
+            json!(["git tag -s -m Beta 1.0.1-beta.3"])
+        );
+        assert_eq!(result["created"], false);
+    }
+
+    #[test]
+    fn test_create_tag_release() {
+        let mut git_command = MockGitCommandTrait::new();
+        mock_git(&mut git_command, None);
+        let args = TagArgs {
+            release: true,
+            dry_run: true,
+            ..tag_args()
+        };
+
+        let result = create_tag(
+            &git_command,
+            &VersionOptions::default(),
+            &version_info("release/2.0.0", "2.0.0-rc.3+1234567"),
+            &args,
+        )
+        .unwrap();
+
+        assert_eq!(result["tag"], "2.0.0");
+    }
+
+    #[test]
+    fn test_create_tag_exists_on_head() {
+        let mut git_command = MockGitCommandTrait::new();
+        mock_git(&mut git_command, Some("1234567890"));
+
+        let result = create_tag(
+            &git_command,
+            &VersionOptions::default(),
+            &version_info("develop", "1.0.1-beta.3+1234567"),
+            &tag_args(),
+        )
+        .unwrap();
+
+        assert_eq!(result["created"], false);
+        assert_eq!(result["commands"], json!([]));
+    }
+
+    #[test]
+    fn test_create_tag_exists_elsewhere() {
+        let mut git_command = MockGitCommandTrait::new();
+        mock_git(&mut git_command, Some("abcdef0123"));
+
+        let result = create_tag(
+            &git_command,
+            &VersionOptions::default(),
+            &version_info("develop", "1.0.1-beta.3+1234567"),
+            &tag_args(),
+        );
+
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("already exists on commit 'abcdef0123'"));
+    }
+}
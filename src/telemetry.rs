@@ -0,0 +1,313 @@
+use anyhow::Result;
+use serde_json::json;
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::forge::{HttpClient, HttpClientTrait, HttpRequest};
+use crate::git_command::GitCommandTrait;
+
+/// A finished span, a child of the run
+struct Span {
+    span_id: u64,
+    name: String,
+    start: u128,
+    end: u128,
+    attributes: Vec<(String, String)>,
+    error: Option<String>,
+}
+
+/// Traces the versioning run as OTLP spans: a span of the run, with the computed version and the
+/// decisions as attributes, and a child span per git call. Enabled by the standard
+/// `OTEL_EXPORTER_OTLP_ENDPOINT` or `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT` variables, and exported
+/// when dropped. A `TRACEPARENT` of the CI system makes the run a child of the pipeline's trace.
+pub struct Tracer {
+    endpoint: Option<String>,
+    headers: Vec<(String, String)>,
+    service_name: String,
+    name: String,
+    trace_id: u128,
+    parent_span_id: Option<u64>,
+    span_id: u64,
+    start: u128,
+    attributes: Mutex<Vec<(String, String)>>,
+    spans: Mutex<Vec<Span>>,
+    offline: bool,
+}
+
+impl Tracer {
+    pub fn from_env(name: &str, env: impl Fn(&str) -> Option<String>) -> Self {
+        let env = |name: &str| env(name).filter(|value| !value.is_empty());
+        let endpoint = env("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT").or_else(|| {
+            env("OTEL_EXPORTER_OTLP_ENDPOINT")
+                .map(|url| format!("{}/v1/traces", url.trim_end_matches('/')))
+        });
+        let headers = env("OTEL_EXPORTER_OTLP_HEADERS")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|header| {
+                let (name, value) = header.split_once('=')?;
+                Some((name.trim().to_string(), value.trim().to_string()))
+            })
+            .collect();
+        let parent = env("TRACEPARENT").and_then(|traceparent| parse_traceparent(&traceparent));
+        Self {
+            endpoint,
+            headers,
+            service_name: env("OTEL_SERVICE_NAME").unwrap_or("version-vine".to_string()),
+            name: name.to_string(),
+            trace_id: parent.map_or_else(
+                || (random_id() as u128) << 64 | random_id() as u128,
+                |(trace_id, _)| trace_id,
+            ),
+            parent_span_id: parent.map(|(_, span_id)| span_id),
+            span_id: random_id(),
+            start: now(),
+            attributes: Mutex::new(vec![]),
+            spans: Mutex::new(vec![]),
+            offline: false,
+        }
+    }
+
+    /// Refuses the export of the traces, for `--offline`
+    pub fn with_offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.endpoint.is_some()
+    }
+
+    /// Adds an attribute to the span of the run, e.g. `version_vine.version`
+    pub fn set_attribute(&self, key: &str, value: impl ToString) {
+        if self.enabled() {
+            let mut attributes = self.attributes.lock().unwrap();
+            attributes.retain(|(name, _)| name != key);
+            attributes.push((key.to_string(), value.to_string()));
+        }
+    }
+
+    fn record(&self, span: Span) {
+        if self.enabled() {
+            self.spans.lock().unwrap().push(span);
+        }
+    }
+
+    /// The spans of the run in the OTLP/HTTP JSON encoding
+    fn export_request(&self, end: u128) -> Option<HttpRequest> {
+        let endpoint = self.endpoint.as_ref()?;
+        let attributes = |attributes: &[(String, String)]| {
+            attributes
+                .iter()
+                .map(|(key, value)| json!({"key": key, "value": {"stringValue": value}}))
+                .collect::<Vec<_>>()
+        };
+        let trace_id = format!("{:032x}", self.trace_id);
+        let mut spans = vec![json!({
+            "traceId": trace_id,
+            "spanId": format!("{:016x}", self.span_id),
+            "parentSpanId": self.parent_span_id.map(|span_id| format!("{:016x}", span_id)).unwrap_or_default(),
+            "name": self.name,
+            // Internal
+            "kind": 1,
+            "startTimeUnixNano": self.start.to_string(),
+            "endTimeUnixNano": end.to_string(),
+            "attributes": attributes(&self.attributes.lock().unwrap()),
+        })];
+        for span in self.spans.lock().unwrap().iter() {
+            let status = match &span.error {
+                // Error
+                Some(message) => json!({"code": 2, "message": message}),
+                None => json!({}),
+            };
+            spans.push(json!({
+                "traceId": trace_id,
+                "spanId": format!("{:016x}", span.span_id),
+                "parentSpanId": format!("{:016x}", self.span_id),
+                "name": span.name,
+                // Client
+                "kind": 3,
+                "startTimeUnixNano": span.start.to_string(),
+                "endTimeUnixNano": span.end.to_string(),
+                "attributes": attributes(&span.attributes),
+                "status": status,
+            }));
+        }
+        let mut headers = vec![("Content-Type".to_string(), "application/json".to_string())];
+        headers.extend(self.headers.iter().cloned());
+        Some(HttpRequest {
+            method: "POST".to_string(),
+            url: endpoint.clone(),
+            headers,
+            body: Some(json!({
+                "resourceSpans": [{
+                    "resource": {"attributes": attributes(&[("service.name".to_string(), self.service_name.clone())])},
+                    "scopeSpans": [{
+                        "scope": {"name": "version-vine", "version": env!("CARGO_PKG_VERSION")},
+                        "spans": spans,
+                    }],
+                }],
+            })),
+        })
+    }
+
+    /// Exports the spans recorded so far, ending the span of the run
+    pub fn export(&self, http_client: &impl HttpClientTrait) -> Result<()> {
+        match self.export_request(now()) {
+            Some(request) => http_client.send(&request).map(|_| ()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Tracer {
+    fn drop(&mut self) {
+        // Tracing never fails the run
+        if let Err(err) = self.export(&HttpClient {
+            offline: self.offline,
+        }) {
+            eprintln!("Warning: Cannot export the traces: {}", err);
+        }
+    }
+}
+
+/// Records a span per git call of the wrapped git command
+pub struct TracedGitCommand<'a, G: GitCommandTrait> {
+    git_command: G,
+    tracer: &'a Tracer,
+}
+
+impl<'a, G: GitCommandTrait> TracedGitCommand<'a, G> {
+    pub fn new(git_command: G, tracer: &'a Tracer) -> Self {
+        Self {
+            git_command,
+            tracer,
+        }
+    }
+}
+
+impl<G: GitCommandTrait> GitCommandTrait for TracedGitCommand<'_, G> {
+    fn run(&self, args: Vec<&str>) -> Result<String> {
+        if !self.tracer.enabled() {
+            return self.git_command.run(args);
+        }
+        let name = format!("git {}", args.first().unwrap_or(&""));
+        let command = args.join(" ");
+        let start = now();
+        let result = self.git_command.run(args);
+        self.tracer.record(Span {
+            span_id: random_id(),
+            name,
+            start,
+            end: now(),
+            attributes: vec![("process.command_args".to_string(), command)],
+            error: result.as_ref().err().map(|err| err.to_string()),
+        });
+        result
+    }
+}
+
+/// Trace ID and parent span ID of a W3C `traceparent`, e.g.
+/// `00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01`
+fn parse_traceparent(traceparent: &str) -> Option<(u128, u64)> {
+    match traceparent.split('-').collect::<Vec<_>>().as_slice() {
+        [_, trace_id, span_id, _] if trace_id.len() == 32 && span_id.len() == 16 => Some((
+            u128::from_str_radix(trace_id, 16).ok()?,
+            u64::from_str_radix(span_id, 16).ok()?,
+        )),
+        _ => None,
+    }
+}
+
+/// Random ID from the randomly seeded hasher of the standard library, never zero
+fn random_id() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(now());
+    hasher.finish().max(1)
+}
+
+/// Nanoseconds since the epoch
+fn now() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_nanos())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::forge::MockHttpClientTrait;
+    use crate::git_command::MockGitCommandTrait;
+
+    use anyhow::Error;
+
+    use super::*;
+
+    fn tracer(vars: &'static [(&'static str, &'static str)]) -> Tracer {
+        Tracer::from_env("version-vine", |name| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| value.to_string())
+        })
+    }
+
+    #[test]
+    fn test_traced_git_command() {
+        let tracer = tracer(&[
+            ("OTEL_EXPORTER_OTLP_ENDPOINT", "http://collector:4318/"),
+            ("OTEL_EXPORTER_OTLP_HEADERS", "x-api-key=secret"),
+            (
+                "TRACEPARENT",
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            ),
+        ]);
+        let mut git_command = MockGitCommandTrait::new();
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "describe --abbrev=0 --tags")
+            .returning(|_| Err(Error::msg("No names found")));
+        let traced = TracedGitCommand::new(git_command, &tracer);
+        assert!(traced
+            .run(vec!["describe", "--abbrev=0", "--tags"])
+            .is_err());
+        tracer.set_attribute("version_vine.version", "1.0.1-beta.3");
+
+        let request = tracer.export_request(now()).unwrap();
+        assert_eq!(request.url, "http://collector:4318/v1/traces");
+        assert!(request
+            .headers
+            .contains(&("x-api-key".to_string(), "secret".to_string())));
+        let spans = &request.body.unwrap()["resourceSpans"][0]["scopeSpans"][0]["spans"];
+        assert_eq!(spans[0]["traceId"], "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(spans[0]["parentSpanId"], "00f067aa0ba902b7");
+        assert_eq!(
+            spans[0]["attributes"][0]["value"]["stringValue"],
+            "1.0.1-beta.3"
+        );
+        assert_eq!(spans[1]["name"], "git describe");
+        assert_eq!(spans[1]["parentSpanId"], spans[0]["spanId"]);
+        assert_eq!(spans[1]["status"]["message"], "No names found");
+        // Exported by the test instead
+        drop(traced);
+        std::mem::forget(tracer);
+    }
+
+    #[test]
+    fn test_tracer_disabled() {
+        let tracer = tracer(&[]);
+        let mut git_command = MockGitCommandTrait::new();
+        git_command
+            .expect_run()
+            .returning(|_| Ok("1.0.0".to_string()));
+        let traced = TracedGitCommand::new(git_command, &tracer);
+        traced
+            .run(vec!["describe", "--abbrev=0", "--tags"])
+            .unwrap();
+
+        assert!(tracer.export_request(now()).is_none());
+        // Nothing is sent
+        tracer.export(&MockHttpClientTrait::new()).unwrap();
+    }
+}
@@ -0,0 +1,89 @@
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::json;
+
+use std::sync::Mutex;
+
+use crate::log::{Level, Logger};
+use crate::messages::{Message, Messages};
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Explain {
+    /// Print the decisions to stderr as they're made, also when the calculation fails
+    Stderr,
+    /// Add them to the output as `trace` field
+    Field,
+}
+
+/// A decision of the version calculation, e.g. the `tag` step selecting the base tag
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct TraceStep {
+    pub step: String,
+    pub detail: String,
+}
+
+/// Collects the decisions of the version calculation for `--explain`. Nothing is recorded without
+/// it. The decisions printed to stderr follow the `[messages]`, the `trace` field is in English.
+/// They're also logged at debug level, in English, with `-v`.
+pub struct Trace {
+    explain: Option<Explain>,
+    app_name: Option<String>,
+    messages: Messages,
+    logger: Option<Logger>,
+    steps: Mutex<Vec<TraceStep>>,
+}
+
+impl Trace {
+    pub fn new(explain: Option<Explain>, app_name: &Option<String>, messages: Messages) -> Self {
+        Self {
+            explain,
+            app_name: app_name.clone(),
+            messages,
+            logger: None,
+            steps: Mutex::new(vec![]),
+        }
+    }
+
+    pub fn with_logger(mut self, logger: Logger) -> Self {
+        self.logger = Some(logger);
+        self
+    }
+
+    pub fn record(&self, step: &str, detail: Message) {
+        if let Some(logger) = &self.logger {
+            logger.log(
+                Level::Debug,
+                "decision",
+                &format!("{}: {}", step, detail),
+                match &self.app_name {
+                    Some(app_name) => json!({"app_name": app_name}),
+                    None => json!({}),
+                },
+            );
+        }
+        match self.explain {
+            None => {}
+            Some(Explain::Stderr) => {
+                let detail = self.messages.get(&detail);
+                match &self.app_name {
+                    Some(app_name) => eprintln!("explain [{}] {}: {}", app_name, step, detail),
+                    None => eprintln!("explain {}: {}", step, detail),
+                }
+            }
+            Some(Explain::Field) => self.steps.lock().unwrap().push(TraceStep {
+                step: step.to_string(),
+                detail: detail.to_string(),
+            }),
+        }
+    }
+
+    /// The steps recorded for the `trace` field, empty unless it's `--explain field`
+    pub fn take(&self) -> Vec<TraceStep> {
+        std::mem::take(&mut self.steps.lock().unwrap())
+    }
+}
+
+/// Serialized name of an enum value, e.g. `pull-request`
+pub(crate) fn name(value: impl Serialize) -> String {
+    json!(value).as_str().unwrap_or_default().to_string()
+}
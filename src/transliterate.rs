@@ -0,0 +1,158 @@
+use std::borrow::Cow;
+
+use crate::config::NonAscii;
+
+/// Makes the value ASCII with the `non_ascii` strategy, so branch names like `feature/función`
+/// yield readable build metadata. Characters the strategy can't handle are kept, and replaced by
+/// the escaping of the build metadata afterwards.
+pub fn to_ascii(value: &str, non_ascii: NonAscii) -> Cow<'_, str> {
+    if value.is_ascii() {
+        return Cow::Borrowed(value);
+    }
+    match non_ascii {
+        NonAscii::Replace => Cow::Borrowed(value),
+        NonAscii::Strip => Cow::Owned(value.chars().filter(char::is_ascii).collect()),
+        NonAscii::Transliterate => {
+            let mut ascii = String::with_capacity(value.len());
+            for c in value.chars() {
+                match transliterate(c) {
+                    Some(replacement) if c.is_uppercase() => {
+                        let mut chars = replacement.chars();
+                        ascii.extend(chars.next().map(|first| first.to_ascii_uppercase()));
+                        ascii.push_str(chars.as_str());
+                    }
+                    Some(replacement) => ascii.push_str(replacement),
+                    None => ascii.push(c),
+                }
+            }
+            Cow::Owned(ascii)
+        }
+    }
+}
+
+/// ASCII spelling of the Latin, Greek and Cyrillic letters, by their lowercase letter, and nothing
+/// for the combining diacritics of decomposed (NFD) names, e.g. of macOS file systems
+fn transliterate(c: char) -> Option<&'static str> {
+    if c.is_ascii() {
+        return None;
+    }
+    if ('\u{300}'..='\u{36f}').contains(&c) {
+        return Some("");
+    }
+    let replacement = match c.to_lowercase().next()? {
+        // Latin
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => "a",
+        'æ' => "ae",
+        'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => "c",
+        'ď' | 'đ' | 'ð' => "d",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => "e",
+        'ĝ' | 'ğ' | 'ġ' | 'ģ' => "g",
+        'ĥ' | 'ħ' => "h",
+        'ì' | 'í' | 'î' | 'ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => "i",
+        'ĳ' => "ij",
+        'ĵ' => "j",
+        'ķ' => "k",
+        'ĺ' | 'ļ' | 'ľ' | 'ŀ' | 'ł' => "l",
+        'ñ' | 'ń' | 'ņ' | 'ň' | 'ŋ' => "n",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => "o",
+        'œ' => "oe",
+        'ŕ' | 'ŗ' | 'ř' => "r",
+        'ś' | 'ŝ' | 'ş' | 'š' | 'ș' => "s",
+        'ß' => "ss",
+        'ţ' | 'ť' | 'ŧ' | 'ț' => "t",
+        'þ' => "th",
+        'ù' | 'ú' | 'û' | 'ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => "u",
+        'ŵ' => "w",
+        'ý' | 'ÿ' | 'ŷ' => "y",
+        'ź' | 'ż' | 'ž' => "z",
+        // Greek
+        'α' | 'ά' => "a",
+        'β' => "v",
+        'γ' => "g",
+        'δ' => "d",
+        'ε' | 'έ' => "e",
+        'ζ' => "z",
+        'η' | 'ή' => "i",
+        'θ' => "th",
+        'ι' | 'ί' | 'ϊ' | 'ΐ' => "i",
+        'κ' => "k",
+        'λ' => "l",
+        'μ' => "m",
+        'ν' => "n",
+        'ξ' => "x",
+        'ο' | 'ό' => "o",
+        'π' => "p",
+        'ρ' => "r",
+        'σ' | 'ς' => "s",
+        'τ' => "t",
+        'υ' | 'ύ' | 'ϋ' | 'ΰ' => "y",
+        'φ' => "f",
+        'χ' => "ch",
+        'ψ' => "ps",
+        'ω' | 'ώ' => "o",
+        // Cyrillic
+        'а' => "a",
+        'б' => "b",
+        'в' => "v",
+        'г' | 'ґ' => "g",
+        'д' => "d",
+        'е' | 'ё' | 'э' => "e",
+        'є' => "ye",
+        'ж' => "zh",
+        'з' => "z",
+        'и' | 'і' => "i",
+        'ї' => "yi",
+        'й' | 'ы' => "y",
+        'к' => "k",
+        'л' => "l",
+        'м' => "m",
+        'н' => "n",
+        'о' => "o",
+        'п' => "p",
+        'р' => "r",
+        'с' => "s",
+        'т' => "t",
+        'у' => "u",
+        'ф' => "f",
+        'х' => "kh",
+        'ц' => "ts",
+        'ч' => "ch",
+        'ш' => "sh",
+        'щ' => "shch",
+        'ъ' | 'ь' => "",
+        'ю' => "yu",
+        'я' => "ya",
+        _ => return None,
+    };
+    Some(replacement)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_ascii() {
+        let transliterate = |value| to_ascii(value, NonAscii::Transliterate).to_string();
+        assert_eq!(transliterate("feature/función"), "feature/funcion");
+        // Decomposed
+        assert_eq!(transliterate("feature/funcio\u{301}n"), "feature/funcion");
+        assert_eq!(
+            transliterate("feature/Straße-Æbleskiver"),
+            "feature/Strasse-Aebleskiver"
+        );
+        assert_eq!(transliterate("feature/Железо"), "feature/Zhelezo");
+        assert_eq!(transliterate("feature/λόγος"), "feature/logos");
+        // Left to the escaping
+        assert_eq!(transliterate("feature/日本"), "feature/日本");
+
+        assert_eq!(
+            to_ascii("feature/función", NonAscii::Strip),
+            "feature/funcin"
+        );
+        assert_eq!(
+            to_ascii("feature/función", NonAscii::Replace),
+            "feature/función"
+        );
+    }
+}
@@ -0,0 +1,451 @@
+use anyhow::Result;
+use std::io::{BufRead, Write};
+
+use crate::changelog::{changelog, ChangelogArgs};
+use crate::explain::explain;
+use crate::git_command::GitCommandTrait;
+use crate::messages::{Message, Messages};
+use crate::models::Regexes;
+use crate::next::{next_release, NextBump};
+use crate::start_release::release_branch;
+use crate::stats::parse_tag;
+use crate::tag::{create_tag, TagArgs};
+use crate::{calculate_apps, VersionCalculator, VersionInfo, VersionOptions};
+
+const RECENT_TAGS: usize = 5;
+
+/// State of the checkout shown by `ui`
+#[derive(Debug, PartialEq)]
+pub struct Dashboard {
+    pub branch: String,
+    pub class: String,
+    pub version: String,
+    pub container_tag: String,
+    /// Latest tags of the app, newest first
+    pub recent_tags: Vec<String>,
+    /// `[apps]` with commits since their latest tag
+    pub affected_apps: Vec<String>,
+}
+
+impl Dashboard {
+    pub fn new(
+        git_command: &impl GitCommandTrait,
+        options: &VersionOptions,
+        version_info: &VersionInfo,
+    ) -> Result<Self> {
+        let regexes = Regexes::new(&options.app_name, &options.config)?;
+        let class = explain(git_command, options)?["class"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        let recent_tags = git_command
+            .run(vec![
+                "for-each-ref",
+                "--sort=-creatordate",
+                "--format=%(refname:lstrip=2)",
+                "refs/tags",
+            ])?
+            .lines()
+            .filter(|tag| parse_tag(&regexes, &options.app_name, tag).is_some())
+            .take(RECENT_TAGS)
+            .map(str::to_string)
+            .collect();
+        let apps: Vec<String> = options.config.apps.keys().cloned().collect();
+        let affected_apps = match apps.is_empty() {
+            true => vec![],
+            false => {
+                let options = VersionOptions {
+                    fetch: false,
+                    ..options.clone()
+                };
+                calculate_apps(git_command, &options, &apps)?
+                    .into_iter()
+                    .filter(|(_, version_info)| version_info.changed_since_tag)
+                    .map(|(app, _)| app)
+                    .collect()
+            }
+        };
+        Ok(Self {
+            branch: version_info.git_branch.clone(),
+            class,
+            version: version_info.version.to_string(),
+            container_tag: version_info.container_tag.clone(),
+            recent_tags,
+            affected_apps,
+        })
+    }
+
+    pub fn render(&self, messages: &Messages) -> String {
+        let list = |items: &[String]| match items.is_empty() {
+            true => "-".to_string(),
+            false => items.join(", "),
+        };
+        labeled(
+            messages,
+            &[
+                ("ui.branch", format!("{} ({})", self.branch, self.class)),
+                ("ui.version", self.version.clone()),
+                ("ui.container_tag", self.container_tag.clone()),
+                ("ui.recent_tags", list(&self.recent_tags)),
+                ("ui.affected_apps", list(&self.affected_apps)),
+            ],
+        )
+    }
+}
+
+/// Lines of the labels and their values, aligned after the longest label
+fn labeled(messages: &Messages, rows: &[(&'static str, String)]) -> String {
+    let labels: Vec<String> = rows
+        .iter()
+        .map(|(key, _)| format!("{}:", messages.get(&Message::new(key))))
+        .collect();
+    let width = labels.iter().map(|label| label.chars().count()).max();
+    labels
+        .iter()
+        .zip(rows)
+        .map(|(label, (_, value))| {
+            format!("{:<width$} {}\n", label, value, width = width.unwrap_or(0))
+        })
+        .collect()
+}
+
+/// Interactive release management: shows the dashboard and runs the action of each key, until
+/// `q` or the end of the input. Actions changing the repository ask for confirmation first. The
+/// text follows the `[messages]`.
+pub fn run_ui(
+    git_command: &impl GitCommandTrait,
+    options: &VersionOptions,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> Result<()> {
+    let messages = &Messages::new(&options.config.messages)?;
+    loop {
+        let version_info = VersionCalculator::new(git_command, options.clone())?.calculate()?;
+        let dashboard = Dashboard::new(git_command, options, &version_info)?;
+        write!(
+            output,
+            "\n{}\n{}\n> ",
+            dashboard.render(messages),
+            messages.get(&Message::new("ui.actions"))
+        )?;
+        output.flush()?;
+        let Some(key) = read_line(input)? else {
+            return Ok(());
+        };
+        let result = match key.as_str() {
+            "r" => cut_release_branch(git_command, options, messages, input, output),
+            "t" => tag(git_command, options, &version_info, messages, input, output),
+            "c" => changelog(
+                git_command,
+                options,
+                &version_info,
+                &ChangelogArgs {
+                    output: None,
+                    contributors: None,
+                },
+            )
+            .and_then(|section| Ok(write!(output, "\n{}", section)?)),
+            "q" => return Ok(()),
+            "" => Ok(()),
+            key => Ok(writeln!(
+                output,
+                "{}",
+                messages.get(&Message::new("ui.unknown_key").arg("key", key))
+            )?),
+        };
+        // Failed actions are reported without leaving the UI
+        if let Err(err) = result {
+            writeln!(
+                output,
+                "{}",
+                messages.get(&Message::new("ui.error").arg("error", err))
+            )?;
+        }
+    }
+}
+
+/// Bumps proposed by `interactive`, in the order they're listed
+#[cfg(feature = "interactive")]
+const BUMPS: [(NextBump, &str); 4] = [
+    (NextBump::Patch, "patch"),
+    (NextBump::Minor, "minor"),
+    (NextBump::Major, "major"),
+    (NextBump::Auto, "auto"),
+];
+
+/// Guided release preparation: shows the branch, the base tag and the next release version of
+/// each bump, and offers to cut the release branch or create the tag of the picked version. Quits
+/// with `q` or at the end of the input.
+#[cfg(feature = "interactive")]
+pub fn run_interactive(
+    git_command: &impl GitCommandTrait,
+    options: &VersionOptions,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> Result<()> {
+    let messages = &Messages::new(&options.config.messages)?;
+    let classification = explain(git_command, options)?;
+    let releases = BUMPS
+        .iter()
+        .map(|(bump, _)| next_release(git_command, options, *bump))
+        .collect::<Result<Vec<_>>>()?;
+    let text = |value: &serde_json::Value| value.as_str().unwrap_or("-").to_string();
+    let branch = format!(
+        "{} ({})",
+        text(&classification["branch"]),
+        text(&classification["class"])
+    );
+    writeln!(
+        output,
+        "{}",
+        labeled(
+            messages,
+            &[
+                ("ui.branch", branch),
+                ("ui.base_tag", text(&releases[0]["latest_tag"])),
+            ],
+        )
+    )?;
+    for (i, ((bump, name), release)) in BUMPS.iter().zip(&releases).enumerate() {
+        let mut line = format!("[{}] {:<6} {}", i + 1, name, text(&release["version"]));
+        if *bump == NextBump::Auto {
+            line.push_str(&format!(" ({})", text(&release["bump"])));
+        }
+        writeln!(output, "{}", line)?;
+    }
+    let release = loop {
+        let prompt = Message::new("ui.pick_version").arg("count", releases.len());
+        write!(output, "{} ", messages.get(&prompt))?;
+        output.flush()?;
+        let Some(choice) = read_line(input)? else {
+            return Ok(());
+        };
+        if choice == "q" {
+            return Ok(());
+        }
+        match choice
+            .parse::<usize>()
+            .ok()
+            .and_then(|i| releases.get(i.wrapping_sub(1)))
+        {
+            Some(release) => break release,
+            None => writeln!(
+                output,
+                "{}",
+                messages.get(&Message::new("ui.unknown_choice").arg("choice", &choice))
+            )?,
+        }
+    };
+    let version = text(&release["version"]);
+    let tag = text(&release["tag"]);
+    let branch = release_branch(options, &version)?;
+    loop {
+        let actions = Message::new("ui.release_actions")
+            .arg("branch", &branch)
+            .arg("tag", &tag);
+        write!(output, "{}\n> ", messages.get(&actions))?;
+        output.flush()?;
+        let Some(key) = read_line(input)? else {
+            return Ok(());
+        };
+        match key.as_str() {
+            "r" => {
+                let question = Message::new("ui.create_branch").arg("branch", &branch);
+                if confirm(&question, messages, input, output)? {
+                    git_command.run(vec!["switch", "-c", &branch])?;
+                    let switched = Message::new("ui.switched").arg("branch", &branch);
+                    writeln!(output, "{}", messages.get(&switched))?;
+                }
+                return Ok(());
+            }
+            "t" => {
+                let question = Message::new("ui.create_tag_head").arg("tag", &tag);
+                if confirm(&question, messages, input, output)? {
+                    let message = format!("Release {}", tag);
+                    git_command.run(vec!["tag", "-a", "-m", &message, &tag])?;
+                    let created = Message::new("ui.created_tag").arg("tag", &tag);
+                    writeln!(output, "{}", messages.get(&created))?;
+                }
+                return Ok(());
+            }
+            "q" => return Ok(()),
+            key => writeln!(
+                output,
+                "{}",
+                messages.get(&Message::new("ui.unknown_key").arg("key", key))
+            )?,
+        }
+    }
+}
+
+fn cut_release_branch(
+    git_command: &impl GitCommandTrait,
+    options: &VersionOptions,
+    messages: &Messages,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> Result<()> {
+    let release = next_release(git_command, options, NextBump::Auto)?;
+    let branch = release_branch(options, release["version"].as_str().unwrap_or_default())?;
+    let question = Message::new("ui.create_branch").arg("branch", &branch);
+    if confirm(&question, messages, input, output)? {
+        git_command.run(vec!["switch", "-c", &branch])?;
+        let switched = Message::new("ui.switched").arg("branch", &branch);
+        writeln!(output, "{}", messages.get(&switched))?;
+    }
+    Ok(())
+}
+
+fn tag(
+    git_command: &impl GitCommandTrait,
+    options: &VersionOptions,
+    version_info: &VersionInfo,
+    messages: &Messages,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> Result<()> {
+    let args = TagArgs {
+        annotate: true,
+        message: None,
+        sign: false,
+        release: false,
+        push: false,
+        remote: "origin".to_string(),
+        dry_run: true,
+    };
+    let plan = create_tag(git_command, options, version_info, &args)?;
+    let tag = plan["tag"].as_str().unwrap_or_default();
+    if plan["commands"]
+        .as_array()
+        .is_some_and(|commands| commands.is_empty())
+    {
+        let exists = Message::new("ui.tag_exists").arg("tag", tag);
+        writeln!(output, "{}", messages.get(&exists))?;
+        return Ok(());
+    }
+    let question = Message::new("ui.create_tag").arg("tag", tag);
+    if confirm(&question, messages, input, output)? {
+        let args = TagArgs {
+            dry_run: false,
+            ..args
+        };
+        create_tag(git_command, options, version_info, &args)?;
+        let created = Message::new("ui.created_tag").arg("tag", tag);
+        writeln!(output, "{}", messages.get(&created))?;
+    }
+    Ok(())
+}
+
+/// Asks the question, confirmed by the `ui.yes` answer
+fn confirm(
+    question: &Message,
+    messages: &Messages,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> Result<bool> {
+    let prompt = Message::new("ui.confirm").message("question", question.clone());
+    write!(output, "{} ", messages.get(&prompt))?;
+    output.flush()?;
+    let yes = messages.get(&Message::new("ui.yes"));
+    Ok(read_line(input)?.is_some_and(|answer| answer.to_lowercase() == yes.to_lowercase()))
+}
+
+/// Trimmed line, `None` at the end of the input
+fn read_line(input: &mut impl BufRead) -> Result<Option<String>> {
+    let mut line = String::new();
+    match input.read_line(&mut line)? {
+        0 => Ok(None),
+        _ => Ok(Some(line.trim().to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let dashboard = Dashboard {
+            branch: "develop".to_string(),
+            class: "develop".to_string(),
+            version: "1.2.1-beta.3+1234567".to_string(),
+            container_tag: "1.2.1-beta.3.1234567".to_string(),
+            recent_tags: vec!["1.2.0".to_string(), "1.1.0".to_string()],
+            affected_apps: vec![],
+        };
+
+        assert_eq!(
+            dashboard.render(&Messages::default()),
+            "Branch:        develop (develop)\n\
+             Version:       1.2.1-beta.3+1234567\n\
+             Container tag: 1.2.1-beta.3.1234567\n\
+             Recent tags:   1.2.0, 1.1.0\n\
+             Affected apps: -\n"
+        );
+
+        let messages = Messages::new(&BTreeMap::from([
+            ("ui.branch".to_string(), "Zweig".to_string()),
+            ("ui.recent_tags".to_string(), "Letzte Tags".to_string()),
+            (
+                "ui.affected_apps".to_string(),
+                "Betroffene Anwendungen".to_string(),
+            ),
+        ]))
+        .unwrap();
+        assert_eq!(
+            dashboard.render(&messages),
+            "Zweig:                  develop (develop)\n\
+             Version:                1.2.1-beta.3+1234567\n\
+             Container tag:          1.2.1-beta.3.1234567\n\
+             Letzte Tags:            1.2.0, 1.1.0\n\
+             Betroffene Anwendungen: -\n"
+        );
+    }
+
+    #[cfg(feature = "interactive")]
+    #[test]
+    fn test_run_interactive() {
+        use crate::git_command::MockGitCommandTrait;
+        use anyhow::Error;
+
+        let mut git_command = MockGitCommandTrait::new();
+        let commands: Vec<(&str, Result<&str, &str>)> = vec![
+            ("describe --abbrev=0 --tags", Ok("1.4.0")),
+            ("log --format=%B%x00 1.4.0..HEAD", Ok("fix: a\n\0")),
+        ];
+        for (command, output) in commands {
+            git_command
+                .expect_run()
+                .withf(move |args| args.join(" ") == command)
+                .returning(move |_| output.map(String::from).map_err(Error::msg));
+        }
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "switch -c release/1.5.0")
+            .times(1)
+            .returning(|_| Ok(String::new()));
+        git_command.expect_run().returning(|_| Err(Error::msg("")));
+        let options = VersionOptions {
+            branch: Some("develop".to_string()),
+            ..Default::default()
+        };
+        let mut output = vec![];
+
+        run_interactive(
+            &git_command,
+            &options,
+            &mut &b"5\n2\nr\ny\n"[..],
+            &mut output,
+        )
+        .unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("Branch:   develop (develop)\nBase tag: 1.4.0\n"));
+        assert!(output.contains("[2] minor  1.5.0\n"));
+        assert!(output.contains("[4] auto   1.4.1 (patch)\n"));
+        assert!(output.contains("Unknown choice '5'"));
+        assert!(output.ends_with("Switched to 'release/1.5.0'\n"));
+    }
+}
@@ -0,0 +1,214 @@
+use anyhow::Result;
+use semver::Version;
+use serde::Serialize;
+
+use crate::audit::{audit, Check as AuditCheck};
+use crate::branch_resolver::resolve_branch;
+use crate::git_command::GitCommandTrait;
+use crate::models::{BranchClass, Regexes};
+use crate::version_calculator::{describe_tag, get_default_branch, get_latest_tag};
+use crate::VersionOptions;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Check {
+    /// The branch matches multiple branch patterns, or none of them and not the `feature` pattern
+    BranchName,
+    /// The latest tag isn't a SemVer version
+    TagVersion,
+    /// The release branch version isn't SemVer, or not greater than the latest tag
+    ReleaseVersion,
+    /// `HEAD` of a main branch isn't tagged
+    UntaggedMain,
+    /// A version tagged more than once, on different commits
+    DuplicateTag,
+}
+
+/// Repository state which doesn't follow the versioning model
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Violation {
+    pub check: Check,
+    pub message: String,
+}
+
+/// Checks the current branch, its latest tag and the tags of all apps against the versioning
+/// model, e.g. as pull request gate
+pub fn validate(
+    git_command: &impl GitCommandTrait,
+    options: &VersionOptions,
+) -> Result<Vec<Violation>> {
+    let branch = resolve_branch(git_command, &options.branch)?;
+    let default_branch = get_default_branch(git_command, options);
+    let regexes =
+        Regexes::new(&options.app_name, &options.config)?.with_default_branch(&default_branch)?;
+    let mut violations = vec![];
+    let mut violation =
+        |check: Check, message: String| violations.push(Violation { check, message });
+
+    let classes = regexes.matching_classes(&branch);
+    let class = regexes.branch_class(&branch);
+    if classes.len() > 1 && regexes.custom_branch(&branch).is_none() {
+        violation(
+            Check::BranchName,
+            format!(
+                "Branch '{}' matches the {:?} branch patterns",
+                branch, classes
+            ),
+        );
+    }
+    if let (BranchClass::Feature, Some(feature)) = (class, &options.config.branches.feature) {
+        if !Regexes::branch_regex("feature", feature)?.is_match(&branch) {
+            violation(
+                Check::BranchName,
+                format!(
+                    "Branch '{}' matches none of the branch patterns, nor the feature pattern '{}'",
+                    branch, feature
+                ),
+            );
+        }
+    }
+
+    let latest_tag = get_latest_tag(git_command, &regexes, None);
+    let latest_version = latest_tag.as_ref().and_then(|tag| {
+        let version = regexes
+            .tag
+            .captures(tag)
+            .and_then(|caps| Version::parse(caps.name("version")?.as_str()).ok());
+        if version.is_none() {
+            violation(
+                Check::TagVersion,
+                format!("Latest tag '{}' isn't a SemVer version", tag),
+            );
+        }
+        version
+    });
+
+    match class {
+        BranchClass::Release => {
+            let version = regexes.branch_version(&branch).unwrap_or_default();
+            match (Version::parse(version), &latest_version) {
+                (Err(_), _) => violation(
+                    Check::ReleaseVersion,
+                    format!(
+                        "Version '{}' of release branch '{}' isn't a SemVer version",
+                        version, branch
+                    ),
+                ),
+                (Ok(version), Some(latest_version)) if version <= *latest_version => violation(
+                    Check::ReleaseVersion,
+                    format!(
+                        "Version '{}' of release branch '{}' isn't greater than the latest tag '{}'",
+                        version,
+                        branch,
+                        latest_tag.as_deref().unwrap_or_default()
+                    ),
+                ),
+                _ => {}
+            }
+        }
+        BranchClass::Main => {
+            if describe_tag(git_command, &regexes, true, None).is_err() {
+                violation(
+                    Check::UntaggedMain,
+                    format!("HEAD of main branch '{}' isn't tagged", branch),
+                );
+            }
+        }
+        BranchClass::Hotfix
+        | BranchClass::Develop
+        | BranchClass::Support
+        | BranchClass::Feature
+        | BranchClass::PullRequest => {}
+    }
+
+    for finding in audit(git_command, options)? {
+        if finding.check == AuditCheck::Duplicate {
+            violation(Check::DuplicateTag, finding.message);
+        }
+    }
+    Ok(violations)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::git_command::MockGitCommandTrait;
+
+    use anyhow::Error;
+
+    use super::*;
+
+    fn mock_git(
+        git_command: &mut MockGitCommandTrait,
+        branch: &'static str,
+        latest_tag: Option<&'static str>,
+    ) {
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "branch --show-current")
+            .returning(move |_| Ok(branch.to_string()));
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "symbolic-ref")
+            .returning(|_| Err(Error::msg("not a symbolic ref")));
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "describe --abbrev=0 --tags")
+            .returning(move |_| latest_tag.map(str::to_string).ok_or(Error::msg("No names")));
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "describe --abbrev=0 --exact-match --tags")
+            .returning(|_| Err(Error::msg("no tag exactly matches")));
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "for-each-ref")
+            .returning(|_| Ok("1.2.0\taaaaaaaaaa\t\n1.2.0+build\tbbbbbbbbbb\t".to_string()));
+    }
+
+    #[test]
+    fn test_validate_release_branch() {
+        let mut git_command = MockGitCommandTrait::new();
+        mock_git(&mut git_command, "release/1.2.0", Some("1.2.0"));
+
+        let violations = validate(&git_command, &VersionOptions::default()).unwrap();
+
+        assert_eq!(
+            violations,
+            vec![
+                Violation {
+                    check: Check::ReleaseVersion,
+                    message: "Version '1.2.0' of release branch 'release/1.2.0' isn't greater than the latest tag '1.2.0'".to_string(),
+                },
+                Violation {
+                    check: Check::DuplicateTag,
+                    message: "Version 1.2.0 is tagged on different commits: 1.2.0 (aaaaaaa), 1.2.0+build (bbbbbbb)".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_branch_and_main() {
+        let checks = |branch: &'static str, latest_tag: Option<&'static str>| {
+            let mut git_command = MockGitCommandTrait::new();
+            mock_git(&mut git_command, branch, latest_tag);
+            let mut options = VersionOptions::default();
+            options.apply_config(Config::parse("[branches]\nfeature = '^feature/'").unwrap());
+            validate(&git_command, &options)
+                .unwrap()
+                .into_iter()
+                .map(|violation| violation.check)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            checks("main", Some("v1")),
+            vec![Check::TagVersion, Check::UntaggedMain, Check::DuplicateTag]
+        );
+        assert_eq!(checks("feature/login", None), vec![Check::DuplicateTag]);
+        assert_eq!(
+            checks("wip", None),
+            vec![Check::BranchName, Check::DuplicateTag]
+        );
+    }
+}
@@ -0,0 +1,117 @@
+use anyhow::{Error, Result};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::git_command::GitCommandTrait;
+use crate::models::Inputs;
+use crate::{VersionCalculator, VersionOptions};
+
+/// A check of `verify`: whether the recorded input or result matches
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct VerifyCheck {
+    pub check: &'static str,
+    pub recorded: String,
+    pub current: String,
+    pub ok: bool,
+}
+
+/// Result of recomputing the version of a manifest
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Verification {
+    /// Whether the version derives from the recorded inputs with the current configuration
+    pub verified: bool,
+    pub checks: Vec<VerifyCheck>,
+}
+
+/// The `inputs` of a version output of `--inputs`
+pub fn manifest_inputs(manifest: &Value) -> Result<Inputs> {
+    let inputs = manifest.get("inputs").ok_or(Error::msg(
+        "The manifest has no inputs. Record them with --inputs.",
+    ))?;
+    serde_json::from_value(inputs.clone())
+        .map_err(|err| Error::msg(format!("Invalid inputs of the manifest: {}", err)))
+}
+
+/// Recomputes the version of the manifest from its inputs: the recorded commit and branch, with the
+/// recorded environment variables only. `options` are the recorded arguments. A different version
+/// of version-vine is reported, but only fails the verification when the version differs.
+pub fn verify(
+    git_command: &impl GitCommandTrait,
+    options: &VersionOptions,
+    manifest: &Value,
+) -> Result<Verification> {
+    let inputs = manifest_inputs(manifest)?;
+    let recorded_version = manifest["app_version"]
+        .as_str()
+        .ok_or(Error::msg("The manifest has no app_version to verify"))?;
+    let head_sha = inputs.head_sha.clone().ok_or(Error::msg(
+        "The manifest has no commit to recompute the version of",
+    ))?;
+    let options = VersionOptions {
+        git_ref: Some(head_sha),
+        branch: Some(inputs.branch.clone()),
+        inputs: false,
+        ..options.clone()
+    };
+    let version_info = VersionCalculator::new(git_command, options.clone())?
+        .calculate_with_env(|name| inputs.env.get(name).cloned())?;
+    let check = |check, recorded: &str, current: &str| VerifyCheck {
+        check,
+        recorded: recorded.to_string(),
+        current: current.to_string(),
+        ok: recorded == current,
+    };
+    let checks = vec![
+        check(
+            "tool_version",
+            &inputs.tool_version,
+            env!("CARGO_PKG_VERSION"),
+        ),
+        check("config_hash", &inputs.config_hash, &options.config.hash()),
+        check(
+            "app_version",
+            recorded_version,
+            &version_info.version.to_string(),
+        ),
+    ];
+    Ok(Verification {
+        verified: checks
+            .iter()
+            .all(|check| check.ok || check.check == "tool_version"),
+        checks,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_manifest_inputs() {
+        let manifest = json!({
+            "app_version": "1.0.1-beta.1+1234567",
+            "inputs": {
+                "tool_version": "0.1.2",
+                "head_sha": "1234567890abcdef1234567890abcdef12345678",
+                "base_tag": "1.0.0",
+                "branch": "develop",
+                "config_hash": "cbf29ce484222325",
+                "args": ["--inputs"],
+                "env": {"GITHUB_ACTIONS": "true"},
+            },
+        });
+
+        let inputs = manifest_inputs(&manifest).unwrap();
+
+        assert_eq!(inputs.branch, "develop");
+        assert_eq!(inputs.env["GITHUB_ACTIONS"], "true");
+        assert_eq!(
+            manifest_inputs(&json!({"app_version": "1.0.0"}))
+                .unwrap_err()
+                .to_string(),
+            "The manifest has no inputs. Record them with --inputs."
+        );
+    }
+}
@@ -0,0 +1,6077 @@
+use anyhow::{Error, Result};
+use clap::{Parser, ValueEnum};
+use regex_lite::Regex;
+use semver::{BuildMetadata, Prerelease, Version};
+use serde::Deserialize;
+use serde_json::{to_value, Value};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::num::NonZeroUsize;
+use std::panic;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::branch_resolver::{
+    all_branches, ci_pull_request, resolve_branch_alias, resolve_branch_with_env,
+    resolve_ref_branch,
+};
+use crate::changelog::fnv1a;
+use crate::ci::{builder, run_attempt, Builder};
+use crate::components::{components, Component};
+use crate::config::{
+    AmbiguousBranch, ClockSkew, Config, MessageRule, PrereleaseLabels, TagSelection,
+};
+use crate::container_tag::{container_tag, ContainerTagStrategy};
+use crate::conventional_commits::{self, Bump};
+use crate::dotnet::{dotnet_versions, DotnetVersions};
+use crate::error::VersionError;
+use crate::git_command::GitCommandTrait;
+use crate::log::{LogFormat, Logger};
+use crate::messages::{Message, Messages};
+use crate::models::{
+    BranchClass, BuildMetadataBuilder, Inputs, PrereleaseSource, Regexes, SharedRegexes,
+    VersionOutput, SCHEMA_VERSION,
+};
+use crate::output::{cache_key, ExtraFormat};
+use crate::scheme::{nightly_version, PrereleaseCounter, Scheme, SchemeContext, VersionScheme};
+use crate::trace::{self, Explain, Trace, TraceStep};
+
+const MAJOR_APPROVAL_TRAILER: &str = "Major-Release-Approved-By";
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+#[derive(clap::Args, Clone, Debug)]
+pub struct VersionOptions {
+    /// Useful for monorepos with multiple versionable applications. Tags and release branches will have to be prefixed with an application name. E.g. tag: `app-1.0.0`, branch: `release/app-1.0.0`. Pass it multiple times to version multiple applications in one run.
+    #[arg(short, long = "app-name", value_name = "APP_NAME")]
+    pub app_names: Vec<String>,
+
+    /// Version all the applications of the `[apps]` configuration in one run
+    #[arg(long, action, conflicts_with = "app_names")]
+    pub all_apps: bool,
+
+    /// Version the crates of a Cargo workspace in one run, each as application named after its package with its directory as `--path`. Takes the workspace manifest, `Cargo.toml` when not provided.
+    #[arg(long, value_name = "MANIFEST", num_args = 0..=1, default_missing_value = "Cargo.toml", conflicts_with_all = ["app_names", "all_apps"])]
+    pub from_cargo_workspace: Option<PathBuf>,
+
+    /// Threads computing the versions of multiple applications in parallel. Defaults to the available parallelism.
+    #[arg(long)]
+    pub jobs: Option<usize>,
+
+    /// Application to version, the single `--app-name` once `apply_config` ran
+    #[arg(skip)]
+    pub app_name: Option<String>,
+
+    /// Prefix of the version in the tags, e.g. `v` for `v1.2.3`, or `myapp-v1.2.3` with `--app-name myapp`
+    #[arg(long)]
+    pub tag_prefix: Option<String>,
+
+    /// Which tag reachable from the commit the version is based on: `nearest`, as `git describe` finds it, or `highest`, the highest version by SemVer precedence. Defaults to the `[tag] selection`, `nearest`.
+    #[arg(long, value_enum, value_name = "SELECTION")]
+    pub tag_selection: Option<TagSelection>,
+
+    /// Build number used as the pre-release counter. Often used when using a build system. Implies `--counter build-number`.
+    #[arg(short, long)]
+    pub build_number: Option<u64>,
+
+    /// Pre-release counter, e.g. the `3` of `1.0.1-beta.3`. Defaults to `build-number` with `--build-number`, or else `distance`.
+    #[arg(long, value_enum)]
+    pub counter: Option<Counter>,
+
+    /// Branch the `merge-base` counter counts the commits ahead of, e.g. `origin/develop`. Defaults to `develop`, or the default branch without a `develop` branch.
+    #[arg(long, value_name = "BRANCH")]
+    pub counter_base: Option<String>,
+
+    /// How pull requests are merged into the default branch. `squash` lists the commits of the bullet list of squash commits, as GitHub writes them, in the changelog and the bump; `merge` counts only the first-parent commits, one per merge. `auto` detects it from the recent history of the default branch. Commits are taken as they are when not provided.
+    #[arg(long, value_enum)]
+    pub merge_strategy: Option<MergeStrategy>,
+
+    /// Commits the `distance` and `merge-base` counters count: `all`, only the `first-parent` commits or all but the merge commits (`no-merges`). Defaults to `first-parent` with the `merge` merge strategy, or else `all`.
+    #[arg(long, value_enum)]
+    pub count_strategy: Option<CountStrategy>,
+
+    /// Branch to version, e.g. for detached HEAD checkouts. Defaults to the current branch, or else the branch of the CI build (GitHub Actions, GitLab CI and Azure Pipelines) or the branch pointing at HEAD.
+    #[arg(long)]
+    pub branch: Option<String>,
+
+    /// Version a commit, tag or branch other than the checkout, e.g. `origin/release/2.0.0`. The branch is taken from a branch ref, or else from `--branch` or the branch pointing at the commit.
+    #[arg(long = "ref", visible_alias = "commit", value_name = "REF")]
+    pub git_ref: Option<String>,
+
+    /// Version the branch as it was at a date or commit: its last commit at that time, with only the tags created by then. Dates take the formats of `git log --before`, e.g. `2024-03-01 12:00`. Rebuilds historical artifacts with their original version.
+    #[arg(long, value_name = "DATE|COMMIT")]
+    pub as_of: Option<String>,
+
+    /// Version HEAD as if it were merged into the target branch, e.g. the version `develop` gets by merging a pull request. The merge commit is simulated with `git merge-tree` (git 2.38 or later), without touching the working tree, so the commit count and the bump follow both branches.
+    #[arg(long, value_name = "TARGET", conflicts_with = "as_of")]
+    pub simulate_merge: Option<String>,
+
+    /// Include fetching (decreases performance for local runs, but ensures latest information is used)
+    #[arg(short, long, action)]
+    pub fetch: bool,
+
+    /// Fetch the full history of shallow clones, instead of failing. Shallow clones lack the tags and commits the version is derived from.
+    #[arg(long, action)]
+    pub auto_deepen: bool,
+
+    /// Guarantee the same output for the same repository state, e.g. for build systems caching on the hash of the version output: no fetching or listing the tags of `origin`, the dates of the commit (or `SOURCE_DATE_EPOCH`) instead of the system clock, and canonical JSON. Also the `reproducible` of the configuration.
+    #[arg(long, action, conflicts_with_all = ["fetch", "auto_deepen", "builder"])]
+    pub reproducible: bool,
+
+    /// Default branch the `ahead_of_main` and `behind_main` commit counts are computed against. It's versioned like main, unless it matches another branch class. Defaults to the default branch of `origin` (`origin/HEAD`), or else `main`.
+    #[arg(long)]
+    pub default_branch: Option<String>,
+
+    /// Require major version bumps to be confirmed with `--confirm-major` or a `Major-Release-Approved-By` commit trailer since the latest tag
+    #[arg(long, action)]
+    pub require_major_approval: bool,
+
+    /// Confirm a major version bump when `--require-major-approval` is set
+    #[arg(long, action)]
+    pub confirm_major: bool,
+
+    /// Fail when the computed version isn't greater than the tags of its channel (the tags with the same pre-release label, and the releases) and the tags reachable from HEAD. E.g. a release branch named lower than an existing release.
+    #[arg(long, action)]
+    pub verify_semver_order: bool,
+
+    /// Base develop, feature and pull request branches on the highest release branch when it's ahead of the latest tag, e.g. `2.0.1-beta.3` on develop once `release/2.0.0` is cut while the latest tag is `1.4.2`. Local and remote branches count.
+    #[arg(long, visible_alias = "base-branch", action)]
+    pub release_branch_base: bool,
+
+    /// Require a verified signature of the release on main branches, checked with `git verify-commit` or `git verify-tag`, before emitting a production version
+    #[arg(long, value_enum, value_name = "OBJECT")]
+    pub require_signature: Option<RequireSignature>,
+
+    /// Require the release tag of main branches to be an annotated tag carrying a signature, for audited release pipelines. Unlike `--require-signature tag`, the signature isn't verified, so no keyring is needed.
+    #[arg(long, action)]
+    pub require_signed_tags: bool,
+
+    /// How develop, feature, hotfix and pull request branches are versioned when `HEAD` is exactly on their latest tag, e.g. right after merging a release back. Defaults to `bump`.
+    #[arg(long, value_enum)]
+    pub on_tag: Option<OnTag>,
+
+    /// How feature branches at the same commit count are kept from producing the same pre-release, as build metadata doesn't distinguish versions: `branch-hash` adds a hash of the branch name, `fail` and `increment` check the tags of `origin`. Defaults to `off`.
+    #[arg(long, value_enum, value_name = "STRATEGY")]
+    pub unique_prerelease: Option<UniquePrerelease>,
+
+    /// How the `container_tag` is made a valid OCI image tag of at most 128 characters, e.g. for long feature branch names: `truncate-hash` cuts long tags ending them with a hash, `drop-build-metadata` leaves out the build metadata and `slugify` lowercases the tag. Defaults to `truncate-hash`.
+    #[arg(long, value_enum, value_name = "STRATEGY")]
+    pub container_tag_strategy: Option<ContainerTagStrategy>,
+
+    /// How uncommitted changes to tracked files (`git status --porcelain`) of the checkout are handled, so local builds aren't mistaken for builds of the commit. Defaults to `ignore`.
+    #[arg(long, value_enum, value_name = "POLICY")]
+    pub dirty: Option<Dirty>,
+
+    /// How to handle branches without any commits, like freshly created orphan branches. Defaults to `version`.
+    #[arg(long, value_enum)]
+    pub empty_branch: Option<EmptyBranch>,
+
+    /// Version used for branches without any commits. The escaped branch name is added as build metadata. Defaults to `0.0.1-alpha.0`.
+    #[arg(long)]
+    pub empty_branch_version: Option<Version>,
+
+    /// Print the decisions of the calculation: the branch pattern that matched, the tag selected and why, the counter and how the version was formed. To stderr, or as `trace` output field with `--explain=field`.
+    #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "stderr", value_name = "TARGET")]
+    pub explain: Option<Explain>,
+
+    /// Log the git invocations (command, duration and status) and the decisions of the calculation to stderr. `-vv` also logs the trimmed output of the git commands.
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Format of the `--verbose` log
+    #[arg(long, value_enum, default_value = "text")]
+    pub log_format: LogFormat,
+
+    /// Add the `builder` block to the output: the detected CI system, the OS and architecture, the runner and the URL of the pipeline run, so the version manifest records where the artifacts were built
+    #[arg(long, action)]
+    pub builder: bool,
+
+    /// Add the `inputs` block to the output: the tool version, the commit, the base tag, the branch, the hash of the configuration, the arguments and the environment variables read, so the version can be recomputed and verified later
+    #[arg(long, action)]
+    pub inputs: bool,
+
+    /// Add the `components` array to the output: the submodules with the commits they're pinned to, and the version of their latest tag when checked out, so the contents of a composite build are fully described
+    #[arg(long, action)]
+    pub components: bool,
+
+    /// Add a `cache_key` field rendered from the template, with the placeholders of `--template`, e.g. `{{app_name}}-{{app_version}}-{{runner_os}}` with `RUNNER_OS` in the `[output] env`. Characters other than letters, digits, `.`, `_` and `-` are replaced by `-`.
+    #[arg(long, value_name = "TEMPLATE")]
+    pub cache_key: Option<String>,
+
+    /// Add the fields of other version formats to the output, comma separated: `dotnet` adds `assembly_version`, `file_version` and `informational_version` for MSBuild, following the `[dotnet]` configuration
+    #[arg(long, value_enum, value_delimiter = ',', value_name = "FORMAT")]
+    pub extra_formats: Vec<ExtraFormat>,
+
+    /// How the `calver` scheme handles a system clock behind the dates of the commit, the remote refs or `SOURCE_DATE_EPOCH`, by more than the `[calver] max_clock_skew`. Defaults to the `[calver] clock_skew`, `warn`.
+    #[arg(long, value_enum, value_name = "POLICY")]
+    pub clock_skew: Option<ClockSkew>,
+
+    /// Image name the `container_tags` are prefixed with as fully qualified references, e.g. `ghcr.io/org/app`
+    #[arg(long)]
+    pub image_name: Option<String>,
+
+    /// Path the commit count, change detection and commit message bumps are scoped to. Useful for monorepos, e.g. `services/myapp`.
+    #[arg(long)]
+    pub path: Option<String>,
+
+    /// How the version is formed from the latest tag or release branch. Defaults to `semver`.
+    #[arg(long, value_enum)]
+    pub scheme: Option<Scheme>,
+
+    /// How develop and feature branches are bumped from the latest tag. Defaults to `patch`.
+    #[arg(long, value_enum)]
+    pub bump_strategy: Option<BumpStrategy>,
+
+    /// Bump the major version when a commit message since the latest tag matches the regex, e.g. `\+semver:\s?(breaking|major)`. Enables the `[increment]` hints.
+    #[arg(long, value_name = "REGEX")]
+    pub major_increment: Option<String>,
+
+    /// Bump the minor version when a commit message since the latest tag matches the regex, e.g. `\+semver:\s?(feature|minor)`. Enables the `[increment]` hints.
+    #[arg(long, value_name = "REGEX")]
+    pub minor_increment: Option<String>,
+
+    /// Bump when a commit message since the latest tag matches the regex, for legacy commit conventions, e.g. `minor=^\[FEATURE\]`. Can be passed multiple times. Enables the `[increment]` hints.
+    #[arg(long = "bump-message-regex", value_name = "BUMP=REGEX")]
+    pub bump_message_regexes: Vec<MessageRule>,
+
+    /// Date the date based fields (branch age, changelog and release dates) are derived from. Defaults to `tag`.
+    #[arg(long, value_enum)]
+    pub date_source: Option<DateSource>,
+
+    /// Sources of the base version, comma separated, tried in order until one provides it: `tag`, the latest tag; `file`, a `VERSION` or `version.txt` file in the repository root (or the `--path`) of the commit; or `trailer`, a `Version:` trailer of the commit. Defaults to `tag`.
+    #[arg(long, value_enum, value_delimiter = ',', value_name = "SOURCES")]
+    pub version_source: Vec<VersionSource>,
+
+    /// Version a nightly or scheduled build, regardless of the branch: the next release after the latest tag, dated by the build date, e.g. `1.3.0-nightly.20240512+56c1976`. Builds of the same commit on the same day (UTC) get the same version.
+    #[arg(long, action)]
+    pub nightly: bool,
+
+    /// Pre-release label of release branches. Defaults to `rc`.
+    #[arg(long)]
+    pub prerelease_release: Option<String>,
+
+    /// Number of the pull request of a pull request build, versioned like `1.2.4-pr.123.3`. Detected from `GITHUB_REF` (`refs/pull/<number>/merge`), `CI_MERGE_REQUEST_IID` and `SYSTEM_PULLREQUEST_PULLREQUESTNUMBER` when not provided.
+    #[arg(long)]
+    pub pr_number: Option<u64>,
+
+    /// Pre-release label of hotfix branches. Defaults to `hotfix`.
+    #[arg(long)]
+    pub prerelease_hotfix: Option<String>,
+
+    /// Pre-release label of develop branches, e.g. `nightly`. Defaults to `beta`.
+    #[arg(long)]
+    pub prerelease_develop: Option<String>,
+
+    /// Pre-release label of feature branches, e.g. `pr`. Defaults to `alpha`.
+    #[arg(long)]
+    pub prerelease_feature: Option<String>,
+
+    /// Repository configuration, see `apply_config`
+    #[arg(skip)]
+    pub config: Config,
+
+    /// The options as provided, before `apply_config`, so the configuration of each application
+    /// of `calculate_apps` can be applied in between
+    #[arg(skip)]
+    pub provided: Option<Box<VersionOptions>>,
+}
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum EmptyBranch {
+    /// Produce the `--empty-branch-version`
+    Version,
+    /// Fail with an error
+    Error,
+}
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum OnTag {
+    /// Bump the tag and add the pre-release, as for any other commit
+    Bump,
+    /// The version of the tag as-is
+    Tag,
+    /// The version of the tag, with the build metadata of the branch
+    Metadata,
+}
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum UniquePrerelease {
+    /// Branches at the same counter may produce the same pre-release
+    Off,
+    /// Add a short hash of the branch name to the pre-release of feature branches, e.g. `1.3.0-alpha.4.k3f9a2`
+    BranchHash,
+    /// Fail when the tag of the pre-release is published on another commit of `origin`
+    Fail,
+    /// Increment the counter until the tag of the pre-release isn't published on another commit of `origin`
+    Increment,
+}
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Dirty {
+    /// Version the commit, regardless of the changes
+    Ignore,
+    /// Add `dirty` to the build metadata, e.g. `1.2.1-beta.3+56c1976.dirty`
+    Metadata,
+    /// Fail, e.g. before publishing artifacts
+    Fail,
+}
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RequireSignature {
+    /// The tagged commit, with `git verify-commit`
+    Commit,
+    /// The release tag, with `git verify-tag`. Requires annotated tags.
+    Tag,
+}
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum BumpStrategy {
+    /// Always bump the patch version
+    Patch,
+    /// Bump major, minor or patch based on the Conventional Commits since the latest tag
+    Conventional,
+}
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Counter {
+    /// Commits since the latest tag, restarting with each release. All commits without tags.
+    Distance,
+    /// All commits of `HEAD`, the `rev_count`
+    Total,
+    /// The `--build-number`
+    BuildNumber,
+    /// Commits of feature and pull request branches ahead of the `--counter-base` branch, which
+    /// stay stable when they're squash-merged. Other branches count the distance.
+    MergeBase,
+}
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum VersionSource {
+    /// The latest tag
+    Tag,
+    /// A `VERSION` or `version.txt` file of the commit
+    File,
+    /// A `Version:` trailer of the commit
+    Trailer,
+}
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum MergeStrategy {
+    /// Detected from the latest first-parent commits of the default branch
+    Auto,
+    /// Merge commits, so the counters count only the first-parent commits
+    Merge,
+    /// Squash merges, so the commits listed in their bodies are taken one by one
+    Squash,
+}
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum CountStrategy {
+    /// All commits, as `git rev-list --count`
+    All,
+    /// Only the first-parent commits, one per merge, as `git rev-list --count --first-parent`
+    FirstParent,
+    /// All commits but the merge commits, as `git rev-list --count --no-merges`
+    NoMerges,
+}
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum DateSource {
+    /// Author date of the commits, which rebases keep
+    Author,
+    /// Committer date of the commits, also for tagged commits
+    Committer,
+    /// Creation date of the tags, and committer date of the commits
+    Tag,
+}
+
+impl DateSource {
+    /// `git log` placeholder letter of the commit date, e.g. `c` for `%ct`
+    pub fn log_placeholder(&self) -> char {
+        match self {
+            DateSource::Author => 'a',
+            DateSource::Committer | DateSource::Tag => 'c',
+        }
+    }
+
+    /// `git for-each-ref` field of the tag date, and whether it's read from the tagged commit
+    pub fn ref_field(&self) -> (&'static str, bool) {
+        match self {
+            DateSource::Author => ("authordate", true),
+            DateSource::Committer => ("committerdate", true),
+            DateSource::Tag => ("creatordate", false),
+        }
+    }
+}
+
+/// Parses the options on their own, so `Default` stays in sync with the CLI defaults
+#[derive(Parser)]
+struct DefaultOptions {
+    #[command(flatten)]
+    options: VersionOptions,
+}
+
+impl Default for VersionOptions {
+    fn default() -> Self {
+        DefaultOptions::parse_from(["version-vine"]).options
+    }
+}
+
+impl VersionOptions {
+    /// Fills in the options which were not provided from the configuration, the `[apps]`
+    /// configuration of the app name taking precedence over the global one
+    pub fn apply_config(&mut self, config: Config) {
+        if self.provided.is_none() {
+            self.provided = Some(Box::new(self.clone()));
+        }
+        let single_app = match self.app_names.as_slice() {
+            [app_name] => Some(app_name.clone()),
+            _ => None,
+        };
+        self.app_name = self
+            .app_name
+            .take()
+            .or(single_app)
+            .or(config.app_name.clone());
+        // Checked when the configuration is parsed
+        let mut config = match &self.app_name {
+            Some(app_name) => config.for_app(app_name).unwrap_or(config),
+            None => config,
+        };
+        self.jobs = self.jobs.or(config.jobs);
+        self.fetch = self.fetch || config.fetch.unwrap_or(false);
+        self.auto_deepen = self.auto_deepen || config.auto_deepen.unwrap_or(false);
+        self.reproducible = self.reproducible || config.reproducible.unwrap_or(false);
+        self.default_branch = self.default_branch.take().or(config.default_branch.clone());
+        self.require_major_approval =
+            self.require_major_approval || config.require_major_approval.unwrap_or(false);
+        self.verify_semver_order =
+            self.verify_semver_order || config.verify_semver_order.unwrap_or(false);
+        self.release_branch_base =
+            self.release_branch_base || config.release_branch_base.unwrap_or(false);
+        self.require_signature = self.require_signature.or(config.require_signature);
+        self.require_signed_tags =
+            self.require_signed_tags || config.require_signed_tags.unwrap_or(false);
+        self.on_tag = self.on_tag.or(config.on_tag);
+        self.unique_prerelease = self.unique_prerelease.or(config.unique_prerelease);
+        self.container_tag_strategy = self
+            .container_tag_strategy
+            .or(config.container_tag_strategy);
+        self.dirty = self.dirty.or(config.dirty);
+        self.builder = self.builder || config.output.builder;
+        self.inputs = self.inputs || config.output.inputs;
+        self.components = self.components || config.output.components;
+        self.cache_key = self.cache_key.take().or(config.output.cache_key.clone());
+        if self.extra_formats.is_empty() {
+            self.extra_formats = config.output.extra_formats.clone();
+        }
+        if self.reproducible {
+            // Inputs which change without the repository changing
+            self.fetch = false;
+            self.auto_deepen = false;
+            self.builder = false;
+            // `fail` and `increment` list the tags of `origin`, `branch-hash` is local
+            if matches!(
+                self.unique_prerelease,
+                Some(UniquePrerelease::Fail | UniquePrerelease::Increment)
+            ) {
+                self.unique_prerelease = Some(UniquePrerelease::Off);
+            }
+        }
+        self.empty_branch = self.empty_branch.or(config.empty_branch);
+        self.empty_branch_version = self
+            .empty_branch_version
+            .take()
+            .or(config.empty_branch_version.clone());
+        self.bump_strategy = self.bump_strategy.or(config.bump_strategy);
+        self.scheme = self.scheme.or(config.scheme);
+        self.counter = self.counter.or(config.counter);
+        self.counter_base = self.counter_base.take().or(config.counter_base.clone());
+        if self.version_source.is_empty() {
+            self.version_source = config.version_source.clone().unwrap_or_default();
+        }
+        self.merge_strategy = self.merge_strategy.or(config.merge_strategy);
+        self.count_strategy = self.count_strategy.or(config.count_strategy);
+        self.date_source = self.date_source.or(config.date_source);
+        self.path = self.path.take().or(config.path.clone());
+        if let Some(clock_skew) = self.clock_skew {
+            config.calver.clock_skew = clock_skew;
+        }
+        if let Some(tag_prefix) = &self.tag_prefix {
+            config.tag.prefix = tag_prefix.clone();
+        }
+        if let Some(tag_selection) = self.tag_selection {
+            config.tag.selection = tag_selection;
+        }
+        if let Some(major_increment) = &self.major_increment {
+            config.increment.major = major_increment.clone();
+            config.increment.enabled = true;
+        }
+        if let Some(minor_increment) = &self.minor_increment {
+            config.increment.minor = minor_increment.clone();
+            config.increment.enabled = true;
+        }
+        if !self.bump_message_regexes.is_empty() {
+            config
+                .increment
+                .messages
+                .extend(self.bump_message_regexes.iter().cloned());
+            config.increment.enabled = true;
+        }
+        self.config = config;
+    }
+
+    /// Applications versioned in one run: the `--app-name`s when there are multiple, or the
+    /// configured `[apps]` with `--all-apps`. Empty when a single application is versioned.
+    pub fn apps(&self) -> Result<Vec<String>> {
+        if self.all_apps {
+            if self.config.apps.is_empty() {
+                return Err(Error::msg(
+                    "--all-apps requires the applications in the [apps] configuration",
+                ));
+            }
+            return Ok(self.config.apps.keys().cloned().collect());
+        }
+        match self.app_names.len() {
+            0 | 1 => Ok(vec![]),
+            _ => Ok(self.app_names.clone()),
+        }
+    }
+
+    pub fn jobs(&self) -> usize {
+        self.jobs
+            .unwrap_or_else(|| thread::available_parallelism().map_or(1, NonZeroUsize::get))
+            .max(1)
+    }
+
+    pub fn default_branch(&self) -> &str {
+        self.default_branch.as_deref().unwrap_or("main")
+    }
+
+    pub fn scheme(&self) -> Scheme {
+        self.scheme.unwrap_or(Scheme::Semver)
+    }
+
+    pub fn bump_strategy(&self) -> BumpStrategy {
+        self.bump_strategy.unwrap_or(BumpStrategy::Patch)
+    }
+
+    pub fn version_sources(&self) -> Vec<VersionSource> {
+        match self.version_source.is_empty() {
+            true => vec![VersionSource::Tag],
+            false => self.version_source.clone(),
+        }
+    }
+
+    pub fn counter(&self) -> Counter {
+        match (self.counter, self.build_number) {
+            (Some(counter), _) => counter,
+            (None, Some(_)) => Counter::BuildNumber,
+            (None, None) => Counter::Distance,
+        }
+    }
+
+    /// The `--count-strategy`, or else `first-parent` with the `merge` merge strategy
+    pub fn count_strategy(&self, merge_strategy: Option<MergeStrategy>) -> CountStrategy {
+        match (self.count_strategy, merge_strategy) {
+            (Some(count_strategy), _) => count_strategy,
+            (None, Some(MergeStrategy::Merge)) => CountStrategy::FirstParent,
+            (None, _) => CountStrategy::All,
+        }
+    }
+
+    pub fn logger(&self) -> Logger {
+        Logger::new(self.verbose, self.log_format)
+    }
+
+    pub fn date_source(&self) -> DateSource {
+        self.date_source.unwrap_or(DateSource::Tag)
+    }
+
+    /// Pre-release labels of the configuration, with the labels of the options taking precedence
+    pub fn prerelease_labels(&self) -> PrereleaseLabels {
+        let labels = &self.config.prerelease;
+        PrereleaseLabels {
+            release: self
+                .prerelease_release
+                .clone()
+                .unwrap_or(labels.release.clone()),
+            hotfix: self
+                .prerelease_hotfix
+                .clone()
+                .unwrap_or(labels.hotfix.clone()),
+            develop: self
+                .prerelease_develop
+                .clone()
+                .unwrap_or(labels.develop.clone()),
+            support: labels.support.clone(),
+            feature: self
+                .prerelease_feature
+                .clone()
+                .unwrap_or(labels.feature.clone()),
+            pull_request: labels.pull_request.clone(),
+            nightly: labels.nightly.clone(),
+            branches: labels.branches.clone(),
+        }
+    }
+
+    pub fn on_tag(&self) -> OnTag {
+        self.on_tag.unwrap_or(OnTag::Bump)
+    }
+
+    pub fn unique_prerelease(&self) -> UniquePrerelease {
+        self.unique_prerelease.unwrap_or(UniquePrerelease::Off)
+    }
+
+    pub fn container_tag_strategy(&self) -> ContainerTagStrategy {
+        self.container_tag_strategy
+            .unwrap_or(ContainerTagStrategy::TruncateHash)
+    }
+
+    pub fn dirty(&self) -> Dirty {
+        self.dirty.unwrap_or(Dirty::Ignore)
+    }
+
+    pub fn empty_branch(&self) -> EmptyBranch {
+        self.empty_branch.unwrap_or(EmptyBranch::Version)
+    }
+
+    pub fn empty_branch_version(&self) -> Version {
+        self.empty_branch_version
+            .clone()
+            .unwrap_or(Version::parse("0.0.1-alpha.0").unwrap())
+    }
+}
+
+/// The computed version together with the git information it was derived from
+#[derive(Clone, Debug, PartialEq)]
+pub struct VersionInfo {
+    pub git_branch: String,
+    /// Short SHA of `HEAD`, `None` for branches without commits
+    pub git_rev: Option<String>,
+    pub rev_count: String,
+    pub version: Version,
+    /// The version with `+` replaced by `.`, as `+` isn't allowed in container image tags
+    pub container_tag: String,
+    pub ahead_of_main: Option<u32>,
+    pub behind_main: Option<u32>,
+    pub branch_created_at: Option<String>,
+    pub branch_age_days: Option<u64>,
+    pub days_since_last_commit: Option<u64>,
+    /// Whether there are commits (touching the `--path`) since the latest tag, so builds can be skipped
+    pub changed_since_tag: bool,
+    pub branch_class: BranchClass,
+    /// Release channel of the branch class, following the `[channels]` configuration
+    pub channel: String,
+    /// `<app name>:<channel>`, `None` without app name
+    pub channel_image: Option<String>,
+    /// `container_tag` and the rolling tags of the branch class, e.g. `1.2` and `latest`
+    pub container_tags: Vec<String>,
+    /// Bump applied to the latest tag for develop and feature branches, following the bump strategy
+    pub bump: Option<Bump>,
+    /// Version the bump is applied to: of the base tag, the release branch name or the version
+    /// source. `None` for branches without commits.
+    pub base_version: Option<Version>,
+    /// What the pre-release is derived from, `None` without pre-release
+    pub prerelease_source: Option<PrereleaseSource>,
+    /// Number of the pull request of pull request builds
+    pub pr_number: Option<u64>,
+    /// Whether the signature of the release was verified, following `--require-signature`. `None`
+    /// when it isn't checked.
+    pub signature_verified: Option<bool>,
+    /// Metadata of the versioned commit and the base tag, for reproducible builds and SBOMs
+    pub commit: CommitMetadata,
+    /// Values of the `[output] env` variables, by lowercased name
+    pub env_fields: BTreeMap<String, String>,
+    /// Decisions of the calculation with `--explain=field`
+    pub trace: Vec<TraceStep>,
+    /// Machine identity of the build with `--builder`
+    pub builder: Option<Builder>,
+    /// What the version was computed from with `--inputs`
+    pub inputs: Option<Inputs>,
+    /// Submodules with `--components`
+    pub components: Option<Vec<Component>>,
+    /// Cache key of the `--cache-key` template
+    pub cache_key: Option<String>,
+    /// .NET versions with `--extra-formats dotnet`
+    pub dotnet: Option<DotnetVersions>,
+}
+
+/// Metadata of the versioned commit, `None` for branches without commits
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CommitMetadata {
+    /// Name of the base tag
+    pub tag: Option<String>,
+    /// Full SHA of the commit
+    pub sha: Option<String>,
+    /// Committer date of the commit, ISO-8601
+    pub timestamp: Option<String>,
+    /// Committer date of the commit, seconds since the epoch, e.g. for `SOURCE_DATE_EPOCH`
+    pub epoch: Option<u64>,
+    /// `Name <email>` of the committer
+    pub committer: Option<String>,
+    /// Creation date of the base tag, ISO-8601. The committer date for lightweight tags.
+    pub tag_date: Option<String>,
+    /// Annotation message of the base tag, `None` for lightweight tags
+    pub tag_message: Option<String>,
+    /// `Name <email>` of the tagger of the base tag, `None` for lightweight tags
+    pub tagger: Option<String>,
+    /// Whether the base tag carries a signature, `None` for lightweight tags. Not verified against
+    /// the keyring, see `--require-signature tag`.
+    pub tag_signed: Option<bool>,
+}
+
+impl VersionInfo {
+    /// The version without build metadata, as it's tagged
+    pub fn tag_version(&self) -> Version {
+        let mut version = self.version.clone();
+        version.build = BuildMetadata::EMPTY;
+        version
+    }
+
+    /// Fails for branches without any commits, which only get the bootstrap version
+    pub fn require_commits(&self, action: &str) -> Result<()> {
+        match self.git_rev {
+            Some(_) => Ok(()),
+            None => Err(Error::msg(format!(
+                "Branch '{}' doesn't have any commits to {} yet",
+                self.git_branch, action
+            ))),
+        }
+    }
+
+    pub fn output(&self) -> VersionOutput {
+        VersionOutput {
+            schema_version: SCHEMA_VERSION,
+            git_branch: self.git_branch.clone(),
+            git_rev: self.git_rev.clone(),
+            rev_count: self.rev_count.clone(),
+            app_version: self.version.to_string(),
+            container_tag: self.container_tag.clone(),
+            major: self.version.major,
+            minor: self.version.minor,
+            patch: self.version.patch,
+            prerelease: self.version.pre.to_string(),
+            build: self.version.build.to_string(),
+            branch_class: self.branch_class,
+            channel: self.channel.clone(),
+            channel_image: self.channel_image.clone(),
+            container_tags: self.container_tags.clone(),
+            ahead_of_main: self.ahead_of_main,
+            behind_main: self.behind_main,
+            branch_created_at: self.branch_created_at.clone(),
+            branch_age_days: self.branch_age_days,
+            days_since_last_commit: self.days_since_last_commit,
+            changed_since_tag: self.changed_since_tag,
+            bump: self.bump,
+            base_tag: self.commit.tag.clone(),
+            base_version: self.base_version.as_ref().map(Version::to_string),
+            prerelease_source: self.prerelease_source,
+            pr_number: self.pr_number,
+            signature_verified: self.signature_verified,
+            git_sha_full: self.commit.sha.clone(),
+            commit_timestamp: self.commit.timestamp.clone(),
+            commit_epoch: self.commit.epoch,
+            committer: self.commit.committer.clone(),
+            tag_date: self.commit.tag_date.clone(),
+            tag_message: self.commit.tag_message.clone(),
+            tagger: self.commit.tagger.clone(),
+            tag_signed: self.commit.tag_signed,
+            env_fields: self.env_fields.clone(),
+            trace: self.trace.clone(),
+            builder: self.builder.clone(),
+            inputs: self.inputs.clone(),
+            components: self.components.clone(),
+            cache_key: self.cache_key.clone(),
+            dotnet: self.dotnet.clone(),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        to_value(self.output()).unwrap()
+    }
+}
+
+/// Version info of a commit on `git_branch` with the default configuration, for tests to override
+/// the fields they assert on
+#[cfg(test)]
+pub(crate) fn version_info(git_branch: &str, version: &str) -> VersionInfo {
+    let version = Version::parse(version).unwrap();
+    let branch_class = Regexes::new(&None, &Default::default())
+        .unwrap()
+        .branch_class(git_branch);
+    VersionInfo {
+        git_branch: git_branch.to_string(),
+        git_rev: Some("1234567".to_string()),
+        rev_count: "3".to_string(),
+        container_tag: version.to_string().replace('+', "."),
+        version,
+        ahead_of_main: None,
+        behind_main: None,
+        branch_created_at: None,
+        branch_age_days: None,
+        days_since_last_commit: None,
+        changed_since_tag: true,
+        branch_class,
+        channel: crate::config::Channels::default()
+            .channel(branch_class)
+            .to_string(),
+        channel_image: None,
+        container_tags: vec![],
+        bump: None,
+        base_version: None,
+        prerelease_source: None,
+        pr_number: None,
+        signature_verified: None,
+        commit: Default::default(),
+        env_fields: BTreeMap::new(),
+        trace: vec![],
+        builder: None,
+        inputs: None,
+        components: None,
+        cache_key: None,
+        dotnet: None,
+    }
+}
+
+/// Versions of the applications of a monorepo, computed in one run. Tags are fetched once, and
+/// the `path` of the `[apps]` configuration is the `--path` of each application.
+///
+/// The applications are computed by `--jobs` threads in parallel. The result doesn't depend on
+/// the scheduling: when several applications fail, the error of the first one in order is returned.
+pub fn calculate_apps(
+    git_command: &impl GitCommandTrait,
+    options: &VersionOptions,
+    apps: &[String],
+) -> Result<BTreeMap<String, VersionInfo>> {
+    if options.fetch {
+        git_command.run(vec!["fetch", "--tags"])?;
+    }
+    let shared = SharedRegexes::new(&options.config)?;
+    let next_app = AtomicUsize::new(0);
+    let mut results = thread::scope(|scope| {
+        let workers: Vec<_> = (0..options.jobs().min(apps.len()))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = vec![];
+                    loop {
+                        let index = next_app.fetch_add(1, Ordering::Relaxed);
+                        let Some(app) = apps.get(index) else {
+                            break results;
+                        };
+                        let options = app_options(options, app);
+                        let version_info =
+                            VersionCalculator::with_shared(git_command, options, &shared)
+                                .and_then(|version_calculator| version_calculator.calculate());
+                        results.push((index, version_info));
+                    }
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|panic| panic::resume_unwind(panic))
+            })
+            .collect::<Vec<_>>()
+    });
+    results.sort_by_key(|(index, _)| *index);
+    results
+        .into_iter()
+        .map(|(index, version_info)| Ok((apps[index].clone(), version_info?)))
+        .collect()
+}
+
+/// Options of an application of `calculate_apps`, without fetching again. The configuration of
+/// the app is applied over the global one, under the provided options except `--path`.
+pub(crate) fn app_options(options: &VersionOptions, app: &str) -> VersionOptions {
+    let app_config = options.config.apps.get(app);
+    let mut app_options = match (&options.provided, app_config) {
+        (Some(provided), Some(app_config)) if !app_config.overrides.is_empty() => {
+            let mut app_options = VersionOptions {
+                app_name: Some(app.to_string()),
+                app_names: vec![],
+                ..(**provided).clone()
+            };
+            app_options.apply_config(options.config.clone());
+            app_options
+        }
+        _ => VersionOptions {
+            app_name: Some(app.to_string()),
+            ..options.clone()
+        },
+    };
+    // The path of the app over the shared `--path`
+    if let Some(path) = app_config.and_then(|app_config| app_config.path.clone()) {
+        app_options.path = Some(path);
+    }
+    app_options.fetch = false;
+    app_options
+}
+
+/// Source of the current time, e.g. the date of nightly versions, so it can be fixed in tests
+pub trait Clock {
+    /// Seconds since the Unix epoch
+    fn now(&self) -> Result<u64>;
+}
+
+/// The system time
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Result<u64> {
+        Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+    }
+}
+
+/// A fixed time, in seconds since the Unix epoch
+pub struct FixedClock(pub u64);
+
+impl Clock for FixedClock {
+    fn now(&self) -> Result<u64> {
+        Ok(self.0)
+    }
+}
+
+/// Calculates the version of the current git checkout
+pub struct VersionCalculator<'a, G: GitCommandTrait> {
+    git_command: &'a G,
+    options: VersionOptions,
+    regexes: Regexes,
+    scheme: Box<dyn VersionScheme>,
+    clock: Box<dyn Clock>,
+    trace: Trace,
+}
+
+impl<'a, G: GitCommandTrait> VersionCalculator<'a, G> {
+    pub fn new(git_command: &'a G, options: VersionOptions) -> Result<Self, Error> {
+        let shared = SharedRegexes::new(&options.config)?;
+        Self::with_shared(git_command, options, &shared)
+    }
+
+    /// Calculator reusing the regexes compiled for all the apps of a multi-app run
+    pub fn with_shared(
+        git_command: &'a G,
+        options: VersionOptions,
+        shared: &SharedRegexes,
+    ) -> Result<Self, Error> {
+        let regexes = Regexes::with_shared(shared, &options.app_name, &options.config)?;
+        let scheme = options.scheme().version_scheme(&options.config.calver)?;
+        let messages = Messages::new(&options.config.messages)?;
+        let trace =
+            Trace::new(options.explain, &options.app_name, messages).with_logger(options.logger());
+        Ok(Self {
+            git_command,
+            options,
+            regexes,
+            scheme,
+            clock: Box::new(SystemClock),
+            trace,
+        })
+    }
+
+    /// Calculates with a custom version scheme instead of the `scheme` of the options
+    pub fn with_scheme(mut self, scheme: impl VersionScheme + 'static) -> Self {
+        self.scheme = Box::new(scheme);
+        self
+    }
+
+    /// Calculates with another clock than the system time, e.g. a `FixedClock` in tests
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    pub fn calculate(&self) -> Result<VersionInfo, Error> {
+        self.calculate_with_env(|name| std::env::var(name).ok())
+    }
+
+    /// `calculate` with the environment variables, the CI branch and pull request and the
+    /// `[build_metadata] env` and `[output] env` variables, looked up by `env`
+    pub fn calculate_with_env(&self, env: impl Fn(&str) -> Option<String>) -> Result<VersionInfo> {
+        // The variables which influenced the version, for the `inputs`
+        let read = RefCell::new(BTreeMap::new());
+        let env = |name: &str| {
+            let value = env(name);
+            if let Some(value) = &value {
+                read.borrow_mut().insert(name.to_string(), value.clone());
+            }
+            value
+        };
+        let version_info = self.calculate_version(env)?;
+        let build = version_info.version.build.clone();
+        let mut version_info = apply_env(version_info, &self.options.config, &self.regexes, env)?;
+        if version_info.version.build != build {
+            self.trace.record(
+                "build",
+                Message::new("explain.build").arg("build", &version_info.version.build),
+            );
+        }
+        version_info.container_tag =
+            container_tag(&version_info.version, self.options.container_tag_strategy());
+        version_info.container_tags = container_tags(&version_info, &self.options);
+        if let Some(template) = &self.options.cache_key {
+            version_info.cache_key = Some(cache_key(&version_info, &self.options, template)?);
+        }
+        if self.options.extra_formats.contains(&ExtraFormat::Dotnet) {
+            version_info.dotnet = Some(dotnet_versions(&version_info, &self.options)?);
+        }
+        if self.options.builder {
+            version_info.builder = Some(builder(env));
+        }
+        if self.options.inputs {
+            version_info.inputs = Some(Inputs {
+                tool_version: env!("CARGO_PKG_VERSION").to_string(),
+                head_sha: version_info.commit.sha.clone(),
+                base_tag: version_info.commit.tag.clone(),
+                branch: version_info.git_branch.clone(),
+                config_hash: self.options.config.hash(),
+                args: std::env::args().skip(1).collect(),
+                env: read.take(),
+            });
+        }
+        if self.options.components {
+            // Branches without commits don't have submodules yet
+            version_info.components = match version_info.git_rev {
+                Some(_) => Some(components(
+                    self.git_command,
+                    self.options.git_ref.as_deref().unwrap_or("HEAD"),
+                )?),
+                None => Some(vec![]),
+            };
+        }
+        self.trace.record(
+            "result",
+            Message::new("explain.result").arg("version", &version_info.version),
+        );
+        version_info.trace = self.trace.take();
+        Ok(version_info)
+    }
+
+    fn calculate_version(
+        &self,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<VersionInfo, Error> {
+        let git_command = self.git_command;
+        let options = &self.options;
+        let regexes = &self.regexes;
+        let trace = &self.trace;
+        if options.fetch {
+            match regexes.tag_refspec(&options.config) {
+                // Keeps the tags of other apps, which may collide, out of the repository
+                Some(refspec) => git_command.run(vec![
+                    "fetch",
+                    "origin",
+                    "+refs/heads/*:refs/remotes/origin/*",
+                    &refspec,
+                ])?,
+                None => git_command.run(vec!["fetch", "--tags"])?,
+            };
+        }
+        let head = match &options.git_ref {
+            Some(git_ref) => Some(resolve_commit(git_command, git_ref)?),
+            None => None,
+        };
+        let head_rev = head.as_deref().unwrap_or("HEAD");
+        let (git_branch, merge_commit) = match (&options.simulate_merge, &options.git_ref) {
+            (Some(target), _) => (
+                target.clone(),
+                Some(simulate_merge(git_command, target, head_rev)?),
+            ),
+            (None, Some(git_ref)) => (
+                resolve_ref_branch(git_command, git_ref, &options.branch)?,
+                None,
+            ),
+            (None, None) => (
+                resolve_branch_with_env(git_command, &options.branch, &env)?,
+                None,
+            ),
+        };
+        trace.record(
+            "branch",
+            match (&options.simulate_merge, &options.git_ref) {
+                (Some(_), _) => Message::new("explain.branch_simulated")
+                    .arg("branch", &git_branch)
+                    .arg("head", head_rev),
+                (None, Some(git_ref)) => Message::new("explain.branch_ref")
+                    .arg("branch", &git_branch)
+                    .arg("ref", git_ref),
+                (None, None) => Message::new("explain.branch").arg("branch", &git_branch),
+            },
+        );
+        let as_of = match &options.as_of {
+            Some(as_of) => Some(resolve_as_of(git_command, as_of, head_rev)?),
+            None => None,
+        };
+        let rev = match &as_of {
+            Some(as_of) => Some(as_of.commit.as_str()),
+            None => merge_commit.as_deref().or(head.as_deref()),
+        };
+        // `--short` honors `core.abbrev` and extends the SHA until it's unambiguous.
+        let short = match options.config.build_metadata.sha_length {
+            Some(sha_length) => format!("--short={}", sha_length),
+            None => "--short".to_string(),
+        };
+        let git_rev = match git_command.run(vec!["rev-parse", &short, rev.unwrap_or("HEAD")]) {
+            Ok(git_rev) => git_rev,
+            // Resolving HEAD fails when the current branch doesn't have any commits yet
+            Err(err) if rev.is_none() && is_unborn(git_command).unwrap_or(false) => {
+                trace.record("commit", Message::new("explain.no_commits"));
+                return get_empty_branch_info(options, regexes, git_branch, err);
+            }
+            Err(err) => return Err(err),
+        };
+        check_shallow(git_command, options)?;
+        let rev_count = git_command.run(with_path(
+            vec!["rev-list", "--count", rev.unwrap_or("HEAD")],
+            options,
+        ))?;
+        let default_branch = get_default_branch(git_command, options);
+        // Aliases are versioned as the branch they stand for, e.g. while the default branch is renamed
+        let git_branch = match resolve_branch_alias(git_command, &git_branch, &default_branch) {
+            Some(alias) => {
+                trace.record(
+                    "branch",
+                    Message::new("explain.branch_alias")
+                        .arg("target", &alias)
+                        .arg("branch", &git_branch),
+                );
+                alias
+            }
+            None => git_branch,
+        };
+        let regexes = &regexes.with_default_branch(&default_branch)?;
+        let merge_strategy = get_merge_strategy(git_command, options);
+        // Other refs than the checkout aren't the pull request build
+        let pr_number = match (&options.simulate_merge, &options.git_ref) {
+            (None, None) => options.pr_number.or(ci_pull_request(&env)),
+            _ => options.pr_number,
+        };
+        let regexes = &match pr_number {
+            Some(pr_number) => regexes.with_pull_request(&git_branch, pr_number),
+            None => regexes.clone(),
+        };
+        let regexes = &match &as_of {
+            Some(as_of) => regexes.with_excluded_tags(tags_created_after(git_command, as_of)?),
+            None => regexes.clone(),
+        };
+        trace.record(
+            "commit",
+            Message::new("explain.commit")
+                .arg("rev", &git_rev)
+                .arg("count", &rev_count),
+        );
+        check_ambiguous_branch(regexes, &options.config, &git_branch)?;
+        trace.record("class", explain_class(regexes, &git_branch));
+        let base_tag = match regexes.branch_class(&git_branch) {
+            BranchClass::Hotfix if !options.nightly => {
+                get_branch_point_tag(git_command, regexes, &default_branch, rev)
+            }
+            BranchClass::Support if !options.nightly => {
+                Some(get_support_tag(git_command, regexes, &git_branch)?)
+            }
+            _ => get_latest_path_tag(git_command, regexes, options, rev)?,
+        };
+        // Release branches take their version from the branch name
+        let source_version = match regexes.branch_class(&git_branch) {
+            BranchClass::Release if !options.nightly => None,
+            _ => get_source_version(git_command, options, trace, base_tag.as_deref(), rev)?,
+        };
+        // Nightly versions follow the latest tag on any branch, also on untagged main commits
+        let semver = match (&base_tag, options.nightly, source_version) {
+            (_, _, Some(version)) => version,
+            (Some(tag), true, None) => {
+                trace.record("tag", Message::new("explain.tag_nightly").arg("tag", tag));
+                parse_tag_version(regexes, tag)?
+            }
+            (None, true, None) => {
+                trace.record("tag", Message::new("explain.tag_none"));
+                Version::new(0, 0, 0)
+            }
+            (_, false, None) => get_version(
+                git_command,
+                regexes,
+                trace,
+                &git_branch,
+                base_tag.as_deref(),
+                rev,
+            )?,
+        };
+        // Whether the version is of a release branch name rather than a tag
+        let (semver, from_branch_name) = match regexes.branch_class(&git_branch) {
+            BranchClass::Release => (semver, !options.nightly),
+            BranchClass::Develop | BranchClass::Feature | BranchClass::PullRequest
+                if options.release_branch_base && !options.nightly =>
+            {
+                match get_release_branch_version(git_command, regexes)? {
+                    Some((branch, version)) if version > semver => {
+                        trace.record(
+                            "tag",
+                            Message::new("explain.tag_ahead")
+                                .arg("branch", &branch)
+                                .arg("version", &version),
+                        );
+                        (version, true)
+                    }
+                    _ => (semver, false),
+                }
+            }
+            _ => (semver, false),
+        };
+        let bump = match (regexes.branch_class(&git_branch), options.bump_strategy()) {
+            (BranchClass::Hotfix, _) if !options.nightly => {
+                trace.record("bump", Message::new("explain.bump_hotfix"));
+                Bump::Patch
+            }
+            (BranchClass::Support, _) if !options.nightly => {
+                trace.record("bump", Message::new("explain.bump_support"));
+                Bump::Patch
+            }
+            (_, BumpStrategy::Patch) => {
+                trace.record("bump", Message::new("explain.bump_patch"));
+                Bump::Patch
+            }
+            (_, BumpStrategy::Conventional) => {
+                let bump =
+                    get_conventional_bump(git_command, regexes, options, merge_strategy, rev)?;
+                trace.record(
+                    "bump",
+                    Message::new("explain.bump_conventional").arg("bump", trace::name(bump)),
+                );
+                bump
+            }
+        };
+        let bump = match regexes.branch_class(&git_branch) {
+            BranchClass::Develop | BranchClass::Feature | BranchClass::PullRequest
+                if !options.nightly =>
+            {
+                let hint = get_increment_hint(git_command, regexes, options, &git_branch, rev)?;
+                if hint > bump {
+                    trace.record(
+                        "bump",
+                        Message::new("explain.bump_hint").arg("bump", trace::name(hint)),
+                    );
+                }
+                bump.max(hint)
+            }
+            _ => bump,
+        };
+        // The build date of reproducible builds
+        let source_date_epoch =
+            env("SOURCE_DATE_EPOCH").and_then(|epoch| epoch.parse::<u64>().ok());
+        let now = match (&as_of, options.reproducible) {
+            (Some(as_of), _) => as_of.timestamp,
+            (None, true) => match source_date_epoch {
+                Some(epoch) => epoch,
+                None => get_commit_timestamp(git_command, options, rev).unwrap_or_default(),
+            },
+            (None, false) => self.clock.now()?,
+        };
+        let branch_age = get_branch_age(
+            git_command,
+            &default_branch,
+            options.date_source(),
+            now,
+            rev,
+        );
+        let (counter, counter_source) = get_count(
+            git_command,
+            options,
+            &rev_count,
+            base_tag.as_deref(),
+            regexes.branch_class(&git_branch),
+            &default_branch,
+            merge_strategy,
+            rev,
+        )?;
+        trace.record(
+            "counter",
+            Message::new("explain.counter")
+                .arg("counter", counter)
+                .message("source", counter_source),
+        );
+        if options.scheme() == Scheme::Calver
+            && as_of.is_none()
+            && !options.reproducible
+            && options.config.calver.clock_skew != ClockSkew::Ignore
+        {
+            check_clock_skew(
+                git_command,
+                options,
+                trace,
+                branch_age.last_commit_timestamp,
+                source_date_epoch,
+                now,
+            )?;
+        }
+        let commit = VersionedCommit {
+            git_rev: &git_rev,
+            counter,
+            run_attempt: match options.reproducible {
+                true => 1,
+                false => run_attempt(&env),
+            },
+            date: branch_age.last_commit_timestamp.unwrap_or(now),
+            now: match as_of {
+                Some(_) => now,
+                None => source_date_epoch.unwrap_or(now),
+            },
+        };
+        let new_semver = match options.nightly {
+            true => nightly_version(
+                &semver,
+                bump,
+                &options.prerelease_labels().nightly,
+                commit.now,
+                &git_rev,
+                &options.config.build_metadata,
+            )?,
+            false => self.unique_version(&git_branch, regexes, &commit, &semver, bump, rev)?,
+        };
+        // Nightly builds of tagged commits are nightly versions as well
+        let on_tag = match base_tag.as_deref() {
+            Some(tag) if !options.nightly => {
+                on_tag(git_command, regexes, options, &git_branch, tag, rev)
+            }
+            _ => OnTag::Bump,
+        };
+        if on_tag != OnTag::Bump {
+            trace.record(
+                "on-tag",
+                Message::new("explain.on_tag")
+                    .arg("tag", base_tag.as_deref().unwrap_or_default())
+                    .arg("on_tag", on_tag.to_possible_value().unwrap().get_name()),
+            );
+        }
+        let new_semver = match on_tag {
+            OnTag::Bump => new_semver,
+            OnTag::Tag => semver.clone(),
+            OnTag::Metadata => Version {
+                build: new_semver.build,
+                ..semver.clone()
+            },
+        };
+        // Other refs than the checkout aren't affected by the working tree
+        let new_semver = match (options.dirty(), rev) {
+            (Dirty::Ignore, _) | (_, Some(_)) => new_semver,
+            (dirty, None) => check_dirty(git_command, options, trace, dirty, new_semver)?,
+        };
+        check_major_approval(git_command, regexes, options, &new_semver, rev)?;
+        if options.verify_semver_order {
+            check_semver_order(git_command, regexes, &new_semver, rev)?;
+        }
+        let signature_verified = match regexes.branch_class(&git_branch) {
+            BranchClass::Main => check_signature(git_command, options, base_tag.as_deref(), rev)?,
+            _ => None,
+        };
+        let commit = get_commit_metadata(git_command, base_tag.as_deref(), rev);
+        if options.require_signed_tags
+            && regexes.branch_class(&git_branch) == BranchClass::Main
+            && commit.tag_signed != Some(true)
+        {
+            return Err(Error::msg(format!(
+                "The release tag '{}' must be an annotated tag with a signature, e.g. created with `git tag -s`",
+                base_tag.as_deref().unwrap_or_default()
+            )));
+        }
+        let (ahead_of_main, behind_main) = get_ahead_behind(git_command, &default_branch, rev);
+        let changed_since_tag = get_changed_since_tag(git_command, regexes, options, rev)?;
+        let branch_class = regexes.branch_class(&git_branch);
+        let bump = match branch_class {
+            _ if options.nightly => Some(bump),
+            BranchClass::Hotfix
+            | BranchClass::Support
+            | BranchClass::Develop
+            | BranchClass::Feature
+            | BranchClass::PullRequest
+                if on_tag == OnTag::Bump =>
+            {
+                Some(bump)
+            }
+            _ => None,
+        };
+        let prerelease_source = (!new_semver.pre.is_empty()).then(|| match options.counter() {
+            Counter::BuildNumber => PrereleaseSource::BuildNumber,
+            _ if from_branch_name => PrereleaseSource::BranchName,
+            _ => PrereleaseSource::Tag,
+        });
+        Ok(VersionInfo {
+            git_branch,
+            git_rev: Some(git_rev),
+            rev_count,
+            container_tag: container_tag(&new_semver, self.options.container_tag_strategy()),
+            version: new_semver,
+            ahead_of_main,
+            behind_main,
+            branch_created_at: branch_age.created_at,
+            branch_age_days: branch_age.age_days,
+            days_since_last_commit: branch_age.days_since_last_commit,
+            changed_since_tag,
+            branch_class,
+            channel: options.config.channels.channel(branch_class).to_string(),
+            channel_image: channel_image(options, branch_class),
+            container_tags: vec![],
+            bump,
+            base_version: Some(semver),
+            prerelease_source,
+            pr_number,
+            signature_verified,
+            commit,
+            env_fields: BTreeMap::new(),
+            trace: vec![],
+            builder: None,
+            inputs: None,
+            components: None,
+            cache_key: None,
+            dotnet: None,
+        })
+    }
+
+    /// `update_version` following the `--unique-prerelease` strategy. Tags published on the
+    /// versioned commit itself aren't collisions, e.g. when re-running the build of a tag.
+    fn unique_version(
+        &self,
+        git_branch: &str,
+        regexes: &Regexes,
+        commit: &VersionedCommit,
+        semver: &Version,
+        bump: Bump,
+        rev: Option<&str>,
+    ) -> Result<Version> {
+        let mut version = self.update_version(git_branch, regexes, commit, semver, bump)?;
+        let unique = match self.options.unique_prerelease() {
+            _ if version.pre.is_empty() => return Ok(version),
+            UniquePrerelease::Off => return Ok(version),
+            UniquePrerelease::BranchHash => {
+                if regexes.branch_class(git_branch) == BranchClass::Feature {
+                    let hash = branch_hash(git_branch);
+                    version.pre = Prerelease::new(&format!("{}.{}", version.pre, hash))?;
+                    self.trace.record(
+                        "unique",
+                        Message::new("explain.unique_branch_hash")
+                            .arg("version", &version)
+                            .arg("hash", &hash),
+                    );
+                }
+                return Ok(version);
+            }
+            unique => unique,
+        };
+        let head = self
+            .git_command
+            .run(vec!["rev-parse", rev.unwrap_or("HEAD")])?;
+        let published = remote_tags(self.git_command)?;
+        let mut commit = *commit;
+        loop {
+            let mut tag_version = version.clone();
+            tag_version.build = BuildMetadata::EMPTY;
+            let tag = regexes.tag_name(&tag_version.to_string());
+            let sha = match published.get(&tag) {
+                Some(sha) if *sha != head => sha,
+                _ => return Ok(version),
+            };
+            let collision = format!(
+                "Version {} collides with the tag '{}' published on commit '{}' of origin",
+                version, tag, sha
+            );
+            if unique == UniquePrerelease::Fail {
+                return Err(Error::msg(format!(
+                    "{}. Use --unique-prerelease increment or branch-hash.",
+                    collision
+                )));
+            }
+            self.trace.record(
+                "unique",
+                Message::new("explain.unique_taken").arg("tag", &tag),
+            );
+            commit.counter += 1;
+            let next = self.update_version(git_branch, regexes, &commit, semver, bump)?;
+            // Schemes without a counter can't avoid the collision
+            if next == version {
+                return Err(Error::msg(format!(
+                    "{}, and the version scheme doesn't follow the counter",
+                    collision
+                )));
+            }
+            version = next;
+        }
+    }
+
+    fn update_version(
+        &self,
+        git_branch: &str,
+        regexes: &Regexes,
+        commit: &VersionedCommit,
+        semver: &Version,
+        bump: Bump,
+    ) -> Result<Version> {
+        let options = &self.options;
+        let labels = options.prerelease_labels();
+        let branch_class = regexes.branch_class(git_branch);
+        let label = match branch_class {
+            BranchClass::Main => String::new(),
+            BranchClass::Release => labels.release,
+            BranchClass::Hotfix => labels.hotfix,
+            BranchClass::Develop => labels.develop,
+            BranchClass::Support => labels.support,
+            BranchClass::Feature => labels.feature,
+            // Unique per pull request, so the labels of the branch patterns don't apply
+            BranchClass::PullRequest => format!(
+                "{}.{}",
+                labels.pull_request,
+                regexes.pull_request_number(git_branch).unwrap_or_default()
+            ),
+        };
+        let label = match branch_class {
+            BranchClass::PullRequest => &label,
+            _ => regexes.prerelease_label(git_branch).unwrap_or(&label),
+        };
+        let escaped_branch = regexes.escaped_branch(git_branch);
+        let version = self.scheme.version(&SchemeContext {
+            branch: git_branch,
+            branch_class,
+            base: semver,
+            bump,
+            label,
+            counter: PrereleaseCounter {
+                value: commit.counter,
+                format: options.config.counter_format.format(branch_class),
+                padding: options.config.counter_format.padding,
+                date: commit.date,
+                run_attempt: commit.run_attempt,
+            },
+            git_rev: commit.git_rev,
+            escaped_branch: &escaped_branch,
+            commit_date: commit.date,
+            now: commit.now,
+            build_number: options.build_number,
+            build_metadata: &options.config.build_metadata,
+        })?;
+        self.trace.record(
+            "version",
+            Message::new("explain.version")
+                .arg("version", &version)
+                .arg("semver", semver)
+                .arg("bump", trace::name(bump))
+                .arg("label", label)
+                .arg("counter", commit.counter)
+                .arg("branch", &escaped_branch),
+        );
+        Ok(version)
+    }
+}
+
+/// Adds the values of the configured environment variables to the build metadata and the output
+/// fields. Characters which aren't allowed in build metadata are replaced by `-`, and control
+/// characters are dropped from the output fields.
+fn apply_env(
+    mut version_info: VersionInfo,
+    config: &Config,
+    regexes: &Regexes,
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<VersionInfo> {
+    let env = |name: &str| env(name).filter(|value| !value.is_empty());
+    let mut builder = BuildMetadataBuilder::extend(
+        &version_info.version.build,
+        config.build_metadata.max_length,
+    );
+    for name in &config.build_metadata.env {
+        if let Some(value) = env(name) {
+            builder.push(&regexes.escape(&value))?;
+        }
+    }
+    version_info.version.build = builder.build()?;
+
+    let json = version_info.to_json();
+    for name in &config.output.env {
+        let field = name.to_lowercase();
+        if json.get(&field).is_some() {
+            return Err(Error::msg(format!(
+                "Output variable '{}' conflicts with the '{}' output field",
+                name, field
+            )));
+        }
+        if let Some(value) = env(name) {
+            let value = value.chars().filter(|c| !c.is_control()).collect();
+            version_info.env_fields.insert(field, value);
+        }
+    }
+    Ok(version_info)
+}
+
+/// The full `container_tag`, followed by the rolling tags of the `[container_tags]` rules,
+/// prefixed with the `--image-name`. The bootstrap version of branches without any commits
+/// doesn't get rolling tags, which would shadow the images of released versions.
+fn container_tags(version_info: &VersionInfo, options: &VersionOptions) -> Vec<String> {
+    let version = &version_info.version;
+    let rules = match version_info.git_rev {
+        Some(_) => options
+            .config
+            .container_tags
+            .rules(version_info.branch_class),
+        None => &[],
+    };
+    let mut tags = vec![version_info.container_tag.clone()];
+    for rule in rules {
+        let tag = rule
+            .replace("{major}", &version.major.to_string())
+            .replace("{minor}", &version.minor.to_string())
+            .replace("{patch}", &version.patch.to_string())
+            .replace("{channel}", &version_info.channel);
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    match &options.image_name {
+        Some(image_name) => tags
+            .iter()
+            .map(|tag| format!("{}:{}", image_name, tag))
+            .collect(),
+        None => tags,
+    }
+}
+
+fn channel_image(options: &VersionOptions, branch_class: BranchClass) -> Option<String> {
+    let channel = options.config.channels.channel(branch_class);
+    options
+        .config
+        .image
+        .as_ref()
+        .or(options.app_name.as_ref())
+        .map(|image| format!("{}:{}", image, channel))
+}
+
+/// Whether HEAD is a branch without any commits yet, e.g. right after `git init`: HEAD still names
+/// a branch but doesn't resolve to a commit. Any other failure, like a path outside a repository,
+/// is passed through.
+pub(crate) fn is_unborn(git_command: &impl GitCommandTrait) -> Result<bool, Error> {
+    let verified = match git_command.run(vec!["rev-parse", "-q", "--verify", "HEAD"]) {
+        Ok(rev) if !rev.is_empty() => return Ok(false),
+        verified => verified,
+    };
+    match git_command.run(vec!["symbolic-ref", "-q", "HEAD"]) {
+        Ok(_) => Ok(true),
+        Err(err) => verified.and(Err(err)),
+    }
+}
+
+fn get_empty_branch_info(
+    options: &VersionOptions,
+    regexes: &Regexes,
+    git_branch: String,
+    err: Error,
+) -> Result<VersionInfo, Error> {
+    if options.empty_branch() == EmptyBranch::Error {
+        return Err(Error::msg(format!(
+            "Branch '{}' doesn't have any commits to version.\nError: '{}'",
+            git_branch, err
+        )));
+    }
+    let mut semver = options.empty_branch_version();
+    let escaped_branch = regexes.escaped_branch(&git_branch);
+    if !escaped_branch.is_empty() {
+        semver.build = BuildMetadata::new(&escaped_branch)?;
+    }
+    let branch_class = regexes.branch_class(&git_branch);
+    Ok(VersionInfo {
+        git_branch,
+        git_rev: None,
+        rev_count: "0".to_string(),
+        container_tag: container_tag(&semver, options.container_tag_strategy()),
+        version: semver,
+        ahead_of_main: None,
+        behind_main: None,
+        branch_created_at: None,
+        branch_age_days: None,
+        days_since_last_commit: None,
+        changed_since_tag: true,
+        branch_class,
+        channel: options.config.channels.channel(branch_class).to_string(),
+        channel_image: channel_image(options, branch_class),
+        container_tags: vec![],
+        bump: None,
+        base_version: None,
+        prerelease_source: None,
+        pr_number: None,
+        signature_verified: None,
+        commit: CommitMetadata::default(),
+        env_fields: BTreeMap::new(),
+        trace: vec![],
+        builder: None,
+        inputs: None,
+        components: None,
+        cache_key: None,
+        dotnet: None,
+    })
+}
+
+/// The pre-release counter, and where it's from for the trace
+#[allow(clippy::too_many_arguments)]
+fn get_count(
+    git_command: &impl GitCommandTrait,
+    options: &VersionOptions,
+    rev_count: &str,
+    base_tag: Option<&str>,
+    branch_class: BranchClass,
+    default_branch: &str,
+    merge_strategy: Option<MergeStrategy>,
+    rev: Option<&str>,
+) -> Result<(u64, Message), Error> {
+    // Merge commits bring in the commits of the merged branch, while the first parents are the
+    // commits of the branch itself, one per merge
+    let count_strategy = options.count_strategy(merge_strategy);
+    let count = |range: &str| -> Result<u64> {
+        let mut args = vec!["rev-list", "--count"];
+        match count_strategy {
+            CountStrategy::All => {}
+            CountStrategy::FirstParent => args.push("--first-parent"),
+            CountStrategy::NoMerges => args.push("--no-merges"),
+        }
+        args.push(range);
+        Ok(git_command.run(with_path(args, options))?.parse::<u64>()?)
+    };
+    let counter = match (options.counter(), branch_class) {
+        (Counter::BuildNumber, _) => (
+            options.build_number.ok_or(Error::msg(
+                "The build-number counter requires a --build-number",
+            ))?,
+            Message::new("explain.counter_build_number"),
+        ),
+        (Counter::Total, _) => (
+            rev_count.parse::<u64>()?,
+            Message::new("explain.counter_total"),
+        ),
+        // Squash merges leave the commits of the branch out of the history of the base branch, so
+        // the distance to the tag would change with every merge into the base branch
+        (Counter::MergeBase, BranchClass::Feature | BranchClass::PullRequest) => {
+            let base = get_counter_base(git_command, options, default_branch);
+            (
+                count(&format!("{}..{}", base, rev.unwrap_or("HEAD")))?,
+                Message::new("explain.counter_ahead").arg("base", &base),
+            )
+        }
+        (Counter::Distance | Counter::MergeBase, _) => match base_tag {
+            Some(tag) => (
+                count(&format!("{}..{}", tag, rev.unwrap_or("HEAD")))?,
+                match count_strategy {
+                    CountStrategy::All => Message::new("explain.counter_since_tag"),
+                    CountStrategy::FirstParent => Message::new("explain.counter_first_parent"),
+                    CountStrategy::NoMerges => Message::new("explain.counter_no_merges"),
+                }
+                .arg("tag", tag),
+            ),
+            None => (
+                rev_count.parse::<u64>()?,
+                Message::new("explain.counter_total"),
+            ),
+        },
+    };
+    Ok(counter)
+}
+
+/// The `--counter-base` option, or else `develop` when it exists, or else the default branch
+fn get_counter_base(
+    git_command: &impl GitCommandTrait,
+    options: &VersionOptions,
+    default_branch: &str,
+) -> String {
+    if let Some(counter_base) = &options.counter_base {
+        return counter_base.clone();
+    }
+    match git_command.run(vec!["rev-parse", "--verify", "--quiet", "develop"]) {
+        Ok(_) => "develop".to_string(),
+        Err(_) => default_branch.to_string(),
+    }
+}
+
+fn get_version(
+    git_command: &impl GitCommandTrait,
+    regexes: &Regexes,
+    trace: &Trace,
+    git_branch: &str,
+    base_tag: Option<&str>,
+    rev: Option<&str>,
+) -> Result<Version> {
+    let tag: String;
+    let semver: Version;
+    let branch_class = regexes.branch_class(git_branch);
+    // For release branches, get the version from the branch name
+    if branch_class == BranchClass::Release {
+        let version = regexes
+            .branch_version(git_branch)
+            .ok_or(VersionError::InvalidBranch(
+                "Invalid branch name format".to_string(),
+            ))?;
+        semver = Version::parse(version).map_err(|err| {
+            VersionError::InvalidBranch(format!(
+                "Version '{}' of the branch '{}' isn't SemVer: {}",
+                version, git_branch, err
+            ))
+        })?;
+        trace.record(
+            "tag",
+            Message::new("explain.tag_release_branch").arg("version", &semver),
+        );
+    } else {
+        // For all other branches, get the version from the latest tag, or the tag at the branch
+        // point for hotfix branches
+        // Fall back to 0.0.0 if no tags are found
+        tag = base_tag
+            .map(str::to_string)
+            .unwrap_or(regexes.tag_name("0.0.0"));
+
+        // For the main branch, a tag must exist on the current commit
+        if branch_class == BranchClass::Main {
+            // Git fails without a tag on the commit
+            let exact_tag = describe_tag(git_command, regexes, true, rev).ok();
+            if exact_tag.as_ref() != Some(&tag) {
+                return Err(VersionError::NoTagOnMain(
+                    "Cannot version a production release from a commit without a tag".to_string(),
+                )
+                .into());
+            }
+        }
+
+        trace.record(
+            "tag",
+            match (base_tag, branch_class) {
+                (None, _) => Message::new("explain.tag_none_from").arg("tag", &tag),
+                (Some(_), BranchClass::Main) => {
+                    Message::new("explain.tag_on_commit").arg("tag", &tag)
+                }
+                (Some(_), BranchClass::Hotfix) => {
+                    Message::new("explain.tag_branch_point").arg("tag", &tag)
+                }
+                (Some(_), BranchClass::Support) => Message::new("explain.tag_support")
+                    .arg("tag", &tag)
+                    .arg("line", regexes.support_line(git_branch).unwrap_or_default()),
+                (Some(_), _) => Message::new("explain.tag_reachable").arg("tag", &tag),
+            },
+        );
+        semver = parse_tag_version(regexes, &tag)?;
+    }
+    Ok(semver)
+}
+
+/// Highest version of the local and remote release branches, without its pre-release, for
+/// `--release-branch-base`
+fn get_release_branch_version(
+    git_command: &impl GitCommandTrait,
+    regexes: &Regexes,
+) -> Result<Option<(String, Version)>> {
+    let release_branches = all_branches(git_command)?.into_iter().filter_map(|branch| {
+        let caps = regexes.rc_branches.captures(&branch)?;
+        let mut version = Version::parse(caps.name("version")?.as_str()).ok()?;
+        version.pre = Prerelease::EMPTY;
+        version.build = BuildMetadata::EMPTY;
+        Some((branch, version))
+    });
+    Ok(release_branches.max_by(|(_, a), (_, b)| a.cmp(b)))
+}
+
+/// Which branch pattern classified the branch, for `--explain`
+fn explain_class(regexes: &Regexes, git_branch: &str) -> Message {
+    let class = regexes.branch_class(git_branch);
+    if let Some(number) = regexes.pull_request_number(git_branch) {
+        return Message::new("explain.class_pull_request").arg("number", number);
+    }
+    if let Some((name, regex, _)) = regexes.custom_branch(git_branch) {
+        return Message::new("explain.class_custom")
+            .arg("class", trace::name(class))
+            .arg("name", name)
+            .arg("pattern", regex.as_str());
+    }
+    let mut classes = regexes.matching_classes(git_branch).into_iter();
+    match (classes.next(), regexes.classifying_regex(git_branch)) {
+        (Some(_), Some(regex)) => {
+            let others: Vec<String> = classes.map(trace::name).collect();
+            let message = match others.is_empty() {
+                true => Message::new("explain.class"),
+                false => Message::new("explain.class_first").arg("others", others.join(", ")),
+            };
+            message
+                .arg("class", trace::name(class))
+                .arg("pattern", regex.as_str())
+        }
+        _ => Message::new("explain.class_none"),
+    }
+}
+
+/// How the version is formed from the base tag: the `--on-tag` behavior when `HEAD` is exactly on
+/// the tag of a bumped branch, e.g. develop after merging the release back, or else `bump`
+fn on_tag(
+    git_command: &impl GitCommandTrait,
+    regexes: &Regexes,
+    options: &VersionOptions,
+    git_branch: &str,
+    base_tag: &str,
+    rev: Option<&str>,
+) -> OnTag {
+    match (options.on_tag(), regexes.branch_class(git_branch)) {
+        (OnTag::Bump, _) | (_, BranchClass::Main | BranchClass::Release) => OnTag::Bump,
+        (on_tag, _) => match describe_tag(git_command, regexes, true, rev) {
+            Ok(tag) if tag == base_tag => on_tag,
+            _ => OnTag::Bump,
+        },
+    }
+}
+
+/// Latest tag at the merge-base with the default branch, the release the hotfix branch branched
+/// off from. The latest tag of HEAD when the default branch doesn't exist.
+fn get_branch_point_tag(
+    git_command: &impl GitCommandTrait,
+    regexes: &Regexes,
+    default_branch: &str,
+    rev: Option<&str>,
+) -> Option<String> {
+    match git_command.run(vec!["merge-base", rev.unwrap_or("HEAD"), default_branch]) {
+        Ok(merge_base) => describe_tag(git_command, regexes, false, Some(&merge_base)).ok(),
+        Err(_) => get_latest_tag(git_command, regexes, rev),
+    }
+}
+
+/// Highest release tag of the line of a support branch, e.g. `1.4.2` for `support/1.x`, also
+/// when it's on another branch. The tags of the line are listed by version, highest first.
+fn get_support_tag(
+    git_command: &impl GitCommandTrait,
+    regexes: &Regexes,
+    git_branch: &str,
+) -> Result<String> {
+    let line = regexes.support_line(git_branch).unwrap_or_default();
+    let parts: Vec<u64> = line
+        .split('.')
+        .map(|part| part.parse::<u64>())
+        .collect::<Result<_, _>>()
+        .ok()
+        .filter(|parts: &Vec<u64>| (1..=2).contains(&parts.len()))
+        .ok_or(VersionError::InvalidBranch(format!(
+            "Line '{}' of the support branch '{}' isn't a major or major.minor version",
+            line, git_branch
+        )))?;
+    let mut args = vec!["tag", "--list", "--sort=-v:refname"];
+    // Tag patterns may match tags the format doesn't
+    let glob = regexes.tag_name(&format!("{}.*", line));
+    if !regexes.tag_pattern {
+        args.push(&glob);
+    }
+    let tags = git_command.run(args)?;
+    tags.lines()
+        .filter(|tag| !regexes.tag_exclude.iter().any(|exclude| exclude == tag))
+        .filter_map(|tag| Some((tag, parse_tag_version(regexes, tag).ok()?)))
+        .find(|(_, version)| {
+            version.pre.is_empty()
+                && version.major == parts[0]
+                && parts.get(1).is_none_or(|minor| version.minor == *minor)
+        })
+        .map(|(tag, _)| tag.to_string())
+        .ok_or(
+            VersionError::InvalidBranch(format!(
+                "No release of the {}.x line is tagged for the support branch '{}'",
+                line, git_branch
+            ))
+            .into(),
+        )
+}
+
+/// Shallow clones make the latest tag and the commit count unreliable, so they're either made
+/// complete with `--auto-deepen`, or rejected
+fn check_shallow(git_command: &impl GitCommandTrait, options: &VersionOptions) -> Result<()> {
+    if git_command.run(vec!["rev-parse", "--is-shallow-repository"])? != "true" {
+        return Ok(());
+    }
+    if !options.auto_deepen {
+        return Err(Error::msg(
+            "The repository is a shallow clone, so the latest tag and the commit count can't be determined. \
+            Fetch the full history (e.g. `fetch-depth: 0` for actions/checkout, `GIT_DEPTH: 0` in GitLab CI \
+            or `fetchDepth: 0` in Azure Pipelines), or use --auto-deepen.",
+        ));
+    }
+    git_command.run(vec!["fetch", "--unshallow", "--tags"])?;
+    Ok(())
+}
+
+/// Errors or warns, depending on the configuration, when the branch matches multiple classes
+fn check_ambiguous_branch(regexes: &Regexes, config: &Config, git_branch: &str) -> Result<()> {
+    let classes = regexes.matching_classes(git_branch);
+    // Custom classes are matched first on purpose
+    if classes.len() < 2 || regexes.custom_branch(git_branch).is_some() {
+        return Ok(());
+    }
+    let message = format!(
+        "Branch '{}' matches the {:?} branch patterns, classified as {:?} by priority. Run `explain` for details.",
+        git_branch, classes, classes[0]
+    );
+    match config.branches.ambiguous {
+        AmbiguousBranch::Error => Err(Error::msg(message)),
+        AmbiguousBranch::Warn => {
+            eprintln!("Warning: {}", message);
+            Ok(())
+        }
+    }
+}
+
+/// The `--default-branch` option, or else the branch `origin/HEAD` points at. It's set by
+/// `git clone`, or with `git remote set-head origin --auto`.
+pub(crate) fn get_default_branch(
+    git_command: &impl GitCommandTrait,
+    options: &VersionOptions,
+) -> String {
+    if let Some(default_branch) = &options.default_branch {
+        return default_branch.clone();
+    }
+    git_command
+        .run(vec!["symbolic-ref", "--short", "refs/remotes/origin/HEAD"])
+        .ok()
+        .and_then(|remote_head| remote_head.strip_prefix("origin/").map(str::to_string))
+        .unwrap_or(options.default_branch().to_string())
+}
+
+/// The `--merge-strategy`, with `auto` detected from the latest 100 first-parent commits of the
+/// default branch: squash when more of them are squash merges, which GitHub suffixes with the pull
+/// request number, e.g. `Add login (#12)`, than merge commits. `None` without a merge strategy.
+pub(crate) fn get_merge_strategy(
+    git_command: &impl GitCommandTrait,
+    options: &VersionOptions,
+) -> Option<MergeStrategy> {
+    match options.merge_strategy? {
+        MergeStrategy::Auto => {
+            let default_branch = get_default_branch(git_command, options);
+            let log = git_command
+                .run(vec![
+                    "log",
+                    "--first-parent",
+                    "--max-count=100",
+                    "--format=%p%x1f%s",
+                    &default_branch,
+                ])
+                .ok()?;
+            let squash_subject = Regex::new(r"\(#\d+\)$").unwrap();
+            let (mut merges, mut squashes) = (0, 0);
+            for (parents, subject) in log.lines().filter_map(|line| line.split_once('\x1f')) {
+                if parents.contains(' ') {
+                    merges += 1;
+                } else if squash_subject.is_match(subject.trim_end()) {
+                    squashes += 1;
+                }
+            }
+            Some(match squashes > merges {
+                true => MergeStrategy::Squash,
+                false => MergeStrategy::Merge,
+            })
+        }
+        strategy => Some(strategy),
+    }
+}
+
+/// Commits HEAD (or `rev`) is ahead of and behind the default branch. Unknown when the default
+/// branch doesn't exist (e.g. not fetched on CI).
+fn get_ahead_behind(
+    git_command: &impl GitCommandTrait,
+    default_branch: &str,
+    rev: Option<&str>,
+) -> (Option<u32>, Option<u32>) {
+    let counts = git_command.run(vec![
+        "rev-list",
+        "--left-right",
+        "--count",
+        &format!("{}...{}", rev.unwrap_or("HEAD"), default_branch),
+    ]);
+    let counts: Vec<Option<u32>> = match counts {
+        Ok(counts) => counts
+            .split_whitespace()
+            .map(|count| count.parse::<u32>().ok())
+            .collect(),
+        Err(_) => vec![],
+    };
+    match counts.as_slice() {
+        [ahead, behind] => (*ahead, *behind),
+        _ => (None, None),
+    }
+}
+
+/// One `git show` of the commit, and the creation date of the base tag
+fn get_commit_metadata(
+    git_command: &impl GitCommandTrait,
+    base_tag: Option<&str>,
+    rev: Option<&str>,
+) -> CommitMetadata {
+    let show = git_command
+        .run(vec![
+            "show",
+            "-s",
+            "--format=%H%n%cI%n%ct%n%cn <%ce>",
+            rev.unwrap_or("HEAD"),
+        ])
+        .unwrap_or_default();
+    let mut lines = show.lines().map(str::to_string);
+    let tag = base_tag
+        .and_then(|tag| {
+            git_command
+                .run(vec![
+                    "for-each-ref",
+                    TAG_METADATA_FORMAT,
+                    &format!("refs/tags/{}", tag),
+                ])
+                .ok()
+        })
+        .unwrap_or_default();
+    let (header, annotation) = tag.split_once('\n').unwrap_or((&tag, ""));
+    let mut fields = header.split('\t');
+    let tag_date = fields.next().filter(|date| !date.is_empty());
+    let annotated = fields.next() == Some("tag");
+    let tagger = fields.next().filter(|_| annotated);
+    let signed = fields.next() == Some("signed");
+    let (subject, body) = annotation.split_once('\n').unwrap_or((annotation, ""));
+    let tag_message = match body.trim() {
+        "" => subject.trim().to_string(),
+        body => format!("{}\n\n{}", subject.trim(), body),
+    };
+    CommitMetadata {
+        tag: base_tag.map(str::to_string),
+        sha: lines.next(),
+        timestamp: lines.next(),
+        epoch: lines.next().and_then(|epoch| epoch.parse().ok()),
+        committer: lines.next(),
+        tag_date: tag_date.map(str::to_string),
+        tag_message: annotated.then_some(tag_message),
+        tagger: tagger.map(str::to_string),
+        tag_signed: annotated.then_some(signed),
+    }
+}
+
+/// Creation date, object type, tagger and signature presence of a tag, followed by the subject and
+/// body of its annotation. The creation date is the committer date for lightweight tags.
+const TAG_METADATA_FORMAT: &str = "--format=%(creatordate:iso-strict)%09%(objecttype)%09%(taggername) %(taggeremail)%09%(if)%(contents:signature)%(then)signed%(end)%0a%(contents:subject)%0a%(contents:body)";
+
+struct BranchAge {
+    /// Committer date of the merge-base with the default branch, ISO-8601
+    created_at: Option<String>,
+    age_days: Option<u64>,
+    days_since_last_commit: Option<u64>,
+    last_commit_timestamp: Option<u64>,
+}
+
+/// The commit the version scheme versions
+#[derive(Clone, Copy)]
+struct VersionedCommit<'a> {
+    git_rev: &'a str,
+    counter: u64,
+    /// Attempt of the CI run, for the `run-attempt` counter format
+    run_attempt: u64,
+    /// Date of the commit following the date source
+    date: u64,
+    now: u64,
+}
+
+/// Short hash of the branch name for `--unique-prerelease branch-hash`. Hashes of digits only
+/// start with a letter instead, as numeric pre-release identifiers can't have leading zeros.
+fn branch_hash(branch: &str) -> String {
+    let hash = format!("{:016x}", fnv1a(branch.as_bytes()))[..6].to_string();
+    match hash.bytes().all(|byte| byte.is_ascii_digit()) {
+        true => format!("g{}", &hash[1..]),
+        false => hash,
+    }
+}
+
+/// The tags of `origin` with the commits they point at, for `--unique-prerelease`
+fn remote_tags(git_command: &impl GitCommandTrait) -> Result<BTreeMap<String, String>> {
+    let mut tags = BTreeMap::new();
+    for line in git_command
+        .run(vec!["ls-remote", "--tags", "origin"])?
+        .lines()
+    {
+        let Some((sha, name)) = line.split_once('\t') else {
+            continue;
+        };
+        let Some(name) = name.strip_prefix("refs/tags/") else {
+            continue;
+        };
+        // The commit of an annotated tag follows it as peeled `<tag>^{}`
+        let name = name.strip_suffix("^{}").unwrap_or(name);
+        tags.insert(name.to_string(), sha.to_string());
+    }
+    Ok(tags)
+}
+
+/// The branch creation is approximated by the merge-base with the default branch, as git doesn't
+/// record when branches are created
+fn get_branch_age(
+    git_command: &impl GitCommandTrait,
+    default_branch: &str,
+    date_source: DateSource,
+    now: u64,
+    rev: Option<&str>,
+) -> BranchAge {
+    let head = rev.unwrap_or("HEAD");
+    let days_since = |timestamp: u64| now.saturating_sub(timestamp) / SECONDS_PER_DAY;
+    let date = date_source.log_placeholder();
+    let merge_base = git_command
+        .run(vec!["merge-base", head, default_branch])
+        .and_then(|merge_base| {
+            git_command.run(vec![
+                "log",
+                "-1",
+                &format!("--format=%{0}t%n%{0}I", date),
+                &merge_base,
+            ])
+        })
+        .ok();
+    let (created_timestamp, created_at) = match merge_base.as_ref().and_then(|m| m.split_once('\n'))
+    {
+        Some((timestamp, date)) => (timestamp.parse::<u64>().ok(), Some(date.to_string())),
+        None => (None, None),
+    };
+    let last_commit_timestamp = git_command
+        .run(vec!["log", "-1", &format!("--format=%{}t", date), head])
+        .ok()
+        .and_then(|timestamp| timestamp.parse::<u64>().ok());
+    BranchAge {
+        created_at,
+        age_days: created_timestamp.map(days_since),
+        days_since_last_commit: last_commit_timestamp.map(days_since),
+        last_commit_timestamp,
+    }
+}
+
+/// Date of the commit following the date source, the time of `--reproducible` calculations
+fn get_commit_timestamp(
+    git_command: &impl GitCommandTrait,
+    options: &VersionOptions,
+    rev: Option<&str>,
+) -> Option<u64> {
+    let format = format!("--format=%{}t", options.date_source().log_placeholder());
+    git_command
+        .run(vec!["log", "-1", &format, rev.unwrap_or("HEAD")])
+        .ok()?
+        .parse()
+        .ok()
+}
+
+/// Fails or warns, following the `[calver] clock_skew`, when the system clock is behind the date
+/// of the commit, the latest remote ref or `SOURCE_DATE_EPOCH`, which would date the calendar
+/// versions in the past
+fn check_clock_skew(
+    git_command: &impl GitCommandTrait,
+    options: &VersionOptions,
+    trace: &Trace,
+    commit_timestamp: Option<u64>,
+    source_date_epoch: Option<u64>,
+    now: u64,
+) -> Result<()> {
+    let config = &options.config.calver;
+    let remote_ref = git_command
+        .run(vec![
+            "for-each-ref",
+            "--sort=-committerdate",
+            "--count=1",
+            "--format=%(committerdate:unix) %(refname:short)",
+            "refs/remotes",
+        ])
+        .ok()
+        .and_then(|line| {
+            let (timestamp, name) = line.split_once(' ')?;
+            Some((
+                timestamp.parse::<u64>().ok()?,
+                Message::new("explain.clock_remote_ref").arg("ref", name),
+            ))
+        });
+    let latest = [
+        commit_timestamp.map(|timestamp| (timestamp, Message::new("explain.clock_commit"))),
+        remote_ref,
+        source_date_epoch.map(|epoch| (epoch, Message::new("explain.clock_source_date_epoch"))),
+    ]
+    .into_iter()
+    .flatten()
+    .max_by_key(|(timestamp, _)| *timestamp);
+    let Some((timestamp, source)) = latest else {
+        return Ok(());
+    };
+    if timestamp <= now.saturating_add(config.max_clock_skew) {
+        return Ok(());
+    }
+    let message = Message::new("explain.clock")
+        .arg("hours", (timestamp - now) / 3600)
+        .message("source", source);
+    trace.record("clock", message.clone());
+    let message = message.to_string();
+    match config.clock_skew {
+        ClockSkew::Fail => Err(Error::msg(message)),
+        _ => {
+            eprintln!("Warning: {}", message);
+            Ok(())
+        }
+    }
+}
+
+/// Appends the pathspec of the `--path` option to a git command
+pub(crate) fn with_path<'a>(mut args: Vec<&'a str>, options: &'a VersionOptions) -> Vec<&'a str> {
+    if let Some(path) = &options.path {
+        args.extend(["--", path.as_str()]);
+    }
+    args
+}
+
+fn get_changed_since_tag(
+    git_command: &impl GitCommandTrait,
+    regexes: &Regexes,
+    options: &VersionOptions,
+    rev: Option<&str>,
+) -> Result<bool> {
+    let tag = match get_latest_path_tag(git_command, regexes, options, rev)? {
+        Some(tag) => tag,
+        None => return Ok(true),
+    };
+    let range = format!("{}..{}", tag, rev.unwrap_or("HEAD"));
+    let count = git_command.run(with_path(vec!["rev-list", "--count", &range], options))?;
+    Ok(count != "0")
+}
+
+/// Most significant bump of the Conventional Commits since the latest tag, up to `rev` (`HEAD`
+/// when not provided). The commits of squash merges count one by one with the squash strategy.
+pub(crate) fn get_conventional_bump(
+    git_command: &impl GitCommandTrait,
+    regexes: &Regexes,
+    options: &VersionOptions,
+    merge_strategy: Option<MergeStrategy>,
+    rev: Option<&str>,
+) -> Result<Bump> {
+    let messages = get_messages_since_tag(git_command, regexes, options, rev)?;
+    let messages: Vec<String> = messages
+        .split('\0')
+        .flat_map(|message| match merge_strategy {
+            Some(MergeStrategy::Squash) => conventional_commits::squashed_messages(message),
+            _ => vec![message.to_string()],
+        })
+        .collect();
+    Ok(conventional_commits::analyze(
+        messages.iter().map(String::as_str),
+    ))
+}
+
+/// Messages of the commits (touching the `--path`) since the latest tag, separated by NUL
+/// characters
+fn get_messages_since_tag(
+    git_command: &impl GitCommandTrait,
+    regexes: &Regexes,
+    options: &VersionOptions,
+    rev: Option<&str>,
+) -> Result<String> {
+    let head = rev.unwrap_or("HEAD");
+    let range = match get_latest_path_tag(git_command, regexes, options, rev)? {
+        Some(tag) => format!("{}..{}", tag, head),
+        None => head.to_string(),
+    };
+    git_command.run(with_path(vec!["log", "--format=%B%x00", &range], options))
+}
+
+/// Bump hinted by the branch name, e.g. `feature/major/new-api`, or the commit messages since
+/// the latest tag, e.g. a `+semver: minor` line. The most significant hint wins, and the hints
+/// only raise the bump of the bump strategy. A patch without hints or when they're disabled.
+fn get_increment_hint(
+    git_command: &impl GitCommandTrait,
+    regexes: &Regexes,
+    options: &VersionOptions,
+    git_branch: &str,
+    rev: Option<&str>,
+) -> Result<Bump> {
+    let increment = &options.config.increment;
+    if !increment.enabled {
+        return Ok(Bump::Patch);
+    }
+    let regex = |name: &str, pattern: &str| {
+        Regex::new(pattern).map_err(|err| {
+            Error::msg(format!(
+                "Invalid [increment] {} regex '{}': {}",
+                name, pattern, err
+            ))
+        })
+    };
+    if regex("major_branch", &increment.major_branch)?.is_match(git_branch) {
+        return Ok(Bump::Major);
+    }
+    let minor_branch = regex("minor_branch", &increment.minor_branch)?.is_match(git_branch);
+    let mut rules = vec![
+        (regex("major", &increment.major)?, Bump::Major),
+        (regex("minor", &increment.minor)?, Bump::Minor),
+    ];
+    for rule in &increment.messages {
+        rules.push((regex("messages", &rule.regex)?, rule.bump));
+    }
+    let messages = get_messages_since_tag(git_command, regexes, options, rev)?;
+    let hint = messages
+        .split('\0')
+        .filter(|message| !message.trim().is_empty())
+        .flat_map(|message| {
+            rules
+                .iter()
+                .filter(|(regex, _)| regex.is_match(message))
+                .map(|(_, bump)| *bump)
+        })
+        .max();
+    Ok(match (hint, minor_branch) {
+        (Some(Bump::Major), _) => Bump::Major,
+        (_, true) | (Some(Bump::Minor), _) => Bump::Minor,
+        _ => Bump::Patch,
+    })
+}
+
+/// Latest tag of the app reachable from `rev` (`HEAD` when not provided), following the
+/// `[tag] selection`
+pub(crate) fn get_latest_tag(
+    git_command: &impl GitCommandTrait,
+    regexes: &Regexes,
+    rev: Option<&str>,
+) -> Option<String> {
+    match regexes.tag_selection {
+        TagSelection::Nearest => describe_tag(git_command, regexes, false, rev).ok(),
+        TagSelection::Highest => version_tags(git_command, regexes, Some(rev.unwrap_or("HEAD")))
+            .ok()?
+            .into_iter()
+            .next()
+            .map(|(tag, _)| tag),
+    }
+}
+
+/// Latest tag of the app reachable from `rev` for the `--path`. Without an app name scoping the
+/// tags by name, the apps of a monorepo share the tags, and they're looked up from the latest
+/// commit touching the path instead, so the releases of the other apps since don't count.
+pub(crate) fn get_latest_path_tag(
+    git_command: &impl GitCommandTrait,
+    regexes: &Regexes,
+    options: &VersionOptions,
+    rev: Option<&str>,
+) -> Result<Option<String>> {
+    let (Some(path), None) = (&options.path, &options.app_name) else {
+        return Ok(get_latest_tag(git_command, regexes, rev));
+    };
+    let last_change = git_command.run(vec!["rev-list", "-1", rev.unwrap_or("HEAD"), "--", path])?;
+    match last_change.is_empty() {
+        true => Ok(get_latest_tag(git_command, regexes, rev)),
+        false => Ok(get_latest_tag(git_command, regexes, Some(&last_change))),
+    }
+}
+
+/// The tags of the app with their versions, highest first by SemVer precedence. Only the tags
+/// reachable from `merged` when provided. Tags which aren't SemVer are skipped.
+pub(crate) fn version_tags(
+    git_command: &impl GitCommandTrait,
+    regexes: &Regexes,
+    merged: Option<&str>,
+) -> Result<Vec<(String, Version)>> {
+    let mut args = vec!["for-each-ref", "--format=%(refname:lstrip=2)"];
+    if let Some(merged) = merged {
+        args.extend(["--merged", merged]);
+    }
+    args.push("refs/tags");
+    let mut tags: Vec<(String, Version)> = git_command
+        .run(args)?
+        .lines()
+        // The tags created after `--as-of` are excluded by name
+        .filter(|tag| !regexes.tag_exclude.contains(&glob_escape(tag)))
+        .filter_map(|tag| Some((tag.to_string(), parse_tag_version(regexes, tag).ok()?)))
+        .collect();
+    tags.sort_by(|(_, a), (_, b)| b.cmp_precedence(a));
+    Ok(tags)
+}
+
+/// Commit and time `--as-of` resolved to
+struct AsOf {
+    commit: String,
+    /// Unix timestamp of the date, or the committer date of the commit
+    timestamp: u64,
+    /// Whether a commit was given. Its own tags are kept, even when created after the commit,
+    /// e.g. the release tag of a main branch commit.
+    is_commit: bool,
+}
+
+/// The commit, or else the last commit on the first-parent history of `head` before the date, as
+/// commits merged later may be older than the date
+fn resolve_as_of(git_command: &impl GitCommandTrait, as_of: &str, head: &str) -> Result<AsOf> {
+    let commit_ref = format!("{}^{{commit}}", as_of);
+    if let Ok(commit) = git_command.run(vec!["rev-parse", "-q", "--verify", &commit_ref]) {
+        let timestamp = git_command.run(vec!["log", "-1", "--format=%ct", &commit])?;
+        return Ok(AsOf {
+            commit,
+            timestamp: timestamp.parse::<u64>()?,
+            is_commit: true,
+        });
+    }
+    // `git rev-parse` converts the date to a `--min-age=<timestamp>` option
+    let before = format!("--before={}", as_of);
+    let timestamp = git_command
+        .run(vec!["rev-parse", &before])?
+        .strip_prefix("--min-age=")
+        .and_then(|timestamp| timestamp.parse::<u64>().ok())
+        .ok_or(Error::msg(format!(
+            "Cannot parse the --as-of date or commit '{}'",
+            as_of
+        )))?;
+    let commit = git_command.run(vec!["rev-list", "-1", "--first-parent", &before, head])?;
+    if commit.is_empty() {
+        return Err(Error::msg(format!(
+            "{} doesn't have any commits before '{}'",
+            head, as_of
+        )));
+    }
+    Ok(AsOf {
+        commit,
+        timestamp,
+        is_commit: false,
+    })
+}
+
+/// Commit of `--ref`
+fn resolve_commit(git_command: &impl GitCommandTrait, git_ref: &str) -> Result<String> {
+    git_command
+        .run(vec![
+            "rev-parse",
+            "-q",
+            "--verify",
+            &format!("{}^{{commit}}", git_ref),
+        ])
+        .map_err(|_| Error::msg(format!("Ref '{}' doesn't exist", git_ref)))
+}
+
+/// Commit merging `head` into the target branch, created without touching the working tree, the
+/// index or any ref. The remote branch, which CI clones mostly only have, is merged into, or else
+/// the local branch.
+fn simulate_merge(git_command: &impl GitCommandTrait, target: &str, head: &str) -> Result<String> {
+    let target_ref = [
+        format!("refs/remotes/origin/{}", target),
+        format!("refs/heads/{}", target),
+    ]
+    .into_iter()
+    .find(|target_ref| {
+        git_command
+            .run(vec!["rev-parse", "-q", "--verify", target_ref])
+            .is_ok()
+    })
+    .ok_or(Error::msg(format!(
+        "Target branch '{}' doesn't exist. Fetch it, e.g. with `git fetch origin {}`.",
+        target, target
+    )))?;
+    let tree = git_command
+        .run(vec!["merge-tree", "--write-tree", &target_ref, head])
+        .map_err(|err| {
+            Error::msg(format!(
+                "Cannot simulate merging into '{}': {}",
+                target, err
+            ))
+        })?;
+    // The identity is required in CI clones without one, the commit is never referenced
+    git_command.run(vec![
+        "-c",
+        "user.name=version-vine",
+        "-c",
+        "user.email=version-vine@localhost",
+        "commit-tree",
+        tree.lines().next().unwrap_or_default(),
+        "-p",
+        &target_ref,
+        "-p",
+        head,
+        "-m",
+        "Simulated merge",
+    ])
+}
+
+/// Tags created after the `--as-of` time, as `git describe --exclude` globs. The creation date of
+/// lightweight tags is the committer date of their commit.
+fn tags_created_after(git_command: &impl GitCommandTrait, as_of: &AsOf) -> Result<Vec<String>> {
+    let tags = git_command.run(vec![
+        "for-each-ref",
+        "--format=%(refname:lstrip=2)%09%(creatordate:unix)%09%(objectname)%09%(*objectname)",
+        "refs/tags",
+    ])?;
+    Ok(tags
+        .lines()
+        .filter_map(|line| {
+            let [name, created, object, commit] = line.split('\t').collect::<Vec<_>>()[..] else {
+                return None;
+            };
+            // Annotated tags point at the commit when dereferenced (`*`)
+            let commit = if commit.is_empty() { object } else { commit };
+            let created_later = created.parse::<u64>().ok()? > as_of.timestamp;
+            let own_tag = as_of.is_commit && commit == as_of.commit;
+            (created_later && !own_tag).then(|| glob_escape(name))
+        })
+        .collect())
+}
+
+/// Nearest tag of the app reachable from `rev` (`HEAD` when not provided), or pointing at it with
+/// `exact_match`. With a `[tag] pattern`, the tags not matching it are excluded one by one, as
+/// `git describe` only filters by globs.
+pub(crate) fn describe_tag(
+    git_command: &impl GitCommandTrait,
+    regexes: &Regexes,
+    exact_match: bool,
+    rev: Option<&str>,
+) -> Result<String> {
+    let mut excluded: Vec<String> = vec![];
+    loop {
+        let mut args = vec!["describe", "--abbrev=0"];
+        if exact_match {
+            args.push("--exact-match");
+        }
+        args.extend(regexes.describe_filters());
+        for tag in &excluded {
+            args.extend(["--exclude", tag.as_str()]);
+        }
+        args.push("--tags");
+        args.extend(rev);
+        let tag = git_command.run(args)?;
+        if !regexes.tag_pattern || regexes.tag.is_match(&tag) {
+            return Ok(tag);
+        }
+        excluded.push(glob_escape(&tag));
+    }
+}
+
+/// Escapes the glob characters of `git describe --exclude`
+fn glob_escape(value: &str) -> String {
+    value
+        .chars()
+        .flat_map(|c| match c {
+            '*' | '?' | '[' | '\\' => vec!['\\', c],
+            c => vec![c],
+        })
+        .collect()
+}
+
+/// Files of the `file` version source, in the repository root or the `--path`
+const VERSION_FILES: [&str; 2] = ["VERSION", "version.txt"];
+
+/// Base version of the first `--version-source` providing one before the `tag` source. `None`
+/// when the latest tag comes first, or when no source provides a version.
+fn get_source_version(
+    git_command: &impl GitCommandTrait,
+    options: &VersionOptions,
+    trace: &Trace,
+    base_tag: Option<&str>,
+    rev: Option<&str>,
+) -> Result<Option<Version>> {
+    let rev = rev.unwrap_or("HEAD");
+    let parse = |value: &str, source: &str| {
+        Version::parse(value.trim().trim_start_matches('v')).map_err(|err| {
+            Error::from(VersionError::ParseError(format!(
+                "Version '{}' of {} isn't SemVer: {}",
+                value.trim(),
+                source,
+                err
+            )))
+        })
+    };
+    for source in options.version_sources() {
+        match source {
+            VersionSource::Tag if base_tag.is_some() => return Ok(None),
+            VersionSource::Tag => {}
+            VersionSource::File => {
+                let dir = options.path.as_deref().unwrap_or_default();
+                for file in VERSION_FILES {
+                    let path = match dir.trim_end_matches('/') {
+                        "" | "." => file.to_string(),
+                        dir => format!("{}/{}", dir, file),
+                    };
+                    // The file as committed, also for other refs than the checkout
+                    let Ok(content) = git_command.run(vec!["show", &format!("{}:./{}", rev, path)])
+                    else {
+                        continue;
+                    };
+                    let version = parse(&content, &format!("the file '{}'", path))?;
+                    trace.record(
+                        "tag",
+                        Message::new("explain.tag_file")
+                            .arg("version", &version)
+                            .arg("path", &path),
+                    );
+                    return Ok(Some(version));
+                }
+            }
+            VersionSource::Trailer => {
+                let trailers = git_command.run(vec![
+                    "log",
+                    "-1",
+                    "--format=%(trailers:key=Version,valueonly)",
+                    rev,
+                ])?;
+                if let Some(value) = trailers.lines().rfind(|line| !line.trim().is_empty()) {
+                    let version = parse(value, "the 'Version' trailer")?;
+                    trace.record(
+                        "tag",
+                        Message::new("explain.tag_trailer").arg("version", &version),
+                    );
+                    return Ok(Some(version));
+                }
+            }
+        }
+    }
+    Ok(None)
+}
+
+pub(crate) fn parse_tag_version(regexes: &Regexes, tag: &str) -> Result<Version> {
+    // Extract the semver version from the tag
+    let caps = regexes
+        .tag
+        .captures(tag)
+        .ok_or(VersionError::ParseError("No tag found".to_string()))?;
+    let version = caps.name("version").unwrap().as_str();
+    Version::parse(version).map_err(|err| {
+        Error::from(VersionError::ParseError(format!(
+            "Tag '{}' cannot be parsed to SemVer Version.\nDo you have app names in your tags? Provide the '--app-name' option.\nError: '{}'",
+            tag, err
+        )))
+    })
+}
+
+/// Fails, or adds `dirty` to the build metadata of the version, when tracked files (of the `--path`)
+/// have uncommitted changes. Untracked files, like build outputs, don't count.
+fn check_dirty(
+    git_command: &impl GitCommandTrait,
+    options: &VersionOptions,
+    trace: &Trace,
+    dirty: Dirty,
+    mut version: Version,
+) -> Result<Version> {
+    let status = git_command.run(with_path(
+        vec!["status", "--porcelain", "--untracked-files=no"],
+        options,
+    ))?;
+    // `XY <path>` lines, of which the status may be trimmed
+    let changed: Vec<&str> = status
+        .lines()
+        .filter_map(|line| Some(line.trim_start().split_once(' ')?.1.trim_start()))
+        .collect();
+    if changed.is_empty() {
+        return Ok(version);
+    }
+    trace.record(
+        "dirty",
+        Message::new("explain.dirty").arg("files", changed.join(", ")),
+    );
+    if dirty == Dirty::Fail {
+        return Err(VersionError::DirtyWorktree(format!(
+            "The working tree has uncommitted changes in {}. Commit or stash them, or version with --dirty metadata.",
+            changed.join(", ")
+        ))
+        .into());
+    }
+    version.build = match version.build.is_empty() {
+        true => BuildMetadata::new("dirty")?,
+        false => BuildMetadata::new(&format!("{}.dirty", version.build))?,
+    };
+    Ok(version)
+}
+
+/// Major bumps relative to the latest tag have to be confirmed, either explicitly or by a commit
+/// trailer since that tag, preventing accidental major releases
+fn check_major_approval(
+    git_command: &impl GitCommandTrait,
+    regexes: &Regexes,
+    options: &VersionOptions,
+    semver: &Version,
+    rev: Option<&str>,
+) -> Result<()> {
+    if !options.require_major_approval || options.confirm_major {
+        return Ok(());
+    }
+    let latest_tag = get_latest_tag(git_command, regexes, rev);
+    let latest_version = match &latest_tag {
+        Some(tag) => parse_tag_version(regexes, tag)?,
+        None => Version::new(0, 0, 0),
+    };
+    if semver.major <= latest_version.major {
+        return Ok(());
+    }
+    let head = rev.unwrap_or("HEAD");
+    let range = match &latest_tag {
+        Some(tag) => format!("{}..{}", tag, head),
+        None => head.to_string(),
+    };
+    let approvals = git_command.run(vec![
+        "log",
+        &format!(
+            "--format=%(trailers:key={},valueonly)",
+            MAJOR_APPROVAL_TRAILER
+        ),
+        &range,
+    ])?;
+    if approvals.trim().is_empty() {
+        return Err(Error::msg(format!(
+            "Version '{}' is a major bump from '{}'. Confirm it with the '--confirm-major' option or a '{}' commit trailer.",
+            semver, latest_version, MAJOR_APPROVAL_TRAILER
+        )));
+    }
+    Ok(())
+}
+
+/// The computed version has to be greater than the tags of its channel, and the tags reachable
+/// from the commit, except its own tag, e.g. of a main branch or a re-run of a tagged build
+fn check_semver_order(
+    git_command: &impl GitCommandTrait,
+    regexes: &Regexes,
+    semver: &Version,
+    rev: Option<&str>,
+) -> Result<()> {
+    let mut version = semver.clone();
+    version.build = BuildMetadata::EMPTY;
+    let own_tag = regexes.tag_name(&version.to_string());
+    let label = |version: &Version| {
+        version
+            .pre
+            .split('.')
+            .next()
+            .unwrap_or_default()
+            .to_string()
+    };
+    let tags = |merged: Option<&str>| -> Result<Vec<(String, Version)>> {
+        let mut args = vec!["for-each-ref", "--format=%(refname:lstrip=2)"];
+        if let Some(merged) = merged {
+            args.extend(["--merged", merged]);
+        }
+        args.push("refs/tags");
+        Ok(git_command
+            .run(args)?
+            .lines()
+            // The tags created after `--as-of` are excluded by name
+            .filter(|tag| {
+                *tag != own_tag && !regexes.tag_exclude.iter().any(|exclude| exclude == tag)
+            })
+            .filter_map(|tag| Some((tag.to_string(), parse_tag_version(regexes, tag).ok()?)))
+            .collect())
+    };
+    let channel_tags = tags(None)?.into_iter().filter(|(_, tag_version)| {
+        tag_version.pre.is_empty() || label(tag_version) == label(&version)
+    });
+    let reachable_tags = tags(Some(rev.unwrap_or("HEAD")))?;
+    let highest = channel_tags
+        .chain(reachable_tags)
+        .max_by(|(_, a), (_, b)| a.cmp_precedence(b));
+    match highest {
+        Some((tag, tag_version)) if tag_version.cmp_precedence(&version).is_ge() => {
+            Err(Error::msg(format!(
+                "Version '{}' isn't greater than the version of tag '{}'. Check the version of the branch name.",
+                version, tag
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Verifies the signature of the release of a main branch following `--require-signature`, so
+/// unsigned or forged releases don't get a production version
+fn check_signature(
+    git_command: &impl GitCommandTrait,
+    options: &VersionOptions,
+    tag: Option<&str>,
+    rev: Option<&str>,
+) -> Result<Option<bool>> {
+    let (command, object) = match (options.require_signature, tag) {
+        (None, _) => return Ok(None),
+        (Some(RequireSignature::Commit), _) => ("verify-commit", rev.unwrap_or("HEAD")),
+        (Some(RequireSignature::Tag), Some(tag)) => ("verify-tag", tag),
+        (Some(RequireSignature::Tag), None) => {
+            return Err(Error::msg(
+                "A verified tag signature is required, but the commit isn't tagged",
+            ))
+        }
+    };
+    git_command.run(vec![command, object]).map_err(|err| {
+        Error::msg(format!(
+            "A verified signature of '{}' is required for a production version.\nError: '{}'",
+            object,
+            err.to_string().trim()
+        ))
+    })?;
+    Ok(Some(true))
+}
+
+#[cfg(test)]
+// The lints flag the baseline `get_version` tests, which are kept as they were written
+#[allow(
+    clippy::clone_on_copy,
+    clippy::needless_borrow,
+    clippy::needless_late_init,
+    clippy::unnecessary_literal_unwrap,
+    clippy::unnecessary_unwrap
+)]
+mod tests {
+    use crate::config::CounterFormat;
+    use crate::git_command::MockGitCommandTrait;
+
+    use serde_json::json;
+
+    use super::*;
+
+    fn get_version_output(
+        options: &VersionOptions,
+        git_command: &MockGitCommandTrait,
+    ) -> Result<Value> {
+        let calculator = VersionCalculator::new(git_command, options.clone())?;
+        // Without the environment of the build, e.g. the pull request of a CI run
+        Ok(calculator.calculate_with_env(|_| None)?.to_json())
+    }
+
+    #[test]
+    fn test_get_version_main_branch_with_tag() {
+        let mut git_command = MockGitCommandTrait::new();
+        let app_name = Some("myapp");
+        let branch = "main";
+        let rev = "1234567";
+        let count = "1";
+        let version = Some("1.0.0");
+
+        let args = VersionOptions {
+            app_name: Some(app_name.unwrap().to_owned()),
+            ..Default::default()
+        };
+
+        mock_git(&mut git_command, app_name, branch, rev, count, version);
+
+        let result = get_version_output(&args, &git_command);
+
+        assert!(result.is_ok());
+
+        let output = result.unwrap();
+
+        let mut expected_version = Version::parse(&version.clone().unwrap()).unwrap();
+        expected_version.build = BuildMetadata::new(&rev).unwrap();
+        assert_expected_version(branch, rev, count, expected_version, output);
+    }
+
+    fn mock_tags(git_command: &mut MockGitCommandTrait, tags: &'static str, merged: &'static str) {
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "for-each-ref --format=%(refname:lstrip=2) refs/tags")
+            .returning(move |_| Ok(tags.to_string()));
+        git_command
+            .expect_run()
+            .withf(|args| {
+                args.join(" ")
+                    == "for-each-ref --format=%(refname:lstrip=2) --merged HEAD refs/tags"
+            })
+            .returning(move |_| Ok(merged.to_string()));
+    }
+
+    #[test]
+    fn test_get_version_verify_semver_order() {
+        let options = VersionOptions {
+            verify_semver_order: true,
+            ..Default::default()
+        };
+
+        // Own tag of main
+        let mut git_command = MockGitCommandTrait::new();
+        mock_git(
+            &mut git_command,
+            None,
+            "main",
+            "1234567",
+            "1",
+            Some("1.1.0"),
+        );
+        mock_tags(&mut git_command, "1.0.0\n1.1.0-rc.2\n1.1.0", "1.0.0\n1.1.0");
+        assert!(get_version_output(&options, &git_command).is_ok());
+
+        // Release branch named lower than an existing release
+        let mut git_command = MockGitCommandTrait::new();
+        mock_git(
+            &mut git_command,
+            None,
+            "release/1.0.5",
+            "1234567",
+            "1",
+            Some("1.0.0"),
+        );
+        mock_tags(
+            &mut git_command,
+            "1.0.0\n1.1.0-rc.2\n1.1.0\n2.0.0-beta.1",
+            "1.0.0",
+        );
+        let result = get_version_output(&options, &git_command);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Version '1.0.5-rc.1' isn't greater than the version of tag '1.1.0'. Check the version of the branch name."
+        );
+    }
+
+    #[test]
+    fn test_get_version_main_branch_require_signature() {
+        for (require_signature, command) in [
+            (RequireSignature::Tag, "verify-tag myapp-1.0.0"),
+            (RequireSignature::Commit, "verify-commit HEAD"),
+        ] {
+            let mut git_command = MockGitCommandTrait::new();
+            git_command
+                .expect_run()
+                .withf(move |args| args.join(" ") == command)
+                .times(1)
+                .returning(|_| Ok(String::new()));
+            mock_git(
+                &mut git_command,
+                Some("myapp"),
+                "main",
+                "1234567",
+                "1",
+                Some("1.0.0"),
+            );
+            let options = VersionOptions {
+                app_name: Some(String::from("myapp")),
+                require_signature: Some(require_signature),
+                ..Default::default()
+            };
+
+            let output = get_version_output(&options, &git_command).unwrap();
+
+            assert_eq!(output["signature_verified"], true);
+        }
+    }
+
+    #[test]
+    fn test_get_version_main_branch_unsigned() {
+        let mut git_command = MockGitCommandTrait::new();
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "verify-tag")
+            .returning(|_| Err(Error::msg("Git command failed: error: no signature found")));
+        mock_git(
+            &mut git_command,
+            None,
+            "main",
+            "1234567",
+            "1",
+            Some("1.0.0"),
+        );
+        let options = VersionOptions {
+            require_signature: Some(RequireSignature::Tag),
+            ..Default::default()
+        };
+
+        let result = get_version_output(&options, &git_command);
+
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .starts_with("A verified signature of '1.0.0' is required for a production version."));
+    }
+
+    #[test]
+    fn test_get_version_container_tags() {
+        let container_tags = |branch: &'static str, options: VersionOptions| {
+            let mut git_command = MockGitCommandTrait::new();
+            mock_git(
+                &mut git_command,
+                None,
+                branch,
+                "1234567",
+                "1",
+                Some("1.2.3"),
+            );
+            get_version_output(&options, &git_command).unwrap()["container_tags"].clone()
+        };
+
+        assert_eq!(
+            container_tags("main", VersionOptions::default()),
+            json!(["1.2.3.1234567", "1.2.3", "1.2", "1", "latest"])
+        );
+        assert_eq!(
+            container_tags(
+                "develop",
+                VersionOptions {
+                    image_name: Some("ghcr.io/org/app".to_string()),
+                    ..Default::default()
+                }
+            ),
+            json!([
+                "ghcr.io/org/app:1.2.4-beta.1.1234567",
+                "ghcr.io/org/app:beta"
+            ])
+        );
+        let mut options = VersionOptions::default();
+        options.apply_config(
+            Config::parse("[container_tags]\nfeature = ['{major}.{minor}-dev']").unwrap(),
+        );
+        assert_eq!(
+            container_tags("feature/login", options),
+            json!(["1.2.4-alpha.1.feature-login.1234567", "1.2-dev"])
+        );
+        assert_eq!(
+            container_tags(
+                "feature/login",
+                VersionOptions {
+                    container_tag_strategy: Some(ContainerTagStrategy::DropBuildMetadata),
+                    ..Default::default()
+                }
+            ),
+            json!(["1.2.4-alpha.1"])
+        );
+    }
+
+    #[test]
+    fn test_get_version_tag_selection_highest() {
+        let mut git_command = MockGitCommandTrait::new();
+        // 1.9.0 is the nearest tag, 1.10.0 was merged in from a branch tagged before it
+        git_command
+            .expect_run()
+            .withf(|args| {
+                args.join(" ")
+                    == "for-each-ref --format=%(refname:lstrip=2) --merged HEAD refs/tags"
+            })
+            .returning(|_| Ok(["1.10.0", "1.2.3", "1.9.0", "nightly"].join("\n")));
+        mock_git(
+            &mut git_command,
+            None,
+            "develop",
+            "1234567",
+            "1",
+            Some("1.9.0"),
+        );
+        let mut options = VersionOptions {
+            tag_selection: Some(TagSelection::Highest),
+            ..Default::default()
+        };
+        options.apply_config(Config::default());
+
+        let output = get_version_output(&options, &git_command).unwrap();
+
+        assert_eq!(output["app_version"], "1.10.1-beta.1+1234567");
+        assert_eq!(
+            get_version_output(&VersionOptions::default(), &git_command).unwrap()["app_version"],
+            "1.9.1-beta.1+1234567"
+        );
+    }
+
+    #[test]
+    fn test_get_version_detected_default_branch() {
+        let mut git_command = MockGitCommandTrait::new();
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "symbolic-ref --short refs/remotes/origin/HEAD")
+            .returning(|_| Ok("origin/trunk".to_string()));
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "rev-list --left-right --count HEAD...trunk")
+            .returning(|_| Ok("0\t0".to_string()));
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "merge-base HEAD trunk")
+            .returning(|_| Err(Error::msg("")));
+        mock_git(
+            &mut git_command,
+            None,
+            "trunk",
+            "1234567",
+            "1",
+            Some("1.0.0"),
+        );
+
+        let output = get_version_output(&VersionOptions::default(), &git_command).unwrap();
+
+        assert_eq!(output["app_version"], "1.0.0+1234567");
+        assert_eq!(output["ahead_of_main"], 0);
+        assert_eq!(output["bump"], Value::Null);
+
+        // Default branches matching another class keep it
+        let regexes = Regexes::new(&None, &Config::default()).unwrap();
+        let regexes = regexes.with_default_branch("develop").unwrap();
+        assert!(!regexes.main_branches.is_match("develop"));
+    }
+
+    #[test]
+    fn test_get_version_ambiguous_branch() {
+        let mut git_command = MockGitCommandTrait::new();
+        mock_git(
+            &mut git_command,
+            None,
+            "release/1.1.0-dev",
+            "1234567",
+            "3",
+            Some("1.0.0"),
+        );
+        let mut args = VersionOptions::default();
+        args.apply_config(
+            Config::parse("[branches]\npriority = ['develop', 'release', 'main']").unwrap(),
+        );
+
+        let output = get_version_output(&args, &git_command).unwrap();
+
+        assert_eq!(output["app_version"], "1.0.1-beta.3+1234567");
+
+        args.apply_config(Config::parse("[branches]\nambiguous = 'error'").unwrap());
+        let result = get_version_output(&args, &git_command);
+
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Branch 'release/1.1.0-dev' matches the [Release, Develop] branch patterns"));
+    }
+
+    #[test]
+    fn test_version_output() {
+        let mut git_command = MockGitCommandTrait::new();
+        let branch = "release/1.2.0";
+        mock_git(&mut git_command, None, branch, "1234567", "3", None);
+        let version_calculator =
+            VersionCalculator::new(&git_command, VersionOptions::default()).unwrap();
+
+        let output = version_calculator.calculate().unwrap().output();
+
+        assert_eq!(output.branch_class, BranchClass::Release);
+        assert_eq!(output.channel, "rc");
+        assert_eq!(output.channel_image, None);
+        assert_eq!((output.major, output.minor, output.patch), (1, 2, 0));
+        assert_eq!(output.prerelease, "rc.3");
+        assert_eq!(output.build, "1234567");
+        assert_eq!(output.bump, None);
+    }
+
+    #[test]
+    fn test_get_version_env() {
+        let mut git_command = MockGitCommandTrait::new();
+        let branch = "feature/feat-1";
+        let rev = "1234567";
+        let count = "3";
+        mock_git(&mut git_command, None, branch, rev, count, Some("1.0.0"));
+        let mut options = VersionOptions::default();
+        options.apply_config(
+            Config::parse(
+                "[build_metadata]\nenv = ['RUNNER_ARCH', 'UNSET']\n[output]\nenv = ['BUILD_FLAVOR']",
+            )
+            .unwrap(),
+        );
+        let version_calculator = VersionCalculator::new(&git_command, options).unwrap();
+
+        let version_info = version_calculator
+            .calculate_with_env(|name| match name {
+                "RUNNER_ARCH" => Some("x86_64".to_string()),
+                "BUILD_FLAVOR" => Some("debug\n".to_string()),
+                _ => None,
+            })
+            .unwrap();
+
+        let output = version_info.to_json();
+        assert_eq!(
+            output["app_version"],
+            "1.0.1-alpha.3+feature-feat-1.1234567.x86-64"
+        );
+        assert_eq!(
+            output["container_tag"],
+            "1.0.1-alpha.3.feature-feat-1.1234567.x86-64"
+        );
+        assert_eq!(output["build_flavor"], "debug");
+    }
+
+    #[test]
+    fn test_get_version_env_conflict() {
+        let mut git_command = MockGitCommandTrait::new();
+        mock_git(
+            &mut git_command,
+            None,
+            "main",
+            "1234567",
+            "3",
+            Some("1.0.0"),
+        );
+        let mut options = VersionOptions::default();
+        options.apply_config(Config::parse("[output]\nenv = ['GIT_BRANCH']").unwrap());
+        let version_calculator = VersionCalculator::new(&git_command, options).unwrap();
+
+        let result = version_calculator.calculate_with_env(|_| Some("other".to_string()));
+
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("conflicts with the 'git_branch' output field"));
+    }
+
+    #[test]
+    fn test_get_version_shallow() {
+        let mut git_command = MockGitCommandTrait::new();
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "rev-parse --is-shallow-repository")
+            .returning(|_| Ok(String::from("true")));
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "fetch --unshallow --tags")
+            .times(1)
+            .returning(|_| Ok(String::new()));
+        mock_git(
+            &mut git_command,
+            None,
+            "develop",
+            "1234567",
+            "3",
+            Some("1.0.0"),
+        );
+
+        let result = get_version_output(&VersionOptions::default(), &git_command);
+
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("The repository is a shallow clone"));
+
+        let args = VersionOptions {
+            auto_deepen: true,
+            ..Default::default()
+        };
+        let output = get_version_output(&args, &git_command).unwrap();
+
+        assert_eq!(output["app_version"], "1.0.1-beta.3+1234567");
+        assert_eq!(output["bump"], "patch");
+    }
+
+    #[test]
+    fn test_get_version_base() {
+        let output = |branch: &'static str, options: VersionOptions| {
+            let mut git_command = MockGitCommandTrait::new();
+            mock_git(
+                &mut git_command,
+                None,
+                branch,
+                "1234567",
+                "3",
+                Some("1.0.0"),
+            );
+            get_version_output(&options, &git_command).unwrap()
+        };
+
+        let develop = output("develop", VersionOptions::default());
+        assert_eq!(develop["base_tag"], "1.0.0");
+        assert_eq!(develop["base_version"], "1.0.0");
+        assert_eq!(develop["bump"], "patch");
+        assert_eq!(develop["prerelease_source"], "tag");
+
+        let release = output("release/1.1.0", VersionOptions::default());
+        assert_eq!(release["base_tag"], "1.0.0");
+        assert_eq!(release["base_version"], "1.1.0");
+        assert_eq!(release["bump"], Value::Null);
+        assert_eq!(release["prerelease_source"], "branch-name");
+
+        let options = VersionOptions {
+            counter: Some(Counter::BuildNumber),
+            build_number: Some(42),
+            ..Default::default()
+        };
+        assert_eq!(
+            output("develop", options)["prerelease_source"],
+            "build-number"
+        );
+
+        let main = output("main", VersionOptions::default());
+        assert_eq!(main["base_version"], "1.0.0");
+        assert_eq!(main["prerelease_source"], Value::Null);
+    }
+
+    #[test]
+    fn test_get_version_main_branch_without_tag() {
+        let mut git_command = MockGitCommandTrait::new();
+        let app_name = Some("myapp");
+        let branch = "main";
+        let rev = "1234567";
+        let count = "1";
+        let version = None;
+
+        let args = VersionOptions {
+            app_name: Some(String::from("myapp")),
+            ..Default::default()
+        };
+
+        mock_git(&mut git_command, app_name, branch, rev, count, version);
+
+        let result = get_version_output(&args, &git_command);
+
+        assert!(result.is_ok());
+
+        let output = result.unwrap();
+
+        let mut expected_version = Version::parse("0.0.0").unwrap();
+        expected_version.build = BuildMetadata::new(&rev).unwrap();
+        assert_expected_version(branch, rev, count, expected_version, output);
+    }
+
+    #[test]
+    fn test_get_version_main_branch_tag_not_on_commit() {
+        let mut git_command = MockGitCommandTrait::new();
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "describe --abbrev=0 --exact-match --tags")
+            .returning(|_| {
+                Err(Error::msg(
+                    "Git command failed: fatal: no tag exactly matches",
+                ))
+            });
+        mock_git(
+            &mut git_command,
+            None,
+            "main",
+            "1234567",
+            "3",
+            Some("1.0.0"),
+        );
+
+        let err = get_version_output(&VersionOptions::default(), &git_command).unwrap_err();
+
+        assert!(matches!(
+            crate::error::version_error(&err),
+            Some(VersionError::NoTagOnMain(_))
+        ));
+    }
+
+    #[test]
+    fn test_get_version_no_app_name_main_branch_with_tag() {
+        let mut git_command = MockGitCommandTrait::new();
+        let app_name: Option<&str> = None;
+        let branch = "main";
+        let rev = "1234567";
+        let count = "1";
+        let version = Some("1.0.0");
+
+        let args = VersionOptions {
+            app_name: None,
+            ..Default::default()
+        };
+
+        mock_git(&mut git_command, app_name, branch, rev, count, version);
+
+        let result = get_version_output(&args, &git_command);
+
+        assert!(result.is_ok());
+
+        let output = result.unwrap();
+
+        let mut expected_version = Version::parse(&version.clone().unwrap()).unwrap();
+        expected_version.build = BuildMetadata::new(&rev).unwrap();
+        assert_expected_version(branch, rev, count, expected_version, output);
+    }
+
+    #[test]
+    fn test_get_version_no_app_name_main_branch_without_tag() {
+        let mut git_command = MockGitCommandTrait::new();
+        let app_name: Option<&str> = None;
+        let branch = "main";
+        let rev = "1234567";
+        let count = "1";
+        let version = None;
+
+        let args = VersionOptions {
+            app_name: None,
+            ..Default::default()
+        };
+
+        mock_git(&mut git_command, app_name, branch, rev, count, version);
+
+        let result = get_version_output(&args, &git_command);
+
+        assert!(result.is_ok());
+
+        let output = result.unwrap();
+
+        let mut expected_version = Version::parse("0.0.0").unwrap();
+        expected_version.build = BuildMetadata::new(&rev).unwrap();
+        assert_expected_version(branch, rev, count, expected_version, output);
+    }
+
+    #[test]
+    fn test_get_version_develop_branch_with_tag() {
+        let mut git_command = MockGitCommandTrait::new();
+        let app_name = Some("myapp");
+        let branch = "develop";
+        let rev = "1234567";
+        let count = "1";
+        let version = Some("1.0.0");
+
+        let args = VersionOptions {
+            app_name: Some(String::from("myapp")),
+            ..Default::default()
+        };
+
+        mock_git(&mut git_command, app_name, branch, rev, count, version);
+
+        let result = get_version_output(&args, &git_command);
+
+        assert!(result.is_ok());
+
+        let output = result.unwrap();
+
+        let mut expected_version = Version::parse(&version.clone().unwrap()).unwrap();
+        expected_version.patch += 1;
+        expected_version.pre = Prerelease::new(&format!("beta.{}", count)).unwrap();
+        expected_version.build = BuildMetadata::new(&rev).unwrap();
+        assert_expected_version(branch, rev, count, expected_version, output);
+    }
+
+    #[test]
+    fn test_get_version_develop_branch_without_tag() {
+        let mut git_command = MockGitCommandTrait::new();
+        let app_name = Some("myapp");
+        let branch = "develop";
+        let rev = "1234567";
+        let count = "1";
+        let version = None;
+
+        let args = VersionOptions {
+            app_name: Some(String::from("myapp")),
+            ..Default::default()
+        };
+
+        mock_git(&mut git_command, app_name, branch, rev, count, version);
+
+        let result = get_version_output(&args, &git_command);
+
+        assert!(result.is_ok());
+
+        let output = result.unwrap();
+
+        let mut expected_version = Version::parse("0.0.0").unwrap();
+        expected_version.patch += 1;
+        expected_version.pre = Prerelease::new(&format!("beta.{}", count)).unwrap();
+        expected_version.build = BuildMetadata::new(&rev).unwrap();
+
+        assert_expected_version(branch, rev, count, expected_version, output);
+    }
+
+    #[test]
+    fn test_get_version_no_app_name_develop_branch_with_tag() {
+        let mut git_command = MockGitCommandTrait::new();
+        let app_name: Option<&str> = None;
+        let branch = "develop";
+        let rev = "1234567";
+        let count = "1";
+        let version = Some("1.0.0");
+
+        let args = VersionOptions {
+            app_name: None,
+            ..Default::default()
+        };
+
+        mock_git(&mut git_command, app_name, branch, rev, count, version);
+
+        let result = get_version_output(&args, &git_command);
+
+        assert!(result.is_ok());
+
+        let output = result.unwrap();
+
+        let mut expected_version = Version::parse(&version.clone().unwrap()).unwrap();
+        expected_version.patch += 1;
+        expected_version.pre = Prerelease::new(&format!("beta.{}", count)).unwrap();
+        expected_version.build = BuildMetadata::new(&rev).unwrap();
+        assert_expected_version(branch, rev, count, expected_version, output);
+    }
+
+    #[test]
+    fn test_get_version_no_app_name_develop_branch_without_tag() {
+        let mut git_command = MockGitCommandTrait::new();
+        let app_name: Option<&str> = None;
+        let branch = "develop";
+        let rev = "1234567";
+        let count = "1";
+        let version = None;
+
+        let args = VersionOptions {
+            app_name: None,
+            ..Default::default()
+        };
+
+        mock_git(&mut git_command, app_name, branch, rev, count, version);
+
+        let result = get_version_output(&args, &git_command);
+
+        assert!(result.is_ok());
+
+        let output = result.unwrap();
+
+        let mut expected_version = Version::parse("0.0.0").unwrap();
+        expected_version.patch += 1;
+        expected_version.pre = Prerelease::new(&format!("beta.{}", count)).unwrap();
+        expected_version.build = BuildMetadata::new(&rev).unwrap();
+
+        assert_expected_version(branch, rev, count, expected_version, output);
+    }
+
+    #[test]
+    fn test_get_version_release_branch_with_tag() {
+        let mut git_command = MockGitCommandTrait::new();
+        let app_name = Some("myapp");
+        let branch = "release/myapp-1.1.0";
+        let rev = "1234567";
+        let count = "1";
+        let version = Some("1.0.0");
+
+        let args = VersionOptions {
+            app_name: Some(String::from("myapp")),
+            ..Default::default()
+        };
+
+        mock_git(&mut git_command, app_name, branch, rev, count, version);
+
+        let result = get_version_output(&args, &git_command);
+
+        assert!(result.is_ok());
+
+        let output = result.unwrap();
+
+        let mut expected_version = Version::parse("1.1.0").unwrap();
+        expected_version.pre = Prerelease::new(&format!("rc.{}", count)).unwrap();
+        expected_version.build = BuildMetadata::new(&rev).unwrap();
+        assert_expected_version(branch, rev, count, expected_version, output);
+    }
+
+    #[test]
+    fn test_get_version_release_branch_without_tag() {
+        let mut git_command = MockGitCommandTrait::new();
+        let app_name = Some("myapp");
+        let branch = "release/myapp-1.1.0";
+        let rev = "1234567";
+        let count = "1";
+        let version = None;
+
+        let args = VersionOptions {
+            app_name: Some(String::from("myapp")),
+            ..Default::default()
+        };
+
+        mock_git(&mut git_command, app_name, branch, rev, count, version);
+
+        let result = get_version_output(&args, &git_command);
+
+        assert!(result.is_ok());
+
+        let output = result.unwrap();
+
+        let mut expected_version = Version::parse("1.1.0").unwrap();
+        expected_version.pre = Prerelease::new(&format!("rc.{}", count)).unwrap();
+        expected_version.build = BuildMetadata::new(&rev).unwrap();
+        assert_expected_version(branch, rev, count, expected_version, output);
+    }
+
+    #[test]
+    fn test_get_version_no_app_name_release_branch_with_tag() {
+        let mut git_command = MockGitCommandTrait::new();
+        let app_name: Option<&str> = None;
+        let branch = "release/1.1.0";
+        let rev = "1234567";
+        let count = "1";
+        let version = Some("1.0.0");
+
+        let args = VersionOptions {
+            app_name: None,
+            ..Default::default()
+        };
+
+        mock_git(&mut git_command, app_name, branch, rev, count, version);
+
+        let result = get_version_output(&args, &git_command);
+
+        assert!(result.is_ok());
+
+        let output = result.unwrap();
+
+        let mut expected_version = Version::parse("1.1.0").unwrap();
+        expected_version.pre = Prerelease::new(&format!("rc.{}", count)).unwrap();
+        expected_version.build = BuildMetadata::new(&rev).unwrap();
+        assert_expected_version(branch, rev, count, expected_version, output);
+    }
+
+    #[test]
+    fn test_get_version_no_app_name_release_branch_without_tag() {
+        let mut git_command = MockGitCommandTrait::new();
+        let app_name: Option<&str> = None;
+        let branch = "release/1.1.0";
+        let rev = "1234567";
+        let count = "1";
+        let version = None;
+
+        let args = VersionOptions {
+            app_name: None,
+            ..Default::default()
+        };
+
+        mock_git(&mut git_command, app_name, branch, rev, count, version);
+
+        let result = get_version_output(&args, &git_command);
+
+        assert!(result.is_ok());
+
+        let output = result.unwrap();
+
+        let mut expected_version = Version::parse("1.1.0").unwrap();
+        expected_version.pre = Prerelease::new(&format!("rc.{}", count)).unwrap();
+        expected_version.build = BuildMetadata::new(&rev).unwrap();
+        assert_expected_version(branch, rev, count, expected_version, output);
+    }
+
+    #[test]
+    fn test_get_version_feature_branch_with_tag() {
+        let mut git_command = MockGitCommandTrait::new();
+        let app_name = Some("myapp");
+        let branch = "feature/feat-1";
+        let rev = "1234567";
+        let count = "1";
+        let version = Some("1.0.0");
+
+        let args = VersionOptions {
+            app_name: Some(String::from("myapp")),
+            ..Default::default()
+        };
+
+        mock_git(&mut git_command, app_name, branch, rev, count, version);
+
+        let result = get_version_output(&args, &git_command);
+
+        assert!(result.is_ok());
+
+        let output = result.unwrap();
+
+        let mut expected_version = Version::parse("1.0.0").unwrap();
+        expected_version.patch += 1;
+        expected_version.pre = Prerelease::new(&format!("alpha.{}", count)).unwrap();
+        expected_version.build = BuildMetadata::new(&format!("feature-feat-1.{}", rev)).unwrap();
+        assert_expected_version(branch, rev, count, expected_version, output);
+    }
+
+    #[test]
+    fn test_get_version_feature_branch_without_tag() {
+        let mut git_command = MockGitCommandTrait::new();
+        let app_name = Some("myapp");
+        let branch = "feature/feat-1";
+        let rev = "1234567";
+        let count = "1";
+        let version = None;
+
+        let args = VersionOptions {
+            app_name: Some(String::from("myapp")),
+            ..Default::default()
+        };
+
+        mock_git(&mut git_command, app_name, branch, rev, count, version);
+
+        let result = get_version_output(&args, &git_command);
+
+        assert!(result.is_ok());
+
+        let output = result.unwrap();
+
+        let mut expected_version = Version::parse("0.0.0").unwrap();
+        expected_version.patch += 1;
+        expected_version.pre = Prerelease::new(&format!("alpha.{}", count)).unwrap();
+        expected_version.build = BuildMetadata::new(&format!("feature-feat-1.{}", rev)).unwrap();
+        assert_expected_version(branch, rev, count, expected_version, output);
+    }
+
+    #[test]
+    fn test_get_version_no_app_name_feature_branch_with_tag() {
+        let mut git_command = MockGitCommandTrait::new();
+        let app_name: Option<&str> = None;
+        let branch = "feature/feat-1";
+        let rev = "1234567";
+        let count = "1";
+        let version = Some("1.0.0");
+
+        let args = VersionOptions {
+            app_name: None,
+            ..Default::default()
+        };
+
+        mock_git(&mut git_command, app_name, branch, rev, count, version);
+
+        let result = get_version_output(&args, &git_command);
+
+        assert!(result.is_ok());
+
+        let output = result.unwrap();
+
+        let mut expected_version = Version::parse("1.0.0").unwrap();
+        expected_version.patch += 1;
+        expected_version.pre = Prerelease::new(&format!("alpha.{}", count)).unwrap();
+        expected_version.build = BuildMetadata::new(&format!("feature-feat-1.{}", rev)).unwrap();
+        assert_expected_version(branch, rev, count, expected_version, output);
+    }
+
+    #[test]
+    fn test_get_version_no_app_name_feature_branch_without_tag() {
+        let mut git_command = MockGitCommandTrait::new();
+        let app_name: Option<&str> = None;
+        let branch = "feature/feat-1";
+        let rev = "1234567";
+        let count = "1";
+        let version = None;
+
+        let args = VersionOptions {
+            app_name: None,
+            ..Default::default()
+        };
+
+        mock_git(&mut git_command, app_name, branch, rev, count, version);
+
+        let result = get_version_output(&args, &git_command);
+
+        assert!(result.is_ok());
+
+        let output = result.unwrap();
+
+        let mut expected_version = Version::parse("0.0.0").unwrap();
+        expected_version.patch += 1;
+        expected_version.pre = Prerelease::new(&format!("alpha.{}", count)).unwrap();
+        expected_version.build = BuildMetadata::new(&format!("feature-feat-1.{}", rev)).unwrap();
+        assert_expected_version(branch, rev, count, expected_version, output);
+    }
+
+    #[test]
+    fn test_get_version_release_branch_major_requires_approval() {
+        let mut git_command = MockGitCommandTrait::new();
+        let branch = "release/2.0.0";
+        let rev = "1234567";
+        let count = "1";
+
+        let args = VersionOptions {
+            require_major_approval: true,
+            ..Default::default()
+        };
+
+        mock_git(&mut git_command, None, branch, rev, count, Some("1.0.0"));
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "log" && args[2] == "1.0.0..HEAD")
+            .returning(|_| Ok(String::from("\n")));
+
+        let result = get_version_output(&args, &git_command);
+
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("is a major bump from '1.0.0'"));
+    }
+
+    #[test]
+    fn test_get_version_release_branch_major_approved_by_trailer() {
+        let mut git_command = MockGitCommandTrait::new();
+        let branch = "release/2.0.0";
+        let rev = "1234567";
+        let count = "1";
+
+        let args = VersionOptions {
+            require_major_approval: true,
+            ..Default::default()
+        };
+
+        mock_git(&mut git_command, None, branch, rev, count, Some("1.0.0"));
+        git_command
+            .expect_run()
+            .withf(|args| {
+                args[0] == "log"
+                    && args[1] == "--format=%(trailers:key=Major-Release-Approved-By,valueonly)"
+            })
+            .returning(|_| Ok(String::from("Jane Doe <jane@example.com>")));
+
+        let result = get_version_output(&args, &git_command);
+
+        let mut expected_version = Version::parse("2.0.0").unwrap();
+        expected_version.pre = Prerelease::new(&format!("rc.{}", count)).unwrap();
+        expected_version.build = BuildMetadata::new(rev).unwrap();
+        assert_expected_version(branch, rev, count, expected_version, result.unwrap());
+    }
+
+    #[test]
+    fn test_get_version_release_branch_major_confirmed() {
+        let mut git_command = MockGitCommandTrait::new();
+        let branch = "release/2.0.0";
+        let rev = "1234567";
+        let count = "1";
+
+        let args = VersionOptions {
+            require_major_approval: true,
+            confirm_major: true,
+            ..Default::default()
+        };
+
+        mock_git(&mut git_command, None, branch, rev, count, Some("1.0.0"));
+
+        let result = get_version_output(&args, &git_command);
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_get_version_empty_branch() {
+        let mut git_command = MockGitCommandTrait::new();
+
+        let args = VersionOptions::default();
+
+        mock_git_empty_branch(&mut git_command, "feature/orphan");
+
+        let output = get_version_output(&args, &git_command).unwrap();
+
+        assert_eq!(output["app_version"], "0.0.1-alpha.0+feature-orphan");
+        assert_eq!(output["container_tag"], "0.0.1-alpha.0.feature-orphan");
+        assert_eq!(output["git_rev"], Value::Null);
+        assert_eq!(output["rev_count"], "0");
+    }
+
+    #[test]
+    fn test_get_version_unborn_main() {
+        let mut git_command = MockGitCommandTrait::new();
+        mock_git_empty_branch(&mut git_command, "main");
+
+        let version_info = VersionCalculator::new(&git_command, VersionOptions::default())
+            .unwrap()
+            .calculate_with_env(|_| None)
+            .unwrap();
+
+        assert_eq!(version_info.container_tags, vec!["0.0.1-alpha.0.main"]);
+        assert_eq!(
+            version_info.require_commits("tag").unwrap_err().to_string(),
+            "Branch 'main' doesn't have any commits to tag yet"
+        );
+    }
+
+    #[test]
+    fn test_get_version_outside_repository() {
+        let mut git_command = MockGitCommandTrait::new();
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "branch" && args[1] == "--show-current")
+            .returning(|_| Ok(String::new()));
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "rev-parse" || args[0] == "symbolic-ref")
+            .returning(|_| Err(Error::msg("fatal: not a git repository")));
+
+        let args = VersionOptions {
+            branch: Some("main".to_string()),
+            ..Default::default()
+        };
+
+        let result = get_version_output(&args, &git_command);
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "fatal: not a git repository"
+        );
+    }
+
+    #[test]
+    fn test_get_version_empty_branch_custom_version() {
+        let mut git_command = MockGitCommandTrait::new();
+
+        let args = VersionOptions {
+            empty_branch_version: Some(Version::parse("0.1.0-init").unwrap()),
+            ..Default::default()
+        };
+
+        mock_git_empty_branch(&mut git_command, "docs");
+
+        let output = get_version_output(&args, &git_command).unwrap();
+
+        assert_eq!(output["app_version"], "0.1.0-init+docs");
+    }
+
+    #[test]
+    fn test_get_version_empty_branch_error() {
+        let mut git_command = MockGitCommandTrait::new();
+
+        let args = VersionOptions {
+            empty_branch: Some(EmptyBranch::Error),
+            ..Default::default()
+        };
+
+        mock_git_empty_branch(&mut git_command, "docs");
+
+        let result = get_version_output(&args, &git_command);
+
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Branch 'docs' doesn't have any commits to version."));
+    }
+
+    #[test]
+    fn test_get_version_config_prerelease_label() {
+        let mut git_command = MockGitCommandTrait::new();
+        let branch = "develop";
+        let rev = "1234567";
+        let count = "1";
+
+        let mut args = VersionOptions::default();
+        args.apply_config(Config::parse("[prerelease]\ndevelop = \"nightly\"").unwrap());
+
+        mock_git(&mut git_command, None, branch, rev, count, Some("1.0.0"));
+
+        let output = get_version_output(&args, &git_command).unwrap();
+
+        let expected_version = Version::parse("1.0.1-nightly.1+1234567").unwrap();
+        assert_expected_version(branch, rev, count, expected_version, output);
+    }
+
+    #[test]
+    fn test_get_version_prerelease_label_options() {
+        let mut git_command = MockGitCommandTrait::new();
+        let branch = "feature/feat-1";
+        let rev = "1234567";
+        let count = "1";
+
+        let mut args = VersionOptions {
+            prerelease_feature: Some("pr".to_string()),
+            ..Default::default()
+        };
+        args.apply_config(Config::parse("[prerelease]\nfeature = \"preview\"").unwrap());
+
+        mock_git(&mut git_command, None, branch, rev, count, Some("1.0.0"));
+
+        let output = get_version_output(&args, &git_command).unwrap();
+
+        let expected_version = Version::parse("1.0.1-pr.1+feature-feat-1.1234567").unwrap();
+        assert_expected_version(branch, rev, count, expected_version, output);
+    }
+
+    #[test]
+    fn test_get_version_prerelease_label_branch_pattern() {
+        let mut git_command = MockGitCommandTrait::new();
+        let branch = "feature/feat-1";
+        let rev = "1234567";
+        let count = "1";
+
+        let mut args = VersionOptions {
+            prerelease_feature: Some("pr".to_string()),
+            ..Default::default()
+        };
+        args.apply_config(
+            Config::parse(
+                r#"
+                [[prerelease.branches]]
+                pattern = "^canary/"
+                label = "canary"
+
+                [[prerelease.branches]]
+                pattern = "^feature/feat-"
+                label = "preview"
+                "#,
+            )
+            .unwrap(),
+        );
+
+        mock_git(&mut git_command, None, branch, rev, count, Some("1.0.0"));
+
+        let output = get_version_output(&args, &git_command).unwrap();
+
+        let expected_version = Version::parse("1.0.1-preview.1+feature-feat-1.1234567").unwrap();
+        assert_expected_version(branch, rev, count, expected_version, output);
+    }
+
+    #[test]
+    fn test_get_version_config_tag_format() {
+        let mut git_command = MockGitCommandTrait::new();
+        let branch = "develop";
+        let rev = "1234567";
+        let count = "1";
+
+        let mut args = VersionOptions::default();
+        args.apply_config(Config::parse("[tag]\nformat = \"v{version}\"").unwrap());
+
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "describe" && args[2] == "--match" && args[3] == "v[0-9]*")
+            .returning(|_| Ok("v1.2.0".to_string()));
+        mock_git(&mut git_command, None, branch, rev, count, None);
+
+        let output = get_version_output(&args, &git_command).unwrap();
+
+        let expected_version = Version::parse("1.2.1-beta.1+1234567").unwrap();
+        assert_expected_version(branch, rev, count, expected_version, output);
+    }
+
+    #[test]
+    fn test_get_version_tag_prefix() {
+        let mut git_command = MockGitCommandTrait::new();
+        let branch = "develop";
+        let rev = "1234567";
+        let count = "1";
+
+        let mut args = VersionOptions {
+            app_names: vec!["myapp".to_string()],
+            tag_prefix: Some("v".to_string()),
+            ..Default::default()
+        };
+        args.apply_config(Config::default());
+
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "describe --abbrev=0 --match myapp-v[0-9]* --tags")
+            .returning(|_| Ok("myapp-v1.2.0".to_string()));
+        mock_git(&mut git_command, None, branch, rev, count, None);
+
+        let output = get_version_output(&args, &git_command).unwrap();
+
+        let expected_version = Version::parse("1.2.1-beta.1+1234567").unwrap();
+        assert_expected_version(branch, rev, count, expected_version, output);
+    }
+
+    #[test]
+    fn test_get_version_tag_pattern() {
+        let mut git_command = MockGitCommandTrait::new();
+        let branch = "develop";
+        let rev = "1234567";
+        let count = "1";
+
+        let mut args = VersionOptions::default();
+        args.apply_config(
+            Config::parse("[tag]\nformat = 'v{version}'\npattern = '^v?(?<version>\\d.*)$'")
+                .unwrap(),
+        );
+
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "describe --abbrev=0 --tags")
+            .returning(|_| Ok("nightly*".to_string()));
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "describe --abbrev=0 --exclude nightly\\* --tags")
+            .returning(|_| Ok("1.2.0".to_string()));
+        mock_git(&mut git_command, None, branch, rev, count, None);
+
+        let output = get_version_output(&args, &git_command).unwrap();
+
+        let expected_version = Version::parse("1.2.1-beta.1+1234567").unwrap();
+        assert_expected_version(branch, rev, count, expected_version, output);
+    }
+
+    #[test]
+    fn test_get_version_as_of_date() {
+        let mut git_command = MockGitCommandTrait::new();
+        let as_of = 1707955200;
+        let mocks: Vec<(&str, Result<String, &str>)> = vec![
+            ("branch --show-current", Ok("main".to_string())),
+            ("rev-parse -q --verify 2024-02-15^{commit}", Err("")),
+            ("rev-parse --before=2024-02-15", Ok(format!("--min-age={}", as_of))),
+            (
+                "rev-list -1 --first-parent --before=2024-02-15 HEAD",
+                Ok("abcdef0123".to_string()),
+            ),
+            ("rev-parse --short abcdef0123", Ok("abcdef0".to_string())),
+            ("rev-parse --is-shallow-repository", Ok("false".to_string())),
+            ("rev-list --count abcdef0123", Ok("2".to_string())),
+            (
+                "for-each-ref --format=%(refname:lstrip=2)%09%(creatordate:unix)%09%(objectname)%09%(*objectname) refs/tags",
+                Ok(format!(
+                    "1.0.0\t1704103200\t1111111111\t\n1.1.0\t{}\t2222222222\tabcdef0123\n1.2.0\t1709636400\t3333333333\t\n1.2.0-rc.1\t{}\t4444444444\t",
+                    as_of - 3600,
+                    as_of + 60
+                )),
+            ),
+            (
+                "describe --abbrev=0 --exclude 1.2.0 --exclude 1.2.0-rc.1 --tags abcdef0123",
+                Ok("1.1.0".to_string()),
+            ),
+            (
+                "describe --abbrev=0 --exact-match --exclude 1.2.0 --exclude 1.2.0-rc.1 --tags abcdef0123",
+                Ok("1.1.0".to_string()),
+            ),
+            ("rev-list --count 1.1.0..abcdef0123", Ok("0".to_string())),
+            ("rev-list --left-right --count abcdef0123...main", Ok("0\t1".to_string())),
+            (
+                "log -1 --format=%ct abcdef0123",
+                Ok((as_of - 3 * SECONDS_PER_DAY).to_string()),
+            ),
+        ];
+        for (command, output) in mocks {
+            git_command
+                .expect_run()
+                .withf(move |args| args.join(" ") == command)
+                .returning(move |_| output.clone().map_err(Error::msg));
+        }
+        git_command.expect_run().returning(|_| Err(Error::msg("")));
+        let args = VersionOptions {
+            as_of: Some("2024-02-15".to_string()),
+            ..Default::default()
+        };
+
+        let output = get_version_output(&args, &git_command).unwrap();
+
+        assert_eq!(output["app_version"], "1.1.0+abcdef0");
+        assert_eq!(output["rev_count"], "2");
+        assert_eq!(output["changed_since_tag"], false);
+        assert_eq!(output["behind_main"], 1);
+        assert_eq!(output["days_since_last_commit"], 3);
+    }
+
+    #[test]
+    fn test_get_version_hotfix_branch() {
+        let mut git_command = MockGitCommandTrait::new();
+        let mocks: Vec<(&str, Result<&str, &str>)> = vec![
+            ("branch --show-current", Ok("hotfix/login-timeout")),
+            ("rev-parse --short HEAD", Ok("1234567")),
+            ("rev-parse --is-shallow-repository", Ok("false")),
+            ("merge-base HEAD main", Ok("abcdef0123")),
+            ("describe --abbrev=0 --tags abcdef0123", Ok("1.2.0")),
+            // A pre-release tagged on the branch doesn't become the base version
+            ("describe --abbrev=0 --tags", Ok("1.2.1-hotfix.1")),
+            ("rev-list --count HEAD", Ok("9")),
+            ("rev-list --count 1.2.0..HEAD", Ok("2")),
+            ("rev-list --count 1.2.1-hotfix.1..HEAD", Ok("1")),
+        ];
+        for (command, output) in mocks {
+            git_command
+                .expect_run()
+                .withf(move |args| args.join(" ") == command)
+                .returning(move |_| output.map(str::to_string).map_err(Error::msg));
+        }
+        git_command.expect_run().returning(|_| Err(Error::msg("")));
+        let args = VersionOptions {
+            bump_strategy: Some(BumpStrategy::Conventional),
+            ..Default::default()
+        };
+
+        let output = get_version_output(&args, &git_command).unwrap();
+
+        assert_eq!(output["branch_class"], "hotfix");
+        assert_eq!(output["app_version"], "1.2.1-hotfix.2+1234567");
+        assert_eq!(output["bump"], "patch");
+        assert_eq!(output["channel"], "rc");
+    }
+
+    #[test]
+    fn test_get_version_support_branch() {
+        let mut git_command = MockGitCommandTrait::new();
+        let mocks: Vec<(&str, Result<&str, &str>)> = vec![
+            ("branch --show-current", Ok("maint/1.x")),
+            ("rev-parse --short HEAD", Ok("1234567")),
+            ("rev-parse --is-shallow-repository", Ok("false")),
+            (
+                "tag --list --sort=-v:refname 1.*",
+                Ok("1.5.0-rc.1\n1.4.2\n1.4.0\n1.3.9"),
+            ),
+            ("rev-list --count HEAD", Ok("30")),
+            ("rev-list --count 1.4.2..HEAD", Ok("3")),
+        ];
+        for (command, output) in mocks {
+            git_command
+                .expect_run()
+                .withf(move |args| args.join(" ") == command)
+                .returning(move |_| output.map(str::to_string).map_err(Error::msg));
+        }
+        git_command.expect_run().returning(|_| Err(Error::msg("")));
+        let mut args = VersionOptions {
+            bump_strategy: Some(BumpStrategy::Conventional),
+            ..Default::default()
+        };
+        args.apply_config(
+            Config::parse(
+                "[[branches.custom]]\nname = 'maintenance'\npattern = '^maint/(?<version>\\d+)\\.x$'\nclass = 'support'",
+            )
+            .unwrap(),
+        );
+
+        let output = get_version_output(&args, &git_command).unwrap();
+
+        assert_eq!(output["branch_class"], "support");
+        assert_eq!(output["app_version"], "1.4.3-support.3+1234567");
+        assert_eq!(output["bump"], "patch");
+
+        let err = Config::parse(
+            "[[branches.custom]]\nname = 'legacy'\npattern = '^legacy/'\nclass = 'release'",
+        )
+        .and_then(|config| Regexes::new(&None, &config))
+        .err()
+        .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Pattern '^legacy/' of custom branch class 'legacy' must contain a 'version' capture group"
+        );
+    }
+
+    #[test]
+    fn test_get_version_simulate_merge() {
+        let mut git_command = MockGitCommandTrait::new();
+        let mocks: Vec<(&str, Result<&str, &str>)> = vec![
+            ("rev-parse -q --verify refs/remotes/origin/develop", Err("")),
+            ("rev-parse -q --verify refs/heads/develop", Ok("2222222222")),
+            (
+                "merge-tree --write-tree refs/heads/develop HEAD",
+                Ok("3333333333"),
+            ),
+            (
+                "-c user.name=version-vine -c user.email=version-vine@localhost commit-tree 3333333333 -p refs/heads/develop -p HEAD -m Simulated merge",
+                Ok("abcdef0123"),
+            ),
+            ("rev-parse --short abcdef0123", Ok("abcdef0")),
+            ("rev-parse --is-shallow-repository", Ok("false")),
+            ("rev-list --count abcdef0123", Ok("12")),
+            ("describe --abbrev=0 --tags abcdef0123", Ok("1.2.0")),
+            ("rev-list --count 1.2.0..abcdef0123", Ok("5")),
+        ];
+        for (command, output) in mocks {
+            git_command
+                .expect_run()
+                .withf(move |args| args.join(" ") == command)
+                .returning(move |_| output.map(str::to_string).map_err(Error::msg));
+        }
+        git_command.expect_run().returning(|_| Err(Error::msg("")));
+        let args = VersionOptions {
+            simulate_merge: Some("develop".to_string()),
+            ..Default::default()
+        };
+
+        let output = get_version_output(&args, &git_command).unwrap();
+
+        assert_eq!(output["git_branch"], "develop");
+        assert_eq!(output["app_version"], "1.2.1-beta.5+abcdef0");
+        assert_eq!(output["rev_count"], "12");
+    }
+
+    #[test]
+    fn test_get_version_ref() {
+        let mut git_command = MockGitCommandTrait::new();
+        let mocks: Vec<(&str, Result<&str, &str>)> = vec![
+            (
+                "rev-parse -q --verify origin/release/2.0.0^{commit}",
+                Ok("abcdef0123"),
+            ),
+            (
+                "rev-parse --symbolic-full-name origin/release/2.0.0",
+                Ok("refs/remotes/origin/release/2.0.0"),
+            ),
+            ("rev-parse --short abcdef0123", Ok("abcdef0")),
+            ("rev-parse --is-shallow-repository", Ok("false")),
+            ("rev-list --count abcdef0123", Ok("12")),
+            ("describe --abbrev=0 --tags abcdef0123", Ok("1.2.0")),
+            ("rev-list --count 1.2.0..abcdef0123", Ok("3")),
+        ];
+        for (command, output) in mocks {
+            git_command
+                .expect_run()
+                .withf(move |args| args.join(" ") == command)
+                .returning(move |_| output.map(str::to_string).map_err(Error::msg));
+        }
+        git_command.expect_run().returning(|_| Err(Error::msg("")));
+        let args = VersionOptions {
+            git_ref: Some("origin/release/2.0.0".to_string()),
+            ..Default::default()
+        };
+
+        let output = get_version_output(&args, &git_command).unwrap();
+
+        assert_eq!(output["git_branch"], "release/2.0.0");
+        assert_eq!(output["app_version"], "2.0.0-rc.3+abcdef0");
+    }
+
+    #[test]
+    fn test_get_version_pull_request() {
+        let mut git_command = MockGitCommandTrait::new();
+        let mocks: Vec<(&str, Result<&str, &str>)> = vec![
+            ("branch --show-current", Ok("")),
+            ("rev-parse --short HEAD", Ok("1234567")),
+            ("rev-parse --is-shallow-repository", Ok("false")),
+            ("rev-list --count HEAD", Ok("9")),
+            ("describe --abbrev=0 --tags", Ok("1.2.3")),
+            ("rev-list --count 1.2.3..HEAD", Ok("3")),
+        ];
+        for (command, output) in mocks {
+            git_command
+                .expect_run()
+                .withf(move |args| args.join(" ") == command)
+                .returning(move |_| output.map(str::to_string).map_err(Error::msg));
+        }
+        git_command.expect_run().returning(|_| Err(Error::msg("")));
+        let calculator = VersionCalculator::new(&git_command, VersionOptions::default()).unwrap();
+
+        let output = calculator
+            .calculate_with_env(|name| match name {
+                "GITHUB_HEAD_REF" => Some("feature/login".to_string()),
+                "GITHUB_REF" => Some("refs/pull/123/merge".to_string()),
+                _ => None,
+            })
+            .unwrap()
+            .to_json();
+
+        assert_eq!(output["git_branch"], "feature/login");
+        assert_eq!(output["branch_class"], "pull-request");
+        assert_eq!(output["pr_number"], 123);
+        assert_eq!(output["app_version"], "1.2.4-pr.123.3+1234567");
+    }
+
+    #[test]
+    fn test_tags_created_after_commit() {
+        let mut git_command = MockGitCommandTrait::new();
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "for-each-ref")
+            .returning(|_| {
+                Ok(
+                    "1.0.0\t100\tabc\t\n1.1.0\t300\tdef\tcommit\nlatest*\t300\tcommit\t"
+                        .to_string(),
+                )
+            });
+        let as_of = AsOf {
+            commit: "commit".to_string(),
+            timestamp: 200,
+            is_commit: true,
+        };
+
+        assert_eq!(
+            tags_created_after(&git_command, &as_of).unwrap(),
+            Vec::<String>::new()
+        );
+        let as_of = AsOf {
+            is_commit: false,
+            ..as_of
+        };
+        assert_eq!(
+            tags_created_after(&git_command, &as_of).unwrap(),
+            vec!["1.1.0", "latest\\*"]
+        );
+    }
+
+    #[test]
+    fn test_get_version_namespaced_tags() {
+        let mut git_command = MockGitCommandTrait::new();
+        let branch = "develop";
+        let rev = "1234567";
+        let count = "1";
+
+        let mut args = VersionOptions {
+            app_name: Some("api".to_string()),
+            fetch: true,
+            ..Default::default()
+        };
+        args.apply_config(Config::parse("[tag]\nformat = \"apps/{app_name}/{version}\"").unwrap());
+
+        git_command
+            .expect_run()
+            .withf(|args| {
+                args.join(" ")
+                    == "fetch origin +refs/heads/*:refs/remotes/origin/* refs/tags/apps/api/*:refs/tags/apps/api/*"
+            })
+            .times(1)
+            .returning(|_| Ok(String::new()));
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "describe --abbrev=0 --match apps/api/[0-9]* --tags")
+            .returning(|_| Ok("apps/api/1.2.0".to_string()));
+        mock_git(&mut git_command, None, branch, rev, count, None);
+
+        let output = get_version_output(&args, &git_command).unwrap();
+
+        let expected_version = Version::parse("1.2.1-beta.1+1234567").unwrap();
+        assert_expected_version(branch, rev, count, expected_version, output);
+    }
+
+    #[test]
+    fn test_get_version_sibling_app_tags_excluded() {
+        let mut git_command = MockGitCommandTrait::new();
+        let branch = "develop";
+        let rev = "1234567";
+        let count = "1";
+
+        let mut args = VersionOptions {
+            app_name: Some("app".to_string()),
+            ..Default::default()
+        };
+        args.apply_config(Config::parse("[apps.app]\n[apps.app-ui]\n[apps.web]").unwrap());
+
+        git_command
+            .expect_run()
+            .withf(|args| {
+                args.join(" ") == "describe --abbrev=0 --match app-[0-9]* --exclude app-ui-* --tags"
+            })
+            .returning(|_| Ok("app-1.2.0".to_string()));
+        mock_git(&mut git_command, None, branch, rev, count, None);
+
+        let output = get_version_output(&args, &git_command).unwrap();
+
+        let expected_version = Version::parse("1.2.1-beta.1+1234567").unwrap();
+        assert_expected_version(branch, rev, count, expected_version, output);
+    }
+
+    #[test]
+    fn test_get_version_config_max_branch_length() {
+        let mut git_command = MockGitCommandTrait::new();
+        let branch = "feature/feat-1";
+        let rev = "1234567";
+        let count = "1";
+
+        let mut args = VersionOptions::default();
+        args.apply_config(Config::parse("[build_metadata]\nmax_branch_length = 7").unwrap());
+
+        mock_git(&mut git_command, None, branch, rev, count, Some("1.0.0"));
+
+        let output = get_version_output(&args, &git_command).unwrap();
+
+        let expected_version = Version::parse("1.0.1-alpha.1+feature.1234567").unwrap();
+        assert_expected_version(branch, rev, count, expected_version, output);
+    }
+
+    #[test]
+    fn test_get_version_explain() {
+        let branch = "feature/login";
+        let rev = "1234567";
+        let count = "3";
+        let mut git_command = MockGitCommandTrait::new();
+        mock_git(&mut git_command, None, branch, rev, count, Some("1.0.0"));
+        let options = VersionOptions {
+            explain: Some(Explain::Field),
+            ..Default::default()
+        };
+
+        let output = get_version_output(&options, &git_command).unwrap();
+
+        let steps: Vec<(&str, &str)> = output["trace"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|step| {
+                (
+                    step["step"].as_str().unwrap(),
+                    step["detail"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            steps,
+            vec![
+                ("branch", "'feature/login'"),
+                ("commit", "1234567 with 3 commits in total"),
+                ("class", "feature, matching none of the branch patterns"),
+                ("tag", "'1.0.0', the latest reachable from the commit"),
+                ("bump", "patch, following the patch bump strategy"),
+                ("counter", "3, the commits since the tag '1.0.0'"),
+                ("version", "1.0.1-alpha.3+feature-login.1234567 from 1.0.0 with the patch bump, pre-release label 'alpha', counter 3 and escaped branch 'feature-login'"),
+                ("result", "Version 1.0.1-alpha.3+feature-login.1234567"),
+            ]
+        );
+        // No trace without `--explain=field`
+        let mut git_command = MockGitCommandTrait::new();
+        mock_git(&mut git_command, None, branch, rev, count, Some("1.0.0"));
+        let output = get_version_output(&VersionOptions::default(), &git_command).unwrap();
+        assert!(output.get("trace").is_none());
+    }
+
+    #[test]
+    fn test_get_version_release_branch_base() {
+        let branch = "develop";
+        let rev = "1234567";
+        let count = "3";
+        let options = VersionOptions {
+            release_branch_base: true,
+            ..Default::default()
+        };
+        let mock_branches = |git_command: &mut MockGitCommandTrait, refs: &'static str| {
+            git_command
+                .expect_run()
+                .withf(|args| {
+                    args.join(" ") == "for-each-ref --format=%(refname) refs/heads refs/remotes"
+                })
+                .returning(move |_| Ok(refs.to_string()));
+        };
+
+        let mut git_command = MockGitCommandTrait::new();
+        mock_branches(
+            &mut git_command,
+            "refs/heads/develop\nrefs/remotes/origin/release/1.5.0\nrefs/remotes/origin/release/2.0.0",
+        );
+        mock_git(&mut git_command, None, branch, rev, count, Some("1.4.2"));
+        let output = get_version_output(&options, &git_command).unwrap();
+        let expected_version = Version::parse("2.0.1-beta.3+1234567").unwrap();
+        assert_expected_version(branch, rev, count, expected_version, output);
+
+        // Released already
+        let mut git_command = MockGitCommandTrait::new();
+        mock_branches(&mut git_command, "refs/heads/release/1.4.0");
+        mock_git(&mut git_command, None, branch, rev, count, Some("1.4.2"));
+        let output = get_version_output(&options, &git_command).unwrap();
+        let expected_version = Version::parse("1.4.3-beta.3+1234567").unwrap();
+        assert_expected_version(branch, rev, count, expected_version, output);
+    }
+
+    #[test]
+    fn test_get_version_dirty() {
+        let branch = "develop";
+        let rev = "1234567";
+        let count = "3";
+        let mock_status = |git_command: &mut MockGitCommandTrait, status: &'static str| {
+            git_command
+                .expect_run()
+                .withf(|args| args.join(" ") == "status --porcelain --untracked-files=no")
+                .returning(move |_| Ok(status.to_string()));
+        };
+        let options = |dirty| VersionOptions {
+            dirty: Some(dirty),
+            ..Default::default()
+        };
+
+        let mut git_command = MockGitCommandTrait::new();
+        mock_status(&mut git_command, " M src/main.rs\nA  src/lib.rs");
+        mock_git(&mut git_command, None, branch, rev, count, Some("1.0.0"));
+        let output = get_version_output(&options(Dirty::Metadata), &git_command).unwrap();
+        let expected_version = Version::parse("1.0.1-beta.3+1234567.dirty").unwrap();
+        assert_expected_version(branch, rev, count, expected_version, output);
+
+        let mut git_command = MockGitCommandTrait::new();
+        mock_status(&mut git_command, " M src/main.rs");
+        mock_git(&mut git_command, None, branch, rev, count, Some("1.0.0"));
+        let err = get_version_output(&options(Dirty::Fail), &git_command).unwrap_err();
+        assert!(matches!(
+            crate::error::version_error(&err),
+            Some(VersionError::DirtyWorktree(_))
+        ));
+
+        // Clean
+        let mut git_command = MockGitCommandTrait::new();
+        mock_status(&mut git_command, "");
+        mock_git(&mut git_command, None, branch, rev, count, Some("1.0.0"));
+        let output = get_version_output(&options(Dirty::Fail), &git_command).unwrap();
+        let expected_version = Version::parse("1.0.1-beta.3+1234567").unwrap();
+        assert_expected_version(branch, rev, count, expected_version, output);
+    }
+
+    #[test]
+    fn test_get_version_calver_clock_skew() {
+        let branch = "develop";
+        let mock_remote_ref = |git_command: &mut MockGitCommandTrait, days: i64| {
+            let timestamp = (days_ago(0) as i64 - days * SECONDS_PER_DAY as i64).to_string();
+            git_command
+                .expect_run()
+                .withf(|args| args[0] == "for-each-ref" && args[1] == "--sort=-committerdate")
+                .returning(move |_| Ok(format!("{} origin/develop", timestamp)));
+        };
+        let mut options = VersionOptions {
+            scheme: Some(Scheme::Calver),
+            clock_skew: Some(ClockSkew::Fail),
+            ..Default::default()
+        };
+        options.apply_config(Config::default());
+
+        // Pushed from a machine two days ahead
+        let mut git_command = MockGitCommandTrait::new();
+        mock_remote_ref(&mut git_command, -2);
+        mock_git(
+            &mut git_command,
+            None,
+            branch,
+            "1234567",
+            "3",
+            Some("1.0.0"),
+        );
+        let err = get_version_output(&options, &git_command).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("hours behind the remote ref 'origin/develop'"),
+            "{}",
+            err
+        );
+
+        let mut git_command = MockGitCommandTrait::new();
+        mock_remote_ref(&mut git_command, 1);
+        mock_git(
+            &mut git_command,
+            None,
+            branch,
+            "1234567",
+            "3",
+            Some("1.0.0"),
+        );
+        assert!(get_version_output(&options, &git_command).is_ok());
+    }
+
+    #[test]
+    fn test_get_version_non_ascii_branch() {
+        let branch = "feature/función";
+        let rev = "1234567";
+        let count = "1";
+
+        let mut git_command = MockGitCommandTrait::new();
+        mock_git(&mut git_command, None, branch, rev, count, Some("1.0.0"));
+        let output = get_version_output(&VersionOptions::default(), &git_command).unwrap();
+
+        let expected_version = Version::parse("1.0.1-alpha.1+feature-funcion.1234567").unwrap();
+        assert_expected_version(branch, rev, count, expected_version, output);
+
+        let mut git_command = MockGitCommandTrait::new();
+        mock_git(&mut git_command, None, branch, rev, count, Some("1.0.0"));
+        let mut args = VersionOptions::default();
+        args.apply_config(Config::parse("[build_metadata]\nnon_ascii = \"replace\"").unwrap());
+        let output = get_version_output(&args, &git_command).unwrap();
+
+        let expected_version = Version::parse("1.0.1-alpha.1+feature-funci-n.1234567").unwrap();
+        assert_expected_version(branch, rev, count, expected_version, output);
+    }
+
+    #[test]
+    fn test_calculate_apps() {
+        let mut git_command = MockGitCommandTrait::new();
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "fetch --tags")
+            .times(1)
+            .returning(|_| Ok(String::new()));
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "rev-list --count HEAD -- services/api")
+            .returning(|_| Ok(String::from("2")));
+        for app_name in ["api", "web"] {
+            mock_git(
+                &mut git_command,
+                Some(app_name),
+                "develop",
+                "1234567",
+                "3",
+                Some("1.0.0"),
+            );
+        }
+        let mut options = VersionOptions {
+            app_names: vec!["api".to_string(), "web".to_string()],
+            fetch: true,
+            ..Default::default()
+        };
+        options.apply_config(Config::parse("[apps.api]\npath = 'services/api'").unwrap());
+
+        let apps = options.apps().unwrap();
+        let versions = calculate_apps(&git_command, &options, &apps).unwrap();
+
+        assert_eq!(options.app_name, None);
+        assert_eq!(apps, vec!["api", "web"]);
+        assert_eq!(versions["api"].rev_count, "2");
+        assert_eq!(versions["web"].rev_count, "3");
+    }
+
+    #[test]
+    fn test_calculate_apps_compiles_shared_regexes_once() {
+        let mut git_command = MockGitCommandTrait::new();
+        git_command.expect_run().returning(|args| {
+            match args[..2] {
+                ["branch", "--show-current"] => Ok("develop"),
+                ["rev-parse", "--short"] => Ok("1234567"),
+                ["rev-parse", "--is-shallow-repository"] => Ok("false"),
+                ["rev-list", "--count"] => Ok("3"),
+                _ => Err(""),
+            }
+            .map(str::to_string)
+            .map_err(Error::msg)
+        });
+        let apps: Vec<String> = (0..20).map(|app| format!("scaling{}", app)).collect();
+        let mut options = VersionOptions {
+            all_apps: true,
+            jobs: Some(4),
+            ..Default::default()
+        };
+        let config = apps
+            .iter()
+            .map(|app| format!("[apps.{}]\npath = 'services/{}'\n", app, app))
+            .collect::<String>();
+        options.apply_config(
+            Config::parse(&format!("[branches]\nmain = '^scaling-main$'\n{}", config)).unwrap(),
+        );
+
+        let versions = calculate_apps(&git_command, &options, &apps).unwrap();
+
+        assert_eq!(versions.len(), 20);
+        assert_eq!(
+            versions["scaling0"].version.to_string(),
+            "0.0.1-beta.3+1234567"
+        );
+        let compilations = crate::models::COMPILATIONS.lock().unwrap();
+        assert_eq!(compilations["^scaling-main$"], 1);
+        // The tag regexes of the apps are compiled once per app
+        for app in &apps {
+            let tag = format!("^{}", regex_lite::escape(&format!("{}-", app)));
+            assert_eq!(
+                compilations
+                    .iter()
+                    .filter(|(pattern, _)| pattern.starts_with(&tag))
+                    .map(|(_, count)| count)
+                    .collect::<Vec<_>>(),
+                [&1]
+            );
+        }
+    }
+
+    // Wall-clock comparison, which is flaky on loaded machines. Run with `cargo test -- --ignored`.
+    #[test]
+    #[ignore]
+    fn test_calculate_apps_scales_linearly() {
+        let mut git_command = MockGitCommandTrait::new();
+        git_command.expect_run().returning(|args| {
+            match args[..2] {
+                ["branch", "--show-current"] => Ok("develop"),
+                ["rev-parse", "--short"] => Ok("1234567"),
+                ["rev-parse", "--is-shallow-repository"] => Ok("false"),
+                ["rev-list", "--count"] => Ok("3"),
+                _ => Err(""),
+            }
+            .map(str::to_string)
+            .map_err(Error::msg)
+        });
+        let duration = |count: usize| {
+            let apps: Vec<String> = (0..count).map(|app| format!("app{}", app)).collect();
+            let mut options = VersionOptions {
+                all_apps: true,
+                jobs: Some(1),
+                ..Default::default()
+            };
+            let config = apps
+                .iter()
+                .map(|app| format!("[apps.{}]\npath = 'services/{}'\n", app, app))
+                .collect::<String>();
+            options.apply_config(Config::parse(&config).unwrap());
+            let start = std::time::Instant::now();
+            let versions = calculate_apps(&git_command, &options, &apps).unwrap();
+            assert_eq!(versions.len(), count);
+            assert_eq!(versions["app0"].version.to_string(), "0.0.1-beta.3+1234567");
+            start.elapsed()
+        };
+
+        // Warm up, then compare the time per app of a small and a large run
+        duration(10);
+        let small = duration(10) / 10;
+        let large = duration(80) / 80;
+        assert!(
+            large < small * 3 + std::time::Duration::from_millis(1),
+            "{:?} per app for 80 apps, {:?} for 10 apps",
+            large,
+            small
+        );
+    }
+
+    #[test]
+    fn test_calculate_apps_first_error() {
+        let mut git_command = MockGitCommandTrait::new();
+        for (app_name, path) in [("api", "services/api"), ("web", "services/web")] {
+            git_command
+                .expect_run()
+                .withf(move |args| args.join(" ") == format!("rev-list --count HEAD -- {}", path))
+                .returning(move |_| Err(Error::msg(format!("{} failed", app_name))));
+        }
+        for app_name in ["api", "web", "worker"] {
+            mock_git(
+                &mut git_command,
+                Some(app_name),
+                "develop",
+                "1234567",
+                "3",
+                Some("1.0.0"),
+            );
+        }
+        let mut options = VersionOptions {
+            all_apps: true,
+            jobs: Some(3),
+            ..Default::default()
+        };
+        options.apply_config(
+            Config::parse(
+                "[apps.api]\npath = 'services/api'\n[apps.web]\npath = 'services/web'\n[apps.worker]",
+            )
+            .unwrap(),
+        );
+
+        let apps = options.apps().unwrap();
+        for _ in 0..10 {
+            let result = calculate_apps(&git_command, &options, &apps);
+            assert_eq!(result.unwrap_err().to_string(), "api failed");
+        }
+    }
+
+    #[test]
+    fn test_apps() {
+        let mut options = VersionOptions {
+            app_names: vec!["api".to_string()],
+            ..Default::default()
+        };
+        options.apply_config(Config::parse("[apps.web]\n[apps.api]").unwrap());
+
+        assert_eq!(options.app_name, Some("api".to_string()));
+        assert_eq!(options.apps().unwrap(), Vec::<String>::new());
+        options.all_apps = true;
+        assert_eq!(options.apps().unwrap(), vec!["api", "web"]);
+        options.config.apps.clear();
+        assert!(options.apps().is_err());
+    }
+
+    #[test]
+    fn test_apply_config_options_take_precedence() {
+        let mut args = VersionOptions {
+            app_name: Some(String::from("myapp")),
+            ..Default::default()
+        };
+        args.apply_config(
+            Config::parse("app_name = \"other\"\ndefault_branch = \"trunk\"").unwrap(),
+        );
+
+        assert_eq!(args.app_name, Some(String::from("myapp")));
+        assert_eq!(args.default_branch(), "trunk");
+    }
+
+    #[test]
+    fn test_apply_config_apps() {
+        let config = Config::parse(
+            "bump_strategy = \"conventional\"\n[apps.api]\nbump_strategy = \"patch\"\n[apps.web]",
+        )
+        .unwrap();
+        let options = |app_names: Vec<&str>, bump_strategy: Option<BumpStrategy>| {
+            let mut options = VersionOptions {
+                app_names: app_names.into_iter().map(String::from).collect(),
+                bump_strategy,
+                ..Default::default()
+            };
+            options.apply_config(config.clone());
+            options
+        };
+
+        // The app configuration over the global one
+        assert_eq!(
+            options(vec!["api"], None).bump_strategy(),
+            BumpStrategy::Patch
+        );
+        assert_eq!(
+            options(vec!["web"], None).bump_strategy(),
+            BumpStrategy::Conventional
+        );
+        // The options over the app configuration
+        assert_eq!(
+            options(vec!["api"], Some(BumpStrategy::Conventional)).bump_strategy(),
+            BumpStrategy::Conventional
+        );
+
+        // Also per app of multiple apps
+        let multiple = options(vec!["api", "web"], None);
+        assert_eq!(
+            app_options(&multiple, "api").bump_strategy(),
+            BumpStrategy::Patch
+        );
+        assert_eq!(
+            app_options(&multiple, "web").bump_strategy(),
+            BumpStrategy::Conventional
+        );
+        let multiple = options(vec!["api", "web"], Some(BumpStrategy::Conventional));
+        assert_eq!(
+            app_options(&multiple, "api").bump_strategy(),
+            BumpStrategy::Conventional
+        );
+    }
+
+    #[test]
+    fn test_get_version_config_sha_length() {
+        let mut git_command = MockGitCommandTrait::new();
+        let branch = "develop";
+        let rev = "1234567890ab";
+        let count = "1";
+
+        let mut args = VersionOptions::default();
+        args.apply_config(Config::parse("[build_metadata]\nsha_length = 12").unwrap());
+
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "rev-parse" && args[1] == "--short=12" && args[2] == "HEAD")
+            .returning(move |_| Ok(rev.to_string()));
+        mock_git(
+            &mut git_command,
+            None,
+            branch,
+            "1234567",
+            count,
+            Some("1.0.0"),
+        );
+
+        let output = get_version_output(&args, &git_command).unwrap();
+
+        let expected_version = Version::parse("1.0.1-beta.1+1234567890ab").unwrap();
+        assert_expected_version(branch, rev, count, expected_version, output);
+    }
+
+    #[test]
+    fn test_get_version_develop_branch_conventional_commits() {
+        let mut git_command = MockGitCommandTrait::new();
+        let branch = "develop";
+        let rev = "1234567";
+        let count = "1";
+
+        let args = VersionOptions {
+            bump_strategy: Some(BumpStrategy::Conventional),
+            ..Default::default()
+        };
+
+        git_command
+            .expect_run()
+            .withf(|args| {
+                args[0] == "log" && args[1] == "--format=%B%x00" && args[2] == "1.0.0..HEAD"
+            })
+            .returning(|_| Ok("fix: b\n\0feat(cli): a\n\0Merge branch 'x'\n".to_string()));
+        mock_git(&mut git_command, None, branch, rev, count, Some("1.0.0"));
+
+        let output = get_version_output(&args, &git_command).unwrap();
+
+        assert_eq!(output["bump"], "minor");
+        let expected_version = Version::parse("1.1.0-beta.1+1234567").unwrap();
+        assert_expected_version(branch, rev, count, expected_version, output);
+    }
+
+    #[test]
+    fn test_get_version_increment_hints() {
+        let get_version = |branch: &'static str, messages: &'static str| {
+            let mut git_command = MockGitCommandTrait::new();
+            git_command
+                .expect_run()
+                .withf(|args| args.join(" ") == "log --format=%B%x00 1.0.0..HEAD")
+                .returning(move |_| Ok(messages.to_string()));
+            mock_git(
+                &mut git_command,
+                None,
+                branch,
+                "1234567",
+                "1",
+                Some("1.0.0"),
+            );
+            let mut options = VersionOptions::default();
+            options.apply_config(Config::parse("[increment]\nenabled = true").unwrap());
+            get_version_output(&options, &git_command).unwrap()
+        };
+
+        let output = get_version("develop", "Add login\n\n+semver: minor\n\0Fix typo\n");
+        assert_eq!(output["bump"], "minor");
+        assert_eq!(output["app_version"], "1.1.0-beta.1+1234567");
+        assert_eq!(
+            get_version("develop", "+semver: breaking\n\0+semver: feature\n")["bump"],
+            "major"
+        );
+        assert_eq!(get_version("develop", "Fix typo\n")["bump"], "patch");
+        assert_eq!(
+            get_version("feature/minor/login", "Fix typo\n")["app_version"],
+            "1.1.0-alpha.1+feature-minor-login.1234567"
+        );
+        // A major branch doesn't need the messages
+        assert_eq!(get_version("feature/major/new-api", "")["bump"], "major");
+    }
+
+    #[test]
+    fn test_get_version_bump_message_regex() {
+        let get_version = |messages: &'static str| {
+            let mut git_command = MockGitCommandTrait::new();
+            git_command
+                .expect_run()
+                .withf(|args| args.join(" ") == "log --format=%B%x00 1.0.0..HEAD")
+                .returning(move |_| Ok(messages.to_string()));
+            mock_git(
+                &mut git_command,
+                None,
+                "develop",
+                "1234567",
+                "1",
+                Some("1.0.0"),
+            );
+            let mut options = DefaultOptions::try_parse_from([
+                "version-vine",
+                "--bump-message-regex",
+                r"minor=^\[FEATURE\]",
+            ])
+            .unwrap()
+            .options;
+            options.apply_config(
+                Config::parse("[[increment.messages]]\nbump = 'major'\nregex = '(?m)^BREAKING:'")
+                    .unwrap(),
+            );
+            get_version_output(&options, &git_command).unwrap()["bump"].clone()
+        };
+
+        assert_eq!(get_version("[FEATURE] Add login\n\0"), "minor");
+        assert_eq!(get_version("Fix typo\n\nBREAKING: drop v1\n\0"), "major");
+        // The built-in directives still apply
+        assert_eq!(get_version("Fix typo\n\n+semver: minor\n\0"), "minor");
+        assert_eq!(get_version("Fix [FEATURE] typo\n\0"), "patch");
+        assert!(
+            DefaultOptions::try_parse_from(["version-vine", "--bump-message-regex", "big=x"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_get_version_feature_branch_conventional_commits_breaking() {
+        let mut git_command = MockGitCommandTrait::new();
+        let branch = "feature/feat-1";
+        let rev = "1234567";
+        let count = "1";
+
+        let args = VersionOptions {
+            bump_strategy: Some(BumpStrategy::Conventional),
+            ..Default::default()
+        };
+
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "log" && args[1] == "--format=%B%x00")
+            .returning(|_| Ok("feat: b\n\nBREAKING CHANGE: removed a\n".to_string()));
+        mock_git(&mut git_command, None, branch, rev, count, Some("1.2.3"));
+
+        let output = get_version_output(&args, &git_command).unwrap();
+
+        assert_eq!(output["bump"], "major");
+        let expected_version = Version::parse("2.0.0-alpha.1+feature-feat-1.1234567").unwrap();
+        assert_expected_version(branch, rev, count, expected_version, output);
+    }
+
+    #[test]
+    fn test_get_version_path_unchanged_since_tag() {
+        let mut git_command = MockGitCommandTrait::new();
+
+        let args = VersionOptions {
+            app_name: Some(String::from("myapp")),
+            path: Some(String::from("services/myapp")),
+            counter: Some(Counter::Total),
+            ..Default::default()
+        };
+
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "rev-list --count HEAD -- services/myapp")
+            .times(1)
+            .returning(|_| Ok(String::from("4")));
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "rev-list --count myapp-1.0.0..HEAD -- services/myapp")
+            .times(1)
+            .returning(|_| Ok(String::from("0")));
+        mock_git(
+            &mut git_command,
+            Some("myapp"),
+            "develop",
+            "1234567",
+            "1",
+            Some("1.0.0"),
+        );
+
+        let output = get_version_output(&args, &git_command).unwrap();
+
+        assert_eq!(output["rev_count"], "4");
+        assert_eq!(output["app_version"], "1.0.1-beta.4+1234567");
+        assert_eq!(output["changed_since_tag"], false);
+    }
+
+    #[test]
+    fn test_get_version_path_bump() {
+        let mut git_command = MockGitCommandTrait::new();
+        // `web` was released as 1.1.0 after the latest change of `api`, released as 1.0.0
+        let mocks: Vec<(&str, Result<&str, &str>)> = vec![
+            ("branch --show-current", Ok("develop")),
+            ("rev-parse --short HEAD", Ok("1234567")),
+            ("rev-parse --is-shallow-repository", Ok("false")),
+            ("rev-list --count HEAD -- services/api", Ok("12")),
+            ("rev-list -1 HEAD -- services/api", Ok("abcdef0")),
+            ("describe --abbrev=0 --tags abcdef0", Ok("1.0.0")),
+            ("describe --abbrev=0 --tags", Ok("1.1.0")),
+            (
+                "log --format=%B%x00 1.0.0..HEAD -- services/api",
+                Ok("fix: api\0"),
+            ),
+            (
+                "log --format=%B%x00 1.0.0..HEAD",
+                Ok("feat: web\0fix: api\0"),
+            ),
+            ("rev-list --count 1.0.0..HEAD -- services/api", Ok("1")),
+        ];
+        for (command, output) in mocks {
+            git_command
+                .expect_run()
+                .withf(move |args| args.join(" ") == command)
+                .returning(move |_| output.map(str::to_string).map_err(Error::msg));
+        }
+        git_command.expect_run().returning(|_| Err(Error::msg("")));
+        let args = VersionOptions {
+            path: Some(String::from("services/api")),
+            bump_strategy: Some(BumpStrategy::Conventional),
+            ..Default::default()
+        };
+
+        let output = get_version_output(&args, &git_command).unwrap();
+
+        assert_eq!(output["base_tag"], "1.0.0");
+        assert_eq!(output["bump"], "patch");
+        assert_eq!(output["app_version"], "1.0.1-beta.1+1234567");
+        assert_eq!(output["changed_since_tag"], true);
+    }
+
+    #[test]
+    fn test_get_version_counter_distance() {
+        let mut git_command = MockGitCommandTrait::new();
+
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "rev-list --count HEAD")
+            .returning(|_| Ok(String::from("120")));
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "rev-list --count 1.0.0..HEAD")
+            .returning(|_| Ok(String::from("2")));
+        mock_git(
+            &mut git_command,
+            None,
+            "develop",
+            "1234567",
+            "1",
+            Some("1.0.0"),
+        );
+
+        let output = get_version_output(&VersionOptions::default(), &git_command).unwrap();
+
+        assert_eq!(output["rev_count"], "120");
+        assert_eq!(output["app_version"], "1.0.1-beta.2+1234567");
+    }
+
+    #[test]
+    fn test_get_version_counter_merge_base() {
+        let mut git_command = MockGitCommandTrait::new();
+
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "rev-parse --verify --quiet develop")
+            .returning(|_| Err(Error::msg("")));
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "rev-list --count main..HEAD")
+            .returning(|_| Ok(String::from("2")));
+        mock_git(
+            &mut git_command,
+            None,
+            "feature/login",
+            "1234567",
+            "120",
+            Some("1.0.0"),
+        );
+        let options = VersionOptions {
+            counter: Some(Counter::MergeBase),
+            ..Default::default()
+        };
+
+        let output = get_version_output(&options, &git_command).unwrap();
+
+        assert_eq!(output["app_version"], "1.0.1-alpha.2+feature-login.1234567");
+    }
+
+    #[test]
+    fn test_get_version_counter_merge_base_develop() {
+        let mut git_command = MockGitCommandTrait::new();
+
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "rev-list --count origin/develop..HEAD")
+            .returning(|_| Ok(String::from("3")));
+        mock_git(
+            &mut git_command,
+            None,
+            "feature/login",
+            "1234567",
+            "120",
+            Some("1.0.0"),
+        );
+        let options = VersionOptions {
+            counter: Some(Counter::MergeBase),
+            counter_base: Some("origin/develop".to_string()),
+            ..Default::default()
+        };
+
+        let output = get_version_output(&options, &git_command).unwrap();
+
+        assert_eq!(output["app_version"], "1.0.1-alpha.3+feature-login.1234567");
+
+        // The develop branch itself counts the distance to the tag
+        let mut git_command = MockGitCommandTrait::new();
+        mock_git(
+            &mut git_command,
+            None,
+            "develop",
+            "1234567",
+            "4",
+            Some("1.0.0"),
+        );
+
+        let output = get_version_output(&options, &git_command).unwrap();
+
+        assert_eq!(output["app_version"], "1.0.1-beta.4+1234567");
+    }
+
+    #[test]
+    fn test_get_version_merge_strategy() {
+        let get_version = |log: &'static str| {
+            let mut git_command = MockGitCommandTrait::new();
+            git_command
+                .expect_run()
+                .withf(|args| args[0] == "log" && args[1] == "--first-parent")
+                .returning(move |args| {
+                    assert_eq!(
+                        args.join(" "),
+                        "log --first-parent --max-count=100 --format=%p%x1f%s main"
+                    );
+                    Ok(log.to_string())
+                });
+            git_command
+                .expect_run()
+                .withf(|args| args.join(" ") == "rev-list --count --first-parent 1.0.0..HEAD")
+                .returning(|_| Ok(String::from("2")));
+            mock_git(
+                &mut git_command,
+                None,
+                "develop",
+                "1234567",
+                "5",
+                Some("1.0.0"),
+            );
+            let options = VersionOptions {
+                merge_strategy: Some(MergeStrategy::Auto),
+                ..Default::default()
+            };
+            get_version_output(&options, &git_command).unwrap()["app_version"].clone()
+        };
+
+        // Merge commits count once, without the commits they bring in
+        assert_eq!(
+            get_version("aaa bbb\x1fMerge pull request #13 from me/login\nbbb\x1fAdd login (#12)\nccc ddd\x1fMerge branch 'fix'\n"),
+            "1.0.1-beta.2+1234567"
+        );
+        assert_eq!(
+            get_version(
+                "aaa\x1fAdd login (#12)\nbbb\x1fFix crash (#11)\nccc ddd\x1fMerge branch 'fix'\n"
+            ),
+            "1.0.1-beta.5+1234567"
+        );
+    }
+
+    #[test]
+    fn test_get_version_count_strategy() {
+        let get_version = |count_strategy: Option<CountStrategy>,
+                           merge_strategy: Option<MergeStrategy>| {
+            let mut git_command = MockGitCommandTrait::new();
+            git_command
+                .expect_run()
+                .withf(|args| args.join(" ") == "rev-list --count --first-parent 1.0.0..HEAD")
+                .returning(|_| Ok(String::from("2")));
+            git_command
+                .expect_run()
+                .withf(|args| args.join(" ") == "rev-list --count --no-merges 1.0.0..HEAD")
+                .returning(|_| Ok(String::from("3")));
+            mock_git(
+                &mut git_command,
+                None,
+                "develop",
+                "1234567",
+                "5",
+                Some("1.0.0"),
+            );
+            let options = VersionOptions {
+                count_strategy,
+                merge_strategy,
+                ..Default::default()
+            };
+            get_version_output(&options, &git_command).unwrap()["app_version"].clone()
+        };
+
+        assert_eq!(get_version(None, None), "1.0.1-beta.5+1234567");
+        assert_eq!(
+            get_version(Some(CountStrategy::FirstParent), None),
+            "1.0.1-beta.2+1234567"
+        );
+        assert_eq!(
+            get_version(Some(CountStrategy::NoMerges), None),
+            "1.0.1-beta.3+1234567"
+        );
+        // The merge strategy implies first-parent counting, unless the count strategy is provided
+        assert_eq!(
+            get_version(None, Some(MergeStrategy::Merge)),
+            "1.0.1-beta.2+1234567"
+        );
+        assert_eq!(
+            get_version(Some(CountStrategy::All), Some(MergeStrategy::Merge)),
+            "1.0.1-beta.5+1234567"
+        );
+    }
+
+    #[test]
+    fn test_get_version_nightly() {
+        let nightly = |branch: &'static str, now: u64| {
+            let mut git_command = MockGitCommandTrait::new();
+            mock_git(
+                &mut git_command,
+                None,
+                branch,
+                "1234567",
+                "5",
+                Some("1.2.0"),
+            );
+            let options = VersionOptions {
+                nightly: true,
+                ..Default::default()
+            };
+            VersionCalculator::new(&git_command, options)
+                .unwrap()
+                .with_clock(FixedClock(now))
+                .calculate_with_env(|_| None)
+                .unwrap()
+                .version
+                .to_string()
+        };
+        // 2024-05-12T00:00:00Z
+        let midnight = 1715472000;
+
+        assert_eq!(
+            nightly("develop", midnight),
+            "1.2.1-nightly.20240512+1234567"
+        );
+        // The same commit on the same day, on any branch, also on untagged main commits
+        assert_eq!(
+            nightly("main", midnight + 23 * 60 * 60),
+            "1.2.1-nightly.20240512+1234567"
+        );
+        assert_eq!(
+            nightly("feature/login", midnight + 24 * 60 * 60),
+            "1.2.1-nightly.20240513+1234567"
+        );
+    }
+
+    #[test]
+    fn test_get_version_reproducible() {
+        let calculate = |now: u64, source_date_epoch: Option<&'static str>| {
+            let mut git_command = MockGitCommandTrait::new();
+            mock_git(
+                &mut git_command,
+                None,
+                "develop",
+                "1234567",
+                "5",
+                Some("1.2.0"),
+            );
+            let mut options = VersionOptions {
+                nightly: true,
+                ..Default::default()
+            };
+            options.apply_config(
+                Config::parse("reproducible = true\nfetch = true\nunique_prerelease = 'increment'")
+                    .unwrap(),
+            );
+            assert!(!options.fetch);
+            assert_eq!(options.unique_prerelease(), UniquePrerelease::Off);
+            VersionCalculator::new(&git_command, options)
+                .unwrap()
+                .with_clock(FixedClock(now))
+                .calculate_with_env(|name| match name {
+                    "SOURCE_DATE_EPOCH" => source_date_epoch.map(str::to_string),
+                    _ => None,
+                })
+                .unwrap()
+        };
+        // 2024-05-12T00:00:00Z
+        let midnight = 1715472000;
+
+        // The dates of the commit, whatever the clock
+        let version_info = calculate(midnight, None);
+        assert_eq!(version_info, calculate(midnight + 48 * 60 * 60, None));
+        assert_eq!(version_info.days_since_last_commit, Some(0));
+        assert_eq!(version_info.branch_age_days, Some(8));
+        assert_ne!(
+            version_info.version.to_string(),
+            "1.2.1-nightly.20240512+1234567"
+        );
+        assert_eq!(
+            calculate(0, Some("1715472000")).version.to_string(),
+            "1.2.1-nightly.20240512+1234567"
+        );
+    }
+
+    #[test]
+    fn test_get_version_unique_prerelease() {
+        let get_version = |unique: UniquePrerelease, remote_tags: &'static str| {
+            let mut git_command = MockGitCommandTrait::new();
+            git_command
+                .expect_run()
+                .withf(|args| args.join(" ") == "rev-parse HEAD")
+                .returning(|_| Ok("1234567890abcdef1234567890abcdef12345678".to_string()));
+            git_command
+                .expect_run()
+                .withf(|args| args.join(" ") == "ls-remote --tags origin")
+                .returning(move |_| Ok(remote_tags.to_string()));
+            mock_git(
+                &mut git_command,
+                Some("myapp"),
+                "feature/feat-1",
+                "1234567",
+                "1",
+                Some("1.0.0"),
+            );
+            let options = VersionOptions {
+                app_name: Some("myapp".to_string()),
+                unique_prerelease: Some(unique),
+                ..Default::default()
+            };
+            get_version_output(&options, &git_command)
+                .map(|output| output["app_version"].as_str().unwrap().to_string())
+        };
+        let published = "aaaaaaa\trefs/tags/myapp-1.0.1-alpha.1\n\
+                         bbbbbbb\trefs/tags/myapp-1.0.1-alpha.2\n\
+                         1234567890abcdef1234567890abcdef12345678\trefs/tags/myapp-1.0.1-alpha.2^{}";
+
+        assert_eq!(
+            get_version(UniquePrerelease::BranchHash, "").unwrap(),
+            format!(
+                "1.0.1-alpha.1.{}+feature-feat-1.1234567",
+                branch_hash("feature/feat-1")
+            )
+        );
+        assert_eq!(
+            get_version(UniquePrerelease::Off, published).unwrap(),
+            "1.0.1-alpha.1+feature-feat-1.1234567"
+        );
+        assert_eq!(
+            get_version(UniquePrerelease::Fail, published)
+                .unwrap_err()
+                .to_string(),
+            "Version 1.0.1-alpha.1+feature-feat-1.1234567 collides with the tag 'myapp-1.0.1-alpha.1' published on commit 'aaaaaaa' of origin. Use --unique-prerelease increment or branch-hash."
+        );
+        // The annotated alpha.2 tag points at the commit itself
+        assert_eq!(
+            get_version(UniquePrerelease::Increment, published).unwrap(),
+            "1.0.1-alpha.2+feature-feat-1.1234567"
+        );
+        assert_eq!(
+            get_version(UniquePrerelease::Increment, "").unwrap(),
+            "1.0.1-alpha.1+feature-feat-1.1234567"
+        );
+    }
+
+    #[test]
+    fn test_branch_hash() {
+        let hash = branch_hash("feature/feat-1");
+
+        assert_eq!(hash.len(), 6);
+        assert!(hash.bytes().all(|byte| byte.is_ascii_hexdigit()));
+        assert_eq!(hash, branch_hash("feature/feat-1"));
+        assert_ne!(hash, branch_hash("feature/feat-2"));
+    }
+
+    #[test]
+    fn test_get_version_renamed_default_branch() {
+        let mut git_command = MockGitCommandTrait::new();
+        mock_git(
+            &mut git_command,
+            None,
+            "master",
+            "1234567",
+            "3",
+            Some("1.2.0"),
+        );
+        let mut options = VersionOptions::default();
+        options.apply_config(Config::parse("[branches]\nmain = '^main$'").unwrap());
+
+        let output = get_version_output(&options, &git_command).unwrap();
+
+        // The former name of the default branch is versioned as it
+        assert_eq!(output["git_branch"], "main");
+        assert_eq!(output["branch_class"], "main");
+        assert_eq!(output["app_version"], "1.2.0+1234567");
+    }
+
+    #[test]
+    fn test_get_version_source() {
+        let get_version = |sources: Vec<VersionSource>, tag: Option<&'static str>| {
+            let mut git_command = MockGitCommandTrait::new();
+            let commands: Vec<(&str, Result<&str, &str>)> = vec![
+                (
+                    "show HEAD:./VERSION",
+                    Err("fatal: path 'VERSION' does not exist"),
+                ),
+                ("show HEAD:./version.txt", Ok("v2.3.0\n")),
+                (
+                    "log -1 --format=%(trailers:key=Version,valueonly) HEAD",
+                    Ok("1.9.0\n\n"),
+                ),
+            ];
+            for (command, output) in commands {
+                git_command
+                    .expect_run()
+                    .withf(move |args| args.join(" ") == command)
+                    .returning(move |_| output.map(String::from).map_err(Error::msg));
+            }
+            mock_git(&mut git_command, None, "develop", "1234567", "3", tag);
+            let options = VersionOptions {
+                version_source: sources,
+                ..Default::default()
+            };
+            get_version_output(&options, &git_command)
+        };
+
+        // The file is taken over the tag, the version of develop bumps it
+        let output = get_version(vec![VersionSource::File, VersionSource::Tag], Some("1.0.0"));
+        assert_eq!(output.unwrap()["app_version"], "2.3.1-beta.3+1234567");
+        // The tag comes first
+        let output = get_version(vec![VersionSource::Tag, VersionSource::File], Some("1.0.0"));
+        assert_eq!(output.unwrap()["app_version"], "1.0.1-beta.3+1234567");
+        // Falls back when there's no tag yet
+        let output = get_version(vec![VersionSource::Tag, VersionSource::Trailer], None);
+        assert_eq!(output.unwrap()["app_version"], "1.9.1-beta.3+1234567");
+    }
+
+    #[test]
+    fn test_get_version_on_tag() {
+        let get_version = |on_tag: OnTag, on_exact_tag: bool| {
+            let mut git_command = MockGitCommandTrait::new();
+            if !on_exact_tag {
+                git_command
+                    .expect_run()
+                    .withf(|args| args.contains(&"--exact-match"))
+                    .returning(|_| Err(Error::msg("fatal: no tag exactly matches")));
+            }
+            mock_git(
+                &mut git_command,
+                None,
+                "develop",
+                "1234567",
+                "1",
+                Some("1.0.0"),
+            );
+            let options = VersionOptions {
+                on_tag: Some(on_tag),
+                ..Default::default()
+            };
+            get_version_output(&options, &git_command).unwrap()
+        };
+
+        let output = get_version(OnTag::Tag, true);
+        assert_eq!(output["app_version"], "1.0.0");
+        assert_eq!(output["bump"], Value::Null);
+        assert_eq!(
+            get_version(OnTag::Metadata, true)["app_version"],
+            "1.0.0+1234567"
+        );
+        assert_eq!(
+            get_version(OnTag::Bump, true)["app_version"],
+            "1.0.1-beta.1+1234567"
+        );
+        // Commits after the tag are bumped
+        assert_eq!(
+            get_version(OnTag::Tag, false)["app_version"],
+            "1.0.1-beta.1+1234567"
+        );
+    }
+
+    #[test]
+    fn test_get_version_commit_metadata() {
+        let mut git_command = MockGitCommandTrait::new();
+        mock_git(
+            &mut git_command,
+            None,
+            "develop",
+            "1234567",
+            "1",
+            Some("1.0.0"),
+        );
+
+        let output = get_version_output(&VersionOptions::default(), &git_command).unwrap();
+
+        assert_eq!(
+            output["git_sha_full"],
+            "1234567890abcdef1234567890abcdef12345678"
+        );
+        assert_eq!(output["commit_epoch"], 1707908400);
+        assert_eq!(output["tag_date"], "2024-02-01T09:00:00+01:00");
+
+        let mut git_command = MockGitCommandTrait::new();
+        mock_git(&mut git_command, None, "develop", "1234567", "1", None);
+
+        let output = get_version_output(&VersionOptions::default(), &git_command).unwrap();
+
+        assert_eq!(output["tag_date"], Value::Null);
+    }
+
+    #[test]
+    fn test_get_version_tag_metadata() {
+        let mut git_command = MockGitCommandTrait::new();
+        git_command
+            .expect_run()
+            .withf(|args| args[1] == TAG_METADATA_FORMAT && args[2] == "refs/tags/1.0.0")
+            .returning(|_| {
+                Ok(String::from(
+                    "2024-02-01T09:00:00+01:00\ttag\tJane Doe <jane@example.com>\tsigned\nRelease 1.0.0\nAudited by QA\n",
+                ))
+            });
+        mock_git(
+            &mut git_command,
+            None,
+            "main",
+            "1234567",
+            "1",
+            Some("1.0.0"),
+        );
+        let options = VersionOptions {
+            require_signed_tags: true,
+            ..Default::default()
+        };
+
+        let output = get_version_output(&options, &git_command).unwrap();
+
+        assert_eq!(output["tag_message"], "Release 1.0.0\n\nAudited by QA");
+        assert_eq!(output["tagger"], "Jane Doe <jane@example.com>");
+        assert_eq!(output["tag_signed"], true);
+
+        // Lightweight
+        let mut git_command = MockGitCommandTrait::new();
+        mock_git(
+            &mut git_command,
+            None,
+            "main",
+            "1234567",
+            "1",
+            Some("1.0.0"),
+        );
+
+        let output = get_version_output(&VersionOptions::default(), &git_command).unwrap();
+
+        assert_eq!(output["tag_message"], Value::Null);
+        assert_eq!(output["tagger"], Value::Null);
+        assert_eq!(output["tag_signed"], Value::Null);
+        let result = get_version_output(&options, &git_command);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "The release tag '1.0.0' must be an annotated tag with a signature, e.g. created with `git tag -s`"
+        );
+    }
+
+    #[test]
+    fn test_get_version_counter_build_number() {
+        let mut git_command = MockGitCommandTrait::new();
+        mock_git(
+            &mut git_command,
+            None,
+            "develop",
+            "1234567",
+            "3",
+            Some("1.0.0"),
+        );
+        let args = VersionOptions {
+            build_number: Some(812),
+            ..Default::default()
+        };
+
+        let output = get_version_output(&args, &git_command).unwrap();
+
+        assert_eq!(output["app_version"], "1.0.1-beta.812+1234567");
+
+        let args = VersionOptions {
+            counter: Some(Counter::BuildNumber),
+            ..Default::default()
+        };
+        let result = get_version_output(&args, &git_command);
+
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("requires a --build-number"));
+    }
+
+    #[test]
+    fn test_get_version_inputs() {
+        let mut git_command = MockGitCommandTrait::new();
+        mock_git(
+            &mut git_command,
+            None,
+            "develop",
+            "1234567",
+            "1",
+            Some("1.0.0"),
+        );
+        let options = VersionOptions {
+            inputs: true,
+            ..Default::default()
+        };
+        let calculator = VersionCalculator::new(&git_command, options).unwrap();
+
+        let output = calculator
+            .calculate_with_env(|name| match name {
+                "SOURCE_DATE_EPOCH" => Some("1707908400".to_string()),
+                _ => None,
+            })
+            .unwrap()
+            .to_json();
+
+        let inputs = &output["inputs"];
+        assert_eq!(inputs["head_sha"], output["git_sha_full"]);
+        assert_eq!(inputs["base_tag"], "1.0.0");
+        assert_eq!(inputs["branch"], "develop");
+        assert_eq!(inputs["config_hash"], Config::default().hash());
+        assert_eq!(inputs["env"], json!({"SOURCE_DATE_EPOCH": "1707908400"}));
+    }
+
+    #[test]
+    fn test_get_version_counter_format() {
+        let mut git_command = MockGitCommandTrait::new();
+        mock_git(
+            &mut git_command,
+            None,
+            "develop",
+            "1234567",
+            "42",
+            Some("1.0.0"),
+        );
+        let mut options = VersionOptions::default();
+        options.config.counter_format.develop = CounterFormat::Padded;
+
+        let output = get_version_output(&options, &git_command).unwrap();
+
+        assert_eq!(output["app_version"], "1.0.1-beta00042+1234567");
+    }
+
+    fn mock_git_empty_branch(git_command: &mut MockGitCommandTrait, branch: &'static str) {
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "branch" && args[1] == "--show-current")
+            .returning(move |_| Ok(branch.to_string()));
+
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "rev-parse" && args[1] == "--short" && args[2] == "HEAD")
+            .returning(|_| Err(Error::msg("fatal: Needed a single revision")));
+
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "rev-parse -q --verify HEAD")
+            .returning(|_| Err(Error::msg("")));
+
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "symbolic-ref -q HEAD")
+            .returning(|_| Ok(String::from("refs/heads/main")));
+    }
+
+    fn mock_git<'a>(
+        git_command: &mut MockGitCommandTrait,
+        app_name: Option<&'a str>,
+        branch: &'a str,
+        rev: &'a str,
+        count: &'a str,
+        version: Option<&'a str>,
+    ) where
+        'a: 'static,
+    {
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "fetch" && args[1] == "--tags")
+            .returning(|_| Ok(String::from("")));
+
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "branch" && args[1] == "--show-current")
+            .returning(|_| Ok(branch.to_string()));
+
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "rev-parse" && args[1] == "--short" && args[2] == "HEAD")
+            .returning(|_| Ok(rev.to_string()));
+
+        git_command
+            .expect_run()
+            .withf(|args| args.join(" ") == "rev-parse --is-shallow-repository")
+            .returning(|_| Ok(String::from("false")));
+
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "show" && args[1] == "-s")
+            .returning(|_| {
+                Ok(String::from(
+                    "1234567890abcdef1234567890abcdef12345678\n2024-02-14T12:00:00+01:00\n1707908400\nJane Doe <jane@example.com>",
+                ))
+            });
+
+        git_command
+            .expect_run()
+            .withf(|args| args[1] == TAG_METADATA_FORMAT)
+            .returning(|_| {
+                Ok(String::from(
+                    "2024-02-01T09:00:00+01:00\tcommit\t \t\nMerge release",
+                ))
+            });
+
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "rev-list" && args[1] == "--count" && args[2] == "HEAD")
+            .returning(move |_| Ok(count.to_string()));
+
+        git_command
+            .expect_run()
+            .withf(|args| {
+                args[0] == "rev-list" && args[1] == "--count" && args[2].ends_with("..HEAD")
+            })
+            .returning(move |_| Ok(count.to_string()));
+
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "symbolic-ref")
+            .returning(|_| {
+                Err(Error::msg(
+                    "fatal: ref refs/remotes/origin/HEAD is not a symbolic ref",
+                ))
+            });
+
+        git_command
+            .expect_run()
+            .withf(|args| {
+                args[0] == "rev-list"
+                    && args[1] == "--left-right"
+                    && args[2] == "--count"
+                    && args[3] == "HEAD...main"
+            })
+            .returning(|_| Ok(String::from("2\t3")));
+
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "merge-base" && args[1] == "HEAD" && args[2] == "main")
+            .returning(|_| Ok(String::from("abcdef0")));
+
+        git_command
+            .expect_run()
+            .withf(|args| {
+                args[0] == "log" && args[2] == "--format=%ct%n%cI" && args[3] == "abcdef0"
+            })
+            .returning(|_| Ok(format!("{}\n2024-01-31T12:00:00+01:00", days_ago(10))));
+
+        git_command
+            .expect_run()
+            .withf(|args| args[0] == "log" && args[2] == "--format=%ct" && args[3] == "HEAD")
+            .returning(|_| Ok(days_ago(2).to_string()));
+
+        let exact_version: &str;
+        if version.is_some() {
+            if app_name.is_none() {
+                git_command
+                    .expect_run()
+                    .withf(|args| {
+                        args[0] == "describe" && args[1] == "--abbrev=0" && args[2] == "--tags"
+                    })
+                    .returning(move |_| Ok(version.unwrap().to_string()));
+            } else {
+                git_command
+                    .expect_run()
+                    .withf(move |args| {
+                        args[0] == "describe"
+                            && args[1] == "--abbrev=0"
+                            && args[2] == "--match"
+                            && args[3] == format!("{}-[0-9]*", app_name.unwrap())
+                            && args[4] == "--tags"
+                    })
+                    .returning(move |_| Ok(format!("{}-{}", app_name.unwrap(), version.unwrap())));
+            }
+            exact_version = version.unwrap();
+        } else {
+            if app_name.is_none() {
+                git_command
+                    .expect_run()
+                    .withf(|args| {
+                        args[0] == "describe" && args[1] == "--abbrev=0" && args[2] == "--tags"
+                    })
+                    .returning(|_| Err(Error::msg("No tag found")));
+            } else {
+                git_command
+                    .expect_run()
+                    .withf(move |args| {
+                        args[0] == "describe"
+                            && args[1] == "--abbrev=0"
+                            && args[2] == "--match"
+                            && args[3] == format!("{}-[0-9]*", app_name.unwrap())
+                            && args[4] == "--tags"
+                    })
+                    .returning(|_| Err(Error::msg("No tag found")));
+            }
+            exact_version = "0.0.0";
+        }
+        if app_name.is_none() {
+            git_command
+                .expect_run()
+                .withf(|args| {
+                    args[0] == "describe"
+                        && args[1] == "--abbrev=0"
+                        && args[2] == "--exact-match"
+                        && args[3] == "--tags"
+                })
+                .returning(move |_| Ok(exact_version.to_string()));
+        } else {
+            git_command
+                .expect_run()
+                .withf(move |args| {
+                    args[0] == "describe"
+                        && args[1] == "--abbrev=0"
+                        && args[2] == "--exact-match"
+                        && args[3] == "--match"
+                        && args[4] == format!("{}-[0-9]*", app_name.unwrap())
+                })
+                .returning(move |_| Ok(format!("{}-{}", app_name.unwrap(), exact_version)));
+        }
+    }
+
+    fn days_ago(days: u64) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            - days * SECONDS_PER_DAY
+    }
+
+    fn assert_expected_version(
+        branch: &str,
+        rev: &str,
+        count: &str,
+        expected_version: Version,
+        mut output: Value,
+    ) {
+        // Asserted by the bump strategy, base, branch class and commit metadata tests
+        for field in [
+            "tag_date",
+            "tag_message",
+            "tagger",
+            "tag_signed",
+            "bump",
+            "base_tag",
+            "base_version",
+            "prerelease_source",
+            "branch_class",
+            "channel",
+            "channel_image",
+            "container_tags",
+        ] {
+            output.as_object_mut().unwrap().remove(field);
+        }
+        let expected_output = json!(
+            {
+                "schema_version": SCHEMA_VERSION,
+                "git_branch": branch,
+                "git_rev": rev,
+                "rev_count": count,
+                "app_version":  format!("{}", expected_version),
+                "container_tag": format!("{}", expected_version).replace('+', "."),
+                "ahead_of_main": 2,
+                "behind_main": 3,
+                "branch_created_at": "2024-01-31T12:00:00+01:00",
+                "branch_age_days": 10,
+                "days_since_last_commit": 2,
+                "changed_since_tag": true,
+                "pr_number": null,
+                "signature_verified": null,
+                "git_sha_full": "1234567890abcdef1234567890abcdef12345678",
+                "commit_timestamp": "2024-02-14T12:00:00+01:00",
+                "commit_epoch": 1707908400,
+                "committer": "Jane Doe <jane@example.com>",
+                "major": expected_version.major,
+                "minor": expected_version.minor,
+                "patch": expected_version.patch,
+                "prerelease": expected_version.pre.to_string(),
+                "build": expected_version.build.to_string()
+            }
+        );
+        assert_eq!(output, expected_output);
+    }
+}